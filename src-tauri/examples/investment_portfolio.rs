@@ -6,6 +6,7 @@
 // - Return calculations with proper unit handling
 // - Asset allocation summary
 // - Percentage calculations
+// - Projections with time-value-of-money functions (FV, PMT)
 
 use std::path::PathBuf;
 use unicel_lib::core::cell::Cell;
@@ -338,6 +339,27 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
         Cell::with_text("- Multi-currency demonstration (USD, EUR, GBP)"),
     )?;
 
+    // Projection: the rate is per year and the contributions per month; the unit system
+    // converts the rate to the monthly period
+    sheet4.set(CellAddr::new("A", 17), Cell::with_text("US Projection"))?;
+    sheet4.set(
+        CellAddr::new("A", 18),
+        Cell::with_text("Value in 10 years at 7%/year, adding 500 USD/month"),
+    )?;
+    sheet4.set(
+        CellAddr::new("B", 18),
+        Cell::with_formula("=FV(7 %/year, 120 month, -500 USD/month, -B6)"),
+    )?;
+    sheet4.set(
+        CellAddr::new("A", 19),
+        Cell::with_text("Monthly saving needed to reach 150000 USD"),
+    )?;
+    sheet4.set(
+        CellAddr::new("B", 19),
+        Cell::with_formula("=-PMT(7 %/year, 120 month, -B6, 150000 USD)"),
+    )?;
+    sheet4.recalculate(&[CellAddr::new("B", 18), CellAddr::new("B", 19)])?;
+
     // Save to file
     let file = WorkbookFile::from_workbook(&workbook);
     let path = PathBuf::from("examples/investment_portfolio.usheet");
@@ -348,6 +370,7 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
     println!("  - Multi-currency holdings (USD, EUR, GBP)");
     println!("  - Return calculations (dimensionless shares × currency = currency)");
     println!("  - Gain/Loss tracking with proper unit handling");
+    println!("  - 10-year projection with FV and PMT (7 %/year, USD/month)");
     println!("  - 4 sheets: US Stocks, EU Holdings, UK Holdings, Summary");
 
     Ok(())
@@ -58,9 +58,13 @@ fn print_cells(state: &AppState) {
             unicel_lib::commands::workbook::CellValueData::Number { value } => {
                 format!("{:.2}", value)
             }
-            unicel_lib::commands::workbook::CellValueData::Text { text } => text.clone(),
+            unicel_lib::commands::workbook::CellValueData::Text { text }
+            | unicel_lib::commands::workbook::CellValueData::DateTime { text, .. } => text.clone(),
+            unicel_lib::commands::workbook::CellValueData::Boolean { value } => {
+                value.to_string().to_uppercase()
+            }
             unicel_lib::commands::workbook::CellValueData::Empty => "".to_string(),
-            unicel_lib::commands::workbook::CellValueData::Error { message } => {
+            unicel_lib::commands::workbook::CellValueData::Error { message, .. } => {
                 format!("ERROR: {}", message)
             }
         };
@@ -1,13 +1,18 @@
 // Workbook types and helper functions for Tauri commands
 
+pub use crate::core::cell::input::{parse_cell_input, parse_unit};
+
 use crate::core::{
-    cell::{Cell, CellValue},
+    cell::{format_boolean, input::get_base_dimension, Cell, CellError, CellValue, ErrorKind},
+    datetime::format_datetime,
+    precision::format_significant,
     settings::UnitPreferences,
-    table::CellAddr,
-    units::{BaseDimension, Unit},
-    workbook::Workbook,
+    table::{CellAddr, CellRange},
+    units::BaseDimension,
+    workbook::{GoalSeekResult, IterativeCalculation, Workbook},
 };
 use crate::formats::json::WorkbookFile;
+use chrono::NaiveDateTime;
 use serde::{Deserialize, Serialize};
 use std::sync::Mutex;
 
@@ -46,6 +51,10 @@ pub struct CellData {
     pub value: CellValueData,
     pub storage_unit: String,
     pub display_unit: Option<String>,
+    /// Standard uncertainty of a number, in the unit the number is shown in
+    pub uncertainty: Option<f64>,
+    /// Significant figures to show a number with (only in significant-figures mode)
+    pub significant_figures: Option<u32>,
     pub formula: Option<String>,
     pub warning: Option<String>,
 }
@@ -54,9 +63,27 @@ pub struct CellData {
 #[serde(tag = "type")]
 pub enum CellValueData {
     Empty,
-    Number { value: f64 },
-    Text { text: String },
-    Error { message: String },
+    Number {
+        value: f64,
+    },
+    Text {
+        text: String,
+    },
+    Boolean {
+        value: bool,
+    },
+    DateTime {
+        /// ISO 8601 date and time, e.g. "2024-03-15T00:00:00"
+        value: NaiveDateTime,
+        /// The date as displayed, e.g. "2024-03-15"
+        text: String,
+    },
+    Error {
+        kind: ErrorKind,
+        /// The error value to show, e.g. "#DIV/0!"
+        code: String,
+        message: String,
+    },
 }
 
 #[derive(Debug, Serialize)]
@@ -65,17 +92,40 @@ pub struct WorkbookInfo {
     pub sheet_names: Vec<String>,
     pub active_sheet_index: usize,
     pub is_dirty: bool,
+    /// Whether numbers are shown to their significant figures
+    pub significant_figures: bool,
+    /// Seed for RAND and RANDBETWEEN, if the workbook has one
+    pub random_seed: Option<u64>,
+    /// Limits for calculating circular references, if iterative calculation is on
+    pub iterative_calculation: Option<IterativeCalculation>,
 }
 
 // Helper functions
 
-pub fn cell_to_data(cell: &Cell) -> CellData {
-    let value = match cell.value() {
+fn error_data(error: &CellError) -> CellValueData {
+    CellValueData::Error {
+        kind: error.kind,
+        code: error.kind.code().to_string(),
+        message: error.message.clone(),
+    }
+}
+
+fn value_data(value: &CellValue) -> CellValueData {
+    match value {
         CellValue::Empty => CellValueData::Empty,
         CellValue::Number(n) => CellValueData::Number { value: *n },
         CellValue::Text(t) => CellValueData::Text { text: t.clone() },
-        CellValue::Error(e) => CellValueData::Error { message: e.clone() },
-    };
+        CellValue::Boolean(b) => CellValueData::Boolean { value: *b },
+        CellValue::DateTime(d) => CellValueData::DateTime {
+            value: *d,
+            text: format_datetime(d),
+        },
+        CellValue::Error(e) => error_data(e),
+    }
+}
+
+pub fn cell_to_data(cell: &Cell) -> CellData {
+    let value = value_data(cell.value());
 
     let storage_unit = cell.storage_unit().canonical().to_string();
     let display_unit_canonical = cell.display_unit().canonical().to_string();
@@ -88,6 +138,8 @@ pub fn cell_to_data(cell: &Cell) -> CellData {
         } else {
             None
         },
+        uncertainty: cell.uncertainty(),
+        significant_figures: cell.significant_figures(),
         formula: cell.formula().map(|s| s.to_string()),
         warning: cell.warning().map(|s| s.to_string()),
     }
@@ -116,11 +168,11 @@ pub fn cell_to_data_with_mode(
     tracing::debug!("  -> target display unit: {:?}", display_unit_str);
 
     // Convert value if we have a different display unit
-    let (display_value, display_unit_final) = if let Some(target_unit) = display_unit_str {
+    let (display_value, display_unit_final) = if let Some(target_unit) = &display_unit_str {
         if let Some(original_value) = cell.as_number() {
             // Try to convert compound units
             if let Some(converted) =
-                convert_compound_unit(original_value, &storage_unit, &target_unit)
+                convert_compound_unit(original_value, &storage_unit, target_unit)
             {
                 tracing::debug!(
                     "  compound conversion succeeded: {} {} -> {} {}",
@@ -131,13 +183,13 @@ pub fn cell_to_data_with_mode(
                 );
                 (
                     CellValueData::Number { value: converted },
-                    Some(format_unit_display(&target_unit)),
+                    Some(format_unit_display(target_unit)),
                 )
             } else {
                 // Try simple conversion with normalized units (for currency symbols)
                 let library = UnitLibrary::new();
                 let storage_norm = normalize_unit(&storage_unit);
-                let target_norm = normalize_unit(&target_unit);
+                let target_norm = normalize_unit(target_unit);
 
                 if let Some(converted) =
                     library.convert(original_value, &storage_norm, &target_norm)
@@ -151,7 +203,7 @@ pub fn cell_to_data_with_mode(
                     );
                     (
                         CellValueData::Number { value: converted },
-                        Some(format_unit_display(&target_unit)),
+                        Some(format_unit_display(target_unit)),
                     )
                 } else {
                     // Conversion failed, use original
@@ -171,38 +223,41 @@ pub fn cell_to_data_with_mode(
             }
         } else {
             // Not a number, use original
-            (
-                match cell.value() {
-                    CellValue::Empty => CellValueData::Empty,
-                    CellValue::Number(n) => CellValueData::Number { value: *n },
-                    CellValue::Text(t) => CellValueData::Text { text: t.clone() },
-                    CellValue::Error(e) => CellValueData::Error { message: e.clone() },
-                },
-                None,
-            )
+            (value_data(cell.value()), None)
         }
     } else {
         // No conversion needed
-        (
-            match cell.value() {
-                CellValue::Empty => CellValueData::Empty,
-                CellValue::Number(n) => CellValueData::Number { value: *n },
-                CellValue::Text(t) => CellValueData::Text { text: t.clone() },
-                CellValue::Error(e) => CellValueData::Error { message: e.clone() },
-            },
-            None,
-        )
+        (value_data(cell.value()), None)
+    };
+
+    // The uncertainty is shown in the same unit as the value
+    let uncertainty = match (&display_unit_final, &display_unit_str) {
+        (Some(_), Some(target_unit)) => cell
+            .uncertainty()
+            .and_then(|uncertainty| convert_difference(uncertainty, &storage_unit, target_unit)),
+        _ => cell.uncertainty(),
     };
 
     CellData {
         value: display_value,
         storage_unit: format_unit_display(&storage_unit),
         display_unit: display_unit_final,
+        uncertainty,
+        significant_figures: cell.significant_figures(),
         formula: cell.formula().map(|s| s.to_string()),
         warning: cell.warning().map(|s| s.to_string()),
     }
 }
 
+/// Numbers are shown to their significant figures only in the workbook's
+/// significant-figures mode
+fn with_precision_mode(workbook: &Workbook, mut data: CellData) -> CellData {
+    if !workbook.settings().significant_figures {
+        data.significant_figures = None;
+    }
+    data
+}
+
 /// Normalize currency symbols ("$" -> "USD", etc.)
 fn normalize_unit(unit: &str) -> String {
     match unit {
@@ -213,6 +268,20 @@ fn normalize_unit(unit: &str) -> String {
     }
 }
 
+/// Convert a difference, such as an uncertainty, between units
+/// Unlike values, differences convert without the offset between units (1 °C is 1.8 °F)
+fn convert_difference(value: f64, from_unit: &str, to_unit: &str) -> Option<f64> {
+    use crate::core::units::UnitLibrary;
+
+    let library = UnitLibrary::new();
+    let convert = |value| {
+        convert_compound_unit(value, from_unit, to_unit).or_else(|| {
+            library.convert(value, &normalize_unit(from_unit), &normalize_unit(to_unit))
+        })
+    };
+    Some((convert(value)? - convert(0.0)?).abs())
+}
+
 /// Convert compound unit values (e.g., 10 ft*ft → m*m, 15 $/ft -> USD/m)
 fn convert_compound_unit(value: f64, from_unit: &str, to_unit: &str) -> Option<f64> {
     use crate::core::units::UnitLibrary;
@@ -380,212 +449,6 @@ fn convert_time_unit(value: f64, from: &str, to: &str) -> Option<f64> {
     Some(value * from_hours / to_hours_val)
 }
 
-pub fn parse_cell_input(input: &str) -> Result<Cell, String> {
-    // Check if it's a formula
-    if input.starts_with('=') {
-        return Ok(Cell::with_formula(input.to_string()));
-    }
-
-    let input = input.trim();
-
-    // Check if it's empty
-    if input.is_empty() {
-        return Ok(Cell::empty());
-    }
-
-    // Check for percentage (e.g., "15%", "15 %")
-    if let Some(number_str) = input.strip_suffix('%') {
-        let number_str = number_str.trim();
-        if let Ok(value) = number_str.parse::<f64>() {
-            // Store as fraction (15% -> 0.15)
-            return Ok(Cell::new(
-                value / 100.0,
-                Unit::simple("%", BaseDimension::Custom("%".to_string())),
-            ));
-        }
-    }
-
-    // Check if it starts with a currency symbol (e.g., "$15", "USD 15")
-    if input.starts_with('$')
-        || input.starts_with("USD")
-        || input.starts_with("EUR")
-        || input.starts_with("GBP")
-    {
-        // Try to parse as currency-first format
-        if let Some((currency, number_part)) = parse_currency_first(input) {
-            return Ok(Cell::new(number_part, parse_unit(currency)));
-        }
-    }
-
-    // Try to parse as number with optional unit (standard format: "15 USD", "100 m", "15 $/ft")
-    let parts: Vec<&str> = input.split_whitespace().collect();
-    if parts.is_empty() {
-        return Ok(Cell::empty());
-    }
-
-    // Try to parse the first part as a number
-    match parts[0].parse::<f64>() {
-        Ok(value) => {
-            // Successfully parsed as number, check for unit
-            let unit_str = if parts.len() > 1 {
-                parts[1..].join(" ")
-            } else {
-                String::new()
-            };
-
-            let unit = if unit_str.is_empty() {
-                Unit::dimensionless()
-            } else {
-                parse_unit(&unit_str)
-            };
-
-            Ok(Cell::new(value, unit))
-        }
-        Err(_) => {
-            // Not a number, treat as plain text
-            Ok(Cell::with_text(input))
-        }
-    }
-}
-
-/// Parse currency-first format like "$15", "USD 100", "$15/ft"
-fn parse_currency_first(input: &str) -> Option<(&str, f64)> {
-    // Handle "$15" or "$15.5"
-    if let Some(number_str) = input.strip_prefix('$') {
-        if let Ok(value) = number_str.parse::<f64>() {
-            return Some(("$", value));
-        }
-    }
-
-    // Handle "USD 100", "EUR 50.5"
-    if let Some(number_str) = input.strip_prefix("USD ") {
-        if let Ok(value) = number_str.parse::<f64>() {
-            return Some(("USD", value));
-        }
-    }
-    if let Some(number_str) = input.strip_prefix("EUR ") {
-        if let Ok(value) = number_str.parse::<f64>() {
-            return Some(("EUR", value));
-        }
-    }
-    if let Some(number_str) = input.strip_prefix("GBP ") {
-        if let Ok(value) = number_str.parse::<f64>() {
-            return Some(("GBP", value));
-        }
-    }
-    None
-}
-
-pub fn parse_unit(unit_str: &str) -> Unit {
-    // Check if it's a compound unit with division (e.g., "USD/ft", "mi/hr", "$/ft^2")
-    if let Some(pos) = unit_str.find('/') {
-        let numerator_str = &unit_str[..pos];
-        let denominator_str = &unit_str[pos + 1..];
-
-        let (num_dim, num_power) = parse_dimension_with_power(numerator_str);
-        let (den_dim, den_power) = parse_dimension_with_power(denominator_str);
-
-        return Unit::compound(
-            unit_str.to_string(),
-            vec![(num_dim, num_power)],
-            vec![(den_dim, den_power)],
-        );
-    }
-
-    // Check if it's a compound unit with multiplication (e.g., "ft*ft", "kg*m")
-    if let Some(pos) = unit_str.find('*') {
-        let left_str = &unit_str[..pos];
-        let right_str = &unit_str[pos + 1..];
-
-        let (left_dim, left_power) = parse_dimension_with_power(left_str);
-        let (right_dim, right_power) = parse_dimension_with_power(right_str);
-
-        return Unit::compound(
-            unit_str.to_string(),
-            vec![(left_dim.clone(), left_power), (right_dim, right_power)],
-            vec![],
-        );
-    }
-
-    // Check if it's a unit with power (e.g., "ft^2", "m^3")
-    if let Some(pos) = unit_str.find('^') {
-        let base_str = &unit_str[..pos];
-        let power_str = &unit_str[pos + 1..];
-
-        if let Ok(power) = power_str.parse::<i32>() {
-            let dimension = get_base_dimension(base_str);
-            return Unit::compound(unit_str.to_string(), vec![(dimension, power)], vec![]);
-        }
-    }
-
-    // Simple unit
-    let dimension = get_base_dimension(unit_str);
-    Unit::simple(unit_str, dimension)
-}
-
-/// Parse a unit string and extract dimension with power (e.g., "ft^2" -> (Length, 2))
-fn parse_dimension_with_power(unit_str: &str) -> (BaseDimension, i32) {
-    if let Some(pos) = unit_str.find('^') {
-        let base_str = &unit_str[..pos];
-        let power_str = &unit_str[pos + 1..];
-
-        if let Ok(power) = power_str.parse::<i32>() {
-            return (get_base_dimension(base_str), power);
-        }
-    }
-
-    (get_base_dimension(unit_str), 1)
-}
-
-fn get_base_dimension(unit_str: &str) -> BaseDimension {
-    match unit_str {
-        // Length - short forms
-        "m" | "cm" | "mm" | "km" | "in" | "ft" | "yd" | "mi" => BaseDimension::Length,
-        // Length - long forms (singular and plural)
-        "meter" | "meters" | "centimeter" | "centimeters" | "millimeter" | "millimeters"
-        | "kilometer" | "kilometers" | "inch" | "inches" | "foot" | "feet" | "yard" | "yards"
-        | "mile" | "miles" => BaseDimension::Length,
-
-        // Mass - short forms
-        "g" | "kg" | "mg" | "oz" | "lb" => BaseDimension::Mass,
-        // Mass - long forms (singular and plural)
-        "gram" | "grams" | "kilogram" | "kilograms" | "milligram" | "milligrams" | "ounce"
-        | "ounces" | "pound" | "pounds" => BaseDimension::Mass,
-
-        // Time - short forms (basic time units are Time dimension)
-        "s" | "min" | "hr" | "h" => BaseDimension::Time,
-        // Time - long forms (singular and plural)
-        "second" | "seconds" | "minute" | "minutes" | "hour" | "hours" => BaseDimension::Time,
-
-        // Period units (day, month, quarter, year) are also Time dimension
-        // This allows proper conversion between rates like $/quarter and $/year
-        "day" | "days" | "month" | "months" | "quarter" | "quarters" | "year" | "years" | "yr" => {
-            BaseDimension::Time
-        }
-
-        // Temperature - short forms
-        "C" | "F" | "K" => BaseDimension::Temperature,
-        // Temperature - long forms
-        "Celsius" | "celsius" | "Fahrenheit" | "fahrenheit" | "Kelvin" | "kelvin" => {
-            BaseDimension::Temperature
-        }
-
-        // Currency
-        "USD" | "EUR" | "GBP" | "$" => BaseDimension::Currency,
-
-        // Digital Storage
-        "B" | "KB" | "MB" | "GB" | "TB" | "PB" | "Kb" | "Mb" | "Gb" | "Tb" | "Pb" | "Tok"
-        | "MTok" => BaseDimension::DigitalStorage,
-        // Digital Storage - long forms
-        "byte" | "bytes" | "kilobyte" | "kilobytes" | "megabyte" | "megabytes" | "gigabyte"
-        | "gigabytes" | "terabyte" | "terabytes" | "petabyte" | "petabytes" => {
-            BaseDimension::DigitalStorage
-        }
-
-        _ => BaseDimension::Custom(unit_str.to_string()),
-    }
-}
-
 // Workbook operations (library functions, not Tauri commands)
 
 pub fn create_workbook_impl(state: &AppState, name: String) -> Result<(), String> {
@@ -604,6 +467,9 @@ pub fn get_workbook_info_impl(state: &AppState) -> Result<WorkbookInfo, String>
         sheet_names: workbook.sheet_names(),
         active_sheet_index: workbook.active_sheet_index(),
         is_dirty: workbook.is_dirty(),
+        significant_figures: workbook.settings().significant_figures,
+        random_seed: workbook.settings().random_seed,
+        iterative_calculation: workbook.settings().iterative_calculation,
     })
 }
 
@@ -616,16 +482,16 @@ pub fn get_sheet_cells_impl(state: &AppState) -> Result<Vec<(String, CellData)>,
     tracing::debug!("get_sheet_cells_impl: display_mode={:?}, metric_time={}, imperial_time={}, time_rate_unit={}",
         display_mode, preferences.metric_time, preferences.imperial_time, preferences.time_rate_unit);
 
+    // Cells that array formulas spilled into are shown like any other cell
     let sheet = workbook.active_sheet();
     let cells: Vec<(String, CellData)> = sheet
         .cell_addresses()
         .into_iter()
+        .chain(sheet.spilled_addresses())
         .filter_map(|addr| {
             sheet.get(&addr).map(|cell| {
-                (
-                    addr.to_string(),
-                    cell_to_data_with_mode(cell, &display_mode, &preferences),
-                )
+                let data = cell_to_data_with_mode(cell, &display_mode, &preferences);
+                (addr.to_string(), with_precision_mode(workbook, data))
             })
         })
         .collect();
@@ -661,23 +527,9 @@ pub fn set_cell_impl(state: &AppState, address: String, value: String) -> Result
         }
     };
 
-    // Get named range mapping for dependency tracking
-    let named_range_mapping = workbook.get_named_range_mapping_for_sheet(active_sheet_idx);
-
-    // Set the cell with named range context for dependency tracking
-    workbook
-        .active_sheet_mut()
-        .set_with_named_ranges(addr.clone(), cell.clone(), Some(&named_range_mapping))
-        .map_err(|e| e.to_string())?;
-
-    // Resolve all named ranges to their current values for recalculation
-    let named_refs = workbook.resolve_named_ranges();
-
-    // Always recalculate dependent cells when ANY cell changes
-    // This ensures formulas that reference this cell get updated
+    // Set the cell and recalculate everything that depends on it, on any sheet
     workbook
-        .active_sheet_mut()
-        .recalculate_with_named_refs(std::slice::from_ref(&addr), Some(&named_refs))
+        .set_cell(active_sheet_idx, addr.clone(), cell)
         .map_err(|e| e.to_string())?;
 
     // Return the updated cell (which may have been evaluated)
@@ -686,7 +538,7 @@ pub fn set_cell_impl(state: &AppState, address: String, value: String) -> Result
         .get(&addr)
         .ok_or("Cell not found after setting")?;
 
-    Ok(cell_to_data(updated_cell))
+    Ok(with_precision_mode(workbook, cell_to_data(updated_cell)))
 }
 
 pub fn save_workbook_impl(state: &AppState, path: String) -> Result<(), String> {
@@ -718,39 +570,9 @@ pub fn load_workbook_impl(state: &AppState, path: String) -> Result<(), String>
         workbook.set_name(filename);
     }
 
-    // Save the original active sheet index
-    let original_active = workbook.active_sheet_index();
-
-    // Recalculate all formulas in ALL sheets after loading
+    // Recalculate all formulas in all sheets after loading
     // This ensures all formula cells have up-to-date values
-    for sheet_idx in 0..workbook.sheet_count() {
-        workbook.set_active_sheet(sheet_idx).ok();
-
-        let changed_cells: Vec<CellAddr> = workbook
-            .active_sheet()
-            .cell_addresses()
-            .into_iter()
-            .filter(|addr| {
-                workbook
-                    .active_sheet()
-                    .get(addr)
-                    .map(|cell| cell.formula().is_some())
-                    .unwrap_or(false)
-            })
-            .collect();
-
-        if !changed_cells.is_empty() {
-            // Resolve named ranges before recalculation
-            let named_refs = workbook.resolve_named_ranges();
-            workbook
-                .active_sheet_mut()
-                .recalculate_with_named_refs(&changed_cells, Some(&named_refs))
-                .map_err(|e| e.to_string())?;
-        }
-    }
-
-    // Restore the original active sheet
-    workbook.set_active_sheet(original_active).ok();
+    workbook.recalculate_all().map_err(|e| e.to_string())?;
 
     *state.workbook.lock().unwrap() = Some(workbook);
     *state.current_file.lock().unwrap() = Some(path.clone());
@@ -778,6 +600,88 @@ pub fn set_display_mode_impl(state: &AppState, mode: String) -> Result<(), Strin
     Ok(())
 }
 
+/// Turn significant-figures mode on or off for the workbook (saved with it)
+pub fn set_significant_figures_impl(state: &AppState, enabled: bool) -> Result<(), String> {
+    let mut workbook_guard = state.workbook.lock().unwrap();
+    let workbook = workbook_guard.as_mut().ok_or("No workbook loaded")?;
+
+    tracing::info!("Setting significant-figures mode to: {}", enabled);
+    workbook.set_significant_figures(enabled);
+    Ok(())
+}
+
+/// Seed RAND and RANDBETWEEN so that the workbook shows the same numbers every time,
+/// or remove the seed (None) to draw new numbers on every recalculation
+pub fn set_random_seed_impl(state: &AppState, seed: Option<u64>) -> Result<(), String> {
+    let mut workbook_guard = state.workbook.lock().unwrap();
+    let workbook = workbook_guard.as_mut().ok_or("No workbook loaded")?;
+
+    tracing::info!("Setting random seed to: {:?}", seed);
+    workbook.set_random_seed(seed).map_err(|e| e.to_string())
+}
+
+/// Turn iterative calculation of circular references on (with the most iterations and the
+/// tolerance to settle within) or off (None)
+pub fn set_iterative_calculation_impl(
+    state: &AppState,
+    iteration: Option<IterativeCalculation>,
+) -> Result<(), String> {
+    let mut workbook_guard = state.workbook.lock().unwrap();
+    let workbook = workbook_guard.as_mut().ok_or("No workbook loaded")?;
+
+    tracing::info!("Setting iterative calculation to: {:?}", iteration);
+    workbook
+        .set_iterative_calculation(iteration)
+        .map_err(|e| e.to_string())
+}
+
+/// List the circular references in the workbook, each as the cells in the loop
+pub fn get_circular_references_impl(state: &AppState) -> Result<Vec<Vec<String>>, String> {
+    let workbook_guard = state.workbook.lock().unwrap();
+    let workbook = workbook_guard.as_ref().ok_or("No workbook loaded")?;
+
+    Ok(workbook.circular_references())
+}
+
+/// Find the value of the changing cell that makes the formula in the target cell reach a goal
+/// such as "50000 USD", on the active sheet. A goal without a unit is in the target's unit
+pub fn goal_seek_impl(
+    state: &AppState,
+    target: String,
+    goal: String,
+    changing: String,
+) -> Result<GoalSeekResult, String> {
+    let mut workbook_guard = state.workbook.lock().unwrap();
+    let workbook = workbook_guard.as_mut().ok_or("No workbook loaded")?;
+
+    let target = CellAddr::from_string(&target).map_err(|e| e.to_string())?;
+    let changing = CellAddr::from_string(&changing).map_err(|e| e.to_string())?;
+    let goal = parse_cell_input(&goal)?;
+    let value = goal
+        .as_number()
+        .ok_or("The goal must be a number, with or without a unit")?;
+    let unit = Some(goal.storage_unit()).filter(|unit| !unit.is_dimensionless());
+
+    tracing::info!(
+        "Goal seek: {} to {} by changing {}",
+        target,
+        value,
+        changing
+    );
+    let sheet_index = workbook.active_sheet_index();
+    workbook
+        .goal_seek(sheet_index, &target, value, unit, &changing)
+        .map_err(|e| e.to_string())
+}
+
+/// Recalculate every formula in the workbook, e.g. to update NOW() or draw new random numbers
+pub fn recalculate_impl(state: &AppState) -> Result<(), String> {
+    let mut workbook_guard = state.workbook.lock().unwrap();
+    let workbook = workbook_guard.as_mut().ok_or("No workbook loaded")?;
+
+    workbook.recalculate_all().map_err(|e| e.to_string())
+}
+
 /// Format a unit string for better display (e.g., "ft^2" → "ft²", "ft*ft" → "ft²")
 fn format_unit_display(unit: &str) -> String {
     // Check for ^2 notation (preferred internal format)
@@ -1302,15 +1206,22 @@ pub fn get_debug_export_impl(
         if let Some(cell) = sheet.get(&addr) {
             // Get storage value (unconverted)
             let storage_data = cell_to_data(cell);
-            let storage_str = format_cell_value(&storage_data.value, &storage_data.storage_unit);
+            let storage_str =
+                format_cell_value(&storage_data.value, &storage_data.storage_unit, None);
 
             // Get display value (converted based on mode)
-            let display_data = cell_to_data_with_mode(cell, &display_mode, &preferences);
-            let display_str = if let Some(display_unit) = &display_data.display_unit {
-                format_cell_value(&display_data.value, display_unit)
-            } else {
-                storage_str.clone()
-            };
+            let display_data = with_precision_mode(
+                workbook,
+                cell_to_data_with_mode(cell, &display_mode, &preferences),
+            );
+            let display_str = format_cell_value(
+                &display_data.value,
+                display_data
+                    .display_unit
+                    .as_deref()
+                    .unwrap_or(&display_data.storage_unit),
+                display_data.significant_figures,
+            );
 
             // Show formula if present
             if let Some(formula) = &storage_data.formula {
@@ -1324,22 +1235,28 @@ pub fn get_debug_export_impl(
     Ok(output)
 }
 
-/// Format cell value with unit for debug export
-fn format_cell_value(value: &CellValueData, unit: &str) -> String {
+/// Format cell value with unit for debug export, rounded to its significant figures if given
+fn format_cell_value(value: &CellValueData, unit: &str, figures: Option<u32>) -> String {
+    let number = |n: f64| match figures {
+        Some(figures) => format_significant(n, figures),
+        None => n.to_string(),
+    };
     match value {
         CellValueData::Empty => String::new(),
         CellValueData::Number { value } => {
-            // Special handling for percentages: convert 0.15 -> "15%"
-            if unit == "%" {
-                format!("{}%", value * 100.0)
+            // Special handling for percentages: convert 0.15 -> "15%", 0.06 %/year -> "6%/year"
+            if unit.starts_with('%') {
+                format!("{}{}", number(value * 100.0), unit)
             } else if unit.is_empty() || unit == "1" {
-                format!("{}", value)
+                number(*value)
             } else {
-                format!("{} {}", value, unit)
+                format!("{} {}", number(*value), unit)
             }
         }
         CellValueData::Text { text } => text.clone(),
-        CellValueData::Error { message } => format!("#ERROR: {}", message),
+        CellValueData::Boolean { value } => format_boolean(*value).to_string(),
+        CellValueData::DateTime { text, .. } => text.clone(),
+        CellValueData::Error { code, message, .. } => format!("{} ({})", code, message),
     }
 }
 
@@ -1365,30 +1282,6 @@ pub fn set_active_sheet_impl(state: &AppState, index: usize) -> Result<(), Strin
         .set_active_sheet(index)
         .map_err(|e| e.to_string())?;
 
-    // Recalculate all formulas in the newly active sheet
-    // This ensures all formula cells have up-to-date values
-    let changed_cells: Vec<CellAddr> = workbook
-        .active_sheet()
-        .cell_addresses()
-        .into_iter()
-        .filter(|addr| {
-            workbook
-                .active_sheet()
-                .get(addr)
-                .map(|cell| cell.formula().is_some())
-                .unwrap_or(false)
-        })
-        .collect();
-
-    if !changed_cells.is_empty() {
-        // Resolve named ranges before recalculation
-        let named_refs = workbook.resolve_named_ranges();
-        workbook
-            .active_sheet_mut()
-            .recalculate_with_named_refs(&changed_cells, Some(&named_refs))
-            .map_err(|e| e.to_string())?;
-    }
-
     Ok(())
 }
 
@@ -1543,6 +1436,50 @@ pub fn get_named_range_for_cell_impl(
     Ok(workbook.get_named_range_for_cell(sheet_index, &addr))
 }
 
+// Workbook function commands
+
+/// A function defined in the workbook with LAMBDA
+#[derive(Debug, Serialize, Deserialize)]
+pub struct FunctionInfo {
+    pub name: String,
+    pub definition: String,
+}
+
+/// List the workbook's functions
+pub fn list_functions_impl(state: &AppState) -> Result<Vec<FunctionInfo>, String> {
+    let workbook_guard = state.workbook.lock().unwrap();
+    let workbook = workbook_guard.as_ref().ok_or("No workbook loaded")?;
+
+    Ok(workbook
+        .list_functions()
+        .into_iter()
+        .map(|(name, definition)| FunctionInfo { name, definition })
+        .collect())
+}
+
+/// Define or redefine a workbook function, e.g. `monthly_cost` as
+/// `LAMBDA(rate, util, rate*util*730 hr/month)`
+pub fn define_function_impl(
+    state: &AppState,
+    name: String,
+    definition: String,
+) -> Result<(), String> {
+    let mut workbook_guard = state.workbook.lock().unwrap();
+    let workbook = workbook_guard.as_mut().ok_or("No workbook loaded")?;
+
+    workbook
+        .define_function(name, definition)
+        .map_err(|e| e.to_string())
+}
+
+/// Delete a workbook function
+pub fn delete_function_impl(state: &AppState, name: String) -> Result<(), String> {
+    let mut workbook_guard = state.workbook.lock().unwrap();
+    let workbook = workbook_guard.as_mut().ok_or("No workbook loaded")?;
+
+    workbook.remove_function(&name).map_err(|e| e.to_string())
+}
+
 // Column and row sizing commands
 
 /// Set the width of a column
@@ -1605,76 +1542,102 @@ pub fn insert_column_before_impl(state: &AppState, col: String) -> Result<(), St
     let mut workbook_guard = state.workbook.lock().unwrap();
     let workbook = workbook_guard.as_mut().ok_or("No workbook loaded")?;
 
+    let active_sheet_idx = workbook.active_sheet_index();
     workbook
-        .active_sheet_mut()
-        .insert_column_before(&col)
-        .map_err(|e| e.to_string())?;
-
-    workbook.mark_dirty();
-    Ok(())
+        .insert_column_before(active_sheet_idx, &col)
+        .map_err(|e| e.to_string())
 }
 
 pub fn insert_column_after_impl(state: &AppState, col: String) -> Result<(), String> {
     let mut workbook_guard = state.workbook.lock().unwrap();
     let workbook = workbook_guard.as_mut().ok_or("No workbook loaded")?;
 
+    let active_sheet_idx = workbook.active_sheet_index();
     workbook
-        .active_sheet_mut()
-        .insert_column_after(&col)
-        .map_err(|e| e.to_string())?;
-
-    workbook.mark_dirty();
-    Ok(())
+        .insert_column_after(active_sheet_idx, &col)
+        .map_err(|e| e.to_string())
 }
 
 pub fn insert_row_before_impl(state: &AppState, row: usize) -> Result<(), String> {
     let mut workbook_guard = state.workbook.lock().unwrap();
     let workbook = workbook_guard.as_mut().ok_or("No workbook loaded")?;
 
+    let active_sheet_idx = workbook.active_sheet_index();
     workbook
-        .active_sheet_mut()
-        .insert_row_before(row)
-        .map_err(|e| e.to_string())?;
-
-    workbook.mark_dirty();
-    Ok(())
+        .insert_row_before(active_sheet_idx, row)
+        .map_err(|e| e.to_string())
 }
 
 pub fn insert_row_after_impl(state: &AppState, row: usize) -> Result<(), String> {
     let mut workbook_guard = state.workbook.lock().unwrap();
     let workbook = workbook_guard.as_mut().ok_or("No workbook loaded")?;
 
+    let active_sheet_idx = workbook.active_sheet_index();
     workbook
-        .active_sheet_mut()
-        .insert_row_after(row)
-        .map_err(|e| e.to_string())?;
-
-    workbook.mark_dirty();
-    Ok(())
+        .insert_row_after(active_sheet_idx, row)
+        .map_err(|e| e.to_string())
 }
 
 pub fn delete_column_impl(state: &AppState, col: String) -> Result<(), String> {
     let mut workbook_guard = state.workbook.lock().unwrap();
     let workbook = workbook_guard.as_mut().ok_or("No workbook loaded")?;
 
+    let active_sheet_idx = workbook.active_sheet_index();
     workbook
-        .active_sheet_mut()
-        .delete_column(&col)
-        .map_err(|e| e.to_string())?;
-
-    workbook.mark_dirty();
-    Ok(())
+        .delete_column(active_sheet_idx, &col)
+        .map_err(|e| e.to_string())
 }
 
 pub fn delete_row_impl(state: &AppState, row: usize) -> Result<(), String> {
     let mut workbook_guard = state.workbook.lock().unwrap();
     let workbook = workbook_guard.as_mut().ok_or("No workbook loaded")?;
 
+    let active_sheet_idx = workbook.active_sheet_index();
     workbook
-        .active_sheet_mut()
-        .delete_row(row)
-        .map_err(|e| e.to_string())?;
+        .delete_row(active_sheet_idx, row)
+        .map_err(|e| e.to_string())
+}
 
-    workbook.mark_dirty();
-    Ok(())
+// Copy and move implementations
+
+/// Parse a block of cells given as "A1" or "A1:C10"
+fn parse_cell_range(range: &str) -> Result<CellRange, String> {
+    let (start, end) = range.split_once(':').unwrap_or((range, range));
+    let start = CellAddr::from_string(start.trim()).map_err(|e| e.to_string())?;
+    let end = CellAddr::from_string(end.trim()).map_err(|e| e.to_string())?;
+    Ok(CellRange::new(&start, &end))
+}
+
+pub fn copy_range_impl(
+    state: &AppState,
+    source: String,
+    destination: String,
+) -> Result<(), String> {
+    let mut workbook_guard = state.workbook.lock().unwrap();
+    let workbook = workbook_guard.as_mut().ok_or("No workbook loaded")?;
+
+    let src = parse_cell_range(&source)?;
+    let dest = parse_cell_range(&destination)?;
+    let active_sheet_idx = workbook.active_sheet_index();
+
+    workbook
+        .copy_range(active_sheet_idx, &src, &dest)
+        .map_err(|e| e.to_string())
+}
+
+pub fn move_range_impl(
+    state: &AppState,
+    source: String,
+    destination: String,
+) -> Result<(), String> {
+    let mut workbook_guard = state.workbook.lock().unwrap();
+    let workbook = workbook_guard.as_mut().ok_or("No workbook loaded")?;
+
+    let src = parse_cell_range(&source)?;
+    let dest = CellAddr::from_string(destination.trim()).map_err(|e| e.to_string())?;
+    let active_sheet_idx = workbook.active_sheet_index();
+
+    workbook
+        .move_range(active_sheet_idx, &src, &dest)
+        .map_err(|e| e.to_string())
 }
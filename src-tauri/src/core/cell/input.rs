@@ -0,0 +1,319 @@
+// Parsing what is typed into a cell
+//
+// Input is tried as, in order: a formula, a boolean, a date, a quantity with an uncertainty,
+// a percentage, a currency-first amount ("$15"), and a number with an optional unit. Anything
+// else is text. Numbers keep the significant figures they were typed with.
+
+use super::Cell;
+use crate::core::datetime::parse_datetime;
+use crate::core::formula::{Evaluator, Expr};
+use crate::core::precision::count_significant_figures;
+use crate::core::units::{BaseDimension, Unit, UnitLibrary};
+
+/// Parse what was typed into a cell: a formula, boolean, date, quantity with an optional
+/// unit and uncertainty ("15 USD", "$15", "15%", "100 m ± 2 m"), or otherwise text
+pub fn parse_cell_input(input: &str) -> Result<Cell, String> {
+    // Check if it's a formula
+    if input.starts_with('=') {
+        return Ok(Cell::with_formula(input.to_string()));
+    }
+
+    let input = input.trim();
+
+    // Check if it's empty
+    if input.is_empty() {
+        return Ok(Cell::empty());
+    }
+
+    // Check for a boolean (TRUE or FALSE, in any case)
+    if input.eq_ignore_ascii_case("true") || input.eq_ignore_ascii_case("false") {
+        return Ok(Cell::with_boolean(input.eq_ignore_ascii_case("true")));
+    }
+
+    // Check for a date (e.g., "2024-03-15", "2024-03-15 14:30", "Mar 15, 2024")
+    if let Some(date) = parse_datetime(input) {
+        return Ok(Cell::with_datetime(date));
+    }
+
+    // Check for a quantity with its standard uncertainty (e.g., "100 m ± 2 m", "100±2 m")
+    if let Some((value, uncertainty)) = input.split_once('±') {
+        if let Some(cell) = parse_uncertain_quantity(value, uncertainty)? {
+            return Ok(cell);
+        }
+    }
+
+    // Check for percentage (e.g., "15%", "15 %") or a rate per time (e.g., "6 %/year")
+    if let Some((number_str, per)) = input.split_once('%') {
+        let per = per.trim();
+        if let Ok(value) = number_str.trim().parse::<f64>() {
+            // Store as fraction (15% -> 0.15)
+            if per.is_empty() {
+                return Ok(measured(
+                    value / 100.0,
+                    Unit::simple("%", BaseDimension::Custom("%".to_string())),
+                    number_str,
+                ));
+            }
+            if per.starts_with('/') {
+                return Ok(measured(
+                    value / 100.0,
+                    parse_unit(&format!("%{}", per)),
+                    number_str,
+                ));
+            }
+        }
+    }
+
+    // Check if it starts with a currency symbol (e.g., "$15", "USD 15")
+    if input.starts_with('$')
+        || input.starts_with("USD")
+        || input.starts_with("EUR")
+        || input.starts_with("GBP")
+    {
+        // Try to parse as currency-first format
+        if let Some((currency, number_part)) = parse_currency_first(input) {
+            return Ok(measured(
+                number_part,
+                parse_unit(currency),
+                &input[currency.len()..],
+            ));
+        }
+    }
+
+    // Try to parse as number with optional unit (standard format: "15 USD", "100 m", "15 $/ft")
+    let parts: Vec<&str> = input.split_whitespace().collect();
+    if parts.is_empty() {
+        return Ok(Cell::empty());
+    }
+
+    // Try to parse the first part as a number
+    match parts[0].parse::<f64>() {
+        Ok(value) => {
+            // Successfully parsed as number, check for unit
+            let unit_str = if parts.len() > 1 {
+                parts[1..].join(" ")
+            } else {
+                String::new()
+            };
+
+            let unit = if unit_str.is_empty() {
+                Unit::dimensionless()
+            } else {
+                parse_unit(&unit_str)
+            };
+
+            Ok(measured(value, unit, parts[0]))
+        }
+        Err(_) => {
+            // Not a number, treat as plain text
+            Ok(Cell::with_text(input))
+        }
+    }
+}
+
+/// A number cell known to the significant figures of the text it was typed as
+fn measured(value: f64, unit: Unit, number: &str) -> Cell {
+    let mut cell = Cell::new(value, unit);
+    cell.set_significant_figures(count_significant_figures(number));
+    cell
+}
+
+/// Convert a difference, such as an uncertainty, between units the way CONVERT does
+/// Unlike values, differences convert without the offset between units (1 °C is 1.8 °F)
+fn convert_difference(value: f64, from: &Unit, to: &Unit) -> Option<f64> {
+    let library = UnitLibrary::new();
+    let convert = |value| {
+        let expr = Expr::Function {
+            name: "CONVERT".to_string(),
+            args: vec![
+                Expr::number_with_unit(value, from.canonical()),
+                Expr::string(to.canonical()),
+            ],
+        };
+        Evaluator::new(&library).eval(&expr).ok()?.as_number()
+    };
+    Some((convert(value)? - convert(0.0)?).abs())
+}
+
+/// Parse a quantity with its standard uncertainty, such as "100 m ± 2 m"
+/// The uncertainty is stored in the unit of the value ("100 m ± 2 cm" is 100 m ± 0.02 m),
+/// a unit written only after the uncertainty is the unit of both ("100±2 m"), and a
+/// percentage is relative to the value ("100 m ± 5%" is 100 m ± 5 m) unless the value is one.
+/// None if either side is not a number (so the input is text), and an error for a negative
+/// uncertainty or one in a unit the value cannot convert to ("100 m ± 2 kg")
+fn parse_uncertain_quantity(value: &str, uncertainty: &str) -> Result<Option<Cell>, String> {
+    let mut cell = parse_cell_input(value)?;
+    let uncertainty_cell = parse_cell_input(uncertainty)?;
+    let (Some(value), Some(uncertainty)) = (cell.as_number(), uncertainty_cell.as_number()) else {
+        return Ok(None);
+    };
+    if uncertainty < 0.0 {
+        return Err(format!(
+            "The uncertainty after ± cannot be negative ({})",
+            uncertainty
+        ));
+    }
+
+    let unit = cell.storage_unit().clone();
+    let uncertainty_unit = uncertainty_cell.storage_unit();
+    let uncertainty = if uncertainty_unit.canonical() == "%" && unit.canonical() != "%" {
+        value.abs() * uncertainty
+    } else if uncertainty_unit.is_dimensionless() || uncertainty_unit.is_equal(&unit) {
+        uncertainty
+    } else if unit.is_dimensionless() {
+        let figures = cell.significant_figures();
+        cell = Cell::new(value, uncertainty_unit.clone());
+        cell.set_significant_figures(figures);
+        uncertainty
+    } else {
+        convert_difference(uncertainty, uncertainty_unit, &unit).ok_or_else(|| {
+            format!(
+                "The uncertainty in {} cannot be converted to the value's unit, {}",
+                uncertainty_unit, unit
+            )
+        })?
+    };
+
+    cell.set_uncertainty(Some(uncertainty));
+    Ok(Some(cell))
+}
+
+/// Parse currency-first format like "$15", "USD 100", "$15/ft"
+fn parse_currency_first(input: &str) -> Option<(&str, f64)> {
+    // Handle "$15" or "$15.5"
+    if let Some(number_str) = input.strip_prefix('$') {
+        if let Ok(value) = number_str.parse::<f64>() {
+            return Some(("$", value));
+        }
+    }
+
+    // Handle "USD 100", "EUR 50.5"
+    if let Some(number_str) = input.strip_prefix("USD ") {
+        if let Ok(value) = number_str.parse::<f64>() {
+            return Some(("USD", value));
+        }
+    }
+    if let Some(number_str) = input.strip_prefix("EUR ") {
+        if let Ok(value) = number_str.parse::<f64>() {
+            return Some(("EUR", value));
+        }
+    }
+    if let Some(number_str) = input.strip_prefix("GBP ") {
+        if let Ok(value) = number_str.parse::<f64>() {
+            return Some(("GBP", value));
+        }
+    }
+    None
+}
+
+/// Parse the unit of a quantity typed into a cell
+pub fn parse_unit(unit_str: &str) -> Unit {
+    // Check if it's a compound unit with division (e.g., "USD/ft", "mi/hr", "$/ft^2")
+    if let Some(pos) = unit_str.find('/') {
+        let numerator_str = &unit_str[..pos];
+        let denominator_str = &unit_str[pos + 1..];
+
+        let (num_dim, num_power) = parse_dimension_with_power(numerator_str);
+        let (den_dim, den_power) = parse_dimension_with_power(denominator_str);
+
+        return Unit::compound(
+            unit_str.to_string(),
+            vec![(num_dim, num_power)],
+            vec![(den_dim, den_power)],
+        );
+    }
+
+    // Check if it's a compound unit with multiplication (e.g., "ft*ft", "kg*m")
+    if let Some(pos) = unit_str.find('*') {
+        let left_str = &unit_str[..pos];
+        let right_str = &unit_str[pos + 1..];
+
+        let (left_dim, left_power) = parse_dimension_with_power(left_str);
+        let (right_dim, right_power) = parse_dimension_with_power(right_str);
+
+        return Unit::compound(
+            unit_str.to_string(),
+            vec![(left_dim.clone(), left_power), (right_dim, right_power)],
+            vec![],
+        );
+    }
+
+    // Check if it's a unit with power (e.g., "ft^2", "m^3")
+    if let Some(pos) = unit_str.find('^') {
+        let base_str = &unit_str[..pos];
+        let power_str = &unit_str[pos + 1..];
+
+        if let Ok(power) = power_str.parse::<i32>() {
+            let dimension = get_base_dimension(base_str);
+            return Unit::compound(unit_str.to_string(), vec![(dimension, power)], vec![]);
+        }
+    }
+
+    // Simple unit
+    let dimension = get_base_dimension(unit_str);
+    Unit::simple(unit_str, dimension)
+}
+
+/// Parse a unit string and extract dimension with power (e.g., "ft^2" -> (Length, 2))
+fn parse_dimension_with_power(unit_str: &str) -> (BaseDimension, i32) {
+    if let Some(pos) = unit_str.find('^') {
+        let base_str = &unit_str[..pos];
+        let power_str = &unit_str[pos + 1..];
+
+        if let Ok(power) = power_str.parse::<i32>() {
+            return (get_base_dimension(base_str), power);
+        }
+    }
+
+    (get_base_dimension(unit_str), 1)
+}
+
+/// The dimension of a unit symbol, or a custom dimension for symbols it does not know
+pub(crate) fn get_base_dimension(unit_str: &str) -> BaseDimension {
+    match unit_str {
+        // Length - short forms
+        "m" | "cm" | "mm" | "km" | "in" | "ft" | "yd" | "mi" => BaseDimension::Length,
+        // Length - long forms (singular and plural)
+        "meter" | "meters" | "centimeter" | "centimeters" | "millimeter" | "millimeters"
+        | "kilometer" | "kilometers" | "inch" | "inches" | "foot" | "feet" | "yard" | "yards"
+        | "mile" | "miles" => BaseDimension::Length,
+
+        // Mass - short forms
+        "g" | "kg" | "mg" | "oz" | "lb" => BaseDimension::Mass,
+        // Mass - long forms (singular and plural)
+        "gram" | "grams" | "kilogram" | "kilograms" | "milligram" | "milligrams" | "ounce"
+        | "ounces" | "pound" | "pounds" => BaseDimension::Mass,
+
+        // Time - short forms (basic time units are Time dimension)
+        "ms" | "s" | "min" | "hr" | "h" => BaseDimension::Time,
+        // Time - long forms (singular and plural)
+        "millisecond" | "milliseconds" | "second" | "seconds" | "minute" | "minutes" | "hour"
+        | "hours" => BaseDimension::Time,
+
+        // Period units (day, week, month, quarter, year) are also Time dimension
+        // This allows proper conversion between rates like $/quarter and $/year
+        "day" | "days" | "wk" | "week" | "weeks" | "month" | "months" | "quarter" | "quarters"
+        | "year" | "years" | "yr" => BaseDimension::Time,
+
+        // Temperature - short forms
+        "C" | "F" | "K" => BaseDimension::Temperature,
+        // Temperature - long forms
+        "Celsius" | "celsius" | "Fahrenheit" | "fahrenheit" | "Kelvin" | "kelvin" => {
+            BaseDimension::Temperature
+        }
+
+        // Currency
+        "USD" | "EUR" | "GBP" | "$" => BaseDimension::Currency,
+
+        // Digital Storage
+        "B" | "KB" | "MB" | "GB" | "TB" | "PB" | "Kb" | "Mb" | "Gb" | "Tb" | "Pb" | "Tok"
+        | "MTok" => BaseDimension::DigitalStorage,
+        // Digital Storage - long forms
+        "byte" | "bytes" | "kilobyte" | "kilobytes" | "megabyte" | "megabytes" | "gigabyte"
+        | "gigabytes" | "terabyte" | "terabytes" | "petabyte" | "petabytes" => {
+            BaseDimension::DigitalStorage
+        }
+
+        _ => BaseDimension::Custom(unit_str.to_string()),
+    }
+}
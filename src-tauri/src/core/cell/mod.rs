@@ -5,8 +5,18 @@
 //   Display conversion is applied based on display_unit preference.
 // - Formula Support: Cells can contain either a direct value or a formula.
 // - Warning System: Incompatible operations are flagged but not blocked.
+// - Uncertainty: A number can carry a standard uncertainty (100 m ± 2 m), kept in the
+//   storage unit alongside the value.
+// - Precision: A number typed in keeps the significant figures it was written with
+//   ("12.0 m" has three), shown in the workbook's significant-figures mode.
 
+pub mod input;
+
+pub use input::parse_cell_input;
+
+use super::datetime::format_datetime;
 use super::units::Unit;
+use chrono::NaiveDateTime;
 use serde::{Deserialize, Serialize};
 use std::fmt;
 
@@ -23,6 +33,14 @@ pub struct Cell {
     /// If None, uses storage_unit
     display_unit: Option<Unit>,
 
+    /// Standard uncertainty of a number, in the storage unit (2 for 100 m ± 2 m)
+    #[serde(default)]
+    uncertainty: Option<f64>,
+
+    /// Significant figures of a measured number (3 for 12.0 m); None for exact numbers
+    #[serde(default)]
+    significant_figures: Option<u32>,
+
     /// Original formula text if this is a formula cell
     formula: Option<String>,
 
@@ -42,8 +60,134 @@ pub enum CellValue {
     /// Plain text string
     Text(String),
 
+    /// TRUE or FALSE
+    Boolean(bool),
+
+    /// A date, or a date and time
+    DateTime(NaiveDateTime),
+
     /// An error (e.g., division by zero, circular reference)
-    Error(String),
+    Error(CellError),
+}
+
+/// The kind of an error value, matching the error values of other spreadsheets
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum ErrorKind {
+    /// Division by zero (#DIV/0!)
+    DivZero,
+
+    /// Reference to a cell or sheet that does not exist (#REF!)
+    Ref,
+
+    /// Unknown function or name (#NAME?)
+    Name,
+
+    /// Wrong kind of value, e.g. text where a number is needed (#VALUE!)
+    #[default]
+    Value,
+
+    /// Incompatible or unknown units (#UNIT!)
+    Unit,
+
+    /// Circular reference (#CIRC!)
+    Circular,
+
+    /// Value not available (#N/A)
+    NA,
+
+    /// An array result blocked by cells in the way (#SPILL!)
+    Spill,
+}
+
+impl ErrorKind {
+    pub const ALL: [ErrorKind; 8] = [
+        ErrorKind::DivZero,
+        ErrorKind::Ref,
+        ErrorKind::Name,
+        ErrorKind::Value,
+        ErrorKind::Unit,
+        ErrorKind::Circular,
+        ErrorKind::NA,
+        ErrorKind::Spill,
+    ];
+
+    /// The error value as shown in a cell and written in formulas
+    pub fn code(&self) -> &'static str {
+        match self {
+            ErrorKind::DivZero => "#DIV/0!",
+            ErrorKind::Ref => "#REF!",
+            ErrorKind::Name => "#NAME?",
+            ErrorKind::Value => "#VALUE!",
+            ErrorKind::Unit => "#UNIT!",
+            ErrorKind::Circular => "#CIRC!",
+            ErrorKind::NA => "#N/A",
+            ErrorKind::Spill => "#SPILL!",
+        }
+    }
+
+    /// The closest native Excel error (Excel has no unit or circular reference errors)
+    pub fn excel_code(&self) -> &'static str {
+        match self {
+            ErrorKind::Unit => "#VALUE!",
+            ErrorKind::Circular => "#REF!",
+            _ => self.code(),
+        }
+    }
+
+    /// Look up an error kind by its code (e.g. "#DIV/0!")
+    pub fn from_code(code: &str) -> Option<Self> {
+        Self::ALL
+            .into_iter()
+            .find(|kind| kind.code().eq_ignore_ascii_case(code))
+    }
+
+    /// A generic description, for errors that have no more specific message
+    pub fn description(&self) -> &'static str {
+        match self {
+            ErrorKind::DivZero => "Division by zero",
+            ErrorKind::Ref => "Invalid reference",
+            ErrorKind::Name => "Unknown name",
+            ErrorKind::Value => "Invalid value",
+            ErrorKind::Unit => "Incompatible units",
+            ErrorKind::Circular => "Circular reference",
+            ErrorKind::NA => "Value not available",
+            ErrorKind::Spill => "Array result blocked by other cells",
+        }
+    }
+}
+
+impl fmt::Display for ErrorKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.code())
+    }
+}
+
+/// An error value held by a cell: its kind and a message explaining it
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct CellError {
+    pub kind: ErrorKind,
+    pub message: String,
+}
+
+impl CellError {
+    pub fn new(kind: ErrorKind, message: impl Into<String>) -> Self {
+        Self {
+            kind,
+            message: message.into(),
+        }
+    }
+}
+
+impl From<ErrorKind> for CellError {
+    fn from(kind: ErrorKind) -> Self {
+        Self::new(kind, kind.description())
+    }
+}
+
+impl fmt::Display for CellError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.message)
+    }
 }
 
 impl Cell {
@@ -53,6 +197,8 @@ impl Cell {
             value: CellValue::Empty,
             storage_unit: Unit::dimensionless(),
             display_unit: None,
+            uncertainty: None,
+            significant_figures: None,
             formula: None,
             warning: None,
         }
@@ -64,6 +210,8 @@ impl Cell {
             value: CellValue::Number(value),
             storage_unit: unit,
             display_unit: None,
+            uncertainty: None,
+            significant_figures: None,
             formula: None,
             warning: None,
         }
@@ -75,6 +223,8 @@ impl Cell {
             value: CellValue::Empty, // Will be computed during evaluation
             storage_unit: Unit::dimensionless(),
             display_unit: None,
+            uncertainty: None,
+            significant_figures: None,
             formula: Some(formula.into()),
             warning: None,
         }
@@ -93,12 +243,24 @@ impl Cell {
         }
     }
 
+    /// Get the standard uncertainty if this cell contains a number with one
+    pub fn uncertainty(&self) -> Option<f64> {
+        self.uncertainty.filter(|_| self.is_number())
+    }
+
+    /// Get the significant figures if this cell contains a number known to a precision
+    pub fn significant_figures(&self) -> Option<u32> {
+        self.significant_figures.filter(|_| self.is_number())
+    }
+
     /// Create a cell with plain text
     pub fn with_text(text: impl Into<String>) -> Self {
         Self {
             value: CellValue::Text(text.into()),
             storage_unit: Unit::dimensionless(),
             display_unit: None,
+            uncertainty: None,
+            significant_figures: None,
             formula: None,
             warning: None,
         }
@@ -112,6 +274,42 @@ impl Cell {
         }
     }
 
+    /// Create a cell with TRUE or FALSE
+    pub fn with_boolean(value: bool) -> Self {
+        Self {
+            value: CellValue::Boolean(value),
+            storage_unit: Unit::dimensionless(),
+            display_unit: None,
+            uncertainty: None,
+            significant_figures: None,
+            formula: None,
+            warning: None,
+        }
+    }
+
+    /// Get the boolean if this cell contains TRUE or FALSE
+    pub fn as_boolean(&self) -> Option<bool> {
+        self.value.as_boolean()
+    }
+
+    /// Create a cell with a date, or a date and time
+    pub fn with_datetime(value: NaiveDateTime) -> Self {
+        Self {
+            value: CellValue::DateTime(value),
+            storage_unit: Unit::dimensionless(),
+            display_unit: None,
+            uncertainty: None,
+            significant_figures: None,
+            formula: None,
+            warning: None,
+        }
+    }
+
+    /// Get the date if this cell contains a date
+    pub fn as_datetime(&self) -> Option<NaiveDateTime> {
+        self.value.as_datetime()
+    }
+
     /// Check if this cell contains text
     pub fn is_text(&self) -> bool {
         matches!(self.value, CellValue::Text(_))
@@ -162,6 +360,11 @@ impl Cell {
         self.warning.is_some()
     }
 
+    /// Replace the formula, keeping the rest of the cell (for rewriting its references)
+    pub fn set_formula(&mut self, formula: impl Into<String>) {
+        self.formula = Some(formula.into());
+    }
+
     /// Set the cell value (for formula evaluation results)
     pub fn set_value(&mut self, value: CellValue) {
         self.value = value;
@@ -177,6 +380,16 @@ impl Cell {
         self.display_unit = unit;
     }
 
+    /// Set the standard uncertainty of the number, in the storage unit
+    pub fn set_uncertainty(&mut self, uncertainty: Option<f64>) {
+        self.uncertainty = uncertainty;
+    }
+
+    /// Set the significant figures the number is known to
+    pub fn set_significant_figures(&mut self, figures: Option<u32>) {
+        self.significant_figures = figures;
+    }
+
     /// Set a warning message
     pub fn set_warning(&mut self, warning: Option<String>) {
         self.warning = warning;
@@ -194,14 +407,22 @@ impl Cell {
             CellValue::Empty => String::new(),
             CellValue::Number(n) => {
                 let unit = self.display_unit();
-                if unit.is_dimensionless() {
-                    format!("{}", n)
-                } else {
-                    format!("{} {}", n, unit)
+                let quantity = |n: f64| {
+                    if unit.is_dimensionless() {
+                        format!("{}", n)
+                    } else {
+                        format!("{} {}", n, unit)
+                    }
+                };
+                match self.uncertainty {
+                    Some(uncertainty) => format!("{} ± {}", quantity(*n), quantity(uncertainty)),
+                    None => quantity(*n),
                 }
             }
             CellValue::Text(t) => t.clone(),
-            CellValue::Error(e) => format!("ERROR: {}", e),
+            CellValue::Boolean(b) => format_boolean(*b).to_string(),
+            CellValue::DateTime(d) => format_datetime(d),
+            CellValue::Error(e) => e.kind.code().to_string(),
         }
     }
 }
@@ -222,6 +443,16 @@ impl CellValue {
         matches!(self, CellValue::Text(_))
     }
 
+    /// Check if this is TRUE or FALSE
+    pub fn is_boolean(&self) -> bool {
+        matches!(self, CellValue::Boolean(_))
+    }
+
+    /// Check if this is a date
+    pub fn is_datetime(&self) -> bool {
+        matches!(self, CellValue::DateTime(_))
+    }
+
     /// Check if this is an error
     pub fn is_error(&self) -> bool {
         matches!(self, CellValue::Error(_))
@@ -243,8 +474,24 @@ impl CellValue {
         }
     }
 
-    /// Get the error message if this is an error
-    pub fn as_error(&self) -> Option<&str> {
+    /// Get as a boolean if possible
+    pub fn as_boolean(&self) -> Option<bool> {
+        match self {
+            CellValue::Boolean(b) => Some(*b),
+            _ => None,
+        }
+    }
+
+    /// Get as a date if possible
+    pub fn as_datetime(&self) -> Option<NaiveDateTime> {
+        match self {
+            CellValue::DateTime(d) => Some(*d),
+            _ => None,
+        }
+    }
+
+    /// Get the error if this is an error
+    pub fn as_error(&self) -> Option<&CellError> {
         match self {
             CellValue::Error(e) => Some(e),
             _ => None,
@@ -252,6 +499,15 @@ impl CellValue {
     }
 }
 
+/// Booleans display as TRUE and FALSE, as in other spreadsheets
+pub fn format_boolean(value: bool) -> &'static str {
+    if value {
+        "TRUE"
+    } else {
+        "FALSE"
+    }
+}
+
 impl fmt::Display for Cell {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         write!(f, "{}", self.formatted())
@@ -264,7 +520,9 @@ impl fmt::Display for CellValue {
             CellValue::Empty => write!(f, ""),
             CellValue::Number(n) => write!(f, "{}", n),
             CellValue::Text(t) => write!(f, "{}", t),
-            CellValue::Error(e) => write!(f, "ERROR: {}", e),
+            CellValue::Boolean(b) => write!(f, "{}", format_boolean(*b)),
+            CellValue::DateTime(d) => write!(f, "{}", format_datetime(d)),
+            CellValue::Error(e) => write!(f, "{}", e.kind),
         }
     }
 }
@@ -342,14 +600,16 @@ mod tests {
     fn test_cell_value_types() {
         let empty = CellValue::Empty;
         let number = CellValue::Number(42.0);
-        let error = CellValue::Error("Division by zero".to_string());
+        let error = CellValue::Error(ErrorKind::DivZero.into());
 
         assert!(empty.is_empty());
         assert!(number.is_number());
         assert!(error.is_error());
 
         assert_eq!(number.as_number(), Some(42.0));
-        assert_eq!(error.as_error(), Some("Division by zero"));
+        assert_eq!(error.as_error().unwrap().kind, ErrorKind::DivZero);
+        assert_eq!(error.as_error().unwrap().message, "Division by zero");
+        assert_eq!(error.to_string(), "#DIV/0!");
     }
 
     #[test]
@@ -365,7 +625,10 @@ mod tests {
         assert_eq!(cell.storage_unit().canonical(), "kg");
 
         // Set error
-        cell.set_value(CellValue::Error("Invalid operation".to_string()));
+        cell.set_value(CellValue::Error(CellError::new(
+            ErrorKind::Value,
+            "Invalid operation",
+        )));
         assert!(cell.is_error());
     }
 
@@ -375,13 +638,52 @@ mod tests {
         assert_eq!(format!("{}", cell), "100 m");
 
         let error_cell = Cell {
-            value: CellValue::Error("Test error".to_string()),
+            value: CellValue::Error(CellError::new(ErrorKind::Unit, "Test error")),
             storage_unit: Unit::dimensionless(),
             display_unit: None,
+            uncertainty: None,
+            significant_figures: None,
             formula: None,
             warning: None,
         };
-        assert_eq!(format!("{}", error_cell), "ERROR: Test error");
+        assert_eq!(format!("{}", error_cell), "#UNIT!");
+    }
+
+    #[test]
+    fn test_cell_with_uncertainty() {
+        let mut cell = Cell::new(100.0, Unit::simple("m", BaseDimension::Length));
+        assert_eq!(cell.uncertainty(), None);
+
+        cell.set_uncertainty(Some(2.0));
+        assert_eq!(cell.uncertainty(), Some(2.0));
+        assert_eq!(cell.formatted(), "100 m ± 2 m");
+
+        // Only numbers have an uncertainty
+        cell.set_value(CellValue::Error(ErrorKind::Value.into()));
+        assert_eq!(cell.uncertainty(), None);
+    }
+
+    #[test]
+    fn test_cell_significant_figures() {
+        let mut cell = Cell::new(12.0, Unit::simple("m", BaseDimension::Length));
+        assert_eq!(cell.significant_figures(), None);
+
+        cell.set_significant_figures(Some(3));
+        assert_eq!(cell.significant_figures(), Some(3));
+
+        cell.set_value(CellValue::Text("twelve".to_string()));
+        assert_eq!(cell.significant_figures(), None);
+    }
+
+    #[test]
+    fn test_error_codes() {
+        for kind in ErrorKind::ALL {
+            assert_eq!(ErrorKind::from_code(kind.code()), Some(kind));
+        }
+        assert_eq!(ErrorKind::from_code("#n/a"), Some(ErrorKind::NA));
+        assert_eq!(ErrorKind::from_code("#OOPS!"), None);
+        assert_eq!(ErrorKind::Unit.excel_code(), "#VALUE!");
+        assert_eq!(ErrorKind::DivZero.excel_code(), "#DIV/0!");
     }
 
     #[test]
@@ -395,6 +697,31 @@ mod tests {
         assert_eq!(cell.formatted(), "Hello, World!");
     }
 
+    #[test]
+    fn test_datetime_cell() {
+        let date = chrono::NaiveDate::from_ymd_opt(2024, 3, 15)
+            .unwrap()
+            .and_hms_opt(0, 0, 0)
+            .unwrap();
+        let cell = Cell::with_datetime(date);
+
+        assert!(cell.value().is_datetime());
+        assert!(!cell.is_number());
+        assert_eq!(cell.as_datetime(), Some(date));
+        assert_eq!(cell.formatted(), "2024-03-15");
+    }
+
+    #[test]
+    fn test_boolean_cell() {
+        let cell = Cell::with_boolean(true);
+
+        assert!(cell.value().is_boolean());
+        assert!(!cell.is_number());
+        assert_eq!(cell.as_boolean(), Some(true));
+        assert_eq!(cell.formatted(), "TRUE");
+        assert_eq!(format!("{}", CellValue::Boolean(false)), "FALSE");
+    }
+
     #[test]
     fn test_text_cell_value() {
         let text = CellValue::Text("Test".to_string());
@@ -0,0 +1,172 @@
+// Date and time values
+//
+// Dates are stored as chrono NaiveDateTime (no time zone). For Excel they are
+// converted to date serials: days since 1899-12-30, with the time as a fraction.
+// (These match Excel's serials from March 1900; Excel counts a 29 February 1900.)
+
+use chrono::{Datelike, Duration, Months, NaiveDate, NaiveDateTime, NaiveTime, Timelike};
+
+/// Formats accepted for dates typed into a cell, tried in order
+const DATETIME_FORMATS: &[&str] = &[
+    "%Y-%m-%d %H:%M:%S",
+    "%Y-%m-%d %H:%M",
+    "%Y-%m-%dT%H:%M:%S",
+    "%Y-%m-%dT%H:%M",
+];
+const DATE_FORMATS: &[&str] = &["%Y-%m-%d", "%Y/%m/%d", "%d %b %Y", "%b %d, %Y", "%B %d, %Y"];
+
+/// Units that durations are added in calendar months rather than a fixed length
+const MONTH_UNITS: &[(&str, u32)] = &[
+    ("month", 1),
+    ("months", 1),
+    ("quarter", 3),
+    ("quarters", 3),
+    ("year", 12),
+    ("years", 12),
+    ("yr", 12),
+];
+
+fn excel_epoch() -> NaiveDateTime {
+    NaiveDate::from_ymd_opt(1899, 12, 30)
+        .expect("valid date")
+        .and_time(NaiveTime::MIN)
+}
+
+/// Parse a date or date and time typed into a cell
+/// Examples: "2024-03-15", "2024-03-15 14:30", "15 Mar 2024", "Mar 15, 2024"
+pub fn parse_datetime(input: &str) -> Option<NaiveDateTime> {
+    let input = input.trim();
+    DATETIME_FORMATS
+        .iter()
+        .find_map(|format| NaiveDateTime::parse_from_str(input, format).ok())
+        .or_else(|| {
+            DATE_FORMATS
+                .iter()
+                .find_map(|format| NaiveDate::parse_from_str(input, format).ok())
+                .map(|date| date.and_time(NaiveTime::MIN))
+        })
+}
+
+/// Format a date for display: the date alone at midnight, otherwise with the time
+pub fn format_datetime(value: &NaiveDateTime) -> String {
+    if value.time() == NaiveTime::MIN {
+        value.format("%Y-%m-%d").to_string()
+    } else if value.second() == 0 {
+        value.format("%Y-%m-%d %H:%M").to_string()
+    } else {
+        value.format("%Y-%m-%d %H:%M:%S").to_string()
+    }
+}
+
+/// Convert to an Excel date serial
+pub fn to_serial(value: &NaiveDateTime) -> f64 {
+    (*value - excel_epoch()).num_milliseconds() as f64 / 86_400_000.0
+}
+
+/// Convert from an Excel date serial
+pub fn from_serial(serial: f64) -> Option<NaiveDateTime> {
+    if !serial.is_finite() {
+        return None;
+    }
+    let millis = (serial * 86_400_000.0).round() as i64;
+    excel_epoch().checked_add_signed(Duration::try_milliseconds(millis)?)
+}
+
+/// Days between two dates, including any fraction of a day
+pub fn days_between(start: &NaiveDateTime, end: &NaiveDateTime) -> f64 {
+    (*end - *start).num_milliseconds() as f64 / 86_400_000.0
+}
+
+/// Add a whole number of calendar months, clamping to the end of shorter months
+/// (Jan 31 + 1 month is Feb 28 or 29)
+pub fn add_months(value: &NaiveDateTime, months: i32) -> Option<NaiveDateTime> {
+    let delta = Months::new(months.unsigned_abs());
+    if months >= 0 {
+        value.checked_add_months(delta)
+    } else {
+        value.checked_sub_months(delta)
+    }
+}
+
+/// Add a number of seconds
+pub fn add_seconds(value: &NaiveDateTime, seconds: f64) -> Option<NaiveDateTime> {
+    let millis = (seconds * 1000.0).round();
+    if !millis.is_finite() || millis.abs() > i64::MAX as f64 {
+        return None;
+    }
+    value.checked_add_signed(Duration::try_milliseconds(millis as i64)?)
+}
+
+/// Months in a calendar unit (month, quarter, year), or None for fixed-length units
+pub fn calendar_months(unit: &str) -> Option<u32> {
+    MONTH_UNITS
+        .iter()
+        .find(|(name, _)| *name == unit)
+        .map(|(_, months)| *months)
+}
+
+/// The last day of the month `value` falls in
+pub fn end_of_month(value: &NaiveDateTime) -> Option<NaiveDateTime> {
+    let first = value.date().with_day(1)?;
+    let next = first.checked_add_months(Months::new(1))?;
+    Some(next.pred_opt()?.and_time(NaiveTime::MIN))
+}
+
+/// Whether a date falls on a Saturday or Sunday
+pub fn is_weekend(value: &NaiveDate) -> bool {
+    value.weekday().number_from_monday() > 5
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn date(y: i32, m: u32, d: u32) -> NaiveDateTime {
+        NaiveDate::from_ymd_opt(y, m, d)
+            .unwrap()
+            .and_time(NaiveTime::MIN)
+    }
+
+    #[test]
+    fn test_parse_datetime() {
+        assert_eq!(parse_datetime("2024-03-15"), Some(date(2024, 3, 15)));
+        assert_eq!(parse_datetime("2024/03/15"), Some(date(2024, 3, 15)));
+        assert_eq!(parse_datetime("15 Mar 2024"), Some(date(2024, 3, 15)));
+        assert_eq!(parse_datetime("Mar 15, 2024"), Some(date(2024, 3, 15)));
+        assert_eq!(
+            parse_datetime("2024-03-15 14:30"),
+            Some(date(2024, 3, 15) + Duration::minutes(14 * 60 + 30))
+        );
+        assert_eq!(parse_datetime("2024-02-30"), None);
+        assert_eq!(parse_datetime("100"), None);
+        assert_eq!(parse_datetime("12:30"), None);
+    }
+
+    #[test]
+    fn test_format_datetime() {
+        assert_eq!(format_datetime(&date(2024, 3, 15)), "2024-03-15");
+        let afternoon = date(2024, 3, 15) + Duration::minutes(14 * 60 + 30);
+        assert_eq!(format_datetime(&afternoon), "2024-03-15 14:30");
+    }
+
+    #[test]
+    fn test_excel_serials() {
+        assert_eq!(to_serial(&date(1900, 3, 1)), 61.0);
+        assert_eq!(to_serial(&date(2024, 3, 15)), 45366.0);
+        assert_eq!(
+            to_serial(&(date(2024, 3, 15) + Duration::hours(12))),
+            45366.5
+        );
+        assert_eq!(from_serial(45366.0), Some(date(2024, 3, 15)));
+    }
+
+    #[test]
+    fn test_calendar_arithmetic() {
+        assert_eq!(add_months(&date(2024, 1, 31), 1), Some(date(2024, 2, 29)));
+        assert_eq!(add_months(&date(2024, 3, 15), -3), Some(date(2023, 12, 15)));
+        assert_eq!(end_of_month(&date(2023, 2, 10)), Some(date(2023, 2, 28)));
+        assert_eq!(days_between(&date(2024, 3, 1), &date(2024, 3, 15)), 14.0);
+        assert!(is_weekend(&NaiveDate::from_ymd_opt(2024, 3, 16).unwrap()));
+        assert!(!is_weekend(&NaiveDate::from_ymd_opt(2024, 3, 15).unwrap()));
+    }
+}
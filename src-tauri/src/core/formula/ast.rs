@@ -1,5 +1,6 @@
 // Abstract Syntax Tree for formulas
 
+use crate::core::cell::ErrorKind;
 use serde::{Deserialize, Serialize};
 
 /// A parsed formula expression
@@ -17,10 +18,13 @@ pub enum Expr {
     /// A string literal (e.g., "Hello", "world")
     String(String),
 
-    /// A cell reference (e.g., A1, B12)
+    /// A cell reference (e.g., A1, B12, $A$1, A$1)
+    /// An absolute column or row is marked with `$` and is kept as-is when the formula is copied
     CellRef {
         col: String,
         row: usize,
+        col_absolute: bool,
+        row_absolute: bool,
     },
 
     /// A named cell/range reference (e.g., revenue, tax_rate)
@@ -34,6 +38,29 @@ pub enum Expr {
         end: Box<Expr>,
     },
 
+    /// A whole-column range (e.g., B:B, A:C)
+    ColumnRange {
+        start: String,
+        end: String,
+    },
+
+    /// A whole-row range (e.g., 3:3, 2:5)
+    RowRange {
+        start: usize,
+        end: usize,
+    },
+
+    /// The cells an array formula spilled into, named by the formula's cell (e.g., A1#)
+    /// The inner expression is a CellRef
+    SpillRef(Box<Expr>),
+
+    /// A reference into another sheet (e.g., Sheet2!A1, 'Cost Model'!B3:B20)
+    /// The inner expression is a CellRef, Range, ColumnRange, RowRange or SpillRef
+    SheetRef {
+        sheet: String,
+        reference: Box<Expr>,
+    },
+
     /// Addition
     Add(Box<Expr>, Box<Expr>),
 
@@ -46,9 +73,15 @@ pub enum Expr {
     /// Division
     Divide(Box<Expr>, Box<Expr>),
 
+    /// Exponentiation (base ^ exponent)
+    Power(Box<Expr>, Box<Expr>),
+
     /// Unary negation
     Negate(Box<Expr>),
 
+    /// Text concatenation (a & b)
+    Concat(Box<Expr>, Box<Expr>),
+
     /// Function call (e.g., SUM(A1:A10))
     Function {
         name: String,
@@ -58,6 +91,10 @@ pub enum Expr {
     /// Boolean literal (TRUE or FALSE)
     Boolean(bool),
 
+    /// Error literal (e.g., #REF!, #N/A)
+    /// References to deleted cells and sheets are rewritten to #REF!
+    Error(ErrorKind),
+
     /// Comparison operators
     GreaterThan(Box<Expr>, Box<Expr>),
     LessThan(Box<Expr>, Box<Expr>),
@@ -91,11 +128,23 @@ impl Expr {
         Self::String(value.into())
     }
 
-    /// Create a cell reference
+    /// Create a relative cell reference
     pub fn cell_ref(col: impl Into<String>, row: usize) -> Self {
+        Self::absolute_cell_ref(col, row, false, false)
+    }
+
+    /// Create a cell reference with an absolute (`$`) column and/or row
+    pub fn absolute_cell_ref(
+        col: impl Into<String>,
+        row: usize,
+        col_absolute: bool,
+        row_absolute: bool,
+    ) -> Self {
         Self::CellRef {
             col: col.into(),
             row,
+            col_absolute,
+            row_absolute,
         }
     }
 
@@ -112,6 +161,32 @@ impl Expr {
         }
     }
 
+    /// Create a whole-column range
+    pub fn column_range(start: impl Into<String>, end: impl Into<String>) -> Self {
+        Self::ColumnRange {
+            start: start.into(),
+            end: end.into(),
+        }
+    }
+
+    /// Create a whole-row range
+    pub fn row_range(start: usize, end: usize) -> Self {
+        Self::RowRange { start, end }
+    }
+
+    /// Create a reference to the cells an array formula spilled into
+    pub fn spill_ref(anchor: Expr) -> Self {
+        Self::SpillRef(Box::new(anchor))
+    }
+
+    /// Create a sheet-qualified reference
+    pub fn sheet_ref(sheet: impl Into<String>, reference: Expr) -> Self {
+        Self::SheetRef {
+            sheet: sheet.into(),
+            reference: Box::new(reference),
+        }
+    }
+
     /// Create an addition
     pub fn new_add(left: Expr, right: Expr) -> Self {
         Self::Add(Box::new(left), Box::new(right))
@@ -132,11 +207,21 @@ impl Expr {
         Self::Divide(Box::new(left), Box::new(right))
     }
 
+    /// Create an exponentiation
+    pub fn new_power(base: Expr, exponent: Expr) -> Self {
+        Self::Power(Box::new(base), Box::new(exponent))
+    }
+
     /// Create a negation
     pub fn negate(expr: Expr) -> Self {
         Self::Negate(Box::new(expr))
     }
 
+    /// Create a text concatenation
+    pub fn concat(left: Expr, right: Expr) -> Self {
+        Self::Concat(Box::new(left), Box::new(right))
+    }
+
     /// Create a function call
     pub fn function(name: impl Into<String>, args: Vec<Expr>) -> Self {
         Self::Function {
@@ -202,14 +287,37 @@ impl std::fmt::Display for Expr {
             Expr::Number(n) => write!(f, "{}", n),
             Expr::NumberWithUnit { value, unit } => write!(f, "{}{}", value, unit),
             Expr::String(s) => write!(f, "\"{}\"", s),
-            Expr::CellRef { col, row } => write!(f, "{}{}", col, row),
+            Expr::CellRef {
+                col,
+                row,
+                col_absolute,
+                row_absolute,
+            } => {
+                let anchor = |absolute: &bool| if *absolute { "$" } else { "" };
+                write!(
+                    f,
+                    "{}{}{}{}",
+                    anchor(col_absolute),
+                    col,
+                    anchor(row_absolute),
+                    row
+                )
+            }
             Expr::NamedRef { name } => write!(f, "{}", name),
             Expr::Range { start, end } => write!(f, "{}:{}", start, end),
+            Expr::ColumnRange { start, end } => write!(f, "{}:{}", start, end),
+            Expr::RowRange { start, end } => write!(f, "{}:{}", start, end),
+            Expr::SpillRef(anchor) => write!(f, "{}#", anchor),
+            Expr::SheetRef { sheet, reference } => {
+                write!(f, "{}!{}", quote_sheet_name(sheet), reference)
+            }
             Expr::Add(l, r) => write!(f, "({} + {})", l, r),
             Expr::Subtract(l, r) => write!(f, "({} - {})", l, r),
             Expr::Multiply(l, r) => write!(f, "({} * {})", l, r),
             Expr::Divide(l, r) => write!(f, "({} / {})", l, r),
+            Expr::Power(l, r) => write!(f, "({} ^ {})", l, r),
             Expr::Negate(e) => write!(f, "(-{})", e),
+            Expr::Concat(l, r) => write!(f, "({} & {})", l, r),
             Expr::Function { name, args } => {
                 write!(f, "{}(", name)?;
                 for (i, arg) in args.iter().enumerate() {
@@ -221,12 +329,13 @@ impl std::fmt::Display for Expr {
                 write!(f, ")")
             }
             Expr::Boolean(b) => write!(f, "{}", if *b { "TRUE" } else { "FALSE" }),
+            Expr::Error(kind) => write!(f, "{}", kind.code()),
             Expr::GreaterThan(l, r) => write!(f, "({} > {})", l, r),
             Expr::LessThan(l, r) => write!(f, "({} < {})", l, r),
             Expr::GreaterOrEqual(l, r) => write!(f, "({} >= {})", l, r),
             Expr::LessOrEqual(l, r) => write!(f, "({} <= {})", l, r),
-            Expr::Equal(l, r) => write!(f, "({} == {})", l, r),
-            Expr::NotEqual(l, r) => write!(f, "({} != {})", l, r),
+            Expr::Equal(l, r) => write!(f, "({} = {})", l, r),
+            Expr::NotEqual(l, r) => write!(f, "({} <> {})", l, r),
             Expr::And(l, r) => write!(f, "({} AND {})", l, r),
             Expr::Or(l, r) => write!(f, "({} OR {})", l, r),
             Expr::Not(e) => write!(f, "(NOT {})", e),
@@ -234,6 +343,40 @@ impl std::fmt::Display for Expr {
     }
 }
 
+/// A workbook function defined with LAMBDA, e.g. LAMBDA(rate: USD/hr, util, rate*util*730 hr/month)
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Lambda {
+    pub params: Vec<LambdaParam>,
+    /// The expression the function returns, which refers to the parameters by name
+    pub body: Expr,
+}
+
+/// A parameter of a LAMBDA, with the unit its argument must be compatible with, if any
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct LambdaParam {
+    pub name: String,
+    pub unit: Option<String>,
+}
+
+/// Quote a sheet name for use in a formula if it is not a plain identifier
+/// Examples: "Sheet2" → "Sheet2", "Cost Model" → "'Cost Model'", "Bob's" → "'Bob''s'"
+pub fn quote_sheet_name(name: &str) -> String {
+    let mut chars = name.chars();
+    let is_plain = match chars.next() {
+        Some(first) => {
+            (first.is_ascii_alphabetic() || first == '_')
+                && chars.all(|c| c.is_ascii_alphanumeric() || c == '_')
+        }
+        None => false,
+    };
+
+    if is_plain {
+        name.to_string()
+    } else {
+        format!("'{}'", name.replace('\'', "''"))
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -249,8 +392,40 @@ mod tests {
         );
         assert_eq!(format!("{}", expr), "(100m * 2m)");
 
+        let expr = Expr::new_power(Expr::cell_ref("A", 1), Expr::number(2.0));
+        assert_eq!(format!("{}", expr), "(A1 ^ 2)");
+
         let expr = Expr::cell_ref("A", 1);
         assert_eq!(format!("{}", expr), "A1");
+
+        let expr = Expr::sheet_ref("Sheet2", Expr::cell_ref("A", 1));
+        assert_eq!(format!("{}", expr), "Sheet2!A1");
+
+        let expr = Expr::sheet_ref(
+            "Cost Model",
+            Expr::range(Expr::cell_ref("B", 3), Expr::cell_ref("B", 20)),
+        );
+        assert_eq!(format!("{}", expr), "'Cost Model'!B3:B20");
+
+        let expr = Expr::column_range("B", "D");
+        assert_eq!(format!("{}", expr), "B:D");
+
+        let expr = Expr::row_range(3, 3);
+        assert_eq!(format!("{}", expr), "3:3");
+
+        let expr = Expr::or(
+            Expr::not_equal(Expr::cell_ref("A", 1), Expr::number(0.0)),
+            Expr::new_not(Expr::equal(Expr::cell_ref("B", 1), Expr::number(1.0))),
+        );
+        assert_eq!(format!("{}", expr), "((A1 <> 0) OR (NOT (B1 = 1)))");
+    }
+
+    #[test]
+    fn test_quote_sheet_name() {
+        assert_eq!(quote_sheet_name("Inputs"), "Inputs");
+        assert_eq!(quote_sheet_name("Cost Model"), "'Cost Model'");
+        assert_eq!(quote_sheet_name("2024"), "'2024'");
+        assert_eq!(quote_sheet_name("Bob's"), "'Bob''s'");
     }
 
     #[test]
@@ -265,6 +440,6 @@ mod tests {
         ));
 
         let cell = Expr::cell_ref("B", 5);
-        assert!(matches!(cell, Expr::CellRef { col, row: 5 } if col == "B"));
+        assert!(matches!(cell, Expr::CellRef { col, row: 5, .. } if col == "B"));
     }
 }
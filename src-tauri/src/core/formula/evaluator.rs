@@ -1,8 +1,20 @@
 // Formula evaluator with unit-aware operations
 
-use super::ast::Expr;
-use crate::core::units::{parse_unit, BaseDimension, Dimension, Unit, UnitLibrary};
+use super::ast::{Expr, Lambda, LambdaParam};
+use super::functions::FunctionRegistry;
+use crate::core::cell::{format_boolean, CellError, ErrorKind};
+use crate::core::datetime;
+use crate::core::precision;
+use crate::core::random::RandomStream;
+use crate::core::table::{CellAddr, CellRange};
+use crate::core::units::{
+    normalize_unit_exponents, parse_unit, BaseDimension, Dimension, Exponent, Unit, UnitLibrary,
+};
+use chrono::NaiveDateTime;
+use std::cell::RefCell;
+use std::cmp::Ordering;
 use std::collections::HashMap;
+use std::rc::Rc;
 use thiserror::Error;
 
 #[derive(Debug, Error)]
@@ -23,6 +35,9 @@ pub enum EvalError {
     #[error("Named reference not found: {0}")]
     NamedRefNotFound(String),
 
+    #[error("Sheet not found: {0}")]
+    SheetNotFound(String),
+
     #[error("Unknown unit: {0}")]
     UnknownUnit(String),
 
@@ -31,12 +46,44 @@ pub enum EvalError {
 
     #[error("Invalid operation: {0}")]
     InvalidOperation(String),
+
+    #[error("Value not available: {0}")]
+    NotAvailable(String),
+
+    /// An error value (from a referenced cell or an error literal), passed on unchanged
+    #[error("{0}")]
+    ErrorValue(CellError),
+}
+
+impl EvalError {
+    /// The kind of error value a cell shows for this error
+    pub fn kind(&self) -> ErrorKind {
+        match self {
+            EvalError::IncompatibleUnits { .. } | EvalError::UnknownUnit(_) => ErrorKind::Unit,
+            EvalError::DivisionByZero => ErrorKind::DivZero,
+            EvalError::CellNotFound(_) | EvalError::SheetNotFound(_) => ErrorKind::Ref,
+            EvalError::NamedRefNotFound(_) | EvalError::FunctionNotImplemented(_) => {
+                ErrorKind::Name
+            }
+            EvalError::InvalidOperation(_) => ErrorKind::Value,
+            EvalError::NotAvailable(_) => ErrorKind::NA,
+            EvalError::ErrorValue(error) => error.kind,
+        }
+    }
+
+    /// The error value to store in a cell
+    pub fn to_cell_error(&self) -> CellError {
+        match self {
+            EvalError::ErrorValue(error) => error.clone(),
+            _ => CellError::new(self.kind(), self.to_string()),
+        }
+    }
 }
 
 /// Result of evaluating an expression
 #[derive(Debug, Clone, PartialEq)]
 pub struct EvalResult {
-    /// The computed value (either number or text)
+    /// The computed value (a number, text, a boolean, a date or an array of values)
     pub value: EvalValue,
 
     /// The unit of the result (only applicable for numeric values)
@@ -44,6 +91,12 @@ pub struct EvalResult {
 
     /// Optional warning message (for incompatible but allowed operations)
     pub warning: Option<String>,
+
+    /// Standard uncertainty of a number, in the result's unit (2 for 100 m ± 2 m)
+    pub uncertainty: Option<f64>,
+
+    /// Significant figures of a measured number (3 for 12.0 m); None for exact numbers
+    pub significant_figures: Option<u32>,
 }
 
 /// The value type for evaluation results
@@ -51,6 +104,10 @@ pub struct EvalResult {
 pub enum EvalValue {
     Number(f64),
     Text(String),
+    Boolean(bool),
+    DateTime(NaiveDateTime),
+    /// Several values (a range, SORT(...)), which spill into neighbouring cells
+    Array(Array),
 }
 
 impl EvalResult {
@@ -59,6 +116,8 @@ impl EvalResult {
             value: EvalValue::Number(value),
             unit,
             warning: None,
+            uncertainty: None,
+            significant_figures: None,
         }
     }
 
@@ -67,6 +126,38 @@ impl EvalResult {
             value: EvalValue::Text(value),
             unit: Unit::dimensionless(),
             warning: None,
+            uncertainty: None,
+            significant_figures: None,
+        }
+    }
+
+    pub fn boolean(value: bool) -> Self {
+        Self {
+            value: EvalValue::Boolean(value),
+            unit: Unit::dimensionless(),
+            warning: None,
+            uncertainty: None,
+            significant_figures: None,
+        }
+    }
+
+    pub fn datetime(value: NaiveDateTime) -> Self {
+        Self {
+            value: EvalValue::DateTime(value),
+            unit: Unit::dimensionless(),
+            warning: None,
+            uncertainty: None,
+            significant_figures: None,
+        }
+    }
+
+    pub fn array(values: Array) -> Self {
+        Self {
+            value: EvalValue::Array(values),
+            unit: Unit::dimensionless(),
+            warning: None,
+            uncertainty: None,
+            significant_figures: None,
         }
     }
 
@@ -75,6 +166,16 @@ impl EvalResult {
         self
     }
 
+    pub fn with_uncertainty(mut self, uncertainty: Option<f64>) -> Self {
+        self.uncertainty = uncertainty;
+        self
+    }
+
+    pub fn with_significant_figures(mut self, figures: Option<u32>) -> Self {
+        self.significant_figures = figures;
+        self
+    }
+
     pub fn as_number(&self) -> Option<f64> {
         match self.value {
             EvalValue::Number(n) => Some(n),
@@ -89,10 +190,52 @@ impl EvalResult {
         }
     }
 
+    pub fn as_boolean(&self) -> Option<bool> {
+        match self.value {
+            EvalValue::Boolean(b) => Some(b),
+            _ => None,
+        }
+    }
+
+    pub fn as_datetime(&self) -> Option<NaiveDateTime> {
+        match self.value {
+            EvalValue::DateTime(d) => Some(d),
+            _ => None,
+        }
+    }
+
     pub fn is_text(&self) -> bool {
         matches!(self.value, EvalValue::Text(_))
     }
 
+    pub fn is_boolean(&self) -> bool {
+        matches!(self.value, EvalValue::Boolean(_))
+    }
+
+    pub fn is_datetime(&self) -> bool {
+        matches!(self.value, EvalValue::DateTime(_))
+    }
+
+    pub fn is_array(&self) -> bool {
+        matches!(self.value, EvalValue::Array(_))
+    }
+
+    /// The values as an array; a single value is a 1x1 array
+    pub fn into_array(self) -> Array {
+        match self.value {
+            EvalValue::Array(values) => values,
+            _ => vec![vec![self]],
+        }
+    }
+
+    /// Booleans used as numbers count as 1 (TRUE) and 0 (FALSE); other values are unchanged
+    pub fn coerce_number(self) -> Self {
+        match self.value {
+            EvalValue::Boolean(b) => Self::new(if b { 1.0 } else { 0.0 }, Unit::dimensionless()),
+            _ => self,
+        }
+    }
+
     pub fn is_number(&self) -> bool {
         matches!(self.value, EvalValue::Number(_))
     }
@@ -101,22 +244,279 @@ impl EvalResult {
     pub fn numeric_value(&self) -> f64 {
         match self.value {
             EvalValue::Number(n) => n,
+            EvalValue::Boolean(b) => {
+                if b {
+                    1.0
+                } else {
+                    0.0
+                }
+            }
             EvalValue::Text(_) => panic!("Expected number, got text"),
+            EvalValue::DateTime(_) => panic!("Expected number, got a date"),
+            EvalValue::Array(_) => panic!("Expected number, got an array"),
         }
     }
 }
 
+/// The cells of a range row by row, for functions that need each cell's position (lookups)
+/// Empty cells are None and error cells hold their error
+pub type CellGrid = Vec<Vec<Option<Result<EvalResult, EvalError>>>>;
+
+/// The values of an array (A1:A3 * B1:B3, SEQUENCE(3)) row by row
+pub type Array = Vec<Vec<EvalResult>>;
+
+/// Source of the values that references in a formula point at
+/// A sheet implements this to resolve cells, ranges and named references
+pub trait EvalContext {
+    /// Value of a cell, on the given sheet or the current one
+    fn cell(&self, sheet: Option<&str>, addr: &CellAddr) -> Result<EvalResult, EvalError>;
+
+    /// Values in a range, on the given sheet or the current one
+    /// Empty, text and boolean cells are skipped; numbers and dates are returned
+    fn range(&self, sheet: Option<&str>, range: &CellRange) -> Result<Vec<EvalResult>, EvalError>;
+
+    /// Every cell in a range, on the given sheet or the current one
+    /// Whole-column and whole-row ranges stop at the last row or column in use on the sheet
+    fn grid(&self, sheet: Option<&str>, range: &CellRange) -> Result<CellGrid, EvalError>;
+
+    /// The cells a formula's array spilled into (A1#), on the given sheet or the current one
+    fn spill(&self, sheet: Option<&str>, anchor: &CellAddr) -> Result<CellRange, EvalError>;
+
+    /// Value of a named reference
+    fn name(&self, name: &str) -> Result<EvalResult, EvalError>;
+
+    /// Whether a cell is empty, on the given sheet or the current one
+    fn is_blank(&self, sheet: Option<&str>, addr: &CellAddr) -> Result<bool, EvalError>;
+}
+
+/// Context for standalone evaluation, where formulas cannot use references
+pub struct NoContext;
+
+impl EvalContext for NoContext {
+    fn cell(&self, _sheet: Option<&str>, _addr: &CellAddr) -> Result<EvalResult, EvalError> {
+        Err(EvalError::CellNotFound(
+            "Cell references not supported in standalone evaluation".to_string(),
+        ))
+    }
+
+    fn range(
+        &self,
+        _sheet: Option<&str>,
+        _range: &CellRange,
+    ) -> Result<Vec<EvalResult>, EvalError> {
+        Err(EvalError::CellNotFound(
+            "Cell references not supported in standalone evaluation".to_string(),
+        ))
+    }
+
+    fn grid(&self, _sheet: Option<&str>, _range: &CellRange) -> Result<CellGrid, EvalError> {
+        Err(EvalError::CellNotFound(
+            "Cell references not supported in standalone evaluation".to_string(),
+        ))
+    }
+
+    fn spill(&self, _sheet: Option<&str>, _anchor: &CellAddr) -> Result<CellRange, EvalError> {
+        Err(EvalError::CellNotFound(
+            "Cell references not supported in standalone evaluation".to_string(),
+        ))
+    }
+
+    fn name(&self, name: &str) -> Result<EvalResult, EvalError> {
+        Err(EvalError::NamedRefNotFound(format!(
+            "Named reference '{}' not supported in standalone evaluation",
+            name
+        )))
+    }
+
+    fn is_blank(&self, _sheet: Option<&str>, _addr: &CellAddr) -> Result<bool, EvalError> {
+        Err(EvalError::CellNotFound(
+            "Cell references not supported in standalone evaluation".to_string(),
+        ))
+    }
+}
+
+/// A reference a formula worked out while it was evaluated (INDIRECT, OFFSET), on the named
+/// sheet or the formula's own
+pub type ResolvedReference = (Option<String>, CellRange);
+
+/// How deeply workbook functions may call each other, so a function calling itself stops
+const MAX_LAMBDA_DEPTH: usize = 64;
+
 /// Unit-aware formula evaluator
+/// References are resolved through an EvalContext and functions are looked up in a FunctionRegistry
 pub struct Evaluator<'a> {
     library: &'a UnitLibrary,
+    context: &'a dyn EvalContext,
+    functions: &'a FunctionRegistry,
+    /// Values of LET names and LAMBDA parameters, which hide named ranges of the same name
+    bindings: HashMap<String, EvalResult>,
+    /// Number of workbook functions being called
+    depth: usize,
+    /// Where RAND and RANDBETWEEN draw their numbers from
+    random: Rc<RandomStream>,
+    /// References resolved by INDIRECT and OFFSET, which the formula's cell depends on
+    resolved: Rc<RefCell<Vec<ResolvedReference>>>,
+    /// Add and subtract in the left operand's unit rather than the finer of the two units
+    left_operand_units: bool,
 }
 
 impl<'a> Evaluator<'a> {
+    /// Create an evaluator for formulas without references, using the built-in functions
     pub fn new(library: &'a UnitLibrary) -> Self {
-        Self { library }
+        Self::with_context(library, &NoContext)
+    }
+
+    /// Create an evaluator that resolves references through `context`
+    pub fn with_context(library: &'a UnitLibrary, context: &'a dyn EvalContext) -> Self {
+        Self {
+            library,
+            context,
+            functions: FunctionRegistry::standard(),
+            bindings: HashMap::new(),
+            depth: 0,
+            random: Rc::new(RandomStream::unseeded()),
+            resolved: Rc::default(),
+            left_operand_units: false,
+        }
+    }
+
+    /// Use `functions` instead of the built-in functions
+    pub fn with_functions(mut self, functions: &'a FunctionRegistry) -> Self {
+        self.functions = functions;
+        self
     }
 
-    /// Evaluate an expression (without cell references)
+    /// Draw random numbers from `random` (e.g. a cell's seeded stream)
+    pub fn with_random(mut self, random: RandomStream) -> Self {
+        self.random = Rc::new(random);
+        self
+    }
+
+    /// Give sums and differences in the left operand's unit, as cell formulas do
+    /// (5 m + 3 ft is 5.9144 m), instead of the finer of the two units (19.4042 ft)
+    pub fn with_left_operand_units(mut self) -> Self {
+        self.left_operand_units = true;
+        self
+    }
+
+    /// The stream RAND and RANDBETWEEN draw from
+    pub fn random(&self) -> &RandomStream {
+        &self.random
+    }
+
+    /// Note a reference worked out while evaluating (INDIRECT, OFFSET), so that the formula's
+    /// cell can be recalculated when the referenced cells change
+    pub fn record_reference(&self, sheet: Option<&str>, range: CellRange) {
+        self.resolved
+            .borrow_mut()
+            .push((sheet.map(str::to_string), range));
+    }
+
+    /// The references worked out so far, in the order they were resolved
+    pub fn resolved_references(&self) -> Vec<ResolvedReference> {
+        self.resolved.borrow().clone()
+    }
+
+    /// An evaluator that also binds each name to its value, for LET
+    pub fn with_bindings(
+        &self,
+        values: impl IntoIterator<Item = (String, EvalResult)>,
+    ) -> Evaluator<'a> {
+        let mut bindings = self.bindings.clone();
+        bindings.extend(values);
+        Evaluator {
+            library: self.library,
+            context: self.context,
+            functions: self.functions,
+            bindings,
+            depth: self.depth,
+            random: self.random.clone(),
+            left_operand_units: self.left_operand_units,
+            resolved: self.resolved.clone(),
+        }
+    }
+
+    /// Call a workbook function: evaluate the arguments here and the body with the parameters
+    /// bound to them. Parameters with a unit only accept compatible quantities
+    pub fn call_lambda(
+        &self,
+        name: &str,
+        lambda: &Lambda,
+        args: &[Expr],
+    ) -> Result<EvalResult, EvalError> {
+        if self.depth >= MAX_LAMBDA_DEPTH {
+            return Err(EvalError::InvalidOperation(format!(
+                "{} calls itself more than {} times",
+                name, MAX_LAMBDA_DEPTH
+            )));
+        }
+
+        let mut bindings = HashMap::new();
+        for (param, arg) in lambda.params.iter().zip(args) {
+            let value = self.eval(arg)?;
+            self.check_parameter_unit(name, param, &value)?;
+            bindings.insert(param.name.clone(), value);
+        }
+
+        Evaluator {
+            library: self.library,
+            context: self.context,
+            functions: self.functions,
+            bindings,
+            depth: self.depth + 1,
+            random: self.random.clone(),
+            left_operand_units: self.left_operand_units,
+            resolved: self.resolved.clone(),
+        }
+        .eval(&lambda.body)
+    }
+
+    /// Check an argument against its parameter's unit; every value of an array must match
+    fn check_parameter_unit(
+        &self,
+        name: &str,
+        param: &LambdaParam,
+        value: &EvalResult,
+    ) -> Result<(), EvalError> {
+        let Some(unit) = &param.unit else {
+            return Ok(());
+        };
+        let expected =
+            parse_unit(unit, self.library).map_err(|_| EvalError::UnknownUnit(unit.clone()))?;
+
+        let values: Vec<&EvalResult> = match &value.value {
+            EvalValue::Array(array) => array.iter().flatten().collect(),
+            _ => vec![value],
+        };
+        for value in values {
+            if !value.is_number() {
+                return Err(EvalError::InvalidOperation(format!(
+                    "{} expects a quantity in {} for {}",
+                    name, unit, param.name
+                )));
+            }
+            if !value.unit.is_compatible(&expected) {
+                return Err(EvalError::IncompatibleUnits {
+                    operation: "convert".to_string(),
+                    left: value.unit.to_string(),
+                    right: format!("{} (the unit of {}'s {} parameter)", unit, name, param.name),
+                });
+            }
+        }
+        Ok(())
+    }
+
+    /// The unit library used for conversions
+    pub fn library(&self) -> &'a UnitLibrary {
+        self.library
+    }
+
+    /// The context references are resolved through
+    pub fn context(&self) -> &'a dyn EvalContext {
+        self.context
+    }
+
+    /// Evaluate an expression
     pub fn eval(&self, expr: &Expr) -> Result<EvalResult, EvalError> {
         match expr {
             Expr::Number(n) => Ok(EvalResult::new(*n, Unit::dimensionless())),
@@ -125,182 +525,478 @@ impl<'a> Evaluator<'a> {
                 // Parse the unit (supports both simple and compound units)
                 let unit_obj = parse_unit(unit, self.library)
                     .map_err(|_| EvalError::UnknownUnit(unit.clone()))?;
-                Ok(EvalResult::new(*value, unit_obj))
+                // Percentages are fractions, as when typed into a cell (6 %/year is 0.06 a year)
+                let value = if unit.starts_with('%') {
+                    value / 100.0
+                } else {
+                    *value
+                };
+                Ok(EvalResult::new(value, unit_obj))
             }
 
             Expr::String(s) => Ok(EvalResult::text(s.clone())),
 
+            Expr::Concat(left, right) => self.eval_concat(left, right),
             Expr::Add(left, right) => self.eval_add(left, right),
             Expr::Subtract(left, right) => self.eval_subtract(left, right),
             Expr::Multiply(left, right) => self.eval_multiply(left, right),
             Expr::Divide(left, right) => self.eval_divide(left, right),
+            Expr::Power(base, exponent) => self.broadcast(
+                self.eval_operand(base)?,
+                self.eval_operand(exponent)?,
+                "raise",
+                |base, exponent| {
+                    raise_to_power(
+                        &base.clone().coerce_number(),
+                        &exponent.clone().coerce_number(),
+                        self.library,
+                    )
+                },
+            ),
 
             Expr::Negate(expr) => {
-                let result = self.eval(expr)?;
+                let result = self.eval_operand(expr)?;
                 match result.value {
-                    EvalValue::Number(n) => Ok(EvalResult::new(-n, result.unit)),
-                    EvalValue::Text(_) => Err(EvalError::InvalidOperation(
-                        "Cannot negate a text value".to_string(),
+                    EvalValue::Array(values) => Ok(EvalResult::array(
+                        values
+                            .into_iter()
+                            .map(|row| row.into_iter().map(|v| negate(v.coerce_number())).collect())
+                            .collect::<Result<_, _>>()?,
                     )),
+                    _ => negate(result),
                 }
             }
 
-            Expr::CellRef { .. } => Err(EvalError::CellNotFound(
-                "Cell references not supported in standalone evaluation".to_string(),
-            )),
+            Expr::CellRef { col, row, .. } => {
+                self.context.cell(None, &CellAddr::new(col.clone(), *row))
+            }
 
-            Expr::NamedRef { name } => Err(EvalError::NamedRefNotFound(format!(
-                "Named reference '{}' not supported in standalone evaluation",
-                name
-            ))),
+            Expr::SheetRef { sheet, reference } => match reference.as_ref() {
+                Expr::CellRef { col, row, .. } => self
+                    .context
+                    .cell(Some(sheet), &CellAddr::new(col.clone(), *row)),
+                _ => self.eval_reference(Some(sheet), reference),
+            },
 
-            Expr::Range { .. } => Err(EvalError::InvalidOperation(
-                "Ranges can only be used in functions".to_string(),
-            )),
+            Expr::NamedRef { name } => match self.bindings.get(name) {
+                Some(value) => Ok(value.clone()),
+                None => self.context.name(name),
+            },
 
-            Expr::Function { name, args } => self.eval_function(name, args),
+            Expr::Range { .. }
+            | Expr::ColumnRange { .. }
+            | Expr::RowRange { .. }
+            | Expr::SpillRef(_) => self.eval_reference(None, expr),
 
-            Expr::Boolean(b) => Ok(EvalResult::new(
-                if *b { 1.0 } else { 0.0 },
-                Unit::dimensionless(),
-            )),
+            Expr::Function { name, args } => self.functions.call(self, name, args),
+
+            Expr::Boolean(b) => Ok(EvalResult::boolean(*b)),
 
-            Expr::GreaterThan(_left, _right)
-            | Expr::LessThan(_left, _right)
-            | Expr::GreaterOrEqual(_left, _right)
-            | Expr::LessOrEqual(_left, _right)
-            | Expr::Equal(_left, _right)
-            | Expr::NotEqual(_left, _right) => Err(EvalError::InvalidOperation(
-                "Comparison operators not supported in standalone evaluation".to_string(),
-            )),
+            Expr::Error(kind) => Err(EvalError::ErrorValue((*kind).into())),
 
-            Expr::And(_left, _right) | Expr::Or(_left, _right) => Err(EvalError::InvalidOperation(
-                "Logical operators not supported in standalone evaluation".to_string(),
-            )),
+            Expr::GreaterThan(left, right) => self.eval_comparison(left, right, Ordering::is_gt),
+            Expr::LessThan(left, right) => self.eval_comparison(left, right, Ordering::is_lt),
+            Expr::GreaterOrEqual(left, right) => self.eval_comparison(left, right, Ordering::is_ge),
+            Expr::LessOrEqual(left, right) => self.eval_comparison(left, right, Ordering::is_le),
+            Expr::Equal(left, right) => self.eval_comparison(left, right, Ordering::is_eq),
+            Expr::NotEqual(left, right) => self.eval_comparison(left, right, Ordering::is_ne),
 
-            Expr::Not(_expr) => Err(EvalError::InvalidOperation(
-                "NOT operator not supported in standalone evaluation".to_string(),
-            )),
+            Expr::And(left, right) => {
+                let result = self.eval_truth(left, "AND")? && self.eval_truth(right, "AND")?;
+                Ok(Self::boolean(result))
+            }
+            Expr::Or(left, right) => {
+                let result = self.eval_truth(left, "OR")? || self.eval_truth(right, "OR")?;
+                Ok(Self::boolean(result))
+            }
+            Expr::Not(expr) => Ok(Self::boolean(!self.eval_truth(expr, "NOT")?)),
         }
     }
 
-    /// Add two values (requires compatible units) or concatenate strings
-    /// Uses finer unit alignment: converts both operands to the finer unit scale
-    fn eval_add(&self, left: &Expr, right: &Expr) -> Result<EvalResult, EvalError> {
-        let left_result = self.eval(left)?;
-        let right_result = self.eval(right)?;
+    /// Evaluate function arguments, expanding ranges (including ones from INDIRECT and OFFSET)
+    /// into the values they contain
+    /// Booleans given directly count as 1 and 0, while booleans in referenced cells are skipped.
+    /// Arrays (A1:A3 * B1:B3, SORT(...)) give their numbers and dates, like ranges
+    pub fn collect_values(&self, args: &[Expr]) -> Result<Vec<EvalResult>, EvalError> {
+        let mut values = Vec::new();
 
-        // If either operand is text, perform string concatenation
-        if left_result.is_text() || right_result.is_text() {
-            let left_str = match &left_result.value {
-                EvalValue::Text(s) => s.clone(),
-                EvalValue::Number(n) => {
-                    if left_result.unit.is_dimensionless() {
-                        n.to_string()
-                    } else {
-                        format!("{} {}", n, left_result.unit)
-                    }
-                }
+        for arg in args {
+            let resolved = self.functions.resolve_reference(self, arg).transpose()?;
+            let arg = resolved.as_ref().unwrap_or(arg);
+            let (sheet, reference) = match arg {
+                Expr::SheetRef { sheet, reference } => (Some(sheet.as_str()), reference.as_ref()),
+                _ => (None, arg),
             };
-            let right_str = match &right_result.value {
-                EvalValue::Text(s) => s.clone(),
-                EvalValue::Number(n) => {
-                    if right_result.unit.is_dimensionless() {
-                        n.to_string()
-                    } else {
-                        format!("{} {}", n, right_result.unit)
-                    }
-                }
-            };
-            return Ok(EvalResult::text(format!("{}{}", left_str, right_str)));
-        }
 
-        // Both are numbers - proceed with numeric addition
-        let left_value = left_result.as_number().unwrap();
-        let right_value = right_result.as_number().unwrap();
+            if let Some(range) = self.referenced_range(sheet, reference) {
+                values.extend(self.context.range(sheet, &range?)?);
+                continue;
+            }
 
-        // Both dimensionless - simple addition
-        if left_result.unit.is_dimensionless() && right_result.unit.is_dimensionless() {
-            return Ok(EvalResult::new(
-                left_value + right_value,
-                Unit::dimensionless(),
-            ));
+            let result = self.eval(arg)?;
+            match result.value {
+                EvalValue::Array(array) => values.extend(
+                    array
+                        .into_iter()
+                        .flatten()
+                        .filter(|value| value.is_number() || value.is_datetime()),
+                ),
+                // As in a range, a boolean in a referenced cell is not a number (SUM(B1, 1))
+                EvalValue::Boolean(_) if matches!(reference, Expr::CellRef { .. }) => {}
+                _ => values.push(result.coerce_number()),
+            }
         }
 
-        // Check if units are compatible
-        if !left_result.unit.is_compatible(&right_result.unit) {
-            return Err(EvalError::IncompatibleUnits {
-                operation: "add".to_string(),
-                left: left_result.unit.to_string(),
-                right: right_result.unit.to_string(),
-            });
-        }
+        Ok(values)
+    }
 
-        // If units are exactly the same, just add
-        if left_result.unit.is_equal(&right_result.unit) {
-            return Ok(EvalResult::new(
-                left_value + right_value,
-                left_result.unit.clone(),
-            ));
+    /// Evaluate a function argument as a block of cells
+    /// A range (including one from INDIRECT or OFFSET) gives all of its cells and an array all
+    /// of its values; a cell reference or any other value is a single cell
+    pub fn eval_grid(&self, arg: &Expr) -> Result<CellGrid, EvalError> {
+        let resolved = self.functions.resolve_reference(self, arg).transpose()?;
+        let arg = resolved.as_ref().unwrap_or(arg);
+        let (sheet, reference) = match arg {
+            Expr::SheetRef { sheet, reference } => (Some(sheet.as_str()), reference.as_ref()),
+            _ => (None, arg),
+        };
+
+        if let Some(range) = self.referenced_range(sheet, reference) {
+            return self.context.grid(sheet, &range?);
+        }
+        match reference {
+            Expr::CellRef { col, row, .. } => {
+                let addr = CellAddr::new(col.clone(), *row);
+                let range = CellRange::new(&addr, &addr);
+                self.context.grid(sheet, &range)
+            }
+            _ => match self.eval(arg) {
+                Ok(result) => Ok(result
+                    .into_array()
+                    .into_iter()
+                    .map(|row| row.into_iter().map(|value| Some(Ok(value))).collect())
+                    .collect()),
+                Err(e) => Ok(vec![vec![Some(Err(e))]]),
+            },
+        }
+    }
+
+    /// Evaluate an expression as an array; a single value is a 1x1 array
+    pub fn eval_array(&self, expr: &Expr) -> Result<Array, EvalError> {
+        Ok(self.eval(expr)?.into_array())
+    }
+
+    /// The cells a range or spill reference (A1#) covers, or None for other expressions
+    fn referenced_range(
+        &self,
+        sheet: Option<&str>,
+        reference: &Expr,
+    ) -> Option<Result<CellRange, EvalError>> {
+        match reference {
+            Expr::Range { .. } | Expr::ColumnRange { .. } | Expr::RowRange { .. } => {
+                Some(CellRange::from_expr(reference).ok_or_else(|| {
+                    EvalError::InvalidOperation("Range must use cell references".to_string())
+                }))
+            }
+            Expr::SpillRef(anchor) => Some(match anchor.as_ref() {
+                Expr::CellRef { col, row, .. } => {
+                    self.context.spill(sheet, &CellAddr::new(col.clone(), *row))
+                }
+                _ => Err(EvalError::InvalidOperation(
+                    "A spilled range must start at a cell reference".to_string(),
+                )),
+            }),
+            _ => None,
         }
+    }
 
-        // Units are compatible but different - determine finer unit and convert both
-        let left_canonical = left_result.unit.canonical();
-        let right_canonical = right_result.unit.canonical();
+    /// The values of a range or spill reference as an array
+    /// Blank cells are 0; an error in any cell is the result
+    fn eval_reference(
+        &self,
+        sheet: Option<&str>,
+        reference: &Expr,
+    ) -> Result<EvalResult, EvalError> {
+        let range = self.referenced_range(sheet, reference).unwrap_or_else(|| {
+            Err(EvalError::InvalidOperation(
+                "Expected a cell range".to_string(),
+            ))
+        })?;
+        let values = self
+            .context
+            .grid(sheet, &range)?
+            .into_iter()
+            .map(|row| {
+                row.into_iter()
+                    .map(|cell| {
+                        cell.unwrap_or_else(|| Ok(EvalResult::new(0.0, Unit::dimensionless())))
+                    })
+                    .collect()
+            })
+            .collect::<Result<_, _>>()?;
+        Ok(EvalResult::array(values))
+    }
+
+    /// Apply an operation to two values, or element by element when either is an array
+    /// Arrays must have the same shape, or one of the two is a single value
+    pub fn broadcast<F>(
+        &self,
+        left: EvalResult,
+        right: EvalResult,
+        operation: &str,
+        apply: F,
+    ) -> Result<EvalResult, EvalError>
+    where
+        F: Fn(&EvalResult, &EvalResult) -> Result<EvalResult, EvalError>,
+    {
+        if !left.is_array() && !right.is_array() {
+            return apply(&left, &right);
+        }
+
+        let (left, right) = (left.into_array(), right.into_array());
+        let shape = |array: &Array| (array.len(), array.first().map_or(0, Vec::len));
+        let zip_rows = |l: &[EvalResult], r: &[EvalResult]| -> Result<Vec<EvalResult>, EvalError> {
+            l.iter().zip(r).map(|(l, r)| apply(l, r)).collect()
+        };
 
-        // Find finer unit
-        let finer_unit = self
-            .library
-            .get_finer_unit(left_canonical, right_canonical)
-            .ok_or_else(|| EvalError::IncompatibleUnits {
-                operation: "add".to_string(),
-                left: left_result.unit.to_string(),
-                right: right_result.unit.to_string(),
-            })?;
-
-        // Convert both values to finer unit
-        let left_converted = if left_canonical == finer_unit {
-            left_value
-        } else {
-            self.library
-                .convert(left_value, left_canonical, finer_unit)
-                .ok_or_else(|| EvalError::IncompatibleUnits {
-                    operation: "add".to_string(),
-                    left: left_result.unit.to_string(),
-                    right: right_result.unit.to_string(),
-                })?
+        let values = match (shape(&left), shape(&right)) {
+            (l, r) if l == r => left
+                .iter()
+                .zip(&right)
+                .map(|(l, r)| zip_rows(l, r))
+                .collect::<Result<_, _>>()?,
+            ((1, 1), _) => right
+                .iter()
+                .map(|row| row.iter().map(|r| apply(&left[0][0], r)).collect())
+                .collect::<Result<_, _>>()?,
+            (_, (1, 1)) => left
+                .iter()
+                .map(|row| row.iter().map(|l| apply(l, &right[0][0])).collect())
+                .collect::<Result<_, _>>()?,
+            ((lr, lc), (rr, rc)) => {
+                return Err(EvalError::InvalidOperation(format!(
+                    "Cannot {} a {}x{} array and a {}x{} array",
+                    operation, lr, lc, rr, rc
+                )))
+            }
         };
+        Ok(EvalResult::array(values))
+    }
 
-        let right_converted = if right_canonical == finer_unit {
-            right_value
-        } else {
-            self.library
-                .convert(right_value, right_canonical, finer_unit)
-                .ok_or_else(|| EvalError::IncompatibleUnits {
-                    operation: "add".to_string(),
-                    left: left_result.unit.to_string(),
-                    right: right_result.unit.to_string(),
+    /// The result of a comparison or logical operation
+    pub fn boolean(value: bool) -> EvalResult {
+        EvalResult::boolean(value)
+    }
+
+    /// Evaluate an operand of an arithmetic operator; booleans count as 1 and 0
+    pub fn eval_operand(&self, expr: &Expr) -> Result<EvalResult, EvalError> {
+        self.eval(expr).map(EvalResult::coerce_number)
+    }
+
+    /// Evaluate an operand of a logical operator; any non-zero number is true
+    pub fn eval_truth(&self, expr: &Expr, operation: &str) -> Result<bool, EvalError> {
+        let result = self.eval(expr)?;
+        match result.value {
+            EvalValue::Boolean(b) => Ok(b),
+            EvalValue::Number(n) => Ok(n != 0.0),
+            _ => Err(EvalError::InvalidOperation(format!(
+                "Cannot use {} with text values",
+                operation
+            ))),
+        }
+    }
+
+    /// Evaluate a comparison, e.g. A1 > 5 or B2 = "yes"
+    /// Values of different kinds are never equal, so TRUE = 1 is FALSE and TRUE <> 1 is TRUE,
+    /// but they have no order, so TRUE > 1 is an error
+    pub fn eval_comparison<F>(
+        &self,
+        left: &Expr,
+        right: &Expr,
+        compare: F,
+    ) -> Result<EvalResult, EvalError>
+    where
+        F: Fn(Ordering) -> bool,
+    {
+        // = and <> are the comparisons that do not care which side is larger
+        let equality = compare(Ordering::Less) == compare(Ordering::Greater);
+        self.broadcast(self.eval(left)?, self.eval(right)?, "compare", |l, r| {
+            if equality && std::mem::discriminant(&l.value) != std::mem::discriminant(&r.value) {
+                return Ok(Self::boolean(compare(Ordering::Less)));
+            }
+            Ok(Self::boolean(compare(self.compare_values(l, r)?)))
+        })
+    }
+
+    /// Order two values
+    /// Text compares with text (case-insensitively), dates with dates, booleans with booleans
+    /// (FALSE before TRUE), numbers compare after
+    /// converting the right operand to the left's unit, and a bare number compares with any unit
+    pub fn compare_values(
+        &self,
+        left_result: &EvalResult,
+        right_result: &EvalResult,
+    ) -> Result<Ordering, EvalError> {
+        let ordering = match (&left_result.value, &right_result.value) {
+            (EvalValue::Text(l), EvalValue::Text(r)) => l.to_lowercase().cmp(&r.to_lowercase()),
+            (EvalValue::DateTime(l), EvalValue::DateTime(r)) => l.cmp(r),
+            (EvalValue::Boolean(l), EvalValue::Boolean(r)) => l.cmp(r),
+            (EvalValue::Number(l), EvalValue::Number(r)) => {
+                let right_converted = if left_result.unit.is_equal(&right_result.unit)
+                    || left_result.unit.is_dimensionless()
+                    || right_result.unit.is_dimensionless()
+                {
+                    *r
+                } else if left_result.unit.is_compatible(&right_result.unit) {
+                    self.library
+                        .convert(
+                            *r,
+                            right_result.unit.canonical(),
+                            left_result.unit.canonical(),
+                        )
+                        .ok_or_else(|| EvalError::IncompatibleUnits {
+                            operation: "compare".to_string(),
+                            left: left_result.unit.to_string(),
+                            right: right_result.unit.to_string(),
+                        })?
+                } else {
+                    return Err(EvalError::IncompatibleUnits {
+                        operation: "compare".to_string(),
+                        left: left_result.unit.to_string(),
+                        right: right_result.unit.to_string(),
+                    });
+                };
+
+                l.partial_cmp(&right_converted).ok_or_else(|| {
+                    EvalError::InvalidOperation("Cannot compare NaN values".to_string())
                 })?
+            }
+            (EvalValue::DateTime(_), _) | (_, EvalValue::DateTime(_)) => {
+                return Err(EvalError::InvalidOperation(
+                    "Dates can only be compared with dates".to_string(),
+                ))
+            }
+            (EvalValue::Boolean(_), _) | (_, EvalValue::Boolean(_)) => {
+                return Err(EvalError::InvalidOperation(
+                    "Booleans can only be compared with booleans".to_string(),
+                ))
+            }
+            _ => {
+                return Err(EvalError::InvalidOperation(
+                    "Cannot compare text with a number".to_string(),
+                ))
+            }
         };
 
-        // Get the finer unit object
-        let result_unit = self
-            .library
-            .get(finer_unit)
-            .cloned()
-            .unwrap_or_else(|| left_result.unit.clone());
+        Ok(ordering)
+    }
 
-        Ok(EvalResult::new(
-            left_converted + right_converted,
-            result_unit,
-        ))
+    /// Join two values as text (a & b); numbers are written with their unit
+    fn eval_concat(&self, left: &Expr, right: &Expr) -> Result<EvalResult, EvalError> {
+        self.broadcast(self.eval(left)?, self.eval(right)?, "join", |l, r| {
+            Ok(EvalResult::text(format!(
+                "{}{}",
+                display_string(l),
+                display_string(r)
+            )))
+        })
+    }
+
+    fn eval_add(&self, left: &Expr, right: &Expr) -> Result<EvalResult, EvalError> {
+        self.broadcast(self.eval(left)?, self.eval(right)?, "add", |l, r| {
+            let result = self.add_values(l, r)?;
+            let dx = self.unit_scale(&l.unit, &result.unit);
+            let dy = self.unit_scale(&r.unit, &result.unit);
+            let result = self.propagate_uncertainty(result, l, r, |_, _| Ok((dx, dy)))?;
+            Ok(sum_precision(result, l, r, dx, dy))
+        })
+    }
+
+    /// Add two values (requires compatible units) or concatenate strings
+    pub fn add_values(
+        &self,
+        left_result: &EvalResult,
+        right_result: &EvalResult,
+    ) -> Result<EvalResult, EvalError> {
+        // If either operand is text, perform string concatenation
+        if left_result.is_text() || right_result.is_text() {
+            let left_str = display_string(left_result);
+            let right_str = display_string(right_result);
+            return Ok(EvalResult::text(format!("{}{}", left_str, right_str)));
+        }
+        let (left_result, right_result) = (
+            left_result.clone().coerce_number(),
+            right_result.clone().coerce_number(),
+        );
+
+        // A date plus a duration (in either order) is a date
+        match (&left_result.value, &right_result.value) {
+            (EvalValue::DateTime(_), EvalValue::DateTime(_)) => {
+                return Err(EvalError::InvalidOperation(
+                    "Cannot add two dates".to_string(),
+                ))
+            }
+            (EvalValue::DateTime(date), EvalValue::Number(n)) => {
+                return self.shift_date(date, *n, &right_result.unit, "add")
+            }
+            (EvalValue::Number(n), EvalValue::DateTime(date)) => {
+                return self.shift_date(date, *n, &left_result.unit, "add")
+            }
+            _ => {}
+        }
+
+        // Both are numbers - proceed with numeric addition
+        let (left_value, right_value) = match (&left_result.value, &right_result.value) {
+            (EvalValue::Number(left), EvalValue::Number(right)) => (*left, *right),
+            _ => {
+                return Err(EvalError::InvalidOperation(
+                    "Cannot add these values".to_string(),
+                ))
+            }
+        };
+        self.combine_numbers(
+            (left_value, &left_result.unit),
+            (right_value, &right_result.unit),
+            "add",
+            |a, b| a + b,
+        )
     }
 
-    /// Subtract two values (requires compatible units)
-    /// Uses finer unit alignment: converts both operands to the finer unit scale
     fn eval_subtract(&self, left: &Expr, right: &Expr) -> Result<EvalResult, EvalError> {
-        let left_result = self.eval(left)?;
-        let right_result = self.eval(right)?;
+        self.broadcast(self.eval(left)?, self.eval(right)?, "subtract", |l, r| {
+            let result =
+                self.subtract_values(&l.clone().coerce_number(), &r.clone().coerce_number())?;
+            let dx = self.unit_scale(&l.unit, &result.unit);
+            let dy = -self.unit_scale(&r.unit, &result.unit);
+            let result = self.propagate_uncertainty(result, l, r, |_, _| Ok((dx, dy)))?;
+            Ok(sum_precision(result, l, r, dx, dy))
+        })
+    }
+
+    /// Subtract two values (requires compatible units)
+    pub fn subtract_values(
+        &self,
+        left_result: &EvalResult,
+        right_result: &EvalResult,
+    ) -> Result<EvalResult, EvalError> {
+        // The difference of two dates is a duration in days; a date minus a duration is a date
+        match (&left_result.value, &right_result.value) {
+            (EvalValue::DateTime(end), EvalValue::DateTime(start)) => {
+                return Ok(EvalResult::new(
+                    datetime::days_between(start, end),
+                    self.time_unit("day"),
+                ))
+            }
+            (EvalValue::DateTime(date), EvalValue::Number(n)) => {
+                return self.shift_date(date, -n, &right_result.unit, "subtract")
+            }
+            (_, EvalValue::DateTime(_)) => {
+                return Err(EvalError::InvalidOperation(
+                    "Cannot subtract a date from a number".to_string(),
+                ))
+            }
+            _ => {}
+        }
 
         // Subtraction requires both operands to be numbers
         let left_value = left_result.as_number().ok_or_else(|| {
@@ -310,88 +1006,213 @@ impl<'a> Evaluator<'a> {
             EvalError::InvalidOperation("Cannot subtract with text values".to_string())
         })?;
 
-        // Both dimensionless - simple subtraction
-        if left_result.unit.is_dimensionless() && right_result.unit.is_dimensionless() {
+        self.combine_numbers(
+            (left_value, &left_result.unit),
+            (right_value, &right_result.unit),
+            "subtract",
+            |a, b| a - b,
+        )
+    }
+
+    /// Add or subtract two numbers (requires compatible units)
+    /// The result is in the left operand's unit for cell formulas (see
+    /// `with_left_operand_units`); otherwise both operands are converted to the finer unit
+    fn combine_numbers(
+        &self,
+        (left_value, left_unit): (f64, &Unit),
+        (right_value, right_unit): (f64, &Unit),
+        operation: &str,
+        op: impl Fn(f64, f64) -> f64,
+    ) -> Result<EvalResult, EvalError> {
+        // Both dimensionless - simple operation
+        if left_unit.is_dimensionless() && right_unit.is_dimensionless() {
             return Ok(EvalResult::new(
-                left_value - right_value,
+                op(left_value, right_value),
                 Unit::dimensionless(),
             ));
         }
 
+        let incompatible = || EvalError::IncompatibleUnits {
+            operation: operation.to_string(),
+            left: left_unit.to_string(),
+            right: right_unit.to_string(),
+        };
+
         // Check if units are compatible
-        if !left_result.unit.is_compatible(&right_result.unit) {
-            return Err(EvalError::IncompatibleUnits {
-                operation: "subtract".to_string(),
-                left: left_result.unit.to_string(),
-                right: right_result.unit.to_string(),
-            });
+        if !left_unit.is_compatible(right_unit) {
+            return Err(incompatible());
         }
 
-        // If units are exactly the same, just subtract
-        if left_result.unit.is_equal(&right_result.unit) {
+        // If units are exactly the same, just operate
+        if left_unit.is_equal(right_unit) {
             return Ok(EvalResult::new(
-                left_value - right_value,
-                left_result.unit.clone(),
+                op(left_value, right_value),
+                left_unit.clone(),
             ));
         }
 
-        // Units are compatible but different - determine finer unit and convert both
-        let left_canonical = left_result.unit.canonical();
-        let right_canonical = right_result.unit.canonical();
+        let left_canonical = left_unit.canonical();
+        let right_canonical = right_unit.canonical();
 
-        // Find finer unit
+        // Units are compatible but different - convert right to left's unit
+        if self.left_operand_units {
+            let right_converted = self
+                .library
+                .convert(right_value, right_canonical, left_canonical)
+                .ok_or_else(incompatible)?;
+            return Ok(EvalResult::new(
+                op(left_value, right_converted),
+                left_unit.clone(),
+            ));
+        }
+
+        // Or determine the finer unit and convert both
         let finer_unit = self
             .library
             .get_finer_unit(left_canonical, right_canonical)
-            .ok_or_else(|| EvalError::IncompatibleUnits {
-                operation: "subtract".to_string(),
-                left: left_result.unit.to_string(),
-                right: right_result.unit.to_string(),
-            })?;
-
-        // Convert both values to finer unit
-        let left_converted = if left_canonical == finer_unit {
-            left_value
-        } else {
-            self.library
-                .convert(left_value, left_canonical, finer_unit)
-                .ok_or_else(|| EvalError::IncompatibleUnits {
-                    operation: "subtract".to_string(),
-                    left: left_result.unit.to_string(),
-                    right: right_result.unit.to_string(),
-                })?
-        };
-
-        let right_converted = if right_canonical == finer_unit {
-            right_value
-        } else {
-            self.library
-                .convert(right_value, right_canonical, finer_unit)
-                .ok_or_else(|| EvalError::IncompatibleUnits {
-                    operation: "subtract".to_string(),
-                    left: left_result.unit.to_string(),
-                    right: right_result.unit.to_string(),
-                })?
+            .ok_or_else(incompatible)?;
+        let convert = |value: f64, canonical: &str| {
+            if canonical == finer_unit {
+                Ok(value)
+            } else {
+                self.library
+                    .convert(value, canonical, finer_unit)
+                    .ok_or_else(incompatible)
+            }
         };
+        let left_converted = convert(left_value, left_canonical)?;
+        let right_converted = convert(right_value, right_canonical)?;
 
         // Get the finer unit object
         let result_unit = self
             .library
             .get(finer_unit)
             .cloned()
-            .unwrap_or_else(|| left_result.unit.clone());
+            .unwrap_or_else(|| left_unit.clone());
 
         Ok(EvalResult::new(
-            left_converted - right_converted,
+            op(left_converted, right_converted),
             result_unit,
         ))
     }
 
-    /// Multiply two values (creates compound units with symbol-aware cancellation and conversion)
+    /// Move a date by a duration
+    /// Whole months, quarters and years follow the calendar (Jan 31 + 1 month is the end of
+    /// February); other durations are converted to an exact length, and bare numbers are days
+    pub fn shift_date(
+        &self,
+        date: &NaiveDateTime,
+        amount: f64,
+        unit: &Unit,
+        operation: &str,
+    ) -> Result<EvalResult, EvalError> {
+        let out_of_range = || EvalError::InvalidOperation("Date out of range".to_string());
+
+        let shifted = if unit.is_dimensionless() {
+            datetime::add_seconds(date, amount * 86_400.0)
+        } else if *unit.dimension() != Dimension::Simple(BaseDimension::Time) {
+            return Err(EvalError::IncompatibleUnits {
+                operation: operation.to_string(),
+                left: "date".to_string(),
+                right: unit.to_string(),
+            });
+        } else if let Some(months) = datetime::calendar_months(unit.canonical())
+            .filter(|_| amount.fract() == 0.0 && amount.abs() < i32::MAX as f64)
+        {
+            datetime::add_months(date, amount as i32 * months as i32)
+        } else {
+            let seconds = self
+                .library
+                .convert(amount, unit.canonical(), "s")
+                .ok_or_else(|| EvalError::UnknownUnit(unit.to_string()))?;
+            datetime::add_seconds(date, seconds)
+        };
+
+        shifted.map(EvalResult::datetime).ok_or_else(out_of_range)
+    }
+
+    /// A time unit from the library, such as the day unit of date differences
+    pub fn time_unit(&self, symbol: &str) -> Unit {
+        self.library
+            .get(symbol)
+            .cloned()
+            .unwrap_or_else(|| Unit::simple(symbol, BaseDimension::Time))
+    }
+
     fn eval_multiply(&self, left: &Expr, right: &Expr) -> Result<EvalResult, EvalError> {
-        let left_result = self.eval(left)?;
-        let right_result = self.eval(right)?;
+        self.broadcast(self.eval(left)?, self.eval(right)?, "multiply", |l, r| {
+            let result =
+                self.multiply_values(&l.clone().coerce_number(), &r.clone().coerce_number())?;
+            // The result is k * x * y, where k converts the product of the units to the result's
+            let derivatives = |x: f64, y: f64| {
+                let k = self.multiply_values(&unit_value(l), &unit_value(r))?;
+                let k = k.numeric_value();
+                Ok((k * y, k * x))
+            };
+            let result = self.propagate_uncertainty(result, l, r, derivatives)?;
+            Ok(product_precision(result, l, r))
+        })
+    }
+
+    fn eval_divide(&self, left: &Expr, right: &Expr) -> Result<EvalResult, EvalError> {
+        self.broadcast(self.eval(left)?, self.eval(right)?, "divide", |l, r| {
+            let result =
+                self.divide_values(&l.clone().coerce_number(), &r.clone().coerce_number())?;
+            // The result is k * x / y, where k converts the quotient of the units to the result's
+            let derivatives = |x: f64, y: f64| {
+                let k = self.divide_values(&unit_value(l), &unit_value(r))?;
+                let k = k.numeric_value();
+                Ok((k / y, -k * x / (y * y)))
+            };
+            let result = self.propagate_uncertainty(result, l, r, derivatives)?;
+            Ok(product_precision(result, l, r))
+        })
+    }
+
+    /// Give the result of an operation on two values the uncertainty of its operands, by
+    /// first-order propagation: `derivatives` gives the change in the result for a change
+    /// of 1 in each operand's value (x, y). The operands are taken to be independent
+    fn propagate_uncertainty<F>(
+        &self,
+        result: EvalResult,
+        left: &EvalResult,
+        right: &EvalResult,
+        derivatives: F,
+    ) -> Result<EvalResult, EvalError>
+    where
+        F: FnOnce(f64, f64) -> Result<(f64, f64), EvalError>,
+    {
+        if !result.is_number() || (left.uncertainty.is_none() && right.uncertainty.is_none()) {
+            return Ok(result);
+        }
+        let (dx, dy) = derivatives(left.numeric_value(), right.numeric_value())?;
+        let uncertainty = combine_uncertainties(&[(dx, left.uncertainty), (dy, right.uncertainty)]);
+        Ok(result.with_uncertainty(uncertainty))
+    }
+
+    /// The size in one unit of a difference of 1 in another, for converting uncertainties and
+    /// decimal places
+    /// Differences convert without the offset between units (a change of 1 °C is 1.8 °F)
+    pub fn unit_scale(&self, from: &Unit, to: &Unit) -> f64 {
+        if from.is_equal(to) || from.is_dimensionless() || to.is_dimensionless() {
+            return 1.0;
+        }
+        let convert = |value| {
+            self.library
+                .convert(value, from.canonical(), to.canonical())
+        };
+        match (convert(1.0), convert(0.0)) {
+            (Some(one), Some(zero)) => one - zero,
+            _ => 1.0,
+        }
+    }
 
+    /// Multiply two values (creates compound units with symbol-aware cancellation and conversion)
+    pub fn multiply_values(
+        &self,
+        left_result: &EvalResult,
+        right_result: &EvalResult,
+    ) -> Result<EvalResult, EvalError> {
         // Multiplication requires both operands to be numbers
         let left_value = left_result.as_number().ok_or_else(|| {
             EvalError::InvalidOperation("Cannot multiply with text values".to_string())
@@ -437,10 +1258,10 @@ impl<'a> Evaluator<'a> {
 
         // Multiply: add right's numerator to left's numerator, right's denominator to left's denominator
         for (symbol, power) in right_num {
-            *left_num.entry(symbol).or_insert(0) += power;
+            *left_num.entry(symbol).or_default() += power;
         }
         for (symbol, power) in right_den {
-            *left_den.entry(symbol).or_insert(0) += power;
+            *left_den.entry(symbol).or_default() += power;
         }
 
         // Cancel symbols and apply conversions
@@ -457,10 +1278,11 @@ impl<'a> Evaluator<'a> {
     }
 
     /// Divide two values (creates compound units with symbol-aware cancellation and conversion)
-    fn eval_divide(&self, left: &Expr, right: &Expr) -> Result<EvalResult, EvalError> {
-        let left_result = self.eval(left)?;
-        let right_result = self.eval(right)?;
-
+    pub fn divide_values(
+        &self,
+        left_result: &EvalResult,
+        right_result: &EvalResult,
+    ) -> Result<EvalResult, EvalError> {
         // Division requires both operands to be numbers
         let left_value = left_result.as_number().ok_or_else(|| {
             EvalError::InvalidOperation("Cannot divide with text values".to_string())
@@ -517,10 +1339,10 @@ impl<'a> Evaluator<'a> {
 
         // Divide: right's numerator goes to left's denominator, right's denominator goes to left's numerator
         for (symbol, power) in right_num {
-            *left_den.entry(symbol).or_insert(0) += power;
+            *left_den.entry(symbol).or_default() += power;
         }
         for (symbol, power) in right_den {
-            *left_num.entry(symbol).or_insert(0) += power;
+            *left_num.entry(symbol).or_default() += power;
         }
 
         // Cancel symbols and apply conversions
@@ -535,96 +1357,91 @@ impl<'a> Evaluator<'a> {
 
         Ok(EvalResult::new(value, result_unit))
     }
+}
 
-    /// Evaluate a function call
-    fn eval_function(&self, name: &str, args: &[Expr]) -> Result<EvalResult, EvalError> {
-        match name.to_uppercase().as_str() {
-            "CEILING" => self.eval_ceiling(args),
-            _ => Err(EvalError::FunctionNotImplemented(name.to_string())),
-        }
-    }
-
-    /// CEILING function: rounds a number up to the nearest integer or to the nearest multiple of significance
-    /// CEILING(number) or CEILING(number, significance)
-    fn eval_ceiling(&self, args: &[Expr]) -> Result<EvalResult, EvalError> {
-        // Validate argument count (1 or 2 arguments)
-        if args.is_empty() || args.len() > 2 {
-            return Err(EvalError::InvalidOperation(format!(
-                "CEILING requires 1 or 2 arguments, got {}",
-                args.len()
-            )));
-        }
+/// 1 in the unit of a value, for finding how an operation converts units
+fn unit_value(result: &EvalResult) -> EvalResult {
+    EvalResult::new(1.0, result.unit.clone())
+}
 
-        // Evaluate the number argument
-        let number_result = self.eval(&args[0])?;
-        let number = number_result.as_number().ok_or_else(|| {
-            EvalError::InvalidOperation("CEILING requires a numeric first argument".to_string())
-        })?;
+/// Combine the uncertainties a result gets from independent operands, each given with the
+/// change in the result for a change of 1 in the operand: the contributions add in quadrature
+/// None if no operand has an uncertainty, or if the result's is not a finite number
+pub fn combine_uncertainties(terms: &[(f64, Option<f64>)]) -> Option<f64> {
+    let variance = terms
+        .iter()
+        .filter_map(|(derivative, uncertainty)| uncertainty.map(|u| (derivative * u).powi(2)))
+        .reduce(|a, b| a + b)?;
+    Some(variance.sqrt()).filter(|uncertainty| uncertainty.is_finite())
+}
 
-        // Get significance (defaults to 1 if not provided)
-        let significance = if args.len() == 2 {
-            let sig_result = self.eval(&args[1])?;
-            let sig_value = sig_result.as_number().ok_or_else(|| {
-                EvalError::InvalidOperation("CEILING significance must be numeric".to_string())
-            })?;
-
-            // Check unit compatibility
-            if !sig_result.unit.is_dimensionless()
-                && !number_result.unit.is_dimensionless()
-                && !sig_result.unit.is_compatible(&number_result.unit)
-            {
-                return Err(EvalError::IncompatibleUnits {
-                    operation: "CEILING".to_string(),
-                    left: number_result.unit.to_string(),
-                    right: sig_result.unit.to_string(),
-                });
-            }
+/// Give a sum or difference the precision of its operands: it is known to the decimal place
+/// of the least precise one. dx and dy convert the operands to the result's unit
+fn sum_precision(
+    result: EvalResult,
+    left: &EvalResult,
+    right: &EvalResult,
+    dx: f64,
+    dy: f64,
+) -> EvalResult {
+    let Some(sum) = result.as_number() else {
+        return result;
+    };
+    let operand = |operand: &EvalResult, scale: f64| {
+        let value = operand.as_number().unwrap_or_default();
+        (value, operand.significant_figures, scale)
+    };
+    let figures = precision::sum_figures(sum, &[operand(left, dx), operand(right, dy)]);
+    result.with_significant_figures(figures)
+}
 
-            // Convert significance to same unit as number if needed
-            let converted_sig = if sig_result.unit.is_dimensionless()
-                || number_result.unit.is_dimensionless()
-                || sig_result.unit.is_equal(&number_result.unit)
-            {
-                sig_value
-            } else {
-                // Convert significance to number's unit
-                self.library
-                    .convert(
-                        sig_value,
-                        sig_result.unit.canonical(),
-                        number_result.unit.canonical(),
-                    )
-                    .ok_or_else(|| EvalError::IncompatibleUnits {
-                        operation: "CEILING".to_string(),
-                        left: number_result.unit.to_string(),
-                        right: sig_result.unit.to_string(),
-                    })?
-            };
+/// Give a product or quotient the significant figures of its least precise operand
+fn product_precision(result: EvalResult, left: &EvalResult, right: &EvalResult) -> EvalResult {
+    if !result.is_number() {
+        return result;
+    }
+    let figures =
+        precision::product_figures(&[left.significant_figures, right.significant_figures]);
+    result.with_significant_figures(figures)
+}
 
-            converted_sig
-        } else {
-            1.0
-        };
+/// Negate a number
+fn negate(result: EvalResult) -> Result<EvalResult, EvalError> {
+    match result.value {
+        EvalValue::Number(n) => Ok(EvalResult::new(-n, result.unit)
+            .with_uncertainty(result.uncertainty)
+            .with_significant_figures(result.significant_figures)),
+        EvalValue::DateTime(_) => Err(EvalError::InvalidOperation(
+            "Cannot negate a date".to_string(),
+        )),
+        _ => Err(EvalError::InvalidOperation(
+            "Cannot negate a text value".to_string(),
+        )),
+    }
+}
 
-        // Check for zero significance
-        if significance == 0.0 {
-            return Err(EvalError::InvalidOperation(
-                "CEILING significance cannot be zero".to_string(),
-            ));
+/// Text form of a value when it is joined onto a string
+pub fn display_string(result: &EvalResult) -> String {
+    match &result.value {
+        EvalValue::Text(s) => s.clone(),
+        EvalValue::DateTime(d) => datetime::format_datetime(d),
+        EvalValue::Boolean(b) => format_boolean(*b).to_string(),
+        EvalValue::Number(n) => {
+            let quantity = |n: f64| match result.unit.is_dimensionless() {
+                true => n.to_string(),
+                false => format!("{} {}", n, result.unit),
+            };
+            match result.uncertainty {
+                Some(uncertainty) => format!("{} ± {}", quantity(*n), quantity(uncertainty)),
+                None => quantity(*n),
+            }
         }
-
-        // Perform ceiling operation
-        let result_value = if significance == 1.0 {
-            // Simple ceiling to nearest integer
-            number.ceil()
-        } else {
-            // Ceiling to nearest multiple of significance
-            // Formula: ceil(number / significance) * significance
-            (number / significance).ceil() * significance
-        };
-
-        // Preserve the unit from the number argument
-        Ok(EvalResult::new(result_value, number_result.unit.clone()))
+        EvalValue::Array(values) => values
+            .iter()
+            .flatten()
+            .map(display_string)
+            .collect::<Vec<_>>()
+            .join(", "),
     }
 }
 
@@ -643,21 +1460,21 @@ pub fn multiply_units_with_cancellation(left: &Unit, right: &Unit) -> Unit {
 
     // Add right's numerator to our numerator
     for (dim, power) in right_num {
-        *num_dims.entry(dim).or_insert(0) += power;
+        *num_dims.entry(dim).or_default() += power;
     }
 
     // Add right's denominator to our denominator
     for (dim, power) in right_den {
-        *den_dims.entry(dim).or_insert(0) += power;
+        *den_dims.entry(dim).or_default() += power;
     }
 
     // Cancel out matching dimensions
     let keys: Vec<_> = num_dims.keys().cloned().collect();
     for dim in keys {
-        let num_power = num_dims.get(&dim).copied().unwrap_or(0);
-        let den_power = den_dims.get(&dim).copied().unwrap_or(0);
+        let num_power = num_dims.get(&dim).copied().unwrap_or_default();
+        let den_power = den_dims.get(&dim).copied().unwrap_or_default();
 
-        if num_power > 0 && den_power > 0 {
+        if num_power.is_positive() && den_power.is_positive() {
             let cancel = num_power.min(den_power);
             let new_num = num_power - cancel;
             let new_den = den_power - cancel;
@@ -692,21 +1509,21 @@ pub fn divide_units_with_cancellation(left: &Unit, right: &Unit) -> Unit {
 
     // When dividing, right's numerator goes to our denominator
     for (dim, power) in right_num {
-        *den_dims.entry(dim).or_insert(0) += power;
+        *den_dims.entry(dim).or_default() += power;
     }
 
     // And right's denominator goes to our numerator
     for (dim, power) in right_den {
-        *num_dims.entry(dim).or_insert(0) += power;
+        *num_dims.entry(dim).or_default() += power;
     }
 
     // Cancel out matching dimensions
     let keys: Vec<_> = num_dims.keys().cloned().collect();
     for dim in keys {
-        let num_power = num_dims.get(&dim).copied().unwrap_or(0);
-        let den_power = den_dims.get(&dim).copied().unwrap_or(0);
+        let num_power = num_dims.get(&dim).copied().unwrap_or_default();
+        let den_power = den_dims.get(&dim).copied().unwrap_or_default();
 
-        if num_power > 0 && den_power > 0 {
+        if num_power.is_positive() && den_power.is_positive() {
             let cancel = num_power.min(den_power);
             let new_num = num_power - cancel;
             let new_den = den_power - cancel;
@@ -735,8 +1552,8 @@ pub fn divide_units_with_cancellation(left: &Unit, right: &Unit) -> Unit {
 
 // Build a unit from dimension maps, preserving original symbols when possible
 fn build_unit_from_dimensions_with_originals(
-    numerator: HashMap<BaseDimension, i32>,
-    denominator: HashMap<BaseDimension, i32>,
+    numerator: HashMap<BaseDimension, Exponent>,
+    denominator: HashMap<BaseDimension, Exponent>,
     left_unit: &Unit,
     right_unit: &Unit,
 ) -> Unit {
@@ -910,8 +1727,8 @@ fn dimension_matches_symbol(dim: &BaseDimension, symbol: &str) -> bool {
 
 // Build unit symbol with original symbols when possible
 fn build_unit_symbol_with_originals(
-    numerator: &HashMap<BaseDimension, i32>,
-    denominator: &HashMap<BaseDimension, i32>,
+    numerator: &HashMap<BaseDimension, Exponent>,
+    denominator: &HashMap<BaseDimension, Exponent>,
     left: &Unit,
     right: &Unit,
 ) -> String {
@@ -977,24 +1794,27 @@ fn build_unit_symbol_with_originals(
 // Extract dimensions from a unit into numerator and denominator maps
 pub fn extract_dimensions(
     unit: &Unit,
-) -> (HashMap<BaseDimension, i32>, HashMap<BaseDimension, i32>) {
+) -> (
+    HashMap<BaseDimension, Exponent>,
+    HashMap<BaseDimension, Exponent>,
+) {
     let mut numerator = HashMap::new();
     let mut denominator = HashMap::new();
 
     match unit.dimension() {
         Dimension::Dimensionless => {}
         Dimension::Simple(base) => {
-            numerator.insert(base.clone(), 1);
+            numerator.insert(base.clone(), Exponent::ONE);
         }
         Dimension::Compound {
             numerator: num,
             denominator: den,
         } => {
             for (base, power) in num {
-                *numerator.entry(base.clone()).or_insert(0) += power;
+                *numerator.entry(base.clone()).or_default() += *power;
             }
             for (base, power) in den {
-                *denominator.entry(base.clone()).or_insert(0) += power;
+                *denominator.entry(base.clone()).or_default() += *power;
             }
         }
     }
@@ -1012,11 +1832,12 @@ pub fn extract_dimensions(
 /// - "$/GB" → ({$: 1}, {GB: 1})
 /// - "GB/Month" → ({GB: 1}, {Month: 1})
 /// - "$/GB·Month" → ({$: 1}, {GB: 1, Month: 1})
-pub fn extract_unit_symbols(unit: &Unit) -> (HashMap<String, i32>, HashMap<String, i32>) {
+pub fn extract_unit_symbols(unit: &Unit) -> (HashMap<String, Exponent>, HashMap<String, Exponent>) {
     let mut numerator = HashMap::new();
     let mut denominator = HashMap::new();
 
-    let canonical = unit.canonical();
+    // Superscript exponents (m²) are read as ^ exponents (m^2)
+    let canonical = normalize_unit_exponents(unit.canonical());
 
     if canonical.is_empty() {
         return (numerator, denominator);
@@ -1034,12 +1855,10 @@ pub fn extract_unit_symbols(unit: &Unit) -> (HashMap<String, i32>, HashMap<Strin
         parse_unit_part(parts[0], &mut numerator);
     }
 
-    // Parse denominator part (if exists)
-    if parts.len() > 1 {
-        // Join remaining parts (in case there are multiple / symbols)
-        let denom_part = parts[1..].join("/");
+    // Parse denominator parts (if any); $/GB/month is $ per GB per month
+    for denom_part in &parts[1..] {
         if !denom_part.is_empty() {
-            parse_unit_part(&denom_part, &mut denominator);
+            parse_unit_part(denom_part, &mut denominator);
         }
     }
 
@@ -1048,7 +1867,7 @@ pub fn extract_unit_symbols(unit: &Unit) -> (HashMap<String, i32>, HashMap<Strin
 
 /// Parse a unit part (numerator or denominator) and extract symbols with powers
 /// Handles multiplication (*) and exponents (^)
-fn parse_unit_part(part: &str, map: &mut HashMap<String, i32>) {
+fn parse_unit_part(part: &str, map: &mut HashMap<String, Exponent>) {
     // Split by multiplication
     for term in part.split('*') {
         let term = term.trim();
@@ -1061,15 +1880,15 @@ fn parse_unit_part(part: &str, map: &mut HashMap<String, i32>) {
             let symbol = term[..caret_pos].trim();
             let power_str = term[caret_pos + 1..].trim();
 
-            if let Ok(power) = power_str.parse::<i32>() {
-                *map.entry(symbol.to_string()).or_insert(0) += power;
+            if let Ok(power) = power_str.parse::<Exponent>() {
+                *map.entry(symbol.to_string()).or_default() += power;
             } else {
                 // Invalid power, treat as power 1
-                *map.entry(symbol.to_string()).or_insert(0) += 1;
+                *map.entry(symbol.to_string()).or_default() += 1;
             }
         } else {
             // No exponent, power is 1
-            *map.entry(term.to_string()).or_insert(0) += 1;
+            *map.entry(term.to_string()).or_default() += 1;
         }
     }
 }
@@ -1082,10 +1901,10 @@ fn parse_unit_part(part: &str, map: &mut HashMap<String, i32>) {
 /// 2. Convert and cancel compatible units (e.g., TB/GB → apply ratio 1024/1)
 /// 3. Apply exponents to conversion ratios (e.g., ft^2/m^2 → (ft→m)^2)
 pub fn cancel_and_convert_units(
-    mut numerator: HashMap<String, i32>,
-    mut denominator: HashMap<String, i32>,
+    mut numerator: HashMap<String, Exponent>,
+    mut denominator: HashMap<String, Exponent>,
     library: &UnitLibrary,
-) -> (HashMap<String, i32>, HashMap<String, i32>, f64) {
+) -> (HashMap<String, Exponent>, HashMap<String, Exponent>, f64) {
     let mut conversion_factor = 1.0;
 
     // Step 1: Cancel exact symbol matches
@@ -1094,7 +1913,7 @@ pub fn cancel_and_convert_units(
         if let Some(&num_power) = numerator.get(&symbol) {
             if let Some(&den_power) = denominator.get(&symbol) {
                 let cancel_power = num_power.min(den_power);
-                if cancel_power > 0 {
+                if cancel_power.is_positive() {
                     let new_num_power = num_power - cancel_power;
                     let new_den_power = den_power - cancel_power;
 
@@ -1128,23 +1947,29 @@ pub fn cancel_and_convert_units(
 
             // Check if units are compatible (can be converted)
             if library.can_convert(num_symbol, den_symbol) && num_symbol != den_symbol {
-                // Get conversion ratio
-                if let Some((ratio_num, ratio_den)) =
-                    library.get_conversion_ratio(num_symbol, den_symbol)
-                {
+                // Get conversion ratio, exact where the library has one (TB/GB), otherwise
+                // the conversion factor (year/month). Conversions with an offset (C/F) have
+                // no ratio, so those units stay as a compound unit
+                let ratio = library
+                    .get_conversion_ratio(num_symbol, den_symbol)
+                    .map(|(ratio_num, ratio_den)| ratio_num as f64 / ratio_den as f64)
+                    .or_else(|| {
+                        let multiplicative =
+                            library.convert(0.0, num_symbol, den_symbol) == Some(0.0);
+                        multiplicative
+                            .then(|| library.convert(1.0, num_symbol, den_symbol))
+                            .flatten()
+                    });
+                if let Some(ratio) = ratio {
                     let num_power = *numerator.get(num_symbol).unwrap();
                     let den_power = *denominator.get(den_symbol).unwrap();
 
                     // Cancel as many as possible
                     let cancel_power = num_power.min(den_power);
 
-                    if cancel_power > 0 {
+                    if cancel_power.is_positive() {
                         // Apply conversion ratio raised to cancel_power
-                        // ratio = (ratio_num / ratio_den)
-                        // ratio^cancel_power = (ratio_num^cancel_power) / (ratio_den^cancel_power)
-                        let ratio_factor = (ratio_num as f64).powi(cancel_power)
-                            / (ratio_den as f64).powi(cancel_power);
-                        conversion_factor *= ratio_factor;
+                        conversion_factor *= cancel_power.apply(ratio);
 
                         // Update powers
                         let new_num_power = num_power - cancel_power;
@@ -1176,8 +2001,8 @@ pub fn cancel_and_convert_units(
 
 /// Build a Unit from symbol maps (numerator and denominator with powers)
 pub fn build_unit_from_symbols(
-    numerator: HashMap<String, i32>,
-    denominator: HashMap<String, i32>,
+    numerator: HashMap<String, Exponent>,
+    denominator: HashMap<String, Exponent>,
     library: &UnitLibrary,
 ) -> Unit {
     // If no units, return dimensionless
@@ -1263,38 +2088,113 @@ pub fn build_unit_from_symbols(
     }
 }
 
-/// Transform unit by multiplying all dimension exponents by a factor
-/// Used for SQRT (factor=0.5) and POWER (factor=exponent)
-pub fn transform_unit_exponents(unit: &Unit, factor: f64) -> Unit {
-    // Extract dimensions
-    let (numerator, denominator) = extract_dimensions(unit);
+/// Transform unit by multiplying all exponents by a factor
+/// Used for SQRT (factor=0.5) and POWER and ^ (factor=exponent)
+/// Symbols are kept, so ft^2 raised to 0.5 is ft, and m raised to 0.5 is m^0.5
+/// A unit raised to a factor that is not a simple fraction (m^0.123) is an error
+pub fn transform_unit_exponents(
+    unit: &Unit,
+    factor: f64,
+    library: &UnitLibrary,
+) -> Result<Unit, EvalError> {
+    let (numerator, denominator) = extract_unit_symbols(unit);
+    if numerator.is_empty() && denominator.is_empty() {
+        return Ok(Unit::dimensionless());
+    }
+    let factor = Exponent::from_f64(factor)
+        .ok_or_else(|| EvalError::UnknownUnit(format!("{}^{}", unit, factor)))?;
 
-    // Transform all exponents in numerator
-    let transformed_numerator: HashMap<BaseDimension, i32> = numerator
+    let mut transformed_numerator = HashMap::new();
+    let mut transformed_denominator = HashMap::new();
+    let terms = numerator
         .into_iter()
-        .map(|(base, power)| {
-            let new_power = ((power as f64) * factor).round() as i32;
-            (base, new_power)
-        })
-        .collect();
+        .map(|term| (term, false))
+        .chain(denominator.into_iter().map(|term| (term, true)));
 
-    // Transform all exponents in denominator
-    let transformed_denominator: HashMap<BaseDimension, i32> = denominator
-        .into_iter()
-        .map(|(base, power)| {
-            let new_power = ((power as f64) * factor).round() as i32;
-            (base, new_power)
-        })
-        .collect();
+    for ((symbol, power), in_denominator) in terms {
+        let new_power = power * factor;
+
+        // A negative power moves the symbol to the other side of the fraction
+        if new_power.is_zero() {
+            continue;
+        }
+        let side = if new_power.is_positive() != in_denominator {
+            &mut transformed_numerator
+        } else {
+            &mut transformed_denominator
+        };
+        *side.entry(symbol).or_default() += new_power.abs();
+    }
 
-    // Build the new unit
-    build_unit_from_dimensions(transformed_numerator, transformed_denominator)
+    Ok(build_unit_from_symbols(
+        transformed_numerator,
+        transformed_denominator,
+        library,
+    ))
+}
+
+/// Raise a value to a power, transforming its unit (5 m ^ 2 = 25 m^2)
+/// Shared by the ^ operator and the POWER function
+pub fn raise_to_power(
+    base: &EvalResult,
+    exponent: &EvalResult,
+    library: &UnitLibrary,
+) -> Result<EvalResult, EvalError> {
+    let base_value = base.as_number().ok_or_else(|| {
+        EvalError::InvalidOperation("Cannot raise a text value to a power".to_string())
+    })?;
+    let exponent_value = exponent
+        .as_number()
+        .ok_or_else(|| EvalError::InvalidOperation("Exponent must be a number".to_string()))?;
+
+    // Check that exponent is dimensionless
+    if !exponent.unit.is_dimensionless() {
+        return Err(EvalError::InvalidOperation(format!(
+            "Exponent must be dimensionless, got: {}",
+            exponent.unit
+        )));
+    }
+
+    // 0^-1 is 1/0, and powf gives NaN for a root of a negative number ((-8)^(1/3)) and
+    // infinity past the largest f64, none of which a cell can hold
+    if base_value == 0.0 && exponent_value < 0.0 {
+        return Err(EvalError::DivisionByZero);
+    }
+    let power_value = base_value.powf(exponent_value);
+    if !power_value.is_finite() {
+        return Err(EvalError::InvalidOperation(format!(
+            "{}^{} is not a real number that can be stored",
+            base_value, exponent_value
+        )));
+    }
+
+    // First-order propagation: d(x^n)/dx = n x^(n-1) and d(x^n)/dn = x^n ln x
+    let uncertainty = combine_uncertainties(&[
+        (
+            exponent_value * base_value.powf(exponent_value - 1.0),
+            base.uncertainty,
+        ),
+        (power_value * base_value.ln(), exponent.uncertainty),
+    ]);
+
+    // Special case: any unit^0 = dimensionless
+    let power_unit = if exponent_value == 0.0 {
+        Unit::dimensionless()
+    } else {
+        // Transform unit by multiplying exponents by power
+        transform_unit_exponents(&base.unit, exponent_value, library)?
+    };
+
+    // A power is known to the significant figures of its base
+    Ok(EvalResult::new(power_value, power_unit)
+        .with_uncertainty(uncertainty)
+        .with_significant_figures(base.significant_figures))
 }
 
 // Build a unit from dimension maps
 pub fn build_unit_from_dimensions(
-    numerator: HashMap<BaseDimension, i32>,
-    denominator: HashMap<BaseDimension, i32>,
+    numerator: HashMap<BaseDimension, Exponent>,
+    denominator: HashMap<BaseDimension, Exponent>,
 ) -> Unit {
     // If no dimensions, return dimensionless
     if numerator.is_empty() && denominator.is_empty() {
@@ -1334,8 +2234,8 @@ pub fn build_unit_from_dimensions(
 
 // Build unit symbol string from dimensions
 fn build_unit_symbol(
-    numerator: &HashMap<BaseDimension, i32>,
-    denominator: &HashMap<BaseDimension, i32>,
+    numerator: &HashMap<BaseDimension, Exponent>,
+    denominator: &HashMap<BaseDimension, Exponent>,
 ) -> String {
     let mut parts = Vec::new();
 
@@ -1425,6 +2325,63 @@ mod tests {
         assert_eq!(result.unit.canonical(), "m");
     }
 
+    #[test]
+    fn test_comparison_operators() {
+        let library = UnitLibrary::new();
+        let eval = Evaluator::new(&library);
+        let check = |formula: &str, expected: bool| {
+            let expr = crate::core::formula::parse_formula(formula).unwrap();
+            let result = eval.eval(&expr).unwrap();
+            assert_eq!(result.value, EvalValue::Boolean(expected), "{}", formula);
+            assert!(result.unit.is_dimensionless());
+        };
+
+        check("=5 ft > 1 m", true);
+        check("=5 ft < 1 m", false);
+        check("=100 cm = 1 m", true);
+        check("=100 cm <> 1 m", false);
+        check("=3 >= 3", true);
+        check("=2 <= 1", false);
+        check("=5 m > 3", true);
+        check("=\"abc\" = \"ABC\"", true);
+
+        // Values of different kinds are unequal rather than an error
+        check("=TRUE = 1", false);
+        check("=TRUE <> 1", true);
+        check("=1=1=1", false);
+        check("=\"1\" = 1", false);
+        check("=DATE(2024, 1, 1) <> \"2024-01-01\"", true);
+        let expr = crate::core::formula::parse_formula("=TRUE > 1").unwrap();
+        assert!(matches!(
+            eval.eval(&expr),
+            Err(EvalError::InvalidOperation(_))
+        ));
+
+        let expr = crate::core::formula::parse_formula("=5 ft > 1 kg").unwrap();
+        assert!(matches!(
+            eval.eval(&expr),
+            Err(EvalError::IncompatibleUnits { .. })
+        ));
+    }
+
+    #[test]
+    fn test_logical_operators() {
+        let library = UnitLibrary::new();
+        let eval = Evaluator::new(&library);
+        let check = |formula: &str, expected: bool| {
+            let expr = crate::core::formula::parse_formula(formula).unwrap();
+            let result = eval.eval(&expr).unwrap();
+            assert_eq!(result.value, EvalValue::Boolean(expected), "{}", formula);
+        };
+
+        check("=1 > 0 AND 2 > 1", true);
+        check("=1 > 0 && 2 < 1", false);
+        check("=1 < 0 OR 2 > 1", true);
+        check("=1 < 0 || 2 < 1", false);
+        check("=NOT 1 > 0", false);
+        check("=1 < 0 OR NOT 2 < 1 AND 3 > 2", true);
+    }
+
     #[test]
     fn test_add_same_units() {
         let library = UnitLibrary::new();
@@ -1543,6 +2500,31 @@ mod tests {
         assert!(matches!(result.unwrap_err(), EvalError::DivisionByZero));
     }
 
+    #[test]
+    fn test_power_without_a_real_result() {
+        let library = UnitLibrary::new();
+        let eval = Evaluator::new(&library);
+
+        let expr = Expr::new_power(Expr::number(0.0), Expr::number(-1.0));
+        assert!(matches!(
+            eval.eval(&expr).unwrap_err(),
+            EvalError::DivisionByZero
+        ));
+
+        let expr = Expr::new_power(
+            Expr::number(-8.0),
+            Expr::new_divide(Expr::number(1.0), Expr::number(3.0)),
+        );
+        assert_eq!(eval.eval(&expr).unwrap_err().kind(), ErrorKind::Value);
+
+        let expr = Expr::new_power(Expr::number(10.0), Expr::number(400.0));
+        assert_eq!(eval.eval(&expr).unwrap_err().kind(), ErrorKind::Value);
+
+        // Whole powers of negative numbers are still real
+        let expr = Expr::new_power(Expr::number(-2.0), Expr::number(3.0));
+        assert_eq!(eval.eval(&expr).unwrap().value, EvalValue::Number(-8.0));
+    }
+
     #[test]
     fn test_negate() {
         let library = UnitLibrary::new();
@@ -1553,6 +2535,26 @@ mod tests {
         assert_eq!(result.unit.canonical(), "m");
     }
 
+    #[test]
+    fn test_combine_uncertainties() {
+        assert_eq!(combine_uncertainties(&[(1.0, None), (2.0, None)]), None);
+        assert_eq!(
+            combine_uncertainties(&[(1.0, Some(3.0)), (2.0, Some(2.0))]),
+            Some(5.0)
+        );
+        assert_eq!(
+            combine_uncertainties(&[(-2.0, Some(1.5)), (7.0, None)]),
+            Some(3.0)
+        );
+        assert_eq!(combine_uncertainties(&[(f64::INFINITY, Some(1.0))]), None);
+
+        let result = EvalResult::new(100.0, Unit::simple("m", BaseDimension::Length))
+            .with_uncertainty(Some(2.0));
+        assert_eq!(display_string(&result), "100 m ± 2 m");
+        let negated = negate(result).unwrap();
+        assert_eq!(negated.uncertainty, Some(2.0));
+    }
+
     #[test]
     fn test_complex_expression() {
         let library = UnitLibrary::new();
@@ -1846,6 +2848,20 @@ mod tests {
         assert!(result.unit.canonical() == "$" || result.unit.canonical() == "USD");
     }
 
+    #[test]
+    fn test_temperature_ratio_keeps_compound_unit() {
+        let library = UnitLibrary::new();
+        let eval = Evaluator::new(&library);
+        // C and F differ by an offset, so C/F is not a ratio that cancels
+        let expr = Expr::new_divide(
+            Expr::number_with_unit(10.0, "C"),
+            Expr::number_with_unit(5.0, "F"),
+        );
+        let result = eval.eval(&expr).unwrap();
+        assert_eq!(result.value, EvalValue::Number(2.0));
+        assert_eq!(result.unit.canonical(), "C/F");
+    }
+
     // Test 12: Time squared cancellation
     #[test]
     fn test_time_squared_cancellation() {
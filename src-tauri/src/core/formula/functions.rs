@@ -0,0 +1,3507 @@
+// Function registry: the functions formulas can call
+
+use super::ast::{Expr, Lambda};
+use super::evaluator::{
+    combine_uncertainties, display_string, extract_unit_symbols, raise_to_power,
+    transform_unit_exponents, Array, EvalError, EvalResult, EvalValue, Evaluator,
+};
+use super::parser::parse_formula;
+use crate::core::cell::{parse_cell_input, CellError, CellValue, ErrorKind};
+use crate::core::datetime;
+use crate::core::precision;
+use crate::core::table::sheet::{MAX_ARRAY_CELLS, MAX_COLUMNS, MAX_ROWS};
+use crate::core::table::{CellAddr, CellRange};
+use crate::core::units::{parse_unit, BaseDimension, Dimension, Exponent, Unit, UnitLibrary};
+use chrono::{Datelike, Duration, Local, NaiveDate, NaiveDateTime};
+use statrs::statistics::{Data, Distribution, OrderStatistics};
+use std::cmp::Ordering;
+use std::collections::HashMap;
+use std::sync::{Arc, OnceLock};
+
+/// Implementation of a function
+/// Arguments are passed unevaluated, so a function can short-circuit (IF) or expand ranges (SUM)
+pub type FunctionImpl = fn(&Evaluator, &[Expr]) -> Result<EvalResult, EvalError>;
+
+/// Number of arguments a function accepts
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Arity {
+    pub min: usize,
+    /// None for functions taking any number of arguments
+    pub max: Option<usize>,
+}
+
+impl Arity {
+    pub fn exactly(count: usize) -> Self {
+        Self {
+            min: count,
+            max: Some(count),
+        }
+    }
+
+    pub fn between(min: usize, max: usize) -> Self {
+        Self {
+            min,
+            max: Some(max),
+        }
+    }
+
+    pub fn at_least(min: usize) -> Self {
+        Self { min, max: None }
+    }
+
+    /// Check whether a call with `count` arguments is allowed
+    pub fn accepts(&self, count: usize) -> bool {
+        count >= self.min && self.max.is_none_or(|max| count <= max)
+    }
+}
+
+impl std::fmt::Display for Arity {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let plural = |n: usize| if n == 1 { "argument" } else { "arguments" };
+        match self.max {
+            Some(max) if max == self.min => write!(f, "exactly {} {}", max, plural(max)),
+            Some(max) if max == self.min + 1 => {
+                write!(f, "{} or {} {}", self.min, max, plural(max))
+            }
+            Some(max) => write!(f, "{} to {} {}", self.min, max, plural(max)),
+            None => write!(f, "at least {} {}", self.min, plural(self.min)),
+        }
+    }
+}
+
+/// How the unit of a function's result relates to the units of its arguments
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum UnitSignature {
+    /// The result has the unit of the first argument (ABS, ROUND)
+    Preserve,
+    /// All values must have compatible units; the result is in the first one's unit (SUM, MAX)
+    Common,
+    /// The result is a dimensionless number (COUNT, SIGN, comparisons)
+    Dimensionless,
+    /// The function works out the result unit itself (SQRT, CONVERT, IF)
+    Derived,
+}
+
+/// What a function runs when it is called
+#[derive(Debug, Clone)]
+pub enum FunctionBody {
+    /// A function written in Rust
+    Native(FunctionImpl),
+    /// A function defined in the workbook with LAMBDA
+    Lambda(Arc<Lambda>),
+}
+
+/// A function that formulas can call
+#[derive(Debug, Clone)]
+pub struct FunctionSpec {
+    /// Upper-case name the function is called by
+    pub name: String,
+    pub arity: Arity,
+    pub units: UnitSignature,
+    pub body: FunctionBody,
+    /// Whether the result can change without the arguments changing (NOW, RAND), so that
+    /// cells calling the function are recalculated on every recalculation
+    pub volatile: bool,
+}
+
+impl FunctionSpec {
+    pub fn new(
+        name: impl Into<String>,
+        arity: Arity,
+        units: UnitSignature,
+        implementation: FunctionImpl,
+    ) -> Self {
+        Self {
+            name: name.into().to_uppercase(),
+            arity,
+            units,
+            body: FunctionBody::Native(implementation),
+            volatile: false,
+        }
+    }
+
+    /// Mark the function as volatile
+    pub fn volatile(mut self) -> Self {
+        self.volatile = true;
+        self
+    }
+
+    /// A function defined with LAMBDA, taking one argument per parameter
+    pub fn lambda(name: impl Into<String>, lambda: Lambda) -> Self {
+        Self {
+            name: name.into().to_uppercase(),
+            arity: Arity::exactly(lambda.params.len()),
+            units: UnitSignature::Derived,
+            body: FunctionBody::Lambda(Arc::new(lambda)),
+            volatile: false,
+        }
+    }
+}
+
+/// The set of functions available to formulas, looked up case-insensitively
+#[derive(Debug, Clone, Default)]
+pub struct FunctionRegistry {
+    functions: HashMap<String, FunctionSpec>,
+}
+
+impl FunctionRegistry {
+    /// Create an empty registry
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Create a registry holding the built-in functions, to extend with domain functions
+    pub fn with_builtins() -> Self {
+        let mut registry = Self::new();
+        register_builtins(&mut registry);
+        registry
+    }
+
+    /// The shared registry of built-in functions
+    pub fn standard() -> &'static Arc<FunctionRegistry> {
+        static STANDARD: OnceLock<Arc<FunctionRegistry>> = OnceLock::new();
+        STANDARD.get_or_init(|| Arc::new(Self::with_builtins()))
+    }
+
+    /// Add a function, replacing any existing function of the same name
+    pub fn register(&mut self, spec: FunctionSpec) {
+        self.functions.insert(spec.name.clone(), spec);
+    }
+
+    /// Look up a function by name
+    pub fn get(&self, name: &str) -> Option<&FunctionSpec> {
+        self.functions.get(&name.to_uppercase())
+    }
+
+    /// Whether a function is registered and volatile
+    pub fn is_volatile(&self, name: &str) -> bool {
+        self.get(name).is_some_and(|spec| spec.volatile)
+    }
+
+    /// Names of all registered functions, sorted
+    pub fn names(&self) -> Vec<&str> {
+        let mut names: Vec<&str> = self.functions.keys().map(String::as_str).collect();
+        names.sort_unstable();
+        names
+    }
+
+    /// The reference a call to INDIRECT or OFFSET resolves to, noted as a reference the
+    /// formula's cell depends on. None for other expressions, and for calls with the wrong
+    /// number of arguments, which fail when called. Functions taking ranges (SUM, INDEX) read
+    /// the resolved reference as a range, so blank cells are skipped rather than being zeros
+    pub fn resolve_reference(
+        &self,
+        evaluator: &Evaluator,
+        expr: &Expr,
+    ) -> Option<Result<Expr, EvalError>> {
+        let Expr::Function { name, args } = expr else {
+            return None;
+        };
+        let spec = self.get(name)?;
+        if !spec.arity.accepts(args.len()) || !matches!(spec.body, FunctionBody::Native(_)) {
+            return None;
+        }
+        match spec.name.as_str() {
+            "INDIRECT" => Some(indirect_reference(evaluator, args)),
+            "OFFSET" => Some(offset_reference(evaluator, args)),
+            _ => None,
+        }
+    }
+
+    /// Check the argument count and call a function
+    pub fn call(
+        &self,
+        evaluator: &Evaluator,
+        name: &str,
+        args: &[Expr],
+    ) -> Result<EvalResult, EvalError> {
+        let spec = self
+            .get(name)
+            .ok_or_else(|| EvalError::FunctionNotImplemented(name.to_string()))?;
+
+        if !spec.arity.accepts(args.len()) {
+            return Err(EvalError::InvalidOperation(format!(
+                "{} requires {}, got {}",
+                spec.name,
+                spec.arity,
+                args.len()
+            )));
+        }
+
+        match &spec.body {
+            FunctionBody::Native(implementation) => implementation(evaluator, args),
+            FunctionBody::Lambda(lambda) => evaluator.call_lambda(&spec.name, lambda, args),
+        }
+    }
+}
+
+fn register_builtins(registry: &mut FunctionRegistry) {
+    use UnitSignature::*;
+
+    let builtins: &[(&str, Arity, UnitSignature, FunctionImpl)] = &[
+        // Aggregates
+        ("SUM", Arity::at_least(0), Common, sum),
+        ("AVERAGE", Arity::at_least(1), Common, average),
+        ("COUNT", Arity::at_least(0), Dimensionless, count),
+        ("MIN", Arity::at_least(1), Common, min),
+        ("MAX", Arity::at_least(1), Common, max),
+        ("MEDIAN", Arity::at_least(1), Common, median),
+        ("STDEV", Arity::at_least(1), Common, stdev),
+        ("VAR", Arity::at_least(1), Derived, var),
+        ("SUMPRODUCT", Arity::at_least(1), Derived, sumproduct),
+        // Statistics
+        ("PERCENTILE", Arity::exactly(2), Common, percentile),
+        ("QUARTILE", Arity::exactly(2), Common, quartile),
+        ("MODE", Arity::at_least(1), Common, mode),
+        ("CORREL", Arity::exactly(2), Dimensionless, correl),
+        ("SLOPE", Arity::exactly(2), Derived, slope),
+        ("INTERCEPT", Arity::exactly(2), Derived, intercept),
+        ("LINEST", Arity::between(1, 3), Derived, linest),
+        ("FORECAST", Arity::exactly(3), Derived, forecast),
+        // Conditional aggregates
+        ("SUMIF", Arity::between(2, 3), Common, sumif),
+        ("SUMIFS", Arity::at_least(3), Common, sumifs),
+        ("COUNTIF", Arity::exactly(2), Dimensionless, |ev, args| {
+            count_matching(ev, "COUNTIF", args)
+        }),
+        ("COUNTIFS", Arity::at_least(2), Dimensionless, |ev, args| {
+            count_matching(ev, "COUNTIFS", args)
+        }),
+        ("AVERAGEIF", Arity::between(2, 3), Common, averageif),
+        ("AVERAGEIFS", Arity::at_least(3), Common, averageifs),
+        ("MAXIFS", Arity::at_least(3), Common, maxifs),
+        ("MINIFS", Arity::at_least(3), Common, minifs),
+        // Units
+        ("CONVERT", Arity::exactly(2), Derived, convert),
+        ("PERCENT", Arity::exactly(1), Derived, percent),
+        ("UNIT", Arity::exactly(1), Derived, unit_fn),
+        ("VALUE_IN", Arity::exactly(2), Dimensionless, value_in),
+        ("DIMENSION", Arity::exactly(1), Derived, dimension),
+        ("STRIPUNIT", Arity::exactly(1), Dimensionless, stripunit),
+        ("WITHUNIT", Arity::exactly(2), Derived, withunit),
+        (
+            "ISCOMPATIBLE",
+            Arity::exactly(2),
+            Dimensionless,
+            iscompatible,
+        ),
+        // Text
+        ("CONCAT", Arity::at_least(1), Derived, concat),
+        ("TEXTJOIN", Arity::at_least(3), Derived, textjoin),
+        ("LEFT", Arity::between(1, 2), Derived, |ev, args| {
+            text_slice(ev, "LEFT", args, |text, count| &text[..count])
+        }),
+        ("RIGHT", Arity::between(1, 2), Derived, |ev, args| {
+            text_slice(ev, "RIGHT", args, |text, count| &text[text.len() - count..])
+        }),
+        ("MID", Arity::exactly(3), Derived, mid),
+        ("LEN", Arity::exactly(1), Dimensionless, len),
+        ("UPPER", Arity::exactly(1), Derived, |ev, args| {
+            Ok(EvalResult::text(
+                text_arg(ev, "UPPER", &args[0])?.to_uppercase(),
+            ))
+        }),
+        ("LOWER", Arity::exactly(1), Derived, |ev, args| {
+            Ok(EvalResult::text(
+                text_arg(ev, "LOWER", &args[0])?.to_lowercase(),
+            ))
+        }),
+        ("TRIM", Arity::exactly(1), Derived, trim),
+        ("SUBSTITUTE", Arity::between(3, 4), Derived, substitute),
+        ("FIND", Arity::between(2, 3), Dimensionless, find),
+        ("VALUE", Arity::exactly(1), Derived, value),
+        ("TEXT", Arity::exactly(2), Derived, text),
+        // Math
+        ("ABS", Arity::exactly(1), Preserve, abs),
+        ("ROUND", Arity::between(1, 2), Preserve, round),
+        ("FLOOR", Arity::exactly(1), Preserve, floor),
+        ("CEILING", Arity::between(1, 2), Preserve, ceiling),
+        ("CEIL", Arity::between(1, 2), Preserve, ceiling),
+        ("TRUNC", Arity::exactly(1), Preserve, trunc),
+        ("MOD", Arity::exactly(2), Common, modulo),
+        ("SIGN", Arity::exactly(1), Dimensionless, sign),
+        ("SQRT", Arity::exactly(1), Derived, sqrt),
+        ("POWER", Arity::exactly(2), Derived, power),
+        // Logic
+        ("IF", Arity::exactly(3), Derived, if_fn),
+        ("LET", Arity::at_least(3), Derived, let_fn),
+        ("AND", Arity::at_least(1), Dimensionless, and),
+        ("OR", Arity::at_least(1), Dimensionless, or),
+        ("NOT", Arity::exactly(1), Dimensionless, not),
+        ("TRUE", Arity::exactly(0), Dimensionless, |_, _| {
+            Ok(Evaluator::boolean(true))
+        }),
+        ("FALSE", Arity::exactly(0), Dimensionless, |_, _| {
+            Ok(Evaluator::boolean(false))
+        }),
+        ("GT", Arity::exactly(2), Dimensionless, |ev, args| {
+            compare(ev, args, Ordering::is_gt)
+        }),
+        ("LT", Arity::exactly(2), Dimensionless, |ev, args| {
+            compare(ev, args, Ordering::is_lt)
+        }),
+        ("GTE", Arity::exactly(2), Dimensionless, |ev, args| {
+            compare(ev, args, Ordering::is_ge)
+        }),
+        ("LTE", Arity::exactly(2), Dimensionless, |ev, args| {
+            compare(ev, args, Ordering::is_le)
+        }),
+        ("EQ", Arity::exactly(2), Dimensionless, |ev, args| {
+            compare(ev, args, Ordering::is_eq)
+        }),
+        ("NE", Arity::exactly(2), Dimensionless, |ev, args| {
+            compare(ev, args, Ordering::is_ne)
+        }),
+        // Errors and value types
+        ("IFERROR", Arity::exactly(2), Derived, iferror),
+        ("IFNA", Arity::exactly(2), Derived, ifna),
+        ("NA", Arity::exactly(0), Dimensionless, na),
+        ("ISERROR", Arity::exactly(1), Dimensionless, iserror),
+        ("ISNUMBER", Arity::exactly(1), Dimensionless, isnumber),
+        ("ISTEXT", Arity::exactly(1), Dimensionless, istext),
+        ("ISLOGICAL", Arity::exactly(1), Dimensionless, islogical),
+        ("ISBLANK", Arity::exactly(1), Dimensionless, isblank),
+        ("ISUNIT", Arity::between(1, 2), Dimensionless, isunit),
+        // Dates
+        ("TODAY", Arity::exactly(0), Derived, today),
+        ("NOW", Arity::exactly(0), Derived, now),
+        // Random numbers
+        ("RAND", Arity::exactly(0), Dimensionless, rand),
+        ("RANDBETWEEN", Arity::exactly(2), Common, randbetween),
+        ("DATE", Arity::exactly(3), Derived, date),
+        ("YEAR", Arity::exactly(1), Dimensionless, |ev, args| {
+            date_part(ev, "YEAR", args, |d| d.year() as f64)
+        }),
+        ("MONTH", Arity::exactly(1), Dimensionless, |ev, args| {
+            date_part(ev, "MONTH", args, |d| d.month() as f64)
+        }),
+        ("DAY", Arity::exactly(1), Dimensionless, |ev, args| {
+            date_part(ev, "DAY", args, |d| d.day() as f64)
+        }),
+        ("EDATE", Arity::exactly(2), Derived, edate),
+        ("EOMONTH", Arity::exactly(2), Derived, eomonth),
+        ("NETWORKDAYS", Arity::between(2, 3), Derived, networkdays),
+        ("DATEDIF", Arity::exactly(3), Derived, datedif),
+        // Lookups
+        ("VLOOKUP", Arity::between(3, 4), Derived, vlookup),
+        ("HLOOKUP", Arity::between(3, 4), Derived, hlookup),
+        ("XLOOKUP", Arity::between(3, 6), Derived, xlookup),
+        ("INDEX", Arity::between(2, 3), Derived, index),
+        ("MATCH", Arity::between(2, 3), Dimensionless, match_fn),
+        // References
+        ("INDIRECT", Arity::exactly(1), Derived, indirect),
+        ("OFFSET", Arity::between(3, 5), Derived, offset),
+        // Arrays
+        ("SEQUENCE", Arity::between(1, 4), Derived, sequence),
+        ("SORT", Arity::between(1, 4), Derived, sort),
+        ("UNIQUE", Arity::between(1, 3), Derived, unique),
+        ("FILTER", Arity::between(2, 3), Derived, filter),
+        // Financial
+        ("PMT", Arity::between(3, 6), Derived, pmt),
+        ("FV", Arity::between(3, 5), Derived, fv),
+        ("PV", Arity::between(3, 5), Derived, pv),
+        ("NPV", Arity::at_least(2), Derived, npv),
+        ("IRR", Arity::between(1, 2), Derived, irr),
+        ("XNPV", Arity::exactly(3), Derived, xnpv),
+    ];
+
+    let volatile = ["TODAY", "NOW", "RAND", "RANDBETWEEN"];
+    for &(name, arity, units, implementation) in builtins {
+        let spec = FunctionSpec::new(name, arity, units, implementation);
+        registry.register(if volatile.contains(&name) {
+            spec.volatile()
+        } else {
+            spec
+        });
+    }
+}
+
+/// Evaluate an argument that must be a number
+fn number_arg(ev: &Evaluator, name: &str, arg: &Expr) -> Result<(f64, Unit), EvalError> {
+    let result = ev.eval(arg)?;
+    let value = result.as_number().ok_or_else(|| {
+        EvalError::InvalidOperation(format!("{} can only be used with numbers", name))
+    })?;
+    Ok((value, result.unit))
+}
+
+/// Collect the values of the arguments (expanding ranges) and convert them all to the
+/// unit of the first value
+fn common_values(ev: &Evaluator, name: &str, args: &[Expr]) -> Result<(Vec<f64>, Unit), EvalError> {
+    to_common_unit(ev, name, &ev.collect_values(args)?)
+}
+
+/// Convert values to the unit of the first one; they must all be numbers with compatible units
+fn to_common_unit(
+    ev: &Evaluator,
+    name: &str,
+    values: &[EvalResult],
+) -> Result<(Vec<f64>, Unit), EvalError> {
+    let unit = match values.first() {
+        Some(first) => first.unit.clone(),
+        None => return Ok((Vec::new(), Unit::dimensionless())),
+    };
+
+    let mut numbers = Vec::with_capacity(values.len());
+    for val in values {
+        let number = val.as_number().ok_or_else(|| {
+            EvalError::InvalidOperation(format!("{} can only be used with numbers", name))
+        })?;
+        numbers.push(convert_value(ev.library(), name, number, &val.unit, &unit)?);
+    }
+
+    Ok((numbers, unit))
+}
+
+/// Convert a value between two compatible units
+fn convert_value(
+    library: &UnitLibrary,
+    operation: &str,
+    value: f64,
+    from: &Unit,
+    to: &Unit,
+) -> Result<f64, EvalError> {
+    let incompatible = || EvalError::IncompatibleUnits {
+        operation: operation.to_string(),
+        left: to.to_string(),
+        right: from.to_string(),
+    };
+
+    if !from.is_compatible(to) {
+        return Err(incompatible());
+    }
+    if from.is_equal(to) {
+        return Ok(value);
+    }
+    library
+        .convert(value, from.canonical(), to.canonical())
+        .ok_or_else(incompatible)
+}
+
+/// SUM(values...); the sum is known to the decimal place of its least precise value
+fn sum(ev: &Evaluator, args: &[Expr]) -> Result<EvalResult, EvalError> {
+    let values = ev.collect_values(args)?;
+    let (numbers, unit) = to_common_unit(ev, "SUM", &values)?;
+    // Summing no values with f64's Sum gives -0.0, which would show as "-0"
+    let total = numbers.iter().fold(0.0, |total, number| total + number);
+    let operands: Vec<_> = values
+        .iter()
+        .map(|value| {
+            let scale = ev.unit_scale(&value.unit, &unit);
+            (value.numeric_value(), value.significant_figures, scale)
+        })
+        .collect();
+    let figures = precision::sum_figures(total, &operands);
+    Ok(EvalResult::new(total, unit).with_significant_figures(figures))
+}
+
+fn average(ev: &Evaluator, args: &[Expr]) -> Result<EvalResult, EvalError> {
+    let (values, unit) = common_values(ev, "AVERAGE", args)?;
+    if values.is_empty() {
+        return Err(EvalError::InvalidOperation(
+            "AVERAGE requires at least one value".to_string(),
+        ));
+    }
+    let sum: f64 = values.iter().sum();
+    Ok(EvalResult::new(sum / values.len() as f64, unit))
+}
+
+/// COUNT counts the values in its arguments; empty and text cells in ranges are skipped
+fn count(ev: &Evaluator, args: &[Expr]) -> Result<EvalResult, EvalError> {
+    let values = ev.collect_values(args)?;
+    Ok(EvalResult::new(values.len() as f64, Unit::dimensionless()))
+}
+
+fn min(ev: &Evaluator, args: &[Expr]) -> Result<EvalResult, EvalError> {
+    let (values, unit) = common_values(ev, "MIN", args)?;
+    let min = values.into_iter().reduce(f64::min).ok_or_else(|| {
+        EvalError::InvalidOperation("MIN requires at least one value".to_string())
+    })?;
+    Ok(EvalResult::new(min, unit))
+}
+
+fn max(ev: &Evaluator, args: &[Expr]) -> Result<EvalResult, EvalError> {
+    let (values, unit) = common_values(ev, "MAX", args)?;
+    let max = values.into_iter().reduce(f64::max).ok_or_else(|| {
+        EvalError::InvalidOperation("MAX requires at least one value".to_string())
+    })?;
+    Ok(EvalResult::new(max, unit))
+}
+
+fn median(ev: &Evaluator, args: &[Expr]) -> Result<EvalResult, EvalError> {
+    let (values, unit) = common_values(ev, "MEDIAN", args)?;
+    if values.is_empty() {
+        return Err(EvalError::InvalidOperation(
+            "MEDIAN requires at least one value".to_string(),
+        ));
+    }
+    Ok(EvalResult::new(Data::new(values).median(), unit))
+}
+
+/// STDEV is the sample standard deviation, in the unit of the values
+fn stdev(ev: &Evaluator, args: &[Expr]) -> Result<EvalResult, EvalError> {
+    let (values, unit) = common_values(ev, "STDEV", args)?;
+    if values.len() < 2 {
+        return Err(EvalError::InvalidOperation(
+            "STDEV requires at least 2 values".to_string(),
+        ));
+    }
+    let stdev = Data::new(values).std_dev().ok_or_else(|| {
+        EvalError::InvalidOperation("Failed to compute standard deviation".to_string())
+    })?;
+    Ok(EvalResult::new(stdev, unit))
+}
+
+/// VAR is the sample variance, so its unit is the square of the values' unit
+fn var(ev: &Evaluator, args: &[Expr]) -> Result<EvalResult, EvalError> {
+    let (values, unit) = common_values(ev, "VAR", args)?;
+    if values.len() < 2 {
+        return Err(EvalError::InvalidOperation(
+            "VAR requires at least 2 values".to_string(),
+        ));
+    }
+    let variance = Data::new(values)
+        .variance()
+        .ok_or_else(|| EvalError::InvalidOperation("Failed to compute variance".to_string()))?;
+    let unit = transform_unit_exponents(&unit, 2.0, ev.library())?;
+    Ok(EvalResult::new(variance, unit))
+}
+
+/// The value below which a fraction k of the values fall, interpolating between the two
+/// nearest values as Excel's PERCENTILE does
+fn kth_percentile(name: &str, mut values: Vec<f64>, k: f64) -> Result<f64, EvalError> {
+    if values.is_empty() {
+        return Err(EvalError::InvalidOperation(format!(
+            "{} requires at least one value",
+            name
+        )));
+    }
+    values.sort_by(f64::total_cmp);
+
+    let position = k * (values.len() - 1) as f64;
+    let below = position.floor() as usize;
+    let above = (below + 1).min(values.len() - 1);
+    Ok(values[below] + (position - below as f64) * (values[above] - values[below]))
+}
+
+/// PERCENTILE(array, k): the k-th percentile (k from 0 to 1, or 0 % to 100 %), in the unit of
+/// the first value, so latencies in ms and s can be mixed
+fn percentile(ev: &Evaluator, args: &[Expr]) -> Result<EvalResult, EvalError> {
+    let (values, unit) = common_values(ev, "PERCENTILE", &args[..1])?;
+    let (k, k_unit) = number_arg(ev, "PERCENTILE", &args[1])?;
+    if !(k_unit.is_dimensionless() || k_unit.canonical() == "%") || !(0.0..=1.0).contains(&k) {
+        return Err(EvalError::InvalidOperation(
+            "PERCENTILE k must be between 0 and 1".to_string(),
+        ));
+    }
+    Ok(EvalResult::new(
+        kth_percentile("PERCENTILE", values, k)?,
+        unit,
+    ))
+}
+
+/// QUARTILE(array, quart): the minimum (0), a quartile (1 to 3) or the maximum (4)
+fn quartile(ev: &Evaluator, args: &[Expr]) -> Result<EvalResult, EvalError> {
+    let (values, unit) = common_values(ev, "QUARTILE", &args[..1])?;
+    let (quart, quart_unit) = number_arg(ev, "QUARTILE", &args[1])?;
+    let quart = quart.trunc();
+    if !quart_unit.is_dimensionless() || !(0.0..=4.0).contains(&quart) {
+        return Err(EvalError::InvalidOperation(
+            "QUARTILE quart must be 0, 1, 2, 3 or 4".to_string(),
+        ));
+    }
+    let value = kth_percentile("QUARTILE", values, quart / 4.0)?;
+    Ok(EvalResult::new(value, unit))
+}
+
+/// MODE(value1, [value2], ...): the most common value (the first, if several are as common)
+/// Values are compared in one unit, so 1 km and 1000 m are the same
+fn mode(ev: &Evaluator, args: &[Expr]) -> Result<EvalResult, EvalError> {
+    let (values, unit) = common_values(ev, "MODE", args)?;
+    let mut best: Option<(f64, usize)> = None;
+    for (i, value) in values.iter().enumerate() {
+        let count = values[i..].iter().filter(|other| *other == value).count();
+        if count > 1 && best.is_none_or(|(_, most)| count > most) {
+            best = Some((*value, count));
+        }
+    }
+    best.map(|(value, _)| EvalResult::new(value, unit))
+        .ok_or_else(|| EvalError::NotAvailable("MODE found no repeated values".to_string()))
+}
+
+/// Numbers converted to one unit
+type Values = (Vec<f64>, Unit);
+
+/// Paired values of two ranges of the same size (known_ys, known_xs), each in one unit
+/// Pairs where either cell is empty, text or a boolean are skipped, as in Excel; dates are
+/// counted in days, so a trend over dates is per day
+fn paired_values(
+    ev: &Evaluator,
+    name: &str,
+    ys: &Expr,
+    xs: &Expr,
+) -> Result<(Values, Values), EvalError> {
+    let (y_cells, _) = cells_arg(ev, ys)?;
+    let (x_cells, _) = cells_arg(ev, xs)?;
+    if y_cells.len() != x_cells.len() {
+        return Err(EvalError::InvalidOperation(format!(
+            "{} ranges must have the same number of values",
+            name
+        )));
+    }
+
+    let (mut y_values, mut x_values) = (Vec::new(), Vec::new());
+    for (y, x) in y_cells.into_iter().zip(x_cells) {
+        let (y, x) = match (y, x) {
+            (Some(Err(e)), _) | (_, Some(Err(e))) => return Err(e),
+            (Some(Ok(y)), Some(Ok(x))) => (y, x),
+            _ => continue,
+        };
+        if let (Some(y), Some(x)) = (statistic_value(ev, y), statistic_value(ev, x)) {
+            y_values.push(y);
+            x_values.push(x);
+        }
+    }
+    Ok((
+        to_common_unit(ev, name, &y_values)?,
+        to_common_unit(ev, name, &x_values)?,
+    ))
+}
+
+/// A number, or a date as a number of days; None for other values
+fn statistic_value(ev: &Evaluator, value: EvalResult) -> Option<EvalResult> {
+    if let Some(date) = value.as_datetime() {
+        let day = parse_unit("day", ev.library()).ok()?;
+        return Some(EvalResult::new(datetime::to_serial(&date), day));
+    }
+    value.is_number().then_some(value)
+}
+
+/// Sums of products of deviations from the means: (xx, xy, yy)
+fn deviations(ys: &[f64], xs: &[f64]) -> (f64, f64, f64) {
+    let mean = |values: &[f64]| values.iter().sum::<f64>() / values.len() as f64;
+    let (y_mean, x_mean) = (mean(ys), mean(xs));
+    ys.iter()
+        .zip(xs)
+        .fold((0.0, 0.0, 0.0), |(xx, xy, yy), (y, x)| {
+            let (dy, dx) = (y - y_mean, x - x_mean);
+            (xx + dx * dx, xy + dx * dy, yy + dy * dy)
+        })
+}
+
+/// The least-squares line through the points, as (slope, intercept)
+/// Without a constant the line goes through the origin
+fn fit_line(name: &str, ys: &[f64], xs: &[f64], constant: bool) -> Result<(f64, f64), EvalError> {
+    if ys.len() < 2 {
+        return Err(EvalError::InvalidOperation(format!(
+            "{} requires at least 2 pairs of values",
+            name
+        )));
+    }
+    if !constant {
+        let xx: f64 = xs.iter().map(|x| x * x).sum();
+        let xy: f64 = xs.iter().zip(ys).map(|(x, y)| x * y).sum();
+        if xx == 0.0 {
+            return Err(EvalError::DivisionByZero);
+        }
+        return Ok((xy / xx, 0.0));
+    }
+
+    let (xx, xy, _) = deviations(ys, xs);
+    if xx == 0.0 {
+        return Err(EvalError::DivisionByZero);
+    }
+    let slope = xy / xx;
+    let mean = |values: &[f64]| values.iter().sum::<f64>() / values.len() as f64;
+    Ok((slope, mean(ys) - slope * mean(xs)))
+}
+
+/// A slope in the y unit per x unit: GB per day
+fn slope_result(ev: &Evaluator, slope: f64, y_unit: &Unit, x_unit: &Unit) -> EvalResult {
+    ev.divide_values(
+        &EvalResult::new(slope, y_unit.clone()),
+        &EvalResult::new(1.0, x_unit.clone()),
+    )
+    .unwrap_or_else(|_| EvalResult::new(slope, Unit::dimensionless()))
+}
+
+/// CORREL(array1, array2): the correlation coefficient of the pairs, which has no unit
+fn correl(ev: &Evaluator, args: &[Expr]) -> Result<EvalResult, EvalError> {
+    let ((ys, _), (xs, _)) = paired_values(ev, "CORREL", &args[0], &args[1])?;
+    let (xx, xy, yy) = deviations(&ys, &xs);
+    if xx == 0.0 || yy == 0.0 {
+        return Err(EvalError::DivisionByZero);
+    }
+    Ok(EvalResult::new(
+        xy / (xx * yy).sqrt(),
+        Unit::dimensionless(),
+    ))
+}
+
+/// SLOPE(known_ys, known_xs): the slope of the trend line, in y units per x unit
+/// (SLOPE of GB used over days is in GB/day)
+fn slope(ev: &Evaluator, args: &[Expr]) -> Result<EvalResult, EvalError> {
+    let ((ys, y_unit), (xs, x_unit)) = paired_values(ev, "SLOPE", &args[0], &args[1])?;
+    let (slope, _) = fit_line("SLOPE", &ys, &xs, true)?;
+    Ok(slope_result(ev, slope, &y_unit, &x_unit))
+}
+
+/// INTERCEPT(known_ys, known_xs): where the trend line crosses x = 0, in the y unit
+fn intercept(ev: &Evaluator, args: &[Expr]) -> Result<EvalResult, EvalError> {
+    let ((ys, y_unit), (xs, _)) = paired_values(ev, "INTERCEPT", &args[0], &args[1])?;
+    let (_, intercept) = fit_line("INTERCEPT", &ys, &xs, true)?;
+    Ok(EvalResult::new(intercept, y_unit))
+}
+
+/// LINEST(known_ys, [known_xs], [const]): the slope and intercept of the trend line as a row
+/// that spills into two cells. known_xs defaults to 1, 2, 3, ...; const FALSE fits a line
+/// through the origin
+fn linest(ev: &Evaluator, args: &[Expr]) -> Result<EvalResult, EvalError> {
+    let ((ys, y_unit), (xs, x_unit)) = match args.get(1) {
+        Some(xs) => paired_values(ev, "LINEST", &args[0], xs)?,
+        None => {
+            let (ys, y_unit) = common_values(ev, "LINEST", &args[..1])?;
+            let xs = (1..=ys.len()).map(|x| x as f64).collect();
+            ((ys, y_unit), (xs, Unit::dimensionless()))
+        }
+    };
+    let constant = match args.get(2) {
+        Some(arg) => ev.eval_truth(arg, "LINEST")?,
+        None => true,
+    };
+
+    let (slope, intercept) = fit_line("LINEST", &ys, &xs, constant)?;
+    Ok(EvalResult::array(vec![vec![
+        slope_result(ev, slope, &y_unit, &x_unit),
+        EvalResult::new(intercept, y_unit),
+    ]]))
+}
+
+/// FORECAST(x, known_ys, known_xs): the y the trend line gives at x, in the y unit
+/// x may be in any unit compatible with the known xs, or a date when they are dates
+fn forecast(ev: &Evaluator, args: &[Expr]) -> Result<EvalResult, EvalError> {
+    let ((ys, y_unit), (xs, x_unit)) = paired_values(ev, "FORECAST", &args[1], &args[2])?;
+    let x = statistic_value(ev, ev.eval(&args[0])?).ok_or_else(|| {
+        EvalError::InvalidOperation("FORECAST x must be a number or a date".to_string())
+    })?;
+    let x = if x.unit.is_dimensionless() || x_unit.is_dimensionless() {
+        x.numeric_value()
+    } else {
+        convert_value(
+            ev.library(),
+            "FORECAST",
+            x.numeric_value(),
+            &x.unit,
+            &x_unit,
+        )?
+    };
+
+    let (slope, intercept) = fit_line("FORECAST", &ys, &xs, true)?;
+    Ok(EvalResult::new(intercept + slope * x, y_unit))
+}
+
+/// SUMPRODUCT(array1, [array2], ...): multiply the arrays element by element and add the products
+/// Units multiply as with *, so $/GB/month times GB sums to $/month. Elements that are not
+/// numbers count as 0, and every product must have a unit compatible with the first
+fn sumproduct(ev: &Evaluator, args: &[Expr]) -> Result<EvalResult, EvalError> {
+    let arrays = args
+        .iter()
+        .map(|arg| ev.eval_array(arg))
+        .collect::<Result<Vec<_>, _>>()?;
+    let shape = |array: &Array| (array.len(), array.first().map_or(0, Vec::len));
+    let (rows, cols) = shape(&arrays[0]);
+    if arrays.iter().any(|array| shape(array) != (rows, cols)) {
+        return Err(EvalError::InvalidOperation(
+            "SUMPRODUCT arrays must be the same size".to_string(),
+        ));
+    }
+
+    let mut total: Option<EvalResult> = None;
+    for row in 0..rows {
+        for col in 0..cols {
+            let factors: Vec<&EvalResult> = arrays.iter().map(|array| &array[row][col]).collect();
+            if !factors.iter().all(|factor| factor.is_number()) {
+                continue;
+            }
+            let mut product = factors[0].clone();
+            for factor in &factors[1..] {
+                product = ev.multiply_values(&product, factor)?;
+            }
+
+            total = match total {
+                None => Some(product),
+                // A zero without a unit (from blank cells) adds nothing
+                Some(total)
+                    if product.unit.is_dimensionless() && product.numeric_value() == 0.0 =>
+                {
+                    Some(total)
+                }
+                Some(total) => {
+                    let value = convert_value(
+                        ev.library(),
+                        "add SUMPRODUCT rows",
+                        product.numeric_value(),
+                        &product.unit,
+                        &total.unit,
+                    )
+                    .map_err(|_| EvalError::IncompatibleUnits {
+                        operation: "add SUMPRODUCT rows".to_string(),
+                        left: total.unit.to_string(),
+                        right: format!("{} in row {}", product.unit, row + 1),
+                    })?;
+                    Some(EvalResult::new(total.numeric_value() + value, total.unit))
+                }
+            };
+        }
+    }
+    Ok(total.unwrap_or_else(|| EvalResult::new(0.0, Unit::dimensionless())))
+}
+
+/// CONVERT(value, target_unit)
+/// The target is a value in the wanted unit (1km, a cell) or the unit name as text ("km").
+/// A range or array is converted value by value
+fn convert(ev: &Evaluator, args: &[Expr]) -> Result<EvalResult, EvalError> {
+    let value = ev.eval(&args[0])?;
+
+    let target_unit = unit_arg(ev, &args[1])?;
+
+    match value.value {
+        EvalValue::Array(values) => Ok(EvalResult::array(
+            values
+                .iter()
+                .map(|row| {
+                    row.iter()
+                        .map(|value| convert_to(ev, value, &target_unit))
+                        .collect()
+                })
+                .collect::<Result<_, _>>()?,
+        )),
+        _ => convert_to(ev, &value, &target_unit),
+    }
+}
+
+/// Convert one value for CONVERT
+fn convert_to(
+    ev: &Evaluator,
+    result: &EvalResult,
+    target_unit: &Unit,
+) -> Result<EvalResult, EvalError> {
+    let value = result.as_number().ok_or_else(|| {
+        EvalError::InvalidOperation("CONVERT can only be used with numbers".to_string())
+    })?;
+    let unit = &result.unit;
+
+    if !unit.is_compatible(target_unit) {
+        return Err(EvalError::IncompatibleUnits {
+            operation: "CONVERT".to_string(),
+            left: unit.to_string(),
+            right: target_unit.to_string(),
+        });
+    }
+
+    if unit.is_equal(target_unit) {
+        return Ok(EvalResult::new(value, target_unit.clone())
+            .with_uncertainty(result.uncertainty)
+            .with_significant_figures(result.significant_figures));
+    }
+
+    // Try compound unit conversion first (handles units like $/quarter -> $/year)
+    let convert = |value| match convert_compound_unit(
+        value,
+        unit.canonical(),
+        target_unit.canonical(),
+        ev.library(),
+    ) {
+        Some(converted) => Ok(converted),
+        None => convert_value(ev.library(), "CONVERT", value, unit, target_unit),
+    };
+    let converted = convert(value)?;
+
+    // An uncertainty is a difference, so it converts without the offset between units
+    let uncertainty = match result.uncertainty {
+        Some(uncertainty) => Some((convert(value + uncertainty)? - converted).abs()),
+        None => None,
+    };
+
+    Ok(EvalResult::new(converted, target_unit.clone())
+        .with_uncertainty(uncertainty)
+        .with_significant_figures(result.significant_figures))
+}
+
+/// PERCENT(value): the value with a "%" unit, so PERCENT(0.15) displays as 15%
+fn percent(ev: &Evaluator, args: &[Expr]) -> Result<EvalResult, EvalError> {
+    let (value, _) = number_arg(ev, "PERCENT", &args[0])?;
+    let percent_unit = Unit::simple("%", BaseDimension::Custom("%".to_string()));
+    Ok(EvalResult::new(value, percent_unit))
+}
+
+/// Evaluate an argument naming a unit: the unit name as text ("km") or a value in the unit (1km)
+fn unit_arg(ev: &Evaluator, arg: &Expr) -> Result<Unit, EvalError> {
+    let target = ev.eval(arg)?;
+    match target.as_text() {
+        Some(text) => {
+            parse_unit(text, ev.library()).map_err(|_| EvalError::UnknownUnit(text.to_string()))
+        }
+        None => Ok(target.unit),
+    }
+}
+
+/// UNIT(value): the value's unit as text, empty for a plain number
+fn unit_fn(ev: &Evaluator, args: &[Expr]) -> Result<EvalResult, EvalError> {
+    let (_, unit) = number_arg(ev, "UNIT", &args[0])?;
+    Ok(EvalResult::text(unit.to_string()))
+}
+
+/// VALUE_IN(value, unit): the value converted to the unit, as a plain number
+/// VALUE_IN(3 m, "ft") = 9.84...
+fn value_in(ev: &Evaluator, args: &[Expr]) -> Result<EvalResult, EvalError> {
+    without_unit(convert(ev, args)?, "VALUE_IN")
+}
+
+/// DIMENSION(value): what the value measures, e.g. "Length" or "Length/Time"
+/// A unit name as text ("mph") gives the dimension of the unit
+fn dimension(ev: &Evaluator, args: &[Expr]) -> Result<EvalResult, EvalError> {
+    let result = ev.eval(&args[0])?;
+    let unit = match result.as_text() {
+        Some(text) => {
+            parse_unit(text, ev.library()).map_err(|_| EvalError::UnknownUnit(text.to_string()))?
+        }
+        None if result.is_number() => result.unit,
+        None => {
+            return Err(EvalError::InvalidOperation(
+                "DIMENSION can only be used with numbers or unit names".to_string(),
+            ))
+        }
+    };
+    Ok(EvalResult::text(dimension_name(unit.dimension())))
+}
+
+/// STRIPUNIT(value): the number without its unit, so STRIPUNIT(5 kg) = 5
+/// A range or array is stripped value by value
+fn stripunit(ev: &Evaluator, args: &[Expr]) -> Result<EvalResult, EvalError> {
+    without_unit(ev.eval(&args[0])?, "STRIPUNIT")
+}
+
+/// Drop the unit of a number, or of each number in an array
+fn without_unit(result: EvalResult, name: &str) -> Result<EvalResult, EvalError> {
+    match result.value {
+        EvalValue::Array(values) => Ok(EvalResult::array(
+            values
+                .into_iter()
+                .map(|row| {
+                    row.into_iter()
+                        .map(|value| without_unit(value, name))
+                        .collect()
+                })
+                .collect::<Result<_, _>>()?,
+        )),
+        EvalValue::Number(value) => Ok(EvalResult::new(value, Unit::dimensionless())),
+        _ => Err(EvalError::InvalidOperation(format!(
+            "{} can only be used with numbers",
+            name
+        ))),
+    }
+}
+
+/// WITHUNIT(number, unit): a plain number given a unit, so WITHUNIT(5, "kg") = 5 kg
+/// Like PERCENT, the number is kept as is: WITHUNIT(0.15, "%") is 15%
+fn withunit(ev: &Evaluator, args: &[Expr]) -> Result<EvalResult, EvalError> {
+    let (value, unit) = number_arg(ev, "WITHUNIT", &args[0])?;
+    if !unit.is_dimensionless() {
+        return Err(EvalError::InvalidOperation(format!(
+            "WITHUNIT expects a number without a unit, got {}; use CONVERT to change units",
+            unit
+        )));
+    }
+    Ok(EvalResult::new(value, unit_arg(ev, &args[1])?))
+}
+
+/// ISCOMPATIBLE(a, b): whether two values (or unit names) measure the same dimension,
+/// so one can be converted to the other
+fn iscompatible(ev: &Evaluator, args: &[Expr]) -> Result<EvalResult, EvalError> {
+    let left = unit_arg(ev, &args[0])?;
+    let right = unit_arg(ev, &args[1])?;
+    Ok(Evaluator::boolean(left.is_compatible(&right)))
+}
+
+/// Evaluate an argument as text; numbers are written with their unit ("5 kg")
+fn text_arg(ev: &Evaluator, name: &str, arg: &Expr) -> Result<String, EvalError> {
+    let result = ev.eval(arg)?;
+    if result.is_array() {
+        return Err(EvalError::InvalidOperation(format!(
+            "{} expects a single value, not a range",
+            name
+        )));
+    }
+    Ok(display_string(&result))
+}
+
+/// Evaluate a count or position argument of a text function: a number no less than `min`,
+/// of which the whole part is used
+fn text_count_arg(ev: &Evaluator, name: &str, arg: &Expr, min: usize) -> Result<usize, EvalError> {
+    let (value, _) = number_arg(ev, name, arg)?;
+    if value < min as f64 {
+        return Err(EvalError::InvalidOperation(format!(
+            "{} expects a number of at least {}, got {}",
+            name, min, value
+        )));
+    }
+    Ok(value.trunc() as usize)
+}
+
+/// The text of every cell of the arguments (expanding ranges), None for blank cells
+fn text_pieces(ev: &Evaluator, args: &[Expr]) -> Result<Vec<Option<String>>, EvalError> {
+    let mut pieces = Vec::new();
+    for arg in args {
+        for cell in cells_arg(ev, arg)?.0 {
+            pieces.push(cell.transpose()?.as_ref().map(display_string));
+        }
+    }
+    Ok(pieces)
+}
+
+/// CONCAT(values...): the values (and the cells of ranges) joined into one text
+fn concat(ev: &Evaluator, args: &[Expr]) -> Result<EvalResult, EvalError> {
+    let pieces = text_pieces(ev, args)?;
+    Ok(EvalResult::text(pieces.into_iter().flatten().collect()))
+}
+
+/// TEXTJOIN(delimiter, ignore_empty, values...): the values joined with a delimiter
+/// With ignore_empty, blank cells and empty text are left out
+fn textjoin(ev: &Evaluator, args: &[Expr]) -> Result<EvalResult, EvalError> {
+    let delimiter = text_arg(ev, "TEXTJOIN", &args[0])?;
+    let ignore_empty = ev.eval_truth(&args[1], "TEXTJOIN")?;
+    let pieces: Vec<String> = text_pieces(ev, &args[2..])?
+        .into_iter()
+        .filter(|piece| !ignore_empty || piece.as_ref().is_some_and(|text| !text.is_empty()))
+        .map(Option::unwrap_or_default)
+        .collect();
+    Ok(EvalResult::text(pieces.join(&delimiter)))
+}
+
+/// LEFT(text, [count]) and RIGHT(text, [count]): the first or last characters (1 by default)
+fn text_slice(
+    ev: &Evaluator,
+    name: &str,
+    args: &[Expr],
+    slice: fn(&[char], usize) -> &[char],
+) -> Result<EvalResult, EvalError> {
+    let text: Vec<char> = text_arg(ev, name, &args[0])?.chars().collect();
+    let count = match args.get(1) {
+        Some(arg) => text_count_arg(ev, name, arg, 0)?,
+        None => 1,
+    };
+    Ok(EvalResult::text(
+        slice(&text, count.min(text.len())).iter().collect(),
+    ))
+}
+
+/// MID(text, start, count): `count` characters from position `start` (the first is 1)
+fn mid(ev: &Evaluator, args: &[Expr]) -> Result<EvalResult, EvalError> {
+    let text = text_arg(ev, "MID", &args[0])?;
+    let start = text_count_arg(ev, "MID", &args[1], 1)?;
+    let count = text_count_arg(ev, "MID", &args[2], 0)?;
+    Ok(EvalResult::text(
+        text.chars().skip(start - 1).take(count).collect(),
+    ))
+}
+
+/// LEN(text): the number of characters
+fn len(ev: &Evaluator, args: &[Expr]) -> Result<EvalResult, EvalError> {
+    let text = text_arg(ev, "LEN", &args[0])?;
+    Ok(EvalResult::new(
+        text.chars().count() as f64,
+        Unit::dimensionless(),
+    ))
+}
+
+/// TRIM(text): the text without spaces at either end, and with single spaces between words
+fn trim(ev: &Evaluator, args: &[Expr]) -> Result<EvalResult, EvalError> {
+    let text = text_arg(ev, "TRIM", &args[0])?;
+    Ok(EvalResult::text(
+        text.split_whitespace().collect::<Vec<_>>().join(" "),
+    ))
+}
+
+/// SUBSTITUTE(text, old_text, new_text, [instance]): the text with old_text replaced,
+/// everywhere or only at the given occurrence (the first is 1)
+fn substitute(ev: &Evaluator, args: &[Expr]) -> Result<EvalResult, EvalError> {
+    let text = text_arg(ev, "SUBSTITUTE", &args[0])?;
+    let old = text_arg(ev, "SUBSTITUTE", &args[1])?;
+    let new = text_arg(ev, "SUBSTITUTE", &args[2])?;
+    if old.is_empty() {
+        return Ok(EvalResult::text(text));
+    }
+
+    let result = match args.get(3) {
+        Some(arg) => {
+            let instance = text_count_arg(ev, "SUBSTITUTE", arg, 1)?;
+            match text.match_indices(&old).nth(instance - 1) {
+                Some((index, _)) => {
+                    format!("{}{}{}", &text[..index], new, &text[index + old.len()..])
+                }
+                None => text,
+            }
+        }
+        None => text.replace(&old, &new),
+    };
+    Ok(EvalResult::text(result))
+}
+
+/// FIND(find_text, within_text, [start]): the position of find_text (the first character
+/// is 1), searching from position `start`; upper and lower case are different
+fn find(ev: &Evaluator, args: &[Expr]) -> Result<EvalResult, EvalError> {
+    let needle: Vec<char> = text_arg(ev, "FIND", &args[0])?.chars().collect();
+    let text: Vec<char> = text_arg(ev, "FIND", &args[1])?.chars().collect();
+    let start = match args.get(2) {
+        Some(arg) => text_count_arg(ev, "FIND", arg, 1)?,
+        None => 1,
+    };
+
+    let position = (start - 1..=text.len())
+        .find(|&index| text[index..].starts_with(&needle))
+        .ok_or_else(|| {
+            EvalError::InvalidOperation(format!(
+                "FIND could not find \"{}\"",
+                needle.iter().collect::<String>()
+            ))
+        })?;
+    Ok(EvalResult::new(
+        (position + 1) as f64,
+        Unit::dimensionless(),
+    ))
+}
+
+/// VALUE(text): the quantity written in the text, read as if typed into a cell
+/// VALUE("12.5 kg") = 12.5 kg and VALUE("15%") = 15%; a number is returned as it is
+fn value(ev: &Evaluator, args: &[Expr]) -> Result<EvalResult, EvalError> {
+    let result = ev.eval(&args[0])?;
+    let Some(text) = result.as_text() else {
+        return match result.value {
+            EvalValue::Number(_) | EvalValue::DateTime(_) => Ok(result),
+            _ => Err(EvalError::InvalidOperation(
+                "VALUE expects text or a number".to_string(),
+            )),
+        };
+    };
+
+    let not_a_number =
+        || EvalError::InvalidOperation(format!("VALUE could not read \"{}\" as a number", text));
+    // Text starting with = would be read as a formula
+    if text.starts_with('=') {
+        return Err(not_a_number());
+    }
+    let cell = parse_cell_input(text).map_err(|_| not_a_number())?;
+    match cell.value() {
+        CellValue::Number(n) => Ok(EvalResult::new(*n, cell.storage_unit().clone())
+            .with_uncertainty(cell.uncertainty())
+            .with_significant_figures(cell.significant_figures())),
+        CellValue::DateTime(d) => Ok(EvalResult::datetime(*d)),
+        _ => Err(not_a_number()),
+    }
+}
+
+/// TEXT(value, format): a number written with an Excel number format such as "0.00",
+/// "#,##0.0" or "0%"
+/// A unit in brackets converts the value to that unit and is written in its place:
+/// TEXT(376.3 m, "#,##0.0 [ft]") = "1,234.6 ft". A plain number is taken to be in the unit
+fn text(ev: &Evaluator, args: &[Expr]) -> Result<EvalResult, EvalError> {
+    let result = ev.eval(&args[0])?;
+    let format = text_arg(ev, "TEXT", &args[1])?;
+
+    // The unit, and the format with a placeholder where the unit is written
+    let (format, unit) = match format.split_once('[') {
+        Some((before, rest)) => {
+            let (name, after) = rest.split_once(']').ok_or_else(|| {
+                EvalError::InvalidOperation(format!("TEXT format is missing a ']': {}", format))
+            })?;
+            let name = name.trim();
+            let unit = parse_unit(name, ev.library())
+                .map_err(|_| EvalError::UnknownUnit(name.to_string()))?;
+            (
+                format!("{}\0{}", before, after),
+                Some((name.to_string(), unit)),
+            )
+        }
+        None => (format, None),
+    };
+
+    let number = match &unit {
+        Some((_, unit)) if result.is_number() && !result.unit.is_dimensionless() => {
+            convert_to(ev, &result, unit)?.numeric_value()
+        }
+        _ => result.as_number().ok_or_else(|| {
+            EvalError::InvalidOperation("TEXT can only be used with numbers".to_string())
+        })?,
+    };
+
+    let formatted = format_number(number, &format).ok_or_else(|| {
+        EvalError::InvalidOperation(format!(
+            "TEXT expects a number format such as \"0.00\", got \"{}\"",
+            format.replace('\0', "")
+        ))
+    })?;
+    let name = unit.map(|(name, _)| name).unwrap_or_default();
+    Ok(EvalResult::text(formatted.replace('\0', &name)))
+}
+
+/// Write a number with an Excel number format: 0 is a digit that is always written, # one
+/// that is written only if needed, "," separates thousands and "%" writes a fraction as a
+/// percentage. Text around the digits is kept (quotes and backslashes are dropped)
+/// Returns None if the format has no digits
+fn format_number(value: f64, format: &str) -> Option<String> {
+    let start = format.find(['0', '#'])?;
+    let end = format[start..]
+        .find(|c| !matches!(c, '0' | '#' | ',' | '.'))
+        .map_or(format.len(), |len| start + len);
+    let literal = |text: &str| text.replace(['"', '\\'], "");
+    let (prefix, code, suffix) = (
+        literal(&format[..start]),
+        &format[start..end],
+        literal(&format[end..]),
+    );
+
+    let value = if prefix.contains('%') || suffix.contains('%') {
+        value * 100.0
+    } else {
+        value
+    };
+    let (integer_code, fraction_code) = code.split_once('.').unwrap_or((code, ""));
+    let min_decimals = fraction_code.matches('0').count();
+    let max_decimals = min_decimals + fraction_code.matches('#').count();
+    let min_digits = integer_code.matches('0').count();
+
+    // Round halves away from zero (as Excel does), then drop the optional zeros at the end
+    let scale = 10f64.powi(max_decimals as i32);
+    let rounded = format!("{:.*}", max_decimals, (value.abs() * scale).round() / scale);
+    let (integer, fraction) = rounded.split_once('.').unwrap_or((&rounded, ""));
+    let mut fraction = fraction.to_string();
+    while fraction.len() > min_decimals && fraction.ends_with('0') {
+        fraction.pop();
+    }
+
+    let integer = format!(
+        "{:0>width$}",
+        integer.trim_start_matches('0'),
+        width = min_digits
+    );
+    let integer = if integer_code.contains(',') {
+        group_thousands(&integer)
+    } else {
+        integer
+    };
+
+    let negative = value < 0.0 && rounded.contains(|c: char| matches!(c, '1'..='9'));
+    let mut number = format!("{}{}{}", if negative { "-" } else { "" }, prefix, integer);
+    if !fraction.is_empty() {
+        number.push('.');
+        number.push_str(&fraction);
+    }
+    number.push_str(&suffix);
+    Some(number)
+}
+
+/// Separate the digits of a whole number into groups of three: 1234567 becomes 1,234,567
+fn group_thousands(digits: &str) -> String {
+    let mut grouped = String::new();
+    for (index, digit) in digits.chars().enumerate() {
+        if index > 0 && (digits.len() - index).is_multiple_of(3) {
+            grouped.push(',');
+        }
+        grouped.push(digit);
+    }
+    grouped
+}
+
+fn abs(ev: &Evaluator, args: &[Expr]) -> Result<EvalResult, EvalError> {
+    let (value, unit) = number_arg(ev, "ABS", &args[0])?;
+    Ok(EvalResult::new(value.abs(), unit))
+}
+
+/// ROUND(value, [decimals])
+fn round(ev: &Evaluator, args: &[Expr]) -> Result<EvalResult, EvalError> {
+    let (value, unit) = number_arg(ev, "ROUND", &args[0])?;
+    let decimals = match args.get(1) {
+        Some(arg) => number_arg(ev, "ROUND", arg)?.0.round() as i32,
+        None => 0,
+    };
+
+    let multiplier = 10f64.powi(decimals);
+    Ok(EvalResult::new(
+        (value * multiplier).round() / multiplier,
+        unit,
+    ))
+}
+
+fn floor(ev: &Evaluator, args: &[Expr]) -> Result<EvalResult, EvalError> {
+    let (value, unit) = number_arg(ev, "FLOOR", &args[0])?;
+    Ok(EvalResult::new(value.floor(), unit))
+}
+
+/// CEILING(number, [significance]): round up to the nearest multiple of significance (default 1)
+/// The significance may have its own unit, e.g. CEILING(1234 m, 1 km)
+fn ceiling(ev: &Evaluator, args: &[Expr]) -> Result<EvalResult, EvalError> {
+    let (number, unit) = number_arg(ev, "CEILING", &args[0])?;
+
+    let significance = match args.get(1) {
+        Some(arg) => {
+            let (value, sig_unit) = number_arg(ev, "CEILING", arg)?;
+            if sig_unit.is_dimensionless() || unit.is_dimensionless() {
+                value
+            } else {
+                convert_value(ev.library(), "CEILING", value, &sig_unit, &unit)?
+            }
+        }
+        None => 1.0,
+    };
+
+    if significance == 0.0 {
+        return Err(EvalError::InvalidOperation(
+            "CEILING significance cannot be zero".to_string(),
+        ));
+    }
+
+    Ok(EvalResult::new(
+        (number / significance).ceil() * significance,
+        unit,
+    ))
+}
+
+fn trunc(ev: &Evaluator, args: &[Expr]) -> Result<EvalResult, EvalError> {
+    let (value, unit) = number_arg(ev, "TRUNC", &args[0])?;
+    Ok(EvalResult::new(value.trunc(), unit))
+}
+
+/// MOD(dividend, divisor), in the dividend's unit
+fn modulo(ev: &Evaluator, args: &[Expr]) -> Result<EvalResult, EvalError> {
+    let (dividend, unit) = number_arg(ev, "MOD", &args[0])?;
+    let (divisor, divisor_unit) = number_arg(ev, "MOD", &args[1])?;
+    let divisor = convert_value(ev.library(), "MOD", divisor, &divisor_unit, &unit)?;
+    Ok(EvalResult::new(dividend % divisor, unit))
+}
+
+/// SIGN returns -1, 0 or 1
+fn sign(ev: &Evaluator, args: &[Expr]) -> Result<EvalResult, EvalError> {
+    let (value, _) = number_arg(ev, "SIGN", &args[0])?;
+    let sign = if value == 0.0 { 0.0 } else { value.signum() };
+    Ok(EvalResult::new(sign, Unit::dimensionless()))
+}
+
+/// SQRT halves the unit's exponents: SQRT(100 m²) = 10 m
+fn sqrt(ev: &Evaluator, args: &[Expr]) -> Result<EvalResult, EvalError> {
+    let result = ev.eval(&args[0])?;
+    let value = result.as_number().ok_or_else(|| {
+        EvalError::InvalidOperation("SQRT can only be used with numbers".to_string())
+    })?;
+    if value < 0.0 {
+        return Err(EvalError::InvalidOperation(
+            "SQRT of negative number is not supported (complex numbers not supported)".to_string(),
+        ));
+    }
+    let unit = transform_unit_exponents(&result.unit, 0.5, ev.library())?;
+    // First-order propagation: d(sqrt x)/dx = 1 / (2 sqrt x)
+    let uncertainty = combine_uncertainties(&[(0.5 / value.sqrt(), result.uncertainty)]);
+    Ok(EvalResult::new(value.sqrt(), unit)
+        .with_uncertainty(uncertainty)
+        .with_significant_figures(result.significant_figures))
+}
+
+/// POWER(base, exponent), the same as base ^ exponent
+fn power(ev: &Evaluator, args: &[Expr]) -> Result<EvalResult, EvalError> {
+    raise_to_power(&ev.eval(&args[0])?, &ev.eval(&args[1])?, ev.library())
+}
+
+/// IF(condition, true_value, false_value); only the chosen branch is evaluated
+fn if_fn(ev: &Evaluator, args: &[Expr]) -> Result<EvalResult, EvalError> {
+    if ev.eval_truth(&args[0], "IF")? {
+        ev.eval(&args[1])
+    } else {
+        ev.eval(&args[2])
+    }
+}
+
+/// LET(name, value, ..., body): the body, with each name standing for its value
+/// A value can use the names bound before it
+fn let_fn(ev: &Evaluator, args: &[Expr]) -> Result<EvalResult, EvalError> {
+    let Some((body, pairs)) = args.split_last().filter(|(_, pairs)| pairs.len() % 2 == 0) else {
+        return Err(EvalError::InvalidOperation(
+            "LET requires pairs of a name and a value, then a body".to_string(),
+        ));
+    };
+
+    let mut bound = Vec::new();
+    for pair in pairs.chunks(2) {
+        let Expr::NamedRef { name } = &pair[0] else {
+            return Err(EvalError::InvalidOperation(format!(
+                "LET names must be names, not {}",
+                pair[0]
+            )));
+        };
+        let value = ev.with_bindings(bound.clone()).eval(&pair[1])?;
+        bound.push((name.clone(), value));
+    }
+    ev.with_bindings(bound).eval(body)
+}
+
+fn and(ev: &Evaluator, args: &[Expr]) -> Result<EvalResult, EvalError> {
+    for arg in args {
+        if !ev.eval_truth(arg, "AND")? {
+            return Ok(Evaluator::boolean(false));
+        }
+    }
+    Ok(Evaluator::boolean(true))
+}
+
+fn or(ev: &Evaluator, args: &[Expr]) -> Result<EvalResult, EvalError> {
+    for arg in args {
+        if ev.eval_truth(arg, "OR")? {
+            return Ok(Evaluator::boolean(true));
+        }
+    }
+    Ok(Evaluator::boolean(false))
+}
+
+fn not(ev: &Evaluator, args: &[Expr]) -> Result<EvalResult, EvalError> {
+    Ok(Evaluator::boolean(!ev.eval_truth(&args[0], "NOT")?))
+}
+
+/// GT, LT, GTE, LTE, EQ and NE are the function forms of the comparison operators
+fn compare(
+    ev: &Evaluator,
+    args: &[Expr],
+    predicate: fn(Ordering) -> bool,
+) -> Result<EvalResult, EvalError> {
+    ev.eval_comparison(&args[0], &args[1], predicate)
+}
+
+/// IFERROR(value, fallback): the fallback if the value is any error
+fn iferror(ev: &Evaluator, args: &[Expr]) -> Result<EvalResult, EvalError> {
+    ev.eval(&args[0]).or_else(|_| ev.eval(&args[1]))
+}
+
+/// IFNA(value, fallback): the fallback if the value is #N/A; other errors pass through
+fn ifna(ev: &Evaluator, args: &[Expr]) -> Result<EvalResult, EvalError> {
+    match ev.eval(&args[0]) {
+        Err(e) if e.kind() == ErrorKind::NA => ev.eval(&args[1]),
+        result => result,
+    }
+}
+
+/// NA(): the #N/A error, to mark a value as missing
+fn na(_ev: &Evaluator, _args: &[Expr]) -> Result<EvalResult, EvalError> {
+    Err(EvalError::NotAvailable("NA()".to_string()))
+}
+
+/// ISERROR(value): whether the value is any error
+fn iserror(ev: &Evaluator, args: &[Expr]) -> Result<EvalResult, EvalError> {
+    Ok(Evaluator::boolean(ev.eval(&args[0]).is_err()))
+}
+
+/// ISNUMBER(value): whether the value is a number (with or without a unit); FALSE for errors
+fn isnumber(ev: &Evaluator, args: &[Expr]) -> Result<EvalResult, EvalError> {
+    let is_number = ev.eval(&args[0]).is_ok_and(|result| result.is_number());
+    Ok(Evaluator::boolean(is_number))
+}
+
+/// ISTEXT(value): whether the value is text; FALSE for errors
+fn istext(ev: &Evaluator, args: &[Expr]) -> Result<EvalResult, EvalError> {
+    let is_text = ev.eval(&args[0]).is_ok_and(|result| result.is_text());
+    Ok(Evaluator::boolean(is_text))
+}
+
+/// ISLOGICAL(value): whether the value is TRUE or FALSE; FALSE for errors
+fn islogical(ev: &Evaluator, args: &[Expr]) -> Result<EvalResult, EvalError> {
+    let is_logical = ev.eval(&args[0]).is_ok_and(|result| result.is_boolean());
+    Ok(Evaluator::boolean(is_logical))
+}
+
+/// ISBLANK(reference): whether a cell is empty; FALSE for anything but a cell reference
+fn isblank(ev: &Evaluator, args: &[Expr]) -> Result<EvalResult, EvalError> {
+    let (sheet, reference) = match &args[0] {
+        Expr::SheetRef { sheet, reference } => (Some(sheet.as_str()), reference.as_ref()),
+        arg => (None, arg),
+    };
+
+    let is_blank = match reference {
+        Expr::CellRef { col, row, .. } => ev
+            .context()
+            .is_blank(sheet, &CellAddr::new(col.clone(), *row))?,
+        _ => false,
+    };
+    Ok(Evaluator::boolean(is_blank))
+}
+
+/// ISUNIT(value, [dimension_or_unit]): whether the value is a number with a unit
+/// With a dimension name (ISUNIT(A1, "Length")) the unit must be of that dimension;
+/// with a unit (ISUNIT(A1, "km")) it must be convertible to that unit
+fn isunit(ev: &Evaluator, args: &[Expr]) -> Result<EvalResult, EvalError> {
+    let unit = match ev.eval(&args[0]) {
+        Ok(result) if result.is_number() && !result.unit.is_dimensionless() => result.unit,
+        _ => return Ok(Evaluator::boolean(false)),
+    };
+
+    let Some(target) = args.get(1) else {
+        return Ok(Evaluator::boolean(true));
+    };
+    let target = ev.eval(target)?;
+    let name = target.as_text().ok_or_else(|| {
+        EvalError::InvalidOperation(
+            "ISUNIT expects a dimension or unit name as text, e.g. \"Length\"".to_string(),
+        )
+    })?;
+
+    let matches = match dimension_by_name(name) {
+        Some(dimension) => *unit.dimension() == Dimension::Simple(dimension),
+        None => {
+            let target_unit = parse_unit(name, ev.library())
+                .map_err(|_| EvalError::UnknownUnit(name.to_string()))?;
+            unit.is_compatible(&target_unit)
+        }
+    };
+    Ok(Evaluator::boolean(matches))
+}
+
+/// Names of the base dimensions, as used by ISUNIT and DIMENSION
+const DIMENSION_NAMES: [(&str, BaseDimension); 6] = [
+    ("Length", BaseDimension::Length),
+    ("Mass", BaseDimension::Mass),
+    ("Time", BaseDimension::Time),
+    ("Currency", BaseDimension::Currency),
+    ("Temperature", BaseDimension::Temperature),
+    ("DigitalStorage", BaseDimension::DigitalStorage),
+];
+
+/// Look up a base dimension by name
+fn dimension_by_name(name: &str) -> Option<BaseDimension> {
+    DIMENSION_NAMES
+        .into_iter()
+        .find(|(dimension_name, _)| dimension_name.eq_ignore_ascii_case(name))
+        .map(|(_, dimension)| dimension)
+}
+
+/// Name of a base dimension; custom dimensions are named by their unit ("%")
+fn base_dimension_name(dimension: &BaseDimension) -> &str {
+    match dimension {
+        BaseDimension::Custom(name) => name,
+        _ => DIMENSION_NAMES
+            .iter()
+            .find(|(_, base)| base == dimension)
+            .map_or("", |(name, _)| name),
+    }
+}
+
+/// Name of a dimension: "Dimensionless", "Length", "Length^2" or "Currency/Time"
+fn dimension_name(dimension: &Dimension) -> String {
+    let factors = |factors: &[(BaseDimension, Exponent)]| {
+        // Repeated factors (Length*Length) are combined into a power
+        let mut combined: Vec<(&BaseDimension, Exponent)> = Vec::new();
+        for (base, power) in factors {
+            match combined.iter_mut().find(|(seen, _)| *seen == base) {
+                Some((_, total)) => *total += *power,
+                None => combined.push((base, *power)),
+            }
+        }
+        combined
+            .into_iter()
+            .map(|(base, power)| match power.as_integer() {
+                Some(1) => base_dimension_name(base).to_string(),
+                _ => format!("{}^{}", base_dimension_name(base), power),
+            })
+            .collect::<Vec<_>>()
+            .join("*")
+    };
+
+    match dimension {
+        Dimension::Dimensionless => "Dimensionless".to_string(),
+        Dimension::Simple(base) => base_dimension_name(base).to_string(),
+        Dimension::Compound {
+            numerator,
+            denominator,
+        } if denominator.is_empty() => factors(numerator),
+        Dimension::Compound {
+            numerator,
+            denominator,
+        } => format!(
+            "{}/{}",
+            if numerator.is_empty() {
+                "1".to_string()
+            } else {
+                factors(numerator)
+            },
+            factors(denominator)
+        ),
+    }
+}
+
+/// Evaluate an argument that must be a date; text such as "2024-03-15" is read as a date
+fn date_arg(ev: &Evaluator, name: &str, arg: &Expr) -> Result<NaiveDateTime, EvalError> {
+    let result = ev.eval(arg)?;
+    result
+        .as_datetime()
+        .or_else(|| result.as_text().and_then(datetime::parse_datetime))
+        .ok_or_else(|| EvalError::InvalidOperation(format!("{} expects a date", name)))
+}
+
+/// Evaluate a number of months: a bare number, or a duration in months, quarters or years
+fn months_arg(ev: &Evaluator, name: &str, arg: &Expr) -> Result<i32, EvalError> {
+    let (value, unit) = number_arg(ev, name, arg)?;
+    let per_unit = if unit.is_dimensionless() {
+        1
+    } else {
+        datetime::calendar_months(unit.canonical()).ok_or_else(|| {
+            EvalError::InvalidOperation(format!(
+                "{} expects a number of months, not {}",
+                name, unit
+            ))
+        })?
+    };
+    Ok(value.trunc() as i32 * per_unit as i32)
+}
+
+fn out_of_range() -> EvalError {
+    EvalError::InvalidOperation("Date out of range".to_string())
+}
+
+/// TODAY(): the current date
+fn today(_ev: &Evaluator, _args: &[Expr]) -> Result<EvalResult, EvalError> {
+    let today = Local::now().date_naive();
+    Ok(EvalResult::datetime(today.and_time(Default::default())))
+}
+
+/// NOW(): the current date and time
+fn now(_ev: &Evaluator, _args: &[Expr]) -> Result<EvalResult, EvalError> {
+    Ok(EvalResult::datetime(Local::now().naive_local()))
+}
+
+/// RAND(): a random number, at least 0 and less than 1
+fn rand(ev: &Evaluator, _args: &[Expr]) -> Result<EvalResult, EvalError> {
+    Ok(EvalResult::new(
+        ev.random().next_f64(),
+        Unit::dimensionless(),
+    ))
+}
+
+/// RANDBETWEEN(bottom, top): a random whole number from bottom to top, in bottom's unit
+/// RANDBETWEEN(1 m, 2 km) gives whole metres
+fn randbetween(ev: &Evaluator, args: &[Expr]) -> Result<EvalResult, EvalError> {
+    let (bottom, unit) = number_arg(ev, "RANDBETWEEN", &args[0])?;
+    let (top, top_unit) = number_arg(ev, "RANDBETWEEN", &args[1])?;
+    let top = convert_value(ev.library(), "RANDBETWEEN", top, &top_unit, &unit)?;
+
+    let (bottom, top) = (bottom.ceil(), top.floor());
+    if bottom > top {
+        return Err(EvalError::InvalidOperation(
+            "RANDBETWEEN requires a bottom no greater than the top".to_string(),
+        ));
+    }
+    let value = bottom + (ev.random().next_f64() * (top - bottom + 1.0)).floor();
+    Ok(EvalResult::new(value.min(top), unit))
+}
+
+/// DATE(year, month, day); months and days past the end roll over, so DATE(2024, 14, 1)
+/// is 2025-02-01
+fn date(ev: &Evaluator, args: &[Expr]) -> Result<EvalResult, EvalError> {
+    let mut parts = [0.0; 3];
+    for (part, arg) in parts.iter_mut().zip(args) {
+        let (value, unit) = number_arg(ev, "DATE", arg)?;
+        if !unit.is_dimensionless() {
+            return Err(EvalError::InvalidOperation(
+                "DATE expects a year, month and day as plain numbers".to_string(),
+            ));
+        }
+        *part = value.trunc();
+    }
+    let [year, month, day] = parts;
+
+    let start = NaiveDate::from_ymd_opt(year as i32, 1, 1).ok_or_else(out_of_range)?;
+    datetime::add_months(&start.and_time(Default::default()), month as i32 - 1)
+        .and_then(|d| d.checked_add_signed(Duration::try_days(day as i64 - 1)?))
+        .map(EvalResult::datetime)
+        .ok_or_else(out_of_range)
+}
+
+/// YEAR, MONTH and DAY: a part of a date as a number
+fn date_part(
+    ev: &Evaluator,
+    name: &str,
+    args: &[Expr],
+    part: fn(&NaiveDateTime) -> f64,
+) -> Result<EvalResult, EvalError> {
+    let date = date_arg(ev, name, &args[0])?;
+    Ok(EvalResult::new(part(&date), Unit::dimensionless()))
+}
+
+/// EDATE(start, months): the same day a number of months later (or earlier)
+fn edate(ev: &Evaluator, args: &[Expr]) -> Result<EvalResult, EvalError> {
+    let start = date_arg(ev, "EDATE", &args[0])?;
+    let months = months_arg(ev, "EDATE", &args[1])?;
+    datetime::add_months(&start.date().and_time(Default::default()), months)
+        .map(EvalResult::datetime)
+        .ok_or_else(out_of_range)
+}
+
+/// EOMONTH(start, months): the last day of the month a number of months later (or earlier)
+fn eomonth(ev: &Evaluator, args: &[Expr]) -> Result<EvalResult, EvalError> {
+    let start = date_arg(ev, "EOMONTH", &args[0])?;
+    let months = months_arg(ev, "EOMONTH", &args[1])?;
+    let first = start.date().with_day(1).ok_or_else(out_of_range)?;
+    datetime::add_months(&first.and_time(Default::default()), months)
+        .and_then(|d| datetime::end_of_month(&d))
+        .map(EvalResult::datetime)
+        .ok_or_else(out_of_range)
+}
+
+/// NETWORKDAYS(start, end, [holidays]): the number of weekdays from start to end inclusive,
+/// less any holidays, in days; negative if end is before start
+fn networkdays(ev: &Evaluator, args: &[Expr]) -> Result<EvalResult, EvalError> {
+    let start = date_arg(ev, "NETWORKDAYS", &args[0])?.date();
+    let end = date_arg(ev, "NETWORKDAYS", &args[1])?.date();
+
+    let mut holidays = Vec::new();
+    for value in ev.collect_values(&args[2..])? {
+        let holiday = value.as_datetime().ok_or_else(|| {
+            EvalError::InvalidOperation("NETWORKDAYS expects holidays to be dates".to_string())
+        })?;
+        holidays.push(holiday.date());
+    }
+
+    let (first, last, sign) = if start <= end {
+        (start, end, 1.0)
+    } else {
+        (end, start, -1.0)
+    };
+    let count = first
+        .iter_days()
+        .take_while(|day| *day <= last)
+        .filter(|day| !datetime::is_weekend(day) && !holidays.contains(day))
+        .count();
+
+    Ok(EvalResult::new(sign * count as f64, ev.time_unit("day")))
+}
+
+/// DATEDIF(start, end, unit): the time between two dates as a duration
+/// "Y", "M" and "D" count whole years, months and days; "YM" counts months ignoring years,
+/// and "MD" and "YD" count days ignoring months or years
+fn datedif(ev: &Evaluator, args: &[Expr]) -> Result<EvalResult, EvalError> {
+    let start = date_arg(ev, "DATEDIF", &args[0])?.date();
+    let end = date_arg(ev, "DATEDIF", &args[1])?.date();
+    let unit = ev.eval(&args[2])?;
+    let unit = unit.as_text().ok_or_else(|| {
+        EvalError::InvalidOperation(
+            "DATEDIF expects a unit such as \"Y\", \"M\" or \"D\"".to_string(),
+        )
+    })?;
+
+    if start > end {
+        return Err(EvalError::InvalidOperation(
+            "DATEDIF expects the start date to be on or before the end date".to_string(),
+        ));
+    }
+
+    // Whole months from start to end
+    let mut months = (end.year() - start.year()) * 12 + end.month() as i32 - start.month() as i32;
+    if end.day() < start.day() {
+        months -= 1;
+    }
+    let midnight = |d: NaiveDate| d.and_time(Default::default());
+    let days_after = |months: i32| -> Result<f64, EvalError> {
+        let anchor = datetime::add_months(&midnight(start), months).ok_or_else(out_of_range)?;
+        Ok(datetime::days_between(&anchor, &midnight(end)))
+    };
+
+    let (value, symbol) = match unit.to_uppercase().as_str() {
+        "Y" => ((months / 12) as f64, "year"),
+        "M" => (months as f64, "month"),
+        "D" => (
+            datetime::days_between(&midnight(start), &midnight(end)),
+            "day",
+        ),
+        "YM" => ((months % 12) as f64, "month"),
+        "MD" => (days_after(months)?, "day"),
+        "YD" => (days_after(months / 12 * 12)?, "day"),
+        other => {
+            return Err(EvalError::InvalidOperation(format!(
+                "DATEDIF does not know the unit \"{}\"",
+                other
+            )))
+        }
+    };
+
+    Ok(EvalResult::new(value, ev.time_unit(symbol)))
+}
+
+/// A cell read from a range: None if empty, or the cell's value or error
+type GridCell = Option<Result<EvalResult, EvalError>>;
+
+/// How a lookup value is matched against the values in a range
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum MatchMode {
+    /// An equal value
+    Exact,
+    /// An equal value, else the largest smaller value
+    NextSmaller,
+    /// An equal value, else the smallest larger value
+    NextLarger,
+    /// An equal value, where * in text matches any characters and ? any one character
+    Wildcard,
+}
+
+/// Order a candidate from a looked-up range against the lookup value
+/// Quantities are compared in the lookup value's unit, so 16 GB matches 16384 MB. Candidates
+/// that cannot be compared (another type, an incompatible unit) give None and never match
+fn lookup_order(ev: &Evaluator, candidate: &EvalResult, target: &EvalResult) -> Option<Ordering> {
+    match (&candidate.value, &target.value) {
+        (EvalValue::Number(c), EvalValue::Number(t)) => {
+            let c = if candidate.unit.is_dimensionless() || target.unit.is_dimensionless() {
+                *c
+            } else {
+                convert_value(ev.library(), "look up", *c, &candidate.unit, &target.unit).ok()?
+            };
+            // Allow for rounding in the unit conversion
+            if (c - t).abs() <= 1e-9 * c.abs().max(t.abs()) {
+                Some(Ordering::Equal)
+            } else {
+                c.partial_cmp(t)
+            }
+        }
+        _ => ev.compare_values(candidate, target).ok(),
+    }
+}
+
+/// Match text against a pattern where * is any run of characters and ? is any one character
+fn wildcard_match(pattern: &[char], text: &[char]) -> bool {
+    match pattern.split_first() {
+        None => text.is_empty(),
+        Some(('*', rest)) => (0..=text.len()).any(|skip| wildcard_match(rest, &text[skip..])),
+        Some(('?', rest)) => !text.is_empty() && wildcard_match(rest, &text[1..]),
+        Some((c, rest)) => text.first() == Some(c) && wildcard_match(rest, &text[1..]),
+    }
+}
+
+/// Position of the lookup value among the candidates, searching from the end if `reverse`
+/// The first equal value wins. Otherwise NextSmaller and NextLarger take the closest value on
+/// their side, so approximate lookups work on sorted and unsorted ranges alike
+fn find_match(
+    ev: &Evaluator,
+    target: &EvalResult,
+    candidates: &[Option<&EvalResult>],
+    mode: MatchMode,
+    reverse: bool,
+) -> Option<usize> {
+    let pattern: Option<Vec<char>> = match (mode, target.as_text()) {
+        (MatchMode::Wildcard, Some(text)) => Some(text.to_lowercase().chars().collect()),
+        _ => None,
+    };
+
+    let mut positions: Vec<usize> = (0..candidates.len()).collect();
+    if reverse {
+        positions.reverse();
+    }
+
+    let mut best: Option<(usize, &EvalResult)> = None;
+    for position in positions {
+        let Some(candidate) = candidates[position] else {
+            continue;
+        };
+
+        if let (Some(pattern), Some(text)) = (&pattern, candidate.as_text()) {
+            let text: Vec<char> = text.to_lowercase().chars().collect();
+            if wildcard_match(pattern, &text) {
+                return Some(position);
+            }
+            continue;
+        }
+
+        let wanted = match lookup_order(ev, candidate, target) {
+            Some(Ordering::Equal) => return Some(position),
+            Some(Ordering::Less) => mode == MatchMode::NextSmaller,
+            Some(Ordering::Greater) => mode == MatchMode::NextLarger,
+            None => false,
+        };
+        let closer = |(_, best): (usize, &EvalResult)| {
+            let order = lookup_order(ev, candidate, best);
+            match mode {
+                MatchMode::NextSmaller => order == Some(Ordering::Greater),
+                _ => order == Some(Ordering::Less),
+            }
+        };
+        if wanted && best.is_none_or(closer) {
+            best = Some((position, candidate));
+        }
+    }
+    best.map(|(position, _)| position)
+}
+
+/// The values of a lookup range that can be matched; empty and error cells are None
+fn candidates(cells: &[GridCell]) -> Vec<Option<&EvalResult>> {
+    cells
+        .iter()
+        .map(|cell| cell.as_ref().and_then(|result| result.as_ref().ok()))
+        .collect()
+}
+
+/// The cells of a single row or column
+fn vector_arg(ev: &Evaluator, name: &str, arg: &Expr) -> Result<Vec<GridCell>, EvalError> {
+    let grid = ev.eval_grid(arg)?;
+    if grid.len() == 1 || grid.iter().all(|row| row.len() == 1) {
+        Ok(grid.into_iter().flatten().collect())
+    } else {
+        Err(EvalError::InvalidOperation(format!(
+            "{} expects a single row or column",
+            name
+        )))
+    }
+}
+
+/// A 1-based position within `len` cells (a column number, an INDEX row), as a 0-based index
+fn position_arg(ev: &Evaluator, name: &str, arg: &Expr, len: usize) -> Result<usize, EvalError> {
+    let (value, _) = number_arg(ev, name, arg)?;
+    let position = value.trunc();
+    if position < 1.0 {
+        return Err(EvalError::InvalidOperation(format!(
+            "{} positions start at 1",
+            name
+        )));
+    }
+    if position > len as f64 {
+        return Err(EvalError::ErrorValue(CellError::new(
+            ErrorKind::Ref,
+            format!("{} position {} is outside the range", name, position),
+        )));
+    }
+    Ok(position as usize - 1)
+}
+
+/// The value of a looked-up cell; an empty cell gives 0, as in Excel
+fn lookup_value(cell: GridCell) -> Result<EvalResult, EvalError> {
+    cell.unwrap_or_else(|| Ok(EvalResult::new(0.0, Unit::dimensionless())))
+}
+
+fn not_found(name: &str) -> EvalError {
+    EvalError::NotAvailable(format!("{} found no match", name))
+}
+
+/// Swap the rows and columns of a grid or array
+fn transpose<T>(grid: Vec<Vec<T>>) -> Vec<Vec<T>> {
+    let width = grid.first().map_or(0, Vec::len);
+    let mut columns: Vec<Vec<T>> = (0..width).map(|_| Vec::with_capacity(grid.len())).collect();
+    for row in grid {
+        for (col, cell) in row.into_iter().enumerate() {
+            columns[col].push(cell);
+        }
+    }
+    columns
+}
+
+/// VLOOKUP(value, table, column, [approximate]) and HLOOKUP(value, table, row, [approximate])
+/// Find the value in the table's first column (first row for HLOOKUP) and return the cell in the
+/// given column (row) of the match. An approximate lookup, the default, falls back to the largest
+/// value below the lookup value; an exact one accepts * and ? wildcards in text
+fn table_lookup(
+    ev: &Evaluator,
+    name: &str,
+    args: &[Expr],
+    by_row: bool,
+) -> Result<EvalResult, EvalError> {
+    let target = ev.eval(&args[0])?;
+    let mut table = ev.eval_grid(&args[1])?;
+    if by_row {
+        table = transpose(table);
+    }
+
+    let width = table.first().map_or(0, Vec::len);
+    let column = position_arg(ev, name, &args[2], width)?;
+    let approximate = match args.get(3) {
+        Some(arg) => ev.eval_truth(arg, name)?,
+        None => true,
+    };
+    let mode = if approximate {
+        MatchMode::NextSmaller
+    } else {
+        MatchMode::Wildcard
+    };
+
+    let keys: Vec<GridCell> = table
+        .iter_mut()
+        .map(|row| row.first_mut().and_then(Option::take))
+        .collect();
+    let found =
+        find_match(ev, &target, &candidates(&keys), mode, false).ok_or_else(|| not_found(name))?;
+
+    if column == 0 {
+        return lookup_value(keys.into_iter().nth(found).flatten());
+    }
+    lookup_value(table.swap_remove(found).swap_remove(column))
+}
+
+fn vlookup(ev: &Evaluator, args: &[Expr]) -> Result<EvalResult, EvalError> {
+    table_lookup(ev, "VLOOKUP", args, false)
+}
+
+fn hlookup(ev: &Evaluator, args: &[Expr]) -> Result<EvalResult, EvalError> {
+    table_lookup(ev, "HLOOKUP", args, true)
+}
+
+/// XLOOKUP(value, lookup_range, return_range, [if_not_found], [match_mode], [search_mode])
+/// match_mode: 0 exact (default), -1 exact or next smaller, 1 exact or next larger, 2 wildcard
+/// search_mode: 1 first to last (default), -1 last to first; 2 and -2 search the same way
+fn xlookup(ev: &Evaluator, args: &[Expr]) -> Result<EvalResult, EvalError> {
+    let target = ev.eval(&args[0])?;
+    let keys = vector_arg(ev, "XLOOKUP", &args[1])?;
+    let mut values = vector_arg(ev, "XLOOKUP", &args[2])?;
+    if keys.len() != values.len() {
+        return Err(EvalError::InvalidOperation(
+            "XLOOKUP lookup and return ranges must be the same size".to_string(),
+        ));
+    }
+
+    let mode = match args.get(4) {
+        Some(arg) => match number_arg(ev, "XLOOKUP", arg)?.0 as i64 {
+            0 => MatchMode::Exact,
+            -1 => MatchMode::NextSmaller,
+            1 => MatchMode::NextLarger,
+            2 => MatchMode::Wildcard,
+            _ => {
+                return Err(EvalError::InvalidOperation(
+                    "XLOOKUP match mode must be 0, -1, 1 or 2".to_string(),
+                ))
+            }
+        },
+        None => MatchMode::Exact,
+    };
+    let reverse = match args.get(5) {
+        Some(arg) => match number_arg(ev, "XLOOKUP", arg)?.0 as i64 {
+            1 | 2 => false,
+            -1 | -2 => true,
+            _ => {
+                return Err(EvalError::InvalidOperation(
+                    "XLOOKUP search mode must be 1, -1, 2 or -2".to_string(),
+                ))
+            }
+        },
+        None => false,
+    };
+
+    match find_match(ev, &target, &candidates(&keys), mode, reverse) {
+        Some(found) => lookup_value(values.swap_remove(found)),
+        None => match args.get(3) {
+            Some(if_not_found) => ev.eval(if_not_found),
+            None => Err(not_found("XLOOKUP")),
+        },
+    }
+}
+
+/// INDEX(range, row, [column]): the cell at a position in a range
+/// For a single row or column, one position is enough
+fn index(ev: &Evaluator, args: &[Expr]) -> Result<EvalResult, EvalError> {
+    let mut grid = ev.eval_grid(&args[0])?;
+    let height = grid.len();
+    let width = grid.first().map_or(0, Vec::len);
+
+    let (row, col) = match args.get(2) {
+        Some(col) => (
+            position_arg(ev, "INDEX", &args[1], height)?,
+            position_arg(ev, "INDEX", col, width)?,
+        ),
+        None if height == 1 => (0, position_arg(ev, "INDEX", &args[1], width)?),
+        None if width == 1 => (position_arg(ev, "INDEX", &args[1], height)?, 0),
+        None => {
+            return Err(EvalError::InvalidOperation(
+                "INDEX needs a column for a range with several rows and columns".to_string(),
+            ))
+        }
+    };
+    lookup_value(grid.swap_remove(row).swap_remove(col))
+}
+
+/// MATCH(value, range, [match_type]): the position of the value in a single row or column
+/// match_type: 1 largest value not above (default), 0 exact (with wildcards), -1 smallest not below
+fn match_fn(ev: &Evaluator, args: &[Expr]) -> Result<EvalResult, EvalError> {
+    let target = ev.eval(&args[0])?;
+    let cells = vector_arg(ev, "MATCH", &args[1])?;
+    let mode = match args.get(2) {
+        Some(arg) => {
+            let (match_type, _) = number_arg(ev, "MATCH", arg)?;
+            match match_type.partial_cmp(&0.0) {
+                Some(Ordering::Greater) => MatchMode::NextSmaller,
+                Some(Ordering::Less) => MatchMode::NextLarger,
+                _ => MatchMode::Wildcard,
+            }
+        }
+        None => MatchMode::NextSmaller,
+    };
+
+    let found = find_match(ev, &target, &candidates(&cells), mode, false)
+        .ok_or_else(|| not_found("MATCH"))?;
+    Ok(EvalResult::new((found + 1) as f64, Unit::dimensionless()))
+}
+
+/// A reference, and the sheet it is on if it names one (Sheet2!A1)
+fn split_sheet(reference: &Expr) -> (Option<&str>, &Expr) {
+    match reference {
+        Expr::SheetRef { sheet, reference } => (Some(sheet.as_str()), reference.as_ref()),
+        _ => (None, reference),
+    }
+}
+
+/// The cells a cell reference or range covers, or None for other expressions
+fn reference_range(reference: &Expr) -> Option<CellRange> {
+    match reference {
+        Expr::CellRef { col, row, .. } => {
+            let addr = CellAddr::new(col.clone(), *row);
+            Some(CellRange::new(&addr, &addr))
+        }
+        _ => CellRange::from_expr(reference),
+    }
+}
+
+fn invalid_reference(message: String) -> EvalError {
+    EvalError::ErrorValue(CellError::new(ErrorKind::Ref, message))
+}
+
+/// INDIRECT(text): the cell or range a reference written as text points at, such as
+/// INDIRECT("B" & A1) or INDIRECT("Inputs!C3:C10"). The formula's cell depends on whichever
+/// cells the text names each time it is calculated
+fn indirect(ev: &Evaluator, args: &[Expr]) -> Result<EvalResult, EvalError> {
+    ev.eval(&indirect_reference(ev, args)?)
+}
+
+/// The reference an INDIRECT call's text names
+fn indirect_reference(ev: &Evaluator, args: &[Expr]) -> Result<Expr, EvalError> {
+    let text = text_arg(ev, "INDIRECT", &args[0])?;
+    let invalid = || invalid_reference(format!("INDIRECT: '{}' is not a reference", text));
+
+    let expr = parse_formula(&text).map_err(|_| invalid())?;
+    let (sheet, reference) = split_sheet(&expr);
+    let range = reference_range(reference).ok_or_else(invalid)?;
+
+    ev.record_reference(sheet, range);
+    Ok(expr)
+}
+
+/// A number of rows or columns for OFFSET, of which the whole part is used
+fn offset_arg(ev: &Evaluator, arg: &Expr) -> Result<i64, EvalError> {
+    let (value, unit) = number_arg(ev, "OFFSET", arg)?;
+    if !unit.is_dimensionless() {
+        return Err(EvalError::InvalidOperation(
+            "OFFSET expects plain numbers of rows and columns".to_string(),
+        ));
+    }
+    Ok(value.trunc() as i64)
+}
+
+/// The height or width of the cells OFFSET returns, at least 1
+fn offset_size_arg(ev: &Evaluator, arg: Option<&Expr>, default: usize) -> Result<usize, EvalError> {
+    let Some(arg) = arg else {
+        return Ok(default);
+    };
+    match offset_arg(ev, arg)? {
+        size if size >= 1 => Ok(size as usize),
+        _ => Err(EvalError::InvalidOperation(
+            "OFFSET height and width must be at least 1".to_string(),
+        )),
+    }
+}
+
+/// OFFSET(reference, rows, cols, [height], [width]): the cells `rows` down and `cols` to the
+/// right of a cell or range, `height` by `width` cells (the size of the reference by default).
+/// Negative offsets move up and to the left. As with INDIRECT, the formula's cell depends on
+/// the cells OFFSET lands on each time it is calculated. A range past the edge of the sheet,
+/// or of more than `MAX_ARRAY_CELLS` cells, is #REF!
+fn offset(ev: &Evaluator, args: &[Expr]) -> Result<EvalResult, EvalError> {
+    ev.eval(&offset_reference(ev, args)?)
+}
+
+/// The reference an OFFSET call lands on
+fn offset_reference(ev: &Evaluator, args: &[Expr]) -> Result<Expr, EvalError> {
+    let (sheet, reference) = split_sheet(&args[0]);
+    let start = match reference {
+        Expr::CellRef { .. } | Expr::Range { .. } => reference_range(reference),
+        _ => None,
+    }
+    .ok_or_else(|| {
+        EvalError::InvalidOperation("OFFSET starts from a cell or a range of cells".to_string())
+    })?;
+
+    let rows = offset_arg(ev, &args[1])?;
+    let cols = offset_arg(ev, &args[2])?;
+    let height = offset_size_arg(ev, args.get(3), start.end_row - start.start_row + 1)?;
+    let width = offset_size_arg(ev, args.get(4), start.end_col - start.start_col + 1)?;
+    if height.saturating_mul(width) > MAX_ARRAY_CELLS {
+        return Err(invalid_reference(format!(
+            "OFFSET's {}x{} range is more than the {} cells a formula can use",
+            height, width, MAX_ARRAY_CELLS
+        )));
+    }
+
+    let (range, target) = start
+        .offset(rows, cols, height, width)
+        .filter(|range| range.end_row <= MAX_ROWS && range.end_col <= MAX_COLUMNS)
+        .and_then(|range| Some((range, range.to_expr()?)))
+        .ok_or_else(|| invalid_reference("OFFSET goes past the edge of the sheet".to_string()))?;
+
+    ev.record_reference(sheet, range);
+    Ok(match sheet {
+        Some(sheet) => Expr::sheet_ref(sheet, target),
+        None => target,
+    })
+}
+
+/// How a criterion compares cells with its value
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum CriterionOp {
+    Equal,
+    NotEqual,
+    Less,
+    LessOrEqual,
+    Greater,
+    GreaterOrEqual,
+}
+
+impl CriterionOp {
+    fn test(self, ordering: Ordering) -> bool {
+        match self {
+            CriterionOp::Equal => ordering.is_eq(),
+            CriterionOp::NotEqual => ordering.is_ne(),
+            CriterionOp::Less => ordering.is_lt(),
+            CriterionOp::LessOrEqual => ordering.is_le(),
+            CriterionOp::Greater => ordering.is_gt(),
+            CriterionOp::GreaterOrEqual => ordering.is_ge(),
+        }
+    }
+}
+
+/// A condition that SUMIF, COUNTIFS and the rest test cells against
+struct Criterion {
+    op: CriterionOp,
+    /// The value to compare with; None for a blank cell ("" or "<>")
+    value: Option<EvalResult>,
+}
+
+impl Criterion {
+    /// Read a criteria argument: a value to equal, or text such as ">=16 GB", "<2 hr", "<>east"
+    fn parse(ev: &Evaluator, criteria: EvalResult) -> Self {
+        let Some(text) = criteria.as_text() else {
+            return Self {
+                op: CriterionOp::Equal,
+                value: Some(criteria),
+            };
+        };
+
+        let operators = [
+            (">=", CriterionOp::GreaterOrEqual),
+            ("<=", CriterionOp::LessOrEqual),
+            ("<>", CriterionOp::NotEqual),
+            (">", CriterionOp::Greater),
+            ("<", CriterionOp::Less),
+            ("=", CriterionOp::Equal),
+        ];
+        let (op, operand) = operators
+            .iter()
+            .find_map(|(symbol, op)| text.strip_prefix(symbol).map(|rest| (*op, rest)))
+            .unwrap_or((CriterionOp::Equal, text));
+
+        let operand = operand.trim();
+        let value = (!operand.is_empty()).then(|| criterion_value(ev, operand));
+        Self { op, value }
+    }
+
+    /// Check whether a cell meets the criterion
+    /// Text equality is case-insensitive and allows * and ? wildcards. Quantities compare across
+    /// units; a cell that cannot be compared (other type, incompatible unit) only meets "<>"
+    fn matches(&self, ev: &Evaluator, cell: &GridCell) -> bool {
+        let cell = match cell {
+            Some(Ok(cell)) => cell,
+            Some(Err(_)) => return false,
+            // A blank cell is equal to "" and different from any value
+            None => {
+                return match self.op {
+                    CriterionOp::Equal => self.value.is_none(),
+                    CriterionOp::NotEqual => self.value.is_some(),
+                    _ => false,
+                }
+            }
+        };
+        let Some(value) = &self.value else {
+            return self.op == CriterionOp::NotEqual;
+        };
+
+        if let (Some(pattern), Some(text)) = (value.as_text(), cell.as_text()) {
+            if matches!(self.op, CriterionOp::Equal | CriterionOp::NotEqual) {
+                let pattern: Vec<char> = pattern.to_lowercase().chars().collect();
+                let text: Vec<char> = text.to_lowercase().chars().collect();
+                return wildcard_match(&pattern, &text) == (self.op == CriterionOp::Equal);
+            }
+        }
+
+        match lookup_order(ev, cell, value) {
+            Some(ordering) => self.op.test(ordering),
+            None => self.op == CriterionOp::NotEqual,
+        }
+    }
+}
+
+/// The value in a criterion: a boolean, a date, a quantity such as "16 GB", or else text
+fn criterion_value(ev: &Evaluator, text: &str) -> EvalResult {
+    if text.eq_ignore_ascii_case("true") || text.eq_ignore_ascii_case("false") {
+        return EvalResult::boolean(text.eq_ignore_ascii_case("true"));
+    }
+    if let Some(date) = datetime::parse_datetime(text) {
+        return EvalResult::datetime(date);
+    }
+
+    let quantity = match parse_formula(text) {
+        Ok(expr @ (Expr::Number(_) | Expr::NumberWithUnit { .. } | Expr::Negate(_))) => {
+            Evaluator::new(ev.library()).eval(&expr).ok()
+        }
+        _ => None,
+    };
+    match quantity {
+        Some(result) if result.is_number() => result,
+        _ => EvalResult::text(text.to_string()),
+    }
+}
+
+/// The cells of a range argument, row by row, with the range's size (rows, columns)
+fn cells_arg(ev: &Evaluator, arg: &Expr) -> Result<(Vec<GridCell>, (usize, usize)), EvalError> {
+    let grid = ev.eval_grid(arg)?;
+    let size = (grid.len(), grid.first().map_or(0, Vec::len));
+    Ok((grid.into_iter().flatten().collect(), size))
+}
+
+/// Which cells meet all the criteria, given as (range, criteria) pairs of arguments
+/// All the ranges must have the same size, as must the values range if there is one
+fn criteria_mask(
+    ev: &Evaluator,
+    name: &str,
+    pairs: &[Expr],
+    mut size: Option<(usize, usize)>,
+) -> Result<Vec<bool>, EvalError> {
+    if !pairs.len().is_multiple_of(2) {
+        return Err(EvalError::InvalidOperation(format!(
+            "{} expects ranges and criteria in pairs",
+            name
+        )));
+    }
+
+    let mut mask: Option<Vec<bool>> = None;
+    for pair in pairs.chunks(2) {
+        let (cells, range_size) = cells_arg(ev, &pair[0])?;
+        if size.is_some_and(|size| size != range_size) {
+            return Err(EvalError::InvalidOperation(format!(
+                "{} ranges must be the same size",
+                name
+            )));
+        }
+        size = Some(range_size);
+
+        let criterion = Criterion::parse(ev, ev.eval(&pair[1])?);
+        let meets = cells.iter().map(|cell| criterion.matches(ev, cell));
+        mask = Some(match mask {
+            Some(mask) => mask.into_iter().zip(meets).map(|(a, b)| a && b).collect(),
+            None => meets.collect(),
+        });
+    }
+    Ok(mask.unwrap_or_default())
+}
+
+/// The values of a range at the cells meeting the criteria, converted to a common unit as SUM does
+/// Empty, text and boolean cells are skipped; an error in a selected cell propagates
+fn matching_values(
+    ev: &Evaluator,
+    name: &str,
+    values: &Expr,
+    criteria: &[Expr],
+) -> Result<(Vec<f64>, Unit), EvalError> {
+    let (cells, size) = cells_arg(ev, values)?;
+    let mask = criteria_mask(ev, name, criteria, Some(size))?;
+
+    let mut selected = Vec::new();
+    for (cell, meets) in cells.into_iter().zip(mask) {
+        match cell {
+            Some(Ok(value)) if meets && !(value.is_text() || value.is_boolean()) => {
+                selected.push(value)
+            }
+            Some(Err(e)) if meets => return Err(e),
+            _ => {}
+        }
+    }
+    to_common_unit(ev, name, &selected)
+}
+
+/// COUNTIF(range, criteria) and COUNTIFS(range1, criteria1, ...): the number of cells meeting
+/// all the criteria
+fn count_matching(ev: &Evaluator, name: &str, args: &[Expr]) -> Result<EvalResult, EvalError> {
+    let count = criteria_mask(ev, name, args, None)?
+        .into_iter()
+        .filter(|meets| *meets)
+        .count();
+    Ok(EvalResult::new(count as f64, Unit::dimensionless()))
+}
+
+/// SUMIF(range, criteria, [sum_range]): the sum of the cells (of sum_range, if given) where
+/// the range meets the criteria
+fn sumif(ev: &Evaluator, args: &[Expr]) -> Result<EvalResult, EvalError> {
+    let values = args.get(2).unwrap_or(&args[0]);
+    let (values, unit) = matching_values(ev, "SUMIF", values, &args[..2])?;
+    Ok(EvalResult::new(values.iter().fold(0.0, |a, b| a + b), unit))
+}
+
+/// SUMIFS(sum_range, range1, criteria1, ...)
+fn sumifs(ev: &Evaluator, args: &[Expr]) -> Result<EvalResult, EvalError> {
+    let (values, unit) = matching_values(ev, "SUMIFS", &args[0], &args[1..])?;
+    Ok(EvalResult::new(values.iter().fold(0.0, |a, b| a + b), unit))
+}
+
+/// The mean of the selected values; no values at all is a division by zero, as in Excel
+fn average_of(values: Vec<f64>, unit: Unit) -> Result<EvalResult, EvalError> {
+    if values.is_empty() {
+        return Err(EvalError::DivisionByZero);
+    }
+    let sum: f64 = values.iter().sum();
+    Ok(EvalResult::new(sum / values.len() as f64, unit))
+}
+
+/// AVERAGEIF(range, criteria, [average_range])
+fn averageif(ev: &Evaluator, args: &[Expr]) -> Result<EvalResult, EvalError> {
+    let values = args.get(2).unwrap_or(&args[0]);
+    let (values, unit) = matching_values(ev, "AVERAGEIF", values, &args[..2])?;
+    average_of(values, unit)
+}
+
+/// AVERAGEIFS(average_range, range1, criteria1, ...)
+fn averageifs(ev: &Evaluator, args: &[Expr]) -> Result<EvalResult, EvalError> {
+    let (values, unit) = matching_values(ev, "AVERAGEIFS", &args[0], &args[1..])?;
+    average_of(values, unit)
+}
+
+/// MAXIFS(max_range, range1, criteria1, ...); 0 if no cell meets the criteria
+fn maxifs(ev: &Evaluator, args: &[Expr]) -> Result<EvalResult, EvalError> {
+    let (values, unit) = matching_values(ev, "MAXIFS", &args[0], &args[1..])?;
+    Ok(EvalResult::new(
+        values.into_iter().reduce(f64::max).unwrap_or(0.0),
+        unit,
+    ))
+}
+
+/// MINIFS(min_range, range1, criteria1, ...); 0 if no cell meets the criteria
+fn minifs(ev: &Evaluator, args: &[Expr]) -> Result<EvalResult, EvalError> {
+    let (values, unit) = matching_values(ev, "MINIFS", &args[0], &args[1..])?;
+    Ok(EvalResult::new(
+        values.into_iter().reduce(f64::min).unwrap_or(0.0),
+        unit,
+    ))
+}
+
+/// A whole number of rows or columns for SEQUENCE, up to `max`
+fn count_arg(ev: &Evaluator, arg: Option<&Expr>, max: usize) -> Result<usize, EvalError> {
+    let Some(arg) = arg else {
+        return Ok(1);
+    };
+    let (value, unit) = number_arg(ev, "SEQUENCE", arg)?;
+    let count = value.trunc();
+    if !unit.is_dimensionless() || !(1.0..=max as f64).contains(&count) {
+        return Err(EvalError::InvalidOperation(format!(
+            "SEQUENCE needs between 1 and {} rows and {} columns",
+            MAX_ROWS, MAX_COLUMNS
+        )));
+    }
+    Ok(count as usize)
+}
+
+/// SEQUENCE(rows, [columns], [start], [step]): numbers counting up from start (default 1),
+/// filling each row in turn. A start with a unit counts in that unit (SEQUENCE(3, 1, 0 m, 50 cm)),
+/// and a date start gives dates, stepping by days or a duration (SEQUENCE(12, 1, A1, 1 month))
+fn sequence(ev: &Evaluator, args: &[Expr]) -> Result<EvalResult, EvalError> {
+    let rows = count_arg(ev, args.first(), MAX_ROWS)?;
+    let cols = count_arg(ev, args.get(1), MAX_COLUMNS)?;
+    if rows * cols > MAX_ARRAY_CELLS {
+        return Err(EvalError::InvalidOperation(format!(
+            "SEQUENCE can make at most {} values",
+            MAX_ARRAY_CELLS
+        )));
+    }
+
+    let start = match args.get(2) {
+        Some(arg) => ev.eval_operand(arg)?,
+        None => EvalResult::new(1.0, Unit::dimensionless()),
+    };
+    let step = match args.get(3) {
+        Some(arg) => ev.eval_operand(arg)?,
+        // One of the start's unit, or one day from a date
+        None if start.is_number() => EvalResult::new(1.0, start.unit.clone()),
+        None => EvalResult::new(1.0, Unit::dimensionless()),
+    };
+    if !(start.is_number() || start.is_datetime()) || !step.is_number() {
+        return Err(EvalError::InvalidOperation(
+            "SEQUENCE counts from a number or a date, by a number".to_string(),
+        ));
+    }
+
+    // Each value is start + n * step, so calendar steps do not drift (Jan 31 + 2 months)
+    let values = (0..rows)
+        .map(|row| {
+            (0..cols)
+                .map(|col| {
+                    let n = (row * cols + col) as f64;
+                    let offset = EvalResult::new(n * step.numeric_value(), step.unit.clone());
+                    ev.add_values(&start, &offset)
+                })
+                .collect()
+        })
+        .collect::<Result<_, _>>()?;
+    Ok(EvalResult::array(values))
+}
+
+/// What SORT orders by: numbers (in one unit), then dates, text and booleans, as in Excel
+#[derive(Debug, Clone, PartialEq, PartialOrd)]
+enum SortKey {
+    Number(f64),
+    Date(NaiveDateTime),
+    /// Text sorts case-insensitively
+    Text(String),
+    Boolean(bool),
+}
+
+/// Sort keys for values; numbers are converted to the unit of the first number with a unit,
+/// so 1 km sorts after 900 m
+fn sort_keys(ev: &Evaluator, values: &[&EvalResult]) -> Result<Vec<SortKey>, EvalError> {
+    let unit = values
+        .iter()
+        .find(|value| value.is_number() && !value.unit.is_dimensionless())
+        .map(|value| value.unit.clone());
+
+    values
+        .iter()
+        .map(|value| {
+            Ok(match &value.value {
+                EvalValue::Number(n) => match &unit {
+                    Some(unit) if !value.unit.is_dimensionless() => {
+                        SortKey::Number(convert_value(ev.library(), "SORT", *n, &value.unit, unit)?)
+                    }
+                    _ => SortKey::Number(*n),
+                },
+                EvalValue::DateTime(d) => SortKey::Date(*d),
+                EvalValue::Text(t) => SortKey::Text(t.to_lowercase()),
+                EvalValue::Boolean(b) => SortKey::Boolean(*b),
+                EvalValue::Array(_) => {
+                    return Err(EvalError::InvalidOperation(
+                        "SORT cannot sort nested arrays".to_string(),
+                    ))
+                }
+            })
+        })
+        .collect()
+}
+
+/// Evaluate an optional TRUE/FALSE argument
+fn flag_arg(ev: &Evaluator, name: &str, arg: Option<&Expr>) -> Result<bool, EvalError> {
+    match arg {
+        Some(arg) => ev.eval_truth(arg, name),
+        None => Ok(false),
+    }
+}
+
+/// SORT(array, [sort_index], [sort_order], [by_col]): the rows of an array sorted by one of
+/// its columns (by_col sorts the columns by one of the rows instead)
+/// sort_order: 1 ascending (default), -1 descending. Quantities sort by size across units
+fn sort(ev: &Evaluator, args: &[Expr]) -> Result<EvalResult, EvalError> {
+    let by_col = flag_arg(ev, "SORT", args.get(3))?;
+    let mut array = ev.eval_array(&args[0])?;
+    if by_col {
+        array = transpose(array);
+    }
+
+    let width = array.first().map_or(0, Vec::len);
+    let index = match args.get(1) {
+        Some(arg) => position_arg(ev, "SORT", arg, width)?,
+        None => 0,
+    };
+    let descending = match args.get(2) {
+        Some(arg) => match number_arg(ev, "SORT", arg)?.0 as i64 {
+            1 => false,
+            -1 => true,
+            _ => {
+                return Err(EvalError::InvalidOperation(
+                    "SORT order must be 1 or -1".to_string(),
+                ))
+            }
+        },
+        None => false,
+    };
+
+    let column: Vec<&EvalResult> = array.iter().map(|row| &row[index]).collect();
+    let keys = sort_keys(ev, &column)?;
+    let mut order: Vec<usize> = (0..array.len()).collect();
+    order.sort_by(|&a, &b| {
+        let ordering = keys[a].partial_cmp(&keys[b]).unwrap_or(Ordering::Equal);
+        if descending {
+            ordering.reverse()
+        } else {
+            ordering
+        }
+    });
+
+    let mut rows: Vec<Option<Vec<EvalResult>>> = array.into_iter().map(Some).collect();
+    let mut sorted: Array = order.into_iter().filter_map(|i| rows[i].take()).collect();
+    if by_col {
+        sorted = transpose(sorted);
+    }
+    Ok(EvalResult::array(sorted))
+}
+
+/// Whether two rows of an array hold the same values (quantities compared across units)
+fn same_row(ev: &Evaluator, a: &[EvalResult], b: &[EvalResult]) -> bool {
+    a.len() == b.len()
+        && a.iter()
+            .zip(b)
+            .all(|(a, b)| lookup_order(ev, a, b) == Some(Ordering::Equal))
+}
+
+/// UNIQUE(array, [by_col], [exactly_once]): the distinct rows of an array, in order of first
+/// appearance (by_col compares columns instead); exactly_once keeps only rows that appear once
+fn unique(ev: &Evaluator, args: &[Expr]) -> Result<EvalResult, EvalError> {
+    let by_col = flag_arg(ev, "UNIQUE", args.get(1))?;
+    let exactly_once = flag_arg(ev, "UNIQUE", args.get(2))?;
+    let mut array = ev.eval_array(&args[0])?;
+    if by_col {
+        array = transpose(array);
+    }
+
+    let occurrences = |row: &[EvalResult]| {
+        array
+            .iter()
+            .filter(|other| same_row(ev, row, other))
+            .count()
+    };
+    let mut distinct: Array = Vec::new();
+    for row in &array {
+        if distinct.iter().any(|seen| same_row(ev, row, seen)) {
+            continue;
+        }
+        if !exactly_once || occurrences(row) == 1 {
+            distinct.push(row.clone());
+        }
+    }
+
+    if distinct.is_empty() {
+        return Err(EvalError::NotAvailable(
+            "UNIQUE found no values that appear once".to_string(),
+        ));
+    }
+    if by_col {
+        distinct = transpose(distinct);
+    }
+    Ok(EvalResult::array(distinct))
+}
+
+/// FILTER(array, include, [if_empty]): the rows of an array where `include` (a column as tall as
+/// the array, such as B2:B10 > 5 kg) is TRUE, or the columns where a row as wide as it is TRUE
+fn filter(ev: &Evaluator, args: &[Expr]) -> Result<EvalResult, EvalError> {
+    let array = ev.eval_array(&args[0])?;
+    let include = ev.eval_array(&args[1])?;
+    let (rows, cols) = (array.len(), array.first().map_or(0, Vec::len));
+
+    let keep = |value: &EvalResult| match value.value {
+        EvalValue::Boolean(b) => Ok(b),
+        EvalValue::Number(n) => Ok(n != 0.0),
+        _ => Err(EvalError::InvalidOperation(
+            "FILTER include values must be TRUE or FALSE".to_string(),
+        )),
+    };
+
+    let filtered: Array = if include.len() == rows && include.iter().all(|row| row.len() == 1) {
+        let mut kept = Vec::new();
+        for (row, flag) in array.into_iter().zip(include.iter().flatten()) {
+            if keep(flag)? {
+                kept.push(row);
+            }
+        }
+        kept
+    } else if include.len() == 1 && include[0].len() == cols {
+        let flags = include[0].iter().map(keep).collect::<Result<Vec<_>, _>>()?;
+        array
+            .into_iter()
+            .map(|row| {
+                row.into_iter()
+                    .zip(&flags)
+                    .filter(|(_, keep)| **keep)
+                    .map(|(value, _)| value)
+                    .collect::<Vec<_>>()
+            })
+            .filter(|row| !row.is_empty())
+            .collect()
+    } else {
+        return Err(EvalError::InvalidOperation(format!(
+            "FILTER include must be a column of {} or a row of {} values",
+            rows, cols
+        )));
+    };
+
+    if filtered.is_empty() {
+        return match args.get(2) {
+            Some(if_empty) => ev.eval(if_empty),
+            None => Err(EvalError::NotAvailable(
+                "FILTER found no matching values".to_string(),
+            )),
+        };
+    }
+    Ok(EvalResult::array(filtered))
+}
+
+/// The time unit a quantity is per: year for 6 %/year, month for 1500 USD/month
+fn per_time(ev: &Evaluator, unit: &Unit) -> Option<Unit> {
+    let (_, denominator) = extract_unit_symbols(unit);
+    let denominator: Vec<_> = denominator.into_iter().collect();
+    let [(symbol, power)] = denominator.as_slice() else {
+        return None;
+    };
+    if *power != 1 {
+        return None;
+    }
+    let time = parse_unit(symbol, ev.library()).ok()?;
+    (time.dimension().as_simple() == Some(&BaseDimension::Time)).then_some(time)
+}
+
+/// Evaluate an interest rate: a number or percentage per period (0.5 %), or a percentage per
+/// time (6 %/year), which also says how long a period is
+fn rate_arg(ev: &Evaluator, name: &str, arg: &Expr) -> Result<(f64, Option<Unit>), EvalError> {
+    let (rate, unit) = number_arg(ev, name, arg)?;
+    let (numerator, denominator) = extract_unit_symbols(&unit);
+    let period = per_time(ev, &unit);
+    if numerator.keys().any(|symbol| symbol != "%") || (period.is_none() && !denominator.is_empty())
+    {
+        return Err(EvalError::InvalidOperation(format!(
+            "{} expects a rate such as 6 %/year, not {}",
+            name, unit
+        )));
+    }
+    Ok((rate, period))
+}
+
+/// The rate per period and number of periods of a loan or investment
+struct Schedule {
+    rate: f64,
+    periods: f64,
+    /// How long a period is, if the rate or number of periods says
+    period: Option<Unit>,
+}
+
+/// Evaluate the rate and number of periods. A number of periods given as a time (360 month) sets
+/// the period, and the rate is converted to it (6 %/year is 0.5 % a month); otherwise the period
+/// is the rate's (6 %/year over 30 periods is 30 years)
+fn schedule_args(ev: &Evaluator, name: &str, args: &[Expr]) -> Result<Schedule, EvalError> {
+    let (rate, rate_period) = rate_arg(ev, name, &args[0])?;
+    let (periods, unit) = number_arg(ev, name, &args[1])?;
+    if unit.is_dimensionless() {
+        return Ok(Schedule {
+            rate,
+            periods,
+            period: rate_period,
+        });
+    }
+    if unit.dimension().as_simple() != Some(&BaseDimension::Time) {
+        return Err(EvalError::InvalidOperation(format!(
+            "{} expects a number of periods or a time such as 30 year, not {}",
+            name, unit
+        )));
+    }
+
+    let rate = match &rate_period {
+        Some(rate_period) => rate * convert_value(ev.library(), name, 1.0, &unit, rate_period)?,
+        None => rate,
+    };
+    Ok(Schedule {
+        rate,
+        periods,
+        period: Some(unit),
+    })
+}
+
+/// Evaluate an optional amount, which defaults to 0
+fn amount_arg(ev: &Evaluator, name: &str, arg: Option<&Expr>) -> Result<EvalResult, EvalError> {
+    match arg {
+        Some(arg) => number_arg(ev, name, arg).map(|(value, unit)| EvalResult::new(value, unit)),
+        None => Ok(EvalResult::new(0.0, Unit::dimensionless())),
+    }
+}
+
+/// The amount paid in one period: 1500 USD/month is 4500 USD a quarter. An amount per time
+/// without a known period is paid once per that time
+fn amount_per_period(
+    ev: &Evaluator,
+    amount: EvalResult,
+    period: Option<&Unit>,
+) -> Result<EvalResult, EvalError> {
+    let Some(time) = per_time(ev, &amount.unit) else {
+        return Ok(amount);
+    };
+    let period = period.cloned().unwrap_or(time);
+    ev.multiply_values(&amount, &EvalResult::new(1.0, period))
+}
+
+/// Convert amounts to the unit of the first one that has a unit, so a bare 0 goes with 100 USD
+fn common_amounts(
+    ev: &Evaluator,
+    name: &str,
+    amounts: &[EvalResult],
+) -> Result<(Vec<f64>, Unit), EvalError> {
+    let unit = amounts
+        .iter()
+        .map(|amount| &amount.unit)
+        .find(|unit| !unit.is_dimensionless())
+        .cloned()
+        .unwrap_or_else(Unit::dimensionless);
+
+    let values = amounts
+        .iter()
+        .map(|amount| {
+            let value = amount.as_number().ok_or_else(|| {
+                EvalError::InvalidOperation(format!("{} can only be used with numbers", name))
+            })?;
+            if amount.unit.is_dimensionless() {
+                Ok(value)
+            } else {
+                convert_value(ev.library(), name, value, &amount.unit, &unit)
+            }
+        })
+        .collect::<Result<_, _>>()?;
+    Ok((values, unit))
+}
+
+/// Growth over the schedule, (1 + rate)^periods, and the factor that turns a payment each
+/// period into its total at the end; payments at the start of a period earn one more period
+fn growth(schedule: &Schedule, at_start: bool) -> (f64, f64) {
+    let Schedule { rate, periods, .. } = *schedule;
+    let growth = (1.0 + rate).powf(periods);
+    let annuity = if rate == 0.0 {
+        periods
+    } else {
+        (growth - 1.0) / rate * if at_start { 1.0 + rate } else { 1.0 }
+    };
+    (growth, annuity)
+}
+
+/// The schedule with payments made every `period` ("month"): the rate and number of periods
+/// are converted to it, so 6 %/year over 30 year is 0.5 % a month over 360 months
+fn with_payment_period(
+    ev: &Evaluator,
+    name: &str,
+    schedule: Schedule,
+    period: &Expr,
+) -> Result<Schedule, EvalError> {
+    let period = unit_arg(ev, period)?;
+    if period.dimension().as_simple() != Some(&BaseDimension::Time) {
+        return Err(EvalError::InvalidOperation(format!(
+            "{} expects a payment period such as \"month\", not {}",
+            name, period
+        )));
+    }
+    let Some(current) = schedule.period else {
+        return Err(EvalError::InvalidOperation(format!(
+            "{} needs a rate per time or nper as a time to set the payment period",
+            name
+        )));
+    };
+    Ok(Schedule {
+        rate: schedule.rate * convert_value(ev.library(), name, 1.0, &period, &current)?,
+        periods: convert_value(ev.library(), name, schedule.periods, &current, &period)?,
+        period: Some(period),
+    })
+}
+
+/// PMT(rate, nper, pv, [fv], [type], [period]): the payment each period that pays off pv (and
+/// leaves fv). The result is per period, so PMT(6 %/year, 360 month, 300000 USD) is in USD/month
+/// and PMT(6 %/year, 30 year, 300000 USD) is the yearly payment in USD/year. A yearly payment
+/// shown per month is a twelfth of it, not the monthly payment, so give the payment period to
+/// work that out instead: PMT(6 %/year, 30 year, 300000 USD, 0, 0, "month"). As in Excel, money
+/// paid out is negative; type 1 means payments at the start of each period
+fn pmt(ev: &Evaluator, args: &[Expr]) -> Result<EvalResult, EvalError> {
+    let mut schedule = schedule_args(ev, "PMT", args)?;
+    if let Some(period) = args.get(5) {
+        schedule = with_payment_period(ev, "PMT", schedule, period)?;
+    }
+    let amounts = [
+        amount_arg(ev, "PMT", args.get(2))?,
+        amount_arg(ev, "PMT", args.get(3))?,
+    ];
+    let (amounts, unit) = common_amounts(ev, "PMT", &amounts)?;
+    let (growth, annuity) = growth(&schedule, flag_arg(ev, "PMT", args.get(4))?);
+    if annuity == 0.0 {
+        return Err(EvalError::DivisionByZero);
+    }
+
+    let payment = EvalResult::new(-(amounts[0] * growth + amounts[1]) / annuity, unit);
+    match &schedule.period {
+        Some(period) if !payment.unit.is_dimensionless() => {
+            ev.divide_values(&payment, &EvalResult::new(1.0, period.clone()))
+        }
+        _ => Ok(payment),
+    }
+}
+
+/// FV(rate, nper, pmt, [pv], [type]): the value at the end of the schedule of pv and the
+/// payments. pmt can be per time (500 USD/month)
+fn fv(ev: &Evaluator, args: &[Expr]) -> Result<EvalResult, EvalError> {
+    let schedule = schedule_args(ev, "FV", args)?;
+    let payment = amount_arg(ev, "FV", args.get(2))?;
+    let amounts = [
+        amount_per_period(ev, payment, schedule.period.as_ref())?,
+        amount_arg(ev, "FV", args.get(3))?,
+    ];
+    let (amounts, unit) = common_amounts(ev, "FV", &amounts)?;
+    let (growth, annuity) = growth(&schedule, flag_arg(ev, "FV", args.get(4))?);
+    Ok(EvalResult::new(
+        -(amounts[1] * growth + amounts[0] * annuity),
+        unit,
+    ))
+}
+
+/// PV(rate, nper, pmt, [fv], [type]): the value now of the payments (and fv at the end)
+fn pv(ev: &Evaluator, args: &[Expr]) -> Result<EvalResult, EvalError> {
+    let schedule = schedule_args(ev, "PV", args)?;
+    let payment = amount_arg(ev, "PV", args.get(2))?;
+    let amounts = [
+        amount_per_period(ev, payment, schedule.period.as_ref())?,
+        amount_arg(ev, "PV", args.get(3))?,
+    ];
+    let (amounts, unit) = common_amounts(ev, "PV", &amounts)?;
+    let (growth, annuity) = growth(&schedule, flag_arg(ev, "PV", args.get(4))?);
+    Ok(EvalResult::new(
+        -(amounts[1] + amounts[0] * annuity) / growth,
+        unit,
+    ))
+}
+
+/// Cash flows for NPV and IRR, each converted to an amount per period
+fn cash_flows(
+    ev: &Evaluator,
+    name: &str,
+    flows: Vec<EvalResult>,
+    period: Option<&Unit>,
+) -> Result<(Vec<f64>, Unit), EvalError> {
+    let flows = flows
+        .into_iter()
+        .map(|flow| amount_per_period(ev, flow, period))
+        .collect::<Result<Vec<_>, _>>()?;
+    common_amounts(ev, name, &flows)
+}
+
+/// Value now of cash flows at the end of each period
+fn discounted(rate: f64, flows: &[f64]) -> f64 {
+    flows
+        .iter()
+        .zip(1..)
+        .map(|(flow, n)| flow / (1.0 + rate).powi(n))
+        .sum()
+}
+
+/// NPV(rate, value1, [value2], ...): the value now of cash flows at the end of each period
+/// A rate per time (8 %/year) makes that the period, and flows per time (USD/year) are
+/// converted to it
+fn npv(ev: &Evaluator, args: &[Expr]) -> Result<EvalResult, EvalError> {
+    let (rate, period) = rate_arg(ev, "NPV", &args[0])?;
+    let flows = ev.collect_values(&args[1..])?;
+    let (flows, unit) = cash_flows(ev, "NPV", flows, period.as_ref())?;
+    Ok(EvalResult::new(discounted(rate, &flows), unit))
+}
+
+/// Newton steps IRR takes before giving up
+const IRR_MAX_ITERATIONS: usize = 100;
+
+/// IRR(values, [guess]): the rate per period at which the cash flows (the first one now) have
+/// no net value. Flows per time (USD/year) give a rate per that time (%/year)
+fn irr(ev: &Evaluator, args: &[Expr]) -> Result<EvalResult, EvalError> {
+    let flows = ev.collect_values(&args[..1])?;
+    let period = flows.iter().find_map(|flow| per_time(ev, &flow.unit));
+    let (flows, _) = cash_flows(ev, "IRR", flows, period.as_ref())?;
+    if !(flows.iter().any(|flow| *flow > 0.0) && flows.iter().any(|flow| *flow < 0.0)) {
+        return Err(EvalError::InvalidOperation(
+            "IRR needs at least one positive and one negative cash flow".to_string(),
+        ));
+    }
+    let mut rate = match args.get(1) {
+        Some(arg) => rate_arg(ev, "IRR", arg)?.0,
+        None => 0.1,
+    };
+
+    // Newton's method on the net value; flows[0] is now, the rest at the end of each period
+    let net_value = |rate: f64| flows[0] + discounted(rate, &flows[1..]);
+    for _ in 0..IRR_MAX_ITERATIONS {
+        let slope: f64 = flows
+            .iter()
+            .enumerate()
+            .skip(1)
+            .map(|(n, flow)| -(n as f64) * flow / (1.0 + rate).powi(n as i32 + 1))
+            .sum();
+        let next = rate - net_value(rate) / slope;
+        if !next.is_finite() || next <= -1.0 {
+            break;
+        }
+        if (next - rate).abs() < 1e-10 {
+            let unit = match &period {
+                Some(period) => parse_unit(&format!("%/{}", period.canonical()), ev.library())
+                    .map_err(|_| EvalError::UnknownUnit(period.to_string()))?,
+                None => Unit::simple("%", BaseDimension::Custom("%".to_string())),
+            };
+            return Ok(EvalResult::new(next, unit));
+        }
+        rate = next;
+    }
+    Err(EvalError::InvalidOperation(
+        "IRR did not find a rate; try a different guess".to_string(),
+    ))
+}
+
+/// All the values of an array, row by row
+fn flatten(array: Array) -> Vec<EvalResult> {
+    array.into_iter().flatten().collect()
+}
+
+/// XNPV(rate, values, dates): the value at the first date of cash flows on the given dates
+/// The rate is per year (as in Excel, 365 days); a rate per other time is converted
+fn xnpv(ev: &Evaluator, args: &[Expr]) -> Result<EvalResult, EvalError> {
+    let (rate, period) = rate_arg(ev, "XNPV", &args[0])?;
+    let rate = match period {
+        Some(period) => {
+            let year = Unit::simple("year", BaseDimension::Time);
+            rate * convert_value(ev.library(), "XNPV", 1.0, &year, &period)?
+        }
+        None => rate,
+    };
+    let (flows, unit) = common_amounts(ev, "XNPV", &flatten(ev.eval_array(&args[1])?))?;
+    let dates = flatten(ev.eval_array(&args[2])?)
+        .iter()
+        .map(|date| {
+            date.as_datetime()
+                .ok_or_else(|| EvalError::InvalidOperation("XNPV dates must be dates".to_string()))
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+    if flows.len() != dates.len() || flows.is_empty() {
+        return Err(EvalError::InvalidOperation(
+            "XNPV needs one date for each value".to_string(),
+        ));
+    }
+
+    let value = flows
+        .iter()
+        .zip(&dates)
+        .map(|(flow, date)| {
+            let years = datetime::days_between(&dates[0], date) / 365.0;
+            flow / (1.0 + rate).powf(years)
+        })
+        .sum();
+    Ok(EvalResult::new(value, unit))
+}
+
+/// Convert compound units part by part ($/quarter -> $/year, ft^2 -> m^2)
+fn convert_compound_unit(
+    value: f64,
+    from_unit: &str,
+    to_unit: &str,
+    library: &UnitLibrary,
+) -> Option<f64> {
+    // Handle division (e.g., $/quarter -> $/year)
+    if let (Some(from_pos), Some(to_pos)) = (from_unit.find('/'), to_unit.find('/')) {
+        let from_left = &from_unit[..from_pos];
+        let from_right = &from_unit[from_pos + 1..];
+        let to_left = &to_unit[..to_pos];
+        let to_right = &to_unit[to_pos + 1..];
+
+        // Convert numerator (e.g., $ -> $, both are Currency)
+        let factor_left = if from_left == to_left {
+            1.0
+        } else {
+            library.convert(1.0, from_left, to_left)?
+        };
+
+        // Convert denominator (e.g., quarter -> year)
+        let factor_right = library.convert(1.0, from_right, to_right)?;
+
+        // $/quarter -> $/year = $ * (year / quarter), and 1 quarter = 0.25 year,
+        // so the value is scaled by numerator factor / denominator factor (1 / 0.25 = 4)
+        let combined_factor = factor_left / factor_right;
+        return Some(value * combined_factor);
+    }
+
+    // Handle power notation (e.g., ft^2 -> m^2)
+    if let (Some(from_pos), Some(to_pos)) = (from_unit.find('^'), to_unit.find('^')) {
+        let from_base = &from_unit[..from_pos];
+        let from_power_str = &from_unit[from_pos + 1..];
+        let to_base = &to_unit[..to_pos];
+        let to_power_str = &to_unit[to_pos + 1..];
+
+        // Parse the power
+        if let (Ok(from_power), Ok(to_power)) = (
+            from_power_str.parse::<Exponent>(),
+            to_power_str.parse::<Exponent>(),
+        ) {
+            if from_power == to_power {
+                // Get conversion factor for base unit
+                if let Some(base_factor) = library.convert(1.0, from_base, to_base) {
+                    // Raise to the power
+                    let combined_factor = from_power.apply(base_factor);
+                    return Some(value * combined_factor);
+                }
+            }
+        }
+    }
+
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::formula::{parse_formula, parse_lambda};
+
+    fn eval(formula: &str) -> Result<EvalResult, EvalError> {
+        let library = UnitLibrary::new();
+        Evaluator::new(&library).eval(&parse_formula(formula).unwrap())
+    }
+
+    #[test]
+    fn test_wildcard_match() {
+        let matches = |pattern: &str, text: &str| {
+            let pattern: Vec<char> = pattern.chars().collect();
+            let text: Vec<char> = text.chars().collect();
+            wildcard_match(&pattern, &text)
+        };
+        assert!(matches("t3.*", "t3.large"));
+        assert!(matches("t?.small", "t3.small"));
+        assert!(matches("*", ""));
+        assert!(!matches("t3.?", "t3.large"));
+        assert!(!matches("m5*", "t3.large"));
+    }
+
+    #[test]
+    fn test_format_number() {
+        assert_eq!(format_number(1234.5, "#,##0.0").unwrap(), "1,234.5");
+        assert_eq!(format_number(1234567.0, "#,##0").unwrap(), "1,234,567");
+        assert_eq!(format_number(1234.5678, "0.00").unwrap(), "1234.57");
+        assert_eq!(format_number(2.5, "0.0#").unwrap(), "2.5");
+        assert_eq!(format_number(0.5, "#.00").unwrap(), ".50");
+        assert_eq!(format_number(7.0, "000").unwrap(), "007");
+        assert_eq!(format_number(0.153, "0.0%").unwrap(), "15.3%");
+        assert_eq!(format_number(-42.0, "$0").unwrap(), "-$42");
+        assert_eq!(format_number(-0.01, "0.0").unwrap(), "0.0");
+        assert_eq!(format_number(12.0, "\"Qty: \"0 \\x").unwrap(), "Qty: 12 x");
+        assert!(format_number(12.0, "General").is_none());
+    }
+
+    #[test]
+    fn test_text_functions() {
+        let text = |formula: &str| match eval(formula) {
+            Ok(result) => result.as_text().map(str::to_string),
+            Err(e) => panic!("{}: {}", formula, e),
+        };
+        let number = |formula: &str| eval(formula).unwrap().as_number();
+
+        assert_eq!(text("=\"a\" & 1 & TRUE").as_deref(), Some("a1TRUE"));
+        assert_eq!(text("=\"Mass: \" & 5 kg").as_deref(), Some("Mass: 5 kg"));
+        assert_eq!(text("=CONCAT(\"a\", \"b\", 3)").as_deref(), Some("ab3"));
+        assert_eq!(
+            text("=TEXTJOIN(\", \", TRUE, \"x\", \"\", \"y\")").as_deref(),
+            Some("x, y")
+        );
+        assert_eq!(
+            text("=TEXTJOIN(\"-\", FALSE, \"x\", \"\", \"y\")").as_deref(),
+            Some("x--y")
+        );
+        assert_eq!(text("=LEFT(\"t3.large\", 2)").as_deref(), Some("t3"));
+        assert_eq!(text("=LEFT(\"t3\")").as_deref(), Some("t"));
+        assert_eq!(text("=RIGHT(\"t3.large\", 5)").as_deref(), Some("large"));
+        assert_eq!(text("=RIGHT(\"ab\", 5)").as_deref(), Some("ab"));
+        assert_eq!(text("=MID(\"t3.large\", 4, 3)").as_deref(), Some("lar"));
+        assert_eq!(number("=LEN(\"größe\")"), Some(5.0));
+        assert_eq!(text("=UPPER(\"east\")").as_deref(), Some("EAST"));
+        assert_eq!(text("=LOWER(\"EAST\")").as_deref(), Some("east"));
+        assert_eq!(text("=TRIM(\"  a   b \")").as_deref(), Some("a b"));
+        assert_eq!(
+            text("=SUBSTITUTE(\"a-b-c\", \"-\", \"+\")").as_deref(),
+            Some("a+b+c")
+        );
+        assert_eq!(
+            text("=SUBSTITUTE(\"a-b-c\", \"-\", \"+\", 2)").as_deref(),
+            Some("a-b+c")
+        );
+        assert_eq!(number("=FIND(\"b\", \"abcb\")"), Some(2.0));
+        assert_eq!(number("=FIND(\"b\", \"abcb\", 3)"), Some(4.0));
+        assert!(eval("=FIND(\"B\", \"abc\")").is_err());
+        assert!(eval("=MID(\"abc\", 0, 1)").is_err());
+
+        // VALUE reads text as if typed into a cell
+        let mass = eval("=VALUE(\"12.5 kg\")").unwrap();
+        assert_eq!(mass.as_number(), Some(12.5));
+        assert_eq!(mass.unit.canonical(), "kg");
+        assert_eq!(number("=VALUE(\"15%\")"), Some(0.15));
+        assert!(eval("=VALUE(\"2024-03-15\")").unwrap().is_datetime());
+        assert!(eval("=VALUE(\"twelve\")").is_err());
+
+        // TEXT converts to the unit in brackets
+        assert_eq!(
+            text("=\"Total: \" & TEXT(376.2756 m, \"#,##0.0 [ft]\")").as_deref(),
+            Some("Total: 1,234.5 ft")
+        );
+        assert_eq!(
+            text("=TEXT(2.5, \"0.00 [kg]\")").as_deref(),
+            Some("2.50 kg")
+        );
+        assert_eq!(
+            text("=TEXT(1234.5 ft, \"#,##0\")").as_deref(),
+            Some("1,235")
+        );
+        assert_eq!(text("=TEXT(0.25, \"0%\")").as_deref(), Some("25%"));
+        assert!(eval("=TEXT(5 kg, \"0 [ft]\")").is_err());
+        assert!(eval("=TEXT(\"abc\", \"0\")").is_err());
+    }
+
+    #[test]
+    fn test_criterion_parsing() {
+        let library = UnitLibrary::new();
+        let ev = Evaluator::new(&library);
+        let parse = |text: &str| Criterion::parse(&ev, EvalResult::text(text.to_string()));
+
+        let criterion = parse(">=16 GB");
+        assert_eq!(criterion.op, CriterionOp::GreaterOrEqual);
+        let value = criterion.value.unwrap();
+        assert_eq!(value.as_number(), Some(16.0));
+        assert_eq!(value.unit.canonical(), "GB");
+
+        let criterion = parse("<>east");
+        assert_eq!(criterion.op, CriterionOp::NotEqual);
+        assert_eq!(criterion.value.unwrap().as_text(), Some("east"));
+
+        assert!(parse("<2024-03-15").value.unwrap().is_datetime());
+        assert!(parse("TRUE").value.unwrap().is_boolean());
+        assert!(parse("<>").value.is_none());
+
+        // Only literal quantities are read as numbers
+        assert!(parse("=A1").value.unwrap().is_text());
+    }
+
+    #[test]
+    fn test_arity_display() {
+        assert_eq!(Arity::exactly(1).to_string(), "exactly 1 argument");
+        assert_eq!(Arity::between(1, 2).to_string(), "1 or 2 arguments");
+        assert_eq!(Arity::between(2, 4).to_string(), "2 to 4 arguments");
+        assert_eq!(Arity::at_least(1).to_string(), "at least 1 argument");
+        assert!(Arity::at_least(0).accepts(0));
+        assert!(!Arity::between(1, 2).accepts(3));
+    }
+
+    #[test]
+    fn test_arity_is_checked() {
+        let err = eval("=ABS(1, 2)").unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "Invalid operation: ABS requires exactly 1 argument, got 2"
+        );
+        assert!(matches!(
+            eval("=NOSUCHFUNCTION(1)"),
+            Err(EvalError::FunctionNotImplemented(_))
+        ));
+    }
+
+    #[test]
+    fn test_builtins_without_a_sheet() {
+        let result = eval("=CEILING(1234m, 1km)").unwrap();
+        assert_eq!(result.as_number(), Some(2000.0));
+        assert_eq!(result.unit.canonical(), "m");
+
+        let result = eval("=CONVERT(30000 $/quarter, \"$/year\")").unwrap();
+        assert_eq!(result.as_number(), Some(120000.0));
+
+        let result = eval("=IF(2m > 150cm, MAX(1, 5, 3), 1 / 0)").unwrap();
+        assert_eq!(result.as_number(), Some(5.0));
+    }
+
+    #[test]
+    fn test_financial_functions() {
+        let close = |formula: &str, expected: f64, unit: &str| {
+            let result = eval(formula).unwrap();
+            let value = result.as_number().unwrap();
+            assert!((value - expected).abs() < 0.01, "{}: {}", formula, value);
+            assert_eq!(result.unit.canonical(), unit, "{}", formula);
+        };
+
+        // Percentages are fractions; a rate per time sets the period
+        close("=6 %", 0.06, "%");
+        close("=PMT(6 %/year, 30 year, 300000 USD)", -21794.67, "USD/year");
+        close("=PMT(0.5 %, 360, 300000)", -1798.65, "");
+
+        // The number of periods as a time converts the rate (6 %/year is 0.5 % a month)
+        close(
+            "=PMT(6 %/year, 360 month, 300000 USD)",
+            -1798.65,
+            "USD/month",
+        );
+
+        // A yearly payment converted to USD/month is a twelfth of it, not the monthly payment,
+        // which comes from giving the payment period
+        close(
+            "=CONVERT(PMT(6 %/year, 30 year, 300000 USD), \"USD/month\")",
+            -1816.22,
+            "USD/month",
+        );
+        close(
+            "=PMT(6 %/year, 30 year, 300000 USD, 0, 0, \"month\")",
+            -1798.65,
+            "USD/month",
+        );
+        close(
+            "=PMT(6 %/year, 30, 300000 USD, 0, 0, 1 month)",
+            -1798.65,
+            "USD/month",
+        );
+        close(
+            "=PMT(0.5 %/month, 360 month, 300000 USD, 0, 0, \"month\")",
+            -1798.65,
+            "USD/month",
+        );
+
+        // Payments per time are converted to the period
+        close("=FV(5 %/year, 120 month, -100 USD/month)", 15528.23, "USD");
+        close("=FV(5 %/year, 10 year, -100 USD/month)", 15093.47, "USD");
+        close("=FV(5 %/year, 10 year, 0, -1000 USD)", 1628.89, "USD");
+        close("=PV(8 %/year, 20 year, 1000 USD/year)", -9818.15, "USD");
+        close("=PV(0, 10, 100 EUR, 0, 1)", -1000.0, "EUR");
+        close(
+            "=NPV(10 %/year, -10000 USD, 3000 USD, 4200 USD, 6800 USD)",
+            1188.44,
+            "USD",
+        );
+
+        // A rate must be a percentage or a percentage per time
+        assert!(eval("=PMT(6 USD, 10, 1000)").is_err());
+        assert!(eval("=PMT(6 %/year, 10 m, 1000)").is_err());
+
+        // The payment period must be a time, and the schedule must say how long a period is
+        assert!(eval("=PMT(6 %/year, 30 year, 300000 USD, 0, 0, \"m\")").is_err());
+        assert!(eval("=PMT(0.5 %, 360, 300000, 0, 0, \"month\")").is_err());
+    }
+
+    #[test]
+    fn test_register_domain_function() {
+        let mut registry = FunctionRegistry::with_builtins();
+        registry.register(FunctionSpec::new(
+            "double",
+            Arity::exactly(1),
+            UnitSignature::Preserve,
+            |ev, args| {
+                let result = ev.eval(&args[0])?;
+                Ok(EvalResult::new(
+                    result.as_number().unwrap_or_default() * 2.0,
+                    result.unit,
+                ))
+            },
+        ));
+        assert_eq!(
+            registry.get("DOUBLE").unwrap().units,
+            UnitSignature::Preserve
+        );
+
+        let library = UnitLibrary::new();
+        let evaluator = Evaluator::new(&library).with_functions(&registry);
+        let result = evaluator
+            .eval(&parse_formula("=Double(SUM(2m, 50cm))").unwrap())
+            .unwrap();
+        assert_eq!(result.as_number(), Some(5.0));
+        assert_eq!(result.unit.canonical(), "m");
+
+        // The shared registry is not affected
+        assert!(FunctionRegistry::standard().get("DOUBLE").is_none());
+    }
+
+    #[test]
+    fn test_let() {
+        let result = eval("=LET(x, 3 m, y, x * 2, x + y)").unwrap();
+        assert_eq!(result.as_number(), Some(9.0));
+        assert_eq!(result.unit.canonical(), "m");
+
+        // Inner names hide outer ones
+        let result = eval("=LET(x, 1, LET(x, x + 1, x * 10))").unwrap();
+        assert_eq!(result.as_number(), Some(20.0));
+
+        assert!(eval("=LET(x, 1, y, 2)").is_err());
+        assert!(eval("=LET(A1, 1, A1)").is_err());
+        // Names are only bound inside the LET
+        assert!(eval("=LET(x, 1, x) + x").is_err());
+    }
+
+    #[test]
+    fn test_lambda_functions() {
+        let library = UnitLibrary::new();
+        let mut registry = FunctionRegistry::with_builtins();
+        let define = |registry: &mut FunctionRegistry, name: &str, definition: &str| {
+            registry.register(FunctionSpec::lambda(
+                name,
+                parse_lambda(definition).unwrap(),
+            ))
+        };
+        define(
+            &mut registry,
+            "monthly_cost",
+            "LAMBDA(rate: USD/hr, util, rate * util * 730 hr/month)",
+        );
+        define(
+            &mut registry,
+            "fact",
+            "LAMBDA(n, IF(n <= 1, 1, n * fact(n - 1)))",
+        );
+        define(&mut registry, "forever", "LAMBDA(n, forever(n))");
+        let eval = |formula: &str| {
+            Evaluator::new(&library)
+                .with_functions(&registry)
+                .eval(&parse_formula(formula).unwrap())
+        };
+
+        let result = eval("=monthly_cost(0.10 USD/hr, 0.5)").unwrap();
+        assert!((result.as_number().unwrap() - 36.5).abs() < 1e-9);
+        assert_eq!(result.unit.canonical(), "USD/month");
+        assert_eq!(eval("=FACT(5)").unwrap().as_number(), Some(120.0));
+
+        // Arguments must match the parameter's unit
+        let error = eval("=monthly_cost(0.10 USD, 0.5)").unwrap_err();
+        assert_eq!(error.kind(), ErrorKind::Unit);
+        assert!(eval("=monthly_cost(\"cheap\", 0.5)").is_err());
+        assert!(eval("=monthly_cost(0.10 USD/hr)").is_err());
+
+        // Parameters are not visible to the caller, and runaway recursion stops
+        assert!(eval("=monthly_cost(1 USD/hr, 1) + rate").is_err());
+        assert!(eval("=forever(1)").is_err());
+    }
+
+    #[test]
+    fn test_resolved_references() {
+        let library = UnitLibrary::new();
+        let evaluator = Evaluator::new(&library);
+        let eval = |formula: &str| evaluator.eval(&parse_formula(formula).unwrap());
+        let range = |start: &str, end: &str| {
+            CellRange::new(
+                &CellAddr::from_string(start).unwrap(),
+                &CellAddr::from_string(end).unwrap(),
+            )
+        };
+
+        // The references are noted even though there are no cells to read here
+        assert!(eval("=INDIRECT(\"B\" & 1 + 2)").is_err());
+        assert!(eval("=OFFSET(Inputs!A1:A2, 1, 2, 3)").is_err());
+        assert_eq!(
+            evaluator.resolved_references(),
+            vec![
+                (None, range("B3", "B3")),
+                (Some("Inputs".to_string()), range("C2", "C4")),
+            ]
+        );
+
+        // Text that is not a reference, and offsets past the edge of the sheet, give #REF!
+        let error = eval("=INDIRECT(\"B\" & \"x\")").unwrap_err();
+        assert_eq!(error.kind(), ErrorKind::Ref);
+        let error = eval("=OFFSET(B2, -2, 0)").unwrap_err();
+        assert_eq!(error.kind(), ErrorKind::Ref);
+        assert!(eval("=OFFSET(B2, 1, 1, 0)").is_err());
+        assert!(eval("=OFFSET(1 + 1, 1, 1)").is_err());
+        assert_eq!(evaluator.resolved_references().len(), 2);
+    }
+}
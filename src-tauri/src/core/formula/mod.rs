@@ -2,8 +2,10 @@
 
 pub mod ast;
 pub mod evaluator;
+pub mod functions;
 pub mod parser;
 
-pub use ast::Expr;
-pub use evaluator::{EvalError, EvalResult, Evaluator};
-pub use parser::{parse_formula, ParseError};
+pub use ast::{Expr, Lambda, LambdaParam};
+pub use evaluator::{EvalContext, EvalError, EvalResult, Evaluator, ResolvedReference};
+pub use functions::{Arity, FunctionBody, FunctionRegistry, FunctionSpec, UnitSignature};
+pub use parser::{parse_formula, parse_lambda, reference_spans, ParseError};
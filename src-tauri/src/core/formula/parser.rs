@@ -1,8 +1,10 @@
 // Formula parser using pest
 
-use super::ast::Expr;
+use super::ast::{Expr, Lambda, LambdaParam};
+use crate::core::cell::ErrorKind;
 use pest::Parser;
 use pest_derive::Parser;
+use std::ops::Range;
 use thiserror::Error;
 
 #[derive(Parser)]
@@ -45,9 +47,68 @@ pub fn parse_formula(input: &str) -> Result<Expr, ParseError> {
     Err(ParseError::UnexpectedRule(Rule::formula))
 }
 
+/// Find where the references (`A1`, `$B$2:C5`, `B:B`, `A1#`, `'Cost Model'!B3:B20`) are in a
+/// formula's text, as byte ranges in the order they appear. A range or sheet-qualified
+/// reference is one span, not one for each cell in it
+pub fn reference_spans(input: &str) -> Result<Vec<Range<usize>>, ParseError> {
+    fn collect(pairs: pest::iterators::Pairs<Rule>, spans: &mut Vec<Range<usize>>) {
+        for pair in pairs {
+            match pair.as_rule() {
+                Rule::sheet_ref
+                | Rule::range
+                | Rule::column_range
+                | Rule::row_range
+                | Rule::spill_ref
+                | Rule::cell_ref => spans.push(pair.as_span().start()..pair.as_span().end()),
+                _ => collect(pair.into_inner(), spans),
+            }
+        }
+    }
+
+    let mut spans = Vec::new();
+    collect(FormulaParser::parse(Rule::formula, input)?, &mut spans);
+    Ok(spans)
+}
+
+/// Parse a workbook function definition, e.g. LAMBDA(rate: USD/hr, util, rate*util*730 hr/month)
+pub fn parse_lambda(input: &str) -> Result<Lambda, ParseError> {
+    let lambda = FormulaParser::parse(Rule::lambda, input)?
+        .next()
+        .ok_or(ParseError::UnexpectedRule(Rule::lambda))?;
+
+    let mut params = Vec::new();
+    for pair in lambda.into_inner() {
+        match pair.as_rule() {
+            Rule::lambda_param => {
+                let mut parts = pair.into_inner();
+                params.push(LambdaParam {
+                    name: parts.next().unwrap().as_str().to_string(),
+                    unit: parts.next().map(|unit| unit.as_str().to_string()),
+                });
+            }
+            Rule::expr => {
+                return Ok(Lambda {
+                    params,
+                    body: parse_expr(pair)?,
+                })
+            }
+            _ => {}
+        }
+    }
+
+    Err(ParseError::UnexpectedRule(Rule::lambda))
+}
+
 fn parse_expr(pair: pest::iterators::Pair<Rule>) -> Result<Expr, ParseError> {
     match pair.as_rule() {
-        Rule::expr => {
+        // Left-associative binary operators, one rule per precedence level
+        Rule::expr
+        | Rule::and_expr
+        | Rule::comparison
+        | Rule::concat
+        | Rule::sum
+        | Rule::term
+        | Rule::power => {
             let mut pairs = pair.into_inner();
             let mut left = parse_expr(pairs.next().unwrap())?;
 
@@ -55,8 +116,20 @@ fn parse_expr(pair: pest::iterators::Pair<Rule>) -> Result<Expr, ParseError> {
                 let right = parse_expr(pairs.next().unwrap())?;
 
                 left = match op.as_str() {
+                    "&" => Expr::concat(left, right),
                     "+" => Expr::new_add(left, right),
                     "-" => Expr::new_subtract(left, right),
+                    "*" => Expr::new_multiply(left, right),
+                    "/" => Expr::new_divide(left, right),
+                    "^" => Expr::new_power(left, right),
+                    ">" => Expr::greater_than(left, right),
+                    "<" => Expr::less_than(left, right),
+                    ">=" => Expr::greater_or_equal(left, right),
+                    "<=" => Expr::less_or_equal(left, right),
+                    "=" => Expr::equal(left, right),
+                    "<>" => Expr::not_equal(left, right),
+                    "AND" | "&&" => Expr::and(left, right),
+                    "OR" | "||" => Expr::or(left, right),
                     _ => return Err(ParseError::UnexpectedRule(op.as_rule())),
                 };
             }
@@ -64,21 +137,15 @@ fn parse_expr(pair: pest::iterators::Pair<Rule>) -> Result<Expr, ParseError> {
             Ok(left)
         }
 
-        Rule::term => {
+        Rule::not_expr => {
             let mut pairs = pair.into_inner();
-            let mut left = parse_expr(pairs.next().unwrap())?;
-
-            while let Some(op) = pairs.next() {
-                let right = parse_expr(pairs.next().unwrap())?;
+            let first = pairs.next().unwrap();
 
-                left = match op.as_str() {
-                    "*" => Expr::new_multiply(left, right),
-                    "/" => Expr::new_divide(left, right),
-                    _ => return Err(ParseError::UnexpectedRule(op.as_rule())),
-                };
+            if first.as_rule() == Rule::not_op {
+                Ok(Expr::new_not(parse_expr(pairs.next().unwrap())?))
+            } else {
+                parse_expr(first)
             }
-
-            Ok(left)
         }
 
         Rule::factor => {
@@ -111,6 +178,15 @@ fn parse_expr(pair: pest::iterators::Pair<Rule>) -> Result<Expr, ParseError> {
             Ok(Expr::string(string_content))
         }
 
+        Rule::boolean_literal => Ok(Expr::boolean(pair.as_str() == "TRUE")),
+
+        Rule::error_literal => {
+            let code = pair.as_str();
+            ErrorKind::from_code(code)
+                .map(Expr::Error)
+                .ok_or(ParseError::UnexpectedRule(Rule::error_literal))
+        }
+
         Rule::currency_with_number => {
             let mut pairs = pair.into_inner();
             let num_str = pairs.next().unwrap().as_str();
@@ -136,6 +212,22 @@ fn parse_expr(pair: pest::iterators::Pair<Rule>) -> Result<Expr, ParseError> {
             parse_cell_ref(cell_str)
         }
 
+        Rule::spill_ref => parse_spill_ref(pair.as_str()),
+
+        Rule::sheet_ref => {
+            let mut pairs = pair.into_inner();
+            let sheet = parse_sheet_name(pairs.next().unwrap().as_str());
+            let target = pairs.next().unwrap();
+            let reference = match target.as_rule() {
+                Rule::range => parse_range(target)?,
+                Rule::column_range => parse_column_range(target)?,
+                Rule::row_range => parse_row_range(target)?,
+                Rule::spill_ref => parse_spill_ref(target.as_str())?,
+                _ => parse_cell_ref(target.as_str())?,
+            };
+            Ok(Expr::sheet_ref(sheet, reference))
+        }
+
         Rule::named_ref => {
             let name = pair.as_str();
             Ok(Expr::named_ref(name))
@@ -153,11 +245,7 @@ fn parse_expr(pair: pest::iterators::Pair<Rule>) -> Result<Expr, ParseError> {
                             match arg.as_rule() {
                                 Rule::arg => {
                                     let inner = arg.into_inner().next().unwrap();
-                                    if inner.as_rule() == Rule::range {
-                                        args.push(parse_range(inner)?);
-                                    } else {
-                                        args.push(parse_expr(inner)?);
-                                    }
+                                    args.push(parse_expr(inner)?);
                                 }
                                 Rule::range => args.push(parse_range(arg)?),
                                 _ => args.push(parse_expr(arg)?),
@@ -175,18 +263,28 @@ fn parse_expr(pair: pest::iterators::Pair<Rule>) -> Result<Expr, ParseError> {
         }
 
         Rule::range => parse_range(pair),
+        Rule::column_range => parse_column_range(pair),
+        Rule::row_range => parse_row_range(pair),
 
         _ => Err(ParseError::UnexpectedRule(pair.as_rule())),
     }
 }
 
 fn parse_cell_ref(cell_str: &str) -> Result<Expr, ParseError> {
-    // Split into column and row parts
+    // Split into column and row parts, noting which of them is anchored with "$"
     let mut col = String::new();
     let mut row = String::new();
+    let mut col_absolute = false;
+    let mut row_absolute = false;
 
     for ch in cell_str.chars() {
-        if ch.is_ascii_alphabetic() {
+        if ch == '$' {
+            if col.is_empty() {
+                col_absolute = true;
+            } else {
+                row_absolute = true;
+            }
+        } else if ch.is_ascii_alphabetic() {
             col.push(ch.to_ascii_uppercase());
         } else if ch.is_ascii_digit() {
             row.push(ch);
@@ -201,7 +299,25 @@ fn parse_cell_ref(cell_str: &str) -> Result<Expr, ParseError> {
         .parse::<usize>()
         .map_err(|_| ParseError::InvalidCellRef(cell_str.to_string()))?;
 
-    Ok(Expr::cell_ref(col, row_num))
+    Ok(Expr::absolute_cell_ref(
+        col,
+        row_num,
+        col_absolute,
+        row_absolute,
+    ))
+}
+
+fn parse_spill_ref(spill_str: &str) -> Result<Expr, ParseError> {
+    let anchor = spill_str.strip_suffix('#').unwrap_or(spill_str);
+    Ok(Expr::spill_ref(parse_cell_ref(anchor)?))
+}
+
+/// Strip the quotes from a sheet name ('Cost Model' → Cost Model, 'Bob''s' → Bob's)
+fn parse_sheet_name(name: &str) -> String {
+    match name.strip_prefix('\'').and_then(|s| s.strip_suffix('\'')) {
+        Some(inner) => inner.replace("''", "'"),
+        None => name.to_string(),
+    }
 }
 
 fn parse_range(pair: pest::iterators::Pair<Rule>) -> Result<Expr, ParseError> {
@@ -215,6 +331,28 @@ fn parse_range(pair: pest::iterators::Pair<Rule>) -> Result<Expr, ParseError> {
     Ok(Expr::range(start, end))
 }
 
+fn parse_column_range(pair: pest::iterators::Pair<Rule>) -> Result<Expr, ParseError> {
+    let mut pairs = pair.into_inner();
+    let start = pairs.next().unwrap().as_str();
+    let end = pairs.next().unwrap().as_str();
+
+    Ok(Expr::column_range(start, end))
+}
+
+fn parse_row_range(pair: pest::iterators::Pair<Rule>) -> Result<Expr, ParseError> {
+    let mut pairs = pair.into_inner();
+    let start = parse_row_number(pairs.next().unwrap().as_str())?;
+    let end = parse_row_number(pairs.next().unwrap().as_str())?;
+
+    Ok(Expr::row_range(start, end))
+}
+
+fn parse_row_number(row_str: &str) -> Result<usize, ParseError> {
+    row_str
+        .parse::<usize>()
+        .map_err(|_| ParseError::InvalidCellRef(row_str.to_string()))
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -241,6 +379,15 @@ mod tests {
             expr,
             Expr::NumberWithUnit { value: 5.5, ref unit } if unit == "kg"
         ));
+
+        let expr = parse_formula("=6 %/year * B1").unwrap();
+        assert_eq!(
+            expr,
+            Expr::new_multiply(
+                Expr::number_with_unit(6.0, "%/year"),
+                Expr::cell_ref("B", 1)
+            )
+        );
     }
 
     #[test]
@@ -248,19 +395,19 @@ mod tests {
         let expr = parse_formula("A1").unwrap();
         assert!(matches!(
             expr,
-            Expr::CellRef { ref col, row: 1 } if col == "A"
+            Expr::CellRef { ref col, row: 1, .. } if col == "A"
         ));
 
         let expr = parse_formula("B12").unwrap();
         assert!(matches!(
             expr,
-            Expr::CellRef { ref col, row: 12 } if col == "B"
+            Expr::CellRef { ref col, row: 12, .. } if col == "B"
         ));
 
         let expr = parse_formula("AA100").unwrap();
         assert!(matches!(
             expr,
-            Expr::CellRef { ref col, row: 100 } if col == "AA"
+            Expr::CellRef { ref col, row: 100, .. } if col == "AA"
         ));
     }
 
@@ -460,6 +607,25 @@ mod tests {
         assert!(matches!(expr, Expr::String(ref s) if s.is_empty()));
     }
 
+    #[test]
+    fn test_parse_boolean_literal() {
+        assert_eq!(parse_formula("=TRUE").unwrap(), Expr::Boolean(true));
+        assert_eq!(parse_formula("=FALSE").unwrap(), Expr::Boolean(false));
+
+        let expr = parse_formula("=IF(TRUE, 1, 2)").unwrap();
+        assert!(matches!(expr, Expr::Function { ref args, .. } if args[0] == Expr::Boolean(true)));
+
+        // TRUE() is a function call, and TRUE1 is a cell in column TRUE
+        assert!(matches!(
+            parse_formula("=TRUE()").unwrap(),
+            Expr::Function { .. }
+        ));
+        assert!(matches!(
+            parse_formula("=TRUE1").unwrap(),
+            Expr::CellRef { .. }
+        ));
+    }
+
     #[test]
     fn test_parse_string_concatenation() {
         // String + String
@@ -493,4 +659,368 @@ mod tests {
         let expr = parse_formula("sum_value").unwrap();
         assert!(matches!(expr, Expr::NamedRef { .. }));
     }
+
+    #[test]
+    fn test_parse_sheet_ref() {
+        let expr = parse_formula("=Sheet2!A1").unwrap();
+        match expr {
+            Expr::SheetRef { sheet, reference } => {
+                assert_eq!(sheet, "Sheet2");
+                assert!(matches!(*reference, Expr::CellRef { ref col, row: 1, .. } if col == "A"));
+            }
+            _ => panic!("Expected SheetRef expression"),
+        }
+
+        // Quoted names may contain spaces and escaped quotes
+        let expr = parse_formula("='Bob''s Model'!C7 * 2").unwrap();
+        match expr {
+            Expr::Multiply(left, _) => {
+                assert!(
+                    matches!(*left, Expr::SheetRef { ref sheet, .. } if sheet == "Bob's Model")
+                );
+            }
+            _ => panic!("Expected Multiply expression"),
+        }
+    }
+
+    #[test]
+    fn test_parse_sheet_range() {
+        let expr = parse_formula("=SUM('Cost Model'!B3:B20)").unwrap();
+        match expr {
+            Expr::Function { args, .. } => {
+                assert_eq!(args.len(), 1);
+                match &args[0] {
+                    Expr::SheetRef { sheet, reference } => {
+                        assert_eq!(sheet, "Cost Model");
+                        assert!(matches!(**reference, Expr::Range { .. }));
+                    }
+                    _ => panic!("Expected SheetRef argument"),
+                }
+            }
+            _ => panic!("Expected Function expression"),
+        }
+
+        // Display output round-trips through the parser
+        let expr = parse_formula("=SUM('Cost Model'!B3:B20) + Inputs!A1").unwrap();
+        let reparsed = parse_formula(&format!("={}", expr)).unwrap();
+        assert_eq!(expr, reparsed);
+    }
+
+    #[test]
+    fn test_reference_spans() {
+        let formula =
+            "=SUM('Cost Model'!B3:B20) + Inputs!A1 * \"Sheet2!A1\" - $C$2/SUM(B:B, 3:4, D1#)";
+        let spans = reference_spans(formula).unwrap();
+        let refs: Vec<&str> = spans.into_iter().map(|span| &formula[span]).collect();
+        assert_eq!(
+            refs,
+            vec![
+                "'Cost Model'!B3:B20",
+                "Inputs!A1",
+                "$C$2",
+                "B:B",
+                "3:4",
+                "D1#"
+            ]
+        );
+
+        assert!(reference_spans("=A1 +").is_err());
+    }
+
+    #[test]
+    fn test_parse_whole_column_and_row_ranges() {
+        let expr = parse_formula("=SUM(A1:C10, B:B, 3:5)").unwrap();
+        match expr {
+            Expr::Function { args, .. } => {
+                assert_eq!(args.len(), 3);
+                assert_eq!(
+                    args[0],
+                    Expr::range(Expr::cell_ref("A", 1), Expr::cell_ref("C", 10))
+                );
+                assert_eq!(args[1], Expr::column_range("B", "B"));
+                assert_eq!(args[2], Expr::row_range(3, 5));
+            }
+            _ => panic!("Expected Function expression"),
+        }
+
+        let expr = parse_formula("=MAX(Inputs!A:C) + 3").unwrap();
+        let reparsed = parse_formula(&format!("={}", expr)).unwrap();
+        assert_eq!(expr, reparsed);
+    }
+
+    #[test]
+    fn test_parse_range_operands() {
+        let expr = parse_formula("=SUMPRODUCT(A1:A3 * B1:B3, 2)").unwrap();
+        match expr {
+            Expr::Function { args, .. } => {
+                assert_eq!(
+                    args[0],
+                    Expr::new_multiply(
+                        Expr::range(Expr::cell_ref("A", 1), Expr::cell_ref("A", 3)),
+                        Expr::range(Expr::cell_ref("B", 1), Expr::cell_ref("B", 3))
+                    )
+                );
+                assert_eq!(args[1], Expr::number(2.0));
+            }
+            _ => panic!("Expected Function expression"),
+        }
+
+        let expr = parse_formula("=SUM(B:B / 2:2)").unwrap();
+        let reparsed = parse_formula(&format!("={}", expr)).unwrap();
+        assert_eq!(expr, reparsed);
+    }
+
+    #[test]
+    fn test_parse_comparison_operators() {
+        let cases = [
+            ("A1 > B1", Expr::greater_than as fn(Expr, Expr) -> Expr),
+            ("A1 < B1", Expr::less_than),
+            ("A1 >= B1", Expr::greater_or_equal),
+            ("A1 <= B1", Expr::less_or_equal),
+            ("A1 = B1", Expr::equal),
+            ("A1 <> B1", Expr::not_equal),
+        ];
+        for (formula, op) in cases {
+            let expr = parse_formula(&format!("={}", formula)).unwrap();
+            assert_eq!(expr, op(Expr::cell_ref("A", 1), Expr::cell_ref("B", 1)));
+        }
+
+        // Comparisons bind looser than arithmetic
+        let expr = parse_formula("=A1 + 1 > B1 * 2").unwrap();
+        assert_eq!(
+            expr,
+            Expr::greater_than(
+                Expr::new_add(Expr::cell_ref("A", 1), Expr::number(1.0)),
+                Expr::new_multiply(Expr::cell_ref("B", 1), Expr::number(2.0)),
+            )
+        );
+
+        // Units stay attached to their numbers
+        let expr = parse_formula("=5 ft > 1 m").unwrap();
+        assert_eq!(
+            expr,
+            Expr::greater_than(
+                Expr::number_with_unit(5.0, "ft"),
+                Expr::number_with_unit(1.0, "m"),
+            )
+        );
+    }
+
+    #[test]
+    fn test_parse_logical_operators() {
+        // NOT binds tighter than AND, which binds tighter than OR
+        let expr = parse_formula("=A1 > 0 OR NOT B1 > 0 AND C1 < 5").unwrap();
+        assert_eq!(
+            expr,
+            Expr::or(
+                Expr::greater_than(Expr::cell_ref("A", 1), Expr::number(0.0)),
+                Expr::and(
+                    Expr::new_not(Expr::greater_than(
+                        Expr::cell_ref("B", 1),
+                        Expr::number(0.0)
+                    )),
+                    Expr::less_than(Expr::cell_ref("C", 1), Expr::number(5.0)),
+                ),
+            )
+        );
+
+        let symbolic = parse_formula("=A1 > 0 || B1 > 0 && C1 < 5").unwrap();
+        let keywords = parse_formula("=A1 > 0 OR B1 > 0 AND C1 < 5").unwrap();
+        assert_eq!(symbolic, keywords);
+
+        // Keywords are not mistaken for units
+        let expr = parse_formula("=A1 > 5 AND B1 < 3").unwrap();
+        assert!(matches!(expr, Expr::And(_, _)));
+
+        // AND(...), OR(...) and NOT(...) are still functions
+        let expr = parse_formula("=AND(A1, B1)").unwrap();
+        assert!(matches!(expr, Expr::Function { ref name, .. } if name == "AND"));
+        let expr = parse_formula("=NOT(A1)").unwrap();
+        assert!(matches!(expr, Expr::Function { ref name, .. } if name == "NOT"));
+
+        // Display output parses back to the same expression
+        let expr = parse_formula("=NOT A1 <> 2 m || B1 = 3").unwrap();
+        let reparsed = parse_formula(&format!("={}", expr)).unwrap();
+        assert_eq!(expr, reparsed);
+    }
+
+    #[test]
+    fn test_parse_power_operator() {
+        let expr = parse_formula("=A1^2").unwrap();
+        assert_eq!(
+            expr,
+            Expr::new_power(Expr::cell_ref("A", 1), Expr::number(2.0))
+        );
+
+        // ^ binds tighter than * and is left-associative
+        let expr = parse_formula("=2 * A1 ^ 2 ^ 3").unwrap();
+        assert_eq!(
+            expr,
+            Expr::new_multiply(
+                Expr::number(2.0),
+                Expr::new_power(
+                    Expr::new_power(Expr::cell_ref("A", 1), Expr::number(2.0)),
+                    Expr::number(3.0),
+                ),
+            )
+        );
+
+        // As in Excel, unary minus binds tighter than ^
+        let expr = parse_formula("=-2^2").unwrap();
+        assert_eq!(
+            expr,
+            Expr::new_power(Expr::negate(Expr::number(2.0)), Expr::number(2.0))
+        );
+
+        // A non-numeric exponent after a unit is the operator
+        let expr = parse_formula("=5m^A1").unwrap();
+        assert_eq!(
+            expr,
+            Expr::new_power(Expr::number_with_unit(5.0, "m"), Expr::cell_ref("A", 1))
+        );
+    }
+
+    #[test]
+    fn test_parse_unit_literals_with_exponents() {
+        let cases = [
+            ("=9.81 m/s^2", "m/s^2"),
+            ("=3 kg*m/s^2", "kg*m/s^2"),
+            ("=0.02 $/GB/month", "$/GB/month"),
+            ("=100 ft²", "ft²"),
+            ("=50 s^-1", "s^-1"),
+            ("=2 m^1.5", "m^1.5"),
+        ];
+        for (formula, unit) in cases {
+            let expr = parse_formula(formula).unwrap();
+            assert!(
+                matches!(expr, Expr::NumberWithUnit { unit: ref u, .. } if u == unit),
+                "{} parsed as {:?}",
+                formula,
+                expr
+            );
+        }
+
+        // A unit never swallows a following cell reference
+        let expr = parse_formula("=10m*B1").unwrap();
+        assert_eq!(
+            expr,
+            Expr::new_multiply(Expr::number_with_unit(10.0, "m"), Expr::cell_ref("B", 1))
+        );
+    }
+
+    #[test]
+    fn test_parse_error_literals() {
+        let expr = parse_formula("=#REF! * 2").unwrap();
+        assert_eq!(
+            expr,
+            Expr::new_multiply(Expr::Error(ErrorKind::Ref), Expr::number(2.0))
+        );
+        assert_eq!(expr.to_string(), "(#REF! * 2)");
+
+        let expr = parse_formula("=IFNA(#N/A, #DIV/0!)").unwrap();
+        assert_eq!(expr.to_string(), "IFNA(#N/A, #DIV/0!)");
+    }
+
+    #[test]
+    fn test_parse_spill_references() {
+        let expr = parse_formula("=SUM(A1#)").unwrap();
+        assert_eq!(
+            expr,
+            Expr::function("SUM", vec![Expr::spill_ref(Expr::cell_ref("A", 1))])
+        );
+        assert_eq!(expr.to_string(), "SUM(A1#)");
+
+        let expr = parse_formula("=Sheet2!B3# * 2").unwrap();
+        assert_eq!(expr.to_string(), "(Sheet2!B3# * 2)");
+        assert!(parse_formula("=#SPILL!").is_ok());
+    }
+
+    #[test]
+    fn test_parse_concatenation() {
+        // & binds looser than + and tighter than comparisons
+        let expr = parse_formula("=\"Total: \" & A1 + 1 & \" ft\"").unwrap();
+        assert_eq!(
+            expr,
+            Expr::concat(
+                Expr::concat(
+                    Expr::string("Total: "),
+                    Expr::new_add(Expr::cell_ref("A", 1), Expr::number(1.0)),
+                ),
+                Expr::string(" ft"),
+            )
+        );
+        let expr = parse_formula("=A1 & B1 = \"ab\"").unwrap();
+        assert!(matches!(expr, Expr::Equal(..)));
+        assert_eq!(expr.to_string(), "((A1 & B1) = \"ab\")");
+
+        // && is still AND
+        let expr = parse_formula("=A1 && B1").unwrap();
+        assert!(matches!(expr, Expr::And(..)));
+    }
+
+    #[test]
+    fn test_parse_absolute_references() {
+        let expr = parse_formula("=$A$1").unwrap();
+        assert_eq!(expr, Expr::absolute_cell_ref("A", 1, true, true));
+
+        let expr = parse_formula("=B$2 * $C3").unwrap();
+        assert_eq!(
+            expr,
+            Expr::new_multiply(
+                Expr::absolute_cell_ref("B", 2, false, true),
+                Expr::absolute_cell_ref("C", 3, true, false),
+            )
+        );
+
+        let expr = parse_formula("=SUM($A$1:A10)").unwrap();
+        assert_eq!(
+            expr,
+            Expr::function(
+                "SUM",
+                vec![Expr::range(
+                    Expr::absolute_cell_ref("A", 1, true, true),
+                    Expr::cell_ref("A", 10),
+                )],
+            )
+        );
+
+        // "$" followed by a number is still currency
+        let expr = parse_formula("=$15").unwrap();
+        assert!(matches!(expr, Expr::NumberWithUnit { .. }));
+
+        // The anchors survive a round trip through Display
+        let expr = parse_formula("=Sheet2!$B$3 + A$1").unwrap();
+        assert_eq!(expr.to_string(), "(Sheet2!$B$3 + A$1)");
+    }
+
+    #[test]
+    fn test_parse_lambda() {
+        let lambda =
+            parse_lambda("=LAMBDA(rate: USD/hr, util, rate * util * 730 hr/month)").unwrap();
+        assert_eq!(
+            lambda.params,
+            vec![
+                LambdaParam {
+                    name: "rate".to_string(),
+                    unit: Some("USD/hr".to_string()),
+                },
+                LambdaParam {
+                    name: "util".to_string(),
+                    unit: None,
+                },
+            ]
+        );
+        assert_eq!(lambda.body.to_string(), "((rate * util) * 730hr/month)");
+
+        // A parameter named like the body is still a parameter; a function may take none
+        let lambda = parse_lambda("lambda(x, x)").unwrap();
+        assert_eq!(lambda.params.len(), 1);
+        assert_eq!(lambda.body, Expr::named_ref("x"));
+        assert!(parse_lambda("LAMBDA(42)").unwrap().params.is_empty());
+
+        // Parameters are names, not cell references
+        assert!(parse_lambda("LAMBDA(A1, A1 * 2)").is_err());
+        assert!(parse_lambda("LAMBDA(x, x * 2").is_err());
+        assert!(parse_lambda("x * 2").is_err());
+    }
 }
@@ -3,7 +3,10 @@
 pub mod cell;
 pub mod cell_input;
 pub mod conversion;
+pub mod datetime;
 pub mod formula;
+pub mod precision;
+pub mod random;
 pub mod settings;
 pub mod table;
 pub mod units;
@@ -0,0 +1,153 @@
+// Significant figures of measured values
+//
+// A number typed into a cell is known to the significant figures it was written with:
+// "12.0 m" has three and "12 m" two. Formula results carry the precision of their operands
+// by the usual rules: a product or quotient has as many significant figures as its least
+// precise operand, and a sum or difference is known to the decimal place of its least
+// precise operand. Numbers written in formulas are exact and don't limit the result.
+// In significant-figures mode (a workbook setting), numbers are shown to their precision.
+
+/// The significant figures of a number as written, e.g. 3 for "12.0", 2 for "1200" and
+/// 4 for "1200." (trailing zeros of a whole number only hold the place), 2 for "0.0045"
+/// and 3 for "1.20e3". Zero is known to its last decimal place: "0.00" counts as 3
+/// Returns None if the text is not a decimal number
+pub fn count_significant_figures(number: &str) -> Option<u32> {
+    let number = number.trim().trim_start_matches(['+', '-']);
+    let mantissa = number.split(['e', 'E']).next()?;
+    if !mantissa.chars().any(|c| c.is_ascii_digit())
+        || !mantissa.chars().all(|c| c.is_ascii_digit() || c == '.')
+    {
+        return None;
+    }
+
+    let (integer, fraction) = mantissa.split_once('.').unwrap_or((mantissa, ""));
+    let digits = format!("{}{}", integer, fraction);
+    let significant = digits.trim_start_matches('0');
+    if significant.is_empty() {
+        return Some(fraction.len() as u32 + 1);
+    }
+    let significant = if mantissa.contains('.') {
+        significant
+    } else {
+        significant.trim_end_matches('0')
+    };
+    Some(significant.len() as u32)
+}
+
+/// The power of ten of a number's first digit (2 for 123.4, -3 for 0.0012); 0 for zero
+fn magnitude(value: f64) -> i32 {
+    if value == 0.0 {
+        0
+    } else {
+        value.abs().log10().floor() as i32
+    }
+}
+
+/// The power of ten of the last significant digit: 0 for units, -1 for tenths, 2 for
+/// hundreds. 12.0 with 3 significant figures is known to the tenths (-1)
+pub fn last_digit_place(value: f64, figures: u32) -> i32 {
+    magnitude(value) - figures as i32 + 1
+}
+
+/// Round a value to the given power of ten (-1 for tenths)
+fn round_to_place(value: f64, place: i32) -> f64 {
+    let scale = 10f64.powi(place);
+    (value / scale).round() * scale
+}
+
+/// The significant figures of a value known to a decimal place; at least one
+pub fn figures_at_place(value: f64, place: i32) -> u32 {
+    let rounded = round_to_place(value, place);
+    (magnitude(rounded) - place + 1).max(1) as u32
+}
+
+/// Significant figures of a product or quotient: those of its least precise operand
+/// None (exact) if no operand has a precision
+pub fn product_figures(operands: &[Option<u32>]) -> Option<u32> {
+    operands.iter().flatten().min().copied()
+}
+
+/// Significant figures of a sum or difference, which is known to the decimal place of its
+/// least precise operand. Each operand is given as its value, its significant figures and
+/// the factor that converts it to the unit of the sum
+/// None (exact) if no operand has a precision
+pub fn sum_figures(sum: f64, operands: &[(f64, Option<u32>, f64)]) -> Option<u32> {
+    let place = operands
+        .iter()
+        .filter_map(|&(value, figures, scale)| {
+            let shift = scale.abs().log10().floor() as i32;
+            Some(last_digit_place(value, figures?).saturating_add(shift))
+        })
+        .max()?;
+    Some(figures_at_place(sum, place))
+}
+
+/// Write a number rounded to its significant figures, keeping significant zeros:
+/// 12 with 3 figures is "12.0", 1234 with 2 is "1200" and 0.004567 with 2 is "0.0046"
+pub fn format_significant(value: f64, figures: u32) -> String {
+    let figures = figures.max(1);
+    let rounded = round_to_place(value, last_digit_place(value, figures));
+    // Rounding up can add a digit (9.96 to 10.0), which moves the last significant place
+    let place = last_digit_place(rounded, figures);
+    let rounded = round_to_place(rounded, place);
+    let rounded = if rounded == 0.0 { 0.0 } else { rounded };
+    format!("{:.*}", (-place).max(0) as usize, rounded)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_count_significant_figures() {
+        assert_eq!(count_significant_figures("12.0"), Some(3));
+        assert_eq!(count_significant_figures("12"), Some(2));
+        assert_eq!(count_significant_figures("1200"), Some(2));
+        assert_eq!(count_significant_figures("1200."), Some(4));
+        assert_eq!(count_significant_figures("-0.0045"), Some(2));
+        assert_eq!(count_significant_figures("1.20e3"), Some(3));
+        assert_eq!(count_significant_figures("0"), Some(1));
+        assert_eq!(count_significant_figures("0.00"), Some(3));
+        assert_eq!(count_significant_figures("inf"), None);
+        assert_eq!(count_significant_figures("."), None);
+    }
+
+    #[test]
+    fn test_arithmetic_rules() {
+        // 12.0 * 3.1 has two significant figures
+        assert_eq!(product_figures(&[Some(3), Some(2)]), Some(2));
+        assert_eq!(product_figures(&[None, Some(4)]), Some(4));
+        assert_eq!(product_figures(&[None, None]), None);
+
+        // 12.11 + 0.3 is known to the tenths: 12.4
+        assert_eq!(
+            sum_figures(12.41, &[(12.11, Some(4), 1.0), (0.3, Some(1), 1.0)]),
+            Some(3)
+        );
+        // 100.0 cm + 2.0 m in cm: the metres are known to tens of centimetres
+        assert_eq!(
+            sum_figures(300.0, &[(100.0, Some(4), 1.0), (2.0, Some(2), 100.0)]),
+            Some(2)
+        );
+        // 12.0 - 12.0 is 0.0
+        assert_eq!(
+            sum_figures(0.0, &[(12.0, Some(3), 1.0), (12.0, Some(3), -1.0)]),
+            Some(2)
+        );
+        assert_eq!(
+            sum_figures(5.0, &[(2.0, None, 1.0), (3.0, None, 1.0)]),
+            None
+        );
+    }
+
+    #[test]
+    fn test_format_significant() {
+        assert_eq!(format_significant(12.0, 3), "12.0");
+        assert_eq!(format_significant(1234.0, 2), "1200");
+        assert_eq!(format_significant(0.004567, 2), "0.0046");
+        assert_eq!(format_significant(9.96, 2), "10");
+        assert_eq!(format_significant(-2.345, 3), "-2.35");
+        assert_eq!(format_significant(0.0, 2), "0.0");
+        assert_eq!(format_significant(-0.001, 1), "-0.001");
+    }
+}
@@ -0,0 +1,99 @@
+// Random numbers for RAND and RANDBETWEEN
+//
+// Numbers come from SplitMix64, which is small and gives the same sequence for a seed on
+// every platform and in every version. Without a seed, a formula draws new numbers each time
+// it is calculated. With a seed (a workbook setting), each cell draws from its own stream,
+// which depends only on the seed and the cell, so a model shows the same numbers on every
+// recalculation and wherever it is opened; changing the seed gives a new draw.
+
+use crate::core::table::CellAddr;
+use std::cell::Cell;
+use std::collections::hash_map::RandomState;
+use std::hash::{BuildHasher, Hasher};
+
+/// A stream of random numbers, drawn through a shared reference
+#[derive(Debug)]
+pub struct RandomStream {
+    /// None until an unseeded stream draws its first number
+    state: Cell<Option<u64>>,
+}
+
+impl RandomStream {
+    /// A stream that gives different numbers every time
+    pub fn unseeded() -> Self {
+        Self {
+            state: Cell::new(None),
+        }
+    }
+
+    /// A stream that always gives the same numbers for a seed
+    pub fn seeded(seed: u64) -> Self {
+        Self {
+            state: Cell::new(Some(seed)),
+        }
+    }
+
+    /// The stream the formula in a cell draws from, seeded from the workbook seed and the
+    /// cell's position, or unseeded if the workbook has no seed
+    pub fn for_cell(seed: Option<u64>, sheet: &str, addr: &CellAddr) -> Self {
+        match seed {
+            Some(seed) => {
+                let position = format!("{}!{}", sheet, addr);
+                Self::seeded(
+                    position
+                        .bytes()
+                        .fold(mix(seed), |state, byte| mix(state ^ u64::from(byte))),
+                )
+            }
+            None => Self::unseeded(),
+        }
+    }
+
+    /// The next number, at least 0 and less than 1
+    pub fn next_f64(&self) -> f64 {
+        let state = self
+            .state
+            .get()
+            .unwrap_or_else(|| RandomState::new().build_hasher().finish())
+            .wrapping_add(0x9E37_79B9_7F4A_7C15);
+        self.state.set(Some(state));
+        // The top 53 bits fill an f64's mantissa
+        (mix(state) >> 11) as f64 / (1u64 << 53) as f64
+    }
+}
+
+/// SplitMix64's output function, which scrambles the bits of its input
+fn mix(value: u64) -> u64 {
+    let value = (value ^ (value >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+    let value = (value ^ (value >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+    value ^ (value >> 31)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_seeded_streams() {
+        let draw = |stream: RandomStream| (0..5).map(|_| stream.next_f64()).collect::<Vec<_>>();
+
+        let numbers = draw(RandomStream::seeded(42));
+        assert_eq!(numbers, draw(RandomStream::seeded(42)));
+        assert_ne!(numbers, draw(RandomStream::seeded(43)));
+        assert!(numbers.iter().all(|n| (0.0..1.0).contains(n)));
+
+        // Each cell has its own stream
+        let a1 = CellAddr::new("A", 1);
+        let cell =
+            |sheet: &str, addr: &CellAddr| draw(RandomStream::for_cell(Some(7), sheet, addr));
+        assert_eq!(cell("Sheet1", &a1), cell("Sheet1", &a1));
+        assert_ne!(cell("Sheet1", &a1), cell("Sheet1", &CellAddr::new("A", 2)));
+        assert_ne!(cell("Sheet1", &a1), cell("Sheet2", &a1));
+
+        // Unseeded streams differ
+        assert_ne!(
+            draw(RandomStream::unseeded()),
+            draw(RandomStream::unseeded())
+        );
+    }
+}
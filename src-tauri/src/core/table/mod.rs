@@ -2,4 +2,6 @@
 
 pub mod sheet;
 
-pub use sheet::{CellAddr, DependencyGraph, Sheet, SheetError};
+pub use sheet::{
+    CellAddr, CellRange, DependencyGraph, DependencyKey, RowColumnChange, Sheet, SheetError,
+};
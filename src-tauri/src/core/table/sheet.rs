@@ -1,10 +1,16 @@
 // Spreadsheet sheet with cell management
 
-use crate::core::cell::{Cell, CellValue};
-use crate::core::formula::{parse_formula, EvalError, EvalResult, Expr};
+use crate::core::cell::{Cell, CellError, CellValue, ErrorKind};
+use crate::core::formula::evaluator::{Array, CellGrid, EvalContext, EvalValue};
+use crate::core::formula::{
+    parse_formula, reference_spans, EvalError, EvalResult, Evaluator, Expr, FunctionRegistry,
+    ResolvedReference,
+};
+use crate::core::random::RandomStream;
 use crate::core::units::UnitLibrary;
-use statrs::statistics::{Data, Distribution, OrderStatistics};
 use std::collections::{HashMap, HashSet};
+use std::hash::Hash;
+use std::sync::Arc;
 use thiserror::Error;
 
 #[derive(Debug, Error)]
@@ -20,6 +26,24 @@ pub enum SheetError {
 
     #[error("Evaluation error: {0}")]
     EvalError(#[from] EvalError),
+
+    #[error("Cell {0} is part of a spilled array; edit the formula in {1} instead")]
+    SpilledCell(String, String),
+}
+
+impl SheetError {
+    /// The error value to store in a cell whose formula failed with this error
+    pub fn to_cell_error(&self) -> CellError {
+        match self {
+            SheetError::EvalError(e) => e.to_cell_error(),
+            SheetError::InvalidCellRef(_) => CellError::new(ErrorKind::Ref, self.to_string()),
+            SheetError::CircularReference(_) => {
+                CellError::new(ErrorKind::Circular, self.to_string())
+            }
+            SheetError::ParseError(_) => CellError::new(ErrorKind::Name, self.to_string()),
+            SheetError::SpilledCell(..) => CellError::new(ErrorKind::Spill, self.to_string()),
+        }
+    }
 }
 
 /// A cell address (column, row)
@@ -67,23 +91,205 @@ impl std::fmt::Display for CellAddr {
     }
 }
 
+/// A rectangular block of cells (A1:C10), or whole columns (B:B) or rows (3:3)
+/// Column and row numbers are 1-based and inclusive; whole columns span every row
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct CellRange {
+    pub start_col: usize,
+    pub end_col: usize,
+    pub start_row: usize,
+    pub end_row: usize,
+}
+
+impl CellRange {
+    /// Create a range between two corner cells (given in any order)
+    pub fn new(start: &CellAddr, end: &CellAddr) -> Self {
+        let start_col = col_letter_to_num(&start.col).unwrap_or(0);
+        let end_col = col_letter_to_num(&end.col).unwrap_or(0);
+        Self {
+            start_col: start_col.min(end_col),
+            end_col: start_col.max(end_col),
+            start_row: start.row.min(end.row),
+            end_row: start.row.max(end.row),
+        }
+    }
+
+    /// Create a range covering whole columns (B:B, A:C)
+    pub fn columns(start: &str, end: &str) -> Self {
+        let start_col = col_letter_to_num(start).unwrap_or(0);
+        let end_col = col_letter_to_num(end).unwrap_or(0);
+        Self {
+            start_col: start_col.min(end_col),
+            end_col: start_col.max(end_col),
+            start_row: 1,
+            end_row: usize::MAX,
+        }
+    }
+
+    /// Create a range covering whole rows (3:3, 2:5)
+    pub fn rows(start: usize, end: usize) -> Self {
+        Self {
+            start_col: 1,
+            end_col: usize::MAX,
+            start_row: start.min(end),
+            end_row: start.max(end),
+        }
+    }
+
+    /// Get the range referred to by a Range, ColumnRange or RowRange expression
+    pub fn from_expr(expr: &Expr) -> Option<Self> {
+        match expr {
+            Expr::Range { start, end } => match (start.as_ref(), end.as_ref()) {
+                (
+                    Expr::CellRef {
+                        col: start_col,
+                        row: start_row,
+                        ..
+                    },
+                    Expr::CellRef {
+                        col: end_col,
+                        row: end_row,
+                        ..
+                    },
+                ) => Some(Self::new(
+                    &CellAddr::new(start_col.as_str(), *start_row),
+                    &CellAddr::new(end_col.as_str(), *end_row),
+                )),
+                _ => None,
+            },
+            Expr::ColumnRange { start, end } => Some(Self::columns(start, end)),
+            Expr::RowRange { start, end } => Some(Self::rows(*start, *end)),
+            _ => None,
+        }
+    }
+
+    /// The expression for a bounded range: a cell reference for a single cell, otherwise a
+    /// range between its corners
+    pub fn to_expr(&self) -> Option<Expr> {
+        let cell = |col: usize, row: usize| Some(Expr::cell_ref(col_num_to_letter(col).ok()?, row));
+        let start = cell(self.start_col, self.start_row)?;
+        if self.start_col == self.end_col && self.start_row == self.end_row {
+            return Some(start);
+        }
+        Some(Expr::range(start, cell(self.end_col, self.end_row)?))
+    }
+
+    /// The range moved `rows` down and `cols` right, resized to `height` by `width` cells (OFFSET)
+    /// None if it would start above row 1 or left of column A
+    pub fn offset(&self, rows: i64, cols: i64, height: usize, width: usize) -> Option<Self> {
+        let shift = |start: usize, by: i64| {
+            usize::try_from(i64::try_from(start).ok()?.checked_add(by)?)
+                .ok()
+                .filter(|start| *start >= 1)
+        };
+        let start_row = shift(self.start_row, rows)?;
+        let start_col = shift(self.start_col, cols)?;
+        Some(Self {
+            start_col,
+            end_col: start_col.checked_add(width.checked_sub(1)?)?,
+            start_row,
+            end_row: start_row.checked_add(height.checked_sub(1)?)?,
+        })
+    }
+
+    /// Check whether a cell lies inside the range
+    pub fn contains(&self, addr: &CellAddr) -> bool {
+        let col = col_letter_to_num(&addr.col).unwrap_or(0);
+        (self.start_col..=self.end_col).contains(&col)
+            && (self.start_row..=self.end_row).contains(&addr.row)
+    }
+
+    /// The column numbers the range covers, or None if it is wider than `MAX_INDEXED_COLUMNS`
+    /// (whole rows)
+    fn indexed_columns(&self) -> Option<std::ops::RangeInclusive<usize>> {
+        (self.end_col - self.start_col < MAX_INDEXED_COLUMNS)
+            .then_some(self.start_col..=self.end_col)
+    }
+}
+
+/// Widest range a dependency graph files under each of its columns; wider ones (whole rows)
+/// are checked against every cell that changes
+const MAX_INDEXED_COLUMNS: usize = 64;
+
+/// A key in a dependency graph
+/// Besides single keys, a key can depend on a whole range of keys, which the graph
+/// stores as one entry rather than one edge per cell
+pub trait DependencyKey: Clone + Eq + Hash {
+    /// A block of keys that can be depended on as a whole
+    type Range: Clone + Eq + Hash;
+
+    /// What ranges are filed under, so that the ones a key lies in are found without checking
+    /// them all (a column)
+    type Bucket: Clone + Eq + Hash;
+
+    /// Check whether this key lies inside a range
+    fn in_range(&self, range: &Self::Range) -> bool;
+
+    /// The bucket of the ranges this key may lie in, or None if it is never in a range
+    fn bucket(&self) -> Option<Self::Bucket>;
+
+    /// The buckets a range is filed under, or None if it covers too many to list
+    fn range_buckets(range: &Self::Range) -> Option<Vec<Self::Bucket>>;
+}
+
+impl DependencyKey for CellAddr {
+    type Range = CellRange;
+    type Bucket = usize;
+
+    fn in_range(&self, range: &CellRange) -> bool {
+        range.contains(self)
+    }
+
+    fn bucket(&self) -> Option<usize> {
+        col_letter_to_num(&self.col).ok()
+    }
+
+    fn range_buckets(range: &CellRange) -> Option<Vec<usize>> {
+        range.indexed_columns().map(Iterator::collect)
+    }
+}
+
 /// Dependency graph for tracking cell dependencies
-#[derive(Debug, Default)]
-pub struct DependencyGraph {
+/// Keyed by `CellAddr` within a sheet; the workbook keys it by (sheet_index, CellAddr)
+#[derive(Debug)]
+pub struct DependencyGraph<K: DependencyKey = CellAddr> {
     /// Maps a cell to the cells it depends on
-    dependencies: HashMap<CellAddr, HashSet<CellAddr>>,
+    dependencies: HashMap<K, HashSet<K>>,
 
     /// Maps a cell to the cells that depend on it
-    dependents: HashMap<CellAddr, HashSet<CellAddr>>,
+    dependents: HashMap<K, HashSet<K>>,
+
+    /// Maps a cell to the ranges it depends on (e.g., SUM(A1:C10), MAX(B:B))
+    range_dependencies: HashMap<K, HashSet<K::Range>>,
+
+    /// Maps a range to the cells that depend on it
+    range_dependents: HashMap<K::Range, HashSet<K>>,
+
+    /// The ranges in `range_dependents` by the buckets (columns) they cover, so that finding
+    /// the ranges a cell lies in does not check every range; ranges covering too many buckets
+    /// to list are under None
+    range_index: HashMap<Option<K::Bucket>, HashSet<K::Range>>,
+}
+
+impl<K: DependencyKey> Default for DependencyGraph<K> {
+    fn default() -> Self {
+        Self {
+            dependencies: HashMap::new(),
+            dependents: HashMap::new(),
+            range_dependencies: HashMap::new(),
+            range_dependents: HashMap::new(),
+            range_index: HashMap::new(),
+        }
+    }
 }
 
-impl DependencyGraph {
+impl<K: DependencyKey> DependencyGraph<K> {
     pub fn new() -> Self {
         Self::default()
     }
 
     /// Add a dependency: `cell` depends on `depends_on`
-    pub fn add_dependency(&mut self, cell: CellAddr, depends_on: CellAddr) {
+    pub fn add_dependency(&mut self, cell: K, depends_on: K) {
         self.dependencies
             .entry(cell.clone())
             .or_default()
@@ -92,8 +298,35 @@ impl DependencyGraph {
         self.dependents.entry(depends_on).or_default().insert(cell);
     }
 
+    /// Add a range dependency: `cell` depends on every cell inside `range`
+    pub fn add_range_dependency(&mut self, cell: K, range: K::Range) {
+        self.range_dependencies
+            .entry(cell.clone())
+            .or_default()
+            .insert(range.clone());
+
+        let dependents = self.range_dependents.entry(range.clone()).or_default();
+        if dependents.is_empty() {
+            for bucket in Self::range_buckets(&range) {
+                self.range_index
+                    .entry(bucket)
+                    .or_default()
+                    .insert(range.clone());
+            }
+        }
+        dependents.insert(cell);
+    }
+
+    /// The buckets a range is filed under in `range_index`
+    fn range_buckets(range: &K::Range) -> Vec<Option<K::Bucket>> {
+        match K::range_buckets(range) {
+            Some(buckets) => buckets.into_iter().map(Some).collect(),
+            None => vec![None],
+        }
+    }
+
     /// Remove all dependencies for a cell
-    pub fn remove_dependencies(&mut self, cell: &CellAddr) {
+    pub fn remove_dependencies(&mut self, cell: &K) {
         // Remove from dependencies map
         if let Some(deps) = self.dependencies.remove(cell) {
             // Remove from dependents of each dependency
@@ -103,37 +336,71 @@ impl DependencyGraph {
                 }
             }
         }
+
+        for range in self.range_dependencies.remove(cell).unwrap_or_default() {
+            let Some(dependents) = self.range_dependents.get_mut(&range) else {
+                continue;
+            };
+            dependents.remove(cell);
+            if !dependents.is_empty() {
+                continue;
+            }
+
+            // Nothing depends on the range any more
+            self.range_dependents.remove(&range);
+            for bucket in Self::range_buckets(&range) {
+                if let Some(ranges) = self.range_index.get_mut(&bucket) {
+                    ranges.remove(&range);
+                    if ranges.is_empty() {
+                        self.range_index.remove(&bucket);
+                    }
+                }
+            }
+        }
     }
 
     /// Get cells that this cell depends on
-    pub fn get_dependencies(&self, cell: &CellAddr) -> HashSet<CellAddr> {
+    pub fn get_dependencies(&self, cell: &K) -> HashSet<K> {
         self.dependencies.get(cell).cloned().unwrap_or_default()
     }
 
-    /// Get cells that depend on this cell
-    pub fn get_dependents(&self, cell: &CellAddr) -> HashSet<CellAddr> {
-        self.dependents.get(cell).cloned().unwrap_or_default()
+    /// Get ranges that this cell depends on
+    pub fn get_range_dependencies(&self, cell: &K) -> HashSet<K::Range> {
+        self.range_dependencies
+            .get(cell)
+            .cloned()
+            .unwrap_or_default()
+    }
+
+    /// Get cells that depend on this cell, directly or through a range
+    pub fn get_dependents(&self, cell: &K) -> HashSet<K> {
+        let mut dependents = self.dependents.get(cell).cloned().unwrap_or_default();
+        let buckets = [cell.bucket(), None];
+        let ranges = buckets
+            .iter()
+            .filter_map(|bucket| self.range_index.get(bucket))
+            .flatten()
+            .filter(|range| cell.in_range(range));
+        for range in ranges {
+            dependents.extend(self.range_dependents[range].iter().cloned());
+        }
+        dependents
     }
 
     /// Check for circular references starting from a cell
-    pub fn has_circular_reference(&self, start: &CellAddr) -> bool {
+    /// Follows dependents rather than dependencies, so ranges do not have to be expanded
+    pub fn has_circular_reference(&self, start: &K) -> bool {
         let mut visited = HashSet::new();
-        let mut stack = vec![start.clone()];
+        let mut stack: Vec<K> = self.get_dependents(start).into_iter().collect();
 
         while let Some(cell) = stack.pop() {
-            if !visited.insert(cell.clone()) {
-                // We've seen this cell before in this path
-                if cell == *start {
-                    return true;
-                }
-                continue;
+            // Getting back to the start means the start depends on itself
+            if cell == *start {
+                return true;
             }
 
-            // Add dependencies to explore
-            if let Some(deps) = self.dependencies.get(&cell) {
-                for dep in deps {
-                    stack.push(dep.clone());
-                }
+            if visited.insert(cell.clone()) {
+                stack.extend(self.get_dependents(&cell));
             }
         }
 
@@ -142,7 +409,7 @@ impl DependencyGraph {
 
     /// Get the calculation order (topological sort)
     /// Returns cells in dependency order (cells with no deps first)
-    pub fn calculation_order(&self, changed_cells: &[CellAddr]) -> Vec<CellAddr> {
+    pub fn calculation_order(&self, changed_cells: &[K]) -> Vec<K> {
         // First, collect all cells that need recalculation (changed cells + their dependents)
         let mut to_recalc = HashSet::new();
         for cell in changed_cells {
@@ -164,17 +431,15 @@ impl DependencyGraph {
     }
 
     /// Collect all cells affected by a change (the cell and all its dependents recursively)
-    fn collect_affected_cells(&self, cell: &CellAddr, affected: &mut HashSet<CellAddr>) {
+    fn collect_affected_cells(&self, cell: &K, affected: &mut HashSet<K>) {
         if affected.contains(cell) {
             return;
         }
         affected.insert(cell.clone());
 
         // Add all dependents recursively
-        if let Some(deps) = self.dependents.get(cell) {
-            for dep in deps {
-                self.collect_affected_cells(dep, affected);
-            }
+        for dep in self.get_dependents(cell) {
+            self.collect_affected_cells(&dep, affected);
         }
     }
 
@@ -182,11 +447,11 @@ impl DependencyGraph {
     /// Visits dependencies before the cell itself
     fn topological_sort(
         &self,
-        cell: &CellAddr,
-        visited: &mut HashSet<CellAddr>,
-        temp_mark: &mut HashSet<CellAddr>,
-        order: &mut Vec<CellAddr>,
-        to_recalc: &HashSet<CellAddr>,
+        cell: &K,
+        visited: &mut HashSet<K>,
+        temp_mark: &mut HashSet<K>,
+        order: &mut Vec<K>,
+        to_recalc: &HashSet<K>,
     ) {
         if visited.contains(cell) {
             return;
@@ -209,10 +474,121 @@ impl DependencyGraph {
             }
         }
 
+        // Cells being recalculated that lie inside a range this cell depends on
+        if let Some(ranges) = self.range_dependencies.get(cell) {
+            for dep in to_recalc {
+                if ranges.iter().any(|range| dep.in_range(range)) {
+                    self.topological_sort(dep, visited, temp_mark, order, to_recalc);
+                }
+            }
+        }
+
         temp_mark.remove(cell);
         visited.insert(cell.clone());
         order.push(cell.clone());
     }
+
+    /// Groups of `keys` that depend on each other in a loop (A1 = B1 + 1 and B1 = A1 / 2),
+    /// including keys that depend on themselves
+    /// A loop through one of the keys lies entirely within the keys if they include all of
+    /// their dependents, as `calculation_order` does
+    pub fn cycles(&self, keys: &[K]) -> Vec<Vec<K>> {
+        let keys: HashSet<K> = keys.iter().cloned().collect();
+        let mut search = CycleSearch {
+            index: HashMap::new(),
+            low: HashMap::new(),
+            stack: Vec::new(),
+            on_stack: HashSet::new(),
+            cycles: Vec::new(),
+        };
+
+        for key in &keys {
+            if !search.index.contains_key(key) {
+                self.strong_connect(key, &keys, &mut search);
+            }
+        }
+
+        search.cycles
+    }
+
+    /// Tarjan's algorithm: find the loops through `key`, following dependents
+    fn strong_connect(&self, key: &K, keys: &HashSet<K>, search: &mut CycleSearch<K>) {
+        let index = search.index.len();
+        search.index.insert(key.clone(), index);
+        search.low.insert(key.clone(), index);
+        search.stack.push(key.clone());
+        search.on_stack.insert(key.clone());
+
+        let mut depends_on_itself = false;
+        for dependent in self.get_dependents(key) {
+            if !keys.contains(&dependent) {
+                continue;
+            }
+            depends_on_itself |= dependent == *key;
+
+            let low = if !search.index.contains_key(&dependent) {
+                self.strong_connect(&dependent, keys, search);
+                search.low[&dependent]
+            } else if search.on_stack.contains(&dependent) {
+                search.index[&dependent]
+            } else {
+                continue;
+            };
+            if low < search.low[key] {
+                search.low.insert(key.clone(), low);
+            }
+        }
+
+        // `key` is the first of its group to be found: the group is everything found since
+        if search.low[key] == index {
+            let mut group = Vec::new();
+            while let Some(member) = search.stack.pop() {
+                search.on_stack.remove(&member);
+                let first = member == *key;
+                group.push(member);
+                if first {
+                    break;
+                }
+            }
+            if group.len() > 1 || depends_on_itself {
+                search.cycles.push(group);
+            }
+        }
+    }
+}
+
+/// Progress of a search for loops in a dependency graph
+struct CycleSearch<K> {
+    /// The order keys were found in
+    index: HashMap<K, usize>,
+    /// The earliest key found that each key leads back to
+    low: HashMap<K, usize>,
+    /// Keys found whose group is not complete yet
+    stack: Vec<K>,
+    on_stack: HashSet<K>,
+    /// Groups found so far
+    cycles: Vec<Vec<K>>,
+}
+
+/// Most recalculation passes after array formulas spill, in case a spill feeds back into
+/// its own formula (=SEQUENCE(COUNT(B:B)) in B1)
+pub const MAX_SPILL_PASSES: usize = 16;
+
+/// Rows and columns on a sheet (Excel's limits)
+pub const MAX_ROWS: usize = 1_048_576;
+pub const MAX_COLUMNS: usize = 16_384;
+
+/// Most cells a formula can build a range or array of (OFFSET, SEQUENCE), so that a mistyped
+/// size gives an error rather than using up all memory
+pub const MAX_ARRAY_CELLS: usize = 100_000;
+
+/// The cells an array formula spills into
+#[derive(Debug, Clone)]
+struct Spill {
+    /// The whole array, with the formula's cell at its top-left
+    range: CellRange,
+    /// Other cells are in the way, so the formula shows #SPILL! instead
+    blocked: bool,
 }
 
 /// A spreadsheet sheet
@@ -224,12 +600,28 @@ pub struct Sheet {
     /// Cell storage
     cells: HashMap<CellAddr, Cell>,
 
+    /// Array formulas' spill areas, by the formula's cell
+    spills: HashMap<CellAddr, Spill>,
+
+    /// Values in the cells array formulas spilled into (other than the formulas' own cells)
+    spilled: HashMap<CellAddr, Cell>,
+
     /// Dependency graph
     dependencies: DependencyGraph,
 
     /// Unit library for conversions
     library: UnitLibrary,
 
+    /// Functions available to formulas
+    functions: Arc<FunctionRegistry>,
+
+    /// Seed for RAND and RANDBETWEEN, or None for different numbers on every calculation
+    random_seed: Option<u64>,
+
+    /// Whether the sheet keeps its own dependency graph, which a workbook's sheets leave to
+    /// the workbook, as it also tracks references between sheets and through names
+    track_dependencies: bool,
+
     /// Column widths (in pixels)
     column_widths: HashMap<String, f64>,
 
@@ -246,8 +638,13 @@ impl Sheet {
         Self {
             name: name.into(),
             cells: HashMap::new(),
+            spills: HashMap::new(),
+            spilled: HashMap::new(),
             dependencies: DependencyGraph::new(),
             library: UnitLibrary::new(),
+            functions: FunctionRegistry::standard().clone(),
+            random_seed: None,
+            track_dependencies: true,
             column_widths: HashMap::new(),
             row_heights: HashMap::new(),
         }
@@ -263,9 +660,34 @@ impl Sheet {
         self.name = name.into();
     }
 
-    /// Get a cell
+    /// Get the unit library formulas on this sheet convert with
+    pub fn library(&self) -> &UnitLibrary {
+        &self.library
+    }
+
+    /// Replace the functions available to formulas on this sheet
+    /// Cells are not recalculated; the workbook does that after changing its registry
+    pub fn set_functions(&mut self, functions: Arc<FunctionRegistry>) {
+        self.functions = functions;
+    }
+
+    /// Seed the random numbers formulas draw (see `RandomStream::for_cell`)
+    /// Cells are not recalculated; the workbook does that after changing its seed
+    pub fn set_random_seed(&mut self, seed: Option<u64>) {
+        self.random_seed = seed;
+    }
+
+    /// Keep the sheet's own dependency graph or not (see `Workbook::set_cell`)
+    /// Without it, formulas are still checked to parse, but not for circular references, and
+    /// `recalculate` works out the order from the formulas each time
+    pub fn set_track_dependencies(&mut self, track: bool) {
+        self.track_dependencies = track;
+        self.rebuild_dependencies().ok();
+    }
+
+    /// Get a cell, including the cells array formulas spilled into
     pub fn get(&self, addr: &CellAddr) -> Option<&Cell> {
-        self.cells.get(addr)
+        self.cells.get(addr).or_else(|| self.spilled.get(addr))
     }
 
     /// Get a mutable reference to a cell
@@ -273,22 +695,26 @@ impl Sheet {
         self.cells.get_mut(addr)
     }
 
-    /// Get cells in a range (single column only for MLP)
+    /// Get the non-empty cells in a rectangular range, in row-major order
     pub fn get_range(&self, start: &CellAddr, end: &CellAddr) -> Vec<(CellAddr, &Cell)> {
-        let mut result = Vec::new();
-
-        // For MLP, only support single-column ranges
-        if start.col != end.col {
-            return result;
-        }
+        self.cells_in_range(&CellRange::new(start, end))
+    }
 
-        for row in start.row..=end.row {
-            let addr = CellAddr::new(&start.col, row);
-            if let Some(cell) = self.get(&addr) {
-                result.push((addr, cell));
-            }
-        }
+    /// Get the non-empty cells in a range (including whole columns and rows), in row-major order
+    pub fn cells_in_range(&self, range: &CellRange) -> Vec<(CellAddr, &Cell)> {
+        let spilled = self
+            .spilled
+            .iter()
+            .filter(|(addr, _)| !self.cells.contains_key(addr));
+        let mut result: Vec<(CellAddr, &Cell)> = self
+            .cells
+            .iter()
+            .chain(spilled)
+            .filter(|(addr, _)| range.contains(addr))
+            .map(|(addr, cell)| (addr.clone(), cell))
+            .collect();
 
+        result.sort_by_key(|(addr, _)| (addr.row, col_letter_to_num(&addr.col).unwrap_or(0)));
         result
     }
 
@@ -301,6 +727,7 @@ impl Sheet {
     /// Clear all cells
     pub fn clear(&mut self) {
         self.cells.clear();
+        self.clear_spills();
         self.dependencies = DependencyGraph::new();
     }
 
@@ -309,11 +736,63 @@ impl Sheet {
         self.cells.keys().cloned().collect()
     }
 
+    /// A copy of the entered cells (not the spilled ones), to put back with `restore_cells`
+    /// if a change to many of them has to be undone
+    pub fn snapshot_cells(&self) -> HashMap<CellAddr, Cell> {
+        self.cells.clone()
+    }
+
+    /// Put back the cells from `snapshot_cells`
+    pub fn restore_cells(&mut self, cells: HashMap<CellAddr, Cell>) {
+        self.cells = cells;
+        self.rebuild_dependencies().ok();
+    }
+
     /// Get the count of non-empty cells
     pub fn cell_count(&self) -> usize {
         self.cells.len()
     }
 
+    /// Get the addresses of the cells array formulas spilled into
+    /// (not included in `cell_addresses`, which lists the cells that were entered)
+    pub fn spilled_addresses(&self) -> Vec<CellAddr> {
+        self.spilled.keys().cloned().collect()
+    }
+
+    /// Get the cells the array formula in `anchor` spilled into, including `anchor` itself
+    /// None if the formula did not give an array, or could not spill (#SPILL!)
+    pub fn spill_range(&self, anchor: &CellAddr) -> Option<CellRange> {
+        self.spills
+            .get(anchor)
+            .filter(|spill| !spill.blocked)
+            .map(|spill| spill.range)
+    }
+
+    /// Get the cell holding the array formula that spilled into `addr`
+    pub fn spill_anchor(&self, addr: &CellAddr) -> Option<&CellAddr> {
+        if !self.spilled.contains_key(addr) {
+            return None;
+        }
+        self.spills
+            .iter()
+            .find(|(_, spill)| !spill.blocked && spill.range.contains(addr))
+            .map(|(anchor, _)| anchor)
+    }
+
+    /// Get the array formulas to recalculate after `addr` changed: those that could not
+    /// spill over it before, and those whose spilled cells it now covers
+    pub fn spill_anchors_at(&self, addr: &CellAddr) -> Vec<CellAddr> {
+        self.spills
+            .iter()
+            .filter(|(anchor, spill)| {
+                *anchor != addr
+                    && spill.range.contains(addr)
+                    && (spill.blocked || self.cells.contains_key(addr))
+            })
+            .map(|(anchor, _)| anchor.clone())
+            .collect()
+    }
+
     /// Set a cell with a direct value
     pub fn set(&mut self, addr: CellAddr, cell: Cell) -> Result<(), SheetError> {
         self.set_with_named_ranges(addr, cell, None)
@@ -326,6 +805,17 @@ impl Sheet {
         cell: Cell,
         named_range_mapping: Option<&HashMap<String, CellAddr>>,
     ) -> Result<(), SheetError> {
+        // Spilled cells take their values from the array formula; clearing them does nothing
+        if let Some(anchor) = self.spill_anchor(&addr) {
+            if cell.is_formula() || !cell.is_empty() {
+                return Err(SheetError::SpilledCell(
+                    addr.to_string(),
+                    anchor.to_string(),
+                ));
+            }
+            return Ok(());
+        }
+
         // If the cell has a formula, extract dependencies
         if let Some(formula) = cell.formula() {
             self.update_dependencies(&addr, formula, named_range_mapping)?;
@@ -350,6 +840,9 @@ impl Sheet {
 
         // Parse formula and extract cell references
         let expr = parse_formula(formula).map_err(|e| SheetError::ParseError(e.to_string()))?;
+        if !self.track_dependencies {
+            return Ok(());
+        }
 
         let deps = extract_cell_refs(&expr, named_range_mapping);
 
@@ -357,6 +850,9 @@ impl Sheet {
         for dep in deps {
             self.dependencies.add_dependency(addr.clone(), dep);
         }
+        for range in extract_range_refs(&expr) {
+            self.dependencies.add_range_dependency(addr.clone(), range);
+        }
 
         // Check for circular references
         if self.dependencies.has_circular_reference(addr) {
@@ -380,21 +876,225 @@ impl Sheet {
         formula: &str,
         named_refs: Option<&HashMap<String, (f64, crate::core::units::Unit)>>,
     ) -> Result<(CellValue, crate::core::units::Unit), SheetError> {
-        let expr = parse_formula(formula).map_err(|e| SheetError::ParseError(e.to_string()))?;
+        self.evaluate_formula_with_context(formula, named_refs, None)
+    }
+
+    /// Evaluate a formula with named range context and the workbook's sheets,
+    /// so that sheet-qualified references (Sheet2!A1) can be resolved
+    pub fn evaluate_formula_with_context(
+        &self,
+        formula: &str,
+        named_refs: Option<&HashMap<String, (f64, crate::core::units::Unit)>>,
+        sheets: Option<&[Sheet]>,
+    ) -> Result<(CellValue, crate::core::units::Unit), SheetError> {
+        // An array gives its first value, which is what the formula's own cell shows
+        self.evaluate_formula_result(formula, named_refs, sheets)
+            .map(cell_value)
+    }
+
+    /// Evaluate a formula to its full result, which may be an array to spill
+    pub fn evaluate_formula_result(
+        &self,
+        formula: &str,
+        named_refs: Option<&HashMap<String, (f64, crate::core::units::Unit)>>,
+        sheets: Option<&[Sheet]>,
+    ) -> Result<EvalResult, SheetError> {
+        let random = match self.random_seed {
+            Some(seed) => RandomStream::seeded(seed),
+            None => RandomStream::unseeded(),
+        };
+        self.evaluate_with_random(formula, named_refs, sheets, random)
+            .0
+    }
+
+    /// Evaluate the formula in a cell to its full result, or None if the cell has no formula
+    /// Random numbers come from the cell's own stream. Also returns the references INDIRECT
+    /// and OFFSET resolved, which the cell depends on until it is next evaluated
+    pub fn evaluate_cell(
+        &self,
+        addr: &CellAddr,
+        named_refs: Option<&HashMap<String, (f64, crate::core::units::Unit)>>,
+        sheets: Option<&[Sheet]>,
+    ) -> Option<(Result<EvalResult, SheetError>, Vec<ResolvedReference>)> {
+        let formula = self.cells.get(addr)?.formula()?;
+        let random = RandomStream::for_cell(self.random_seed, &self.name, addr);
+        Some(self.evaluate_with_random(formula, named_refs, sheets, random))
+    }
+
+    fn evaluate_with_random(
+        &self,
+        formula: &str,
+        named_refs: Option<&HashMap<String, (f64, crate::core::units::Unit)>>,
+        sheets: Option<&[Sheet]>,
+        random: RandomStream,
+    ) -> (Result<EvalResult, SheetError>, Vec<ResolvedReference>) {
+        let expr = match parse_formula(formula) {
+            Ok(expr) => expr,
+            Err(e) => return (Err(SheetError::ParseError(e.to_string())), Vec::new()),
+        };
 
-        let evaluator = SheetEvaluator {
+        let context = SheetContext {
             sheet: self,
-            library: &self.library,
             named_refs,
+            sheets,
         };
 
-        let result = evaluator.eval(&expr)?;
-        // Convert EvalValue to CellValue
-        let cell_value = match result.value {
-            crate::core::formula::evaluator::EvalValue::Number(n) => CellValue::Number(n),
-            crate::core::formula::evaluator::EvalValue::Text(s) => CellValue::Text(s),
+        let evaluator = Evaluator::with_context(&self.library, &context)
+            .with_functions(&self.functions)
+            .with_random(random)
+            .with_left_operand_units();
+        let result = evaluator.eval(&expr).map_err(SheetError::from);
+        (result, evaluator.resolved_references())
+    }
+
+    /// Get the cells to recalculate after `changed` cells change, in dependency order
+    pub fn calculation_order(&self, changed: &[CellAddr]) -> Vec<CellAddr> {
+        if self.track_dependencies {
+            self.dependencies.calculation_order(changed)
+        } else {
+            self.build_dependencies().calculation_order(changed)
+        }
+    }
+
+    /// Store the result of evaluating a cell's formula (errors are stored in the cell)
+    /// An array puts its first value in the formula's cell and spills the rest into the
+    /// cells to the right and below, unless other cells are in the way (#SPILL!).
+    /// Returns the spilled cells whose values changed, so their dependents can be recalculated
+    pub fn apply_formula_result(
+        &mut self,
+        addr: &CellAddr,
+        result: Result<EvalResult, SheetError>,
+    ) -> Vec<CellAddr> {
+        let previous = self.take_spill(addr);
+        let result = result.and_then(|result| match result.value {
+            EvalValue::Array(values) => self.spill(addr, values),
+            _ => Ok(result),
+        });
+
+        if let Some(cell) = self.cells.get_mut(addr) {
+            match result {
+                Ok(result) => set_result(cell, result),
+                Err(e) => {
+                    cell.set_value(CellValue::Error(e.to_cell_error()));
+                    cell.set_storage_unit(crate::core::units::Unit::dimensionless());
+                    cell.set_uncertainty(None);
+                    cell.set_significant_figures(None);
+                }
+            }
+        }
+
+        let mut changed: Vec<CellAddr> = previous
+            .iter()
+            .filter(|(addr, cell)| self.spilled.get(*addr) != Some(*cell))
+            .map(|(addr, _)| addr.clone())
+            .collect();
+        if let Some(range) = self.spill_range(addr) {
+            changed.extend(
+                range_addresses(&range)
+                    .skip(1)
+                    .filter(|addr| !previous.contains_key(addr)),
+            );
+        }
+        changed
+    }
+
+    /// Remove the array a cell spilled, once the cell no longer holds a formula
+    /// Returns the cells that were cleared
+    pub fn clear_spill(&mut self, addr: &CellAddr) -> Vec<CellAddr> {
+        self.take_spill(addr).into_keys().collect()
+    }
+
+    /// Remove every spilled array; they are laid out again when their formulas are recalculated
+    pub fn clear_spills(&mut self) {
+        self.spills.clear();
+        self.spilled.clear();
+    }
+
+    /// Remove the array a formula spilled, returning the cells it had filled
+    fn take_spill(&mut self, anchor: &CellAddr) -> HashMap<CellAddr, Cell> {
+        match self.spills.remove(anchor) {
+            Some(spill) if !spill.blocked => range_addresses(&spill.range)
+                .skip(1)
+                .filter_map(|addr| self.spilled.remove_entry(&addr))
+                .collect(),
+            _ => HashMap::new(),
+        }
+    }
+
+    /// Lay out an array formula's values from its cell down and to the right
+    /// Returns the value for the formula's own cell, or #SPILL! if other cells are in the way
+    fn spill(&mut self, anchor: &CellAddr, values: Array) -> Result<EvalResult, SheetError> {
+        let rows = values.len();
+        let cols = values.first().map_or(0, Vec::len);
+        if rows == 0 || cols == 0 {
+            return Err(EvalError::InvalidOperation("The array is empty".to_string()).into());
+        }
+
+        let start_col = col_letter_to_num(&anchor.col).map_err(SheetError::InvalidCellRef)?;
+        let range = CellRange {
+            start_col,
+            end_col: start_col + cols - 1,
+            start_row: anchor.row,
+            end_row: anchor.row + rows - 1,
         };
-        Ok((cell_value, result.unit))
+        if range.end_row > MAX_ROWS || range.end_col > MAX_COLUMNS {
+            let message = format!(
+                "The {}x{} array does not fit on the sheet from {}",
+                rows, cols, anchor
+            );
+            return Err(EvalError::ErrorValue(CellError::new(ErrorKind::Spill, message)).into());
+        }
+
+        // Entered cells (other than blank ones) and other arrays are never overwritten
+        let blocker = range_addresses(&range).skip(1).find(|addr| {
+            self.spilled.contains_key(addr)
+                || self
+                    .cells
+                    .get(addr)
+                    .is_some_and(|cell| cell.is_formula() || !cell.is_empty())
+        });
+        if let Some(blocker) = blocker {
+            self.spills.insert(
+                anchor.clone(),
+                Spill {
+                    range,
+                    blocked: true,
+                },
+            );
+            let message = format!(
+                "The {}x{} array cannot spill because {} is not empty",
+                rows, cols, blocker
+            );
+            return Err(EvalError::ErrorValue(CellError::new(ErrorKind::Spill, message)).into());
+        }
+
+        let mut values = values.into_iter().flatten();
+        let first = values.next().expect("array has a first value");
+        for (addr, result) in range_addresses(&range).skip(1).zip(values) {
+            // Blank cells under the array give way to it
+            self.cells.remove(&addr);
+
+            let mut cell = Cell::empty();
+            set_result(&mut cell, result);
+            self.spilled.insert(addr, cell);
+        }
+        self.spills.insert(
+            anchor.clone(),
+            Spill {
+                range,
+                blocked: false,
+            },
+        );
+        Ok(first)
+    }
+
+    /// Add the array formulas that changed cells could affect the spilling of
+    fn with_spill_anchors(&self, changed: &[CellAddr]) -> Vec<CellAddr> {
+        let mut cells = changed.to_vec();
+        for addr in changed {
+            cells.extend(self.spill_anchors_at(addr));
+        }
+        cells
     }
 
     /// Recalculate cells that depend on changed cells
@@ -408,26 +1108,23 @@ impl Sheet {
         changed: &[CellAddr],
         named_refs: Option<&HashMap<String, (f64, crate::core::units::Unit)>>,
     ) -> Result<(), SheetError> {
-        let order = self.dependencies.calculation_order(changed);
-
-        for addr in order {
-            if let Some(cell) = self.cells.get(&addr).cloned() {
-                if let Some(formula) = cell.formula() {
-                    match self.evaluate_formula_with_named_refs(formula, named_refs) {
-                        Ok((value, unit)) => {
-                            let mut updated_cell = cell;
-                            updated_cell.set_value(value);
-                            updated_cell.set_storage_unit(unit);
-                            self.cells.insert(addr, updated_cell);
-                        }
-                        Err(e) => {
-                            let mut updated_cell = cell;
-                            updated_cell.set_value(CellValue::Error(e.to_string()));
-                            self.cells.insert(addr, updated_cell);
-                        }
-                    }
-                }
+        // Cells that arrays spilled into are recalculated in the next pass
+        let mut changed = self.with_spill_anchors(changed);
+
+        for _ in 0..MAX_SPILL_PASSES {
+            if changed.is_empty() {
+                break;
+            }
+
+            let mut spilled = Vec::new();
+            for addr in self.calculation_order(&changed) {
+                let result = self.evaluate_cell(&addr, named_refs, None);
+                spilled.extend(match result {
+                    Some((result, _)) => self.apply_formula_result(&addr, result),
+                    None => self.clear_spill(&addr),
+                });
             }
+            changed = self.with_spill_anchors(&spilled);
         }
 
         Ok(())
@@ -466,7 +1163,8 @@ impl Sheet {
     }
 
     /// Insert a column before the specified column
-    pub fn insert_column_before(&mut self, col: &str) -> Result<(), String> {
+    /// Returns the change, for references to this sheet from elsewhere (see `shift_sheet_references`)
+    pub fn insert_column_before(&mut self, col: &str) -> Result<RowColumnChange, String> {
         let target_col_num = col_letter_to_num(col)?;
 
         // Step 1: Shift all cells at or after target column to the right
@@ -518,21 +1216,25 @@ impl Sheet {
         // Step 3: Update all formulas
         self.update_formulas_for_column_shift(target_col_num, 1)?;
 
+        // Spilled arrays are laid out again when their formulas are recalculated
+        self.clear_spills();
+
         // Step 4: Rebuild dependency graph
         self.rebuild_dependencies()?;
 
-        Ok(())
+        Ok(RowColumnChange::InsertColumn(target_col_num))
     }
 
     /// Insert a column after the specified column
-    pub fn insert_column_after(&mut self, col: &str) -> Result<(), String> {
+    pub fn insert_column_after(&mut self, col: &str) -> Result<RowColumnChange, String> {
         let col_num = col_letter_to_num(col)?;
         let next_col = col_num_to_letter(col_num + 1)?;
         self.insert_column_before(&next_col)
     }
 
     /// Insert a row before the specified row
-    pub fn insert_row_before(&mut self, target_row: usize) -> Result<(), String> {
+    /// Returns the change, for references to this sheet from elsewhere (see `shift_sheet_references`)
+    pub fn insert_row_before(&mut self, target_row: usize) -> Result<RowColumnChange, String> {
         // Step 1: Shift all cells at or after target row down (process in reverse order)
         let mut cells_to_shift: Vec<(CellAddr, Cell)> = self
             .cells
@@ -569,19 +1271,23 @@ impl Sheet {
         // Step 3: Update all formulas
         self.update_formulas_for_row_shift(target_row, 1)?;
 
+        // Spilled arrays are laid out again when their formulas are recalculated
+        self.clear_spills();
+
         // Step 4: Rebuild dependency graph
         self.rebuild_dependencies()?;
 
-        Ok(())
+        Ok(RowColumnChange::InsertRow(target_row))
     }
 
     /// Insert a row after the specified row
-    pub fn insert_row_after(&mut self, row: usize) -> Result<(), String> {
+    pub fn insert_row_after(&mut self, row: usize) -> Result<RowColumnChange, String> {
         self.insert_row_before(row + 1)
     }
 
     /// Delete a column
-    pub fn delete_column(&mut self, col: &str) -> Result<(), String> {
+    /// Returns the change, for references to this sheet from elsewhere (see `shift_sheet_references`)
+    pub fn delete_column(&mut self, col: &str) -> Result<RowColumnChange, String> {
         let target_col_num = col_letter_to_num(col)?;
 
         // Step 1: Remove all cells in the target column
@@ -633,14 +1339,18 @@ impl Sheet {
         // Step 4: Update all formulas (shift left and mark deleted refs as #REF!)
         self.update_formulas_for_column_delete(target_col_num)?;
 
+        // Spilled arrays are laid out again when their formulas are recalculated
+        self.clear_spills();
+
         // Step 5: Rebuild dependency graph
         self.rebuild_dependencies()?;
 
-        Ok(())
+        Ok(RowColumnChange::DeleteColumn(target_col_num))
     }
 
     /// Delete a row
-    pub fn delete_row(&mut self, target_row: usize) -> Result<(), String> {
+    /// Returns the change, for references to this sheet from elsewhere (see `shift_sheet_references`)
+    pub fn delete_row(&mut self, target_row: usize) -> Result<RowColumnChange, String> {
         // Step 1: Remove all cells in the target row
         let cells_to_remove: Vec<CellAddr> = self
             .cells
@@ -685,23 +1395,183 @@ impl Sheet {
         // Step 4: Update all formulas (shift up and mark deleted refs as #REF!)
         self.update_formulas_for_row_delete(target_row)?;
 
+        // Spilled arrays are laid out again when their formulas are recalculated
+        self.clear_spills();
+
         // Step 5: Rebuild dependency graph
         self.rebuild_dependencies()?;
 
-        Ok(())
+        Ok(RowColumnChange::DeleteRow(target_row))
     }
 
-    /// Update formulas when columns are shifted right
-    fn update_formulas_for_column_shift(
+    /// Copy the cells of `src` to `dest`, rewriting relative references by the offset between
+    /// them the way a spreadsheet paste does: B2 copied one row down becomes B3, $B$2 stays put.
+    /// If `dest` holds a whole number of copies of `src` the block is repeated to fill it
+    /// (fill down/right), otherwise it is pasted once at the top-left of `dest`.
+    /// Returns the destination cells that changed
+    pub fn copy_range(
         &mut self,
-        from_col: usize,
-        offset: isize,
-    ) -> Result<(), String> {
-        let cells_with_formulas: Vec<(CellAddr, String)> = self
-            .cells
-            .iter()
-            .filter_map(|(addr, cell)| {
-                cell.formula()
+        src: &CellRange,
+        dest: &CellRange,
+    ) -> Result<Vec<CellAddr>, String> {
+        let height = src.end_row - src.start_row + 1;
+        let width = src.end_col - src.start_col + 1;
+        let repeat_rows = copies_to_fill(dest.end_row - dest.start_row + 1, height);
+        let repeat_cols = copies_to_fill(dest.end_col - dest.start_col + 1, width);
+
+        // Spilled values are pasted as values, unless their formula is copied too
+        let sources: Vec<(CellAddr, Cell)> = self
+            .cells_in_range(src)
+            .into_iter()
+            .filter(|(addr, _)| {
+                self.spill_anchor(addr)
+                    .is_none_or(|anchor| !src.contains(anchor))
+            })
+            .map(|(addr, cell)| (addr, cell.clone()))
+            .collect();
+
+        let mut changed = Vec::new();
+        for i in 0..repeat_rows {
+            for j in 0..repeat_cols {
+                let start_row = dest.start_row.saturating_add(i * height);
+                let start_col = dest.start_col.saturating_add(j * width);
+                let row_offset = start_row as isize - src.start_row as isize;
+                let col_offset = start_col as isize - src.start_col as isize;
+
+                // Pasting replaces the whole block, including cells that are empty in the source
+                let block = CellRange {
+                    start_col,
+                    end_col: start_col.saturating_add(width - 1),
+                    start_row,
+                    end_row: start_row.saturating_add(height - 1),
+                };
+                self.cells.retain(|addr, _| {
+                    let keep = !block.contains(addr);
+                    if !keep {
+                        changed.push(addr.clone());
+                    }
+                    keep
+                });
+
+                for (addr, cell) in &sources {
+                    let col_num = col_letter_to_num(&addr.col)?;
+                    let new_addr = CellAddr::new(
+                        col_num_to_letter((col_num as isize + col_offset) as usize)?,
+                        (addr.row as isize + row_offset) as usize,
+                    );
+
+                    let mut new_cell = cell.clone();
+                    if let Some(formula) = cell.formula().and_then(|formula| {
+                        rewrite_formula_references(formula, |expr| {
+                            Some(copy_expr(expr, row_offset, col_offset))
+                        })
+                    }) {
+                        new_cell.set_formula(formula);
+                    }
+                    self.cells.insert(new_addr.clone(), new_cell);
+                    changed.push(new_addr);
+                }
+            }
+        }
+
+        changed.sort_by_key(|addr| (addr.row, col_letter_to_num(&addr.col).unwrap_or(0)));
+        changed.dedup();
+
+        self.rebuild_dependencies()?;
+
+        Ok(changed)
+    }
+
+    /// Move the cells of `src` so that its top-left cell lands on `dest`
+    /// Formulas on this sheet that refer to the moved cells (relative or absolute) follow them;
+    /// the moved formulas themselves keep pointing at the same cells as before.
+    /// Returns the cells that changed, at both the old and the new location
+    pub fn move_range(
+        &mut self,
+        src: &CellRange,
+        dest: &CellAddr,
+    ) -> Result<Vec<CellAddr>, String> {
+        let row_offset = dest.row as isize - src.start_row as isize;
+        let col_offset = col_letter_to_num(&dest.col)? as isize - src.start_col as isize;
+
+        // Spilled cells stay behind; they follow their formula when it is recalculated
+        let moved: Vec<(CellAddr, Cell)> = self
+            .cells
+            .iter()
+            .filter(|(addr, _)| src.contains(addr))
+            .map(|(addr, cell)| (addr.clone(), cell.clone()))
+            .collect();
+
+        let mut changed: Vec<CellAddr> = moved.iter().map(|(addr, _)| addr.clone()).collect();
+        for (addr, _) in &moved {
+            self.cells.remove(addr);
+        }
+
+        // Whatever was under the destination block is overwritten
+        let block = CellRange {
+            start_col: (src.start_col as isize + col_offset) as usize,
+            end_col: src.end_col.saturating_add_signed(col_offset),
+            start_row: dest.row,
+            end_row: src.end_row.saturating_add_signed(row_offset),
+        };
+        self.cells.retain(|addr, _| {
+            let keep = !block.contains(addr);
+            if !keep {
+                changed.push(addr.clone());
+            }
+            keep
+        });
+
+        for (addr, cell) in moved {
+            let col_num = col_letter_to_num(&addr.col)?;
+            let new_addr = CellAddr::new(
+                col_num_to_letter((col_num as isize + col_offset) as usize)?,
+                (addr.row as isize + row_offset) as usize,
+            );
+            self.cells.insert(new_addr.clone(), cell);
+            changed.push(new_addr);
+        }
+
+        // Point references to the moved cells at their new location
+        let cells_with_formulas: Vec<(CellAddr, String)> = self
+            .cells
+            .iter()
+            .filter_map(|(addr, cell)| {
+                cell.formula()
+                    .map(|formula| (addr.clone(), formula.to_string()))
+            })
+            .collect();
+
+        for (addr, formula) in cells_with_formulas {
+            let updated = rewrite_formula_references(&formula, |expr| {
+                let moved = move_expr(expr, src, row_offset, col_offset);
+                (moved != *expr).then_some(moved)
+            });
+            if let (Some(updated), Some(cell)) = (updated, self.cells.get_mut(&addr)) {
+                cell.set_formula(updated);
+                changed.push(addr);
+            }
+        }
+
+        changed.sort_by_key(|addr| (addr.row, col_letter_to_num(&addr.col).unwrap_or(0)));
+        changed.dedup();
+
+        self.rebuild_dependencies()?;
+
+        Ok(changed)
+    }
+
+    /// Update formulas when columns are shifted right
+    fn update_formulas_for_column_shift(
+        &mut self,
+        from_col: usize,
+        offset: isize,
+    ) -> Result<(), String> {
+        let cells_with_formulas: Vec<(CellAddr, String)> = self
+            .cells
+            .iter()
+            .filter_map(|(addr, cell)| {
+                cell.formula()
                     .map(|formula| (addr.clone(), formula.to_string()))
             })
             .collect();
@@ -799,9 +1669,76 @@ impl Sheet {
         Ok(())
     }
 
+    /// Rewrite references to another sheet after it was renamed (or deleted, if `new_name` is None)
+    /// Returns the cells whose formulas changed
+    pub fn rename_sheet_references(
+        &mut self,
+        old_name: &str,
+        new_name: Option<&str>,
+    ) -> Vec<CellAddr> {
+        self.rewrite_sheet_references(&|sheet, reference| {
+            (sheet == old_name).then(|| match new_name {
+                Some(name) => Expr::sheet_ref(name, reference.clone()),
+                None => Expr::Error(ErrorKind::Ref),
+            })
+        })
+    }
+
+    /// Apply a row or column inserted into or deleted from another sheet to the references
+    /// into it (`Sheet2!A5` becomes `Sheet2!A6` when a row is inserted above it)
+    /// References in `sheet_name` to itself are qualified references too, and are rewritten
+    /// as well. Returns the cells whose formulas changed
+    pub fn shift_sheet_references(
+        &mut self,
+        sheet_name: &str,
+        change: RowColumnChange,
+    ) -> Vec<CellAddr> {
+        self.rewrite_sheet_references(&|sheet, reference| {
+            (sheet == sheet_name).then(|| match change.apply(reference) {
+                error @ Expr::Error(_) => error,
+                reference => Expr::sheet_ref(sheet, reference),
+            })
+        })
+    }
+
+    /// Replace the sheet-qualified references for which `rewrite`, given the sheet name and the
+    /// reference within it, returns a replacement. Only those references change in the formula
+    /// text, and the cells keep their display units and other settings.
+    /// Returns the cells whose formulas changed
+    fn rewrite_sheet_references(
+        &mut self,
+        rewrite: &dyn Fn(&str, &Expr) -> Option<Expr>,
+    ) -> Vec<CellAddr> {
+        let mut changed = Vec::new();
+        for (addr, cell) in self.cells.iter_mut() {
+            let updated = cell.formula().and_then(|formula| {
+                rewrite_formula_references(formula, |expr| match expr {
+                    Expr::SheetRef { sheet, reference } => rewrite(sheet, reference),
+                    _ => None,
+                })
+            });
+            if let Some(updated) = updated {
+                cell.set_formula(updated);
+                changed.push(addr.clone());
+            }
+        }
+
+        changed
+    }
+
     /// Rebuild dependency graph from all formulas
     fn rebuild_dependencies(&mut self) -> Result<(), String> {
-        self.dependencies = DependencyGraph::new();
+        self.dependencies = if self.track_dependencies {
+            self.build_dependencies()
+        } else {
+            DependencyGraph::new()
+        };
+        Ok(())
+    }
+
+    /// Build a dependency graph from all formulas
+    fn build_dependencies(&self) -> DependencyGraph {
+        let mut dependencies = DependencyGraph::new();
 
         let cells_with_formulas: Vec<(CellAddr, String)> = self
             .cells
@@ -816,12 +1753,15 @@ impl Sheet {
             if let Ok(expr) = parse_formula(&formula) {
                 let refs = extract_cell_refs(&expr, None);
                 for dep in refs {
-                    self.dependencies.add_dependency(addr.clone(), dep);
+                    dependencies.add_dependency(addr.clone(), dep);
+                }
+                for range in extract_range_refs(&expr) {
+                    dependencies.add_range_dependency(addr.clone(), range);
                 }
             }
         }
 
-        Ok(())
+        dependencies
     }
 }
 
@@ -831,8 +1771,8 @@ impl Default for Sheet {
     }
 }
 
-/// Extract cell references from an expression
-fn extract_cell_refs(
+/// Extract single-cell references from an expression (ranges are extracted by `extract_range_refs`)
+pub fn extract_cell_refs(
     expr: &Expr,
     named_range_mapping: Option<&HashMap<String, CellAddr>>,
 ) -> HashSet<CellAddr> {
@@ -847,7 +1787,7 @@ fn extract_cell_refs_recursive(
     named_range_mapping: Option<&HashMap<String, CellAddr>>,
 ) {
     match expr {
-        Expr::CellRef { col, row } => {
+        Expr::CellRef { col, row, .. } => {
             refs.insert(CellAddr::new(col.clone(), *row));
         }
         Expr::NamedRef { name } => {
@@ -858,14 +1798,11 @@ fn extract_cell_refs_recursive(
                 }
             }
         }
-        Expr::Range { start, end } => {
-            extract_cell_refs_recursive(start, refs, named_range_mapping);
-            extract_cell_refs_recursive(end, refs, named_range_mapping);
-        }
         Expr::Add(l, r)
         | Expr::Subtract(l, r)
         | Expr::Multiply(l, r)
         | Expr::Divide(l, r)
+        | Expr::Power(l, r)
         | Expr::GreaterThan(l, r)
         | Expr::LessThan(l, r)
         | Expr::GreaterOrEqual(l, r)
@@ -873,11 +1810,13 @@ fn extract_cell_refs_recursive(
         | Expr::Equal(l, r)
         | Expr::NotEqual(l, r)
         | Expr::And(l, r)
-        | Expr::Or(l, r) => {
+        | Expr::Or(l, r)
+        | Expr::Concat(l, r) => {
             extract_cell_refs_recursive(l, refs, named_range_mapping);
             extract_cell_refs_recursive(r, refs, named_range_mapping);
         }
-        Expr::Negate(e) | Expr::Not(e) => {
+        // A spilled range (A1#) depends on the formula it comes from
+        Expr::Negate(e) | Expr::Not(e) | Expr::SpillRef(e) => {
             extract_cell_refs_recursive(e, refs, named_range_mapping);
         }
         Expr::Function { args, .. } => {
@@ -889,1383 +1828,416 @@ fn extract_cell_refs_recursive(
     }
 }
 
-/// Evaluator that can resolve cell references
-struct SheetEvaluator<'a> {
-    sheet: &'a Sheet,
-    library: &'a UnitLibrary,
-    named_refs: Option<&'a HashMap<String, (f64, crate::core::units::Unit)>>,
+/// Extract sheet-qualified cell references (Sheet2!A1) from an expression
+/// Returns (sheet_name, cell_address) pairs; ranges are extracted by `extract_sheet_range_refs`
+pub fn extract_sheet_refs(expr: &Expr) -> HashSet<(String, CellAddr)> {
+    let mut refs = HashSet::new();
+    extract_sheet_refs_recursive(expr, &mut refs);
+    refs
 }
 
-impl<'a> SheetEvaluator<'a> {
-    fn eval(&self, expr: &Expr) -> Result<EvalResult, EvalError> {
-        match expr {
-            Expr::Number(n) => Ok(EvalResult::new(
-                *n,
-                crate::core::units::Unit::dimensionless(),
-            )),
-
-            Expr::NumberWithUnit { value, unit } => {
-                // Parse the unit (supports both simple and compound units)
-                use crate::core::units::parse_unit;
-                let unit_obj = parse_unit(unit, self.library)
-                    .map_err(|_| EvalError::UnknownUnit(unit.clone()))?;
-                Ok(EvalResult::new(*value, unit_obj))
-            }
-
-            Expr::String(s) => Ok(EvalResult::text(s.clone())),
-
-            Expr::CellRef { col, row } => {
-                let addr = CellAddr::new(col.clone(), *row);
-                let cell = self
-                    .sheet
-                    .get(&addr)
-                    .ok_or_else(|| EvalError::CellNotFound(addr.to_string()))?;
-
-                // Handle both number and text cells
-                match cell.value() {
-                    CellValue::Number(n) => Ok(EvalResult::new(*n, cell.storage_unit().clone())),
-                    CellValue::Text(t) => Ok(EvalResult::text(t.clone())),
-                    CellValue::Empty => Err(EvalError::InvalidOperation(format!(
-                        "Cell {} is empty",
-                        addr
-                    ))),
-                    CellValue::Error(e) => Err(EvalError::InvalidOperation(format!(
-                        "Cell {} has error: {}",
-                        addr, e
-                    ))),
-                }
-            }
-
-            Expr::NamedRef { name } => {
-                // Look up named reference in the provided HashMap
-                if let Some(named_refs) = self.named_refs {
-                    if let Some((value, unit)) = named_refs.get(name) {
-                        Ok(EvalResult::new(*value, unit.clone()))
-                    } else {
-                        Err(EvalError::NamedRefNotFound(format!(
-                            "Named reference '{}' not found",
-                            name
-                        )))
-                    }
-                } else {
-                    Err(EvalError::NamedRefNotFound(format!(
-                        "Named reference '{}' requires workbook context",
-                        name
-                    )))
-                }
-            }
-
-            Expr::Add(left, right) => {
-                let left_result = self.eval(left)?;
-                let right_result = self.eval(right)?;
-
-                // If either operand is text, perform string concatenation
-                if left_result.is_text() || right_result.is_text() {
-                    use crate::core::formula::evaluator::EvalValue;
-                    let left_str = match &left_result.value {
-                        EvalValue::Text(s) => s.clone(),
-                        EvalValue::Number(n) => {
-                            if left_result.unit.is_dimensionless() {
-                                n.to_string()
-                            } else {
-                                format!("{} {}", n, left_result.unit)
-                            }
-                        }
-                    };
-                    let right_str = match &right_result.value {
-                        EvalValue::Text(s) => s.clone(),
-                        EvalValue::Number(n) => {
-                            if right_result.unit.is_dimensionless() {
-                                n.to_string()
-                            } else {
-                                format!("{} {}", n, right_result.unit)
-                            }
-                        }
-                    };
-                    return Ok(EvalResult::text(format!("{}{}", left_str, right_str)));
-                }
-
-                // Both are numbers - proceed with numeric addition
-                self.eval_binary_op(left_result, right_result, |a, b| a + b, "add")
-            }
-
-            Expr::Subtract(left, right) => {
-                let left_result = self.eval(left)?;
-                let right_result = self.eval(right)?;
-                self.eval_binary_op(left_result, right_result, |a, b| a - b, "subtract")
-            }
-
-            Expr::Multiply(left, right) => {
-                let left_result = self.eval(left)?;
-                let right_result = self.eval(right)?;
-
-                // Multiplication requires both operands to be numbers
-                let left_value = left_result.as_number().ok_or_else(|| {
-                    EvalError::InvalidOperation("Cannot multiply with text values".to_string())
-                })?;
-                let right_value = right_result.as_number().ok_or_else(|| {
-                    EvalError::InvalidOperation("Cannot multiply with text values".to_string())
-                })?;
-
-                let mut value = left_value * right_value;
-
-                // Check if either operand is a percentage - treat as dimensionless multiplier
-                let left_is_percent = left_result.unit.canonical() == "%";
-                let right_is_percent = right_result.unit.canonical() == "%";
-
-                // If one is percentage, result has the non-percentage unit (percentage gets removed)
-                if left_is_percent && !right_is_percent {
-                    return Ok(EvalResult::new(value, right_result.unit.clone()));
-                }
-                if right_is_percent && !left_is_percent {
-                    return Ok(EvalResult::new(value, left_result.unit.clone()));
-                }
-                // If both are percentages, result is dimensionless
-                if left_is_percent && right_is_percent {
-                    return Ok(EvalResult::new(
-                        value,
-                        crate::core::units::Unit::dimensionless(),
-                    ));
-                }
-
-                // If both dimensionless, result is dimensionless
-                if left_result.unit.is_dimensionless() && right_result.unit.is_dimensionless() {
-                    return Ok(EvalResult::new(
-                        value,
-                        crate::core::units::Unit::dimensionless(),
-                    ));
-                }
-
-                // If one is dimensionless, result has the other's unit
-                if left_result.unit.is_dimensionless() {
-                    return Ok(EvalResult::new(value, right_result.unit.clone()));
-                }
-                if right_result.unit.is_dimensionless() {
-                    return Ok(EvalResult::new(value, left_result.unit.clone()));
-                }
-
-                // Extract unit symbols with powers
-                use crate::core::formula::evaluator::{
-                    build_unit_from_symbols, cancel_and_convert_units, extract_unit_symbols,
-                };
-                let (mut left_num, mut left_den) = extract_unit_symbols(&left_result.unit);
-                let (right_num, right_den) = extract_unit_symbols(&right_result.unit);
-
-                // Multiply: add right's numerator to left's numerator, right's denominator to left's denominator
-                for (symbol, power) in right_num {
-                    *left_num.entry(symbol).or_insert(0) += power;
-                }
-                for (symbol, power) in right_den {
-                    *left_den.entry(symbol).or_insert(0) += power;
-                }
-
-                // Cancel symbols and apply conversions
-                let (final_num, final_den, conversion_factor) =
-                    cancel_and_convert_units(left_num, left_den, self.library);
-
-                // Apply conversion factor to value
-                value *= conversion_factor;
-
-                // Build result unit
-                let result_unit = build_unit_from_symbols(final_num, final_den, self.library);
-
-                Ok(EvalResult::new(value, result_unit))
-            }
-
-            Expr::Divide(left, right) => {
-                let left_result = self.eval(left)?;
-                let right_result = self.eval(right)?;
-
-                // Division requires both operands to be numbers
-                let left_value = left_result.as_number().ok_or_else(|| {
-                    EvalError::InvalidOperation("Cannot divide with text values".to_string())
-                })?;
-                let right_value = right_result.as_number().ok_or_else(|| {
-                    EvalError::InvalidOperation("Cannot divide with text values".to_string())
-                })?;
-
-                if right_value == 0.0 {
-                    return Err(EvalError::DivisionByZero);
-                }
-
-                let mut value = left_value / right_value;
-
-                // Check if either operand is a percentage - treat as dimensionless multiplier
-                let left_is_percent = left_result.unit.canonical() == "%";
-                let right_is_percent = right_result.unit.canonical() == "%";
-
-                // If right is percentage, result has left's unit (percentage gets removed)
-                if right_is_percent && !left_is_percent {
-                    return Ok(EvalResult::new(value, left_result.unit.clone()));
-                }
-                // If left is percentage and right is not, result is percentage/right_unit
-                if left_is_percent && !right_is_percent {
-                    use crate::core::formula::evaluator::{
-                        build_unit_from_symbols, extract_unit_symbols,
-                    };
-                    let (right_num, right_den) = extract_unit_symbols(&right_result.unit);
-                    let result_unit = build_unit_from_symbols(right_den, right_num, self.library);
-                    return Ok(EvalResult::new(value, result_unit));
-                }
-                // If both are percentages, result is dimensionless
-                if left_is_percent && right_is_percent {
-                    return Ok(EvalResult::new(
-                        value,
-                        crate::core::units::Unit::dimensionless(),
-                    ));
-                }
-
-                // If both dimensionless, result is dimensionless
-                if left_result.unit.is_dimensionless() && right_result.unit.is_dimensionless() {
-                    return Ok(EvalResult::new(
-                        value,
-                        crate::core::units::Unit::dimensionless(),
-                    ));
-                }
-
-                // If left is dimensionless, result is 1/right_unit
-                if left_result.unit.is_dimensionless() {
-                    use crate::core::formula::evaluator::{
-                        build_unit_from_symbols, extract_unit_symbols,
-                    };
-                    let (right_num, right_den) = extract_unit_symbols(&right_result.unit);
-                    let result_unit = build_unit_from_symbols(right_den, right_num, self.library);
-                    return Ok(EvalResult::new(value, result_unit));
-                }
-
-                // If right is dimensionless, result has left's unit
-                if right_result.unit.is_dimensionless() {
-                    return Ok(EvalResult::new(value, left_result.unit.clone()));
-                }
-
-                // Extract unit symbols with powers
-                use crate::core::formula::evaluator::{
-                    build_unit_from_symbols, cancel_and_convert_units, extract_unit_symbols,
-                };
-                let (mut left_num, mut left_den) = extract_unit_symbols(&left_result.unit);
-                let (right_num, right_den) = extract_unit_symbols(&right_result.unit);
-
-                // Divide: right's numerator goes to left's denominator, right's denominator goes to left's numerator
-                for (symbol, power) in right_num {
-                    *left_den.entry(symbol).or_insert(0) += power;
-                }
-                for (symbol, power) in right_den {
-                    *left_num.entry(symbol).or_insert(0) += power;
-                }
-
-                // Cancel symbols and apply conversions
-                let (final_num, final_den, conversion_factor) =
-                    cancel_and_convert_units(left_num, left_den, self.library);
-
-                // Apply conversion factor to value
-                value *= conversion_factor;
-
-                // Build result unit
-                let result_unit = build_unit_from_symbols(final_num, final_den, self.library);
-
-                Ok(EvalResult::new(value, result_unit))
-            }
-
-            Expr::Negate(expr) => {
-                let result = self.eval(expr)?;
-                let value = result.as_number().ok_or_else(|| {
-                    EvalError::InvalidOperation("Cannot negate a text value".to_string())
-                })?;
-                Ok(EvalResult::new(-value, result.unit))
-            }
-
-            Expr::Boolean(b) => Ok(EvalResult::new(
-                if *b { 1.0 } else { 0.0 },
-                crate::core::units::Unit::dimensionless(),
-            )),
-
-            Expr::GreaterThan(left, right) => self.eval_comparison(left, right, |a, b| a > b),
-            Expr::LessThan(left, right) => self.eval_comparison(left, right, |a, b| a < b),
-            Expr::GreaterOrEqual(left, right) => self.eval_comparison(left, right, |a, b| a >= b),
-            Expr::LessOrEqual(left, right) => self.eval_comparison(left, right, |a, b| a <= b),
-            Expr::Equal(left, right) => self.eval_comparison(left, right, |a, b| a == b),
-            Expr::NotEqual(left, right) => self.eval_comparison(left, right, |a, b| a != b),
-
-            Expr::And(left, right) => {
-                let left_result = self.eval(left)?;
-                let right_result = self.eval(right)?;
-                let left_value = left_result.as_number().ok_or_else(|| {
-                    EvalError::InvalidOperation("Cannot use AND with text values".to_string())
-                })?;
-                let right_value = right_result.as_number().ok_or_else(|| {
-                    EvalError::InvalidOperation("Cannot use AND with text values".to_string())
-                })?;
-                let result = if left_value != 0.0 && right_value != 0.0 {
-                    1.0
-                } else {
-                    0.0
-                };
-                Ok(EvalResult::new(
-                    result,
-                    crate::core::units::Unit::dimensionless(),
-                ))
-            }
-
-            Expr::Or(left, right) => {
-                let left_result = self.eval(left)?;
-                let right_result = self.eval(right)?;
-                let left_value = left_result.as_number().ok_or_else(|| {
-                    EvalError::InvalidOperation("Cannot use OR with text values".to_string())
-                })?;
-                let right_value = right_result.as_number().ok_or_else(|| {
-                    EvalError::InvalidOperation("Cannot use OR with text values".to_string())
-                })?;
-                let result = if left_value != 0.0 || right_value != 0.0 {
-                    1.0
-                } else {
-                    0.0
-                };
-                Ok(EvalResult::new(
-                    result,
-                    crate::core::units::Unit::dimensionless(),
-                ))
+fn extract_sheet_refs_recursive(expr: &Expr, refs: &mut HashSet<(String, CellAddr)>) {
+    match expr {
+        Expr::SheetRef { sheet, reference } => {
+            for addr in extract_cell_refs(reference, None) {
+                refs.insert((sheet.clone(), addr));
             }
-
-            Expr::Not(expr) => {
-                let result = self.eval(expr)?;
-                let value = result.as_number().ok_or_else(|| {
-                    EvalError::InvalidOperation("Cannot use NOT with text values".to_string())
-                })?;
-                let not_result = if value == 0.0 { 1.0 } else { 0.0 };
-                Ok(EvalResult::new(
-                    not_result,
-                    crate::core::units::Unit::dimensionless(),
-                ))
+        }
+        Expr::Add(l, r)
+        | Expr::Subtract(l, r)
+        | Expr::Multiply(l, r)
+        | Expr::Divide(l, r)
+        | Expr::Power(l, r)
+        | Expr::GreaterThan(l, r)
+        | Expr::LessThan(l, r)
+        | Expr::GreaterOrEqual(l, r)
+        | Expr::LessOrEqual(l, r)
+        | Expr::Equal(l, r)
+        | Expr::NotEqual(l, r)
+        | Expr::And(l, r)
+        | Expr::Or(l, r)
+        | Expr::Concat(l, r) => {
+            extract_sheet_refs_recursive(l, refs);
+            extract_sheet_refs_recursive(r, refs);
+        }
+        Expr::Negate(e) | Expr::Not(e) => {
+            extract_sheet_refs_recursive(e, refs);
+        }
+        Expr::Function { args, .. } => {
+            for arg in args {
+                extract_sheet_refs_recursive(arg, refs);
             }
-
-            Expr::Range { .. } => Err(EvalError::InvalidOperation(
-                "Ranges can only be used in functions".to_string(),
-            )),
-
-            Expr::Function { name, args } => match name.to_uppercase().as_str() {
-                "SUM" => self.eval_sum(args),
-                "AVERAGE" => self.eval_average(args),
-                "CONVERT" => self.eval_convert(args),
-                "PERCENT" => self.eval_percent(args),
-                "COUNT" => self.eval_count(args),
-                "MIN" => self.eval_min(args),
-                "MAX" => self.eval_max(args),
-                "ABS" => self.eval_abs(args),
-                "ROUND" => self.eval_round(args),
-                "FLOOR" => self.eval_floor(args),
-                "CEIL" | "CEILING" => self.eval_ceil(args),
-                "TRUNC" => self.eval_trunc(args),
-                "MOD" => self.eval_mod(args),
-                "SIGN" => self.eval_sign(args),
-                "SQRT" => self.eval_sqrt(args),
-                "POWER" => self.eval_power(args),
-                "MEDIAN" => self.eval_median(args),
-                "STDEV" => self.eval_stdev(args),
-                "VAR" => self.eval_var(args),
-                "IF" => self.eval_if(args),
-                "AND" => self.eval_and(args),
-                "OR" => self.eval_or(args),
-                "NOT" => self.eval_not_fn(args),
-                "GT" => self.eval_gt(args),
-                "LT" => self.eval_lt(args),
-                "GTE" => self.eval_gte(args),
-                "LTE" => self.eval_lte(args),
-                "EQ" => self.eval_eq(args),
-                "NE" => self.eval_ne(args),
-                _ => Err(EvalError::FunctionNotImplemented(name.clone())),
-            },
         }
+        _ => {}
     }
+}
 
-    fn eval_binary_op<F>(
-        &self,
-        left: EvalResult,
-        right: EvalResult,
-        op: F,
-        op_name: &str,
-    ) -> Result<EvalResult, EvalError>
-    where
-        F: Fn(f64, f64) -> f64,
-    {
-        // Binary operations require both operands to be numbers
-        let left_value = left.as_number().ok_or_else(|| {
-            EvalError::InvalidOperation(format!("Cannot {} with text values", op_name))
-        })?;
-        let right_value = right.as_number().ok_or_else(|| {
-            EvalError::InvalidOperation(format!("Cannot {} with text values", op_name))
-        })?;
+/// Extract ranges (A1:C10, B:B, 3:3) on this sheet from an expression
+pub fn extract_range_refs(expr: &Expr) -> HashSet<CellRange> {
+    let mut ranges = HashSet::new();
+    extract_range_refs_recursive(expr, &mut ranges);
+    ranges
+}
 
-        // Both dimensionless - simple operation
-        if left.unit.is_dimensionless() && right.unit.is_dimensionless() {
-            return Ok(EvalResult::new(
-                op(left_value, right_value),
-                crate::core::units::Unit::dimensionless(),
-            ));
+fn extract_range_refs_recursive(expr: &Expr, ranges: &mut HashSet<CellRange>) {
+    match expr {
+        Expr::Range { .. } | Expr::ColumnRange { .. } | Expr::RowRange { .. } => {
+            if let Some(range) = CellRange::from_expr(expr) {
+                ranges.insert(range);
+            }
         }
-
-        // Check if units are compatible
-        if !left.unit.is_compatible(&right.unit) {
-            return Err(EvalError::IncompatibleUnits {
-                operation: op_name.to_string(),
-                left: left.unit.to_string(),
-                right: right.unit.to_string(),
-            });
+        Expr::Add(l, r)
+        | Expr::Subtract(l, r)
+        | Expr::Multiply(l, r)
+        | Expr::Divide(l, r)
+        | Expr::Power(l, r)
+        | Expr::GreaterThan(l, r)
+        | Expr::LessThan(l, r)
+        | Expr::GreaterOrEqual(l, r)
+        | Expr::LessOrEqual(l, r)
+        | Expr::Equal(l, r)
+        | Expr::NotEqual(l, r)
+        | Expr::And(l, r)
+        | Expr::Or(l, r)
+        | Expr::Concat(l, r) => {
+            extract_range_refs_recursive(l, ranges);
+            extract_range_refs_recursive(r, ranges);
         }
-
-        // If units are exactly the same, just operate
-        if left.unit.is_equal(&right.unit) {
-            return Ok(EvalResult::new(
-                op(left_value, right_value),
-                left.unit.clone(),
-            ));
+        Expr::Negate(e) | Expr::Not(e) => {
+            extract_range_refs_recursive(e, ranges);
         }
-
-        // Units are compatible but different - convert right to left's unit
-        let right_value_converted = self
-            .library
-            .convert(right_value, right.unit.canonical(), left.unit.canonical())
-            .ok_or_else(|| EvalError::IncompatibleUnits {
-                operation: op_name.to_string(),
-                left: left.unit.to_string(),
-                right: right.unit.to_string(),
-            })?;
-
-        Ok(EvalResult::new(
-            op(left_value, right_value_converted),
-            left.unit.clone(),
-        ))
-    }
-
-    /// Evaluate SUM function
-    fn eval_sum(&self, args: &[Expr]) -> Result<EvalResult, EvalError> {
-        if args.is_empty() {
-            return Ok(EvalResult::new(
-                0.0,
-                crate::core::units::Unit::dimensionless(),
-            ));
+        Expr::Function { args, .. } => {
+            for arg in args {
+                extract_range_refs_recursive(arg, ranges);
+            }
         }
+        _ => {}
+    }
+}
 
-        // Collect all values from arguments (including ranges)
-        let values = self.collect_values(args)?;
+/// Extract sheet-qualified ranges (Sheet2!A1:C10, 'Cost Model'!B:B) from an expression
+/// Returns (sheet_name, range) pairs
+pub fn extract_sheet_range_refs(expr: &Expr) -> HashSet<(String, CellRange)> {
+    let mut ranges = HashSet::new();
+    extract_sheet_range_refs_recursive(expr, &mut ranges);
+    ranges
+}
 
-        if values.is_empty() {
-            return Ok(EvalResult::new(
-                0.0,
-                crate::core::units::Unit::dimensionless(),
-            ));
+fn extract_sheet_range_refs_recursive(expr: &Expr, ranges: &mut HashSet<(String, CellRange)>) {
+    match expr {
+        Expr::SheetRef { sheet, reference } => {
+            if let Some(range) = CellRange::from_expr(reference) {
+                ranges.insert((sheet.clone(), range));
+            }
         }
-
-        // All values should have compatible units
-        let first = &values[0];
-        let first_value = first.as_number().ok_or_else(|| {
-            EvalError::InvalidOperation("SUM can only be used with numbers".to_string())
-        })?;
-        let mut sum = first_value;
-        let result_unit = first.unit.clone();
-
-        for val in &values[1..] {
-            if !val.unit.is_compatible(&result_unit) {
-                return Err(EvalError::IncompatibleUnits {
-                    operation: "SUM".to_string(),
-                    left: result_unit.to_string(),
-                    right: val.unit.to_string(),
-                });
+        Expr::Add(l, r)
+        | Expr::Subtract(l, r)
+        | Expr::Multiply(l, r)
+        | Expr::Divide(l, r)
+        | Expr::Power(l, r)
+        | Expr::GreaterThan(l, r)
+        | Expr::LessThan(l, r)
+        | Expr::GreaterOrEqual(l, r)
+        | Expr::LessOrEqual(l, r)
+        | Expr::Equal(l, r)
+        | Expr::NotEqual(l, r)
+        | Expr::And(l, r)
+        | Expr::Or(l, r)
+        | Expr::Concat(l, r) => {
+            extract_sheet_range_refs_recursive(l, ranges);
+            extract_sheet_range_refs_recursive(r, ranges);
+        }
+        Expr::Negate(e) | Expr::Not(e) => {
+            extract_sheet_range_refs_recursive(e, ranges);
+        }
+        Expr::Function { args, .. } => {
+            for arg in args {
+                extract_sheet_range_refs_recursive(arg, ranges);
             }
-
-            // Get numeric value (SUM only works with numbers)
-            let num_value = val.as_number().ok_or_else(|| {
-                EvalError::InvalidOperation("SUM can only be used with numbers".to_string())
-            })?;
-
-            // Convert to result unit if needed
-            let converted_value = if val.unit.is_equal(&result_unit) {
-                num_value
-            } else {
-                self.library
-                    .convert(num_value, val.unit.canonical(), result_unit.canonical())
-                    .ok_or_else(|| EvalError::IncompatibleUnits {
-                        operation: "SUM".to_string(),
-                        left: result_unit.to_string(),
-                        right: val.unit.to_string(),
-                    })?
-            };
-
-            sum += converted_value;
         }
-
-        Ok(EvalResult::new(sum, result_unit))
+        _ => {}
     }
+}
 
-    /// Evaluate AVERAGE function
-    fn eval_average(&self, args: &[Expr]) -> Result<EvalResult, EvalError> {
-        if args.is_empty() {
-            return Err(EvalError::InvalidOperation(
-                "AVERAGE requires at least one argument".to_string(),
-            ));
-        }
-
-        // Collect all values from arguments (including ranges)
-        let values = self.collect_values(args)?;
+/// Extract named references (revenue, tax_rate) from an expression
+pub fn extract_named_refs(expr: &Expr) -> HashSet<String> {
+    let mut names = HashSet::new();
+    extract_named_refs_recursive(expr, &mut names);
+    names
+}
 
-        if values.is_empty() {
-            return Err(EvalError::InvalidOperation(
-                "AVERAGE requires at least one value".to_string(),
-            ));
+fn extract_named_refs_recursive(expr: &Expr, names: &mut HashSet<String>) {
+    match expr {
+        Expr::NamedRef { name } => {
+            names.insert(name.clone());
         }
-
-        // Calculate sum
-        let sum_result = self.eval_sum(args)?;
-
-        // Divide by count
-        let count = values.len() as f64;
-        Ok(EvalResult::new(
-            sum_result.numeric_value() / count,
-            sum_result.unit,
-        ))
-    }
-
-    /// Evaluate CONVERT function
-    /// Syntax: CONVERT(value, target_unit)
-    /// Example: CONVERT(A1, 1km) or CONVERT(100m, 1ft)
-    fn eval_convert(&self, args: &[Expr]) -> Result<EvalResult, EvalError> {
-        if args.len() != 2 {
-            return Err(EvalError::InvalidOperation(
-                "CONVERT requires exactly 2 arguments: CONVERT(value, target_unit)".to_string(),
-            ));
-        }
-
-        // Evaluate the value to convert
-        let value_result = self.eval(&args[0])?;
-
-        // Extract target unit from second argument
-        // The target unit can be specified as "1 km" or just a reference with unit
-        let target_unit = match &args[1] {
-            Expr::NumberWithUnit { unit, .. } => {
-                // Parse the unit string
-                use crate::core::units::parse_unit;
-                parse_unit(unit, self.library).map_err(|_| EvalError::UnknownUnit(unit.clone()))?
-            }
-            Expr::CellRef { col, row } => {
-                // Get unit from referenced cell
-                let addr = CellAddr::new(col.clone(), *row);
-                let cell = self
-                    .sheet
-                    .get(&addr)
-                    .ok_or_else(|| EvalError::CellNotFound(addr.to_string()))?;
-
-                // Check if cell contains text - if so, parse it as a unit string
-                match cell.value() {
-                    CellValue::Text(text) => {
-                        use crate::core::units::parse_unit;
-                        parse_unit(text.as_str(), self.library)
-                            .map_err(|_| EvalError::UnknownUnit(text.clone()))?
-                    }
-                    _ => {
-                        // Otherwise, use the cell's storage unit
-                        cell.storage_unit().clone()
-                    }
-                }
-            }
-            _ => {
-                return Err(EvalError::InvalidOperation(
-                    "CONVERT target unit must be specified as '1 unit' (e.g., 1km) or a cell reference".to_string()
-                ));
-            }
-        };
-
-        // Check if units are compatible
-        if !value_result.unit.is_compatible(&target_unit) {
-            return Err(EvalError::IncompatibleUnits {
-                operation: "CONVERT".to_string(),
-                left: value_result.unit.to_string(),
-                right: target_unit.to_string(),
-            });
-        }
-
-        // If units are already the same, no conversion needed
-        if value_result.unit.is_equal(&target_unit) {
-            return Ok(EvalResult::new(value_result.numeric_value(), target_unit));
+        Expr::Add(l, r)
+        | Expr::Subtract(l, r)
+        | Expr::Multiply(l, r)
+        | Expr::Divide(l, r)
+        | Expr::Power(l, r)
+        | Expr::GreaterThan(l, r)
+        | Expr::LessThan(l, r)
+        | Expr::GreaterOrEqual(l, r)
+        | Expr::LessOrEqual(l, r)
+        | Expr::Equal(l, r)
+        | Expr::NotEqual(l, r)
+        | Expr::And(l, r)
+        | Expr::Or(l, r)
+        | Expr::Concat(l, r) => {
+            extract_named_refs_recursive(l, names);
+            extract_named_refs_recursive(r, names);
         }
-
-        // Perform conversion
-        // Try compound unit conversion first (handles units like $/quarter -> $/year)
-        let converted_value = if let Some(result) = convert_compound_unit_for_formula(
-            value_result.numeric_value(),
-            value_result.unit.canonical(),
-            target_unit.canonical(),
-            self.library,
-        ) {
-            result
-        } else {
-            // Fall back to simple unit conversion
-            self.library
-                .convert(
-                    value_result.numeric_value(),
-                    value_result.unit.canonical(),
-                    target_unit.canonical(),
-                )
-                .ok_or_else(|| EvalError::IncompatibleUnits {
-                    operation: "CONVERT".to_string(),
-                    left: value_result.unit.to_string(),
-                    right: target_unit.to_string(),
-                })?
-        };
-
-        Ok(EvalResult::new(converted_value, target_unit))
-    }
-
-    /// Evaluate PERCENT function
-    /// Syntax: PERCENT(value)
-    /// Example: PERCENT(0.15) returns 0.15 with "%" unit → displays as "15%"
-    fn eval_percent(&self, args: &[Expr]) -> Result<EvalResult, EvalError> {
-        if args.len() != 1 {
-            return Err(EvalError::InvalidOperation(
-                "PERCENT requires exactly 1 argument: PERCENT(value)".to_string(),
-            ));
+        Expr::Negate(e) | Expr::Not(e) => {
+            extract_named_refs_recursive(e, names);
         }
-
-        // Evaluate the value
-        let value_result = self.eval(&args[0])?;
-
-        // Create a percentage unit
-        let percent_unit = crate::core::units::Unit::simple(
-            "%",
-            crate::core::units::BaseDimension::Custom("%".to_string()),
-        );
-
-        // Return the same value with "%" unit
-        Ok(EvalResult::new(value_result.numeric_value(), percent_unit))
-    }
-
-    /// Collect values from arguments (expanding ranges)
-    fn collect_values(&self, args: &[Expr]) -> Result<Vec<EvalResult>, EvalError> {
-        let mut values = Vec::new();
-
-        for arg in args {
-            match arg {
-                Expr::Range { start, end } => {
-                    // Extract range bounds
-                    let (start_col, start_row) = match start.as_ref() {
-                        Expr::CellRef { col, row } => (col.clone(), *row),
-                        _ => {
-                            return Err(EvalError::InvalidOperation(
-                                "Range must use cell references".to_string(),
-                            ))
-                        }
-                    };
-
-                    let (end_col, end_row) = match end.as_ref() {
-                        Expr::CellRef { col, row } => (col.clone(), *row),
-                        _ => {
-                            return Err(EvalError::InvalidOperation(
-                                "Range must use cell references".to_string(),
-                            ))
-                        }
-                    };
-
-                    // For simplicity, only support single-column ranges for now
-                    if start_col != end_col {
-                        return Err(EvalError::InvalidOperation(
-                            "Only single-column ranges supported in MLP".to_string(),
-                        ));
-                    }
-
-                    // Iterate through rows
-                    for row in start_row..=end_row {
-                        let addr = CellAddr::new(&start_col, row);
-                        if let Some(cell) = self.sheet.get(&addr) {
-                            if let Some(value) = cell.as_number() {
-                                values.push(EvalResult::new(value, cell.storage_unit().clone()));
-                            }
-                        }
-                    }
-                }
-                _ => {
-                    // Evaluate single expression
-                    values.push(self.eval(arg)?);
-                }
+        Expr::Function { args, .. } => {
+            for arg in args {
+                extract_named_refs_recursive(arg, names);
             }
         }
-
-        Ok(values)
-    }
-
-    /// Evaluate COUNT function
-    /// Counts non-empty cells in range or arguments
-    fn eval_count(&self, args: &[Expr]) -> Result<EvalResult, EvalError> {
-        let values = self.collect_values(args)?;
-        let count = values.len() as f64;
-        Ok(EvalResult::new(
-            count,
-            crate::core::units::Unit::dimensionless(),
-        ))
+        _ => {}
     }
+}
 
-    /// Evaluate MIN function
-    /// Find minimum value in range with unit compatibility checking
-    fn eval_min(&self, args: &[Expr]) -> Result<EvalResult, EvalError> {
-        let values = self.collect_values(args)?;
-
-        if values.is_empty() {
-            return Err(EvalError::InvalidOperation(
-                "MIN requires at least one value".to_string(),
-            ));
-        }
-
-        // All values must have compatible units
-        let first_unit = &values[0].unit;
-        let mut min_value = values[0].numeric_value();
-
-        for val in &values[1..] {
-            if !val.unit.is_compatible(first_unit) {
-                return Err(EvalError::IncompatibleUnits {
-                    operation: "MIN".to_string(),
-                    left: first_unit.to_string(),
-                    right: val.unit.to_string(),
-                });
-            }
-
-            // Convert to first unit before comparing
-            let converted_value = if val.unit.is_equal(first_unit) {
-                val.numeric_value()
-            } else {
-                self.library
-                    .convert(
-                        val.numeric_value(),
-                        val.unit.canonical(),
-                        first_unit.canonical(),
-                    )
-                    .ok_or_else(|| EvalError::IncompatibleUnits {
-                        operation: "MIN".to_string(),
-                        left: first_unit.to_string(),
-                        right: val.unit.to_string(),
-                    })?
-            };
+/// Extract the names of the functions an expression calls (SUM, monthly_cost), as written
+pub fn extract_function_names(expr: &Expr) -> HashSet<String> {
+    let mut names = HashSet::new();
+    extract_function_names_recursive(expr, &mut names);
+    names
+}
 
-            if converted_value < min_value {
-                min_value = converted_value;
-            }
+fn extract_function_names_recursive(expr: &Expr, names: &mut HashSet<String>) {
+    match expr {
+        Expr::Add(l, r)
+        | Expr::Subtract(l, r)
+        | Expr::Multiply(l, r)
+        | Expr::Divide(l, r)
+        | Expr::Power(l, r)
+        | Expr::GreaterThan(l, r)
+        | Expr::LessThan(l, r)
+        | Expr::GreaterOrEqual(l, r)
+        | Expr::LessOrEqual(l, r)
+        | Expr::Equal(l, r)
+        | Expr::NotEqual(l, r)
+        | Expr::And(l, r)
+        | Expr::Or(l, r)
+        | Expr::Concat(l, r) => {
+            extract_function_names_recursive(l, names);
+            extract_function_names_recursive(r, names);
         }
-
-        Ok(EvalResult::new(min_value, first_unit.clone()))
-    }
-
-    /// Evaluate MAX function
-    /// Find maximum value in range with unit compatibility checking
-    fn eval_max(&self, args: &[Expr]) -> Result<EvalResult, EvalError> {
-        let values = self.collect_values(args)?;
-
-        if values.is_empty() {
-            return Err(EvalError::InvalidOperation(
-                "MAX requires at least one value".to_string(),
-            ));
+        Expr::Negate(e) | Expr::Not(e) => {
+            extract_function_names_recursive(e, names);
         }
-
-        // All values must have compatible units
-        let first_unit = &values[0].unit;
-        let mut max_value = values[0].numeric_value();
-
-        for val in &values[1..] {
-            if !val.unit.is_compatible(first_unit) {
-                return Err(EvalError::IncompatibleUnits {
-                    operation: "MAX".to_string(),
-                    left: first_unit.to_string(),
-                    right: val.unit.to_string(),
-                });
-            }
-
-            // Convert to first unit before comparing
-            let converted_value = if val.unit.is_equal(first_unit) {
-                val.numeric_value()
-            } else {
-                self.library
-                    .convert(
-                        val.numeric_value(),
-                        val.unit.canonical(),
-                        first_unit.canonical(),
-                    )
-                    .ok_or_else(|| EvalError::IncompatibleUnits {
-                        operation: "MAX".to_string(),
-                        left: first_unit.to_string(),
-                        right: val.unit.to_string(),
-                    })?
-            };
-
-            if converted_value > max_value {
-                max_value = converted_value;
+        Expr::Function { name, args } => {
+            names.insert(name.clone());
+            for arg in args {
+                extract_function_names_recursive(arg, names);
             }
         }
-
-        Ok(EvalResult::new(max_value, first_unit.clone()))
+        _ => {}
     }
+}
 
-    /// Evaluate ABS function
-    /// Returns absolute value, preserving unit
-    fn eval_abs(&self, args: &[Expr]) -> Result<EvalResult, EvalError> {
-        if args.len() != 1 {
-            return Err(EvalError::InvalidOperation(
-                "ABS requires exactly 1 argument".to_string(),
-            ));
-        }
-
-        let result = self.eval(&args[0])?;
-        Ok(EvalResult::new(result.numeric_value().abs(), result.unit))
-    }
+/// Extract the units written in an expression's literals (the "USD" of 1000 USD)
+pub fn extract_unit_literals(expr: &Expr) -> HashSet<String> {
+    let mut units = HashSet::new();
+    extract_unit_literals_recursive(expr, &mut units);
+    units
+}
 
-    /// Evaluate ROUND function
-    /// Rounds to specified decimal places (default 0), preserving unit
-    fn eval_round(&self, args: &[Expr]) -> Result<EvalResult, EvalError> {
-        if args.is_empty() || args.len() > 2 {
-            return Err(EvalError::InvalidOperation(
-                "ROUND requires 1 or 2 arguments: ROUND(value, [decimals])".to_string(),
-            ));
+fn extract_unit_literals_recursive(expr: &Expr, units: &mut HashSet<String>) {
+    match expr {
+        Expr::Add(l, r)
+        | Expr::Subtract(l, r)
+        | Expr::Multiply(l, r)
+        | Expr::Divide(l, r)
+        | Expr::Power(l, r)
+        | Expr::GreaterThan(l, r)
+        | Expr::LessThan(l, r)
+        | Expr::GreaterOrEqual(l, r)
+        | Expr::LessOrEqual(l, r)
+        | Expr::Equal(l, r)
+        | Expr::NotEqual(l, r)
+        | Expr::And(l, r)
+        | Expr::Or(l, r)
+        | Expr::Concat(l, r) => {
+            extract_unit_literals_recursive(l, units);
+            extract_unit_literals_recursive(r, units);
         }
-
-        let result = self.eval(&args[0])?;
-
-        let decimals = if args.len() == 2 {
-            let decimals_result = self.eval(&args[1])?;
-            decimals_result.numeric_value().round() as i32
-        } else {
-            0
-        };
-
-        let multiplier = 10f64.powi(decimals);
-        let rounded = (result.numeric_value() * multiplier).round() / multiplier;
-
-        Ok(EvalResult::new(rounded, result.unit))
-    }
-
-    /// Evaluate FLOOR function
-    /// Rounds down to integer, preserving unit
-    fn eval_floor(&self, args: &[Expr]) -> Result<EvalResult, EvalError> {
-        if args.len() != 1 {
-            return Err(EvalError::InvalidOperation(
-                "FLOOR requires exactly 1 argument".to_string(),
-            ));
+        Expr::Negate(e) | Expr::Not(e) => {
+            extract_unit_literals_recursive(e, units);
         }
-
-        let result = self.eval(&args[0])?;
-        Ok(EvalResult::new(result.numeric_value().floor(), result.unit))
-    }
-
-    /// Evaluate CEIL function
-    /// Rounds up to integer, preserving unit
-    fn eval_ceil(&self, args: &[Expr]) -> Result<EvalResult, EvalError> {
-        if args.len() != 1 {
-            return Err(EvalError::InvalidOperation(
-                "CEIL requires exactly 1 argument".to_string(),
-            ));
+        Expr::Function { args, .. } => {
+            for arg in args {
+                extract_unit_literals_recursive(arg, units);
+            }
         }
-
-        let result = self.eval(&args[0])?;
-        Ok(EvalResult::new(result.numeric_value().ceil(), result.unit))
-    }
-
-    /// Evaluate TRUNC function
-    /// Truncates to integer (removes decimals), preserving unit
-    fn eval_trunc(&self, args: &[Expr]) -> Result<EvalResult, EvalError> {
-        if args.len() != 1 {
-            return Err(EvalError::InvalidOperation(
-                "TRUNC requires exactly 1 argument".to_string(),
-            ));
+        Expr::NumberWithUnit { unit, .. } => {
+            units.insert(unit.clone());
         }
-
-        let result = self.eval(&args[0])?;
-        Ok(EvalResult::new(result.numeric_value().trunc(), result.unit))
+        _ => {}
     }
+}
 
-    /// Evaluate MOD function
-    /// Modulo operation, both args must have compatible units
-    fn eval_mod(&self, args: &[Expr]) -> Result<EvalResult, EvalError> {
-        if args.len() != 2 {
-            return Err(EvalError::InvalidOperation(
-                "MOD requires exactly 2 arguments: MOD(dividend, divisor)".to_string(),
-            ));
-        }
-
-        let dividend = self.eval(&args[0])?;
-        let divisor = self.eval(&args[1])?;
-
-        // Check unit compatibility
-        if !dividend.unit.is_compatible(&divisor.unit) {
-            return Err(EvalError::IncompatibleUnits {
-                operation: "MOD".to_string(),
-                left: dividend.unit.to_string(),
-                right: divisor.unit.to_string(),
-            });
-        }
-
-        // Convert divisor to dividend's unit if needed
-        let divisor_value = if divisor.unit.is_equal(&dividend.unit) {
-            divisor.numeric_value()
-        } else {
-            self.library
-                .convert(
-                    divisor.numeric_value(),
-                    divisor.unit.canonical(),
-                    dividend.unit.canonical(),
-                )
-                .ok_or_else(|| EvalError::IncompatibleUnits {
-                    operation: "MOD".to_string(),
-                    left: dividend.unit.to_string(),
-                    right: divisor.unit.to_string(),
-                })?
-        };
-
-        let result_value = dividend.numeric_value() % divisor_value;
-        Ok(EvalResult::new(result_value, dividend.unit))
-    }
+/// Resolves the references in a formula against a sheet and the rest of its workbook
+struct SheetContext<'a> {
+    sheet: &'a Sheet,
+    named_refs: Option<&'a HashMap<String, (f64, crate::core::units::Unit)>>,
+    /// All sheets in the workbook, for resolving sheet-qualified references
+    sheets: Option<&'a [Sheet]>,
+}
 
-    /// Evaluate SIGN function
-    /// Returns -1, 0, or 1 (dimensionless)
-    fn eval_sign(&self, args: &[Expr]) -> Result<EvalResult, EvalError> {
-        if args.len() != 1 {
-            return Err(EvalError::InvalidOperation(
-                "SIGN requires exactly 1 argument".to_string(),
-            ));
+impl SheetContext<'_> {
+    /// Find the sheet a reference points at (the current sheet if unqualified)
+    fn sheet(&self, name: Option<&str>) -> Result<&Sheet, EvalError> {
+        match name {
+            None => Ok(self.sheet),
+            Some(name) => self
+                .sheets
+                .and_then(|sheets| sheets.iter().find(|s| s.name() == name))
+                .ok_or_else(|| EvalError::SheetNotFound(name.to_string())),
         }
-
-        let result = self.eval(&args[0])?;
-        let sign = if result.numeric_value() == 0.0 {
-            0.0
-        } else if result.numeric_value() > 0.0 {
-            1.0
-        } else {
-            -1.0
-        };
-        Ok(EvalResult::new(
-            sign,
-            crate::core::units::Unit::dimensionless(),
-        ))
     }
+}
 
-    /// Generic comparison evaluation with unit conversion
-    fn eval_comparison<F>(
-        &self,
-        left: &Expr,
-        right: &Expr,
-        compare: F,
-    ) -> Result<EvalResult, EvalError>
-    where
-        F: Fn(f64, f64) -> bool,
-    {
-        let left_val = self.eval(left)?;
-        let right_val = self.eval(right)?;
-
-        // Check if units are compatible
-        if !left_val.unit.is_compatible(&right_val.unit) {
-            return Err(EvalError::IncompatibleUnits {
-                operation: "comparison".to_string(),
-                left: left_val.unit.to_string(),
-                right: right_val.unit.to_string(),
-            });
-        }
-
-        // Convert right to left's unit if needed
-        let right_converted = if left_val.unit.is_equal(&right_val.unit) {
-            right_val.numeric_value()
-        } else {
-            self.library
-                .convert(
-                    right_val.numeric_value(),
-                    right_val.unit.canonical(),
-                    left_val.unit.canonical(),
-                )
-                .ok_or_else(|| EvalError::IncompatibleUnits {
-                    operation: "comparison".to_string(),
-                    left: left_val.unit.to_string(),
-                    right: right_val.unit.to_string(),
-                })?
-        };
-
-        // Compare and return boolean (as 1.0 or 0.0)
-        let result = if compare(left_val.numeric_value(), right_converted) {
-            1.0
-        } else {
-            0.0
-        };
-        Ok(EvalResult::new(
-            result,
-            crate::core::units::Unit::dimensionless(),
-        ))
+/// The value a formula sees for a cell, or None if the cell is empty
+fn cell_result(cell: &Cell) -> Option<Result<EvalResult, EvalError>> {
+    match cell.value() {
+        CellValue::Number(n) => Some(Ok(EvalResult::new(*n, cell.storage_unit().clone())
+            .with_uncertainty(cell.uncertainty())
+            .with_significant_figures(cell.significant_figures()))),
+        CellValue::Text(t) => Some(Ok(EvalResult::text(t.clone()))),
+        CellValue::Boolean(b) => Some(Ok(EvalResult::boolean(*b))),
+        CellValue::DateTime(d) => Some(Ok(EvalResult::datetime(*d))),
+        CellValue::Empty => None,
+        // Errors propagate to formulas that use the cell
+        CellValue::Error(e) => Some(Err(EvalError::ErrorValue(e.clone()))),
     }
+}
 
-    /// Evaluate IF function
-    /// IF(condition, true_value, false_value)
-    fn eval_if(&self, args: &[Expr]) -> Result<EvalResult, EvalError> {
-        if args.len() != 3 {
-            return Err(EvalError::InvalidOperation(
-                "IF requires exactly 3 arguments: IF(condition, true_value, false_value)"
-                    .to_string(),
-            ));
-        }
+/// The value a cell stores for a formula's result; an array gives its first value
+fn cell_value(result: EvalResult) -> (CellValue, crate::core::units::Unit) {
+    let value = match result.value {
+        EvalValue::Number(n) => CellValue::Number(n),
+        EvalValue::Text(s) => CellValue::Text(s),
+        EvalValue::Boolean(b) => CellValue::Boolean(b),
+        EvalValue::DateTime(d) => CellValue::DateTime(d),
+        EvalValue::Array(values) => match values.into_iter().flatten().next() {
+            Some(first) => return cell_value(first),
+            None => CellValue::Empty,
+        },
+    };
+    (value, result.unit)
+}
 
-        let condition = self.eval(&args[0])?;
+/// Store a formula's result in a cell: its value, unit, uncertainty and precision
+fn set_result(cell: &mut Cell, result: EvalResult) {
+    cell.set_uncertainty(result.uncertainty);
+    cell.set_significant_figures(result.significant_figures);
+    let (value, unit) = cell_value(result);
+    cell.set_value(value);
+    cell.set_storage_unit(unit);
+}
 
-        // Treat non-zero as true
-        if condition.numeric_value() != 0.0 {
-            self.eval(&args[1])
-        } else {
-            self.eval(&args[2])
-        }
-    }
+/// The addresses in a (bounded) range, in row-major order
+fn range_addresses(range: &CellRange) -> impl Iterator<Item = CellAddr> + '_ {
+    (range.start_row..=range.end_row).flat_map(move |row| {
+        (range.start_col..=range.end_col)
+            .filter_map(move |col| col_num_to_letter(col).ok())
+            .map(move |col| CellAddr::new(col, row))
+    })
+}
 
-    /// Evaluate AND function
-    /// AND(arg1, arg2, ...)
-    fn eval_and(&self, args: &[Expr]) -> Result<EvalResult, EvalError> {
-        if args.is_empty() {
-            return Err(EvalError::InvalidOperation(
-                "AND requires at least one argument".to_string(),
-            ));
-        }
+impl EvalContext for SheetContext<'_> {
+    fn cell(&self, sheet: Option<&str>, addr: &CellAddr) -> Result<EvalResult, EvalError> {
+        let cell = self
+            .sheet(sheet)?
+            .get(addr)
+            .ok_or_else(|| EvalError::CellNotFound(addr.to_string()))?;
 
-        for arg in args {
-            let val = self.eval(arg)?;
-            if val.numeric_value() == 0.0 {
-                return Ok(EvalResult::new(
-                    0.0,
-                    crate::core::units::Unit::dimensionless(),
-                ));
-            }
-        }
-        Ok(EvalResult::new(
-            1.0,
-            crate::core::units::Unit::dimensionless(),
-        ))
+        cell_result(cell).unwrap_or_else(|| {
+            Err(EvalError::InvalidOperation(format!(
+                "Cell {} is empty",
+                addr
+            )))
+        })
     }
 
-    /// Evaluate OR function
-    /// OR(arg1, arg2, ...)
-    fn eval_or(&self, args: &[Expr]) -> Result<EvalResult, EvalError> {
-        if args.is_empty() {
-            return Err(EvalError::InvalidOperation(
-                "OR requires at least one argument".to_string(),
-            ));
-        }
-
-        for arg in args {
-            let val = self.eval(arg)?;
-            if val.numeric_value() != 0.0 {
-                return Ok(EvalResult::new(
-                    1.0,
-                    crate::core::units::Unit::dimensionless(),
-                ));
+    fn range(&self, sheet: Option<&str>, range: &CellRange) -> Result<Vec<EvalResult>, EvalError> {
+        // Empty, text and boolean cells in the range are skipped; the first error propagates
+        let mut values = Vec::new();
+        for (_, cell) in self.sheet(sheet)?.cells_in_range(range) {
+            match cell.value() {
+                CellValue::Number(n) => {
+                    values.push(
+                        EvalResult::new(*n, cell.storage_unit().clone())
+                            .with_uncertainty(cell.uncertainty())
+                            .with_significant_figures(cell.significant_figures()),
+                    );
+                }
+                CellValue::DateTime(d) => values.push(EvalResult::datetime(*d)),
+                CellValue::Error(e) => return Err(EvalError::ErrorValue(e.clone())),
+                CellValue::Empty | CellValue::Text(_) | CellValue::Boolean(_) => {}
             }
         }
-        Ok(EvalResult::new(
-            0.0,
-            crate::core::units::Unit::dimensionless(),
-        ))
-    }
-
-    /// Evaluate NOT function
-    /// NOT(value)
-    fn eval_not_fn(&self, args: &[Expr]) -> Result<EvalResult, EvalError> {
-        if args.len() != 1 {
-            return Err(EvalError::InvalidOperation(
-                "NOT requires exactly 1 argument".to_string(),
-            ));
-        }
-
-        let val = self.eval(&args[0])?;
-        let result = if val.numeric_value() == 0.0 { 1.0 } else { 0.0 };
-        Ok(EvalResult::new(
-            result,
-            crate::core::units::Unit::dimensionless(),
-        ))
-    }
-
-    /// Evaluate GT (greater than) function
-    /// GT(left, right)
-    fn eval_gt(&self, args: &[Expr]) -> Result<EvalResult, EvalError> {
-        if args.len() != 2 {
-            return Err(EvalError::InvalidOperation(
-                "GT requires exactly 2 arguments: GT(left, right)".to_string(),
-            ));
-        }
-        self.eval_comparison(&args[0], &args[1], |a, b| a > b)
-    }
-
-    /// Evaluate LT (less than) function
-    /// LT(left, right)
-    fn eval_lt(&self, args: &[Expr]) -> Result<EvalResult, EvalError> {
-        if args.len() != 2 {
-            return Err(EvalError::InvalidOperation(
-                "LT requires exactly 2 arguments: LT(left, right)".to_string(),
-            ));
-        }
-        self.eval_comparison(&args[0], &args[1], |a, b| a < b)
-    }
-
-    /// Evaluate GTE (greater than or equal) function
-    /// GTE(left, right)
-    fn eval_gte(&self, args: &[Expr]) -> Result<EvalResult, EvalError> {
-        if args.len() != 2 {
-            return Err(EvalError::InvalidOperation(
-                "GTE requires exactly 2 arguments: GTE(left, right)".to_string(),
-            ));
-        }
-        self.eval_comparison(&args[0], &args[1], |a, b| a >= b)
-    }
-
-    /// Evaluate LTE (less than or equal) function
-    /// LTE(left, right)
-    fn eval_lte(&self, args: &[Expr]) -> Result<EvalResult, EvalError> {
-        if args.len() != 2 {
-            return Err(EvalError::InvalidOperation(
-                "LTE requires exactly 2 arguments: LTE(left, right)".to_string(),
-            ));
-        }
-        self.eval_comparison(&args[0], &args[1], |a, b| a <= b)
-    }
-
-    /// Evaluate EQ (equal) function
-    /// EQ(left, right)
-    fn eval_eq(&self, args: &[Expr]) -> Result<EvalResult, EvalError> {
-        if args.len() != 2 {
-            return Err(EvalError::InvalidOperation(
-                "EQ requires exactly 2 arguments: EQ(left, right)".to_string(),
-            ));
-        }
-        self.eval_comparison(&args[0], &args[1], |a, b| a == b)
-    }
-
-    /// Evaluate NE (not equal) function
-    /// NE(left, right)
-    fn eval_ne(&self, args: &[Expr]) -> Result<EvalResult, EvalError> {
-        if args.len() != 2 {
-            return Err(EvalError::InvalidOperation(
-                "NE requires exactly 2 arguments: NE(left, right)".to_string(),
-            ));
-        }
-        self.eval_comparison(&args[0], &args[1], |a, b| a != b)
-    }
-
-    /// Evaluate SQRT function
-    /// Square root with dimension transformation: exponents divided by 2
-    /// Example: SQRT(100 m²) = 10 m
-    fn eval_sqrt(&self, args: &[Expr]) -> Result<EvalResult, EvalError> {
-        if args.len() != 1 {
-            return Err(EvalError::InvalidOperation(
-                "SQRT requires exactly 1 argument".to_string(),
-            ));
-        }
-
-        let result = self.eval(&args[0])?;
-
-        // Check for negative values
-        if result.numeric_value() < 0.0 {
-            return Err(EvalError::InvalidOperation(
-                "SQRT of negative number is not supported (complex numbers not supported)"
-                    .to_string(),
-            ));
-        }
-
-        // Compute square root of value
-        let sqrt_value = result.numeric_value().sqrt();
-
-        // Transform unit by dividing exponents by 2
-        use crate::core::formula::evaluator::transform_unit_exponents;
-        let sqrt_unit = transform_unit_exponents(&result.unit, 0.5);
-
-        Ok(EvalResult::new(sqrt_value, sqrt_unit))
+        Ok(values)
     }
 
-    /// Evaluate POWER function
-    /// Raise to power with dimension transformation: exponents multiplied by power
-    /// Example: POWER(5 m, 2) = 25 m²
-    fn eval_power(&self, args: &[Expr]) -> Result<EvalResult, EvalError> {
-        if args.len() != 2 {
-            return Err(EvalError::InvalidOperation(
-                "POWER requires exactly 2 arguments: POWER(base, exponent)".to_string(),
-            ));
-        }
-
-        // Evaluate base (with unit)
-        let base_result = self.eval(&args[0])?;
-
-        // Evaluate exponent (must be dimensionless)
-        let exponent_result = self.eval(&args[1])?;
+    fn grid(&self, sheet: Option<&str>, range: &CellRange) -> Result<CellGrid, EvalError> {
+        let sheet = self.sheet(sheet)?;
+        let cells = sheet.cells_in_range(range);
+        let col_of = |addr: &CellAddr| col_letter_to_num(&addr.col).unwrap_or(0);
 
-        // Check that exponent is dimensionless
-        if !exponent_result.unit.is_dimensionless() {
-            return Err(EvalError::InvalidOperation(format!(
-                "Exponent must be dimensionless, got: {}",
-                exponent_result.unit
-            )));
-        }
-
-        let exponent = exponent_result.numeric_value();
-
-        // Compute base^exponent
-        let power_value = base_result.numeric_value().powf(exponent);
+        // Whole columns and rows stop at the last row or column in use on the sheet, so that
+        // A:A and B:B line up
+        let mut in_use = sheet.cell_addresses();
+        in_use.extend(sheet.spilled_addresses());
+        let end_row = match range.end_row {
+            usize::MAX => in_use.iter().map(|addr| addr.row).max().unwrap_or(0),
+            end_row => end_row,
+        };
+        let end_col = match range.end_col {
+            usize::MAX => in_use.iter().map(col_of).max().unwrap_or(0),
+            end_col => end_col,
+        };
 
-        // Special case: any unit^0 = dimensionless
-        if exponent == 0.0 {
-            return Ok(EvalResult::new(
-                power_value,
-                crate::core::units::Unit::dimensionless(),
-            ));
+        let mut grid: CellGrid = (range.start_row..=end_row)
+            .map(|_| (range.start_col..=end_col).map(|_| None).collect())
+            .collect();
+        for (addr, cell) in cells {
+            grid[addr.row - range.start_row][col_of(&addr) - range.start_col] = cell_result(cell);
         }
-
-        // Transform unit by multiplying exponents by power
-        use crate::core::formula::evaluator::transform_unit_exponents;
-        let power_unit = transform_unit_exponents(&base_result.unit, exponent);
-
-        Ok(EvalResult::new(power_value, power_unit))
+        Ok(grid)
     }
 
-    /// Evaluate MEDIAN function
-    fn eval_median(&self, args: &[Expr]) -> Result<EvalResult, EvalError> {
-        let values = self.collect_values(args)?;
-        if values.is_empty() {
-            return Err(EvalError::InvalidOperation(
-                "MEDIAN requires at least one value".to_string(),
-            ));
-        }
-
-        // Check unit compatibility
-        let first_unit = &values[0].unit;
-        let mut converted_values = Vec::new();
-
-        for val in &values {
-            if !val.unit.is_compatible(first_unit) {
-                return Err(EvalError::IncompatibleUnits {
-                    operation: "MEDIAN".to_string(),
-                    left: first_unit.to_string(),
-                    right: val.unit.to_string(),
-                });
-            }
-
-            let converted = if val.unit != *first_unit {
-                self.library
-                    .convert(
-                        val.numeric_value(),
-                        val.unit.canonical(),
-                        first_unit.canonical(),
-                    )
-                    .ok_or_else(|| EvalError::IncompatibleUnits {
-                        operation: "MEDIAN".to_string(),
-                        left: first_unit.to_string(),
-                        right: val.unit.to_string(),
-                    })?
-            } else {
-                val.numeric_value()
-            };
-            converted_values.push(converted);
-        }
-
-        // Use statrs to compute median
-        let mut data = Data::new(converted_values);
-        let median = data.median();
-
-        Ok(EvalResult::new(median, first_unit.clone()))
+    fn spill(&self, sheet: Option<&str>, anchor: &CellAddr) -> Result<CellRange, EvalError> {
+        self.sheet(sheet)?
+            .spill_range(anchor)
+            .ok_or_else(|| EvalError::CellNotFound(format!("{} has no spilled array", anchor)))
     }
 
-    /// Evaluate STDEV function (standard deviation)
-    fn eval_stdev(&self, args: &[Expr]) -> Result<EvalResult, EvalError> {
-        let values = self.collect_values(args)?;
-        if values.len() < 2 {
-            return Err(EvalError::InvalidOperation(
-                "STDEV requires at least 2 values".to_string(),
-            ));
-        }
-
-        // Check compatibility and convert
-        let first_unit = &values[0].unit;
-        let mut converted_values = Vec::new();
-
-        for val in &values {
-            if !val.unit.is_compatible(first_unit) {
-                return Err(EvalError::IncompatibleUnits {
-                    operation: "STDEV".to_string(),
-                    left: first_unit.to_string(),
-                    right: val.unit.to_string(),
-                });
-            }
-
-            let converted = if val.unit != *first_unit {
-                self.library
-                    .convert(
-                        val.numeric_value(),
-                        val.unit.canonical(),
-                        first_unit.canonical(),
-                    )
-                    .ok_or_else(|| EvalError::IncompatibleUnits {
-                        operation: "STDEV".to_string(),
-                        left: first_unit.to_string(),
-                        right: val.unit.to_string(),
-                    })?
-            } else {
-                val.numeric_value()
-            };
-            converted_values.push(converted);
-        }
-
-        // Use statrs to compute standard deviation
-        let data = Data::new(converted_values);
-        let stdev = data.std_dev().ok_or_else(|| {
-            EvalError::InvalidOperation("Failed to compute standard deviation".to_string())
+    fn name(&self, name: &str) -> Result<EvalResult, EvalError> {
+        let named_refs = self.named_refs.ok_or_else(|| {
+            EvalError::NamedRefNotFound(format!(
+                "Named reference '{}' requires workbook context",
+                name
+            ))
         })?;
 
-        Ok(EvalResult::new(stdev, first_unit.clone()))
+        named_refs
+            .get(name)
+            .map(|(value, unit)| EvalResult::new(*value, unit.clone()))
+            .ok_or_else(|| {
+                EvalError::NamedRefNotFound(format!("Named reference '{}' not found", name))
+            })
     }
 
-    /// Evaluate VAR function (variance)
-    fn eval_var(&self, args: &[Expr]) -> Result<EvalResult, EvalError> {
-        let values = self.collect_values(args)?;
-        if values.len() < 2 {
-            return Err(EvalError::InvalidOperation(
-                "VAR requires at least 2 values".to_string(),
-            ));
-        }
-
-        // Check compatibility and convert
-        let first_unit = &values[0].unit;
-        let mut converted_values = Vec::new();
-
-        for val in &values {
-            // First check compatibility
-            if !val.unit.is_compatible(first_unit) {
-                return Err(EvalError::IncompatibleUnits {
-                    operation: "VAR".to_string(),
-                    left: first_unit.to_string(),
-                    right: val.unit.to_string(),
-                });
-            }
-
-            // Then convert if needed
-            let converted = if !val.unit.is_equal(first_unit) {
-                self.library
-                    .convert(
-                        val.numeric_value(),
-                        val.unit.canonical(),
-                        first_unit.canonical(),
-                    )
-                    .ok_or_else(|| {
-                        EvalError::InvalidOperation(format!(
-                            "Failed to convert {} to {}",
-                            val.unit, first_unit
-                        ))
-                    })?
-            } else {
-                val.numeric_value()
-            };
-            converted_values.push(converted);
-        }
-
-        // Use statrs to compute variance
-        let data = Data::new(converted_values);
-        let variance = data
-            .variance()
-            .ok_or_else(|| EvalError::InvalidOperation("Failed to compute variance".to_string()))?;
-
-        // Variance has squared units! Use transform_unit_exponents with factor 2
-        use crate::core::formula::evaluator::transform_unit_exponents;
-        let variance_unit = transform_unit_exponents(first_unit, 2.0);
-
-        Ok(EvalResult::new(variance, variance_unit))
+    fn is_blank(&self, sheet: Option<&str>, addr: &CellAddr) -> Result<bool, EvalError> {
+        Ok(self
+            .sheet(sheet)?
+            .get(addr)
+            .is_none_or(|cell| cell.is_empty()))
     }
 }
 
@@ -2333,6 +2305,78 @@ mod tests {
         assert!(graph.has_circular_reference(&a3));
     }
 
+    #[test]
+    fn test_cycles() {
+        let mut graph = DependencyGraph::new();
+        let addr = |name: &str| CellAddr::from_string(name).unwrap();
+
+        // A1 -> A2 -> A3 -> A1 is a loop that B1 reads from; C1 reads itself through a range
+        graph.add_dependency(addr("A2"), addr("A1"));
+        graph.add_dependency(addr("A3"), addr("A2"));
+        graph.add_dependency(addr("A1"), addr("A3"));
+        graph.add_dependency(addr("B1"), addr("A3"));
+        graph.add_range_dependency(addr("C1"), CellRange::new(&addr("C1"), &addr("C2")));
+        graph.add_dependency(addr("D1"), addr("B1"));
+
+        let keys = graph.calculation_order(&[addr("A1"), addr("C1"), addr("D1")]);
+        let mut cycles: Vec<Vec<String>> = graph
+            .cycles(&keys)
+            .into_iter()
+            .map(|group| {
+                let mut group: Vec<String> = group.iter().map(CellAddr::to_string).collect();
+                group.sort();
+                group
+            })
+            .collect();
+        cycles.sort();
+        assert_eq!(cycles, vec![vec!["A1", "A2", "A3"], vec!["C1"]]);
+    }
+
+    #[test]
+    fn test_dependents_through_many_ranges() {
+        let mut graph = DependencyGraph::new();
+        let addr = |col: &str, row: usize| CellAddr::new(col, row);
+
+        // Z1..Z500 each sum ten rows of A:C, starting on their own row; Y1 sums all of row 3
+        for row in 1..=500 {
+            let range = CellRange::new(&addr("A", row), &addr("C", row + 9));
+            graph.add_range_dependency(addr("Z", row), range);
+        }
+        graph.add_range_dependency(addr("Y", 1), CellRange::rows(3, 3));
+
+        let rows_of = |dependents: HashSet<CellAddr>| {
+            let mut rows: Vec<(String, usize)> =
+                dependents.into_iter().map(|a| (a.col, a.row)).collect();
+            rows.sort();
+            rows
+        };
+        let expected: Vec<(String, usize)> = (91..=100).map(|row| ("Z".to_string(), row)).collect();
+        assert_eq!(rows_of(graph.get_dependents(&addr("B", 100))), expected);
+        assert_eq!(
+            rows_of(graph.get_dependents(&addr("A", 3))),
+            vec![
+                ("Y".to_string(), 1),
+                ("Z".to_string(), 1),
+                ("Z".to_string(), 2),
+                ("Z".to_string(), 3)
+            ]
+        );
+        assert!(graph.get_dependents(&addr("D", 100)).is_empty());
+        assert!(graph.get_dependents(&addr("B", 600)).is_empty());
+
+        // Ranges no cell depends on any more are dropped from the index
+        for row in 1..=500 {
+            graph.remove_dependencies(&addr("Z", row));
+        }
+        assert_eq!(
+            rows_of(graph.get_dependents(&addr("B", 3))),
+            vec![("Y".to_string(), 1)]
+        );
+        graph.remove_dependencies(&addr("Y", 1));
+        assert!(graph.range_index.is_empty());
+        assert!(graph.range_dependents.is_empty());
+    }
+
     #[test]
     fn test_sheet_set_get() {
         let mut sheet = Sheet::new();
@@ -2383,6 +2427,26 @@ mod tests {
         assert!(refs.contains(&CellAddr::new("B", 2)));
     }
 
+    #[test]
+    fn test_extract_sheet_refs() {
+        let expr = parse_formula("=A1 + Inputs!B2 * SUM('Cost Model'!C1:C3)").unwrap();
+
+        // Sheet-qualified references are not dependencies within this sheet
+        let refs = extract_cell_refs(&expr, None);
+        assert_eq!(refs.len(), 1);
+        assert!(refs.contains(&CellAddr::new("A", 1)));
+
+        let sheet_refs = extract_sheet_refs(&expr);
+        assert_eq!(sheet_refs.len(), 1);
+        assert!(sheet_refs.contains(&("Inputs".to_string(), CellAddr::new("B", 2))));
+
+        let sheet_ranges = extract_sheet_range_refs(&expr);
+        assert!(sheet_ranges.contains(&(
+            "Cost Model".to_string(),
+            CellRange::new(&CellAddr::new("C", 1), &CellAddr::new("C", 3))
+        )));
+    }
+
     #[test]
     fn test_sum_function() {
         let mut sheet = Sheet::new();
@@ -2413,6 +2477,275 @@ mod tests {
         assert_eq!(unit.canonical(), "m");
     }
 
+    fn grid_sheet() -> Sheet {
+        // A1:C3 holds 1..9 m, row by row
+        let mut sheet = Sheet::new();
+        for (i, col) in ["A", "B", "C"].iter().enumerate() {
+            for row in 1..=3 {
+                let value = ((row - 1) * 3 + i + 1) as f64;
+                sheet
+                    .set(
+                        CellAddr::new(*col, row),
+                        Cell::new(value, Unit::simple("m", BaseDimension::Length)),
+                    )
+                    .unwrap();
+            }
+        }
+        sheet
+    }
+
+    #[test]
+    fn test_rectangular_range() {
+        let sheet = grid_sheet();
+
+        let (value, unit) = sheet.evaluate_formula("=SUM(A1:C3)").unwrap();
+        assert_eq!(value, CellValue::Number(45.0));
+        assert_eq!(unit.canonical(), "m");
+
+        // Corners can be given in any order
+        let (value, _) = sheet.evaluate_formula("=SUM(C2:B1)").unwrap();
+        assert_eq!(value, CellValue::Number(16.0));
+
+        let cells = sheet.get_range(&CellAddr::new("B", 1), &CellAddr::new("C", 2));
+        let addrs: Vec<String> = cells.iter().map(|(addr, _)| addr.to_string()).collect();
+        assert_eq!(addrs, vec!["B1", "C1", "B2", "C2"]);
+    }
+
+    #[test]
+    fn test_whole_column_and_row_ranges() {
+        let sheet = grid_sheet();
+
+        let (value, _) = sheet.evaluate_formula("=SUM(B:B)").unwrap();
+        assert_eq!(value, CellValue::Number(15.0));
+
+        let (value, _) = sheet.evaluate_formula("=SUM(A:B)").unwrap();
+        assert_eq!(value, CellValue::Number(27.0));
+
+        let (value, _) = sheet.evaluate_formula("=SUM(2:2)").unwrap();
+        assert_eq!(value, CellValue::Number(15.0));
+
+        let (value, _) = sheet.evaluate_formula("=MAX(3:3)").unwrap();
+        assert_eq!(value, CellValue::Number(9.0));
+
+        // A range on its own is an array, which shows its first value in the formula's cell
+        let (value, _) = sheet.evaluate_formula("=B:B").unwrap();
+        assert_eq!(value, CellValue::Number(2.0));
+    }
+
+    #[test]
+    fn test_aggregates_over_rectangular_range() {
+        let sheet = grid_sheet();
+
+        let cases = [
+            ("=AVERAGE(A1:C3)", 5.0),
+            ("=MIN(A1:C3)", 1.0),
+            ("=MAX(A1:C3)", 9.0),
+            ("=MEDIAN(A1:C3)", 5.0),
+            ("=COUNT(A1:C3)", 9.0),
+        ];
+        for (formula, expected) in cases {
+            let (value, _) = sheet.evaluate_formula(formula).unwrap();
+            assert_eq!(value, CellValue::Number(expected), "{}", formula);
+        }
+
+        let (value, _) = sheet.evaluate_formula("=STDEV(A1:C3)").unwrap();
+        match value {
+            CellValue::Number(n) => assert!((n - 7.5_f64.sqrt()).abs() < 1e-9),
+            other => panic!("Expected number, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_range_dependencies() {
+        let mut sheet = grid_sheet();
+        sheet
+            .set(CellAddr::new("E", 1), Cell::with_formula("=SUM(A1:C3)"))
+            .unwrap();
+        sheet
+            .set(CellAddr::new("E", 2), Cell::with_formula("=SUM(B:B)"))
+            .unwrap();
+
+        // B2 is inside both ranges even though neither names it
+        let affected = sheet.calculation_order(&[CellAddr::new("B", 2)]);
+        assert!(affected.contains(&CellAddr::new("E", 1)));
+        assert!(affected.contains(&CellAddr::new("E", 2)));
+
+        // A cell summing a range that contains itself is circular
+        let result = sheet.set(CellAddr::new("B", 5), Cell::with_formula("=SUM(B:B)"));
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn test_whole_range_column_and_row_updates() {
+        let mut sheet = Sheet::new();
+        sheet
+            .set(
+                CellAddr::new("F", 1),
+                Cell::with_formula("=SUM(B:D) + SUM(2:4)"),
+            )
+            .unwrap();
+
+        sheet.delete_column("C").unwrap();
+        assert_eq!(
+            sheet.get(&CellAddr::new("E", 1)).unwrap().formula(),
+            Some("=(SUM(B:C) + SUM(2:4))")
+        );
+
+        sheet.delete_row(3).unwrap();
+        assert_eq!(
+            sheet.get(&CellAddr::new("E", 1)).unwrap().formula(),
+            Some("=(SUM(B:C) + SUM(2:3))")
+        );
+
+        sheet.insert_column_before("A").unwrap();
+        assert_eq!(
+            sheet.get(&CellAddr::new("F", 1)).unwrap().formula(),
+            Some("=(SUM(C:D) + SUM(2:3))")
+        );
+    }
+
+    #[test]
+    fn test_fill_down_rewrites_relative_references() {
+        let mut sheet = Sheet::new();
+        let usd_per_hour = Unit::compound(
+            "USD/hr",
+            vec![(BaseDimension::Currency, 1)],
+            vec![(BaseDimension::Time, 1)],
+        );
+        let hours = Unit::simple("hr", BaseDimension::Time);
+        sheet
+            .set(CellAddr::new("E", 1), Cell::new(40.0, usd_per_hour.clone()))
+            .unwrap();
+        for row in 2..=51 {
+            sheet
+                .set(
+                    CellAddr::new("B", row),
+                    Cell::new(row as f64, hours.clone()),
+                )
+                .unwrap();
+        }
+        sheet
+            .set(CellAddr::new("C", 2), Cell::with_formula("=B2 * $E$1"))
+            .unwrap();
+
+        // Fill C2 down the whole cost table
+        let changed = sheet
+            .copy_range(
+                &CellRange::new(&CellAddr::new("C", 2), &CellAddr::new("C", 2)),
+                &CellRange::new(&CellAddr::new("C", 2), &CellAddr::new("C", 51)),
+            )
+            .unwrap();
+        assert_eq!(changed.len(), 50);
+        sheet.recalculate(&changed).unwrap();
+
+        let last = sheet.get(&CellAddr::new("C", 51)).unwrap();
+        assert_eq!(last.formula(), Some("=B51 * $E$1"));
+        assert_eq!(last.as_number(), Some(2040.0));
+
+        // The copies depend on the rate, so changing it reaches the last row
+        sheet
+            .set(CellAddr::new("E", 1), Cell::new(50.0, usd_per_hour))
+            .unwrap();
+        sheet.recalculate(&[CellAddr::new("E", 1)]).unwrap();
+        assert_eq!(
+            sheet.get(&CellAddr::new("C", 51)).unwrap().as_number(),
+            Some(2550.0)
+        );
+    }
+
+    #[test]
+    fn test_copy_range_mixed_references() {
+        let mut sheet = Sheet::new();
+        sheet
+            .set(
+                CellAddr::new("B", 2),
+                Cell::with_formula("=$A2 * B$1 + SUM(A1:A3) + Sheet2!C3"),
+            )
+            .unwrap();
+        sheet
+            .set(CellAddr::new("C", 3), Cell::new(1.0, Unit::dimensionless()))
+            .unwrap();
+
+        // Paste one column right and two rows down; the existing C3 is replaced
+        sheet
+            .copy_range(
+                &CellRange::new(&CellAddr::new("B", 2), &CellAddr::new("B", 2)),
+                &CellRange::new(&CellAddr::new("C", 4), &CellAddr::new("C", 4)),
+            )
+            .unwrap();
+        assert_eq!(
+            sheet.get(&CellAddr::new("C", 4)).unwrap().formula(),
+            Some("=$A4 * C$1 + SUM(B3:B5) + Sheet2!D5")
+        );
+        assert!(sheet.get(&CellAddr::new("C", 3)).is_some());
+
+        // A 2x1 block pasted into a 2x3 destination is repeated across it
+        sheet
+            .copy_range(
+                &CellRange::new(&CellAddr::new("B", 2), &CellAddr::new("B", 3)),
+                &CellRange::new(&CellAddr::new("E", 2), &CellAddr::new("G", 3)),
+            )
+            .unwrap();
+        assert_eq!(
+            sheet.get(&CellAddr::new("G", 2)).unwrap().formula(),
+            Some("=$A2 * G$1 + SUM(F1:F3) + Sheet2!H3")
+        );
+        assert!(sheet.get(&CellAddr::new("G", 3)).is_none());
+
+        // Copying up past row 1 leaves a #REF!
+        sheet
+            .copy_range(
+                &CellRange::new(&CellAddr::new("B", 2), &CellAddr::new("B", 2)),
+                &CellRange::new(&CellAddr::new("B", 1), &CellAddr::new("B", 1)),
+            )
+            .unwrap();
+        assert_eq!(
+            sheet.get(&CellAddr::new("B", 1)).unwrap().formula(),
+            Some("=$A1 * B$1 + SUM(#REF!) + Sheet2!C2")
+        );
+    }
+
+    #[test]
+    fn test_move_range_updates_references() {
+        let mut sheet = Sheet::new();
+        sheet
+            .set(CellAddr::new("A", 1), Cell::new(2.0, Unit::dimensionless()))
+            .unwrap();
+        sheet
+            .set(CellAddr::new("A", 2), Cell::new(3.0, Unit::dimensionless()))
+            .unwrap();
+        sheet
+            .set(CellAddr::new("B", 1), Cell::with_formula("=A1 * 10"))
+            .unwrap();
+        sheet
+            .set(
+                CellAddr::new("C", 1),
+                Cell::with_formula("=$A$1 + SUM(A1:A2) + B1"),
+            )
+            .unwrap();
+
+        // Move A1:B1 to D5
+        sheet
+            .move_range(
+                &CellRange::new(&CellAddr::new("A", 1), &CellAddr::new("B", 1)),
+                &CellAddr::new("D", 5),
+            )
+            .unwrap();
+
+        assert!(sheet.get(&CellAddr::new("A", 1)).is_none());
+        assert!(sheet.get(&CellAddr::new("B", 1)).is_none());
+        // The moved formula still points at the cell it used to, which moved with it
+        assert_eq!(
+            sheet.get(&CellAddr::new("E", 5)).unwrap().formula(),
+            Some("=D5 * 10")
+        );
+        // Only part of A1:A2 moved, so the range stays where it was
+        assert_eq!(
+            sheet.get(&CellAddr::new("C", 1)).unwrap().formula(),
+            Some("=$D$5 + SUM(A1:A2) + E5")
+        );
+    }
+
     #[test]
     fn test_sum_with_individual_args() {
         let mut sheet = Sheet::new();
@@ -2497,6 +2830,36 @@ mod tests {
         assert_eq!(unit.canonical(), "m");
     }
 
+    #[test]
+    fn test_sum_of_empty_range() {
+        let sheet = Sheet::new();
+        for formula in ["=SUM(B1:B3)", "=SUMIF(B1:B3, \">0\")"] {
+            let (value, _) = sheet.evaluate_formula(formula).unwrap();
+            let CellValue::Number(value) = value else {
+                panic!("Expected a number, got {:?}", value);
+            };
+            // Positive zero, which shows as "0" rather than "-0"
+            assert!(value == 0.0 && value.is_sign_positive(), "{}", formula);
+        }
+    }
+
+    #[test]
+    fn test_mixed_unit_arithmetic_keeps_left_unit() {
+        let sheet = Sheet::new();
+
+        // The right operand is converted to the left operand's unit
+        let (value, unit) = sheet.evaluate_formula("=5 m + 3 ft").unwrap();
+        let CellValue::Number(value) = value else {
+            panic!("Expected a number, got {:?}", value);
+        };
+        assert!((value - 5.9144).abs() < 1e-9);
+        assert_eq!(unit.canonical(), "m");
+
+        let (value, unit) = sheet.evaluate_formula("=1 min - 15 s").unwrap();
+        assert_eq!(value, CellValue::Number(0.75));
+        assert_eq!(unit.canonical(), "min");
+    }
+
     #[test]
     fn test_convert_function() {
         let mut sheet = Sheet::new();
@@ -3172,6 +3535,19 @@ mod tests {
         assert!(!unit.is_dimensionless());
     }
 
+    #[test]
+    fn test_power_without_a_real_result() {
+        let sheet = Sheet::new();
+        for (formula, kind) in [
+            ("=0^-1", ErrorKind::DivZero),
+            ("=POWER(0, -2)", ErrorKind::DivZero),
+            ("=(-8)^(1/3)", ErrorKind::Value),
+        ] {
+            let error = sheet.evaluate_formula(formula).unwrap_err();
+            assert_eq!(error.to_cell_error().kind, kind, "{}", formula);
+        }
+    }
+
     #[test]
     fn test_power_zero_exponent() {
         let mut sheet = Sheet::new();
@@ -3280,12 +3656,12 @@ mod tests {
 
         // 10m > 5m → TRUE (1.0)
         let (value, unit) = sheet.evaluate_formula("=GT(A1, A2)").unwrap();
-        assert_eq!(value, CellValue::Number(1.0));
+        assert_eq!(value, CellValue::Boolean(true));
         assert!(unit.is_dimensionless());
 
         // 5m > 10m → FALSE (0.0)
         let (value, unit) = sheet.evaluate_formula("=GT(A2, A1)").unwrap();
-        assert_eq!(value, CellValue::Number(0.0));
+        assert_eq!(value, CellValue::Boolean(false));
         assert!(unit.is_dimensionless());
     }
 
@@ -3308,12 +3684,12 @@ mod tests {
 
         // 5m < 10m → TRUE (1.0)
         let (value, unit) = sheet.evaluate_formula("=LT(A2, A1)").unwrap();
-        assert_eq!(value, CellValue::Number(1.0));
+        assert_eq!(value, CellValue::Boolean(true));
         assert!(unit.is_dimensionless());
 
         // 10m < 5m → FALSE (0.0)
         let (value, unit) = sheet.evaluate_formula("=LT(A1, A2)").unwrap();
-        assert_eq!(value, CellValue::Number(0.0));
+        assert_eq!(value, CellValue::Boolean(false));
         assert!(unit.is_dimensionless());
     }
 
@@ -3342,15 +3718,15 @@ mod tests {
 
         // 10m >= 10m → TRUE (1.0)
         let (value, _) = sheet.evaluate_formula("=GTE(A1, A2)").unwrap();
-        assert_eq!(value, CellValue::Number(1.0));
+        assert_eq!(value, CellValue::Boolean(true));
 
         // 10m >= 5m → TRUE (1.0)
         let (value, _) = sheet.evaluate_formula("=GTE(A1, A3)").unwrap();
-        assert_eq!(value, CellValue::Number(1.0));
+        assert_eq!(value, CellValue::Boolean(true));
 
         // 5m >= 10m → FALSE (0.0)
         let (value, _) = sheet.evaluate_formula("=GTE(A3, A1)").unwrap();
-        assert_eq!(value, CellValue::Number(0.0));
+        assert_eq!(value, CellValue::Boolean(false));
     }
 
     #[test]
@@ -3378,15 +3754,15 @@ mod tests {
 
         // 10m <= 10m → TRUE (1.0)
         let (value, _) = sheet.evaluate_formula("=LTE(A1, A2)").unwrap();
-        assert_eq!(value, CellValue::Number(1.0));
+        assert_eq!(value, CellValue::Boolean(true));
 
         // 10m <= 15m → TRUE (1.0)
         let (value, _) = sheet.evaluate_formula("=LTE(A1, A3)").unwrap();
-        assert_eq!(value, CellValue::Number(1.0));
+        assert_eq!(value, CellValue::Boolean(true));
 
         // 15m <= 10m → FALSE (0.0)
         let (value, _) = sheet.evaluate_formula("=LTE(A3, A1)").unwrap();
-        assert_eq!(value, CellValue::Number(0.0));
+        assert_eq!(value, CellValue::Boolean(false));
     }
 
     #[test]
@@ -3414,11 +3790,11 @@ mod tests {
 
         // 10m == 10m → TRUE (1.0)
         let (value, _) = sheet.evaluate_formula("=EQ(A1, A2)").unwrap();
-        assert_eq!(value, CellValue::Number(1.0));
+        assert_eq!(value, CellValue::Boolean(true));
 
         // 10m == 5m → FALSE (0.0)
         let (value, _) = sheet.evaluate_formula("=EQ(A1, A3)").unwrap();
-        assert_eq!(value, CellValue::Number(0.0));
+        assert_eq!(value, CellValue::Boolean(false));
     }
 
     #[test]
@@ -3446,11 +3822,11 @@ mod tests {
 
         // 10m != 5m → TRUE (1.0)
         let (value, _) = sheet.evaluate_formula("=NE(A1, A3)").unwrap();
-        assert_eq!(value, CellValue::Number(1.0));
+        assert_eq!(value, CellValue::Boolean(true));
 
         // 10m != 10m → FALSE (0.0)
         let (value, _) = sheet.evaluate_formula("=NE(A1, A2)").unwrap();
-        assert_eq!(value, CellValue::Number(0.0));
+        assert_eq!(value, CellValue::Boolean(false));
     }
 
     #[test]
@@ -3473,7 +3849,7 @@ mod tests {
 
         // 1000m > 1km → FALSE (1000m == 1km)
         let (value, _) = sheet.evaluate_formula("=GT(A1, A2)").unwrap();
-        assert_eq!(value, CellValue::Number(0.0));
+        assert_eq!(value, CellValue::Boolean(false));
 
         // 50cm vs 1m
         sheet
@@ -3489,40 +3865,152 @@ mod tests {
             )
             .unwrap();
 
-        // 50cm < 1m → TRUE
-        let (value, _) = sheet.evaluate_formula("=LT(B1, B2)").unwrap();
-        assert_eq!(value, CellValue::Number(1.0));
+        // 50cm < 1m → TRUE
+        let (value, _) = sheet.evaluate_formula("=LT(B1, B2)").unwrap();
+        assert_eq!(value, CellValue::Boolean(true));
+    }
+
+    #[test]
+    fn test_comparison_incompatible_units() {
+        let mut sheet = Sheet::new();
+
+        // 10m vs 5s (incompatible)
+        sheet
+            .set(
+                CellAddr::new("A", 1),
+                Cell::new(10.0, Unit::simple("m", BaseDimension::Length)),
+            )
+            .unwrap();
+        sheet
+            .set(
+                CellAddr::new("A", 2),
+                Cell::new(5.0, Unit::simple("s", BaseDimension::Time)),
+            )
+            .unwrap();
+
+        // GT(10m, 5s) → Error
+        let result = sheet.evaluate_formula("=GT(A1, A2)");
+        assert!(result.is_err());
+
+        // LT(10m, 5s) → Error
+        let result = sheet.evaluate_formula("=LT(A1, A2)");
+        assert!(result.is_err());
+
+        // EQ(10m, 5s) → Error
+        let result = sheet.evaluate_formula("=EQ(A1, A2)");
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn test_infix_comparison_and_logic() {
+        let mut sheet = Sheet::new();
+        sheet
+            .set(
+                CellAddr::new("A", 1),
+                Cell::new(5.0, Unit::simple("ft", BaseDimension::Length)),
+            )
+            .unwrap();
+        sheet
+            .set(
+                CellAddr::new("A", 2),
+                Cell::new(1.0, Unit::simple("m", BaseDimension::Length)),
+            )
+            .unwrap();
+        sheet
+            .set(
+                CellAddr::new("A", 3),
+                Cell::new(2.0, Unit::simple("s", BaseDimension::Time)),
+            )
+            .unwrap();
+
+        let cases = [
+            ("=A1 > A2", CellValue::Boolean(true)),
+            ("=A1 <= A2", CellValue::Boolean(false)),
+            ("=A1 = 5", CellValue::Boolean(true)),
+            ("=A1 <> A2", CellValue::Boolean(true)),
+            ("=A1 > A2 AND A3 < 3", CellValue::Boolean(true)),
+            ("=A1 < A2 || A3 >= 2", CellValue::Boolean(true)),
+            ("=NOT A1 > A2", CellValue::Boolean(false)),
+            ("=IF(A1 > A2, 10, 20)", CellValue::Number(10.0)),
+        ];
+        for (formula, expected) in cases {
+            let (value, _) = sheet.evaluate_formula(formula).unwrap();
+            assert_eq!(value, expected, "{}", formula);
+        }
+
+        // Lengths and times cannot be compared
+        assert!(sheet.evaluate_formula("=A1 > A3").is_err());
     }
 
     #[test]
-    fn test_comparison_incompatible_units() {
+    fn test_power_operator_and_unit_exponents() {
         let mut sheet = Sheet::new();
-
-        // 10m vs 5s (incompatible)
         sheet
             .set(
                 CellAddr::new("A", 1),
-                Cell::new(10.0, Unit::simple("m", BaseDimension::Length)),
-            )
-            .unwrap();
-        sheet
-            .set(
-                CellAddr::new("A", 2),
-                Cell::new(5.0, Unit::simple("s", BaseDimension::Time)),
+                Cell::new(3.0, Unit::simple("ft", BaseDimension::Length)),
             )
             .unwrap();
 
-        // GT(10m, 5s) → Error
-        let result = sheet.evaluate_formula("=GT(A1, A2)");
-        assert!(result.is_err());
+        // Symbols are kept: 3 ft squared is 9 ft^2, not 9 m^2
+        let (value, unit) = sheet.evaluate_formula("=A1^2").unwrap();
+        assert_eq!(value, CellValue::Number(9.0));
+        assert_eq!(unit.canonical(), "ft^2");
 
-        // LT(10m, 5s) → Error
-        let result = sheet.evaluate_formula("=LT(A1, A2)");
-        assert!(result.is_err());
+        let (value, unit) = sheet.evaluate_formula("=POWER(A1, 2)").unwrap();
+        assert_eq!(value, CellValue::Number(9.0));
+        assert_eq!(unit.canonical(), "ft^2");
 
-        // EQ(10m, 5s) → Error
-        let result = sheet.evaluate_formula("=EQ(A1, A2)");
-        assert!(result.is_err());
+        // Acceleration times time is a speed
+        let (value, unit) = sheet.evaluate_formula("=9.81 m/s^2 * 2 s").unwrap();
+        assert_eq!(value, CellValue::Number(19.62));
+        assert_eq!(unit.canonical(), "m/s");
+
+        // Superscripts and ^ are interchangeable, and the root of an area is a length
+        let (value, unit) = sheet.evaluate_formula("=(100 ft² + 44 ft^2)^0.5").unwrap();
+        assert_eq!(value, CellValue::Number(12.0));
+        assert_eq!(unit.canonical(), "ft");
+
+        // Exponents must be dimensionless
+        assert!(sheet.evaluate_formula("=A1^(2 s)").is_err());
+    }
+
+    #[test]
+    fn test_fractional_unit_exponents() {
+        let sheet = Sheet::new();
+        let number = |formula: &str| match sheet.evaluate_formula(formula).unwrap() {
+            (CellValue::Number(value), unit) => (value, unit.canonical().to_string()),
+            other => panic!("Expected a number, got {:?}", other),
+        };
+
+        assert_eq!(number("=2 m^1.5"), (2.0, "m^1.5".to_string()));
+
+        // Roots and fractional powers keep the exact exponent
+        let (value, unit) = number("=(2 m)^0.5");
+        assert!((value - 2f64.sqrt()).abs() < 1e-12);
+        assert_eq!(unit, "m^0.5");
+        let (value, unit) = number("=(2 m)^2.5");
+        assert!((value - 2f64.powf(2.5)).abs() < 1e-12);
+        assert_eq!(unit, "m^2.5");
+        assert_eq!(number("=SQRT(4 m)").1, "m^0.5");
+
+        // Halves add back up to whole exponents
+        let (value, unit) = number("=(2 m)^0.5 * (8 m)^0.5");
+        assert!((value - 4.0).abs() < 1e-12);
+        assert_eq!(unit, "m");
+
+        // A unit has no power that is not a simple fraction
+        let kind = |formula: &str| {
+            sheet
+                .evaluate_formula(formula)
+                .unwrap_err()
+                .to_cell_error()
+                .kind
+        };
+        assert_eq!(kind("=(2 m)^0.123"), ErrorKind::Unit);
+        assert_eq!(kind("=POWER(2 m, 0.123)"), ErrorKind::Unit);
+        assert_eq!(kind("=1 m^0.123"), ErrorKind::Unit);
+        assert!((number("=2^0.123").0 - 2f64.powf(0.123)).abs() < 1e-12);
     }
 
     #[test]
@@ -3617,7 +4105,7 @@ mod tests {
 
         // AND(1, 1, 1) → 1.0
         let (value, unit) = sheet.evaluate_formula("=AND(1, 1, 1)").unwrap();
-        assert_eq!(value, CellValue::Number(1.0));
+        assert_eq!(value, CellValue::Boolean(true));
         assert!(unit.is_dimensionless());
     }
 
@@ -3627,7 +4115,7 @@ mod tests {
 
         // AND(1, 0, 1) → 0.0
         let (value, unit) = sheet.evaluate_formula("=AND(1, 0, 1)").unwrap();
-        assert_eq!(value, CellValue::Number(0.0));
+        assert_eq!(value, CellValue::Boolean(false));
         assert!(unit.is_dimensionless());
     }
 
@@ -3637,7 +4125,7 @@ mod tests {
 
         // AND(0, 0, 0) → 0.0
         let (value, unit) = sheet.evaluate_formula("=AND(0, 0, 0)").unwrap();
-        assert_eq!(value, CellValue::Number(0.0));
+        assert_eq!(value, CellValue::Boolean(false));
         assert!(unit.is_dimensionless());
     }
 
@@ -3647,7 +4135,7 @@ mod tests {
 
         // OR(1, 1, 1) → 1.0
         let (value, unit) = sheet.evaluate_formula("=OR(1, 1, 1)").unwrap();
-        assert_eq!(value, CellValue::Number(1.0));
+        assert_eq!(value, CellValue::Boolean(true));
         assert!(unit.is_dimensionless());
     }
 
@@ -3657,7 +4145,7 @@ mod tests {
 
         // OR(0, 1, 0) → 1.0
         let (value, unit) = sheet.evaluate_formula("=OR(0, 1, 0)").unwrap();
-        assert_eq!(value, CellValue::Number(1.0));
+        assert_eq!(value, CellValue::Boolean(true));
         assert!(unit.is_dimensionless());
     }
 
@@ -3667,108 +4155,355 @@ mod tests {
 
         // OR(0, 0, 0) → 0.0
         let (value, unit) = sheet.evaluate_formula("=OR(0, 0, 0)").unwrap();
-        assert_eq!(value, CellValue::Number(0.0));
+        assert_eq!(value, CellValue::Boolean(false));
+        assert!(unit.is_dimensionless());
+    }
+
+    #[test]
+    fn test_not_function_true() {
+        let sheet = Sheet::new();
+
+        // NOT(1) → 0.0
+        let (value, unit) = sheet.evaluate_formula("=NOT(1)").unwrap();
+        assert_eq!(value, CellValue::Boolean(false));
+        assert!(unit.is_dimensionless());
+    }
+
+    #[test]
+    fn test_not_function_false() {
+        let sheet = Sheet::new();
+
+        // NOT(0) → 1.0
+        let (value, unit) = sheet.evaluate_formula("=NOT(0)").unwrap();
+        assert_eq!(value, CellValue::Boolean(true));
+        assert!(unit.is_dimensionless());
+    }
+
+    #[test]
+    fn test_complex_logical_expression() {
+        let mut sheet = Sheet::new();
+
+        sheet
+            .set(
+                CellAddr::new("A", 1),
+                Cell::new(10.0, Unit::simple("m", BaseDimension::Length)),
+            )
+            .unwrap();
+        sheet
+            .set(
+                CellAddr::new("A", 2),
+                Cell::new(5.0, Unit::simple("m", BaseDimension::Length)),
+            )
+            .unwrap();
+        sheet
+            .set(
+                CellAddr::new("A", 3),
+                Cell::new(15.0, Unit::simple("m", BaseDimension::Length)),
+            )
+            .unwrap();
+
+        // AND(GT(A1, A2), LT(A1, A3)) → AND(10 > 5, 10 < 15) → AND(1, 1) → 1.0
+        let (value, _) = sheet
+            .evaluate_formula("=AND(GT(A1, A2), LT(A1, A3))")
+            .unwrap();
+        assert_eq!(value, CellValue::Boolean(true));
+
+        // OR(GT(A1, A3), LT(A2, A1)) → OR(10 > 15, 5 < 10) → OR(0, 1) → 1.0
+        let (value, _) = sheet
+            .evaluate_formula("=OR(GT(A1, A3), LT(A2, A1))")
+            .unwrap();
+        assert_eq!(value, CellValue::Boolean(true));
+
+        // IF(AND(GT(A1, A2), LT(A1, A3)), A1, A3) → IF(1, 10m, 15m) → 10m
+        let (value, unit) = sheet
+            .evaluate_formula("=IF(AND(GT(A1, A2), LT(A1, A3)), A1, A3)")
+            .unwrap();
+        assert_eq!(value, CellValue::Number(10.0));
+        assert_eq!(unit.canonical(), "m");
+    }
+
+    #[test]
+    fn test_nested_if() {
+        let mut sheet = Sheet::new();
+
+        sheet
+            .set(
+                CellAddr::new("A", 1),
+                Cell::new(10.0, Unit::dimensionless()),
+            )
+            .unwrap();
+
+        // IF(GT(A1, 5), IF(GT(A1, 15), 100, 50), 0)
+        // A1=10 → GT(10, 5)=1 → GT(10, 15)=0 → 50
+        let (value, _) = sheet
+            .evaluate_formula("=IF(GT(A1, 5), IF(GT(A1, 15), 100, 50), 0)")
+            .unwrap();
+        assert_eq!(value, CellValue::Number(50.0));
+    }
+
+    #[test]
+    fn test_median_function() {
+        let sheet = Sheet::new();
+
+        // MEDIAN(1, 2, 3, 4, 5) = 3
+        let (value, unit) = sheet.evaluate_formula("=MEDIAN(1, 2, 3, 4, 5)").unwrap();
+
+        assert_eq!(value, CellValue::Number(3.0));
         assert!(unit.is_dimensionless());
     }
 
     #[test]
-    fn test_not_function_true() {
+    fn test_median_with_units() {
+        let mut sheet = Sheet::new();
+
+        // Set up cells with units
+        sheet
+            .set(
+                CellAddr::new("A", 1),
+                Cell::new(100.0, Unit::simple("m", BaseDimension::Length)),
+            )
+            .unwrap();
+        sheet
+            .set(
+                CellAddr::new("A", 2),
+                Cell::new(200.0, Unit::simple("m", BaseDimension::Length)),
+            )
+            .unwrap();
+        sheet
+            .set(
+                CellAddr::new("A", 3),
+                Cell::new(300.0, Unit::simple("m", BaseDimension::Length)),
+            )
+            .unwrap();
+
+        // MEDIAN(A1:A3) = 200m
+        let (value, unit) = sheet.evaluate_formula("=MEDIAN(A1:A3)").unwrap();
+
+        assert_eq!(value, CellValue::Number(200.0));
+        assert_eq!(unit.canonical(), "m");
+    }
+
+    #[test]
+    fn test_median_with_conversion() {
+        let mut sheet = Sheet::new();
+
+        // 1m, 200cm, 3m → should convert 200cm to 2m → median is 2m
+        sheet
+            .set(
+                CellAddr::new("A", 1),
+                Cell::new(1.0, Unit::simple("m", BaseDimension::Length)),
+            )
+            .unwrap();
+        sheet
+            .set(
+                CellAddr::new("A", 2),
+                Cell::new(200.0, Unit::simple("cm", BaseDimension::Length)),
+            )
+            .unwrap();
+        sheet
+            .set(
+                CellAddr::new("A", 3),
+                Cell::new(3.0, Unit::simple("m", BaseDimension::Length)),
+            )
+            .unwrap();
+
+        let (value, unit) = sheet.evaluate_formula("=MEDIAN(A1:A3)").unwrap();
+
+        assert_eq!(value, CellValue::Number(2.0));
+        assert_eq!(unit.canonical(), "m");
+    }
+
+    #[test]
+    fn test_median_even_count() {
+        let sheet = Sheet::new();
+
+        // MEDIAN(1, 2, 3, 4) = 2.5
+        let (value, _) = sheet.evaluate_formula("=MEDIAN(1, 2, 3, 4)").unwrap();
+
+        assert_eq!(value, CellValue::Number(2.5));
+    }
+
+    #[test]
+    fn test_stdev_function() {
+        let sheet = Sheet::new();
+
+        // Test standard deviation with known values
+        // For [2, 4, 4, 4, 5, 5, 7, 9], sample stdev (n-1) ≈ 2.138
+        let (value, _) = sheet
+            .evaluate_formula("=STDEV(2, 4, 4, 4, 5, 5, 7, 9)")
+            .unwrap();
+
+        // Standard deviation should be approximately 2.138
+        if let CellValue::Number(num) = value {
+            assert!((num - 2.138).abs() < 0.01);
+        } else {
+            panic!("Expected numeric value");
+        }
+    }
+
+    #[test]
+    fn test_stdev_with_units() {
+        let mut sheet = Sheet::new();
+
+        // Set up cells with units
+        sheet
+            .set(
+                CellAddr::new("A", 1),
+                Cell::new(100.0, Unit::simple("m", BaseDimension::Length)),
+            )
+            .unwrap();
+        sheet
+            .set(
+                CellAddr::new("A", 2),
+                Cell::new(200.0, Unit::simple("m", BaseDimension::Length)),
+            )
+            .unwrap();
+        sheet
+            .set(
+                CellAddr::new("A", 3),
+                Cell::new(300.0, Unit::simple("m", BaseDimension::Length)),
+            )
+            .unwrap();
+
+        let (value, unit) = sheet.evaluate_formula("=STDEV(A1:A3)").unwrap();
+
+        // Standard deviation of [100, 200, 300] ≈ 100
+        if let CellValue::Number(num) = value {
+            assert!((num - 100.0).abs() < 1.0);
+        } else {
+            panic!("Expected numeric value");
+        }
+        assert_eq!(unit.canonical(), "m");
+    }
+
+    #[test]
+    fn test_var_function() {
         let sheet = Sheet::new();
 
-        // NOT(1) → 0.0
-        let (value, unit) = sheet.evaluate_formula("=NOT(1)").unwrap();
-        assert_eq!(value, CellValue::Number(0.0));
-        assert!(unit.is_dimensionless());
-    }
-
-    #[test]
-    fn test_not_function_false() {
-        let sheet = Sheet::new();
+        // Variance of [2, 4, 4, 4, 5, 5, 7, 9], sample variance (n-1) ≈ 4.571
+        let (value, _) = sheet
+            .evaluate_formula("=VAR(2, 4, 4, 4, 5, 5, 7, 9)")
+            .unwrap();
 
-        // NOT(0) → 1.0
-        let (value, unit) = sheet.evaluate_formula("=NOT(0)").unwrap();
-        assert_eq!(value, CellValue::Number(1.0));
-        assert!(unit.is_dimensionless());
+        // Variance should be approximately 4.571
+        if let CellValue::Number(num) = value {
+            assert!((num - 4.571).abs() < 0.01);
+        } else {
+            panic!("Expected numeric value");
+        }
     }
 
     #[test]
-    fn test_complex_logical_expression() {
+    fn test_var_squared_units() {
         let mut sheet = Sheet::new();
 
+        // Variance of meters should have m² units
         sheet
             .set(
                 CellAddr::new("A", 1),
-                Cell::new(10.0, Unit::simple("m", BaseDimension::Length)),
+                Cell::new(1.0, Unit::simple("m", BaseDimension::Length)),
             )
             .unwrap();
         sheet
             .set(
                 CellAddr::new("A", 2),
-                Cell::new(5.0, Unit::simple("m", BaseDimension::Length)),
+                Cell::new(2.0, Unit::simple("m", BaseDimension::Length)),
             )
             .unwrap();
         sheet
             .set(
                 CellAddr::new("A", 3),
-                Cell::new(15.0, Unit::simple("m", BaseDimension::Length)),
+                Cell::new(3.0, Unit::simple("m", BaseDimension::Length)),
             )
             .unwrap();
 
-        // AND(GT(A1, A2), LT(A1, A3)) → AND(10 > 5, 10 < 15) → AND(1, 1) → 1.0
-        let (value, _) = sheet
-            .evaluate_formula("=AND(GT(A1, A2), LT(A1, A3))")
-            .unwrap();
-        assert_eq!(value, CellValue::Number(1.0));
+        let (value, unit) = sheet.evaluate_formula("=VAR(A1:A3)").unwrap();
 
-        // OR(GT(A1, A3), LT(A2, A1)) → OR(10 > 15, 5 < 10) → OR(0, 1) → 1.0
-        let (value, _) = sheet
-            .evaluate_formula("=OR(GT(A1, A3), LT(A2, A1))")
-            .unwrap();
-        assert_eq!(value, CellValue::Number(1.0));
+        // Sample variance (n-1) of [1, 2, 3] = 1.0
+        if let CellValue::Number(num) = value {
+            assert!((num - 1.0).abs() < 0.01);
+        } else {
+            panic!("Expected numeric value");
+        }
 
-        // IF(AND(GT(A1, A2), LT(A1, A3)), A1, A3) → IF(1, 10m, 15m) → 10m
-        let (value, unit) = sheet
-            .evaluate_formula("=IF(AND(GT(A1, A2), LT(A1, A3)), A1, A3)")
-            .unwrap();
-        assert_eq!(value, CellValue::Number(10.0));
-        assert_eq!(unit.canonical(), "m");
+        // Unit should be m² (squared)
+        let canonical = unit.canonical();
+        assert!(
+            canonical.contains("m")
+                && (canonical.contains("²") || canonical.contains("^2") || canonical.contains("2"))
+        );
     }
 
     #[test]
-    fn test_nested_if() {
+    fn test_var_with_units() {
         let mut sheet = Sheet::new();
 
+        // Set up cells with units
         sheet
             .set(
                 CellAddr::new("A", 1),
-                Cell::new(10.0, Unit::dimensionless()),
+                Cell::new(10.0, Unit::simple("kg", BaseDimension::Mass)),
             )
             .unwrap();
-
-        // IF(GT(A1, 5), IF(GT(A1, 15), 100, 50), 0)
-        // A1=10 → GT(10, 5)=1 → GT(10, 15)=0 → 50
-        let (value, _) = sheet
-            .evaluate_formula("=IF(GT(A1, 5), IF(GT(A1, 15), 100, 50), 0)")
+        sheet
+            .set(
+                CellAddr::new("A", 2),
+                Cell::new(20.0, Unit::simple("kg", BaseDimension::Mass)),
+            )
             .unwrap();
-        assert_eq!(value, CellValue::Number(50.0));
+        sheet
+            .set(
+                CellAddr::new("A", 3),
+                Cell::new(30.0, Unit::simple("kg", BaseDimension::Mass)),
+            )
+            .unwrap();
+
+        let (value, unit) = sheet.evaluate_formula("=VAR(A1:A3)").unwrap();
+
+        // Sample variance (n-1) of [10, 20, 30] = 100.0
+        if let CellValue::Number(num) = value {
+            assert!((num - 100.0).abs() < 0.1);
+        } else {
+            panic!("Expected numeric value");
+        }
+
+        // Unit should be kg² (squared)
+        let canonical = unit.canonical();
+        assert!(canonical.contains("kg"));
     }
 
     #[test]
-    fn test_median_function() {
+    fn test_median_error_empty() {
         let sheet = Sheet::new();
 
-        // MEDIAN(1, 2, 3, 4, 5) = 3
-        let (value, unit) = sheet.evaluate_formula("=MEDIAN(1, 2, 3, 4, 5)").unwrap();
+        // MEDIAN with no arguments should fail
+        let result = sheet.evaluate_formula("=MEDIAN()");
+        assert!(result.is_err());
+    }
 
-        assert_eq!(value, CellValue::Number(3.0));
-        assert!(unit.is_dimensionless());
+    #[test]
+    fn test_stdev_error_insufficient_values() {
+        let sheet = Sheet::new();
+
+        // STDEV requires at least 2 values
+        let result = sheet.evaluate_formula("=STDEV(5)");
+        assert!(result.is_err());
     }
 
     #[test]
-    fn test_median_with_units() {
+    fn test_var_error_insufficient_values() {
+        let sheet = Sheet::new();
+
+        // VAR requires at least 2 values
+        let result = sheet.evaluate_formula("=VAR(5)");
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn test_median_incompatible_units() {
         let mut sheet = Sheet::new();
 
-        // Set up cells with units
+        // Mix meters and kilograms (incompatible)
         sheet
             .set(
                 CellAddr::new("A", 1),
@@ -3778,337 +4513,731 @@ mod tests {
         sheet
             .set(
                 CellAddr::new("A", 2),
-                Cell::new(200.0, Unit::simple("m", BaseDimension::Length)),
+                Cell::new(200.0, Unit::simple("kg", BaseDimension::Mass)),
             )
             .unwrap();
+
+        let result = sheet.evaluate_formula("=MEDIAN(A1:A2)");
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn test_error_values_propagate() {
+        let mut sheet = Sheet::new();
         sheet
             .set(
-                CellAddr::new("A", 3),
-                Cell::new(300.0, Unit::simple("m", BaseDimension::Length)),
+                CellAddr::new("A", 1),
+                Cell::new(10.0, Unit::simple("m", BaseDimension::Length)),
             )
             .unwrap();
-
-        // MEDIAN(A1:A3) = 200m
-        let (value, unit) = sheet.evaluate_formula("=MEDIAN(A1:A3)").unwrap();
-
-        assert_eq!(value, CellValue::Number(200.0));
-        assert_eq!(unit.canonical(), "m");
+        sheet
+            .set(CellAddr::new("A", 2), Cell::with_formula("=A1 / 0"))
+            .unwrap();
+        sheet
+            .set(CellAddr::new("A", 3), Cell::with_formula("=A2 + 1 m"))
+            .unwrap();
+        sheet.recalculate(&[CellAddr::new("A", 1)]).unwrap();
+
+        let error = |addr: CellAddr| sheet.get(&addr).unwrap().value().as_error().unwrap().kind;
+        assert_eq!(error(CellAddr::new("A", 2)), ErrorKind::DivZero);
+        assert_eq!(error(CellAddr::new("A", 3)), ErrorKind::DivZero);
+
+        // Errors pass through ranges and functions, and the first one wins
+        let kind = |formula: &str| {
+            sheet
+                .evaluate_formula(formula)
+                .unwrap_err()
+                .to_cell_error()
+                .kind
+        };
+        assert_eq!(kind("=SUM(A1:A3)"), ErrorKind::DivZero);
+        assert_eq!(kind("=MAX(A2, B1)"), ErrorKind::DivZero);
+        assert_eq!(kind("=A1 + 1 s"), ErrorKind::Unit);
+        assert_eq!(kind("=#REF! + A2"), ErrorKind::Ref);
+        assert_eq!(kind("=NOSUCHFUNCTION(1)"), ErrorKind::Name);
+        assert_eq!(kind("=NA()"), ErrorKind::NA);
     }
 
     #[test]
-    fn test_median_with_conversion() {
+    fn test_error_and_type_functions() {
         let mut sheet = Sheet::new();
-
-        // 1m, 200cm, 3m → should convert 200cm to 2m → median is 2m
         sheet
             .set(
                 CellAddr::new("A", 1),
-                Cell::new(1.0, Unit::simple("m", BaseDimension::Length)),
+                Cell::new(5.0, Unit::simple("km", BaseDimension::Length)),
             )
             .unwrap();
         sheet
-            .set(
-                CellAddr::new("A", 2),
-                Cell::new(200.0, Unit::simple("cm", BaseDimension::Length)),
-            )
+            .set(CellAddr::new("A", 2), Cell::with_text("label"))
             .unwrap();
         sheet
-            .set(
-                CellAddr::new("A", 3),
-                Cell::new(3.0, Unit::simple("m", BaseDimension::Length)),
-            )
+            .set(CellAddr::new("A", 3), Cell::with_formula("=A1 / 0"))
             .unwrap();
+        sheet
+            .set(CellAddr::new("A", 4), Cell::new(3.0, Unit::dimensionless()))
+            .unwrap();
+        sheet.recalculate(&[CellAddr::new("A", 1)]).unwrap();
+
+        let cases = [
+            ("=IFERROR(A3, 0)", CellValue::Number(0.0)),
+            ("=IFERROR(A4, 0)", CellValue::Number(3.0)),
+            ("=IFNA(NA(), 7)", CellValue::Number(7.0)),
+            ("=ISERROR(A3)", CellValue::Boolean(true)),
+            ("=ISERROR(A1)", CellValue::Boolean(false)),
+            ("=ISNUMBER(A1)", CellValue::Boolean(true)),
+            ("=ISNUMBER(A2)", CellValue::Boolean(false)),
+            ("=ISNUMBER(A3)", CellValue::Boolean(false)),
+            ("=ISTEXT(A2)", CellValue::Boolean(true)),
+            ("=ISTEXT(A1)", CellValue::Boolean(false)),
+            ("=ISBLANK(B1)", CellValue::Boolean(true)),
+            ("=ISBLANK(A2)", CellValue::Boolean(false)),
+            ("=ISUNIT(A1)", CellValue::Boolean(true)),
+            ("=ISUNIT(A4)", CellValue::Boolean(false)),
+            ("=ISUNIT(A1, \"Length\")", CellValue::Boolean(true)),
+            ("=ISUNIT(A1, \"Mass\")", CellValue::Boolean(false)),
+            ("=ISUNIT(A1, \"mi\")", CellValue::Boolean(true)),
+            ("=ISUNIT(A1, \"s\")", CellValue::Boolean(false)),
+        ];
+        for (formula, expected) in cases {
+            let (value, _) = sheet.evaluate_formula(formula).unwrap();
+            assert_eq!(value, expected, "{}", formula);
+        }
 
-        let (value, unit) = sheet.evaluate_formula("=MEDIAN(A1:A3)").unwrap();
-
+        // The fallback keeps its unit
+        let (value, unit) = sheet.evaluate_formula("=IFERROR(A3, 2 m)").unwrap();
         assert_eq!(value, CellValue::Number(2.0));
         assert_eq!(unit.canonical(), "m");
-    }
-
-    #[test]
-    fn test_median_even_count() {
-        let sheet = Sheet::new();
 
-        // MEDIAN(1, 2, 3, 4) = 2.5
-        let (value, _) = sheet.evaluate_formula("=MEDIAN(1, 2, 3, 4)").unwrap();
-
-        assert_eq!(value, CellValue::Number(2.5));
+        // IFNA only catches #N/A
+        let result = sheet.evaluate_formula("=IFNA(A3, 7)").unwrap_err();
+        assert_eq!(result.to_cell_error().kind, ErrorKind::DivZero);
     }
 
     #[test]
-    fn test_stdev_function() {
-        let sheet = Sheet::new();
-
-        // Test standard deviation with known values
-        // For [2, 4, 4, 4, 5, 5, 7, 9], sample stdev (n-1) ≈ 2.138
-        let (value, _) = sheet
-            .evaluate_formula("=STDEV(2, 4, 4, 4, 5, 5, 7, 9)")
+    fn test_date_arithmetic() {
+        let date = |y, m, d| {
+            chrono::NaiveDate::from_ymd_opt(y, m, d)
+                .unwrap()
+                .and_hms_opt(0, 0, 0)
+                .unwrap()
+        };
+        let mut sheet = Sheet::new();
+        sheet
+            .set(
+                CellAddr::new("A", 1),
+                Cell::with_datetime(date(2024, 1, 31)),
+            )
+            .unwrap();
+        sheet
+            .set(
+                CellAddr::new("A", 2),
+                Cell::with_datetime(date(2024, 3, 15)),
+            )
             .unwrap();
 
-        // Standard deviation should be approximately 2.138
-        if let CellValue::Number(num) = value {
-            assert!((num - 2.138).abs() < 0.01);
-        } else {
-            panic!("Expected numeric value");
+        // The difference of two dates is a number of days
+        let (value, unit) = sheet.evaluate_formula("=A2 - A1").unwrap();
+        assert_eq!(value, CellValue::Number(44.0));
+        assert_eq!(unit.canonical(), "day");
+
+        let cases = [
+            ("=A2 + 3 weeks", date(2024, 4, 5)),
+            ("=2 days + A2", date(2024, 3, 17)),
+            ("=A2 - 15", date(2024, 2, 29)),
+            ("=A1 + 1 month", date(2024, 2, 29)),
+            ("=A1 + 1 quarter", date(2024, 4, 30)),
+            (
+                "=A2 + 36 hr",
+                date(2024, 3, 16) + chrono::Duration::hours(12),
+            ),
+            ("=DATE(2024, 14, 1)", date(2025, 2, 1)),
+            ("=EDATE(A1, 1)", date(2024, 2, 29)),
+            ("=EDATE(A2, -1 year)", date(2023, 3, 15)),
+            ("=EOMONTH(A2, 0)", date(2024, 3, 31)),
+            ("=EOMONTH(A2, -1)", date(2024, 2, 29)),
+            ("=IF(A2 > A1, A2, A1)", date(2024, 3, 15)),
+        ];
+        for (formula, expected) in cases {
+            let (value, _) = sheet.evaluate_formula(formula).unwrap();
+            assert_eq!(value, CellValue::DateTime(expected), "{}", formula);
         }
+
+        // Dates only combine with time durations
+        assert!(sheet.evaluate_formula("=A1 + A2").is_err());
+        assert!(sheet.evaluate_formula("=A1 + 3 m").is_err());
+        assert!(sheet.evaluate_formula("=A1 * 2").is_err());
+        assert!(sheet.evaluate_formula("=A1 > 5").is_err());
+
+        // Joined onto text, a date shows as it is displayed
+        let (value, _) = sheet.evaluate_formula("=\"Due \" + A2").unwrap();
+        assert_eq!(value, CellValue::Text("Due 2024-03-15".to_string()));
     }
 
     #[test]
-    fn test_stdev_with_units() {
+    fn test_date_functions() {
+        let date = |y, m, d| {
+            chrono::NaiveDate::from_ymd_opt(y, m, d)
+                .unwrap()
+                .and_hms_opt(0, 0, 0)
+                .unwrap()
+        };
         let mut sheet = Sheet::new();
-
-        // Set up cells with units
         sheet
             .set(
                 CellAddr::new("A", 1),
-                Cell::new(100.0, Unit::simple("m", BaseDimension::Length)),
+                Cell::with_datetime(date(2023, 11, 20)),
             )
             .unwrap();
         sheet
             .set(
                 CellAddr::new("A", 2),
-                Cell::new(200.0, Unit::simple("m", BaseDimension::Length)),
+                Cell::with_datetime(date(2024, 3, 15)),
             )
             .unwrap();
+        // Holidays
         sheet
             .set(
-                CellAddr::new("A", 3),
-                Cell::new(300.0, Unit::simple("m", BaseDimension::Length)),
+                CellAddr::new("B", 1),
+                Cell::with_datetime(date(2023, 12, 25)),
             )
             .unwrap();
+        sheet
+            .set(CellAddr::new("B", 2), Cell::with_datetime(date(2024, 1, 1)))
+            .unwrap();
 
-        let (value, unit) = sheet.evaluate_formula("=STDEV(A1:A3)").unwrap();
+        let cases = [
+            ("=DATEDIF(A1, A2, \"Y\")", 0.0, "year"),
+            ("=DATEDIF(A1, A2, \"M\")", 3.0, "month"),
+            ("=DATEDIF(A1, A2, \"D\")", 116.0, "day"),
+            ("=DATEDIF(A1, A2, \"MD\")", 24.0, "day"),
+            ("=DATEDIF(\"2020-02-29\", A2, \"Y\")", 4.0, "year"),
+            ("=DATEDIF(\"2020-02-29\", A2, \"YM\")", 0.0, "month"),
+            ("=NETWORKDAYS(\"2024-03-11\", A2)", 5.0, "day"),
+            ("=NETWORKDAYS(A2, \"2024-03-11\")", -5.0, "day"),
+            ("=NETWORKDAYS(A1, A2, B1:B2)", 83.0, "day"),
+            ("=YEAR(A2)", 2024.0, ""),
+            ("=MONTH(A2)", 3.0, ""),
+            ("=DAY(A2)", 15.0, ""),
+        ];
+        for (formula, expected, unit) in cases {
+            let (value, result_unit) = sheet.evaluate_formula(formula).unwrap();
+            assert_eq!(value, CellValue::Number(expected), "{}", formula);
+            assert_eq!(result_unit.canonical(), unit, "{}", formula);
+        }
 
-        // Standard deviation of [100, 200, 300] ≈ 100
-        if let CellValue::Number(num) = value {
-            assert!((num - 100.0).abs() < 1.0);
-        } else {
-            panic!("Expected numeric value");
+        // Durations convert like any other time quantity
+        let (value, _) = sheet
+            .evaluate_formula("=CONVERT(DATEDIF(A1, A2, \"D\"), \"wk\")")
+            .unwrap();
+        assert!((value.as_number().unwrap() - 116.0 / 7.0).abs() < 1e-9);
+
+        let (value, _) = sheet.evaluate_formula("=TODAY()").unwrap();
+        assert!(value.is_datetime());
+
+        assert!(sheet.evaluate_formula("=DATEDIF(A2, A1, \"D\")").is_err());
+        assert!(sheet.evaluate_formula("=YEAR(5)").is_err());
+    }
+
+    #[test]
+    fn test_boolean_values() {
+        let mut sheet = Sheet::new();
+        sheet
+            .set(CellAddr::new("A", 1), Cell::new(5.0, Unit::dimensionless()))
+            .unwrap();
+        sheet
+            .set(CellAddr::new("A", 2), Cell::with_boolean(true))
+            .unwrap();
+        sheet
+            .set(CellAddr::new("A", 3), Cell::with_formula("=A1 > 3"))
+            .unwrap();
+        sheet.recalculate(&[CellAddr::new("A", 3)]).unwrap();
+
+        // Comparison results are stored as booleans
+        let stored = sheet.get(&CellAddr::new("A", 3)).unwrap();
+        assert_eq!(stored.as_boolean(), Some(true));
+        assert_eq!(stored.formatted(), "TRUE");
+
+        let cases = [
+            ("=TRUE + 1", CellValue::Number(2.0)),
+            ("=A2 * 10", CellValue::Number(10.0)),
+            ("=-FALSE", CellValue::Number(0.0)),
+            ("=SUM(TRUE, 1)", CellValue::Number(2.0)),
+            // Booleans inside ranges are skipped, as in Excel
+            ("=SUM(A1:A3)", CellValue::Number(5.0)),
+            ("=IF(A2, 10, 20)", CellValue::Number(10.0)),
+            ("=A2 = A3", CellValue::Boolean(true)),
+            ("=AND(A2, NOT FALSE)", CellValue::Boolean(true)),
+            ("=ISLOGICAL(A3)", CellValue::Boolean(true)),
+            ("=ISNUMBER(A2)", CellValue::Boolean(false)),
+        ];
+        for (formula, expected) in cases {
+            let (value, _) = sheet.evaluate_formula(formula).unwrap();
+            assert_eq!(value, expected, "{}", formula);
         }
-        assert_eq!(unit.canonical(), "m");
+
+        // Booleans only compare with booleans
+        assert!(sheet.evaluate_formula("=TRUE > 1").is_err());
+    }
+
+    #[test]
+    fn test_lookup_functions() {
+        let gb = Unit::simple("GB", BaseDimension::DigitalStorage);
+        let mb = Unit::simple("MB", BaseDimension::DigitalStorage);
+        let usd = Unit::simple("USD", BaseDimension::Currency);
+        let mut sheet = Sheet::new();
+
+        // Instance type, memory, price per hour, memory in MB
+        let rows = [
+            ("t3.small", 2.0, 0.02),
+            ("t3.medium", 4.0, 0.04),
+            ("t3.large", 8.0, 0.08),
+        ];
+        for (row, (name, memory, price)) in rows.into_iter().enumerate() {
+            let row = row + 1;
+            sheet
+                .set(CellAddr::new("A", row), Cell::with_text(name))
+                .unwrap();
+            sheet
+                .set(CellAddr::new("B", row), Cell::new(memory, gb.clone()))
+                .unwrap();
+            sheet
+                .set(CellAddr::new("C", row), Cell::new(price, usd.clone()))
+                .unwrap();
+            sheet
+                .set(
+                    CellAddr::new("D", row),
+                    Cell::new(memory * 1024.0, mb.clone()),
+                )
+                .unwrap();
+        }
+        // A horizontal table for HLOOKUP
+        for (col, key, value) in [("F", 1.0, "a"), ("G", 2.0, "b"), ("H", 3.0, "c")] {
+            sheet
+                .set(CellAddr::new(col, 1), Cell::new(key, Unit::dimensionless()))
+                .unwrap();
+            sheet
+                .set(CellAddr::new(col, 2), Cell::with_text(value))
+                .unwrap();
+        }
+
+        let cases = [
+            ("=VLOOKUP(\"t3.medium\", A1:C3, 3, FALSE)", 0.04, "USD"),
+            ("=VLOOKUP(\"T3.L*\", A1:C3, 2, FALSE)", 8.0, "GB"),
+            // Approximate lookups take the largest value not above the lookup value
+            ("=VLOOKUP(5 GB, B1:C3, 2)", 0.04, "USD"),
+            ("=XLOOKUP(5 GB, B1:B3, C1:C3, 0, 1)", 0.08, "USD"),
+            ("=XLOOKUP(5 GB, B1:B3, C1:C3, 0, -1)", 0.04, "USD"),
+            // Quantities match across units
+            ("=MATCH(8192 MB, B1:B3, 0)", 3.0, ""),
+            ("=VLOOKUP(4 GB, B1:C3, 2, FALSE)", 0.04, "USD"),
+            ("=INDEX(A1:C3, 2, 3)", 0.04, "USD"),
+            ("=INDEX(C1:C3, MATCH(\"t3.large\", A1:A3, 0))", 0.08, "USD"),
+            ("=MATCH(3, F1:H1)", 3.0, ""),
+        ];
+        for (formula, expected, unit) in cases {
+            let (value, result_unit) = sheet.evaluate_formula(formula).unwrap();
+            assert_eq!(value, CellValue::Number(expected), "{}", formula);
+            assert_eq!(result_unit.canonical(), unit, "{}", formula);
+        }
+
+        let text = |formula: &str| sheet.evaluate_formula(formula).unwrap().0;
+        assert_eq!(
+            text("=XLOOKUP(4 GB, D1:D3, A1:A3)"),
+            CellValue::Text("t3.medium".to_string())
+        );
+        assert_eq!(
+            text("=HLOOKUP(2, F1:H2, 2, FALSE)"),
+            CellValue::Text("b".to_string())
+        );
+        assert_eq!(
+            text("=XLOOKUP(\"t3.nano\", A1:A3, C1:C3, \"none\")"),
+            CellValue::Text("none".to_string())
+        );
+
+        let kind = |formula: &str| {
+            sheet
+                .evaluate_formula(formula)
+                .unwrap_err()
+                .to_cell_error()
+                .kind
+        };
+        assert_eq!(
+            kind("=VLOOKUP(\"t3.nano\", A1:C3, 2, FALSE)"),
+            ErrorKind::NA
+        );
+        assert_eq!(kind("=VLOOKUP(1 GB, B1:C3, 2)"), ErrorKind::NA);
+        // Incompatible units never match
+        assert_eq!(kind("=MATCH(4 USD, B1:B3, 0)"), ErrorKind::NA);
+        assert_eq!(kind("=INDEX(A1:C3, 4, 1)"), ErrorKind::Ref);
+        assert_eq!(
+            kind("=VLOOKUP(\"t3.small\", A1:C3, 5, FALSE)"),
+            ErrorKind::Ref
+        );
+        assert_eq!(kind("=XLOOKUP(1, A1:A3, C1:C2)"), ErrorKind::Value);
     }
 
     #[test]
-    fn test_var_function() {
-        let sheet = Sheet::new();
+    fn test_conditional_aggregates() {
+        let unit = |symbol: &str, dimension| Unit::simple(symbol, dimension);
+        let mut sheet = Sheet::new();
 
-        // Variance of [2, 4, 4, 4, 5, 5, 7, 9], sample variance (n-1) ≈ 4.571
-        let (value, _) = sheet
-            .evaluate_formula("=VAR(2, 4, 4, 4, 5, 5, 7, 9)")
-            .unwrap();
+        // Region, memory (in MB and GB), cost, runtime (in min and hr)
+        let rows = [
+            ("east", 8192.0, "MB", 100.0, 90.0, "min"),
+            ("west", 16.0, "GB", 200.0, 3.0, "hr"),
+            ("east", 32.0, "GB", 300.0, 1.0, "hr"),
+            ("north", 4.0, "GB", 50.0, 30.0, "min"),
+        ];
+        for (row, (region, memory, memory_unit, cost, runtime, runtime_unit)) in
+            rows.into_iter().enumerate()
+        {
+            let row = row + 1;
+            let cells = [
+                ("A", Cell::with_text(region)),
+                (
+                    "B",
+                    Cell::new(memory, unit(memory_unit, BaseDimension::DigitalStorage)),
+                ),
+                ("C", Cell::new(cost, unit("USD", BaseDimension::Currency))),
+                (
+                    "D",
+                    Cell::new(runtime, unit(runtime_unit, BaseDimension::Time)),
+                ),
+            ];
+            for (col, cell) in cells {
+                sheet.set(CellAddr::new(col, row), cell).unwrap();
+            }
+        }
 
-        // Variance should be approximately 4.571
-        if let CellValue::Number(num) = value {
-            assert!((num - 4.571).abs() < 0.01);
-        } else {
-            panic!("Expected numeric value");
+        let cases = [
+            ("=SUMIF(A1:A4, \"east\", C1:C4)", 400.0, "USD"),
+            ("=SUMIF(A1:A4, \"<>east\", C1:C4)", 250.0, "USD"),
+            ("=AVERAGEIF(A1:A4, \"E*\", C1:C4)", 200.0, "USD"),
+            // Criteria with units match across units
+            ("=COUNTIF(B1:B4, \">=16 GB\")", 2.0, ""),
+            ("=COUNTIF(B1:B4, \">8000 MB\")", 3.0, ""),
+            ("=COUNTIF(B1:B4, 16384 MB)", 1.0, ""),
+            ("=COUNTIF(D1:D4, \"<2 hr\")", 3.0, ""),
+            ("=COUNTIF(B1:B4, \">5 USD\")", 0.0, ""),
+            ("=COUNTIF(A1:A5, \"\")", 1.0, ""),
+            (
+                "=SUMIFS(C1:C4, A1:A4, \"east\", B1:B4, \">=16 GB\")",
+                300.0,
+                "USD",
+            ),
+            ("=COUNTIFS(A1:A4, \"east\", D1:D4, \">1 hr\")", 1.0, ""),
+            ("=MAXIFS(C1:C4, B1:B4, \"<16 GB\")", 100.0, "USD"),
+            ("=MINIFS(C1:C4, A1:A4, \"east\")", 100.0, "USD"),
+            // Results are in the unit of the first value, as with SUM
+            ("=SUMIF(B1:B4, \">=16 GB\")", 48.0, "GB"),
+            ("=SUMIF(D1:D4, \"<2 hr\")", 180.0, "min"),
+            ("=AVERAGEIFS(D1:D4, A1:A4, \"east\")", 75.0, "min"),
+        ];
+        for (formula, expected, unit) in cases {
+            let (value, result_unit) = sheet.evaluate_formula(formula).unwrap();
+            assert_eq!(value, CellValue::Number(expected), "{}", formula);
+            assert_eq!(result_unit.canonical(), unit, "{}", formula);
         }
+
+        let kind = |formula: &str| {
+            sheet
+                .evaluate_formula(formula)
+                .unwrap_err()
+                .to_cell_error()
+                .kind
+        };
+        assert_eq!(
+            kind("=AVERAGEIF(A1:A4, \"south\", C1:C4)"),
+            ErrorKind::DivZero
+        );
+        assert_eq!(kind("=SUMIFS(C1:C3, A1:A4, \"east\")"), ErrorKind::Value);
+        assert_eq!(kind("=COUNTIFS(A1:A4, \"east\", B1:B4)"), ErrorKind::Value);
     }
 
     #[test]
-    fn test_var_squared_units() {
+    fn test_sumproduct_and_array_arithmetic() {
+        let usd = Unit::simple("USD", BaseDimension::Currency);
+        let kg = Unit::simple("kg", BaseDimension::Mass);
         let mut sheet = Sheet::new();
 
-        // Variance of meters should have m² units
-        sheet
-            .set(
-                CellAddr::new("A", 1),
-                Cell::new(1.0, Unit::simple("m", BaseDimension::Length)),
-            )
-            .unwrap();
+        // Unit price, quantity, weight
+        for (row, (price, quantity, weight)) in [(2.0, 10.0, 5.0), (3.0, 4.0, 8.0), (5.0, 1.0, 3.0)]
+            .into_iter()
+            .enumerate()
+        {
+            let row = row + 1;
+            let cells = [
+                ("A", Cell::new(price, usd.clone())),
+                ("B", Cell::new(quantity, Unit::dimensionless())),
+                ("C", Cell::new(weight, kg.clone())),
+            ];
+            for (col, cell) in cells {
+                sheet.set(CellAddr::new(col, row), cell).unwrap();
+            }
+        }
+        // A note in the quantity column and a blank price
         sheet
-            .set(
-                CellAddr::new("A", 2),
-                Cell::new(2.0, Unit::simple("m", BaseDimension::Length)),
-            )
+            .set(CellAddr::new("B", 4), Cell::with_text("n/a"))
             .unwrap();
         sheet
-            .set(
-                CellAddr::new("A", 3),
-                Cell::new(3.0, Unit::simple("m", BaseDimension::Length)),
-            )
+            .set(CellAddr::new("C", 4), Cell::new(7.0, kg.clone()))
             .unwrap();
 
-        let (value, unit) = sheet.evaluate_formula("=VAR(A1:A3)").unwrap();
-
-        // Sample variance (n-1) of [1, 2, 3] = 1.0
-        if let CellValue::Number(num) = value {
-            assert!((num - 1.0).abs() < 0.01);
-        } else {
-            panic!("Expected numeric value");
+        let cases = [
+            ("=SUMPRODUCT(A1:A3, B1:B3)", 37.0, "USD"),
+            ("=SUMPRODUCT(A1:A3 * B1:B3)", 37.0, "USD"),
+            ("=SUM(A1:A3 * B1:B3)", 37.0, "USD"),
+            ("=SUMPRODUCT(A1:A3 * 2)", 20.0, "USD"),
+            ("=SUMPRODUCT(C1:C3 / B1:B3)", 5.5, "kg"),
+            ("=MAX(A1:A3 * B1:B3)", 20.0, "USD"),
+            // Text counts as 0 and blank cells add nothing
+            ("=SUMPRODUCT(A1:A4, B1:B4)", 37.0, "USD"),
+            ("=SUMPRODUCT(B1:B4, C1:C4)", 85.0, "kg"),
+        ];
+        for (formula, expected, unit) in cases {
+            let (value, result_unit) = sheet.evaluate_formula(formula).unwrap();
+            assert_eq!(value, CellValue::Number(expected), "{}", formula);
+            assert_eq!(result_unit.canonical(), unit, "{}", formula);
         }
 
-        // Unit should be m² (squared)
-        let canonical = unit.canonical();
-        assert!(
-            canonical.contains("m")
-                && (canonical.contains("²") || canonical.contains("^2") || canonical.contains("2"))
-        );
+        // Rows whose products have incompatible units cannot be added
+        sheet
+            .set(CellAddr::new("A", 3), Cell::new(5.0, kg.clone()))
+            .unwrap();
+        let error = sheet
+            .evaluate_formula("=SUMPRODUCT(A1:A3, B1:B3)")
+            .unwrap_err()
+            .to_cell_error();
+        assert_eq!(error.kind, ErrorKind::Unit);
+        assert!(error.message.contains("row 3"), "{}", error.message);
+
+        let kind = |formula: &str| {
+            sheet
+                .evaluate_formula(formula)
+                .unwrap_err()
+                .to_cell_error()
+                .kind
+        };
+        assert_eq!(kind("=SUMPRODUCT(A1:A3, B1:B2)"), ErrorKind::Value);
+        assert_eq!(kind("=SUM(A1:A3 * B1:B2)"), ErrorKind::Value);
+        assert_eq!(kind("=SUMPRODUCT(A1:A3 / (B1:B3 * 0))"), ErrorKind::DivZero);
     }
 
     #[test]
-    fn test_var_with_units() {
+    fn test_dynamic_arrays() {
+        let m = Unit::simple("m", BaseDimension::Length);
+        let addr = |s: &str| CellAddr::from_string(s).unwrap();
+        let value = |sheet: &Sheet, s: &str| sheet.get(&addr(s)).map(|cell| cell.value().clone());
         let mut sheet = Sheet::new();
+        for (row, length) in [3.0, 1.0, 2.0].into_iter().enumerate() {
+            sheet
+                .set(CellAddr::new("A", row + 1), Cell::new(length, m.clone()))
+                .unwrap();
+        }
 
-        // Set up cells with units
+        // SORT spills down from the formula's cell
         sheet
-            .set(
-                CellAddr::new("A", 1),
-                Cell::new(10.0, Unit::simple("kg", BaseDimension::Mass)),
-            )
+            .set(addr("C1"), Cell::with_formula("=SORT(A1:A3)"))
+            .unwrap();
+        sheet.recalculate(&[addr("C1")]).unwrap();
+        assert_eq!(value(&sheet, "C1"), Some(CellValue::Number(1.0)));
+        assert_eq!(value(&sheet, "C2"), Some(CellValue::Number(2.0)));
+        assert_eq!(value(&sheet, "C3"), Some(CellValue::Number(3.0)));
+        assert_eq!(
+            sheet.get(&addr("C3")).unwrap().storage_unit().canonical(),
+            "m"
+        );
+        assert_eq!(
+            sheet.spill_range(&addr("C1")),
+            Some(CellRange::new(&addr("C1"), &addr("C3")))
+        );
+        assert_eq!(sheet.spill_anchor(&addr("C2")), Some(&addr("C1")));
+
+        // Spilled cells can be used one by one or all together (C1#), but not edited
+        sheet
+            .set(addr("D1"), Cell::with_formula("=SUM(C1#)"))
             .unwrap();
         sheet
-            .set(
-                CellAddr::new("A", 2),
-                Cell::new(20.0, Unit::simple("kg", BaseDimension::Mass)),
-            )
+            .set(addr("D2"), Cell::with_formula("=C3 * 2"))
             .unwrap();
+        sheet.recalculate(&[addr("D1"), addr("D2")]).unwrap();
+        assert_eq!(value(&sheet, "D1"), Some(CellValue::Number(6.0)));
+        assert_eq!(value(&sheet, "D2"), Some(CellValue::Number(6.0)));
+        assert!(matches!(
+            sheet.set(addr("C2"), Cell::new(5.0, m.clone())),
+            Err(SheetError::SpilledCell(..))
+        ));
+
+        // A change to the source sorts again and updates what uses the spilled cells
+        sheet.set(addr("A4"), Cell::new(0.5, m.clone())).unwrap();
         sheet
-            .set(
-                CellAddr::new("A", 3),
-                Cell::new(30.0, Unit::simple("kg", BaseDimension::Mass)),
-            )
+            .set(addr("C1"), Cell::with_formula("=SORT(A1:A4)"))
             .unwrap();
+        sheet.recalculate(&[addr("C1")]).unwrap();
+        assert_eq!(value(&sheet, "C4"), Some(CellValue::Number(3.0)));
+        assert_eq!(value(&sheet, "D1"), Some(CellValue::Number(6.5)));
+        assert_eq!(value(&sheet, "D2"), Some(CellValue::Number(4.0)));
 
-        let (value, unit) = sheet.evaluate_formula("=VAR(A1:A3)").unwrap();
-
-        // Sample variance (n-1) of [10, 20, 30] = 100.0
-        if let CellValue::Number(num) = value {
-            assert!((num - 100.0).abs() < 0.1);
-        } else {
-            panic!("Expected numeric value");
+        // A spill that would cover other cells is #SPILL! until they are cleared
+        sheet
+            .set(addr("F2"), Cell::with_text("in the way"))
+            .unwrap();
+        sheet
+            .set(addr("F1"), Cell::with_formula("=SEQUENCE(3)"))
+            .unwrap();
+        sheet.recalculate(&[addr("F1")]).unwrap();
+        match value(&sheet, "F1") {
+            Some(CellValue::Error(e)) => assert_eq!(e.kind, ErrorKind::Spill),
+            other => panic!("Expected #SPILL!, got {:?}", other),
         }
+        assert_eq!(value(&sheet, "F3"), None);
 
-        // Unit should be kg² (squared)
-        let canonical = unit.canonical();
-        assert!(canonical.contains("kg"));
-    }
-
-    #[test]
-    fn test_median_error_empty() {
-        let sheet = Sheet::new();
+        sheet.set(addr("F2"), Cell::empty()).unwrap();
+        sheet.recalculate(&[addr("F2")]).unwrap();
+        assert_eq!(value(&sheet, "F1"), Some(CellValue::Number(1.0)));
+        assert_eq!(value(&sheet, "F3"), Some(CellValue::Number(3.0)));
 
-        // MEDIAN with no arguments should fail
-        let result = sheet.evaluate_formula("=MEDIAN()");
-        assert!(result.is_err());
+        // Replacing the formula removes its spilled values
+        sheet
+            .set(addr("F1"), Cell::new(7.0, Unit::dimensionless()))
+            .unwrap();
+        sheet.recalculate(&[addr("F1")]).unwrap();
+        assert_eq!(value(&sheet, "F2"), None);
+        assert_eq!(sheet.spill_range(&addr("F1")), None);
     }
 
     #[test]
-    fn test_stdev_error_insufficient_values() {
-        let sheet = Sheet::new();
+    fn test_statistics_functions() {
+        let ms = Unit::simple("ms", BaseDimension::Time);
+        let s = Unit::simple("s", BaseDimension::Time);
+        let day = Unit::simple("day", BaseDimension::Time);
+        let gb = Unit::simple("GB", BaseDimension::DigitalStorage);
+        let mut sheet = Sheet::new();
 
-        // STDEV requires at least 2 values
-        let result = sheet.evaluate_formula("=STDEV(5)");
-        assert!(result.is_err());
-    }
+        // Latencies in ms and s (80 to 200 ms), and storage used each day
+        let latencies = [
+            Cell::new(120.0, ms.clone()),
+            Cell::new(0.2, s.clone()),
+            Cell::new(80.0, ms.clone()),
+            Cell::new(150.0, ms.clone()),
+            Cell::new(0.1, s.clone()),
+        ];
+        for (row, latency) in latencies.into_iter().enumerate() {
+            let row = row + 1;
+            sheet.set(CellAddr::new("A", row), latency).unwrap();
+            sheet
+                .set(CellAddr::new("B", row), Cell::new(row as f64, day.clone()))
+                .unwrap();
+            sheet
+                .set(
+                    CellAddr::new("C", row),
+                    Cell::new(8.0 + 2.0 * row as f64, gb.clone()),
+                )
+                .unwrap();
+        }
 
-    #[test]
-    fn test_var_error_insufficient_values() {
-        let sheet = Sheet::new();
+        let cases = [
+            ("=PERCENTILE(A1:A5, 0.9)", 180.0, "ms"),
+            ("=PERCENTILE(A1:A5, 50%)", 120.0, "ms"),
+            ("=QUARTILE(A1:A5, 1)", 100.0, "ms"),
+            ("=QUARTILE(A1:A5, 4)", 200.0, "ms"),
+            ("=MODE(A1:A5, 100 ms)", 100.0, "ms"),
+            ("=SLOPE(C1:C5, B1:B5)", 2.0, "GB/day"),
+            ("=INTERCEPT(C1:C5, B1:B5)", 8.0, "GB"),
+            ("=CORREL(C1:C5, B1:B5)", 1.0, ""),
+            ("=FORECAST(10 day, C1:C5, B1:B5)", 28.0, "GB"),
+            ("=FORECAST(2 wk, C1:C5, B1:B5)", 36.0, "GB"),
+        ];
+        for (formula, expected, unit) in cases {
+            let (value, result_unit) = sheet.evaluate_formula(formula).unwrap();
+            let CellValue::Number(value) = value else {
+                panic!("{}: expected a number, got {:?}", formula, value);
+            };
+            assert!((value - expected).abs() < 1e-9, "{}: {}", formula, value);
+            assert_eq!(result_unit.canonical(), unit, "{}", formula);
+        }
 
-        // VAR requires at least 2 values
-        let result = sheet.evaluate_formula("=VAR(5)");
-        assert!(result.is_err());
+        // Out-of-range arguments and data without repeats or spread
+        for formula in [
+            "=PERCENTILE(A1:A5, 1.5)",
+            "=QUARTILE(A1:A5, 5)",
+            "=MODE(A1:A5)",
+            "=SLOPE(C1:C5, C1:C1)",
+            "=CORREL(B1:B5, 1 day)",
+        ] {
+            assert!(sheet.evaluate_formula(formula).is_err(), "{}", formula);
+        }
     }
 
     #[test]
-    fn test_median_incompatible_units() {
+    fn test_unit_functions() {
         let mut sheet = Sheet::new();
-
-        // Mix meters and kilograms (incompatible)
         sheet
             .set(
                 CellAddr::new("A", 1),
-                Cell::new(100.0, Unit::simple("m", BaseDimension::Length)),
+                Cell::new(3.0, Unit::simple("m", BaseDimension::Length)),
             )
             .unwrap();
         sheet
             .set(
                 CellAddr::new("A", 2),
-                Cell::new(200.0, Unit::simple("kg", BaseDimension::Mass)),
+                Cell::new(2.0, Unit::simple("ft", BaseDimension::Length)),
             )
             .unwrap();
+        sheet
+            .set(CellAddr::new("A", 3), Cell::new(5.0, Unit::dimensionless()))
+            .unwrap();
+        sheet
+            .set(CellAddr::new("A", 4), Cell::with_formula("=A1 / 2 s"))
+            .unwrap();
+        sheet.recalculate(&[CellAddr::new("A", 1)]).unwrap();
+
+        let text = |value: &str| CellValue::Text(value.to_string());
+        let cases = [
+            ("=UNIT(A1)", text("m")),
+            ("=UNIT(A3)", text("")),
+            ("=UNIT(A4)", text("m/s")),
+            ("=DIMENSION(A1)", text("Length")),
+            ("=DIMENSION(A3)", text("Dimensionless")),
+            ("=DIMENSION(A4)", text("Length/Time")),
+            ("=DIMENSION(\"USD/month\")", text("Currency/Time")),
+            ("=DIMENSION(A1 * A2)", text("Length^2")),
+            ("=VALUE_IN(A2, \"in\")", CellValue::Number(24.0)),
+            ("=VALUE_IN(A1, 1 cm)", CellValue::Number(300.0)),
+            ("=STRIPUNIT(A2)", CellValue::Number(2.0)),
+            ("=ISCOMPATIBLE(A1, A2)", CellValue::Boolean(true)),
+            ("=ISCOMPATIBLE(A1, A4)", CellValue::Boolean(false)),
+            ("=ISCOMPATIBLE(A4, \"km/h\")", CellValue::Boolean(true)),
+        ];
+        for (formula, expected) in cases {
+            let (value, unit) = sheet.evaluate_formula(formula).unwrap();
+            assert_eq!(value, expected, "{}", formula);
+            assert!(unit.is_dimensionless(), "{}", formula);
+        }
 
-        let result = sheet.evaluate_formula("=MEDIAN(A1:A2)");
-        assert!(result.is_err());
-    }
-}
-
-/// Convert compound unit values for formula evaluation (e.g., $/quarter -> $/year)
-/// This is similar to the convert_compound_unit in commands/workbook.rs but
-/// adapted for use in formula evaluation
-fn convert_compound_unit_for_formula(
-    value: f64,
-    from_unit: &str,
-    to_unit: &str,
-    library: &UnitLibrary,
-) -> Option<f64> {
-    // Handle division (e.g., $/quarter -> $/year)
-    if let (Some(from_pos), Some(to_pos)) = (from_unit.find('/'), to_unit.find('/')) {
-        let from_left = &from_unit[..from_pos];
-        let from_right = &from_unit[from_pos + 1..];
-        let to_left = &to_unit[..to_pos];
-        let to_right = &to_unit[to_pos + 1..];
-
-        // Convert numerator (e.g., $ -> $, both are Currency)
-        let factor_left = if from_left == to_left {
-            1.0
-        } else {
-            library.convert(1.0, from_left, to_left)?
+        // WITHUNIT gives a plain number a unit, so it takes part in conversions
+        let (value, unit) = sheet.evaluate_formula("=WITHUNIT(A3, \"kg\")").unwrap();
+        assert_eq!(value, CellValue::Number(5.0));
+        assert_eq!(unit.canonical(), "kg");
+        let (value, unit) = sheet
+            .evaluate_formula("=WITHUNIT(STRIPUNIT(A1), UNIT(A1)) + A2")
+            .unwrap();
+        let CellValue::Number(value) = value else {
+            panic!("Expected a number, got {:?}", value);
         };
+        assert!((value - 3.6096).abs() < 1e-9, "{}", value);
+        assert_eq!(unit.canonical(), "m");
 
-        // Convert denominator (e.g., quarter -> year)
-        let factor_right = library.convert(1.0, from_right, to_right)?;
-
-        // For division, divide the factors
-        // Example: $/quarter -> $/year
-        // factor_left = 1.0 ($ -> $)
-        // factor_right = 0.25 (quarter -> year, since 1 quarter = 0.25 years)
-        // combined_factor = 1.0 / 0.25 = 4.0... wait, that's wrong
-        //
-        // Actually, let me think about this:
-        // 30000 $/quarter means $30000 per quarter
-        // To convert to $/year: how many $ per year?
-        // If you earn $30000 per quarter, you earn $30000 * 4 per year = $120000/year
-        //
-        // So the conversion is: value * (quarters per year) = value * 4
-        // But factor_right is quarter->year = 0.25 (1 quarter = 0.25 years)
-        // So we need: value / factor_right = value / 0.25 = value * 4
-        //
-        // Let me reconsider:
-        // $/quarter -> $/year means ($ / quarter) -> ($ / year)
-        // = $ * (1 / quarter) -> $ * (1 / year)
-        // = $ * (year / quarter)
-        // year/quarter = 1/0.25 = 4
-        //
-        // So: numerator_factor / denominator_factor
-        let combined_factor = factor_left / factor_right;
-        return Some(value * combined_factor);
-    }
-
-    // Handle power notation (e.g., ft^2 -> m^2)
-    if let (Some(from_pos), Some(to_pos)) = (from_unit.find('^'), to_unit.find('^')) {
-        let from_base = &from_unit[..from_pos];
-        let from_power_str = &from_unit[from_pos + 1..];
-        let to_base = &to_unit[..to_pos];
-        let to_power_str = &to_unit[to_pos + 1..];
-
-        // Parse the power
-        if let (Ok(from_power), Ok(to_power)) =
-            (from_power_str.parse::<i32>(), to_power_str.parse::<i32>())
-        {
-            if from_power == to_power {
-                // Get conversion factor for base unit
-                if let Some(base_factor) = library.convert(1.0, from_base, to_base) {
-                    // Raise to the power
-                    let combined_factor = base_factor.powi(from_power);
-                    return Some(value * combined_factor);
-                }
-            }
+        for formula in [
+            "=WITHUNIT(A1, \"kg\")",
+            "=WITHUNIT(A3, \"furlongs per fortnight\")",
+            "=VALUE_IN(A1, \"kg\")",
+            "=UNIT(\"label\")",
+            "=STRIPUNIT(\"label\")",
+        ] {
+            assert!(sheet.evaluate_formula(formula).is_err(), "{}", formula);
         }
     }
-
-    None
 }
 
 /// Convert column letter to number (A=1, B=2, Z=26, AA=27, etc.)
@@ -4147,7 +5276,12 @@ fn col_num_to_letter(mut num: usize) -> Result<String, String> {
 /// Shift columns in an expression
 fn shift_expr_columns(expr: &Expr, from_col: usize, offset: isize) -> Expr {
     match expr {
-        Expr::CellRef { col, row } => {
+        Expr::CellRef {
+            col,
+            row,
+            col_absolute,
+            row_absolute,
+        } => {
             if let Ok(col_num) = col_letter_to_num(col) {
                 if col_num >= from_col {
                     let new_col_num = (col_num as isize + offset) as usize;
@@ -4155,6 +5289,8 @@ fn shift_expr_columns(expr: &Expr, from_col: usize, offset: isize) -> Expr {
                         return Expr::CellRef {
                             col: new_col,
                             row: *row,
+                            col_absolute: *col_absolute,
+                            row_absolute: *row_absolute,
                         };
                     }
                 }
@@ -4165,6 +5301,19 @@ fn shift_expr_columns(expr: &Expr, from_col: usize, offset: isize) -> Expr {
             start: Box::new(shift_expr_columns(start, from_col, offset)),
             end: Box::new(shift_expr_columns(end, from_col, offset)),
         },
+        Expr::ColumnRange { start, end } => {
+            let shift = |col: &String| match col_letter_to_num(col) {
+                Ok(col_num) if col_num >= from_col => {
+                    col_num_to_letter((col_num as isize + offset) as usize)
+                        .unwrap_or_else(|_| col.clone())
+                }
+                _ => col.clone(),
+            };
+            Expr::ColumnRange {
+                start: shift(start),
+                end: shift(end),
+            }
+        }
         Expr::Add(left, right) => Expr::Add(
             Box::new(shift_expr_columns(left, from_col, offset)),
             Box::new(shift_expr_columns(right, from_col, offset)),
@@ -4181,7 +5330,12 @@ fn shift_expr_columns(expr: &Expr, from_col: usize, offset: isize) -> Expr {
             Box::new(shift_expr_columns(left, from_col, offset)),
             Box::new(shift_expr_columns(right, from_col, offset)),
         ),
+        Expr::Power(left, right) => Expr::Power(
+            Box::new(shift_expr_columns(left, from_col, offset)),
+            Box::new(shift_expr_columns(right, from_col, offset)),
+        ),
         Expr::Negate(inner) => Expr::Negate(Box::new(shift_expr_columns(inner, from_col, offset))),
+        Expr::SpillRef(anchor) => Expr::spill_ref(shift_expr_columns(anchor, from_col, offset)),
         Expr::Function { name, args } => Expr::Function {
             name: name.clone(),
             args: args
@@ -4221,6 +5375,10 @@ fn shift_expr_columns(expr: &Expr, from_col: usize, offset: isize) -> Expr {
             Box::new(shift_expr_columns(left, from_col, offset)),
             Box::new(shift_expr_columns(right, from_col, offset)),
         ),
+        Expr::Concat(left, right) => Expr::Concat(
+            Box::new(shift_expr_columns(left, from_col, offset)),
+            Box::new(shift_expr_columns(right, from_col, offset)),
+        ),
         Expr::Not(inner) => Expr::Not(Box::new(shift_expr_columns(inner, from_col, offset))),
         _ => expr.clone(),
     }
@@ -4229,12 +5387,19 @@ fn shift_expr_columns(expr: &Expr, from_col: usize, offset: isize) -> Expr {
 /// Shift rows in an expression
 fn shift_expr_rows(expr: &Expr, from_row: usize, offset: isize) -> Expr {
     match expr {
-        Expr::CellRef { col, row } => {
+        Expr::CellRef {
+            col,
+            row,
+            col_absolute,
+            row_absolute,
+        } => {
             if *row >= from_row {
                 let new_row = (*row as isize + offset) as usize;
                 return Expr::CellRef {
                     col: col.clone(),
                     row: new_row,
+                    col_absolute: *col_absolute,
+                    row_absolute: *row_absolute,
                 };
             }
             expr.clone()
@@ -4243,6 +5408,19 @@ fn shift_expr_rows(expr: &Expr, from_row: usize, offset: isize) -> Expr {
             start: Box::new(shift_expr_rows(start, from_row, offset)),
             end: Box::new(shift_expr_rows(end, from_row, offset)),
         },
+        Expr::RowRange { start, end } => {
+            let shift = |row: usize| {
+                if row >= from_row {
+                    (row as isize + offset) as usize
+                } else {
+                    row
+                }
+            };
+            Expr::RowRange {
+                start: shift(*start),
+                end: shift(*end),
+            }
+        }
         Expr::Add(left, right) => Expr::Add(
             Box::new(shift_expr_rows(left, from_row, offset)),
             Box::new(shift_expr_rows(right, from_row, offset)),
@@ -4259,7 +5437,12 @@ fn shift_expr_rows(expr: &Expr, from_row: usize, offset: isize) -> Expr {
             Box::new(shift_expr_rows(left, from_row, offset)),
             Box::new(shift_expr_rows(right, from_row, offset)),
         ),
+        Expr::Power(left, right) => Expr::Power(
+            Box::new(shift_expr_rows(left, from_row, offset)),
+            Box::new(shift_expr_rows(right, from_row, offset)),
+        ),
         Expr::Negate(inner) => Expr::Negate(Box::new(shift_expr_rows(inner, from_row, offset))),
+        Expr::SpillRef(anchor) => Expr::spill_ref(shift_expr_rows(anchor, from_row, offset)),
         Expr::Function { name, args } => Expr::Function {
             name: name.clone(),
             args: args
@@ -4299,6 +5482,10 @@ fn shift_expr_rows(expr: &Expr, from_row: usize, offset: isize) -> Expr {
             Box::new(shift_expr_rows(left, from_row, offset)),
             Box::new(shift_expr_rows(right, from_row, offset)),
         ),
+        Expr::Concat(left, right) => Expr::Concat(
+            Box::new(shift_expr_rows(left, from_row, offset)),
+            Box::new(shift_expr_rows(right, from_row, offset)),
+        ),
         Expr::Not(inner) => Expr::Not(Box::new(shift_expr_rows(inner, from_row, offset))),
         _ => expr.clone(),
     }
@@ -4307,17 +5494,24 @@ fn shift_expr_rows(expr: &Expr, from_row: usize, offset: isize) -> Expr {
 /// Delete a column from an expression (mark deleted refs as #REF!)
 fn delete_column_in_expr(expr: &Expr, deleted_col: usize) -> Expr {
     match expr {
-        Expr::CellRef { col, row } => {
+        Expr::CellRef {
+            col,
+            row,
+            col_absolute,
+            row_absolute,
+        } => {
             if let Ok(col_num) = col_letter_to_num(col) {
                 if col_num == deleted_col {
                     // Reference to deleted column becomes #REF!
-                    return Expr::String("#REF!".to_string());
+                    return Expr::Error(ErrorKind::Ref);
                 } else if col_num > deleted_col {
                     // Shift left
                     if let Ok(new_col) = col_num_to_letter(col_num - 1) {
                         return Expr::CellRef {
                             col: new_col,
                             row: *row,
+                            col_absolute: *col_absolute,
+                            row_absolute: *row_absolute,
                         };
                     }
                 }
@@ -4328,6 +5522,29 @@ fn delete_column_in_expr(expr: &Expr, deleted_col: usize) -> Expr {
             start: Box::new(delete_column_in_expr(start, deleted_col)),
             end: Box::new(delete_column_in_expr(end, deleted_col)),
         },
+        Expr::ColumnRange { start, end } => {
+            let range = CellRange::columns(start, end);
+            if range.start_col == deleted_col && range.end_col == deleted_col {
+                // The range only covered the deleted column
+                return Expr::Error(ErrorKind::Ref);
+            }
+
+            // Otherwise the range shrinks by one column
+            let new_start = if range.start_col > deleted_col {
+                range.start_col - 1
+            } else {
+                range.start_col
+            };
+            let new_end = if range.end_col >= deleted_col {
+                range.end_col - 1
+            } else {
+                range.end_col
+            };
+            match (col_num_to_letter(new_start), col_num_to_letter(new_end)) {
+                (Ok(start), Ok(end)) => Expr::ColumnRange { start, end },
+                _ => expr.clone(),
+            }
+        }
         Expr::Add(left, right) => Expr::Add(
             Box::new(delete_column_in_expr(left, deleted_col)),
             Box::new(delete_column_in_expr(right, deleted_col)),
@@ -4344,7 +5561,15 @@ fn delete_column_in_expr(expr: &Expr, deleted_col: usize) -> Expr {
             Box::new(delete_column_in_expr(left, deleted_col)),
             Box::new(delete_column_in_expr(right, deleted_col)),
         ),
+        Expr::Power(left, right) => Expr::Power(
+            Box::new(delete_column_in_expr(left, deleted_col)),
+            Box::new(delete_column_in_expr(right, deleted_col)),
+        ),
         Expr::Negate(inner) => Expr::Negate(Box::new(delete_column_in_expr(inner, deleted_col))),
+        Expr::SpillRef(anchor) => match delete_column_in_expr(anchor, deleted_col) {
+            error @ Expr::Error(_) => error,
+            anchor => Expr::spill_ref(anchor),
+        },
         Expr::Function { name, args } => Expr::Function {
             name: name.clone(),
             args: args
@@ -4384,6 +5609,10 @@ fn delete_column_in_expr(expr: &Expr, deleted_col: usize) -> Expr {
             Box::new(delete_column_in_expr(left, deleted_col)),
             Box::new(delete_column_in_expr(right, deleted_col)),
         ),
+        Expr::Concat(left, right) => Expr::Concat(
+            Box::new(delete_column_in_expr(left, deleted_col)),
+            Box::new(delete_column_in_expr(right, deleted_col)),
+        ),
         Expr::Not(inner) => Expr::Not(Box::new(delete_column_in_expr(inner, deleted_col))),
         _ => expr.clone(),
     }
@@ -4392,15 +5621,22 @@ fn delete_column_in_expr(expr: &Expr, deleted_col: usize) -> Expr {
 /// Delete a row from an expression (mark deleted refs as #REF!)
 fn delete_row_in_expr(expr: &Expr, deleted_row: usize) -> Expr {
     match expr {
-        Expr::CellRef { col, row } => {
+        Expr::CellRef {
+            col,
+            row,
+            col_absolute,
+            row_absolute,
+        } => {
             if *row == deleted_row {
                 // Reference to deleted row becomes #REF!
-                return Expr::String("#REF!".to_string());
+                return Expr::Error(ErrorKind::Ref);
             } else if *row > deleted_row {
                 // Shift up
                 return Expr::CellRef {
                     col: col.clone(),
                     row: row - 1,
+                    col_absolute: *col_absolute,
+                    row_absolute: *row_absolute,
                 };
             }
             expr.clone()
@@ -4409,6 +5645,23 @@ fn delete_row_in_expr(expr: &Expr, deleted_row: usize) -> Expr {
             start: Box::new(delete_row_in_expr(start, deleted_row)),
             end: Box::new(delete_row_in_expr(end, deleted_row)),
         },
+        Expr::RowRange { start, end } => {
+            let (start, end) = (*start.min(end), *start.max(end));
+            if start == deleted_row && end == deleted_row {
+                // The range only covered the deleted row
+                return Expr::Error(ErrorKind::Ref);
+            }
+
+            // Otherwise the range shrinks by one row
+            Expr::RowRange {
+                start: if start > deleted_row {
+                    start - 1
+                } else {
+                    start
+                },
+                end: if end >= deleted_row { end - 1 } else { end },
+            }
+        }
         Expr::Add(left, right) => Expr::Add(
             Box::new(delete_row_in_expr(left, deleted_row)),
             Box::new(delete_row_in_expr(right, deleted_row)),
@@ -4425,7 +5678,15 @@ fn delete_row_in_expr(expr: &Expr, deleted_row: usize) -> Expr {
             Box::new(delete_row_in_expr(left, deleted_row)),
             Box::new(delete_row_in_expr(right, deleted_row)),
         ),
+        Expr::Power(left, right) => Expr::Power(
+            Box::new(delete_row_in_expr(left, deleted_row)),
+            Box::new(delete_row_in_expr(right, deleted_row)),
+        ),
         Expr::Negate(inner) => Expr::Negate(Box::new(delete_row_in_expr(inner, deleted_row))),
+        Expr::SpillRef(anchor) => match delete_row_in_expr(anchor, deleted_row) {
+            error @ Expr::Error(_) => error,
+            anchor => Expr::spill_ref(anchor),
+        },
         Expr::Function { name, args } => Expr::Function {
             name: name.clone(),
             args: args
@@ -4465,7 +5726,224 @@ fn delete_row_in_expr(expr: &Expr, deleted_row: usize) -> Expr {
             Box::new(delete_row_in_expr(left, deleted_row)),
             Box::new(delete_row_in_expr(right, deleted_row)),
         ),
+        Expr::Concat(left, right) => Expr::Concat(
+            Box::new(delete_row_in_expr(left, deleted_row)),
+            Box::new(delete_row_in_expr(right, deleted_row)),
+        ),
         Expr::Not(inner) => Expr::Not(Box::new(delete_row_in_expr(inner, deleted_row))),
         _ => expr.clone(),
     }
 }
+
+/// A row or column inserted into or deleted from a sheet, by number (column A is 1)
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RowColumnChange {
+    InsertColumn(usize),
+    InsertRow(usize),
+    DeleteColumn(usize),
+    DeleteRow(usize),
+}
+
+impl RowColumnChange {
+    /// Rewrite the references in an expression (references to deleted cells become #REF!)
+    fn apply(self, expr: &Expr) -> Expr {
+        match self {
+            Self::InsertColumn(col) => shift_expr_columns(expr, col, 1),
+            Self::InsertRow(row) => shift_expr_rows(expr, row, 1),
+            Self::DeleteColumn(col) => delete_column_in_expr(expr, col),
+            Self::DeleteRow(row) => delete_row_in_expr(expr, row),
+        }
+    }
+
+    /// Where a cell ends up, or None if it was deleted
+    pub fn apply_to_cell(self, addr: &CellAddr) -> Option<CellAddr> {
+        match self.apply(&Expr::cell_ref(addr.col.clone(), addr.row)) {
+            Expr::CellRef { col, row, .. } => Some(CellAddr::new(col, row)),
+            _ => None,
+        }
+    }
+}
+
+/// Number of copies of a block of `len` rows (or columns) needed to fill `target`
+/// A target that is not a whole multiple of the block gets a single copy
+fn copies_to_fill(target: usize, len: usize) -> usize {
+    if len > 0 && target.is_multiple_of(len) {
+        (target / len).max(1)
+    } else {
+        1
+    }
+}
+
+/// Move a column letter by `offset` columns, if the result is still on the sheet
+fn offset_column(col: &str, offset: isize) -> Option<String> {
+    let col_num = col_letter_to_num(col).ok()?;
+    col_num_to_letter(col_num.checked_add_signed(offset)?).ok()
+}
+
+/// Move a row number by `offset` rows, if the result is still on the sheet
+fn offset_row(row: usize, offset: isize) -> Option<usize> {
+    row.checked_add_signed(offset).filter(|row| *row >= 1)
+}
+
+/// Replace the references in a formula's text for which `rewrite` returns a replacement,
+/// leaving the rest of the text (spacing, parentheses) as it was typed.
+/// None if nothing changed or the formula does not parse
+fn rewrite_formula_references(
+    formula: &str,
+    rewrite: impl Fn(&Expr) -> Option<Expr>,
+) -> Option<String> {
+    let spans = reference_spans(formula).ok()?;
+
+    // From the end, so that the positions of earlier references stay the same
+    let mut updated = formula.to_string();
+    for span in spans.into_iter().rev() {
+        if let Some(replacement) = parse_formula(&updated[span.clone()])
+            .ok()
+            .and_then(|reference| rewrite(&reference))
+        {
+            updated.replace_range(span, &replacement.to_string());
+        }
+    }
+    (updated != formula).then_some(updated)
+}
+
+/// Rewrite a formula copied `row_offset` rows down and `col_offset` columns right
+/// Relative columns and rows move with the formula, absolute ones ($A$1) stay put, and
+/// references pushed off the top or left edge of the sheet become #REF!
+fn copy_expr(expr: &Expr, row_offset: isize, col_offset: isize) -> Expr {
+    let recurse = |e: &Expr| Box::new(copy_expr(e, row_offset, col_offset));
+    let invalid = || Expr::Error(ErrorKind::Ref);
+    match expr {
+        Expr::CellRef {
+            col,
+            row,
+            col_absolute,
+            row_absolute,
+        } => {
+            let new_col = match col_absolute {
+                true => Some(col.clone()),
+                false => offset_column(col, col_offset),
+            };
+            let new_row = match row_absolute {
+                true => Some(*row),
+                false => offset_row(*row, row_offset),
+            };
+            match (new_col, new_row) {
+                (Some(col), Some(row)) => {
+                    Expr::absolute_cell_ref(col, row, *col_absolute, *row_absolute)
+                }
+                _ => invalid(),
+            }
+        }
+        Expr::Range { start, end } => {
+            let (start, end) = (recurse(start), recurse(end));
+            if matches!(*start, Expr::Error(_)) || matches!(*end, Expr::Error(_)) {
+                invalid()
+            } else {
+                Expr::Range { start, end }
+            }
+        }
+        Expr::ColumnRange { start, end } => {
+            match (
+                offset_column(start, col_offset),
+                offset_column(end, col_offset),
+            ) {
+                (Some(start), Some(end)) => Expr::column_range(start, end),
+                _ => invalid(),
+            }
+        }
+        Expr::RowRange { start, end } => {
+            match (offset_row(*start, row_offset), offset_row(*end, row_offset)) {
+                (Some(start), Some(end)) => Expr::row_range(start, end),
+                _ => invalid(),
+            }
+        }
+        Expr::SheetRef { sheet, reference } => match copy_expr(reference, row_offset, col_offset) {
+            Expr::Error(_) => invalid(),
+            reference => Expr::sheet_ref(sheet.clone(), reference),
+        },
+        Expr::SpillRef(anchor) => match copy_expr(anchor, row_offset, col_offset) {
+            Expr::Error(_) => invalid(),
+            anchor => Expr::spill_ref(anchor),
+        },
+        Expr::Add(l, r) => Expr::Add(recurse(l), recurse(r)),
+        Expr::Subtract(l, r) => Expr::Subtract(recurse(l), recurse(r)),
+        Expr::Multiply(l, r) => Expr::Multiply(recurse(l), recurse(r)),
+        Expr::Divide(l, r) => Expr::Divide(recurse(l), recurse(r)),
+        Expr::Power(l, r) => Expr::Power(recurse(l), recurse(r)),
+        Expr::Negate(e) => Expr::Negate(recurse(e)),
+        Expr::Function { name, args } => Expr::Function {
+            name: name.clone(),
+            args: args
+                .iter()
+                .map(|arg| copy_expr(arg, row_offset, col_offset))
+                .collect(),
+        },
+        Expr::GreaterThan(l, r) => Expr::GreaterThan(recurse(l), recurse(r)),
+        Expr::LessThan(l, r) => Expr::LessThan(recurse(l), recurse(r)),
+        Expr::GreaterOrEqual(l, r) => Expr::GreaterOrEqual(recurse(l), recurse(r)),
+        Expr::LessOrEqual(l, r) => Expr::LessOrEqual(recurse(l), recurse(r)),
+        Expr::Equal(l, r) => Expr::Equal(recurse(l), recurse(r)),
+        Expr::NotEqual(l, r) => Expr::NotEqual(recurse(l), recurse(r)),
+        Expr::And(l, r) => Expr::And(recurse(l), recurse(r)),
+        Expr::Or(l, r) => Expr::Or(recurse(l), recurse(r)),
+        Expr::Concat(l, r) => Expr::Concat(recurse(l), recurse(r)),
+        Expr::Not(e) => Expr::Not(recurse(e)),
+        _ => expr.clone(),
+    }
+}
+
+/// Point references to cells inside `moved` at where those cells were moved to
+/// A range follows only when it lies entirely inside the moved block; references into
+/// other sheets are left alone
+fn move_expr(expr: &Expr, moved: &CellRange, row_offset: isize, col_offset: isize) -> Expr {
+    let recurse = |e: &Expr| Box::new(move_expr(e, moved, row_offset, col_offset));
+    let is_moved = |e: &Expr| match e {
+        Expr::CellRef { col, row, .. } => moved.contains(&CellAddr::new(col.as_str(), *row)),
+        _ => false,
+    };
+    match expr {
+        Expr::CellRef {
+            col,
+            row,
+            col_absolute,
+            row_absolute,
+        } if is_moved(expr) => {
+            match (offset_column(col, col_offset), offset_row(*row, row_offset)) {
+                (Some(col), Some(row)) => {
+                    Expr::absolute_cell_ref(col, row, *col_absolute, *row_absolute)
+                }
+                _ => Expr::Error(ErrorKind::Ref),
+            }
+        }
+        Expr::Range { start, end } if is_moved(start) && is_moved(end) => Expr::Range {
+            start: recurse(start),
+            end: recurse(end),
+        },
+        Expr::SpillRef(anchor) if is_moved(anchor) => Expr::SpillRef(recurse(anchor)),
+        Expr::Add(l, r) => Expr::Add(recurse(l), recurse(r)),
+        Expr::Subtract(l, r) => Expr::Subtract(recurse(l), recurse(r)),
+        Expr::Multiply(l, r) => Expr::Multiply(recurse(l), recurse(r)),
+        Expr::Divide(l, r) => Expr::Divide(recurse(l), recurse(r)),
+        Expr::Power(l, r) => Expr::Power(recurse(l), recurse(r)),
+        Expr::Negate(e) => Expr::Negate(recurse(e)),
+        Expr::Function { name, args } => Expr::Function {
+            name: name.clone(),
+            args: args
+                .iter()
+                .map(|arg| move_expr(arg, moved, row_offset, col_offset))
+                .collect(),
+        },
+        Expr::GreaterThan(l, r) => Expr::GreaterThan(recurse(l), recurse(r)),
+        Expr::LessThan(l, r) => Expr::LessThan(recurse(l), recurse(r)),
+        Expr::GreaterOrEqual(l, r) => Expr::GreaterOrEqual(recurse(l), recurse(r)),
+        Expr::LessOrEqual(l, r) => Expr::LessOrEqual(recurse(l), recurse(r)),
+        Expr::Equal(l, r) => Expr::Equal(recurse(l), recurse(r)),
+        Expr::NotEqual(l, r) => Expr::NotEqual(recurse(l), recurse(r)),
+        Expr::And(l, r) => Expr::And(recurse(l), recurse(r)),
+        Expr::Or(l, r) => Expr::Or(recurse(l), recurse(r)),
+        Expr::Concat(l, r) => Expr::Concat(recurse(l), recurse(r)),
+        Expr::Not(e) => Expr::Not(recurse(e)),
+        _ => expr.clone(),
+    }
+}
@@ -0,0 +1,273 @@
+// Powers of units and dimensions
+//
+// Most units have whole powers (m^2, s^-1), but roots give fractional ones: the square root
+// of 2 m is 1.414 m^0.5. Powers are kept as exact fractions so that squaring m^0.5 gives m
+// again, rather than a power that is only nearly 1.
+
+use serde::{de, Deserialize, Deserializer, Serialize, Serializer};
+use std::cmp::Ordering;
+use std::fmt;
+use std::ops::{Add, AddAssign, Mul, Neg, Sub};
+use std::str::FromStr;
+
+/// Largest denominator of a fractional power (m^0.25 and cube roots are allowed, m^0.01 is not)
+const MAX_DENOMINATOR: i64 = 12;
+
+/// How far a decimal may be from a fraction to be read as it (0.333333 is 1/3)
+const TOLERANCE: f64 = 1e-6;
+
+/// A power of a unit, as a fraction in lowest terms with a positive denominator
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Exponent {
+    numerator: i32,
+    denominator: i32,
+}
+
+impl Exponent {
+    pub const ZERO: Exponent = Exponent {
+        numerator: 0,
+        denominator: 1,
+    };
+
+    pub const ONE: Exponent = Exponent {
+        numerator: 1,
+        denominator: 1,
+    };
+
+    /// The fraction numerator/denominator, or None if the denominator is 0 or it is too large
+    pub fn new(numerator: i64, denominator: i64) -> Option<Self> {
+        if denominator == 0 {
+            return None;
+        }
+        let divisor = gcd(numerator, denominator) * denominator.signum();
+        Some(Self {
+            numerator: i32::try_from(numerator / divisor).ok()?,
+            denominator: i32::try_from(denominator / divisor).ok()?,
+        })
+    }
+
+    /// The fraction closest to a number, if it has a small denominator
+    /// 2.0 is 2 and 0.5 is 1/2, but 0.123456 has no exponent
+    pub fn from_f64(value: f64) -> Option<Self> {
+        if !value.is_finite() || value.abs() > i32::MAX as f64 {
+            return None;
+        }
+        (1..=MAX_DENOMINATOR).find_map(|denominator| {
+            let numerator = (value * denominator as f64).round();
+            ((value - numerator / denominator as f64).abs() < TOLERANCE)
+                .then(|| Self::new(numerator as i64, denominator))
+                .flatten()
+        })
+    }
+
+    pub fn to_f64(self) -> f64 {
+        self.numerator as f64 / self.denominator as f64
+    }
+
+    /// The power as a whole number, if it is one
+    pub fn as_integer(self) -> Option<i32> {
+        (self.denominator == 1).then_some(self.numerator)
+    }
+
+    pub fn is_zero(self) -> bool {
+        self.numerator == 0
+    }
+
+    pub fn is_positive(self) -> bool {
+        self.numerator > 0
+    }
+
+    pub fn abs(self) -> Self {
+        Self {
+            numerator: self.numerator.abs(),
+            denominator: self.denominator,
+        }
+    }
+
+    /// Raise a conversion factor to this power
+    pub fn apply(self, factor: f64) -> f64 {
+        match self.as_integer() {
+            Some(power) => factor.powi(power),
+            None => factor.powf(self.to_f64()),
+        }
+    }
+
+    fn combine(self, other: Self, op: impl Fn(i64, i64) -> i64) -> Self {
+        let denominator = self.denominator as i64 * other.denominator as i64;
+        let numerator = op(
+            self.numerator as i64 * other.denominator as i64,
+            other.numerator as i64 * self.denominator as i64,
+        );
+        Self::new(numerator, denominator).expect("unit exponent out of range")
+    }
+}
+
+fn gcd(a: i64, b: i64) -> i64 {
+    let (mut a, mut b) = (a.abs(), b.abs());
+    while b != 0 {
+        (a, b) = (b, a % b);
+    }
+    a.max(1)
+}
+
+impl Default for Exponent {
+    fn default() -> Self {
+        Self::ZERO
+    }
+}
+
+impl From<i32> for Exponent {
+    fn from(power: i32) -> Self {
+        Self {
+            numerator: power,
+            denominator: 1,
+        }
+    }
+}
+
+impl PartialEq<i32> for Exponent {
+    fn eq(&self, other: &i32) -> bool {
+        self.as_integer() == Some(*other)
+    }
+}
+
+impl PartialOrd for Exponent {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Exponent {
+    fn cmp(&self, other: &Self) -> Ordering {
+        (self.numerator as i64 * other.denominator as i64)
+            .cmp(&(other.numerator as i64 * self.denominator as i64))
+    }
+}
+
+impl Add for Exponent {
+    type Output = Self;
+
+    fn add(self, other: Self) -> Self {
+        self.combine(other, |a, b| a + b)
+    }
+}
+
+impl Add<i32> for Exponent {
+    type Output = Self;
+
+    fn add(self, other: i32) -> Self {
+        self + Self::from(other)
+    }
+}
+
+impl Sub for Exponent {
+    type Output = Self;
+
+    fn sub(self, other: Self) -> Self {
+        self.combine(other, |a, b| a - b)
+    }
+}
+
+impl Mul for Exponent {
+    type Output = Self;
+
+    fn mul(self, other: Self) -> Self {
+        Self::new(
+            self.numerator as i64 * other.numerator as i64,
+            self.denominator as i64 * other.denominator as i64,
+        )
+        .expect("unit exponent out of range")
+    }
+}
+
+impl Neg for Exponent {
+    type Output = Self;
+
+    fn neg(self) -> Self {
+        Self {
+            numerator: -self.numerator,
+            denominator: self.denominator,
+        }
+    }
+}
+
+impl AddAssign for Exponent {
+    fn add_assign(&mut self, other: Self) {
+        *self = *self + other;
+    }
+}
+
+impl AddAssign<i32> for Exponent {
+    fn add_assign(&mut self, other: i32) {
+        *self = *self + other;
+    }
+}
+
+/// Whole powers are written as integers ("2"), fractional ones as decimals ("0.5", "0.333333"),
+/// which read back as the same fraction
+impl fmt::Display for Exponent {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.as_integer() {
+            Some(power) => write!(f, "{}", power),
+            None => {
+                let decimal = format!("{:.6}", self.to_f64());
+                write!(f, "{}", decimal.trim_end_matches('0'))
+            }
+        }
+    }
+}
+
+impl FromStr for Exponent {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let value: f64 = s
+            .trim()
+            .parse()
+            .map_err(|_| format!("Invalid exponent: {}", s))?;
+        Self::from_f64(value).ok_or_else(|| format!("Unsupported exponent: {}", s))
+    }
+}
+
+impl Serialize for Exponent {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        match self.as_integer() {
+            Some(power) => serializer.serialize_i32(power),
+            None => serializer.serialize_f64(self.to_f64()),
+        }
+    }
+}
+
+impl<'de> Deserialize<'de> for Exponent {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let value = f64::deserialize(deserializer)?;
+        Self::from_f64(value)
+            .ok_or_else(|| de::Error::custom(format!("Unsupported exponent: {}", value)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_fractions() {
+        let half = Exponent::from_f64(0.5).unwrap();
+        assert_eq!(half + half, 1);
+        assert_eq!(half * Exponent::from(4), 2);
+        assert_eq!(Exponent::from(3) * half, Exponent::new(3, 2).unwrap());
+        assert_eq!(Exponent::new(2, -4), Some(-half));
+        assert!(half < Exponent::ONE && half.is_positive());
+
+        // A third survives being written out and read back
+        let third = Exponent::from_f64(1.0 / 3.0).unwrap();
+        assert_eq!(third.to_string(), "0.333333");
+        assert_eq!("0.333333".parse::<Exponent>(), Ok(third));
+        assert_eq!(third + third + third, 1);
+
+        assert_eq!(Exponent::from(-2).to_string(), "-2");
+        assert_eq!(Exponent::new(5, 2).unwrap().to_string(), "2.5");
+        assert!(Exponent::from_f64(0.123456).is_none());
+        assert!("x".parse::<Exponent>().is_err());
+    }
+}
@@ -551,17 +551,27 @@ impl UnitLibrary {
     // === Time Units ===
     fn add_time_units(&mut self) {
         // Short forms
+        self.add_unit("ms", Unit::simple("ms", BaseDimension::Time));
         self.add_unit("s", Unit::simple("s", BaseDimension::Time));
         self.add_unit("min", Unit::simple("min", BaseDimension::Time));
         self.add_unit("hr", Unit::simple("hr", BaseDimension::Time));
         self.add_unit("h", Unit::simple("h", BaseDimension::Time)); // alias for hr
         self.add_unit("day", Unit::simple("day", BaseDimension::Time));
+        self.add_unit("wk", Unit::simple("wk", BaseDimension::Time));
         self.add_unit("month", Unit::simple("month", BaseDimension::Time));
         self.add_unit("quarter", Unit::simple("quarter", BaseDimension::Time));
         self.add_unit("year", Unit::simple("year", BaseDimension::Time));
         self.add_unit("yr", Unit::simple("yr", BaseDimension::Time)); // alias for year
 
         // Long forms
+        self.add_unit(
+            "millisecond",
+            Unit::simple("millisecond", BaseDimension::Time),
+        );
+        self.add_unit(
+            "milliseconds",
+            Unit::simple("milliseconds", BaseDimension::Time),
+        );
         self.add_unit("second", Unit::simple("second", BaseDimension::Time));
         self.add_unit("seconds", Unit::simple("seconds", BaseDimension::Time));
         self.add_unit("minute", Unit::simple("minute", BaseDimension::Time));
@@ -569,11 +579,15 @@ impl UnitLibrary {
         self.add_unit("hour", Unit::simple("hour", BaseDimension::Time));
         self.add_unit("hours", Unit::simple("hours", BaseDimension::Time));
         self.add_unit("days", Unit::simple("days", BaseDimension::Time));
+        self.add_unit("week", Unit::simple("week", BaseDimension::Time));
+        self.add_unit("weeks", Unit::simple("weeks", BaseDimension::Time));
         self.add_unit("months", Unit::simple("months", BaseDimension::Time));
         self.add_unit("quarters", Unit::simple("quarters", BaseDimension::Time));
         self.add_unit("years", Unit::simple("years", BaseDimension::Time));
 
         // Conversions (all to seconds as base) - short to short
+        self.add_conversion("ms", "s", ConversionFactor::new(0.001));
+        self.add_conversion("s", "ms", ConversionFactor::new(1000.0));
         self.add_conversion("min", "s", ConversionFactor::new(60.0));
         self.add_conversion("s", "min", ConversionFactor::new(1.0 / 60.0));
         self.add_conversion("hr", "s", ConversionFactor::new(3600.0));
@@ -582,6 +596,8 @@ impl UnitLibrary {
         self.add_conversion("s", "h", ConversionFactor::new(1.0 / 3600.0));
         self.add_conversion("day", "s", ConversionFactor::new(86400.0));
         self.add_conversion("s", "day", ConversionFactor::new(1.0 / 86400.0));
+        self.add_conversion("wk", "s", ConversionFactor::new(604_800.0));
+        self.add_conversion("s", "wk", ConversionFactor::new(1.0 / 604_800.0));
         self.add_conversion("month", "s", ConversionFactor::new(2_628_000.0)); // 30.42 days average
         self.add_conversion("s", "month", ConversionFactor::new(1.0 / 2_628_000.0));
         self.add_conversion("year", "s", ConversionFactor::new(31_536_000.0)); // 365 days
@@ -590,6 +606,8 @@ impl UnitLibrary {
         self.add_conversion("s", "yr", ConversionFactor::new(1.0 / 31_536_000.0));
 
         // Long to short conversions
+        self.add_conversion("millisecond", "ms", ConversionFactor::new(1.0));
+        self.add_conversion("milliseconds", "ms", ConversionFactor::new(1.0));
         self.add_conversion("second", "s", ConversionFactor::new(1.0));
         self.add_conversion("seconds", "s", ConversionFactor::new(1.0));
         self.add_conversion("minute", "min", ConversionFactor::new(1.0));
@@ -597,6 +615,8 @@ impl UnitLibrary {
         self.add_conversion("hour", "hr", ConversionFactor::new(1.0));
         self.add_conversion("hours", "hr", ConversionFactor::new(1.0));
         self.add_conversion("days", "day", ConversionFactor::new(1.0));
+        self.add_conversion("week", "wk", ConversionFactor::new(1.0));
+        self.add_conversion("weeks", "wk", ConversionFactor::new(1.0));
         self.add_conversion("months", "month", ConversionFactor::new(1.0));
         self.add_conversion("quarters", "quarter", ConversionFactor::new(1.0));
         self.add_conversion("years", "year", ConversionFactor::new(1.0));
@@ -606,6 +626,8 @@ impl UnitLibrary {
         self.add_conversion("min", "hr", ConversionFactor::new(1.0 / 60.0));
         self.add_conversion("day", "hr", ConversionFactor::new(24.0));
         self.add_conversion("hr", "day", ConversionFactor::new(1.0 / 24.0));
+        self.add_conversion("wk", "day", ConversionFactor::new(7.0));
+        self.add_conversion("day", "wk", ConversionFactor::new(1.0 / 7.0));
         self.add_conversion("month", "day", ConversionFactor::new(30.42));
         self.add_conversion("day", "month", ConversionFactor::new(1.0 / 30.42));
         self.add_conversion("month", "hr", ConversionFactor::new(730.0)); // 30.42 * 24
@@ -920,6 +942,10 @@ mod tests {
         // Days to hours
         let day_to_hr = library.get_conversion("day", "hr").unwrap();
         assert_eq!(day_to_hr.convert(1.0), 24.0);
+
+        // Milliseconds, also to units without a direct conversion
+        assert_eq!(library.convert(1500.0, "ms", "s"), Some(1.5));
+        assert_eq!(library.convert(1.0, "min", "ms"), Some(60_000.0));
     }
 
     #[test]
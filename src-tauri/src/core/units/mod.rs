@@ -9,11 +9,13 @@
 // For MLP, we use a simplified direct-lookup conversion system rather than graph pathfinding.
 // This is sufficient for Tier 1 units and keeps the implementation straightforward.
 
+mod exponent;
 mod library;
 mod parser;
 
+pub use exponent::Exponent;
 pub use library::{ConversionFactor, UnitLibrary};
-pub use parser::{parse_unit, ParseError};
+pub use parser::{normalize_unit_exponents, parse_unit, ParseError};
 
 use serde::{Deserialize, Serialize};
 use std::fmt;
@@ -35,8 +37,8 @@ pub enum Dimension {
     Dimensionless,
     Simple(BaseDimension),
     Compound {
-        numerator: Vec<(BaseDimension, i32)>,
-        denominator: Vec<(BaseDimension, i32)>,
+        numerator: Vec<(BaseDimension, Exponent)>,
+        denominator: Vec<(BaseDimension, Exponent)>,
     },
 }
 
@@ -71,19 +73,25 @@ impl Unit {
         }
     }
 
-    /// Create a compound unit (e.g., m/s, kg*m/s²)
-    pub fn compound(
+    /// Create a compound unit (e.g., m/s, kg*m/s², m^0.5)
+    pub fn compound<P: Into<Exponent>>(
         symbol: impl Into<String>,
-        numerator: Vec<(BaseDimension, i32)>,
-        denominator: Vec<(BaseDimension, i32)>,
+        numerator: Vec<(BaseDimension, P)>,
+        denominator: Vec<(BaseDimension, P)>,
     ) -> Self {
         let symbol = symbol.into();
+        let powers = |factors: Vec<(BaseDimension, P)>| {
+            factors
+                .into_iter()
+                .map(|(base, power)| (base, power.into()))
+                .collect()
+        };
         Self {
             canonical: symbol.clone(),
             original: symbol,
             dimension: Dimension::Compound {
-                numerator,
-                denominator,
+                numerator: powers(numerator),
+                denominator: powers(denominator),
             },
         }
     }
@@ -144,7 +152,7 @@ impl Unit {
             let trimmed = part.trim();
 
             // Skip empty strings, "1", and numbers (exponents)
-            if trimmed.is_empty() || trimmed == "1" || trimmed.parse::<i32>().is_ok() {
+            if trimmed.is_empty() || trimmed == "1" || trimmed.parse::<f64>().is_ok() {
                 continue;
             }
 
@@ -303,8 +311,8 @@ mod tests {
         assert_eq!(format!("{}", length), "L");
 
         let velocity = Dimension::Compound {
-            numerator: vec![(BaseDimension::Length, 1)],
-            denominator: vec![(BaseDimension::Time, 1)],
+            numerator: vec![(BaseDimension::Length, 1.into())],
+            denominator: vec![(BaseDimension::Time, 1.into())],
         };
         assert_eq!(format!("{}", velocity), "L/T");
     }
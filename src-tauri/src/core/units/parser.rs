@@ -1,6 +1,6 @@
 // Basic unit parser
 
-use super::{BaseDimension, Unit, UnitLibrary};
+use super::{BaseDimension, Exponent, Unit, UnitLibrary};
 use std::result::Result;
 
 #[derive(Debug, PartialEq)]
@@ -21,6 +21,9 @@ impl std::fmt::Display for ParseError {
 impl std::error::Error for ParseError {}
 
 /// Parse a unit symbol into a Unit
+/// Supports simple units ("m"), products and quotients ("kg*m/s^2", "$/GB/month") and
+/// exponents written with ^ or superscripts ("ft^2", "ft²", "s^-1", "m^0.5")
+/// Every factor after the first "/" is in the denominator, so "$/GB/month" is $ per GB per month
 pub fn parse_unit(symbol: &str, library: &UnitLibrary) -> Result<Unit, ParseError> {
     let symbol = symbol.trim();
 
@@ -34,42 +37,134 @@ pub fn parse_unit(symbol: &str, library: &UnitLibrary) -> Result<Unit, ParseErro
         return Ok(Unit::dimensionless());
     }
 
-    // Try to parse as compound unit with division (e.g., "USD/ft", "mi/hr", "$/ft")
-    if let Some(pos) = symbol.find('/') {
-        let numerator_str = &symbol[..pos];
-        let denominator_str = &symbol[pos + 1..];
+    let symbol = normalize_unit_exponents(symbol);
+    let (numerator, denominator) = parse_unit_factors(&symbol)?;
 
-        let num_dim = get_base_dimension(numerator_str, library)?;
-        let den_dim = get_base_dimension(denominator_str, library)?;
+    // A single factor without an exponent is a simple unit
+    // This handles units that aren't in the library but are commonly used (e.g., "$")
+    if let ([(name, power)], []) = (numerator.as_slice(), denominator.as_slice()) {
+        if *power == 1 {
+            let base_dim = get_base_dimension(name, library)?;
+            return Ok(Unit::simple(name.clone(), base_dim));
+        }
+    }
 
-        return Ok(Unit::compound(
-            symbol.to_string(),
-            vec![(num_dim, 1)],
-            vec![(den_dim, 1)],
-        ));
+    Ok(Unit::compound(
+        symbol.clone(),
+        factor_dimensions(&numerator, library)?,
+        factor_dimensions(&denominator, library)?,
+    ))
+}
+
+/// Rewrite superscript exponents as ^ exponents ("ft²" → "ft^2", "s⁻¹" → "s^-1")
+pub fn normalize_unit_exponents(symbol: &str) -> String {
+    let mut result = String::with_capacity(symbol.len());
+    let mut in_superscript = false;
+
+    for c in symbol.chars() {
+        let plain = match c {
+            '⁻' => Some('-'),
+            '⁰' => Some('0'),
+            '¹' => Some('1'),
+            '²' => Some('2'),
+            '³' => Some('3'),
+            '⁴' => Some('4'),
+            '⁵' => Some('5'),
+            '⁶' => Some('6'),
+            '⁷' => Some('7'),
+            '⁸' => Some('8'),
+            '⁹' => Some('9'),
+            _ => None,
+        };
+
+        match plain {
+            Some(plain) => {
+                if !in_superscript {
+                    result.push('^');
+                }
+                result.push(plain);
+            }
+            None => result.push(c),
+        }
+        in_superscript = plain.is_some();
     }
 
-    // Try to parse as compound unit with multiplication (e.g., "ft*ft", "kg*m")
-    if let Some(pos) = symbol.find('*') {
-        let left_str = &symbol[..pos];
-        let right_str = &symbol[pos + 1..];
+    result
+}
+
+/// Unit symbols with their powers (e.g., [("kg", 1), ("m", 1)])
+type UnitFactors = Vec<(String, Exponent)>;
 
-        let left_dim = get_base_dimension(left_str, library)?;
-        let right_dim = get_base_dimension(right_str, library)?;
+/// Split a unit symbol into numerator and denominator factors with their powers
+/// A negative power moves a factor to the other side ("s^-1" is "1/s")
+fn parse_unit_factors(symbol: &str) -> Result<(UnitFactors, UnitFactors), ParseError> {
+    let mut numerator = Vec::new();
+    let mut denominator = Vec::new();
+    let mut in_denominator = false;
 
-        return Ok(Unit::compound(
-            symbol.to_string(),
-            vec![(left_dim.clone(), 1), (right_dim, 1)],
-            vec![],
-        ));
+    let mut rest = symbol;
+    loop {
+        let end = rest.find(['*', '/']).unwrap_or(rest.len());
+        let (name, power) = parse_unit_factor(&rest[..end])?;
+
+        // "1" is only a placeholder numerator ("1/ft^2")
+        if !(name == "1" && power == 1) && power != 0 {
+            if power.is_positive() != in_denominator {
+                numerator.push((name, power.abs()));
+            } else {
+                denominator.push((name, power.abs()));
+            }
+        }
+
+        if end == rest.len() {
+            break;
+        }
+        in_denominator |= rest[end..].starts_with('/');
+        rest = &rest[end + 1..];
     }
 
-    // Try to parse as simple unit using hardcoded mappings (e.g., "$", "ft")
-    // This handles units that aren't in the library but are commonly used
-    match get_base_dimension(symbol, library) {
-        Ok(base_dim) => Ok(Unit::simple(symbol, base_dim)),
-        Err(e) => Err(e),
+    Ok((numerator, denominator))
+}
+
+/// Parse a single factor such as "m", "s^2", "s^-1" or "m^0.5"
+/// Fractional exponents must be simple fractions (m^0.5 and m^0.25, but not m^0.123)
+fn parse_unit_factor(factor: &str) -> Result<(String, Exponent), ParseError> {
+    let (name, power) = match factor.split_once('^') {
+        Some((name, exponent)) => {
+            let exponent = exponent.parse().map_err(|message: String| {
+                ParseError::InvalidFormat(format!("{} in {}", message, factor))
+            })?;
+            (name.trim(), exponent)
+        }
+        None => (factor.trim(), Exponent::ONE),
+    };
+
+    if name.is_empty() {
+        return Err(ParseError::InvalidFormat(format!(
+            "Missing unit in: {}",
+            factor
+        )));
     }
+
+    Ok((name.to_string(), power))
+}
+
+/// Get the dimensions of a list of factors, combining factors of the same dimension
+fn factor_dimensions(
+    factors: &[(String, Exponent)],
+    library: &UnitLibrary,
+) -> Result<Vec<(BaseDimension, Exponent)>, ParseError> {
+    let mut dimensions: Vec<(BaseDimension, Exponent)> = Vec::new();
+
+    for (name, power) in factors {
+        let base_dim = get_base_dimension(name, library)?;
+        match dimensions.iter_mut().find(|(dim, _)| *dim == base_dim) {
+            Some((_, total)) => *total += *power,
+            None => dimensions.push((base_dim, *power)),
+        }
+    }
+
+    Ok(dimensions)
 }
 
 /// Get the base dimension for a unit symbol
@@ -85,11 +180,12 @@ fn get_base_dimension(unit_str: &str, library: &UnitLibrary) -> Result<BaseDimen
     match unit_str {
         "m" | "cm" | "mm" | "km" | "in" | "ft" | "yd" | "mi" => Ok(BaseDimension::Length),
         "g" | "kg" | "mg" | "oz" | "lb" => Ok(BaseDimension::Mass),
-        "s" | "min" | "hr" | "h" | "day" | "month" | "quarter" | "year" | "yr" => {
+        "ms" | "s" | "min" | "hr" | "h" | "day" | "month" | "quarter" | "year" | "yr" => {
             Ok(BaseDimension::Time)
         }
         "C" | "F" | "K" => Ok(BaseDimension::Temperature),
         "USD" | "EUR" | "GBP" | "$" => Ok(BaseDimension::Currency),
+        "%" => Ok(BaseDimension::Custom("%".to_string())),
         // Digital storage units (bytes)
         "B" | "b" | "KB" | "Kb" | "MB" | "Mb" | "GB" | "Gb" | "TB" | "Tb" | "PB" | "Pb" => {
             Ok(BaseDimension::DigitalStorage)
@@ -105,6 +201,7 @@ fn get_base_dimension(unit_str: &str, library: &UnitLibrary) -> Result<BaseDimen
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::core::units::Dimension;
 
     #[test]
     fn test_parse_known_units() {
@@ -153,4 +250,109 @@ mod tests {
         let result = parse_unit("  m  ", &library).unwrap();
         assert_eq!(result.original(), "m");
     }
+
+    #[test]
+    fn test_parse_compound_units_with_exponents() {
+        let library = UnitLibrary::new();
+
+        let accel = parse_unit("m/s^2", &library).unwrap();
+        assert_eq!(accel.canonical(), "m/s^2");
+        assert_eq!(
+            accel.dimension(),
+            Unit::compound(
+                "m/s^2",
+                vec![(BaseDimension::Length, 1)],
+                vec![(BaseDimension::Time, 2)]
+            )
+            .dimension()
+        );
+
+        let force = parse_unit("kg*m/s^2", &library).unwrap();
+        assert_eq!(
+            force.dimension(),
+            Unit::compound(
+                "kg*m/s^2",
+                vec![(BaseDimension::Mass, 1), (BaseDimension::Length, 1)],
+                vec![(BaseDimension::Time, 2)]
+            )
+            .dimension()
+        );
+
+        // Every factor after the first "/" is in the denominator
+        let price = parse_unit("$/GB/month", &library).unwrap();
+        assert_eq!(
+            price.dimension(),
+            Unit::compound(
+                "$/GB/month",
+                vec![(BaseDimension::Currency, 1)],
+                vec![(BaseDimension::DigitalStorage, 1), (BaseDimension::Time, 1)]
+            )
+            .dimension()
+        );
+
+        // Superscripts, repeated factors and negative exponents
+        let area = parse_unit("ft²", &library).unwrap();
+        assert_eq!(area.canonical(), "ft^2");
+        assert_eq!(
+            area.dimension(),
+            parse_unit("ft*ft", &library).unwrap().dimension()
+        );
+        assert_eq!(
+            parse_unit("s^-1", &library).unwrap().dimension(),
+            parse_unit("1/s", &library).unwrap().dimension()
+        );
+        assert_eq!(
+            parse_unit("m³", &library).unwrap().dimension(),
+            parse_unit("m^3.0", &library).unwrap().dimension()
+        );
+    }
+
+    #[test]
+    fn test_parse_fractional_exponents() {
+        let library = UnitLibrary::new();
+
+        let root = parse_unit("m^0.5", &library).unwrap();
+        assert_eq!(root.canonical(), "m^0.5");
+        assert_eq!(
+            root.dimension(),
+            &Dimension::Compound {
+                numerator: vec![(BaseDimension::Length, Exponent::new(1, 2).unwrap())],
+                denominator: vec![],
+            }
+        );
+        assert_eq!(
+            parse_unit("m^1.5*m^0.5", &library).unwrap().dimension(),
+            parse_unit("m^2", &library).unwrap().dimension()
+        );
+        assert_eq!(
+            parse_unit("kg/s^1.5", &library).unwrap().dimension(),
+            parse_unit("kg*s^-1.5", &library).unwrap().dimension()
+        );
+    }
+
+    #[test]
+    fn test_parse_invalid_exponents() {
+        let library = UnitLibrary::new();
+
+        assert!(matches!(
+            parse_unit("m^0.123", &library),
+            Err(ParseError::InvalidFormat(_))
+        ));
+        assert!(matches!(
+            parse_unit("m^x", &library),
+            Err(ParseError::InvalidFormat(_))
+        ));
+        assert_eq!(
+            parse_unit("m/xyz^2", &library).unwrap_err(),
+            ParseError::UnknownUnit("xyz".to_string())
+        );
+    }
+
+    #[test]
+    fn test_normalize_unit_exponents() {
+        assert_eq!(normalize_unit_exponents("ft²"), "ft^2");
+        assert_eq!(normalize_unit_exponents("kg*m/s²"), "kg*m/s^2");
+        assert_eq!(normalize_unit_exponents("s⁻¹"), "s^-1");
+        assert_eq!(normalize_unit_exponents("m^2"), "m^2");
+    }
 }
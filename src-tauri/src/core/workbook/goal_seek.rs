@@ -0,0 +1,362 @@
+// Goal seek: find the value of an input cell that makes a formula reach a goal
+//
+// The search starts from the input's current value and takes secant steps (Newton's method
+// with the slope measured between the last two tries), which finds the goal in a few
+// recalculations for the smooth formulas of most models. If that stalls, it looks further
+// and further out on both sides for a value past the goal. Once two tries fall either side
+// of the goal, it stays between them, halving the gap whenever a secant step would leave it
+// or is too slow, so it always narrows in on the goal.
+
+use super::{Workbook, WorkbookError};
+use crate::core::cell::{Cell, CellValue};
+use crate::core::formula::{Evaluator, Expr};
+use crate::core::table::CellAddr;
+use crate::core::units::Unit;
+use serde::Serialize;
+
+/// Most recalculations in each stage of the search
+const MAX_ITERATIONS: usize = 100;
+
+/// Most times the search doubles how far out it looks for a value past the goal
+const MAX_EXPANSIONS: usize = 64;
+
+/// How close the target has to come to the goal, relative to the goal (or to 1 for a goal
+/// smaller than 1)
+const RELATIVE_TOLERANCE: f64 = 1e-9;
+
+/// The value goal seek found for the changing cell
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct GoalSeekResult {
+    /// New value of the changing cell, in its unit
+    pub value: f64,
+    pub unit: String,
+
+    /// What the target cell calculates to with that value, in its unit
+    pub target_value: f64,
+    pub target_unit: String,
+
+    /// Number of times the workbook was recalculated
+    pub iterations: usize,
+}
+
+impl Workbook {
+    /// Find the value of `changing` that makes the formula in `target` equal `goal`, both cells
+    /// on the same sheet. `changing` must hold a number, which keeps its unit; the goal is
+    /// converted into the unit the target calculates to, or taken in that unit if `goal_unit`
+    /// is None. The changing cell keeps the value found, or goes back to its old value if
+    /// none is found
+    pub fn goal_seek(
+        &mut self,
+        sheet_index: usize,
+        target: &CellAddr,
+        goal: f64,
+        goal_unit: Option<&Unit>,
+        changing: &CellAddr,
+    ) -> Result<GoalSeekResult, WorkbookError> {
+        let sheet = self
+            .sheets
+            .get(sheet_index)
+            .ok_or(WorkbookError::InvalidSheetIndex(sheet_index))?;
+        let original = sheet
+            .get(changing)
+            .filter(|cell| cell.is_number() && !cell.is_formula())
+            .cloned()
+            .ok_or_else(|| {
+                WorkbookError::GoalSeek(format!("{} must hold a number to change", changing))
+            })?;
+        let target_unit = sheet
+            .get(target)
+            .filter(|cell| cell.is_formula() && cell.is_number())
+            .map(|cell| cell.storage_unit().clone())
+            .ok_or_else(|| {
+                WorkbookError::GoalSeek(format!(
+                    "{} must hold a formula that calculates to a number",
+                    target
+                ))
+            })?;
+
+        let goal = match goal_unit {
+            Some(unit) => self.convert(goal, unit, &target_unit).ok_or_else(|| {
+                WorkbookError::GoalSeek(format!(
+                    "Cannot convert the goal from {} to {}, the unit of {}",
+                    unit, target_unit, target
+                ))
+            })?,
+            None => goal,
+        };
+        let tolerance = RELATIVE_TOLERANCE * goal.abs().max(1.0);
+        let start = original.as_number().unwrap_or_default();
+
+        let solution = solve::<WorkbookError>(start, tolerance, |value| {
+            let target_value = self.try_value(
+                sheet_index,
+                changing,
+                &original,
+                value,
+                target,
+                &target_unit,
+            )?;
+            Ok(target_value.map(|target_value| target_value - goal))
+        })?;
+
+        match solution {
+            Some(solution) => Ok(GoalSeekResult {
+                value: solution.value,
+                unit: original.storage_unit().to_string(),
+                target_value: goal + solution.residual,
+                target_unit: target_unit.to_string(),
+                iterations: solution.iterations,
+            }),
+            None => {
+                self.set_cell(sheet_index, changing.clone(), original)?;
+                Err(WorkbookError::GoalSeek(format!(
+                    "No value of {} makes {} reach {} {}",
+                    changing, target, goal, target_unit
+                )))
+            }
+        }
+    }
+
+    /// Put a value in the changing cell, recalculate, and read the target in its unit
+    /// None if the target does not calculate to a number in a compatible unit
+    fn try_value(
+        &mut self,
+        sheet_index: usize,
+        changing: &CellAddr,
+        original: &Cell,
+        value: f64,
+        target: &CellAddr,
+        target_unit: &Unit,
+    ) -> Result<Option<f64>, WorkbookError> {
+        let mut cell = original.clone();
+        cell.set_value(CellValue::Number(value));
+        cell.set_uncertainty(None);
+        cell.set_significant_figures(None);
+        self.set_cell(sheet_index, changing.clone(), cell)?;
+
+        Ok(self.sheets[sheet_index]
+            .get(target)
+            .and_then(|cell| self.convert(cell.as_number()?, cell.storage_unit(), target_unit)))
+    }
+
+    /// Convert a number between units the way CONVERT does, so compound units work too
+    fn convert(&self, value: f64, from: &Unit, to: &Unit) -> Option<f64> {
+        if from.is_equal(to) {
+            return Some(value);
+        }
+        let expr = Expr::Function {
+            name: "CONVERT".to_string(),
+            args: vec![
+                Expr::number_with_unit(value, from.canonical()),
+                Expr::string(to.canonical()),
+            ],
+        };
+        Evaluator::new(self.active_sheet().library())
+            .eval(&expr)
+            .ok()?
+            .as_number()
+    }
+}
+
+/// A value that brought the target within the tolerance of the goal
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct Solution {
+    value: f64,
+    /// How far the target is from the goal
+    residual: f64,
+    /// Number of values tried
+    iterations: usize,
+}
+
+/// Find a value for which `residual` (how far the target is from the goal, or None if the
+/// target has no value) is within `tolerance` of 0, starting from `start`
+/// The solution is always the last value tried, so the workbook is left showing it
+fn solve<E>(
+    start: f64,
+    tolerance: f64,
+    residual: impl FnMut(f64) -> Result<Option<f64>, E>,
+) -> Result<Option<Solution>, E> {
+    let mut search = Search {
+        residual,
+        tolerance,
+        iterations: 0,
+    };
+    let opposite = |a: (f64, f64), b: (f64, f64)| (a.1 < 0.0) != (b.1 < 0.0);
+
+    // The first step is 1% of the starting value, or 1 if it starts at 0
+    let step = if start == 0.0 {
+        1.0
+    } else {
+        start.abs() * 0.01
+    };
+
+    let mut bracket = None;
+    if let Some(first) = search.try_value(start)? {
+        if search.converged(first) {
+            return Ok(Some(search.solution(first)));
+        }
+
+        // Secant steps from the starting value
+        let mut previous = first;
+        if let Some(mut latest) = search.try_value(start + step)? {
+            for _ in 0..MAX_ITERATIONS {
+                if search.converged(latest) {
+                    return Ok(Some(search.solution(latest)));
+                }
+                if opposite(previous, latest) {
+                    bracket = Some((previous, latest));
+                    break;
+                }
+                let next = secant(previous, latest);
+                let Some(next) = next.is_finite().then_some(next) else {
+                    break;
+                };
+                let Some(next) = search.try_value(next)? else {
+                    break;
+                };
+                previous = latest;
+                latest = next;
+            }
+        }
+
+        // Look further and further out on both sides for a value past the goal
+        if bracket.is_none() {
+            'expand: for expansion in 0..MAX_EXPANSIONS {
+                let distance = step * 2f64.powi(expansion as i32);
+                for value in [start + distance, start - distance] {
+                    if let Some(point) = search.try_value(value)? {
+                        if search.converged(point) {
+                            return Ok(Some(search.solution(point)));
+                        }
+                        if opposite(first, point) {
+                            bracket = Some((first, point));
+                            break 'expand;
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    let Some((mut low, mut high)) = bracket else {
+        return Ok(None);
+    };
+
+    // Narrow in on the goal between two values either side of it
+    let (mut previous, mut latest) = (low, high);
+    let mut bisect = false;
+    for _ in 0..MAX_ITERATIONS {
+        let (min, max) = (low.0.min(high.0), low.0.max(high.0));
+        let middle = min + (max - min) / 2.0;
+        if middle <= min || middle >= max {
+            // The two values are as close as numbers get, so the target jumps past the goal
+            break;
+        }
+
+        let next = secant(previous, latest);
+        let next = if !bisect && next > min && next < max {
+            next
+        } else {
+            middle
+        };
+        let point = match search.try_value(next)? {
+            Some(point) => point,
+            None if next != middle => match search.try_value(middle)? {
+                Some(point) => point,
+                None => break,
+            },
+            None => break,
+        };
+        if search.converged(point) {
+            return Ok(Some(search.solution(point)));
+        }
+
+        if opposite(low, point) {
+            high = point;
+        } else {
+            low = point;
+        }
+        // Halve the gap next time if this step did not
+        bisect = (high.0 - low.0).abs() > (max - min) / 2.0;
+        previous = latest;
+        latest = point;
+    }
+
+    Ok(None)
+}
+
+/// Where the line through two tries crosses the goal
+fn secant((x0, r0): (f64, f64), (x1, r1): (f64, f64)) -> f64 {
+    x1 - r1 * (x1 - x0) / (r1 - r0)
+}
+
+/// The tries goal seek has made, as (value, residual) pairs
+struct Search<F> {
+    residual: F,
+    tolerance: f64,
+    iterations: usize,
+}
+
+impl<F> Search<F> {
+    /// Try a value, giving None if the target has no value for it
+    fn try_value<E>(&mut self, value: f64) -> Result<Option<(f64, f64)>, E>
+    where
+        F: FnMut(f64) -> Result<Option<f64>, E>,
+    {
+        self.iterations += 1;
+        Ok((self.residual)(value)?
+            .filter(|residual| residual.is_finite())
+            .map(|residual| (value, residual)))
+    }
+
+    fn converged(&self, (_, residual): (f64, f64)) -> bool {
+        residual.abs() <= self.tolerance
+    }
+
+    fn solution(&self, (value, residual): (f64, f64)) -> Solution {
+        Solution {
+            value,
+            residual,
+            iterations: self.iterations,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::convert::Infallible;
+
+    fn solve_fn(start: f64, f: impl Fn(f64) -> Option<f64>) -> Option<Solution> {
+        solve::<Infallible>(start, 1e-9, |x| Ok(f(x))).unwrap()
+    }
+
+    #[test]
+    fn test_solve() {
+        // Linear: found by the first secant step
+        let solution = solve_fn(10.0, |x| Some(3.0 * x - 12.0)).unwrap();
+        assert!((solution.value - 4.0).abs() < 1e-9);
+        assert_eq!(solution.iterations, 3);
+
+        // Smooth curves
+        let root = solve_fn(1.0, |x| Some(x * x - 2.0)).unwrap();
+        assert!((root.value - 2f64.sqrt()).abs() < 1e-9);
+        let root = solve_fn(0.0, |x| Some(x.exp() - 1000.0)).unwrap();
+        assert!((root.value - 1000f64.ln()).abs() < 1e-9);
+
+        // Flat at the start, so the secant steps stall and the search looks further out
+        let root = solve_fn(0.0, |x| Some(if x < 50.0 { -1.0 } else { x - 60.0 })).unwrap();
+        assert!((root.value - 60.0).abs() < 1e-9);
+
+        // Steps past where the target is defined are skipped
+        let root = solve_fn(2.0, |x| (x > 0.0).then(|| x.ln())).unwrap();
+        assert!((root.value - 1.0).abs() < 1e-9);
+
+        // No solution: never reaches the goal, or jumps past it
+        assert_eq!(solve_fn(1.0, |x| Some(x * x + 1.0)), None);
+        assert_eq!(
+            solve_fn(1.0, |x| Some(if x < 3.0 { -1.0 } else { 1.0 })),
+            None
+        );
+    }
+}
@@ -1,10 +1,27 @@
 // Workbook management
 
-use crate::core::table::{CellAddr, Sheet};
+use crate::core::cell::{Cell, CellError, CellValue, ErrorKind};
+use crate::core::formula::{
+    parse_formula, parse_lambda, EvalError, EvalResult, Expr, FunctionRegistry, FunctionSpec,
+    ResolvedReference,
+};
+use crate::core::table::sheet::{
+    extract_cell_refs, extract_function_names, extract_named_refs, extract_range_refs,
+    extract_sheet_range_refs, extract_sheet_refs, extract_unit_literals, MAX_SPILL_PASSES,
+};
+use crate::core::table::{
+    CellAddr, CellRange, DependencyGraph, DependencyKey, RowColumnChange, Sheet, SheetError,
+};
+use crate::core::units::{parse_unit, Unit};
 use serde::{Deserialize, Serialize};
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
+use std::sync::Arc;
 use thiserror::Error;
 
+mod goal_seek;
+
+pub use goal_seek::GoalSeekResult;
+
 #[derive(Debug, Error)]
 pub enum WorkbookError {
     #[error("Sheet not found: {0}")]
@@ -13,6 +30,9 @@ pub enum WorkbookError {
     #[error("Sheet name already exists: {0}")]
     SheetNameExists(String),
 
+    #[error("Invalid sheet name: \"{0}\" (names cannot be empty or contain [ ] : * ? / \\)")]
+    InvalidSheetName(String),
+
     #[error("Cannot remove the last sheet")]
     CannotRemoveLastSheet,
 
@@ -27,6 +47,24 @@ pub enum WorkbookError {
 
     #[error("Invalid name: {0}")]
     InvalidName(String),
+
+    #[error("Name already in use: {0}")]
+    NameInUse(String),
+
+    #[error("Function not found: {0}")]
+    FunctionNotFound(String),
+
+    #[error("Invalid function definition: {0}")]
+    InvalidFunction(String),
+
+    #[error("Invalid setting: {0}")]
+    InvalidSetting(String),
+
+    #[error("Goal seek failed: {0}")]
+    GoalSeek(String),
+
+    #[error("Sheet error: {0}")]
+    SheetError(#[from] SheetError),
 }
 
 /// Display preference for units
@@ -57,6 +95,40 @@ pub struct WorkbookSettings {
 
     /// Show warnings for unit mismatches
     pub show_warnings: bool,
+
+    /// Show numbers to the significant figures they are known to (12.0 m * 3.0 m = 36 m²)
+    #[serde(default)]
+    pub significant_figures: bool,
+
+    /// Seed for RAND and RANDBETWEEN, so that the workbook always shows the same numbers
+    /// None draws new numbers on every recalculation
+    #[serde(default)]
+    pub random_seed: Option<u64>,
+
+    /// Calculate circular references by iterating (interest on an average balance, a tax
+    /// gross-up). None rejects them
+    #[serde(default)]
+    pub iterative_calculation: Option<IterativeCalculation>,
+}
+
+/// How circular references are calculated when iterative calculation is on
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct IterativeCalculation {
+    /// Most times the cells of a circular reference are calculated before giving up
+    pub max_iterations: usize,
+
+    /// Largest change from one iteration to the next at which a cell has settled, in the
+    /// cell's own unit (0.001 is a tenth of a cent in a USD cell)
+    pub tolerance: f64,
+}
+
+impl Default for IterativeCalculation {
+    fn default() -> Self {
+        Self {
+            max_iterations: 100,
+            tolerance: 0.001,
+        }
+    }
 }
 
 impl Default for WorkbookSettings {
@@ -65,8 +137,47 @@ impl Default for WorkbookSettings {
             display_preference: DisplayPreference::AsEntered,
             auto_recalculate: true,
             show_warnings: true,
+            significant_figures: false,
+            random_seed: None,
+            iterative_calculation: None,
+        }
+    }
+}
+
+/// Functions that work out which cells they read as they are evaluated (INDIRECT("B" & A1))
+const REFERENCE_FUNCTIONS: [&str; 2] = ["INDIRECT", "OFFSET"];
+
+/// A node in the workbook dependency graph
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum DependencyNode {
+    /// A cell on a sheet (sheet_index, cell_address)
+    Cell(usize, CellAddr),
+
+    /// A named range, which depends on the cell it refers to, or a workbook function
+    Name(String),
+}
+
+impl DependencyKey for DependencyNode {
+    /// A range of cells on one sheet (sheet_index, range)
+    type Range = (usize, CellRange);
+    /// A column of one sheet (sheet_index, column)
+    type Bucket = (usize, usize);
+
+    fn in_range(&self, (range_sheet, range): &(usize, CellRange)) -> bool {
+        matches!(self, DependencyNode::Cell(sheet_index, addr) if sheet_index == range_sheet && range.contains(addr))
+    }
+
+    fn bucket(&self) -> Option<(usize, usize)> {
+        match self {
+            DependencyNode::Cell(sheet_index, addr) => Some((*sheet_index, addr.bucket()?)),
+            DependencyNode::Name(_) => None,
         }
     }
+
+    fn range_buckets((sheet_index, range): &(usize, CellRange)) -> Option<Vec<(usize, usize)>> {
+        let columns = CellAddr::range_buckets(range)?;
+        Some(columns.into_iter().map(|col| (*sheet_index, col)).collect())
+    }
 }
 
 /// A workbook containing multiple sheets
@@ -87,6 +198,30 @@ pub struct Workbook {
     /// Named cell references (name -> (sheet_index, cell_address))
     named_ranges: HashMap<String, (usize, CellAddr)>,
 
+    /// Functions defined in the workbook with LAMBDA (lower-case name -> definition)
+    lambdas: HashMap<String, String>,
+
+    /// Dependencies between cells and named ranges across all sheets
+    dependencies: DependencyGraph<DependencyNode>,
+
+    /// Cells whose formulas call volatile functions (NOW, RAND), which every recalculation
+    /// includes
+    volatile_cells: HashSet<DependencyNode>,
+
+    /// The references each cell's INDIRECT and OFFSET calls resolved when the cell was last
+    /// calculated, which the cell depends on along with the references written in its formula
+    resolved_references: HashMap<DependencyNode, Vec<ResolvedReference>>,
+
+    /// Cells calling INDIRECT or OFFSET whose references are not known yet (e.g. after the
+    /// workbook is opened), which the next recalculation includes
+    unresolved_cells: HashSet<DependencyNode>,
+
+    /// Functions available to formulas on every sheet, including the workbook's own
+    functions: Arc<FunctionRegistry>,
+
+    /// The functions the workbook's own functions are added to
+    base_functions: Arc<FunctionRegistry>,
+
     /// Dirty flag (has unsaved changes)
     dirty: bool,
 }
@@ -94,12 +229,21 @@ pub struct Workbook {
 impl Workbook {
     /// Create a new workbook with a single sheet
     pub fn new(name: impl Into<String>) -> Self {
+        let mut sheet = Sheet::with_name("Sheet1");
+        sheet.set_track_dependencies(false);
         let mut workbook = Self {
             name: name.into(),
-            sheets: vec![Sheet::with_name("Sheet1")],
+            sheets: vec![sheet],
             active_sheet: 0,
             settings: WorkbookSettings::default(),
             named_ranges: HashMap::new(),
+            lambdas: HashMap::new(),
+            dependencies: DependencyGraph::new(),
+            volatile_cells: HashSet::new(),
+            resolved_references: HashMap::new(),
+            unresolved_cells: HashSet::new(),
+            functions: FunctionRegistry::standard().clone(),
+            base_functions: FunctionRegistry::standard().clone(),
             dirty: false,
         };
         workbook.mark_clean(); // New workbook starts clean
@@ -176,7 +320,10 @@ impl Workbook {
     /// Add a new sheet with a specific name
     pub fn add_sheet_with_name(&mut self, name: impl Into<String>) -> usize {
         let name = name.into();
-        let sheet = Sheet::with_name(name);
+        let mut sheet = Sheet::with_name(name);
+        sheet.set_functions(self.functions.clone());
+        sheet.set_random_seed(self.settings.random_seed);
+        sheet.set_track_dependencies(false);
         self.sheets.push(sheet);
         self.mark_dirty();
         self.sheets.len() - 1
@@ -199,8 +346,51 @@ impl Workbook {
             self.active_sheet -= 1;
         }
 
+        let removed = self.sheets.remove(index);
+
+        // Named ranges on the removed sheet go away, later sheets shift down
+        self.named_ranges
+            .retain(|_, (sheet_idx, _)| *sheet_idx != index);
+        for (sheet_idx, _) in self.named_ranges.values_mut() {
+            if *sheet_idx > index {
+                *sheet_idx -= 1;
+            }
+        }
+        self.resolved_references = std::mem::take(&mut self.resolved_references)
+            .into_iter()
+            .filter_map(|(node, references)| match node {
+                DependencyNode::Cell(sheet_idx, _) if sheet_idx == index => None,
+                DependencyNode::Cell(sheet_idx, addr) if sheet_idx > index => {
+                    Some((DependencyNode::Cell(sheet_idx - 1, addr), references))
+                }
+                node => Some((node, references)),
+            })
+            .collect();
+
+        // References to the removed sheet become #REF!
+        let changed: Vec<Vec<CellAddr>> = self
+            .sheets
+            .iter_mut()
+            .map(|sheet| sheet.rename_sheet_references(removed.name(), None))
+            .collect();
+
+        // Sheet indices have shifted, so rebuild the graph from scratch
+        self.rebuild_dependencies();
+        let mut changed: Vec<DependencyNode> = changed
+            .into_iter()
+            .enumerate()
+            .flat_map(|(sheet_idx, cells)| {
+                cells
+                    .into_iter()
+                    .map(move |addr| DependencyNode::Cell(sheet_idx, addr))
+            })
+            .collect();
+        // INDIRECT("Removed!A1") keeps its text, and now gives #REF!
+        changed.extend(self.cells_resolving_into(removed.name()));
+        self.recalculate(&changed)?;
+
         self.mark_dirty();
-        Ok(self.sheets.remove(index))
+        Ok(removed)
     }
 
     /// Rename a sheet
@@ -211,6 +401,11 @@ impl Workbook {
     ) -> Result<(), WorkbookError> {
         let new_name = new_name.into();
 
+        // Formulas refer to sheets by name, so the name must be one they can be written with
+        if !Self::is_valid_sheet_name(&new_name) {
+            return Err(WorkbookError::InvalidSheetName(new_name));
+        }
+
         // Check if name already exists (excluding the sheet being renamed)
         if self
             .sheets
@@ -226,7 +421,24 @@ impl Workbook {
             .get_mut(index)
             .ok_or(WorkbookError::InvalidSheetIndex(index))?;
 
-        sheet.set_name(new_name);
+        let old_name = sheet.name().to_string();
+        sheet.set_name(new_name.clone());
+
+        // Point sheet-qualified references at the new name
+        if old_name != new_name {
+            let mut changed = Vec::new();
+            for (sheet_idx, sheet) in self.sheets.iter_mut().enumerate() {
+                for addr in sheet.rename_sheet_references(&old_name, Some(&new_name)) {
+                    changed.push(DependencyNode::Cell(sheet_idx, addr));
+                }
+            }
+            // References written as text in INDIRECT keep the old name (and now give #REF!),
+            // or may name the sheet by its new name
+            changed.extend(self.cells_resolving_into(&old_name));
+            changed.extend(self.cells_resolving_into(&new_name));
+            self.recalculate(&changed)?;
+        }
+
         self.mark_dirty();
         Ok(())
     }
@@ -247,6 +459,50 @@ impl Workbook {
         &mut self.settings
     }
 
+    /// Show numbers to their significant figures or as they are
+    /// Precision is tracked either way, so nothing is recalculated
+    pub fn set_significant_figures(&mut self, enabled: bool) {
+        self.settings.significant_figures = enabled;
+        self.mark_dirty();
+    }
+
+    /// Seed RAND and RANDBETWEEN, or None for new numbers on every recalculation,
+    /// and recalculate the cells that draw random numbers
+    pub fn set_random_seed(&mut self, seed: Option<u64>) -> Result<(), WorkbookError> {
+        self.settings.random_seed = seed;
+        for sheet in &mut self.sheets {
+            sheet.set_random_seed(seed);
+        }
+        self.mark_dirty();
+        // Volatile cells are part of every recalculation
+        self.recalculate(&[])
+    }
+
+    /// Turn iterative calculation of circular references on (with its limits) or off, and
+    /// recalculate. Turned off, the cells of circular references show #CIRC!
+    pub fn set_iterative_calculation(
+        &mut self,
+        iteration: Option<IterativeCalculation>,
+    ) -> Result<(), WorkbookError> {
+        if let Some(iteration) = iteration {
+            if iteration.max_iterations == 0 {
+                return Err(WorkbookError::InvalidSetting(
+                    "At least one iteration is needed".to_string(),
+                ));
+            }
+            if !(iteration.tolerance >= 0.0 && iteration.tolerance.is_finite()) {
+                return Err(WorkbookError::InvalidSetting(format!(
+                    "The tolerance must be a number no less than 0, not {}",
+                    iteration.tolerance
+                )));
+            }
+        }
+
+        self.settings.iterative_calculation = iteration;
+        self.mark_dirty();
+        self.recalculate_all()
+    }
+
     /// Set display preference
     pub fn set_display_preference(&mut self, pref: DisplayPreference) {
         self.settings.display_preference = pref;
@@ -281,15 +537,21 @@ impl Workbook {
         if !Self::is_valid_name(&name) {
             return Err(WorkbookError::InvalidName(name));
         }
+        if self.lambdas.contains_key(&name.to_lowercase()) {
+            return Err(WorkbookError::NameInUse(name));
+        }
 
         // Validate sheet index
         if sheet_index >= self.sheets.len() {
             return Err(WorkbookError::InvalidSheetIndex(sheet_index));
         }
 
-        self.named_ranges.insert(name, (sheet_index, addr));
+        self.named_ranges.insert(name.clone(), (sheet_index, addr));
+        self.update_named_range_dependency(&name);
         self.mark_dirty();
-        Ok(())
+
+        // Formulas using the name pick up its (new) value
+        self.recalculate(&[DependencyNode::Name(name)])
     }
 
     /// Get a named range
@@ -300,8 +562,9 @@ impl Workbook {
     /// Remove a named range
     pub fn remove_named_range(&mut self, name: &str) -> Result<(), WorkbookError> {
         if self.named_ranges.remove(name).is_some() {
+            self.update_named_range_dependency(name);
             self.mark_dirty();
-            Ok(())
+            self.recalculate(&[DependencyNode::Name(name.to_string())])
         } else {
             Err(WorkbookError::NamedRangeNotFound(name.to_string()))
         }
@@ -315,6 +578,118 @@ impl Workbook {
             .collect()
     }
 
+    /// Define a workbook function, e.g. `monthly_cost` as
+    /// `LAMBDA(rate: USD/hr, util, rate*util*730 hr/month)`, replacing any earlier definition,
+    /// and recalculate the formulas that call it
+    pub fn define_function(
+        &mut self,
+        name: impl Into<String>,
+        definition: impl Into<String>,
+    ) -> Result<(), WorkbookError> {
+        let name = name.into();
+        let definition = definition.into();
+
+        if !Self::is_valid_name(&name) {
+            return Err(WorkbookError::InvalidName(name));
+        }
+        // Built-in functions and named ranges keep their meaning
+        if self.base_functions.get(&name).is_some()
+            || self
+                .named_ranges
+                .keys()
+                .any(|range| range.eq_ignore_ascii_case(&name))
+        {
+            return Err(WorkbookError::NameInUse(name));
+        }
+        parse_lambda(&definition).map_err(|e| WorkbookError::InvalidFunction(e.to_string()))?;
+
+        let name = name.to_lowercase();
+        self.lambdas.insert(name.clone(), definition);
+        self.functions_changed(name)
+    }
+
+    /// Get the definition of a workbook function
+    pub fn get_function(&self, name: &str) -> Option<&str> {
+        self.lambdas.get(&name.to_lowercase()).map(String::as_str)
+    }
+
+    /// Remove a workbook function; formulas calling it show #NAME?
+    pub fn remove_function(&mut self, name: &str) -> Result<(), WorkbookError> {
+        let name = name.to_lowercase();
+        if self.lambdas.remove(&name).is_none() {
+            return Err(WorkbookError::FunctionNotFound(name));
+        }
+        self.functions_changed(name)
+    }
+
+    /// List the workbook functions and their definitions, sorted by name
+    pub fn list_functions(&self) -> Vec<(String, String)> {
+        let mut functions: Vec<(String, String)> = self
+            .lambdas
+            .iter()
+            .map(|(name, definition)| (name.clone(), definition.clone()))
+            .collect();
+        functions.sort();
+        functions
+    }
+
+    /// Make a new or changed workbook function available and recalculate its callers
+    fn functions_changed(&mut self, name: String) -> Result<(), WorkbookError> {
+        self.install_functions();
+        // Calls can reach cells through the function's body, so track them again
+        self.rebuild_dependencies();
+        self.mark_dirty();
+        self.recalculate(&[DependencyNode::Name(name)])
+    }
+
+    /// Give every sheet the base functions plus the workbook's own
+    fn install_functions(&mut self) {
+        let functions = if self.lambdas.is_empty() {
+            self.base_functions.clone()
+        } else {
+            let mut registry = (*self.base_functions).clone();
+            for (name, definition) in &self.lambdas {
+                if let Ok(lambda) = parse_lambda(definition) {
+                    registry.register(FunctionSpec::lambda(name, lambda));
+                }
+            }
+            Arc::new(registry)
+        };
+
+        for sheet in &mut self.sheets {
+            sheet.set_functions(functions.clone());
+        }
+        self.functions = functions;
+    }
+
+    /// The workbook functions an expression calls, directly or through other workbook
+    /// functions, with the bodies of those that are defined. Calls to functions that are not
+    /// built in are included even when undefined, so that defining them recalculates the caller
+    fn called_functions(&self, expr: &Expr) -> Vec<(String, Option<Expr>)> {
+        let mut called: Vec<(String, Option<Expr>)> = Vec::new();
+        let mut pending: Vec<String> = extract_function_names(expr).into_iter().collect();
+
+        while let Some(name) = pending.pop() {
+            let name = name.to_lowercase();
+            if self.base_functions.get(&name).is_some()
+                || called.iter().any(|(seen, _)| *seen == name)
+            {
+                continue;
+            }
+            let body = self
+                .lambdas
+                .get(&name)
+                .and_then(|definition| parse_lambda(definition).ok())
+                .map(|lambda| lambda.body);
+            if let Some(body) = &body {
+                pending.extend(extract_function_names(body));
+            }
+            called.push((name, body));
+        }
+
+        called
+    }
+
     /// Get the named range for a specific cell address on a sheet
     /// Returns the name if the cell has a named range, None otherwise
     pub fn get_named_range_for_cell(&self, sheet_index: usize, addr: &CellAddr) -> Option<String> {
@@ -355,116 +730,910 @@ impl Workbook {
             .collect()
     }
 
-    /// Check if a name is valid for a named range
-    /// Must start with lowercase letter or underscore, contain only alphanumerics and underscores
-    fn is_valid_name(name: &str) -> bool {
-        if name.is_empty() {
-            return false;
+    /// Set a cell on a sheet, update the workbook dependency graph, and recalculate
+    /// every cell (on any sheet) that depends on it
+    /// The workbook's graph is the only one kept for its sheets, so that references within a
+    /// sheet, between sheets and through names are all checked for loops in one place
+    pub fn set_cell(
+        &mut self,
+        sheet_index: usize,
+        addr: CellAddr,
+        cell: Cell,
+    ) -> Result<(), WorkbookError> {
+        let sheet = self
+            .sheets
+            .get_mut(sheet_index)
+            .ok_or(WorkbookError::InvalidSheetIndex(sheet_index))?;
+        let previous = sheet.get(&addr).cloned();
+        sheet.set(addr.clone(), cell)?;
+
+        // What the old formula resolved no longer applies
+        let node = DependencyNode::Cell(sheet_index, addr.clone());
+        let previous_references = self.resolved_references.remove(&node);
+        self.update_dependencies(sheet_index, &addr);
+
+        // Check for circular references, including through other sheets and names, which are
+        // only allowed if they are calculated by iterating. A rejected formula leaves the
+        // cell as it was
+        if self.settings.iterative_calculation.is_none()
+            && self.dependencies.has_circular_reference(&node)
+        {
+            let sheet = &mut self.sheets[sheet_index];
+            match previous {
+                Some(previous) => sheet.set(addr.clone(), previous)?,
+                None => {
+                    sheet.remove(&addr);
+                }
+            }
+            if let Some(references) = previous_references {
+                self.resolved_references.insert(node, references);
+            }
+            self.update_dependencies(sheet_index, &addr);
+            return Err(SheetError::CircularReference(format!(
+                "{}!{}",
+                self.sheets[sheet_index].name(),
+                addr
+            ))
+            .into());
         }
 
-        let mut chars = name.chars();
-        let first = chars.next().unwrap();
+        self.mark_dirty();
+        self.recalculate(&[node])
+    }
 
-        // Must start with lowercase letter or underscore
-        if !first.is_ascii_lowercase() && first != '_' {
-            return false;
-        }
+    /// Copy a block of cells on a sheet, rewriting relative references (see `Sheet::copy_range`),
+    /// and recalculate the pasted cells and everything that depends on them
+    pub fn copy_range(
+        &mut self,
+        sheet_index: usize,
+        src: &CellRange,
+        dest: &CellRange,
+    ) -> Result<(), WorkbookError> {
+        self.rearrange(sheet_index, |sheet| sheet.copy_range(src, dest))
+    }
 
-        // Rest must be alphanumeric or underscore
-        chars.all(|c| c.is_ascii_alphanumeric() || c == '_')
+    /// Move a block of cells on a sheet (see `Sheet::move_range`) and recalculate
+    pub fn move_range(
+        &mut self,
+        sheet_index: usize,
+        src: &CellRange,
+        dest: &CellAddr,
+    ) -> Result<(), WorkbookError> {
+        self.rearrange(sheet_index, |sheet| sheet.move_range(src, dest))
     }
-}
 
-impl Default for Workbook {
-    fn default() -> Self {
-        Self::new("Untitled")
+    /// Insert a column before `col` on a sheet (see `change_rows_or_columns`)
+    pub fn insert_column_before(
+        &mut self,
+        sheet_index: usize,
+        col: &str,
+    ) -> Result<(), WorkbookError> {
+        self.change_rows_or_columns(sheet_index, |sheet| sheet.insert_column_before(col))
     }
-}
 
-#[cfg(test)]
-mod tests {
-    use super::*;
-    use crate::core::cell::Cell;
-    use crate::core::table::CellAddr;
-    use crate::core::units::{BaseDimension, Unit};
+    /// Insert a column after `col` on a sheet (see `change_rows_or_columns`)
+    pub fn insert_column_after(
+        &mut self,
+        sheet_index: usize,
+        col: &str,
+    ) -> Result<(), WorkbookError> {
+        self.change_rows_or_columns(sheet_index, |sheet| sheet.insert_column_after(col))
+    }
 
-    #[test]
-    fn test_workbook_creation() {
-        let wb = Workbook::new("Test Workbook");
-        assert_eq!(wb.name(), "Test Workbook");
-        assert_eq!(wb.sheet_count(), 1);
-        assert_eq!(wb.active_sheet().name(), "Sheet1");
-        assert!(!wb.is_dirty());
+    /// Insert a row before `row` on a sheet (see `change_rows_or_columns`)
+    pub fn insert_row_before(
+        &mut self,
+        sheet_index: usize,
+        row: usize,
+    ) -> Result<(), WorkbookError> {
+        self.change_rows_or_columns(sheet_index, |sheet| sheet.insert_row_before(row))
     }
 
-    #[test]
-    fn test_add_sheets() {
-        let mut wb = Workbook::new("Test");
+    /// Insert a row after `row` on a sheet (see `change_rows_or_columns`)
+    pub fn insert_row_after(
+        &mut self,
+        sheet_index: usize,
+        row: usize,
+    ) -> Result<(), WorkbookError> {
+        self.change_rows_or_columns(sheet_index, |sheet| sheet.insert_row_after(row))
+    }
 
-        let idx1 = wb.add_sheet();
-        assert_eq!(idx1, 1);
-        assert_eq!(wb.sheet_count(), 2);
+    /// Delete a column on a sheet (see `change_rows_or_columns`)
+    pub fn delete_column(&mut self, sheet_index: usize, col: &str) -> Result<(), WorkbookError> {
+        self.change_rows_or_columns(sheet_index, |sheet| sheet.delete_column(col))
+    }
 
-        let idx2 = wb.add_sheet_with_name("Custom");
-        assert_eq!(idx2, 2);
-        assert_eq!(wb.get_sheet(2).unwrap().name(), "Custom");
-        assert!(wb.is_dirty());
+    /// Delete a row on a sheet (see `change_rows_or_columns`)
+    pub fn delete_row(&mut self, sheet_index: usize, row: usize) -> Result<(), WorkbookError> {
+        self.change_rows_or_columns(sheet_index, |sheet| sheet.delete_row(row))
     }
 
-    #[test]
-    fn test_remove_sheet() {
-        let mut wb = Workbook::new("Test");
-        wb.add_sheet();
-        wb.add_sheet();
+    /// Insert or delete a row or column on a sheet, point the references into the sheet from
+    /// every sheet (`Sheet2!A5`) and the names on it at where their cells moved, and
+    /// recalculate. References to deleted cells become #REF!, and names on them are removed
+    fn change_rows_or_columns(
+        &mut self,
+        sheet_index: usize,
+        change: impl FnOnce(&mut Sheet) -> Result<RowColumnChange, String>,
+    ) -> Result<(), WorkbookError> {
+        let sheet = self
+            .sheets
+            .get_mut(sheet_index)
+            .ok_or(WorkbookError::InvalidSheetIndex(sheet_index))?;
+        let change = change(sheet).map_err(SheetError::InvalidCellRef)?;
 
-        assert_eq!(wb.sheet_count(), 3);
+        let sheet_name = sheet.name().to_string();
+        for sheet in &mut self.sheets {
+            sheet.shift_sheet_references(&sheet_name, change);
+        }
+        self.named_ranges.retain(|_, (idx, addr)| {
+            if *idx != sheet_index {
+                return true;
+            }
+            match change.apply_to_cell(addr) {
+                Some(moved) => {
+                    *addr = moved;
+                    true
+                }
+                None => false,
+            }
+        });
 
-        let removed = wb.remove_sheet(1).unwrap();
-        assert_eq!(removed.name(), "Sheet2");
-        assert_eq!(wb.sheet_count(), 2);
+        // Cell addresses moved, so re-track what depends on what (including what INDIRECT
+        // and OFFSET read) and lay out spilled arrays again
+        self.resolved_references.clear();
+        self.rebuild_dependencies();
+        self.mark_dirty();
+        self.recalculate_all()
     }
 
-    #[test]
-    fn test_cannot_remove_last_sheet() {
-        let mut wb = Workbook::new("Test");
+    /// Copy or move cells on a sheet, re-track dependencies and recalculate the changed cells
+    /// As with `set_cell`, formulas that now refer to themselves are only allowed if they are
+    /// calculated by iterating; otherwise the sheet is left as it was
+    fn rearrange(
+        &mut self,
+        sheet_index: usize,
+        rearrange: impl FnOnce(&mut Sheet) -> Result<Vec<CellAddr>, String>,
+    ) -> Result<(), WorkbookError> {
+        let check_cycles = self.settings.iterative_calculation.is_none();
+        let sheet = self
+            .sheets
+            .get_mut(sheet_index)
+            .ok_or(WorkbookError::InvalidSheetIndex(sheet_index))?;
+        let previous = check_cycles.then(|| sheet.snapshot_cells());
+        let changed = rearrange(sheet).map_err(SheetError::InvalidCellRef)?;
+        self.rebuild_dependencies();
+
+        let nodes: Vec<DependencyNode> = changed
+            .into_iter()
+            .map(|addr| DependencyNode::Cell(sheet_index, addr))
+            .collect();
+        if let Some(previous) = previous {
+            let circular = nodes
+                .iter()
+                .find(|node| self.dependencies.has_circular_reference(node));
+            if let Some(DependencyNode::Cell(_, addr)) = circular {
+                let error = SheetError::CircularReference(format!(
+                    "{}!{}",
+                    self.sheets[sheet_index].name(),
+                    addr
+                ));
+                self.sheets[sheet_index].restore_cells(previous);
+                self.rebuild_dependencies();
+                return Err(error.into());
+            }
+        }
 
-        let result = wb.remove_sheet(0);
-        assert!(result.is_err());
-        assert!(matches!(
-            result.unwrap_err(),
-            WorkbookError::CannotRemoveLastSheet
-        ));
+        self.mark_dirty();
+        self.recalculate(&nodes)
     }
 
-    #[test]
-    fn test_rename_sheet() {
-        let mut wb = Workbook::new("Test");
-        wb.add_sheet();
+    /// Update the dependency graph for a cell after its content changed
+    pub fn update_dependencies(&mut self, sheet_index: usize, addr: &CellAddr) {
+        let node = DependencyNode::Cell(sheet_index, addr.clone());
+        self.dependencies.remove_dependencies(&node);
+        self.volatile_cells.remove(&node);
+        self.unresolved_cells.remove(&node);
+
+        let expr = self
+            .get_sheet(sheet_index)
+            .and_then(|sheet| sheet.get(addr))
+            .and_then(|cell| cell.formula())
+            .and_then(|formula| parse_formula(formula).ok());
+
+        let Some(expr) = expr else {
+            return;
+        };
 
-        wb.rename_sheet(0, "First Sheet").unwrap();
-        assert_eq!(wb.get_sheet(0).unwrap().name(), "First Sheet");
+        // A workbook function's body is evaluated in the calling cell, so its references
+        // count as the cell's own
+        let called = self.called_functions(&expr);
+        for (name, _) in &called {
+            self.dependencies
+                .add_dependency(node.clone(), DependencyNode::Name(name.clone()));
+        }
 
-        wb.rename_sheet(1, "Second Sheet").unwrap();
-        assert_eq!(wb.get_sheet(1).unwrap().name(), "Second Sheet");
-    }
+        let exprs: Vec<Expr> = std::iter::once(expr)
+            .chain(called.into_iter().filter_map(|(_, body)| body))
+            .collect();
 
-    #[test]
-    fn test_duplicate_sheet_name() {
-        let mut wb = Workbook::new("Test");
-        wb.add_sheet();
+        let function_names: HashSet<String> =
+            exprs.iter().flat_map(extract_function_names).collect();
+        if function_names
+            .iter()
+            .any(|name| self.functions.is_volatile(name))
+        {
+            self.volatile_cells.insert(node.clone());
+        }
+        let resolves_references = function_names.iter().any(|name| {
+            REFERENCE_FUNCTIONS
+                .iter()
+                .any(|function| name.eq_ignore_ascii_case(function))
+        });
+        if resolves_references && !self.resolved_references.contains_key(&node) {
+            self.unresolved_cells.insert(node.clone());
+        }
 
-        wb.rename_sheet(0, "MySheet").unwrap();
+        for expr in &exprs {
+            for dep_addr in extract_cell_refs(expr, None) {
+                self.dependencies
+                    .add_dependency(node.clone(), DependencyNode::Cell(sheet_index, dep_addr));
+            }
 
-        let result = wb.rename_sheet(1, "MySheet");
-        assert!(result.is_err());
-        assert!(matches!(
-            result.unwrap_err(),
-            WorkbookError::SheetNameExists(_)
-        ));
-    }
+            for (sheet_name, dep_addr) in extract_sheet_refs(expr) {
+                // References to unknown sheets evaluate to an error, nothing to track
+                if let Some(dep_index) = self.sheets.iter().position(|s| s.name() == sheet_name) {
+                    self.dependencies
+                        .add_dependency(node.clone(), DependencyNode::Cell(dep_index, dep_addr));
+                }
+            }
 
-    #[test]
-    fn test_active_sheet() {
+            for range in extract_range_refs(expr) {
+                self.dependencies
+                    .add_range_dependency(node.clone(), (sheet_index, range));
+            }
+
+            for (sheet_name, range) in extract_sheet_range_refs(expr) {
+                if let Some(dep_index) = self.sheets.iter().position(|s| s.name() == sheet_name) {
+                    self.dependencies
+                        .add_range_dependency(node.clone(), (dep_index, range));
+                }
+            }
+
+            // Names are tracked even before they are defined, so that defining
+            // the name later recalculates this cell
+            for name in extract_named_refs(expr) {
+                self.dependencies
+                    .add_dependency(node.clone(), DependencyNode::Name(name));
+            }
+        }
+
+        // The cells INDIRECT and OFFSET read when the cell was last calculated
+        for (sheet_name, range) in self.resolved_references.get(&node).into_iter().flatten() {
+            let dep_index = match sheet_name {
+                Some(sheet_name) => self.sheets.iter().position(|s| s.name() == sheet_name),
+                None => Some(sheet_index),
+            };
+            if let Some(dep_index) = dep_index {
+                self.dependencies
+                    .add_range_dependency(node.clone(), (dep_index, *range));
+            }
+        }
+    }
+
+    /// Cells whose INDIRECT or OFFSET calls last resolved a reference into the named sheet
+    fn cells_resolving_into(&self, sheet_name: &str) -> Vec<DependencyNode> {
+        self.resolved_references
+            .iter()
+            .filter(|(_, references)| {
+                references
+                    .iter()
+                    .any(|(sheet, _)| sheet.as_deref() == Some(sheet_name))
+            })
+            .map(|(node, _)| node.clone())
+            .collect()
+    }
+
+    /// Replace the references a cell's INDIRECT and OFFSET calls resolved with the ones they
+    /// resolved this time, so that the cell depends on the cells it actually read and not on
+    /// ones it read before. Returns whether the references changed, and the result, which is
+    /// #CIRC! if a reference leads back to the cell
+    fn update_resolved_references(
+        &mut self,
+        sheet_index: usize,
+        addr: &CellAddr,
+        references: Vec<ResolvedReference>,
+        result: Result<EvalResult, SheetError>,
+    ) -> (bool, Result<EvalResult, SheetError>) {
+        let node = DependencyNode::Cell(sheet_index, addr.clone());
+        let previous = if references.is_empty() {
+            self.resolved_references.remove(&node)
+        } else {
+            self.resolved_references
+                .insert(node.clone(), references.clone())
+        };
+
+        // Sheet names are looked up again, as sheets may have been renamed since
+        let changed = previous.unwrap_or_default() != references;
+        if changed || !references.is_empty() {
+            self.update_dependencies(sheet_index, addr);
+        }
+        // The references are known now, even if there were none
+        self.unresolved_cells.remove(&node);
+        if !references.is_empty()
+            && self.settings.iterative_calculation.is_none()
+            && self.dependencies.has_circular_reference(&node)
+        {
+            let error = SheetError::CircularReference(format!(
+                "{}!{}",
+                self.sheets[sheet_index].name(),
+                addr
+            ));
+            return (changed, Err(error));
+        }
+        (changed, result)
+    }
+
+    /// Point a named range node at the cell it refers to
+    fn update_named_range_dependency(&mut self, name: &str) {
+        let node = DependencyNode::Name(name.to_string());
+        self.dependencies.remove_dependencies(&node);
+
+        if let Some((sheet_index, addr)) = self.named_ranges.get(name) {
+            self.dependencies
+                .add_dependency(node, DependencyNode::Cell(*sheet_index, addr.clone()));
+        }
+    }
+
+    /// Rebuild the dependency graph from all formulas and named ranges in the workbook
+    pub fn rebuild_dependencies(&mut self) {
+        self.dependencies = DependencyGraph::new();
+        self.volatile_cells = HashSet::new();
+        self.unresolved_cells = HashSet::new();
+
+        for sheet_index in 0..self.sheets.len() {
+            for addr in self.sheets[sheet_index].cell_addresses() {
+                self.update_dependencies(sheet_index, &addr);
+            }
+        }
+
+        let names: Vec<String> = self.named_ranges.keys().cloned().collect();
+        for name in names {
+            self.update_named_range_dependency(&name);
+        }
+    }
+
+    /// Recalculate everything that depends on the changed nodes, across all sheets,
+    /// in topological order. Cells calling volatile functions (NOW, RAND) are always
+    /// recalculated, with their dependents, as are cells whose INDIRECT and OFFSET references
+    /// are not known yet. Cells that array formulas spilled into have no formula pointing at
+    /// them, so their dependents are recalculated in a further pass, as are cells whose
+    /// INDIRECT and OFFSET calls resolved new references (which may not have been up to date)
+    pub fn recalculate(&mut self, changed: &[DependencyNode]) -> Result<(), WorkbookError> {
+        // Named values are kept current as their source cells are recalculated
+        let mut named_refs = self.resolve_named_ranges();
+
+        let mut changed = changed.to_vec();
+        changed.extend(self.volatile_cells.iter().cloned());
+        changed.extend(self.unresolved_cells.iter().cloned());
+        let mut changed = self.with_spill_anchors(changed);
+        for _ in 0..MAX_SPILL_PASSES {
+            if changed.is_empty() {
+                break;
+            }
+            let spilled = self.recalculate_pass(&changed, &mut named_refs);
+            changed = self.with_spill_anchors(spilled);
+        }
+
+        Ok(())
+    }
+
+    /// Add the array formulas whose spilling changed cells could affect (see `Sheet::spill_anchors_at`)
+    fn with_spill_anchors(&self, mut changed: Vec<DependencyNode>) -> Vec<DependencyNode> {
+        let mut anchors = Vec::new();
+        for node in &changed {
+            if let DependencyNode::Cell(sheet_index, addr) = node {
+                if let Some(sheet) = self.sheets.get(*sheet_index) {
+                    anchors.extend(
+                        sheet
+                            .spill_anchors_at(addr)
+                            .into_iter()
+                            .map(|anchor| DependencyNode::Cell(*sheet_index, anchor)),
+                    );
+                }
+            }
+        }
+        changed.extend(anchors);
+        changed
+    }
+
+    /// Recalculate the changed nodes and their dependents once, returning the cells whose
+    /// spilled values or resolved references changed
+    fn recalculate_pass(
+        &mut self,
+        changed: &[DependencyNode],
+        named_refs: &mut HashMap<String, (f64, Unit)>,
+    ) -> Vec<DependencyNode> {
+        let order = self.dependencies.calculation_order(changed);
+
+        // Cells in a circular reference are calculated together, once everything they read
+        // from outside the loop is up to date
+        let cycles = self.dependencies.cycles(&order);
+        let cycle_of: HashMap<&DependencyNode, usize> = cycles
+            .iter()
+            .enumerate()
+            .flat_map(|(cycle, members)| members.iter().map(move |member| (member, cycle)))
+            .collect();
+        let mut calculated_cycles = HashSet::new();
+
+        let mut spilled = Vec::new();
+        for node in &order {
+            match cycle_of.get(node) {
+                Some(&cycle) => {
+                    if calculated_cycles.insert(cycle) {
+                        let members: Vec<DependencyNode> = order
+                            .iter()
+                            .filter(|member| cycle_of.get(member) == Some(&cycle))
+                            .cloned()
+                            .collect();
+                        spilled.extend(self.calculate_cycle(&members, named_refs));
+                    }
+                }
+                None => spilled.extend(self.calculate_node(node, named_refs)),
+            }
+        }
+
+        spilled
+    }
+
+    /// Calculate a cell's formula, or a named range's value, returning the cells whose
+    /// spilled values or resolved references changed
+    fn calculate_node(
+        &mut self,
+        node: &DependencyNode,
+        named_refs: &mut HashMap<String, (f64, Unit)>,
+    ) -> Vec<DependencyNode> {
+        match node {
+            DependencyNode::Cell(sheet_index, addr) => {
+                let sheet_index = *sheet_index;
+                let Some(sheet) = self.sheets.get(sheet_index) else {
+                    return Vec::new();
+                };
+                let evaluation = sheet.evaluate_cell(addr, Some(&*named_refs), Some(&self.sheets));
+
+                let cells = match evaluation {
+                    Some((result, references)) => {
+                        let (resolved_new, result) =
+                            self.update_resolved_references(sheet_index, addr, references, result);
+                        let mut cells = self.sheets[sheet_index].apply_formula_result(addr, result);
+                        // Cells it now reads may come later in this pass
+                        if resolved_new {
+                            cells.push(addr.clone());
+                        }
+                        cells
+                    }
+                    // A cell that no longer holds a formula no longer spills
+                    None => self.sheets[sheet_index].clear_spill(addr),
+                };
+                cells
+                    .into_iter()
+                    .map(|addr| DependencyNode::Cell(sheet_index, addr))
+                    .collect()
+            }
+            DependencyNode::Name(name) => {
+                // Dependencies are visited first, so the source cell is up to date
+                let value = self.named_ranges.get(name).and_then(|(sheet_index, addr)| {
+                    let cell = self.sheets.get(*sheet_index)?.get(addr)?;
+                    Some((cell.as_number()?, cell.storage_unit().clone()))
+                });
+                match value {
+                    Some(value) => named_refs.insert(name.clone(), value),
+                    None => named_refs.remove(name),
+                };
+                Vec::new()
+            }
+        }
+    }
+
+    /// Calculate the cells of a circular reference, in calculation order
+    /// With iterative calculation on, they are calculated over and over until no value changes
+    /// by more than the tolerance (in the cell's unit) from one time to the next. Otherwise,
+    /// or if the values have not settled within the most iterations allowed, every cell of
+    /// the loop shows #CIRC!, naming the cells in the loop
+    fn calculate_cycle(
+        &mut self,
+        members: &[DependencyNode],
+        named_refs: &mut HashMap<String, (f64, Unit)>,
+    ) -> Vec<DependencyNode> {
+        let names = members
+            .iter()
+            .filter_map(|member| self.node_name(member))
+            .collect::<Vec<_>>()
+            .join(", ");
+        let Some(iteration) = self.settings.iterative_calculation else {
+            let message = format!("Circular reference between {}", names);
+            return self.set_cycle_error(members, message, named_refs);
+        };
+
+        self.seed_cycle(members, named_refs);
+
+        let mut changed = Vec::new();
+        for _ in 0..iteration.max_iterations {
+            let previous: Vec<_> = members
+                .iter()
+                .map(|member| self.node_value(member))
+                .collect();
+            for member in members {
+                changed.extend(self.calculate_node(member, named_refs));
+            }
+
+            let settled = members.iter().zip(&previous).all(|(member, previous)| {
+                match (previous, self.node_value(member)) {
+                    (
+                        Some((CellValue::Number(old), old_unit)),
+                        Some((CellValue::Number(new), unit)),
+                    ) => *old_unit == unit && (new - old).abs() <= iteration.tolerance,
+                    (previous, value) => *previous == value,
+                }
+            });
+            if settled {
+                return changed;
+            }
+        }
+
+        let message = format!(
+            "The circular reference between {} did not settle within {} iterations",
+            names, iteration.max_iterations
+        );
+        changed.extend(self.set_cycle_error(members, message, named_refs));
+        changed
+    }
+
+    /// Give the cells of a circular reference a value to start iterating from
+    /// Cells keep the values they settled on last time, but if none of them holds a number
+    /// (the loop was just entered), one starts at zero in a unit that lets the others be
+    /// calculated from it: no unit, the unit of a cell the loop reads, or a unit written in
+    /// the loop's own formulas (the USD of =1000 USD + A2)
+    fn seed_cycle(
+        &mut self,
+        members: &[DependencyNode],
+        named_refs: &mut HashMap<String, (f64, Unit)>,
+    ) {
+        let holds_number = |workbook: &Self, member: &DependencyNode| {
+            matches!(workbook.node_value(member), Some((CellValue::Number(_), _)))
+        };
+        if members.iter().any(|member| holds_number(self, member)) {
+            return;
+        }
+
+        let mut units = vec![Unit::dimensionless()];
+        for member in members {
+            for dep in self.dependencies.get_dependencies(member) {
+                if members.contains(&dep) {
+                    continue;
+                }
+                if let Some((CellValue::Number(_), unit)) = self.node_value(&dep) {
+                    if !units.contains(&unit) {
+                        units.push(unit);
+                    }
+                }
+            }
+        }
+        for member in members {
+            let DependencyNode::Cell(sheet_index, addr) = member else {
+                continue;
+            };
+            let sheet = &self.sheets[*sheet_index];
+            let Some(Ok(expr)) = sheet.get(addr).and_then(Cell::formula).map(parse_formula) else {
+                continue;
+            };
+            for literal in extract_unit_literals(&expr) {
+                if let Ok(unit) = parse_unit(&literal, sheet.library()) {
+                    if !units.contains(&unit) {
+                        units.push(unit);
+                    }
+                }
+            }
+        }
+
+        for (position, member) in members.iter().enumerate() {
+            let DependencyNode::Cell(sheet_index, addr) = member else {
+                continue;
+            };
+            for unit in &units {
+                let seed = Ok(EvalResult::new(0.0, unit.clone()));
+                self.sheets[*sheet_index].apply_formula_result(addr, seed);
+
+                // The others are calculated from the seed, which comes last
+                for other in members[position + 1..].iter().chain(&members[..=position]) {
+                    self.calculate_node(other, named_refs);
+                }
+                if members.iter().all(|member| {
+                    !matches!(member, DependencyNode::Cell(..)) || holds_number(self, member)
+                }) {
+                    return;
+                }
+            }
+        }
+    }
+
+    /// Show #CIRC! in every cell of a circular reference
+    fn set_cycle_error(
+        &mut self,
+        members: &[DependencyNode],
+        message: String,
+        named_refs: &mut HashMap<String, (f64, Unit)>,
+    ) -> Vec<DependencyNode> {
+        let mut changed = Vec::new();
+        for member in members {
+            match member {
+                DependencyNode::Cell(sheet_index, addr) => {
+                    let error = CellError::new(ErrorKind::Circular, message.clone());
+                    let result = Err(SheetError::EvalError(EvalError::ErrorValue(error)));
+                    changed.extend(
+                        self.sheets[*sheet_index]
+                            .apply_formula_result(addr, result)
+                            .into_iter()
+                            .map(|addr| DependencyNode::Cell(*sheet_index, addr)),
+                    );
+                }
+                // The names' cells now hold errors, so the names have no value
+                DependencyNode::Name(_) => changed.extend(self.calculate_node(member, named_refs)),
+            }
+        }
+        changed
+    }
+
+    /// The value of a cell, for comparing from one iteration to the next
+    fn node_value(&self, node: &DependencyNode) -> Option<(CellValue, Unit)> {
+        match node {
+            DependencyNode::Cell(sheet_index, addr) => {
+                let cell = self.sheets.get(*sheet_index)?.get(addr)?;
+                Some((cell.value().clone(), cell.storage_unit().clone()))
+            }
+            DependencyNode::Name(_) => None,
+        }
+    }
+
+    /// A cell's sheet-qualified name (Sheet1!A1), or None for a named range
+    fn node_name(&self, node: &DependencyNode) -> Option<String> {
+        match node {
+            DependencyNode::Cell(sheet_index, addr) => Some(format!(
+                "{}!{}",
+                self.sheets.get(*sheet_index)?.name(),
+                addr
+            )),
+            DependencyNode::Name(_) => None,
+        }
+    }
+
+    /// The circular references in the workbook: groups of cells whose formulas depend on each
+    /// other in a loop, each as sheet-qualified names (Sheet1!A1) in sheet, row, column order
+    pub fn circular_references(&self) -> Vec<Vec<String>> {
+        let mut formula_cells = Vec::new();
+        for (sheet_index, sheet) in self.sheets.iter().enumerate() {
+            for addr in sheet.cell_addresses() {
+                if sheet
+                    .get(&addr)
+                    .is_some_and(|cell| cell.formula().is_some())
+                {
+                    formula_cells.push(DependencyNode::Cell(sheet_index, addr));
+                }
+            }
+        }
+
+        let order = self.dependencies.calculation_order(&formula_cells);
+        let mut cycles: Vec<Vec<String>> = self
+            .dependencies
+            .cycles(&order)
+            .into_iter()
+            .map(|mut members| {
+                members.sort_by_key(|node| match node {
+                    DependencyNode::Cell(sheet_index, addr) => {
+                        (*sheet_index, addr.row, addr.col.len(), addr.col.clone())
+                    }
+                    DependencyNode::Name(name) => (usize::MAX, 0, 0, name.clone()),
+                });
+                members
+                    .iter()
+                    .filter_map(|node| self.node_name(node))
+                    .collect()
+            })
+            .collect();
+        cycles.sort();
+        cycles
+    }
+
+    /// Get the functions available to formulas
+    pub fn function_registry(&self) -> &Arc<FunctionRegistry> {
+        &self.functions
+    }
+
+    /// Replace the functions available to formulas (e.g. to add domain functions)
+    /// and recalculate every formula with them. Workbook functions are kept
+    pub fn set_function_registry(
+        &mut self,
+        functions: Arc<FunctionRegistry>,
+    ) -> Result<(), WorkbookError> {
+        self.base_functions = functions;
+        self.install_functions();
+        // Which functions are volatile may have changed
+        self.rebuild_dependencies();
+        self.recalculate_all()
+    }
+
+    /// Recalculate every formula in the workbook
+    pub fn recalculate_all(&mut self) -> Result<(), WorkbookError> {
+        let mut formula_cells = Vec::new();
+        for (sheet_index, sheet) in self.sheets.iter().enumerate() {
+            for addr in sheet.cell_addresses() {
+                if sheet
+                    .get(&addr)
+                    .map(|cell| cell.formula().is_some())
+                    .unwrap_or(false)
+                {
+                    formula_cells.push(DependencyNode::Cell(sheet_index, addr));
+                }
+            }
+        }
+
+        self.recalculate(&formula_cells)
+    }
+
+    /// Check if a name can be given to a sheet: not blank, and without the characters Excel
+    /// does not allow in sheet names
+    fn is_valid_sheet_name(name: &str) -> bool {
+        !name.trim().is_empty() && !name.contains(['[', ']', ':', '*', '?', '/', '\\'])
+    }
+
+    /// Check if a name is valid for a named range
+    /// Must start with lowercase letter or underscore, contain only alphanumerics and underscores
+    fn is_valid_name(name: &str) -> bool {
+        if name.is_empty() {
+            return false;
+        }
+
+        let mut chars = name.chars();
+        let first = chars.next().unwrap();
+
+        // Must start with lowercase letter or underscore
+        if !first.is_ascii_lowercase() && first != '_' {
+            return false;
+        }
+
+        // Rest must be alphanumeric or underscore
+        chars.all(|c| c.is_ascii_alphanumeric() || c == '_')
+    }
+}
+
+impl Default for Workbook {
+    fn default() -> Self {
+        Self::new("Untitled")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::cell::{Cell, ErrorKind};
+    use crate::core::table::CellAddr;
+    use crate::core::units::{BaseDimension, Unit};
+
+    #[test]
+    fn test_workbook_creation() {
+        let wb = Workbook::new("Test Workbook");
+        assert_eq!(wb.name(), "Test Workbook");
+        assert_eq!(wb.sheet_count(), 1);
+        assert_eq!(wb.active_sheet().name(), "Sheet1");
+        assert!(!wb.is_dirty());
+    }
+
+    #[test]
+    fn test_add_sheets() {
+        let mut wb = Workbook::new("Test");
+
+        let idx1 = wb.add_sheet();
+        assert_eq!(idx1, 1);
+        assert_eq!(wb.sheet_count(), 2);
+
+        let idx2 = wb.add_sheet_with_name("Custom");
+        assert_eq!(idx2, 2);
+        assert_eq!(wb.get_sheet(2).unwrap().name(), "Custom");
+        assert!(wb.is_dirty());
+    }
+
+    #[test]
+    fn test_remove_sheet() {
+        let mut wb = Workbook::new("Test");
+        wb.add_sheet();
+        wb.add_sheet();
+
+        assert_eq!(wb.sheet_count(), 3);
+
+        let removed = wb.remove_sheet(1).unwrap();
+        assert_eq!(removed.name(), "Sheet2");
+        assert_eq!(wb.sheet_count(), 2);
+    }
+
+    #[test]
+    fn test_cannot_remove_last_sheet() {
+        let mut wb = Workbook::new("Test");
+
+        let result = wb.remove_sheet(0);
+        assert!(result.is_err());
+        assert!(matches!(
+            result.unwrap_err(),
+            WorkbookError::CannotRemoveLastSheet
+        ));
+    }
+
+    #[test]
+    fn test_rename_sheet() {
+        let mut wb = Workbook::new("Test");
+        wb.add_sheet();
+
+        wb.rename_sheet(0, "First Sheet").unwrap();
+        assert_eq!(wb.get_sheet(0).unwrap().name(), "First Sheet");
+
+        wb.rename_sheet(1, "Second Sheet").unwrap();
+        assert_eq!(wb.get_sheet(1).unwrap().name(), "Second Sheet");
+    }
+
+    #[test]
+    fn test_duplicate_sheet_name() {
+        let mut wb = Workbook::new("Test");
+        wb.add_sheet();
+
+        wb.rename_sheet(0, "MySheet").unwrap();
+
+        let result = wb.rename_sheet(1, "MySheet");
+        assert!(result.is_err());
+        assert!(matches!(
+            result.unwrap_err(),
+            WorkbookError::SheetNameExists(_)
+        ));
+    }
+
+    #[test]
+    fn test_invalid_sheet_names() {
+        let mut wb = Workbook::new("Test");
+        wb.add_sheet();
+        wb.set_cell(
+            0,
+            CellAddr::new("A", 1),
+            Cell::with_formula("=Sheet2!A1 + 1"),
+        )
+        .unwrap();
+
+        for name in ["", "  ", "a[b", "a]b", "a:b", "a*b", "a?b", "a/b", "a\\b"] {
+            assert!(
+                matches!(
+                    wb.rename_sheet(1, name),
+                    Err(WorkbookError::InvalidSheetName(_))
+                ),
+                "{:?} was accepted",
+                name
+            );
+        }
+        assert!(matches!(
+            wb.rename_sheet(1, "Sheet1"),
+            Err(WorkbookError::SheetNameExists(_))
+        ));
+
+        // Nothing was renamed or rewritten
+        assert_eq!(wb.sheet_names(), vec!["Sheet1", "Sheet2"]);
+        assert_eq!(
+            wb.get_sheet(0)
+                .unwrap()
+                .get(&CellAddr::new("A", 1))
+                .unwrap()
+                .formula(),
+            Some("=Sheet2!A1 + 1")
+        );
+    }
+
+    #[test]
+    fn test_active_sheet() {
         let mut wb = Workbook::new("Test");
         wb.add_sheet();
         wb.add_sheet();
@@ -653,4 +1822,534 @@ mod tests {
         let result = wb.get_named_range("value");
         assert_eq!(result.unwrap().1, &CellAddr::new("B", 2));
     }
+
+    /// Build a workbook with an "Inputs" sheet and a "Cost Model" sheet that references it
+    fn cross_sheet_workbook() -> Workbook {
+        let mut wb = Workbook::new("Test");
+        wb.rename_sheet(0, "Inputs").unwrap();
+        wb.add_sheet_with_name("Cost Model");
+
+        let usd = Unit::simple("USD", BaseDimension::Currency);
+        wb.set_cell(0, CellAddr::new("B", 2), Cell::new(100.0, usd.clone()))
+            .unwrap();
+        wb.set_cell(0, CellAddr::new("B", 3), Cell::new(50.0, usd))
+            .unwrap();
+        wb.set_cell(
+            1,
+            CellAddr::new("A", 1),
+            Cell::with_formula("=Inputs!B2 * 2"),
+        )
+        .unwrap();
+        wb.set_cell(1, CellAddr::new("A", 2), Cell::with_formula("=A1 + 1 USD"))
+            .unwrap();
+        wb.set_cell(
+            0,
+            CellAddr::new("C", 1),
+            Cell::with_formula("=SUM('Cost Model'!A1:A2)"),
+        )
+        .unwrap();
+        wb
+    }
+
+    #[test]
+    fn test_cross_sheet_reference_evaluation() {
+        let wb = cross_sheet_workbook();
+
+        let model = wb.get_sheet(1).unwrap();
+        assert_eq!(
+            model.get(&CellAddr::new("A", 1)).unwrap().as_number(),
+            Some(200.0)
+        );
+        assert_eq!(
+            model.get(&CellAddr::new("A", 2)).unwrap().as_number(),
+            Some(201.0)
+        );
+        assert_eq!(
+            model
+                .get(&CellAddr::new("A", 1))
+                .unwrap()
+                .storage_unit()
+                .canonical(),
+            "USD"
+        );
+
+        let inputs = wb.get_sheet(0).unwrap();
+        assert_eq!(
+            inputs.get(&CellAddr::new("C", 1)).unwrap().as_number(),
+            Some(401.0)
+        );
+    }
+
+    #[test]
+    fn test_cross_sheet_recalculation() {
+        let mut wb = cross_sheet_workbook();
+
+        // Editing Inputs!B2 updates the Cost Model sheet and flows back to Inputs!C1
+        wb.set_cell(
+            0,
+            CellAddr::new("B", 2),
+            Cell::new(10.0, Unit::simple("USD", BaseDimension::Currency)),
+        )
+        .unwrap();
+
+        let model = wb.get_sheet(1).unwrap();
+        assert_eq!(
+            model.get(&CellAddr::new("A", 1)).unwrap().as_number(),
+            Some(20.0)
+        );
+        assert_eq!(
+            model.get(&CellAddr::new("A", 2)).unwrap().as_number(),
+            Some(21.0)
+        );
+
+        let inputs = wb.get_sheet(0).unwrap();
+        assert_eq!(
+            inputs.get(&CellAddr::new("C", 1)).unwrap().as_number(),
+            Some(41.0)
+        );
+    }
+
+    #[test]
+    fn test_range_recalculation() {
+        let mut wb = cross_sheet_workbook();
+        let usd = Unit::simple("USD", BaseDimension::Currency);
+        wb.set_cell(0, CellAddr::new("D", 1), Cell::with_formula("=SUM(B:B)"))
+            .unwrap();
+        wb.set_cell(
+            1,
+            CellAddr::new("B", 1),
+            Cell::with_formula("=SUM(Inputs!A1:B5)"),
+        )
+        .unwrap();
+
+        // B4 is new and named by no formula, but lies inside both ranges
+        wb.set_cell(0, CellAddr::new("B", 4), Cell::new(25.0, usd))
+            .unwrap();
+
+        let inputs = wb.get_sheet(0).unwrap();
+        assert_eq!(
+            inputs.get(&CellAddr::new("D", 1)).unwrap().as_number(),
+            Some(175.0)
+        );
+        let model = wb.get_sheet(1).unwrap();
+        assert_eq!(
+            model.get(&CellAddr::new("B", 1)).unwrap().as_number(),
+            Some(175.0)
+        );
+    }
+
+    #[test]
+    fn test_copy_range_recalculates() {
+        let mut wb = cross_sheet_workbook();
+        wb.set_cell(0, CellAddr::new("C", 1), Cell::with_formula("=B1 * 2"))
+            .unwrap();
+        wb.set_cell(1, CellAddr::new("A", 1), Cell::with_formula("=Inputs!C2"))
+            .unwrap();
+
+        wb.copy_range(
+            0,
+            &CellRange::new(&CellAddr::new("C", 1), &CellAddr::new("C", 1)),
+            &CellRange::new(&CellAddr::new("C", 2), &CellAddr::new("C", 3)),
+        )
+        .unwrap();
+
+        let inputs = wb.get_sheet(0).unwrap();
+        let c3 = inputs.get(&CellAddr::new("C", 3)).unwrap();
+        assert_eq!(c3.formula(), Some("=B3 * 2"));
+        assert_eq!(c3.as_number(), Some(100.0));
+
+        // The other sheet picks up the pasted value
+        let model = wb.get_sheet(1).unwrap();
+        assert_eq!(
+            model.get(&CellAddr::new("A", 1)).unwrap().as_number(),
+            Some(200.0)
+        );
+    }
+
+    #[test]
+    fn test_pasted_formulas_keep_their_text() {
+        let mut wb = Workbook::new("Test");
+        for (row, value) in [(1, 1.0), (2, 2.0), (3, 3.0)] {
+            wb.set_cell(
+                0,
+                CellAddr::new("A", row),
+                Cell::new(value, Unit::dimensionless()),
+            )
+            .unwrap();
+        }
+        wb.set_cell(0, CellAddr::new("B", 1), Cell::with_formula("=(A1+A$1)*2"))
+            .unwrap();
+
+        wb.copy_range(
+            0,
+            &CellRange::new(&CellAddr::new("B", 1), &CellAddr::new("B", 1)),
+            &CellRange::new(&CellAddr::new("B", 2), &CellAddr::new("B", 3)),
+        )
+        .unwrap();
+        let b3 = wb.active_sheet().get(&CellAddr::new("B", 3)).unwrap();
+        assert_eq!(b3.formula(), Some("=(A3+A$1)*2"));
+        assert_eq!(b3.as_number(), Some(8.0));
+
+        // Moving A3 rewrites only the reference to it
+        wb.move_range(
+            0,
+            &CellRange::new(&CellAddr::new("A", 3), &CellAddr::new("A", 3)),
+            &CellAddr::new("D", 3),
+        )
+        .unwrap();
+        let b3 = wb.active_sheet().get(&CellAddr::new("B", 3)).unwrap();
+        assert_eq!(b3.formula(), Some("=(D3+A$1)*2"));
+    }
+
+    #[test]
+    fn test_paste_creating_a_loop_is_rejected() {
+        let mut wb = Workbook::new("Test");
+        let number = |value| Cell::new(value, Unit::dimensionless());
+        wb.set_cell(0, CellAddr::new("A", 1), Cell::with_formula("=B2 + 1"))
+            .unwrap();
+        wb.set_cell(0, CellAddr::new("B", 2), number(5.0)).unwrap();
+        wb.set_cell(0, CellAddr::new("D", 1), number(1.0)).unwrap();
+        wb.set_cell(0, CellAddr::new("E", 2), Cell::with_formula("=D1 + 1"))
+            .unwrap();
+
+        // E2 pasted into B2 becomes =A1 + 1, and A1 reads B2
+        let paste = |wb: &mut Workbook| {
+            wb.copy_range(
+                0,
+                &CellRange::new(&CellAddr::new("E", 2), &CellAddr::new("E", 2)),
+                &CellRange::new(&CellAddr::new("B", 2), &CellAddr::new("B", 2)),
+            )
+        };
+        let err = paste(&mut wb).unwrap_err();
+        assert!(err.to_string().contains("Sheet1!B2"), "{}", err);
+
+        // The paste is undone
+        let sheet = wb.active_sheet();
+        assert_eq!(
+            sheet.get(&CellAddr::new("B", 2)).unwrap().as_number(),
+            Some(5.0)
+        );
+        assert_eq!(
+            sheet.get(&CellAddr::new("A", 1)).unwrap().as_number(),
+            Some(6.0)
+        );
+        wb.set_cell(0, CellAddr::new("B", 2), number(7.0)).unwrap();
+        assert_eq!(
+            wb.active_sheet()
+                .get(&CellAddr::new("A", 1))
+                .unwrap()
+                .as_number(),
+            Some(8.0)
+        );
+
+        // With iterative calculation the same paste is allowed
+        wb.set_iterative_calculation(Some(IterativeCalculation::default()))
+            .unwrap();
+        paste(&mut wb).unwrap();
+        assert_eq!(
+            wb.active_sheet()
+                .get(&CellAddr::new("B", 2))
+                .unwrap()
+                .formula(),
+            Some("=A1 + 1")
+        );
+    }
+
+    #[test]
+    fn test_custom_function_registry() {
+        use crate::core::formula::{Arity, EvalResult, Evaluator, FunctionSpec, UnitSignature};
+
+        let mut wb = Workbook::new("Test");
+        wb.set_cell(
+            0,
+            CellAddr::new("A", 1),
+            Cell::new(10.0, Unit::dimensionless()),
+        )
+        .unwrap();
+        wb.set_cell(0, CellAddr::new("B", 1), Cell::with_formula("=MARGIN(A1)"))
+            .unwrap();
+        assert!(wb
+            .active_sheet()
+            .get(&CellAddr::new("B", 1))
+            .unwrap()
+            .is_error());
+
+        let mut registry = FunctionRegistry::with_builtins();
+        registry.register(FunctionSpec::new(
+            "MARGIN",
+            Arity::exactly(1),
+            UnitSignature::Preserve,
+            |ev, args| {
+                let result = ev.eval(&args[0])?;
+                Ok(EvalResult::new(
+                    result.as_number().unwrap_or_default() * 1.2,
+                    result.unit,
+                ))
+            },
+        ));
+        let registry = Arc::new(registry);
+        wb.set_function_registry(registry.clone()).unwrap();
+
+        // Existing formulas are recalculated and new sheets get the registry too
+        let b1 = wb.active_sheet().get(&CellAddr::new("B", 1)).unwrap();
+        assert_eq!(b1.as_number(), Some(12.0));
+        let index = wb.add_sheet();
+        wb.set_cell(
+            index,
+            CellAddr::new("A", 1),
+            Cell::with_formula("=MARGIN(5)"),
+        )
+        .unwrap();
+        let sheet = wb.get_sheet(index).unwrap();
+        assert_eq!(
+            sheet.get(&CellAddr::new("A", 1)).unwrap().as_number(),
+            Some(6.0)
+        );
+
+        // Standalone evaluation with the same registry agrees with the sheet
+        let library = crate::core::units::UnitLibrary::new();
+        let result = Evaluator::new(&library)
+            .with_functions(&registry)
+            .eval(&parse_formula("=MARGIN(5)").unwrap())
+            .unwrap();
+        assert_eq!(result.as_number(), Some(6.0));
+    }
+
+    #[test]
+    fn test_unknown_sheet_reference() {
+        let mut wb = Workbook::new("Test");
+        let addr = CellAddr::new("A", 1);
+        wb.set_cell(0, addr.clone(), Cell::with_formula("=Missing!A1"))
+            .unwrap();
+
+        assert!(wb.active_sheet().get(&addr).unwrap().is_error());
+    }
+
+    #[test]
+    fn test_rename_sheet_rewrites_references() {
+        let mut wb = cross_sheet_workbook();
+        let usd = Unit::simple("USD", BaseDimension::Currency);
+        wb.get_sheet_mut(1)
+            .unwrap()
+            .get_mut(&CellAddr::new("A", 1))
+            .unwrap()
+            .set_display_unit(Some(usd.clone()));
+        wb.set_cell(
+            1,
+            CellAddr::new("B", 1),
+            Cell::with_formula("=IF(Inputs!B3>0,\"Inputs!B3\",0)"),
+        )
+        .unwrap();
+
+        wb.rename_sheet(0, "Assumptions").unwrap();
+        wb.rename_sheet(1, "Bob's Model").unwrap();
+
+        // Only the sheet names change, not the rest of the formula or the cell
+        let model = wb.get_sheet(1).unwrap();
+        assert_eq!(
+            model.get(&CellAddr::new("A", 1)).unwrap().formula(),
+            Some("=Assumptions!B2 * 2")
+        );
+        assert_eq!(
+            model.get(&CellAddr::new("A", 1)).unwrap().display_unit(),
+            &usd
+        );
+        assert_eq!(
+            model.get(&CellAddr::new("B", 1)).unwrap().formula(),
+            Some("=IF(Assumptions!B3>0,\"Inputs!B3\",0)")
+        );
+        // Rewritten formulas are recalculated
+        assert_eq!(
+            model.get(&CellAddr::new("A", 1)).unwrap().as_number(),
+            Some(200.0)
+        );
+
+        let inputs = wb.get_sheet(0).unwrap();
+        assert_eq!(
+            inputs.get(&CellAddr::new("C", 1)).unwrap().formula(),
+            Some("=SUM('Bob''s Model'!A1:A2)")
+        );
+        assert_eq!(
+            inputs.get(&CellAddr::new("C", 1)).unwrap().as_number(),
+            Some(401.0)
+        );
+    }
+
+    #[test]
+    fn test_remove_sheet_invalidates_references() {
+        let mut wb = cross_sheet_workbook();
+        wb.set_named_range("total", 1, CellAddr::new("A", 2))
+            .unwrap();
+
+        wb.remove_sheet(0).unwrap();
+
+        let model = wb.get_sheet(0).unwrap();
+        let cell = model.get(&CellAddr::new("A", 1)).unwrap();
+        assert_eq!(cell.formula(), Some("=#REF! * 2"));
+        assert_eq!(cell.value().as_error().unwrap().kind, ErrorKind::Ref);
+
+        // Named ranges on later sheets follow the index shift
+        assert_eq!(wb.get_named_range("total").unwrap().0, 0);
+    }
+
+    #[test]
+    fn test_named_range_propagates_across_sheets() {
+        let mut wb = Workbook::new("Test");
+        wb.add_sheet();
+
+        let usd = Unit::simple("USD", BaseDimension::Currency);
+        wb.set_cell(0, CellAddr::new("A", 1), Cell::new(100.0, usd.clone()))
+            .unwrap();
+        wb.set_named_range("price", 0, CellAddr::new("A", 1))
+            .unwrap();
+        wb.set_cell(1, CellAddr::new("B", 1), Cell::with_formula("=price * 3"))
+            .unwrap();
+        wb.set_cell(1, CellAddr::new("B", 2), Cell::with_formula("=B1 + price"))
+            .unwrap();
+
+        // Changing the source cell on Sheet1 refreshes the formulas on Sheet2
+        wb.set_cell(0, CellAddr::new("A", 1), Cell::new(10.0, usd))
+            .unwrap();
+
+        let sheet2 = wb.get_sheet(1).unwrap();
+        assert_eq!(
+            sheet2.get(&CellAddr::new("B", 1)).unwrap().as_number(),
+            Some(30.0)
+        );
+        assert_eq!(
+            sheet2.get(&CellAddr::new("B", 2)).unwrap().as_number(),
+            Some(40.0)
+        );
+    }
+
+    #[test]
+    fn test_defining_name_recalculates_users() {
+        let mut wb = Workbook::new("Test");
+
+        wb.set_cell(0, CellAddr::new("B", 1), Cell::with_formula("=rate * 2"))
+            .unwrap();
+        assert!(wb
+            .active_sheet()
+            .get(&CellAddr::new("B", 1))
+            .unwrap()
+            .is_error());
+
+        wb.set_cell(
+            0,
+            CellAddr::new("A", 1),
+            Cell::new(4.0, Unit::dimensionless()),
+        )
+        .unwrap();
+        wb.set_named_range("rate", 0, CellAddr::new("A", 1))
+            .unwrap();
+        assert_eq!(
+            wb.active_sheet()
+                .get(&CellAddr::new("B", 1))
+                .unwrap()
+                .as_number(),
+            Some(8.0)
+        );
+
+        wb.remove_named_range("rate").unwrap();
+        assert!(wb
+            .active_sheet()
+            .get(&CellAddr::new("B", 1))
+            .unwrap()
+            .is_error());
+    }
+
+    #[test]
+    fn test_circular_reference_across_sheets() {
+        let mut wb = Workbook::new("Test");
+        wb.add_sheet();
+
+        wb.set_cell(
+            0,
+            CellAddr::new("A", 1),
+            Cell::with_formula("=Sheet2!A1 + 1"),
+        )
+        .unwrap();
+        let result = wb.set_cell(
+            1,
+            CellAddr::new("A", 1),
+            Cell::with_formula("=Sheet1!A1 + 1"),
+        );
+
+        assert!(matches!(
+            result,
+            Err(WorkbookError::SheetError(SheetError::CircularReference(_)))
+        ));
+    }
+
+    #[test]
+    fn test_rejected_circular_reference_keeps_cell() {
+        let mut wb = Workbook::new("Test");
+        wb.add_sheet();
+        let a1 = CellAddr::new("A", 1);
+        let b1 = CellAddr::new("B", 1);
+
+        wb.set_cell(1, a1.clone(), Cell::new(5.0, Unit::dimensionless()))
+            .unwrap();
+        wb.set_cell(0, a1.clone(), Cell::with_formula("=Sheet2!A1 + 1"))
+            .unwrap();
+        assert!(wb
+            .set_cell(1, a1.clone(), Cell::with_formula("=Sheet1!A1 + 1"))
+            .is_err());
+
+        let kept = wb.get_sheet(1).unwrap().get(&a1).unwrap();
+        assert_eq!(kept.as_number(), Some(5.0));
+        assert!(kept.formula().is_none());
+        assert_eq!(
+            wb.get_sheet(0).unwrap().get(&a1).unwrap().as_number(),
+            Some(6.0)
+        );
+
+        // The dependency on the kept cell still recalculates
+        wb.set_cell(1, a1.clone(), Cell::new(7.0, Unit::dimensionless()))
+            .unwrap();
+        assert_eq!(
+            wb.get_sheet(0).unwrap().get(&a1).unwrap().as_number(),
+            Some(8.0)
+        );
+
+        // A loop within one sheet is rejected the same way, and an empty cell stays empty
+        wb.set_cell(0, b1.clone(), Cell::with_formula("=A1 * 2"))
+            .unwrap();
+        assert!(wb
+            .set_cell(0, a1.clone(), Cell::with_formula("=B1"))
+            .is_err());
+        assert_eq!(
+            wb.get_sheet(0).unwrap().get(&a1).unwrap().formula(),
+            Some("=Sheet2!A1 + 1")
+        );
+        assert!(wb
+            .set_cell(0, CellAddr::new("C", 1), Cell::with_formula("=C1"))
+            .is_err());
+        assert!(wb
+            .get_sheet(0)
+            .unwrap()
+            .get(&CellAddr::new("C", 1))
+            .is_none());
+    }
+
+    #[test]
+    fn test_recalculate_all() {
+        let mut wb = cross_sheet_workbook();
+
+        // Values written directly into a sheet bypass the dependency graph
+        wb.get_sheet_mut(0)
+            .unwrap()
+            .set(
+                CellAddr::new("B", 2),
+                Cell::new(1.0, Unit::simple("USD", BaseDimension::Currency)),
+            )
+            .unwrap();
+        wb.recalculate_all().unwrap();
+
+        let inputs = wb.get_sheet(0).unwrap();
+        assert_eq!(
+            inputs.get(&CellAddr::new("C", 1)).unwrap().as_number(),
+            Some(5.0)
+        );
+    }
 }
@@ -1,8 +1,9 @@
 // Excel import/export functionality
 
+use crate::core::datetime;
 use crate::core::formula::ast::Expr;
 use crate::core::formula::parser::parse_formula;
-use crate::core::table::Sheet;
+use crate::core::table::{CellAddr, Sheet};
 use crate::core::units::UnitLibrary;
 use crate::core::workbook::Workbook;
 use rust_xlsxwriter::{Format, Workbook as XlsxWorkbook, Worksheet};
@@ -40,7 +41,7 @@ fn get_expr_unit(
     library: &UnitLibrary,
 ) -> Option<crate::core::units::Unit> {
     match expr {
-        Expr::CellRef { col, row } => {
+        Expr::CellRef { col, row, .. } => {
             // Look up the cell and return its storage unit
             let addr = crate::core::table::CellAddr::new(col.clone(), *row);
             sheet.get(&addr).map(|cell| cell.storage_unit().clone())
@@ -70,10 +71,10 @@ fn get_expr_unit(
 
             // Multiply: add symbols together
             for (symbol, power) in right_num {
-                *left_num.entry(symbol).or_insert(0) += power;
+                *left_num.entry(symbol).or_default() += power;
             }
             for (symbol, power) in right_den {
-                *left_den.entry(symbol).or_insert(0) += power;
+                *left_den.entry(symbol).or_default() += power;
             }
 
             // Build the result unit (without cancellation for now)
@@ -92,10 +93,10 @@ fn get_expr_unit(
 
             // Divide: right's numerator becomes left's denominator, right's denominator becomes left's numerator
             for (symbol, power) in right_num {
-                *left_den.entry(symbol).or_insert(0) += power;
+                *left_den.entry(symbol).or_default() += power;
             }
             for (symbol, power) in right_den {
-                *left_num.entry(symbol).or_insert(0) += power;
+                *left_num.entry(symbol).or_default() += power;
             }
 
             // Build the result unit
@@ -110,6 +111,16 @@ fn get_expr_unit(
             // Negation preserves unit
             get_expr_unit(inner, sheet, library)
         }
+        Expr::Power(base, exponent) => {
+            // Only literal exponents have a known unit (A1^2)
+            let Expr::Number(exponent) = exponent.as_ref() else {
+                return None;
+            };
+            let base_unit = get_expr_unit(base, sheet, library)?;
+
+            use crate::core::formula::evaluator::transform_unit_exponents;
+            transform_unit_exponents(&base_unit, *exponent, library).ok()
+        }
         _ => {
             // For other expressions (functions, etc.), return None
             None
@@ -147,10 +158,10 @@ fn inject_conversion_factors(
 
             // Multiply units
             for (symbol, power) in right_num {
-                *left_num.entry(symbol).or_insert(0) += power;
+                *left_num.entry(symbol).or_default() += power;
             }
             for (symbol, power) in right_den {
-                *left_den.entry(symbol).or_insert(0) += power;
+                *left_den.entry(symbol).or_default() += power;
             }
 
             // Before cancellation, store original units
@@ -257,10 +268,10 @@ fn inject_conversion_factors(
 
             // Divide units: flip right's numerator/denominator
             for (symbol, power) in right_num {
-                *left_den.entry(symbol).or_insert(0) += power;
+                *left_den.entry(symbol).or_default() += power;
             }
             for (symbol, power) in right_den {
-                *left_num.entry(symbol).or_insert(0) += power;
+                *left_num.entry(symbol).or_default() += power;
             }
 
             // Before cancellation, store original units
@@ -359,6 +370,17 @@ fn inject_conversion_factors(
             let processed = inject_conversion_factors(*inner, sheet, library, conversions);
             Expr::Negate(Box::new(processed))
         }
+        Expr::Concat(left, right) => {
+            let processed_left = inject_conversion_factors(*left, sheet, library, conversions);
+            let processed_right = inject_conversion_factors(*right, sheet, library, conversions);
+            Expr::Concat(Box::new(processed_left), Box::new(processed_right))
+        }
+        Expr::Power(base, exponent) => {
+            let processed_base = inject_conversion_factors(*base, sheet, library, conversions);
+            let processed_exponent =
+                inject_conversion_factors(*exponent, sheet, library, conversions);
+            Expr::Power(Box::new(processed_base), Box::new(processed_exponent))
+        }
         Expr::Function { name, args } => {
             let processed_args = args
                 .into_iter()
@@ -374,6 +396,48 @@ fn inject_conversion_factors(
     }
 }
 
+/// Functions that work with units themselves (UNIT, WITHUNIT, ...), which Excel can't compute
+/// VALUE reads units from text and TEXT writes them
+const UNIT_FUNCTIONS: [&str; 8] = [
+    "UNIT",
+    "VALUE_IN",
+    "DIMENSION",
+    "STRIPUNIT",
+    "WITHUNIT",
+    "ISCOMPATIBLE",
+    "VALUE",
+    "TEXT",
+];
+
+/// Check whether a formula calls any of the unit functions
+fn calls_unit_functions(formula: &str) -> bool {
+    fn calls(expr: &Expr) -> bool {
+        match expr {
+            Expr::Function { name, args } => {
+                UNIT_FUNCTIONS.contains(&name.to_uppercase().as_str()) || args.iter().any(calls)
+            }
+            Expr::Add(l, r)
+            | Expr::Subtract(l, r)
+            | Expr::Multiply(l, r)
+            | Expr::Divide(l, r)
+            | Expr::Power(l, r)
+            | Expr::GreaterThan(l, r)
+            | Expr::LessThan(l, r)
+            | Expr::GreaterOrEqual(l, r)
+            | Expr::LessOrEqual(l, r)
+            | Expr::Equal(l, r)
+            | Expr::NotEqual(l, r)
+            | Expr::And(l, r)
+            | Expr::Or(l, r)
+            | Expr::Concat(l, r) => calls(l) || calls(r),
+            Expr::Negate(e) | Expr::Not(e) => calls(e),
+            _ => false,
+        }
+    }
+
+    parse_formula(formula).is_ok_and(|ast| calls(&ast))
+}
+
 /// Expand implicit conversions in formulas to Excel-compatible math with conversion factors
 /// Similar to expand_convert_formula but for implicit conversions in arithmetic
 fn expand_implicit_conversions(
@@ -636,14 +700,19 @@ fn normalize_cell_refs_to_uppercase(formula: &str) -> String {
     let mut result = String::new();
     let mut chars = formula.chars().peekable();
     let mut in_string = false;
+    let mut in_sheet_name = false;
 
     while let Some(ch) = chars.next() {
-        if ch == '"' {
+        if ch == '"' && !in_sheet_name {
             // Toggle string mode
             in_string = !in_string;
             result.push(ch);
-        } else if in_string {
-            // Inside string literal - preserve everything
+        } else if ch == '\'' && !in_string {
+            // Toggle quoted sheet name mode ('Cost Model'!A1)
+            in_sheet_name = !in_sheet_name;
+            result.push(ch);
+        } else if in_string || in_sheet_name {
+            // Inside string literal or sheet name - preserve everything
             result.push(ch);
         } else if ch.is_ascii_alphabetic() {
             // Potential cell reference or function name
@@ -669,13 +738,14 @@ fn normalize_cell_refs_to_uppercase(formula: &str) -> String {
                 }
             }
 
-            if !digits.is_empty() {
+            if !digits.is_empty() && chars.peek() != Some(&'!') {
                 // This is a cell reference - uppercase it
                 result.push_str(&word.to_uppercase());
                 result.push_str(&digits);
             } else {
-                // Not a cell reference - preserve original case (might be function name or named ref)
+                // Not a cell reference - preserve original case (might be function name, named ref or sheet name)
                 result.push_str(&word);
+                result.push_str(&digits);
             }
         } else {
             // Non-alphabetic character - preserve as-is
@@ -835,6 +905,15 @@ fn number_to_column_letter(mut num: u32) -> String {
     result.chars().rev().collect()
 }
 
+/// The `$` marker for an absolute column or row in a cell reference
+fn anchor(absolute: bool) -> &'static str {
+    if absolute {
+        "$"
+    } else {
+        ""
+    }
+}
+
 /// Extract unit from a value expression like "100 m" or "1 km"
 fn extract_unit_from_value(s: &str) -> Option<String> {
     let parts: Vec<&str> = s.split_whitespace().collect();
@@ -874,7 +953,8 @@ fn contains_string_literal(expr: &Expr) -> bool {
         Expr::Add(left, right)
         | Expr::Subtract(left, right)
         | Expr::Multiply(left, right)
-        | Expr::Divide(left, right) => {
+        | Expr::Divide(left, right)
+        | Expr::Power(left, right) => {
             contains_string_literal(left) || contains_string_literal(right)
         }
         Expr::Negate(inner) | Expr::Not(inner) => contains_string_literal(inner),
@@ -889,7 +969,10 @@ fn contains_string_literal(expr: &Expr) -> bool {
         | Expr::Equal(left, right)
         | Expr::NotEqual(left, right)
         | Expr::And(left, right)
-        | Expr::Or(left, right) => contains_string_literal(left) || contains_string_literal(right),
+        | Expr::Or(left, right)
+        | Expr::Concat(left, right) => {
+            contains_string_literal(left) || contains_string_literal(right)
+        }
         _ => false,
     }
 }
@@ -929,6 +1012,10 @@ fn transform_ast_for_excel(expr: Expr) -> Expr {
             Box::new(transform_ast_for_excel(*left)),
             Box::new(transform_ast_for_excel(*right)),
         ),
+        Expr::Power(left, right) => Expr::Power(
+            Box::new(transform_ast_for_excel(*left)),
+            Box::new(transform_ast_for_excel(*right)),
+        ),
         Expr::Negate(inner) => Expr::Negate(Box::new(transform_ast_for_excel(*inner))),
         Expr::Function { name, args } => Expr::Function {
             name,
@@ -971,6 +1058,10 @@ fn transform_ast_for_excel(expr: Expr) -> Expr {
             Box::new(transform_ast_for_excel(*right)),
         ),
         Expr::Not(inner) => Expr::Not(Box::new(transform_ast_for_excel(*inner))),
+        Expr::Concat(left, right) => Expr::Concat(
+            Box::new(transform_ast_for_excel(*left)),
+            Box::new(transform_ast_for_excel(*right)),
+        ),
         // Leaf nodes - return as is
         other => other,
     }
@@ -1000,14 +1091,41 @@ fn expr_to_excel_string(expr: &Expr) -> String {
         }
         Expr::NumberWithUnit { value, unit } => format!("{}{}", value, unit),
         Expr::String(s) => format!("\"{}\"", s.replace('"', "\"\"")), // Escape quotes for Excel
-        Expr::CellRef { col, row } => {
+        Expr::Error(kind) => kind.excel_code().to_string(),
+        Expr::CellRef {
+            col,
+            row,
+            col_absolute,
+            row_absolute,
+        } => {
             // Transform cell reference for doubled-column layout
             let col_num = column_letter_to_number(col);
             let excel_col_num = col_num * 2;
             let excel_col = number_to_column_letter(excel_col_num);
-            format!("{}{}", excel_col, row)
+            format!(
+                "{}{}{}{}",
+                anchor(*col_absolute),
+                excel_col,
+                anchor(*row_absolute),
+                row
+            )
         }
         Expr::NamedRef { name } => name.clone(),
+        Expr::SheetRef { sheet, reference } => {
+            // Excel accepts quoted names everywhere, so always quote
+            if let Expr::SpillRef(anchor) = reference.as_ref() {
+                return format!(
+                    "ANCHORARRAY('{}'!{})",
+                    sheet.replace('\'', "''"),
+                    expr_to_excel_string(anchor)
+                );
+            }
+            format!(
+                "'{}'!{}",
+                sheet.replace('\'', "''"),
+                expr_to_excel_string(reference)
+            )
+        }
         Expr::Range { start, end } => {
             format!(
                 "{}:{}",
@@ -1015,6 +1133,17 @@ fn expr_to_excel_string(expr: &Expr) -> String {
                 expr_to_excel_string(end)
             )
         }
+        Expr::ColumnRange { start, end } => {
+            // Whole columns also follow the doubled-column layout
+            format!(
+                "{}:{}",
+                number_to_column_letter(column_letter_to_number(start) * 2),
+                number_to_column_letter(column_letter_to_number(end) * 2)
+            )
+        }
+        Expr::RowRange { start, end } => format!("{}:{}", start, end),
+        // A1# is stored as ANCHORARRAY(A1) in Excel files
+        Expr::SpillRef(anchor) => format!("ANCHORARRAY({})", expr_to_excel_string(anchor)),
         Expr::Add(l, r) => {
             format!("{}+{}", expr_to_excel_string(l), expr_to_excel_string(r))
         }
@@ -1047,9 +1176,34 @@ fn expr_to_excel_string(expr: &Expr) -> String {
             };
             format!("{}/{}", left_str, right_str)
         }
+        Expr::Power(l, r) => {
+            // ^ binds tighter than every other operator, so wrap anything but a plain operand
+            let operand = |e: &Expr| match e {
+                Expr::Number(_)
+                | Expr::CellRef { .. }
+                | Expr::NamedRef { .. }
+                | Expr::SheetRef { .. }
+                | Expr::Function { .. } => expr_to_excel_string(e),
+                _ => format!("({})", expr_to_excel_string(e)),
+            };
+            format!("{}^{}", operand(l), operand(r))
+        }
         Expr::Negate(e) => {
             format!("-({})", expr_to_excel_string(e))
         }
+        Expr::Concat(l, r) => {
+            // & binds tighter than comparisons
+            let operand = |e: &Expr| match e {
+                Expr::GreaterThan(..)
+                | Expr::LessThan(..)
+                | Expr::GreaterOrEqual(..)
+                | Expr::LessOrEqual(..)
+                | Expr::Equal(..)
+                | Expr::NotEqual(..) => format!("({})", expr_to_excel_string(e)),
+                _ => expr_to_excel_string(e),
+            };
+            format!("{}&{}", operand(l), operand(r))
+        }
         Expr::Function { name, args } => {
             let args_str = args
                 .iter()
@@ -1121,8 +1275,26 @@ pub fn export_to_excel(workbook: &Workbook, path: impl AsRef<Path>) -> Result<()
         let mut worksheet = Worksheet::new();
         worksheet.set_name(sheet.name())?;
 
+        // Arrays one column wide spill the same way in Excel. Wider ones would spill into the
+        // unit columns, and Excel can't evaluate the unit functions, so those formulas and
+        // their spilled cells are exported as values
+        let is_exported_as_values = |anchor: &CellAddr| {
+            sheet
+                .spill_range(anchor)
+                .is_some_and(|range| range.end_col > range.start_col)
+                || sheet
+                    .get(anchor)
+                    .and_then(|cell| cell.formula())
+                    .is_some_and(calls_unit_functions)
+        };
+        let (exported_spilled, narrow_spilled): (Vec<CellAddr>, Vec<CellAddr>) = sheet
+            .spilled_addresses()
+            .into_iter()
+            .partition(|addr| sheet.spill_anchor(addr).is_some_and(is_exported_as_values));
+
         // Get all cell addresses and sort them
         let mut addresses = sheet.cell_addresses();
+        addresses.extend(exported_spilled);
         addresses.sort_by(|a, b| {
             // Sort by row first, then by column
             match a.row.cmp(&b.row) {
@@ -1136,6 +1308,9 @@ pub fn export_to_excel(workbook: &Workbook, path: impl AsRef<Path>) -> Result<()
             .set_font_color(rust_xlsxwriter::Color::Blue)
             .set_italic();
         let _unit_format = Format::new().set_italic();
+        let date_format = Format::new().set_num_format("yyyy-mm-dd");
+        let datetime_format = Format::new().set_num_format("yyyy-mm-dd hh:mm");
+        let date_formula_format = formula_format.clone().set_num_format("yyyy-mm-dd");
 
         // Export each cell - using doubled columns (value, unit, value, unit, ...)
         for addr in addresses {
@@ -1147,8 +1322,15 @@ pub fn export_to_excel(workbook: &Workbook, path: impl AsRef<Path>) -> Result<()
 
                 let cell_ref = format!("{}{}", addr.col, addr.row);
 
+                // Formulas that give dates are formatted as dates
+                let formula_format = if cell.value().is_datetime() {
+                    &date_formula_format
+                } else {
+                    &formula_format
+                };
+
                 // Export the cell based on its content
-                if let Some(formula) = cell.formula() {
+                if let Some(formula) = cell.formula().filter(|_| !is_exported_as_values(&addr)) {
                     // Check if formula contains CONVERT function
                     if formula.to_uppercase().contains("CONVERT") {
                         // Try to expand CONVERT functions to Excel-compatible formulas
@@ -1164,11 +1346,12 @@ pub fn export_to_excel(workbook: &Workbook, path: impl AsRef<Path>) -> Result<()
                                 excel_formula
                             );
                             // Export as Excel formula with expanded CONVERT
-                            worksheet.write_formula_with_format(
-                                row_num,
-                                col_num as u16,
+                            write_formula_cell(
+                                &mut worksheet,
+                                sheet,
+                                &addr,
                                 excel_formula.as_str(),
-                                &formula_format,
+                                formula_format,
                             )?;
 
                             // Unit in column N*2+1
@@ -1195,7 +1378,7 @@ pub fn export_to_excel(workbook: &Workbook, path: impl AsRef<Path>) -> Result<()
                                     row_num,
                                     col_num as u16,
                                     value,
-                                    &formula_format,
+                                    formula_format,
                                 )?;
 
                                 let unit_str = cell.storage_unit().canonical();
@@ -1226,11 +1409,12 @@ pub fn export_to_excel(workbook: &Workbook, path: impl AsRef<Path>) -> Result<()
                                 expanded_formula,
                                 excel_formula
                             );
-                            worksheet.write_formula_with_format(
-                                row_num,
-                                col_num as u16,
+                            write_formula_cell(
+                                &mut worksheet,
+                                sheet,
+                                &addr,
                                 excel_formula.as_str(),
-                                &formula_format,
+                                formula_format,
                             )?;
 
                             // Unit in column N*2+1
@@ -1254,11 +1438,12 @@ pub fn export_to_excel(workbook: &Workbook, path: impl AsRef<Path>) -> Result<()
                                 "Could not expand implicit conversions, exporting formula as-is: {}",
                                 formula
                             );
-                            worksheet.write_formula_with_format(
-                                row_num,
-                                col_num as u16,
+                            write_formula_cell(
+                                &mut worksheet,
+                                sheet,
+                                &addr,
                                 excel_formula.as_str(),
-                                &formula_format,
+                                formula_format,
                             )?;
 
                             let unit_str = cell.storage_unit().canonical();
@@ -1283,17 +1468,58 @@ pub fn export_to_excel(workbook: &Workbook, path: impl AsRef<Path>) -> Result<()
                     if !unit_str.is_empty() && unit_str != "1" {
                         worksheet.write_string(row_num, (col_num + 1) as u16, unit_str)?;
 
-                        // Track metadata
+                        // Track metadata; Excel has no uncertainties, so they are noted here
+                        let description = cell.formula().map_or_else(
+                            || "Value".to_string(),
+                            |formula| format!("Computed: {}", formula),
+                        );
+                        let description = match cell.uncertainty() {
+                            Some(uncertainty) => {
+                                format!("{} (± {} {})", description, uncertainty, unit_str)
+                            }
+                            None => description,
+                        };
                         metadata_rows.push((
                             sheet.name().to_string(),
                             cell_ref,
-                            "Value".to_string(),
+                            description,
                             unit_str.to_string(),
                         ));
                     }
                 } else if let Some(text) = cell.as_text() {
                     // Export text in column N*2
                     worksheet.write_string(row_num, col_num as u16, text)?;
+                } else if let Some(value) = cell.as_boolean() {
+                    // Export as a native Excel boolean
+                    worksheet.write_boolean(row_num, col_num as u16, value)?;
+                } else if let Some(date) = cell.as_datetime() {
+                    // Export as a date serial so Excel treats it as a real date
+                    let format = if date.time() == chrono::NaiveTime::MIN {
+                        &date_format
+                    } else {
+                        &datetime_format
+                    };
+                    worksheet.write_number_with_format(
+                        row_num,
+                        col_num as u16,
+                        datetime::to_serial(&date),
+                        format,
+                    )?;
+                } else if let Some(error) = cell.value().as_error() {
+                    // Export as the native Excel error (a formula such as =#DIV/0!)
+                    let excel_formula = format!("={}", error.kind.excel_code());
+                    worksheet.write_formula(row_num, col_num as u16, excel_formula.as_str())?;
+                }
+            }
+        }
+
+        // Excel fills in the cells of spilled arrays itself; only their units are written
+        for addr in narrow_spilled {
+            if let Some(cell) = sheet.get(&addr) {
+                let unit_str = cell.storage_unit().canonical();
+                if cell.is_number() && !unit_str.is_empty() && unit_str != "1" {
+                    let col_num = column_letter_to_number(&addr.col) * 2 + 1;
+                    worksheet.write_string((addr.row - 1) as u32, col_num as u16, unit_str)?;
                 }
             }
         }
@@ -1374,6 +1600,25 @@ pub fn export_to_excel(workbook: &Workbook, path: impl AsRef<Path>) -> Result<()
     Ok(())
 }
 
+/// Write a cell's formula in its value column (see `export_to_excel`)
+/// Formulas that spill, or use a spilled range, are written as dynamic array formulas
+fn write_formula_cell(
+    worksheet: &mut Worksheet,
+    sheet: &Sheet,
+    addr: &CellAddr,
+    excel_formula: &str,
+    format: &Format,
+) -> Result<(), ExcelError> {
+    let row = (addr.row - 1) as u32;
+    let col = (column_letter_to_number(&addr.col) * 2) as u16;
+    if sheet.spill_range(addr).is_some() || excel_formula.contains("ANCHORARRAY(") {
+        worksheet.write_dynamic_formula_with_format(row, col, excel_formula, format)?;
+    } else {
+        worksheet.write_formula_with_format(row, col, excel_formula, format)?;
+    }
+    Ok(())
+}
+
 /// Create a conversions sheet with all unit conversion factors used in formulas
 fn create_conversions_sheet(
     conversions: &BTreeMap<String, ConversionEntry>,
@@ -1696,6 +1941,9 @@ mod tests {
 
         // Mixed case
         assert_eq!(transform_formula_for_excel("=b1"), "=C1");
+
+        // Absolute markers are kept on the doubled columns
+        assert_eq!(transform_formula_for_excel("=$B$1*B$2"), "=$C$1*C$2");
         assert_eq!(transform_formula_for_excel("=B1+b2"), "=C1+C2");
 
         // Functions (should not be transformed)
@@ -1704,6 +1952,48 @@ mod tests {
             transform_formula_for_excel("=AVERAGE(A1:A5)"),
             "=AVERAGE(A1:A5)"
         );
+
+        // Sheet-qualified references keep the sheet name and shift the column
+        assert_eq!(transform_formula_for_excel("=Sheet2!B1"), "='Sheet2'!C1");
+        assert_eq!(
+            transform_formula_for_excel("=SUM('Cost Model'!b3:b20)"),
+            "=SUM('Cost Model'!C3:C20)"
+        );
+
+        // & is Excel's own text operator
+        assert_eq!(
+            transform_formula_for_excel("=\"Total: \" & B1 & \" ft\""),
+            "=\"Total: \"&C1&\" ft\""
+        );
+        assert_eq!(
+            transform_formula_for_excel("=(B1 > 2) & \"\""),
+            "=(C1>2)&\"\""
+        );
+
+        // Spilled ranges are written with ANCHORARRAY
+        assert_eq!(
+            transform_formula_for_excel("=SUM(B1#)"),
+            "=SUM(ANCHORARRAY(C1))"
+        );
+        assert_eq!(
+            transform_formula_for_excel("=COUNT(Sheet2!B1#)"),
+            "=COUNT(ANCHORARRAY('Sheet2'!C1))"
+        );
+    }
+
+    #[test]
+    fn test_calls_unit_functions() {
+        assert!(calls_unit_functions("=UNIT(A1)"));
+        assert!(calls_unit_functions("=2 * VALUE_IN(A1, \"ft\")"));
+        assert!(calls_unit_functions(
+            "=IF(ISCOMPATIBLE(A1, B1), A1 + B1, 0)"
+        ));
+        assert!(!calls_unit_functions("=ISUNIT(A1)"));
+        assert!(!calls_unit_functions("=CONVERT(A1, \"ft\")"));
+        assert!(calls_unit_functions(
+            "=\"Total: \" & TEXT(A1, \"0.0 [ft]\")"
+        ));
+        assert!(!calls_unit_functions("=LEFT(A1, 3) & \"...\""));
     }
 
     #[test]
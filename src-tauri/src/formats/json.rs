@@ -1,9 +1,10 @@
 // JSON serialization for workbook files (.usheet format)
 
-use crate::core::cell::{Cell, CellValue};
+use crate::core::cell::{Cell, CellError, CellValue, ErrorKind};
 use crate::core::table::{CellAddr, Sheet};
-use crate::core::units::{BaseDimension, Unit};
-use crate::core::workbook::{DisplayPreference, Workbook, WorkbookSettings};
+use crate::core::units::{BaseDimension, Exponent, Unit};
+use crate::core::workbook::{DisplayPreference, IterativeCalculation, Workbook, WorkbookSettings};
+use chrono::NaiveDateTime;
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
 use thiserror::Error;
@@ -68,6 +69,10 @@ pub struct WorkbookData {
     /// Named ranges (name -> (sheet_index, cell_address))
     #[serde(default)]
     named_ranges: HashMap<String, NamedRangeData>,
+
+    /// Workbook functions (name -> LAMBDA definition)
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+    lambdas: HashMap<String, String>,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -81,6 +86,12 @@ pub struct WorkbookSettingsData {
     display_preference: String, // "AsEntered", "Metric", "Imperial"
     auto_recalculate: bool,
     show_warnings: bool,
+    #[serde(default)]
+    significant_figures: bool,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    random_seed: Option<u64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    iterative_calculation: Option<IterativeCalculation>,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -122,9 +133,30 @@ pub struct CellData {
 #[serde(tag = "type")]
 pub enum CellValueData {
     Empty,
-    Number { value: f64 },
-    Text { text: String },
-    Error { message: String },
+    Number {
+        value: f64,
+        /// Standard uncertainty, in the storage unit (only for numbers entered with one)
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        uncertainty: Option<f64>,
+        /// Significant figures (only for measured numbers, such as 12.0 m typed into a cell)
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        significant_figures: Option<u32>,
+    },
+    Text {
+        text: String,
+    },
+    Boolean {
+        value: bool,
+    },
+    DateTime {
+        value: NaiveDateTime,
+    },
+    Error {
+        /// Missing in files saved before errors had kinds
+        #[serde(default)]
+        kind: ErrorKind,
+        message: String,
+    },
 }
 
 impl WorkbookFile {
@@ -210,12 +242,18 @@ impl WorkbookData {
             sheets,
             active_sheet: workbook.active_sheet_index(),
             named_ranges,
+            lambdas: workbook.list_functions().into_iter().collect(),
         }
     }
 
     fn to_workbook(&self) -> Result<Workbook, SerializationError> {
         let mut workbook = Workbook::new(&self.name);
 
+        // Circular references are only kept if the workbook calculates them by iterating
+        workbook
+            .set_iterative_calculation(self.settings.iterative_calculation)
+            .ok();
+
         // Process sheets - reuse default sheet for first one, add rest
         for (i, sheet_data) in self.sheets.iter().enumerate() {
             let idx = if i == 0 {
@@ -258,6 +296,8 @@ impl WorkbookData {
         workbook.set_display_preference(self.settings.to_display_preference());
         workbook.settings_mut().auto_recalculate = self.settings.auto_recalculate;
         workbook.settings_mut().show_warnings = self.settings.show_warnings;
+        workbook.settings_mut().significant_figures = self.settings.significant_figures;
+        workbook.set_random_seed(self.settings.random_seed).ok();
 
         // Restore named ranges
         for (name, range_data) in &self.named_ranges {
@@ -268,6 +308,14 @@ impl WorkbookData {
             }
         }
 
+        // Restore workbook functions, which recalculates the cells calling them
+        for (name, definition) in &self.lambdas {
+            workbook.define_function(name, definition).ok();
+        }
+
+        // Track dependencies now that every sheet and named range exists
+        workbook.rebuild_dependencies();
+
         workbook.mark_clean();
 
         Ok(workbook)
@@ -285,6 +333,9 @@ impl WorkbookSettingsData {
             .to_string(),
             auto_recalculate: settings.auto_recalculate,
             show_warnings: settings.show_warnings,
+            significant_figures: settings.significant_figures,
+            random_seed: settings.random_seed,
+            iterative_calculation: settings.iterative_calculation,
         }
     }
 
@@ -321,7 +372,7 @@ impl SheetData {
 impl CellData {
     fn from_cell(cell: &Cell) -> Self {
         Self {
-            value: CellValueData::from_cell_value(cell.value()),
+            value: CellValueData::from_cell(cell),
             storage_unit: cell.storage_unit().canonical().to_string(),
             display_unit: cell
                 .display_unit()
@@ -339,11 +390,13 @@ impl CellData {
 
         let mut cell = match &self.value {
             CellValueData::Empty => Cell::empty(),
-            CellValueData::Number { value } => Cell::new(*value, unit.clone()),
+            CellValueData::Number { value, .. } => Cell::new(*value, unit.clone()),
             CellValueData::Text { text } => Cell::with_text(text.clone()),
-            CellValueData::Error { message } => {
+            CellValueData::Boolean { value } => Cell::with_boolean(*value),
+            CellValueData::DateTime { value } => Cell::with_datetime(*value),
+            CellValueData::Error { kind, message } => {
                 let mut c = Cell::empty();
-                c.set_value(CellValue::Error(message.clone()));
+                c.set_value(CellValue::Error(CellError::new(*kind, message.clone())));
                 c
             }
         };
@@ -352,12 +405,27 @@ impl CellData {
         if let Some(formula) = &self.formula {
             cell = Cell::with_formula(formula.clone());
             // Set the evaluated value
-            if let CellValueData::Number { value } = &self.value {
-                cell.set_value(CellValue::Number(*value));
-                cell.set_storage_unit(unit.clone());
+            match &self.value {
+                CellValueData::Number { value, .. } => {
+                    cell.set_value(CellValue::Number(*value));
+                    cell.set_storage_unit(unit.clone());
+                }
+                CellValueData::Boolean { value } => cell.set_value(CellValue::Boolean(*value)),
+                CellValueData::DateTime { value } => cell.set_value(CellValue::DateTime(*value)),
+                _ => {}
             }
         }
 
+        if let CellValueData::Number {
+            uncertainty,
+            significant_figures,
+            ..
+        } = &self.value
+        {
+            cell.set_uncertainty(*uncertainty);
+            cell.set_significant_figures(*significant_figures);
+        }
+
         // Set display unit if different
         if let Some(display_unit_str) = &self.display_unit {
             let display_unit = parse_unit_from_canonical(display_unit_str);
@@ -374,12 +442,21 @@ impl CellData {
 }
 
 impl CellValueData {
-    fn from_cell_value(value: &CellValue) -> Self {
-        match value {
+    fn from_cell(cell: &Cell) -> Self {
+        match cell.value() {
             CellValue::Empty => Self::Empty,
-            CellValue::Number(n) => Self::Number { value: *n },
+            CellValue::Number(n) => Self::Number {
+                value: *n,
+                uncertainty: cell.uncertainty(),
+                significant_figures: cell.significant_figures(),
+            },
             CellValue::Text(t) => Self::Text { text: t.clone() },
-            CellValue::Error(e) => Self::Error { message: e.clone() },
+            CellValue::Boolean(b) => Self::Boolean { value: *b },
+            CellValue::DateTime(d) => Self::DateTime { value: *d },
+            CellValue::Error(e) => Self::Error {
+                kind: e.kind,
+                message: e.message.clone(),
+            },
         }
     }
 }
@@ -426,7 +503,7 @@ fn parse_unit_from_canonical(canonical: &str) -> Unit {
         let base_str = &canonical[..pos];
         let power_str = &canonical[pos + 1..];
 
-        if let Ok(power) = power_str.parse::<i32>() {
+        if let Ok(power) = power_str.parse::<Exponent>() {
             let dimension = get_base_dimension_for_json(base_str);
             return Unit::compound(canonical.to_string(), vec![(dimension, power)], vec![]);
         }
@@ -438,17 +515,17 @@ fn parse_unit_from_canonical(canonical: &str) -> Unit {
 }
 
 /// Parse a unit string and extract dimension with power (e.g., "ft^2" -> (Length, 2))
-fn parse_dimension_with_power(unit_str: &str) -> (BaseDimension, i32) {
+fn parse_dimension_with_power(unit_str: &str) -> (BaseDimension, Exponent) {
     if let Some(pos) = unit_str.find('^') {
         let base_str = &unit_str[..pos];
         let power_str = &unit_str[pos + 1..];
 
-        if let Ok(power) = power_str.parse::<i32>() {
+        if let Ok(power) = power_str.parse::<Exponent>() {
             return (get_base_dimension_for_json(base_str), power);
         }
     }
 
-    (get_base_dimension_for_json(unit_str), 1)
+    (get_base_dimension_for_json(unit_str), Exponent::ONE)
 }
 
 /// Get base dimension for a unit string (helper for JSON parsing)
@@ -670,4 +747,124 @@ mod tests {
         assert_eq!(cell.as_number(), Some(100.0));
         assert_eq!(cell.storage_unit().canonical(), "m");
     }
+
+    #[test]
+    fn test_error_kind_serialization() {
+        let mut cell = Cell::empty();
+        cell.set_value(CellValue::Error(CellError::new(
+            ErrorKind::DivZero,
+            "Division by zero",
+        )));
+
+        let data = CellData::from_cell(&cell);
+        let json = serde_json::to_string(&data).unwrap();
+        assert!(json.contains("\"kind\":\"DivZero\""));
+
+        let restored: CellData = serde_json::from_str(&json).unwrap();
+        assert_eq!(restored.to_cell().unwrap(), cell);
+
+        // Files saved before errors had kinds still load
+        let legacy: CellData = serde_json::from_str(
+            r#"{"value":{"type":"Error","message":"Oops"},"storage_unit":"","display_unit":null,"formula":null,"warning":null}"#,
+        )
+        .unwrap();
+        let error = legacy
+            .to_cell()
+            .unwrap()
+            .value()
+            .as_error()
+            .cloned()
+            .unwrap();
+        assert_eq!(error, CellError::new(ErrorKind::Value, "Oops"));
+    }
+
+    #[test]
+    fn test_datetime_serialization() {
+        let date = chrono::NaiveDate::from_ymd_opt(2024, 3, 15)
+            .unwrap()
+            .and_hms_opt(14, 30, 0)
+            .unwrap();
+        let cell = Cell::with_datetime(date);
+
+        let data = CellData::from_cell(&cell);
+        let json = serde_json::to_string(&data).unwrap();
+        assert!(json.contains(r#"{"type":"DateTime","value":"2024-03-15T14:30:00"}"#));
+
+        let restored: CellData = serde_json::from_str(&json).unwrap();
+        assert_eq!(restored.to_cell().unwrap(), cell);
+
+        // A formula's date result is kept with the formula
+        let mut formula = Cell::with_formula("=EDATE(A1, 1)");
+        formula.set_value(CellValue::DateTime(date));
+        let restored = CellData::from_cell(&formula).to_cell().unwrap();
+        assert_eq!(restored.as_datetime(), Some(date));
+    }
+
+    #[test]
+    fn test_boolean_serialization() {
+        let cell = Cell::with_boolean(true);
+
+        let data = CellData::from_cell(&cell);
+        let json = serde_json::to_string(&data).unwrap();
+        assert!(json.contains(r#"{"type":"Boolean","value":true}"#));
+
+        let restored: CellData = serde_json::from_str(&json).unwrap();
+        assert_eq!(restored.to_cell().unwrap(), cell);
+
+        // A formula's boolean result is kept with the formula
+        let mut formula = Cell::with_formula("=A1 > 3");
+        formula.set_value(CellValue::Boolean(false));
+        let restored = CellData::from_cell(&formula).to_cell().unwrap();
+        assert_eq!(restored.as_boolean(), Some(false));
+    }
+
+    #[test]
+    fn test_uncertainty_serialization() {
+        let mut cell = Cell::new(100.0, Unit::simple("m", BaseDimension::Length));
+        cell.set_uncertainty(Some(2.0));
+
+        let data = CellData::from_cell(&cell);
+        let json = serde_json::to_string(&data).unwrap();
+        assert!(json.contains(r#"{"type":"Number","value":100.0,"uncertainty":2.0}"#));
+
+        let restored: CellData = serde_json::from_str(&json).unwrap();
+        assert_eq!(restored.to_cell().unwrap(), cell);
+
+        // Numbers without an uncertainty are saved as before
+        let cell = Cell::new(100.0, Unit::simple("m", BaseDimension::Length));
+        let json = serde_json::to_string(&CellData::from_cell(&cell)).unwrap();
+        assert!(json.contains(r#"{"type":"Number","value":100.0}"#));
+    }
+
+    #[test]
+    fn test_significant_figures_serialization() {
+        let mut cell = Cell::new(12.0, Unit::simple("m", BaseDimension::Length));
+        cell.set_significant_figures(Some(3));
+
+        let data = CellData::from_cell(&cell);
+        let json = serde_json::to_string(&data).unwrap();
+        assert!(json.contains(r#"{"type":"Number","value":12.0,"significant_figures":3}"#));
+
+        let restored: CellData = serde_json::from_str(&json).unwrap();
+        assert_eq!(restored.to_cell().unwrap(), cell);
+
+        // The mode is saved with the settings; files from before it have it off
+        let mut workbook = Workbook::new("Lab");
+        workbook.settings_mut().significant_figures = true;
+        let json = serde_json::to_string(&WorkbookFile::from_workbook(&workbook)).unwrap();
+        let restored: WorkbookFile = serde_json::from_str(&json).unwrap();
+        assert!(
+            restored
+                .to_workbook()
+                .unwrap()
+                .settings()
+                .significant_figures
+        );
+
+        let settings: WorkbookSettingsData = serde_json::from_str(
+            r#"{"display_preference":"Metric","auto_recalculate":true,"show_warnings":true}"#,
+        )
+        .unwrap();
+        assert!(!settings.significant_figures);
+    }
 }
@@ -3,8 +3,9 @@
 
 use tauri::{Manager, State};
 use tracing_subscriber::{layer::SubscriberExt, util::SubscriberInitExt};
-use unicel_lib::commands::{AppState, CellData, NamedRangeInfo, WorkbookInfo};
+use unicel_lib::commands::{AppState, CellData, FunctionInfo, NamedRangeInfo, WorkbookInfo};
 use unicel_lib::core::settings::UnitPreferences;
+use unicel_lib::core::workbook::{GoalSeekResult, IterativeCalculation};
 
 // Tauri command definitions (must be in binary crate for macro to work)
 
@@ -53,6 +54,44 @@ fn set_display_mode(state: State<AppState>, mode: String) -> Result<(), String>
     unicel_lib::commands::set_display_mode_impl(&state, mode)
 }
 
+#[tauri::command]
+fn set_significant_figures(state: State<AppState>, enabled: bool) -> Result<(), String> {
+    unicel_lib::commands::set_significant_figures_impl(&state, enabled)
+}
+
+#[tauri::command]
+fn set_random_seed(state: State<AppState>, seed: Option<u64>) -> Result<(), String> {
+    unicel_lib::commands::set_random_seed_impl(&state, seed)
+}
+
+#[tauri::command]
+fn set_iterative_calculation(
+    state: State<AppState>,
+    iteration: Option<IterativeCalculation>,
+) -> Result<(), String> {
+    unicel_lib::commands::set_iterative_calculation_impl(&state, iteration)
+}
+
+#[tauri::command]
+fn get_circular_references(state: State<AppState>) -> Result<Vec<Vec<String>>, String> {
+    unicel_lib::commands::get_circular_references_impl(&state)
+}
+
+#[tauri::command]
+fn goal_seek(
+    state: State<AppState>,
+    target: String,
+    goal: String,
+    changing: String,
+) -> Result<GoalSeekResult, String> {
+    unicel_lib::commands::goal_seek_impl(&state, target, goal, changing)
+}
+
+#[tauri::command]
+fn recalculate(state: State<AppState>) -> Result<(), String> {
+    unicel_lib::commands::recalculate_impl(&state)
+}
+
 #[tauri::command]
 fn get_unit_preferences(state: State<AppState>) -> Result<UnitPreferences, String> {
     unicel_lib::commands::get_unit_preferences_impl(&state)
@@ -225,6 +264,21 @@ fn get_named_range_for_cell(
     unicel_lib::commands::get_named_range_for_cell_impl(&state, sheet_index, cell_address)
 }
 
+#[tauri::command]
+fn list_functions(state: State<AppState>) -> Result<Vec<FunctionInfo>, String> {
+    unicel_lib::commands::list_functions_impl(&state)
+}
+
+#[tauri::command]
+fn define_function(state: State<AppState>, name: String, definition: String) -> Result<(), String> {
+    unicel_lib::commands::define_function_impl(&state, name, definition)
+}
+
+#[tauri::command]
+fn delete_function(state: State<AppState>, name: String) -> Result<(), String> {
+    unicel_lib::commands::delete_function_impl(&state, name)
+}
+
 #[tauri::command]
 fn set_column_width(state: State<AppState>, col: String, width: f64) -> Result<(), String> {
     unicel_lib::commands::set_column_width_impl(&state, col, width)
@@ -289,6 +343,16 @@ fn delete_row(state: State<AppState>, row: usize) -> Result<(), String> {
     unicel_lib::commands::delete_row_impl(&state, row)
 }
 
+#[tauri::command]
+fn copy_range(state: State<AppState>, source: String, destination: String) -> Result<(), String> {
+    unicel_lib::commands::copy_range_impl(&state, source, destination)
+}
+
+#[tauri::command]
+fn move_range(state: State<AppState>, source: String, destination: String) -> Result<(), String> {
+    unicel_lib::commands::move_range_impl(&state, source, destination)
+}
+
 fn main() {
     // Initialize logging
     tracing_subscriber::registry()
@@ -314,6 +378,12 @@ fn main() {
             get_current_file,
             get_recent_files,
             set_display_mode,
+            set_significant_figures,
+            set_random_seed,
+            set_iterative_calculation,
+            get_circular_references,
+            goal_seek,
+            recalculate,
             get_unit_preferences,
             update_unit_preferences,
             set_metric_system,
@@ -336,6 +406,9 @@ fn main() {
             delete_named_range,
             get_named_range,
             get_named_range_for_cell,
+            list_functions,
+            define_function,
+            delete_function,
             set_column_width,
             get_column_width,
             set_row_height,
@@ -348,6 +421,8 @@ fn main() {
             insert_row_after,
             delete_column,
             delete_row,
+            copy_range,
+            move_range,
         ])
         .run(tauri::generate_context!())
         .expect("error while running tauri application");
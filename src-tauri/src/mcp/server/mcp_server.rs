@@ -273,6 +273,7 @@ impl McpServer {
                 "sheets": sheets,
                 "active_sheet": workbook.active_sheet().name(),
                 "display_preference": format!("{:?}", workbook.settings().display_preference),
+                "significant_figures": workbook.settings().significant_figures,
             });
 
             Ok(ResourceContent {
@@ -288,9 +289,11 @@ impl McpServer {
                 JsonRpcError::invalid_params(&format!("Sheet not found: {}", sheet_name))
             })?;
 
+            // Including the cells array formulas spilled into
             let cells: Vec<Value> = sheet
                 .cell_addresses()
                 .iter()
+                .chain(&sheet.spilled_addresses())
                 .filter_map(|addr| {
                     sheet.get(addr).map(|cell| {
                         json!({
@@ -298,7 +301,13 @@ impl McpServer {
                             "value": match cell.value() {
                                 crate::core::cell::CellValue::Number(n) => json!(n),
                                 crate::core::cell::CellValue::Text(t) => json!(t),
-                                crate::core::cell::CellValue::Error(e) => json!({"error": e}),
+                                crate::core::cell::CellValue::Boolean(b) => json!(b),
+                                crate::core::cell::CellValue::DateTime(d) => {
+                                    json!(crate::core::datetime::format_datetime(d))
+                                }
+                                crate::core::cell::CellValue::Error(e) => {
+                                    json!({"error": e.kind.code(), "message": e.message})
+                                }
                                 crate::core::cell::CellValue::Empty => json!(null),
                             },
                             "unit": cell.storage_unit().to_string(),
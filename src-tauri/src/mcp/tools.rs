@@ -3,6 +3,8 @@
 use super::types::*;
 use crate::core::{
     cell::{Cell, CellValue},
+    datetime::{format_datetime, parse_datetime},
+    formula::{Evaluator, Expr},
     table::CellAddr,
     units::{parse_unit, Unit, UnitLibrary},
     workbook::Workbook,
@@ -31,6 +33,8 @@ pub fn get_tool_definitions() -> Vec<ToolDefinition> {
         define_validate_unit(),
         // Schema tools
         define_get_workbook_metadata(),
+        // What-if tools
+        define_goal_seek(),
     ]
 }
 
@@ -145,7 +149,7 @@ fn define_write_cell() -> ToolDefinition {
                     "description": "Cell reference (e.g., 'A1', 'B5')"
                 },
                 "value": {
-                    "description": "Cell value (number, string, or formula)"
+                    "description": "Cell value (number, boolean, string, date such as \"2024-03-15\", or formula)"
                 },
                 "unit": {
                     "type": "string",
@@ -315,6 +319,35 @@ fn define_get_workbook_metadata() -> ToolDefinition {
     }
 }
 
+fn define_goal_seek() -> ToolDefinition {
+    ToolDefinition {
+        name: "goal_seek".to_string(),
+        description: "Find the value of an input cell that makes a formula cell reach a target value, e.g. the hourly rate that makes a monthly total 50000 USD. The input cell keeps the value found".to_string(),
+        input_schema: json!({
+            "type": "object",
+            "properties": {
+                "target_cell": {
+                    "type": "string",
+                    "description": "Cell with the formula to reach the target value (e.g., 'B10')"
+                },
+                "target_value": {
+                    "type": "number",
+                    "description": "Value the formula should reach"
+                },
+                "unit": {
+                    "type": "string",
+                    "description": "Unit of the target value, converted into the target cell's unit (optional, defaults to the target cell's unit)"
+                },
+                "changing_cell": {
+                    "type": "string",
+                    "description": "Cell holding the number to change, which keeps its unit (e.g., 'B2')"
+                }
+            },
+            "required": ["target_cell", "target_value", "changing_cell"]
+        }),
+    }
+}
+
 // ============================================================================
 // Tool Handlers
 // ============================================================================
@@ -353,6 +386,7 @@ impl ToolHandler {
             "list_compatible_units" => self.handle_list_compatible_units(args),
             "validate_unit" => self.handle_validate_unit(args),
             "get_workbook_metadata" => self.handle_get_workbook_metadata(args),
+            "goal_seek" => self.handle_goal_seek(args),
             _ => Err(format!("Unknown tool: {}", name)),
         };
 
@@ -389,7 +423,9 @@ impl ToolHandler {
             "value": match cell.value() {
                 CellValue::Number(n) => json!(n),
                 CellValue::Text(t) => json!(t),
-                CellValue::Error(e) => json!({"error": e}),
+                CellValue::Boolean(b) => json!(b),
+                CellValue::DateTime(d) => json!(format_datetime(d)),
+                CellValue::Error(e) => json!({"error": e.kind.code(), "message": e.message}),
                 CellValue::Empty => json!(null),
             },
             "unit": {
@@ -397,6 +433,8 @@ impl ToolHandler {
                 "dimension": format!("{:?}", cell.storage_unit().dimension()),
                 "display": cell.display_unit().to_string(),
             },
+            "uncertainty": cell.uncertainty(),
+            "significant_figures": cell.significant_figures(),
             "formula": cell.formula().map(|f| f.to_string()),
             "warnings": cell.warning().map(|w| vec![w]).unwrap_or_default(),
             "is_empty": cell.is_empty(),
@@ -469,17 +507,21 @@ impl ToolHandler {
         let unit_str = args.get("unit").and_then(|v| v.as_str());
 
         let mut workbook = self.workbook.lock().unwrap();
-        let sheet = workbook.active_sheet_mut();
+        let sheet_index = workbook.active_sheet_index();
 
         // Determine cell type and create appropriate cell
         let cell = if let Some(s) = value.as_str() {
             if s.starts_with('=') {
                 // Formula
                 Cell::with_formula(s)
+            } else if let Some(date) = parse_datetime(s) {
+                Cell::with_datetime(date)
             } else {
                 // Text
                 Cell::with_text(s)
             }
+        } else if let Some(b) = value.as_bool() {
+            Cell::with_boolean(b)
         } else if let Some(n) = value.as_f64() {
             // Number with optional unit
             if let Some(unit_str) = unit_str {
@@ -493,8 +535,8 @@ impl ToolHandler {
             return Err("Invalid value type".to_string());
         };
 
-        sheet
-            .set(addr.clone(), cell)
+        workbook
+            .set_cell(sheet_index, addr, cell)
             .map_err(|e| format!("Error writing cell: {}", e))?;
 
         let result = json!({
@@ -531,9 +573,18 @@ impl ToolHandler {
             .and_then(|v| v.as_str())
             .ok_or("Missing to_unit")?;
 
-        let converted = self
-            .unit_library
-            .convert(value, from_unit, to_unit)
+        // Convert the same way the CONVERT function does, so compound units work too
+        let expr = Expr::Function {
+            name: "CONVERT".to_string(),
+            args: vec![
+                Expr::number_with_unit(value, from_unit),
+                Expr::String(to_unit.to_string()),
+            ],
+        };
+        let converted = Evaluator::new(&self.unit_library)
+            .eval(&expr)
+            .ok()
+            .and_then(|result| result.as_number())
             .ok_or_else(|| format!("Cannot convert from {} to {}", from_unit, to_unit))?;
 
         let result = json!({
@@ -673,6 +724,53 @@ impl ToolHandler {
             "sheets": sheets,
             "active_sheet": workbook.active_sheet().name(),
             "display_preference": format!("{:?}", workbook.settings().display_preference),
+            "significant_figures": workbook.settings().significant_figures,
+            "random_seed": workbook.settings().random_seed,
+            "iterative_calculation": workbook.settings().iterative_calculation,
+            "circular_references": workbook.circular_references(),
+        });
+
+        Ok(serde_json::to_string_pretty(&result).unwrap())
+    }
+
+    fn handle_goal_seek(&self, args: HashMap<String, Value>) -> Result<String, String> {
+        let cell_arg = |name: &str| {
+            let cell_ref = args
+                .get(name)
+                .and_then(|v| v.as_str())
+                .ok_or(format!("Missing {}", name))?;
+            CellAddr::from_string(cell_ref).map_err(|e| format!("Invalid cell reference: {}", e))
+        };
+        let target = cell_arg("target_cell")?;
+        let changing = cell_arg("changing_cell")?;
+
+        let target_value = args
+            .get("target_value")
+            .and_then(|v| v.as_f64())
+            .ok_or("Missing or invalid target_value")?;
+
+        let unit = match args.get("unit").and_then(|v| v.as_str()) {
+            Some(unit_str) => Some(
+                parse_unit(unit_str, &self.unit_library)
+                    .map_err(|e| format!("Invalid unit '{}': {}", unit_str, e))?,
+            ),
+            None => None,
+        };
+
+        let mut workbook = self.workbook.lock().unwrap();
+        let sheet_index = workbook.active_sheet_index();
+        let result = workbook
+            .goal_seek(sheet_index, &target, target_value, unit.as_ref(), &changing)
+            .map_err(|e| e.to_string())?;
+
+        let result = json!({
+            "changing_cell": changing.to_string(),
+            "value": result.value,
+            "unit": result.unit,
+            "target_cell": target.to_string(),
+            "target_value": result.target_value,
+            "target_unit": result.target_unit,
+            "iterations": result.iterations,
         });
 
         Ok(serde_json::to_string_pretty(&result).unwrap())
@@ -0,0 +1,59 @@
+// Test boolean entry and boolean formulas through the workbook commands
+
+use unicel_lib::commands::workbook::{parse_cell_input, CellValueData};
+
+mod common;
+use common::{new_state, set};
+
+#[test]
+fn test_boolean_input_is_parsed() {
+    for (input, expected) in [("TRUE", true), ("false", false), (" True ", true)] {
+        let cell = parse_cell_input(input).unwrap();
+        assert_eq!(cell.as_boolean(), Some(expected), "{}", input);
+    }
+
+    assert!(parse_cell_input("yes").unwrap().is_text());
+    assert!(parse_cell_input("1").unwrap().is_number());
+}
+
+#[test]
+fn test_flag_column() {
+    let state = new_state("Flags");
+
+    set(&state, "A1", "100 USD");
+    set(&state, "A2", "250 USD");
+    set(&state, "B1", "TRUE");
+    set(&state, "B2", "FALSE");
+
+    match set(&state, "C1", "=B1 * A1 + B2 * A2").value {
+        CellValueData::Number { value } => assert_eq!(value, 100.0),
+        other => panic!("Expected a number, got {:?}", other),
+    }
+
+    match set(&state, "C2", "=A2 > A1").value {
+        CellValueData::Boolean { value } => assert!(value),
+        other => panic!("Expected a boolean, got {:?}", other),
+    }
+
+    // Flags in a range are not counted
+    match set(&state, "C3", "=SUM(B1:B2)").value {
+        CellValueData::Number { value } => assert_eq!(value, 0.0),
+        other => panic!("Expected a number, got {:?}", other),
+    }
+
+    // Nor are flags in a referenced cell, though TRUE written in the formula is 1
+    match set(&state, "C4", "=SUM(B1, 1)").value {
+        CellValueData::Number { value } => assert_eq!(value, 1.0),
+        other => panic!("Expected a number, got {:?}", other),
+    }
+    match set(&state, "C5", "=SUM(TRUE, 1)").value {
+        CellValueData::Number { value } => assert_eq!(value, 2.0),
+        other => panic!("Expected a number, got {:?}", other),
+    }
+
+    // A flag is never equal to a number
+    match set(&state, "C6", "=B1 = 1").value {
+        CellValueData::Boolean { value } => assert!(!value),
+        other => panic!("Expected a boolean, got {:?}", other),
+    }
+}
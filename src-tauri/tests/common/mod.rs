@@ -0,0 +1,70 @@
+// Helpers for the tests that drive a workbook through its commands
+// Each test file uses only some of them
+
+#![allow(dead_code)]
+
+use unicel_lib::commands::workbook::{
+    create_workbook_impl, get_sheet_cells_impl, set_cell_impl, AppState, CellData, CellValueData,
+};
+use unicel_lib::core::table::CellAddr;
+
+/// App state with a new workbook open
+pub fn new_state(name: &str) -> AppState {
+    let state = AppState::default();
+    create_workbook_impl(&state, name.to_string()).unwrap();
+    state
+}
+
+/// Enter text or a formula into a cell of the active sheet, as if typed
+pub fn set(state: &AppState, cell_ref: &str, input: &str) -> CellData {
+    set_cell_impl(state, cell_ref.to_string(), input.to_string()).unwrap()
+}
+
+/// A cell of the active sheet as the front end sees it
+pub fn cell(state: &AppState, cell_ref: &str) -> CellData {
+    get_sheet_cells_impl(state)
+        .unwrap()
+        .into_iter()
+        .find(|(addr, _)| addr == cell_ref)
+        .map(|(_, data)| data)
+        .unwrap_or_else(|| panic!("{} is empty", cell_ref))
+}
+
+pub fn number(data: &CellData) -> f64 {
+    match data.value {
+        CellValueData::Number { value } => value,
+        ref other => panic!("Expected a number, got {:?}", other),
+    }
+}
+
+pub fn error_code(data: &CellData) -> String {
+    match &data.value {
+        CellValueData::Error { code, .. } => code.clone(),
+        other => panic!("Expected an error, got {:?}", other),
+    }
+}
+
+/// The unit a cell's number is stored in, as written by the unit system (m^2, USD/month)
+pub fn unit_of(state: &AppState, cell_ref: &str) -> String {
+    let workbook = state.workbook.lock().unwrap();
+    let sheet = workbook.as_ref().unwrap().active_sheet();
+    sheet
+        .get(&CellAddr::from_string(cell_ref).unwrap())
+        .unwrap()
+        .storage_unit()
+        .canonical()
+        .to_string()
+}
+
+/// A cell's number and the unit it is stored in
+pub fn quantity(state: &AppState, cell_ref: &str) -> (f64, String) {
+    let workbook = state.workbook.lock().unwrap();
+    let sheet = workbook.as_ref().unwrap().active_sheet();
+    let cell = sheet
+        .get(&CellAddr::from_string(cell_ref).unwrap())
+        .unwrap();
+    (
+        cell.as_number().unwrap(),
+        cell.storage_unit().canonical().to_string(),
+    )
+}
@@ -339,3 +339,46 @@ fn test_area_with_numerator_exponent_ft2_per_s() {
         panic!("Expected numeric value");
     }
 }
+
+/// Enter a formula into A1 and return its value as displayed in Metric mode
+fn metric_value_of_formula(formula: &str) -> f64 {
+    let state = AppState::default();
+
+    workbook::create_workbook_impl(&state, "Test".to_string()).unwrap();
+    workbook::set_cell_impl(&state, "A1".to_string(), formula.to_string()).unwrap();
+    workbook::set_display_mode_impl(&state, "Metric".to_string()).unwrap();
+
+    let cells = workbook::get_sheet_cells_impl(&state).unwrap();
+    let cell_data = cells.iter().find(|(addr, _)| addr == "A1").unwrap();
+
+    match cell_data.1.value {
+        CellValueData::Number { value } => value,
+        ref other => panic!("Expected numeric value for {}, got {:?}", formula, other),
+    }
+}
+
+#[test]
+fn test_exponent_unit_literals_in_formulas() {
+    // Literal ft^2, superscript ft² and (10 ft)^2 all mean the same area
+    let expected = 100.0 * 0.09290304;
+    for formula in ["=100 ft^2", "=100 ft²", "=(10 ft)^2"] {
+        let value = metric_value_of_formula(formula);
+        assert!(
+            (value - expected).abs() < 0.001,
+            "{}: expected {} m^2, got {}",
+            formula,
+            expected,
+            value
+        );
+    }
+
+    // Price per area written as a literal
+    let value = metric_value_of_formula("=15 $/ft^2");
+    let expected = 15.0 / (0.3048 * 0.3048);
+    assert!(
+        (value - expected).abs() < 0.01,
+        "Expected {} USD/m^2, got {}",
+        expected,
+        value
+    );
+}
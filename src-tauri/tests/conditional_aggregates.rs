@@ -0,0 +1,52 @@
+// Test conditional aggregates over a cost table through the workbook commands
+
+mod common;
+use common::{new_state, number, set};
+
+#[test]
+fn test_costs_by_region() {
+    let state = new_state("Costs");
+
+    let rows = [
+        ("us-east", "512 MB", "12 USD"),
+        ("eu-west", "16 GB", "80 USD"),
+        ("us-east", "32 GB", "150 USD"),
+        ("us-west", "24576 MB", "110 USD"),
+    ];
+    for (i, (region, memory, cost)) in rows.iter().enumerate() {
+        let row = i + 1;
+        set(&state, &format!("A{}", row), region);
+        set(&state, &format!("B{}", row), memory);
+        set(&state, &format!("C{}", row), cost);
+    }
+
+    assert_eq!(
+        number(&set(&state, "E1", "=SUMIF(A:A, \"us-*\", C:C)")),
+        272.0
+    );
+    assert_eq!(
+        number(&set(&state, "E2", "=COUNTIF(B:B, \">=16 GB\")")),
+        3.0
+    );
+    assert_eq!(
+        number(&set(
+            &state,
+            "E3",
+            "=SUMIFS(C:C, A:A, \"us-east\", B:B, \">1 GB\")"
+        )),
+        150.0
+    );
+
+    // The totals follow changes to the table
+    set(&state, "B1", "20 GB");
+    let workbook = state.workbook.lock().unwrap();
+    let sheet = workbook.as_ref().unwrap().active_sheet();
+    let count = |cell: &str| {
+        sheet
+            .get(&unicel_lib::core::table::CellAddr::from_string(cell).unwrap())
+            .and_then(|cell| cell.as_number())
+            .unwrap()
+    };
+    assert_eq!(count("E2"), 4.0);
+    assert_eq!(count("E3"), 162.0);
+}
@@ -0,0 +1,48 @@
+// Test date entry and date formulas through the workbook commands
+
+use unicel_lib::commands::workbook::{parse_cell_input, CellValueData};
+
+mod common;
+use common::{new_state, set};
+
+#[test]
+fn test_date_input_is_parsed() {
+    for input in ["2024-03-15", "2024/03/15", "Mar 15, 2024", "15 Mar 2024"] {
+        let cell = parse_cell_input(input).unwrap();
+        assert_eq!(cell.formatted(), "2024-03-15", "{}", input);
+    }
+
+    let cell = parse_cell_input("2024-03-15 09:45").unwrap();
+    assert_eq!(cell.formatted(), "2024-03-15 09:45");
+
+    // Numbers, times of day and invalid dates are not dates
+    assert!(parse_cell_input("2024").unwrap().is_number());
+    assert!(parse_cell_input("2024-02-30").unwrap().is_text());
+}
+
+#[test]
+fn test_schedule_with_dates() {
+    let state = new_state("Schedule");
+
+    set(&state, "A1", "2024-03-15");
+    set(&state, "A2", "3 weeks");
+
+    match set(&state, "B1", "=A1 + A2").value {
+        CellValueData::DateTime { text, .. } => assert_eq!(text, "2024-04-05"),
+        other => panic!("Expected a date, got {:?}", other),
+    }
+
+    match set(&state, "B2", "=B1 - A1").value {
+        CellValueData::Number { value } => assert_eq!(value, 21.0),
+        other => panic!("Expected a number of days, got {:?}", other),
+    }
+
+    // Changing the start date moves the end date
+    set(&state, "A1", "2024-04-01");
+    let workbook = state.workbook.lock().unwrap();
+    let sheet = workbook.as_ref().unwrap().active_sheet();
+    let end = sheet
+        .get(&unicel_lib::core::table::CellAddr::new("B", 1))
+        .unwrap();
+    assert_eq!(end.formatted(), "2024-04-22");
+}
@@ -0,0 +1,168 @@
+// Test formulas that return arrays and spill into the cells below and to the right
+
+use std::env;
+use std::fs;
+use unicel_lib::commands::workbook::{set_cell_impl, AppState, CellValueData};
+use unicel_lib::core::cell::ErrorKind;
+use unicel_lib::core::table::CellAddr;
+use unicel_lib::formats::excel::export_to_excel;
+
+mod common;
+use common::{new_state, set};
+
+/// The value shown in a cell, or None if it is empty
+fn shown(state: &AppState, cell_ref: &str) -> Option<String> {
+    let workbook = state.workbook.lock().unwrap();
+    let sheet = workbook.as_ref().unwrap().active_sheet();
+    sheet
+        .get(&CellAddr::from_string(cell_ref).unwrap())
+        .map(|cell| cell.formatted())
+}
+
+fn number(state: &AppState, cell_ref: &str) -> Option<f64> {
+    let workbook = state.workbook.lock().unwrap();
+    let sheet = workbook.as_ref().unwrap().active_sheet();
+    sheet
+        .get(&CellAddr::from_string(cell_ref).unwrap())
+        .and_then(|cell| cell.as_number())
+}
+
+#[test]
+fn test_array_functions_spill() {
+    let state = new_state("Arrays");
+    for (cell, input) in [
+        ("A1", "1.2 kg"),
+        ("A2", "800 g"),
+        ("A3", "1.2 kg"),
+        ("A4", "2 kg"),
+        ("A5", "500 g"),
+    ] {
+        set(&state, cell, input);
+    }
+
+    // SORT compares the masses in one unit
+    set(&state, "B1", "=SORT(A1:A5)");
+    let sorted: Vec<_> = (1..=5)
+        .map(|row| shown(&state, &format!("B{}", row)))
+        .collect();
+    assert_eq!(sorted[0].as_deref(), Some("500 g"));
+    assert_eq!(sorted[4].as_deref(), Some("2 kg"));
+
+    // UNIQUE drops the repeated 1.2 kg
+    set(&state, "C1", "=UNIQUE(A1:A5)");
+    assert_eq!(number(&state, "C3"), Some(2.0));
+    assert_eq!(number(&state, "C4"), Some(500.0));
+    assert_eq!(shown(&state, "C5"), None);
+
+    // FILTER keeps the rows that pass a unit-aware test
+    set(&state, "D1", "=FILTER(A1:A5, A1:A5 > 900 g)");
+    assert_eq!(shown(&state, "D1").as_deref(), Some("1.2 kg"));
+    assert_eq!(shown(&state, "D3").as_deref(), Some("2 kg"));
+    assert_eq!(shown(&state, "D4"), None);
+
+    // SEQUENCE fills rows and columns
+    set(&state, "F1", "=SEQUENCE(3, 2, 10, 5)");
+    assert_eq!(number(&state, "G1"), Some(15.0));
+    assert_eq!(number(&state, "F3"), Some(30.0));
+    assert_eq!(number(&state, "G3"), Some(35.0));
+
+    // A date sequence steps by calendar months
+    set(&state, "H1", "=SEQUENCE(3, 1, DATE(2024, 1, 31), 1 month)");
+    assert_eq!(shown(&state, "H2").as_deref(), Some("2024-02-29"));
+    assert_eq!(shown(&state, "H3").as_deref(), Some("2024-03-31"));
+
+    // CONVERT works on each value
+    set(&state, "I1", "=CONVERT(A1:A3, \"g\")");
+    assert_eq!(shown(&state, "I1").as_deref(), Some("1200 g"));
+    assert_eq!(shown(&state, "I3").as_deref(), Some("1200 g"));
+}
+
+#[test]
+fn test_blocked_spill() {
+    let state = new_state("Arrays");
+    set(&state, "A2", "in the way");
+
+    match set(&state, "A1", "=SEQUENCE(3)").value {
+        CellValueData::Error { kind, code, .. } => {
+            assert_eq!(kind, ErrorKind::Spill);
+            assert_eq!(code, "#SPILL!");
+        }
+        other => panic!("Expected #SPILL!, got {:?}", other),
+    }
+
+    // Clearing the blocking cell lets the array spill
+    set(&state, "A2", "");
+    assert_eq!(number(&state, "A1"), Some(1.0));
+    assert_eq!(number(&state, "A3"), Some(3.0));
+
+    // Spilled cells are edited through the formula that made them
+    assert!(set_cell_impl(&state, "A2".to_string(), "5".to_string()).is_err());
+}
+
+#[test]
+fn test_spill_references() {
+    let state = new_state("Arrays");
+    set(&state, "A1", "3");
+    set(&state, "B1", "=SEQUENCE(A1)");
+
+    match set(&state, "C1", "=SUM(B1#)").value {
+        CellValueData::Number { value } => assert_eq!(value, 6.0),
+        other => panic!("Expected a number, got {:?}", other),
+    }
+
+    // The spilled range grows with its formula and its dependents follow
+    set(&state, "A1", "4");
+    assert_eq!(number(&state, "B4"), Some(4.0));
+    assert_eq!(number(&state, "C1"), Some(10.0));
+
+    // Replacing the formula with a value removes the spilled cells
+    set(&state, "B1", "7");
+    assert_eq!(shown(&state, "B2"), None);
+    match shown(&state, "C1") {
+        Some(text) => assert_eq!(text, "#REF!"),
+        None => panic!("C1 should show an error"),
+    }
+}
+
+#[test]
+fn test_export_spilled_arrays() {
+    let state = new_state("Arrays");
+    set(&state, "A1", "=SEQUENCE(3)");
+    set(&state, "B1", "=SUM(A1#)");
+    set(&state, "C1", "=SEQUENCE(2, 2)");
+
+    let temp_file = env::temp_dir().join("test_dynamic_arrays.xlsx");
+    {
+        let workbook = state.workbook.lock().unwrap();
+        export_to_excel(workbook.as_ref().unwrap(), &temp_file).expect("Export failed");
+    }
+    assert!(fs::metadata(&temp_file).is_ok());
+    fs::remove_file(&temp_file).ok();
+}
+
+#[test]
+fn test_sequence_limits() {
+    let state = new_state("Arrays");
+
+    // Too many values is an error before any are made
+    let started = std::time::Instant::now();
+    for input in [
+        "=SEQUENCE(1000, 1000)",
+        "=SEQUENCE(2000000)",
+        "=SEQUENCE(1, 20000)",
+    ] {
+        match set(&state, "A1", input).value {
+            CellValueData::Error { code, .. } => assert_eq!(code, "#VALUE!", "{}", input),
+            other => panic!("Expected #VALUE! for {}, got {:?}", input, other),
+        }
+    }
+    assert!(started.elapsed().as_secs() < 5);
+
+    // An array that would run past the last row or column does not spill
+    for (cell_ref, input) in [("A1048570", "=SEQUENCE(10)"), ("XFA1", "=SEQUENCE(1, 10)")] {
+        match set(&state, cell_ref, input).value {
+            CellValueData::Error { kind, .. } => assert_eq!(kind, ErrorKind::Spill, "{}", input),
+            other => panic!("Expected #SPILL! for {}, got {:?}", input, other),
+        }
+    }
+}
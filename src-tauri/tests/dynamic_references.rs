@@ -0,0 +1,187 @@
+// Test INDIRECT and OFFSET, which work out the cells they read as they are calculated
+
+use unicel_lib::commands::workbook::{
+    add_sheet_impl, delete_sheet_impl, rename_sheet_impl, set_active_sheet_impl,
+};
+use unicel_lib::core::cell::Cell;
+use unicel_lib::core::table::CellAddr;
+use unicel_lib::core::units::Unit;
+use unicel_lib::formats::json::WorkbookFile;
+
+mod common;
+use common::{cell, error_code, new_state, number, set};
+
+#[test]
+fn test_indirect_follows_the_cells_it_reads() {
+    let state = new_state("Scenarios");
+    set(&state, "B1", "100 USD");
+    set(&state, "B2", "250 USD");
+    set(&state, "A1", "1");
+
+    let result = set(&state, "C1", "=INDIRECT(\"B\" & A1) * 2");
+    assert_eq!(number(&result), 200.0);
+    assert_eq!(result.storage_unit, "USD");
+
+    // Changing the cell it reads recalculates it
+    set(&state, "B1", "150 USD");
+    assert_eq!(number(&cell(&state, "C1")), 300.0);
+
+    // Switching to another row reads that one from then on
+    set(&state, "A1", "2");
+    assert_eq!(number(&cell(&state, "C1")), 500.0);
+    set(&state, "B2", "300 USD");
+    assert_eq!(number(&cell(&state, "C1")), 600.0);
+
+    // The row it no longer reads is no longer a dependency
+    set(&state, "B1", "bad input");
+    assert_eq!(number(&cell(&state, "C1")), 600.0);
+
+    // Ranges can be named too
+    assert_eq!(
+        number(&set(&state, "C2", "=SUM(INDIRECT(\"B1:B\" & 2))")),
+        300.0
+    );
+    assert_eq!(error_code(&set(&state, "C3", "=INDIRECT(\"B\")")), "#REF!");
+}
+
+#[test]
+fn test_offset() {
+    let state = new_state("Scenarios");
+    set(&state, "A1", "10 kg");
+    set(&state, "A2", "20 kg");
+    set(&state, "B2", "30 kg");
+    set(&state, "B3", "40 kg");
+    set(&state, "D1", "1");
+
+    assert_eq!(number(&set(&state, "E1", "=OFFSET(A1, D1, 1)")), 30.0);
+    assert_eq!(
+        number(&set(&state, "E2", "=SUM(OFFSET(A1, 1, 0, 2, 2))")),
+        90.0
+    );
+    assert_eq!(
+        number(&set(&state, "E3", "=SUM(OFFSET(A1:A2, 0, D1))")),
+        30.0
+    );
+
+    set(&state, "B2", "35 kg");
+    assert_eq!(number(&cell(&state, "E1")), 35.0);
+    assert_eq!(number(&cell(&state, "E2")), 95.0);
+
+    set(&state, "D1", "2");
+    assert_eq!(number(&cell(&state, "E1")), 40.0);
+    set(&state, "B2", "0 kg");
+    assert_eq!(number(&cell(&state, "E1")), 40.0);
+    assert_eq!(number(&cell(&state, "E3")), 0.0);
+
+    assert_eq!(
+        error_code(&set(&state, "E4", "=OFFSET(A1, -1, 0)")),
+        "#REF!"
+    );
+
+    // Ranges too large to build, or past the last row or column, are #REF! too
+    for formula in [
+        "=OFFSET(A1, 0, 0, 1000000, 1000)",
+        "=SUM(OFFSET(A1, 0, 0, 1000, 1000))",
+        "=OFFSET(A1, 1048576, 0)",
+        "=OFFSET(A1, 0, 16384)",
+    ] {
+        assert_eq!(
+            error_code(&set(&state, "E5", formula)),
+            "#REF!",
+            "{}",
+            formula
+        );
+    }
+}
+
+#[test]
+fn test_references_across_sheets() {
+    let state = new_state("Scenarios");
+    add_sheet_impl(&state).unwrap();
+    rename_sheet_impl(&state, 1, "Inputs".to_string()).unwrap();
+    set_active_sheet_impl(&state, 1).unwrap();
+    set(&state, "A1", "5 m");
+    set(&state, "A2", "7 m");
+
+    set_active_sheet_impl(&state, 0).unwrap();
+    set(&state, "A1", "Inputs");
+    assert_eq!(number(&set(&state, "B1", "=INDIRECT(A1 & \"!A1\")")), 5.0);
+    assert_eq!(number(&set(&state, "B2", "=OFFSET(Inputs!A1, 1, 0)")), 7.0);
+
+    // Cells on the other sheet are dependencies, not the same cells on this sheet
+    set(&state, "A2", "100 m");
+    assert_eq!(number(&cell(&state, "B2")), 7.0);
+    set_active_sheet_impl(&state, 1).unwrap();
+    set(&state, "A1", "6 m");
+    set(&state, "A2", "8 m");
+    set_active_sheet_impl(&state, 0).unwrap();
+    assert_eq!(number(&cell(&state, "B1")), 6.0);
+    assert_eq!(number(&cell(&state, "B2")), 8.0);
+
+    // The text still names the old sheet after a rename
+    rename_sheet_impl(&state, 1, "Assumptions".to_string()).unwrap();
+    assert_eq!(error_code(&cell(&state, "B1")), "#REF!");
+    set(&state, "A1", "Assumptions");
+    assert_eq!(number(&cell(&state, "B1")), 6.0);
+
+    delete_sheet_impl(&state, 1).unwrap();
+    assert_eq!(error_code(&cell(&state, "B1")), "#REF!");
+}
+
+#[test]
+fn test_circular_references() {
+    let state = new_state("Scenarios");
+    set(&state, "A1", "1");
+    set(&state, "B1", "=A1 + 1");
+
+    assert_eq!(
+        error_code(&set(&state, "A2", "=INDIRECT(\"A\" & 2)")),
+        "#CIRC!"
+    );
+
+    // B1 reads A1, so A1 cannot read B1
+    assert_eq!(
+        error_code(&set(&state, "A1", "=INDIRECT(\"B1\")")),
+        "#CIRC!"
+    );
+    assert_eq!(error_code(&cell(&state, "B1")), "#CIRC!");
+    set(&state, "A1", "1");
+    assert_eq!(number(&cell(&state, "B1")), 2.0);
+}
+
+#[test]
+fn test_references_resolved_after_loading() {
+    let state = new_state("Scenarios");
+    set(&state, "B1", "2");
+    set(&state, "B2", "3");
+    set(&state, "A1", "=INDIRECT(\"B\" & 2) * 10");
+
+    let json = {
+        let workbook = state.workbook.lock().unwrap();
+        WorkbookFile::from_workbook(workbook.as_ref().unwrap())
+            .to_json()
+            .unwrap()
+    };
+    let mut workbook = WorkbookFile::from_json(&json)
+        .unwrap()
+        .to_workbook()
+        .unwrap();
+    let a1 = CellAddr::new("A", 1);
+    assert_eq!(
+        workbook.active_sheet().get(&a1).unwrap().as_number(),
+        Some(30.0)
+    );
+
+    // The loaded workbook learns what A1 reads the next time it recalculates
+    workbook
+        .set_cell(
+            0,
+            CellAddr::new("B", 2),
+            Cell::new(4.0, Unit::dimensionless()),
+        )
+        .unwrap();
+    assert_eq!(
+        workbook.active_sheet().get(&a1).unwrap().as_number(),
+        Some(40.0)
+    );
+}
@@ -160,9 +160,9 @@ fn test_duplicate_sheet_names() {
 fn test_rename_sheet_validation() {
     let mut workbook = Workbook::new("Test");
 
-    // Rename to empty string (should be allowed)
+    // Rename to empty string (rejected: formulas could not refer to the sheet)
     let result = workbook.rename_sheet(0, "");
-    assert!(result.is_ok());
+    assert!(result.is_err());
 
     // Rename to very long name
     let long_name = "A".repeat(1000);
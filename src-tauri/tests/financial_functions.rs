@@ -0,0 +1,86 @@
+// Test time-value-of-money functions with rates per time, durations and currencies
+
+use unicel_lib::commands::workbook::parse_cell_input;
+
+mod common;
+use common::{new_state, number, set, unit_of};
+
+#[test]
+fn test_rate_input_is_a_fraction() {
+    let cell = parse_cell_input("6 %/year").unwrap();
+    assert_eq!(cell.as_number(), Some(0.06));
+    assert_eq!(cell.storage_unit().canonical(), "%/year");
+
+    let cell = parse_cell_input("15%").unwrap();
+    assert_eq!(cell.as_number(), Some(0.15));
+}
+
+#[test]
+fn test_mortgage() {
+    let state = new_state("Mortgage");
+
+    set(&state, "A1", "6 %/year");
+    set(&state, "A2", "30 year");
+    set(&state, "A3", "300000 USD");
+
+    // Yearly payments, which can be shown per month
+    let yearly = number(&set(&state, "B1", "=PMT(A1, A2, A3)"));
+    assert!((yearly + 21794.67).abs() < 0.01);
+    assert_eq!(unit_of(&state, "B1"), "USD/year");
+
+    // Monthly payments: the rate is converted to 0.5 % a month by the unit system
+    let monthly = number(&set(&state, "B2", "=PMT(A1, CONVERT(A2, \"month\"), A3)"));
+    assert!((monthly + 1798.65).abs() < 0.01);
+    assert_eq!(unit_of(&state, "B2"), "USD/month");
+
+    // Showing the yearly payment per month only divides it by 12; giving the payment period
+    // works out the monthly payment from the same inputs
+    let monthly = number(&set(&state, "B4", "=PMT(A1, A2, A3, 0, 0, \"month\")"));
+    assert!((monthly + 1798.65).abs() < 0.01);
+    assert_eq!(unit_of(&state, "B4"), "USD/month");
+
+    // Paying that each month for the term leaves nothing owed
+    let owed = number(&set(&state, "B3", "=FV(A1, 360 month, B2, A3)"));
+    assert!(owed.abs() < 0.01);
+}
+
+#[test]
+fn test_cash_flow_returns() {
+    let state = new_state("Project");
+
+    for (row, (flow, date)) in [
+        ("-10000 USD", "2008-01-01"),
+        ("2750 USD", "2008-03-01"),
+        ("4250 USD", "2008-10-30"),
+        ("3250 USD", "2009-02-15"),
+        ("2750 USD", "2009-04-01"),
+    ]
+    .into_iter()
+    .enumerate()
+    {
+        set(&state, &format!("A{}", row + 1), flow);
+        set(&state, &format!("B{}", row + 1), date);
+    }
+
+    let value = number(&set(&state, "C1", "=XNPV(9 %/year, A1:A5, B1:B5)"));
+    assert!((value - 2086.65).abs() < 0.01);
+    assert_eq!(unit_of(&state, "C1"), "USD");
+
+    // NPV discounts every flow by a period; IRR is the rate where NPV of the rest offsets A1
+    let rate = number(&set(&state, "C2", "=IRR(A1:A5)"));
+    let rest = number(&set(&state, "C3", "=NPV(C2, A2:A5)"));
+    assert!((rest - 10000.0).abs() < 0.01);
+    assert!(rate > 0.0);
+    assert_eq!(unit_of(&state, "C2"), "%");
+
+    // Yearly cash flows give a yearly rate
+    set(&state, "D1", "-70000 USD/year");
+    set(&state, "D2", "12000 USD/year");
+    set(&state, "D3", "15000 USD/year");
+    set(&state, "D4", "18000 USD/year");
+    set(&state, "D5", "21000 USD/year");
+    set(&state, "D6", "26000 USD/year");
+    let rate = number(&set(&state, "E1", "=IRR(D1:D6)"));
+    assert!((rate - 0.0866).abs() < 0.0001);
+    assert_eq!(unit_of(&state, "E1"), "%/year");
+}
@@ -0,0 +1,83 @@
+// Test goal seek, which finds the input that makes a formula reach a goal
+
+use unicel_lib::commands::workbook::{goal_seek_impl, AppState};
+
+mod common;
+use common::{cell, new_state, number, set};
+
+fn goal_seek(
+    state: &AppState,
+    target: &str,
+    goal: &str,
+    changing: &str,
+) -> Result<unicel_lib::core::workbook::GoalSeekResult, String> {
+    goal_seek_impl(
+        state,
+        target.to_string(),
+        goal.to_string(),
+        changing.to_string(),
+    )
+}
+
+#[test]
+fn test_hourly_rate_for_monthly_total() {
+    let state = new_state("Budget");
+    set(&state, "B1", "50 USD/hr");
+    set(&state, "B2", "160 hr");
+    set(&state, "B3", "2000 USD");
+    set(&state, "B4", "=B1 * B2 + B3");
+
+    let result = goal_seek(&state, "B4", "50000 USD", "B1").unwrap();
+    assert!((result.value - 300.0).abs() < 1e-6);
+    assert_eq!(result.unit, "USD/hr");
+    assert!((result.target_value - 50000.0).abs() < 1e-4);
+    assert_eq!(result.target_unit, "USD");
+
+    // The changing cell keeps the value found, in its unit
+    let rate = cell(&state, "B1");
+    assert!((number(&rate) - 300.0).abs() < 1e-6);
+    assert_eq!(rate.storage_unit, "USD/hr");
+    assert!((number(&cell(&state, "B4")) - 50000.0).abs() < 1e-4);
+}
+
+#[test]
+fn test_goal_converted_into_target_unit() {
+    let state = new_state("Budget");
+    set(&state, "A1", "2 m");
+    set(&state, "A2", "=A1 * A1 * 3");
+
+    // 1200 cm² is 0.12 m²; a goal without a unit is in the target's unit
+    let result = goal_seek(&state, "A2", "1200 cm^2", "A1").unwrap();
+    assert!((result.value - 0.2).abs() < 1e-6);
+    assert_eq!(result.unit, "m");
+    let result = goal_seek(&state, "A2", "27", "A1").unwrap();
+    assert!((result.value.abs() - 3.0).abs() < 1e-6);
+
+    assert!(goal_seek(&state, "A2", "5 kg", "A1")
+        .unwrap_err()
+        .contains("Cannot convert"));
+}
+
+#[test]
+fn test_goal_seek_errors() {
+    let state = new_state("Budget");
+    set(&state, "A1", "3");
+    set(&state, "A2", "=A1 * A1 + 1");
+    set(&state, "A3", "=A1 * 2");
+
+    // Never reaches the goal, so the changing cell goes back to where it was
+    let error = goal_seek(&state, "A2", "0", "A1").unwrap_err();
+    assert!(error.contains("No value of A1 makes A2 reach 0"));
+    assert_eq!(number(&cell(&state, "A1")), 3.0);
+    assert_eq!(number(&cell(&state, "A2")), 10.0);
+
+    // The changing cell must hold a number, and the target a formula
+    assert!(goal_seek(&state, "A2", "5", "A3")
+        .unwrap_err()
+        .contains("A3 must hold a number"));
+    set(&state, "B1", "4");
+    assert!(goal_seek(&state, "A1", "5", "B1")
+        .unwrap_err()
+        .contains("A1 must hold a formula"));
+    assert!(goal_seek(&state, "A2", "five", "A1").is_err());
+}
@@ -4,6 +4,7 @@ use unicel_lib::core::{
     cell::Cell,
     table::{CellAddr, Sheet},
     units::{BaseDimension, Unit},
+    workbook::Workbook,
 };
 
 #[test]
@@ -660,3 +661,74 @@ fn test_units_preserved_during_operations() {
     let c1 = sheet.get(&CellAddr::new("C", 1)).unwrap();
     assert_eq!(c1.storage_unit().canonical().to_string(), "kg");
 }
+
+#[test]
+fn test_rows_and_columns_update_references_from_other_sheets() {
+    let mut wb = Workbook::new("Test");
+    wb.rename_sheet(0, "Data").unwrap();
+    wb.add_sheet_with_name("Report");
+    let number = |n: f64| Cell::new(n, Unit::dimensionless());
+
+    wb.set_cell(0, CellAddr::new("A", 5), number(10.0)).unwrap();
+    wb.set_cell(0, CellAddr::new("B", 5), number(20.0)).unwrap();
+    wb.set_cell(0, CellAddr::new("D", 1), Cell::with_formula("=Data!A5"))
+        .unwrap();
+    wb.set_named_range("base", 0, CellAddr::new("A", 5))
+        .unwrap();
+    wb.set_cell(1, CellAddr::new("A", 1), Cell::with_formula("=Data!A5"))
+        .unwrap();
+    wb.set_cell(
+        1,
+        CellAddr::new("A", 2),
+        Cell::with_formula("=SUM(Data!A5:B5)"),
+    )
+    .unwrap();
+    wb.set_cell(1, CellAddr::new("A", 3), Cell::with_formula("=base"))
+        .unwrap();
+    // Unqualified references on the other sheet are its own cells, and stay put
+    wb.set_cell(1, CellAddr::new("A", 4), Cell::with_formula("=A1"))
+        .unwrap();
+
+    wb.insert_row_before(0, 3).unwrap();
+
+    let report = wb.get_sheet(1).unwrap();
+    let formula = |addr: CellAddr| report.get(&addr).unwrap().formula().map(String::from);
+    assert_eq!(formula(CellAddr::new("A", 1)).as_deref(), Some("=Data!A6"));
+    assert_eq!(
+        formula(CellAddr::new("A", 2)).as_deref(),
+        Some("=SUM(Data!A6:B6)")
+    );
+    assert_eq!(formula(CellAddr::new("A", 4)).as_deref(), Some("=A1"));
+    assert_eq!(
+        wb.get_sheet(0)
+            .unwrap()
+            .get(&CellAddr::new("D", 1))
+            .unwrap()
+            .formula(),
+        Some("=Data!A6")
+    );
+    assert_eq!(
+        wb.get_named_range("base"),
+        Some((0, &CellAddr::new("A", 6)))
+    );
+
+    // The moved references are tracked for recalculation
+    wb.set_cell(0, CellAddr::new("A", 6), number(15.0)).unwrap();
+    let value = |wb: &Workbook, row| {
+        wb.get_sheet(1)
+            .unwrap()
+            .get(&CellAddr::new("A", row))
+            .unwrap()
+            .as_number()
+    };
+    assert_eq!(value(&wb, 1), Some(15.0));
+    assert_eq!(value(&wb, 2), Some(35.0));
+    assert_eq!(value(&wb, 3), Some(15.0));
+
+    // Deleting the referenced cells leaves #REF! behind, and removes the name on them
+    wb.delete_column(0, "A").unwrap();
+    let report = wb.get_sheet(1).unwrap();
+    assert!(report.get(&CellAddr::new("A", 1)).unwrap().is_error());
+    assert!(report.get(&CellAddr::new("A", 3)).unwrap().is_error());
+    assert_eq!(wb.get_named_range("base"), None);
+}
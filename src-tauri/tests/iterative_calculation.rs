@@ -0,0 +1,185 @@
+// Test iterative calculation of intentional circular references
+
+use unicel_lib::commands::workbook::{
+    get_circular_references_impl, get_workbook_info_impl, set_cell_impl,
+    set_iterative_calculation_impl, AppState, CellData, CellValueData,
+};
+use unicel_lib::core::workbook::IterativeCalculation;
+use unicel_lib::formats::json::WorkbookFile;
+
+mod common;
+use common::{cell, new_state, number, set};
+
+fn error(data: &CellData) -> (String, String) {
+    match &data.value {
+        CellValueData::Error { code, message, .. } => (code.clone(), message.clone()),
+        other => panic!("Expected an error, got {:?}", other),
+    }
+}
+
+fn model(iteration: Option<IterativeCalculation>) -> AppState {
+    let state = new_state("Model");
+    set_iterative_calculation_impl(&state, iteration).unwrap();
+    state
+}
+
+#[test]
+fn test_tax_gross_up() {
+    let state = model(Some(IterativeCalculation::default()));
+    set(&state, "B1", "100 USD");
+    set(&state, "B2", "0.25");
+
+    // The gross amount includes the tax, which is worked out from the gross amount
+    set(&state, "B3", "=B1 + B4");
+    let tax = set(&state, "B4", "=B3 * B2");
+    assert!((number(&tax) - 33.3333).abs() < 0.001);
+    assert_eq!(tax.storage_unit, "USD");
+    let gross = cell(&state, "B3");
+    assert!((number(&gross) - 133.3333).abs() < 0.001);
+    assert_eq!(gross.storage_unit, "USD");
+
+    // Changing an input settles again from the last values
+    set(&state, "B1", "200 USD");
+    assert!((number(&cell(&state, "B3")) - 266.6667).abs() < 0.001);
+
+    assert_eq!(
+        get_circular_references_impl(&state).unwrap(),
+        vec![vec!["Sheet1!B3".to_string(), "Sheet1!B4".to_string()]]
+    );
+}
+
+#[test]
+fn test_interest_on_average_balance() {
+    let state = model(Some(IterativeCalculation {
+        max_iterations: 50,
+        tolerance: 0.0001,
+    }));
+    set(&state, "A1", "1000 USD");
+    set(&state, "A2", "0.05");
+    set(&state, "A3", "=A2 * (A1 + A4) / 2");
+    set(&state, "A4", "=A1 + A3");
+
+    // closing = opening * (1 + rate / 2) / (1 - rate / 2)
+    assert!((number(&cell(&state, "A4")) - 1051.2821).abs() < 0.0001);
+    assert_eq!(cell(&state, "A4").storage_unit, "USD");
+}
+
+#[test]
+fn test_circular_references_rejected_without_iteration() {
+    let state = model(None);
+    set(&state, "A1", "=B1 + 1");
+    assert!(set_cell_impl(&state, "B1".to_string(), "=A1 * 2".to_string()).is_err());
+    assert!(get_circular_references_impl(&state).unwrap().is_empty());
+
+    // With iteration on, the same loop is accepted, and it does not settle
+    set_iterative_calculation_impl(&state, Some(IterativeCalculation::default())).unwrap();
+    let (code, message) = error(&set(&state, "B1", "=A1 * 2"));
+    assert_eq!(code, "#CIRC!");
+    assert!(message.contains("Sheet1!A1, Sheet1!B1") || message.contains("Sheet1!B1, Sheet1!A1"));
+    assert!(message.contains("did not settle within 100 iterations"));
+    assert_eq!(error(&cell(&state, "A1")).0, "#CIRC!");
+
+    // Turning iteration off again shows the loop as an error
+    set_iterative_calculation_impl(&state, None).unwrap();
+    let (code, message) = error(&cell(&state, "A1"));
+    assert_eq!(code, "#CIRC!");
+    assert!(message.starts_with("Circular reference between"));
+
+    // Breaking the loop calculates normally
+    set(&state, "B1", "3");
+    assert_eq!(number(&cell(&state, "A1")), 4.0);
+}
+
+#[test]
+fn test_non_converging_self_reference() {
+    let state = model(Some(IterativeCalculation {
+        max_iterations: 10,
+        tolerance: 0.001,
+    }));
+    let (code, message) = error(&set(&state, "A1", "=A1 + 1"));
+    assert_eq!(code, "#CIRC!");
+    assert!(message.contains("Sheet1!A1"));
+    assert!(message.contains("10 iterations"));
+    assert_eq!(
+        get_circular_references_impl(&state).unwrap(),
+        vec![vec!["Sheet1!A1".to_string()]]
+    );
+}
+
+#[test]
+fn test_invalid_settings() {
+    let state = model(None);
+    for iteration in [
+        IterativeCalculation {
+            max_iterations: 0,
+            tolerance: 0.001,
+        },
+        IterativeCalculation {
+            max_iterations: 10,
+            tolerance: -1.0,
+        },
+        IterativeCalculation {
+            max_iterations: 10,
+            tolerance: f64::NAN,
+        },
+    ] {
+        assert!(set_iterative_calculation_impl(&state, Some(iteration)).is_err());
+    }
+    assert_eq!(
+        get_workbook_info_impl(&state)
+            .unwrap()
+            .iterative_calculation,
+        None
+    );
+}
+
+#[test]
+fn test_setting_saved_with_workbook() {
+    let iteration = IterativeCalculation {
+        max_iterations: 20,
+        tolerance: 0.01,
+    };
+    let state = model(Some(iteration));
+    set(&state, "B1", "100 USD");
+    set(&state, "B2", "=B1 + B3");
+    set(&state, "B3", "=B2 * 0.1");
+
+    let json = {
+        let workbook = state.workbook.lock().unwrap();
+        WorkbookFile::from_workbook(workbook.as_ref().unwrap())
+            .to_json()
+            .unwrap()
+    };
+    let workbook = WorkbookFile::from_json(&json)
+        .unwrap()
+        .to_workbook()
+        .unwrap();
+    assert_eq!(workbook.settings().iterative_calculation, Some(iteration));
+    assert_eq!(
+        workbook.circular_references(),
+        vec![vec!["Sheet1!B2".to_string(), "Sheet1!B3".to_string()]]
+    );
+}
+
+#[test]
+fn test_loop_unit_from_its_own_literals() {
+    let state = model(Some(IterativeCalculation::default()));
+
+    // Nothing outside the loop has a unit; the USD and m are written in the formulas
+    set(&state, "A1", "=1000 USD + A2");
+    set(&state, "A2", "=A1*5%");
+    let total = cell(&state, "A1");
+    assert!((number(&total) - 1052.6316).abs() < 0.001);
+    assert_eq!(total.storage_unit, "USD");
+
+    set(&state, "G1", "=G2 + 1 m");
+    set(&state, "G2", "=G1 * 0.5");
+    let length = cell(&state, "G1");
+    assert!((number(&length) - 2.0).abs() < 0.001);
+    assert_eq!(length.storage_unit, "m");
+
+    // A loop whose unit never settles shows an error, without the unit it got to
+    let result = set(&state, "H3", "=H3*1 m");
+    assert_eq!(error(&result).0, "#CIRC!");
+    assert_eq!(result.storage_unit, "");
+}
@@ -0,0 +1,158 @@
+// Test LET and workbook functions defined with LAMBDA
+
+use unicel_lib::commands::workbook::{
+    create_named_range_impl, define_function_impl, delete_function_impl, list_functions_impl,
+};
+use unicel_lib::core::table::CellAddr;
+use unicel_lib::formats::json::WorkbookFile;
+
+mod common;
+use common::{cell, error_code, new_state, number, set};
+
+#[test]
+fn test_let_bindings() {
+    let state = new_state("Costs");
+    set(&state, "A1", "100 m");
+
+    let result = set(
+        &state,
+        "B1",
+        "=LET(width, A1, area, width * width, area / 2)",
+    );
+    assert_eq!(number(&result), 5000.0);
+    assert_eq!(result.storage_unit, "m²");
+
+    // Names that are not bound are still named ranges
+    assert_eq!(
+        error_code(&set(&state, "B2", "=LET(x, 1, x + y)")),
+        "#NAME?"
+    );
+}
+
+#[test]
+fn test_workbook_function() {
+    let state = new_state("Costs");
+    define_function_impl(
+        &state,
+        "monthly_cost".to_string(),
+        "LAMBDA(rate: USD/hr, util, rate * util * 730 hr/month)".to_string(),
+    )
+    .unwrap();
+
+    set(&state, "A1", "0.10 USD/hr");
+    let result = set(&state, "B1", "=monthly_cost(A1, 0.8)");
+    assert!((number(&result) - 58.4).abs() < 1e-9);
+    assert_eq!(result.storage_unit, "USD/month");
+
+    // Callers follow the cells they pass in
+    set(&state, "A1", "0.20 USD/hr");
+    assert!((number(&cell(&state, "B1")) - 116.8).abs() < 1e-9);
+
+    // The rate parameter only takes a cost per time
+    assert_eq!(
+        error_code(&set(&state, "B2", "=monthly_cost(5 USD, 0.8)")),
+        "#UNIT!"
+    );
+    assert_eq!(
+        error_code(&set(&state, "B3", "=monthly_cost(A1)")),
+        "#VALUE!"
+    );
+}
+
+#[test]
+fn test_redefining_recalculates_callers() {
+    let state = new_state("Costs");
+    define_function_impl(&state, "double".to_string(), "LAMBDA(x, x * 2)".to_string()).unwrap();
+    define_function_impl(
+        &state,
+        "quadruple".to_string(),
+        "LAMBDA(x, double(double(x)))".to_string(),
+    )
+    .unwrap();
+    set(&state, "A1", "3 kg");
+    assert_eq!(number(&set(&state, "B1", "=quadruple(A1)")), 12.0);
+
+    // B1 calls double through quadruple
+    define_function_impl(&state, "double".to_string(), "LAMBDA(x, x * 3)".to_string()).unwrap();
+    assert_eq!(number(&cell(&state, "B1")), 27.0);
+
+    // A function's body can refer to cells and names, which its callers then depend on
+    set(&state, "C1", "10");
+    create_named_range_impl(&state, "factor".to_string(), 0, "C1".to_string()).unwrap();
+    define_function_impl(
+        &state,
+        "scaled".to_string(),
+        "LAMBDA(x, x * factor)".to_string(),
+    )
+    .unwrap();
+    assert_eq!(number(&set(&state, "B2", "=scaled(A1)")), 30.0);
+    set(&state, "C1", "20");
+    assert_eq!(number(&cell(&state, "B2")), 60.0);
+
+    delete_function_impl(&state, "double".to_string()).unwrap();
+    assert_eq!(error_code(&cell(&state, "B1")), "#NAME?");
+    assert!(delete_function_impl(&state, "double".to_string()).is_err());
+
+    // Defining a function fixes the formulas that already called it
+    define_function_impl(&state, "double".to_string(), "LAMBDA(x, x + x)".to_string()).unwrap();
+    assert_eq!(number(&cell(&state, "B1")), 12.0);
+}
+
+#[test]
+fn test_function_names() {
+    let state = new_state("Costs");
+    let define = |name: &str, definition: &str| {
+        define_function_impl(&state, name.to_string(), definition.to_string())
+    };
+
+    // Built-in functions and named ranges cannot be redefined
+    assert!(define("sum", "LAMBDA(x, x)").is_err());
+    set(&state, "A1", "1");
+    create_named_range_impl(&state, "rate".to_string(), 0, "A1".to_string()).unwrap();
+    assert!(define("rate", "LAMBDA(x, x)").is_err());
+
+    // Names follow the named range rules, and the definition must be a LAMBDA
+    assert!(define("Cost", "LAMBDA(x, x)").is_err());
+    assert!(define("cost", "x * 2").is_err());
+    assert!(define("cost", "LAMBDA(x, x * 2").is_err());
+
+    // A function's name cannot then be used for a named range
+    define("cost", "LAMBDA(x, x * 2)").unwrap();
+    assert!(create_named_range_impl(&state, "cost".to_string(), 0, "A1".to_string()).is_err());
+
+    let functions = list_functions_impl(&state).unwrap();
+    assert_eq!(functions.len(), 1);
+    assert_eq!(functions[0].name, "cost");
+    assert_eq!(functions[0].definition, "LAMBDA(x, x * 2)");
+}
+
+#[test]
+fn test_functions_persist() {
+    let state = new_state("Costs");
+    define_function_impl(
+        &state,
+        "monthly_cost".to_string(),
+        "LAMBDA(rate: USD/hr, util, rate * util * 730 hr/month)".to_string(),
+    )
+    .unwrap();
+    set(&state, "A1", "=monthly_cost(1 USD/hr, 0.5)");
+
+    let json = {
+        let workbook = state.workbook.lock().unwrap();
+        WorkbookFile::from_workbook(workbook.as_ref().unwrap())
+            .to_json()
+            .unwrap()
+    };
+    assert!(json.contains("lambdas"));
+
+    let workbook = WorkbookFile::from_json(&json)
+        .unwrap()
+        .to_workbook()
+        .unwrap();
+    assert_eq!(
+        workbook.get_function("monthly_cost"),
+        Some("LAMBDA(rate: USD/hr, util, rate * util * 730 hr/month)")
+    );
+    let result = workbook.active_sheet().get(&CellAddr::new("A", 1)).unwrap();
+    assert_eq!(result.as_number(), Some(365.0));
+}
@@ -0,0 +1,47 @@
+// Test lookup formulas against price tables through the workbook commands
+
+mod common;
+use common::{cell, new_state, number, set};
+
+#[test]
+fn test_lookup_recalculates_when_table_changes() {
+    let state = new_state("Pricing");
+
+    set(&state, "A1", "t3.small");
+    set(&state, "A2", "t3.medium");
+    set(&state, "B1", "0.0208 USD");
+    set(&state, "B2", "0.0416 USD");
+    set(&state, "D1", "t3.medium");
+
+    set(&state, "E1", "=VLOOKUP(D1, A1:B2, 2, FALSE) * 730");
+    assert!((number(&cell(&state, "E1")) - 30.368).abs() < 1e-9);
+
+    // A price anywhere in the table updates the lookup
+    set(&state, "B2", "0.05 USD");
+    assert!((number(&cell(&state, "E1")) - 36.5).abs() < 1e-9);
+
+    // So does the lookup key
+    set(&state, "D1", "t3.small");
+    assert!((number(&cell(&state, "E1")) - 15.184).abs() < 1e-9);
+}
+
+#[test]
+fn test_lookup_over_whole_columns() {
+    let state = new_state("Materials");
+
+    set(&state, "A1", "oak");
+    set(&state, "B1", "12 USD");
+    set(&state, "A2", "pine");
+    set(&state, "B2", "4 USD");
+    set(&state, "D1", "=XLOOKUP(\"pine\", A:A, B:B)");
+    assert_eq!(number(&cell(&state, "D1")), 4.0);
+
+    // Rows added to the table later are found too
+    set(&state, "A3", "walnut");
+    set(&state, "B3", "20 USD");
+    set(&state, "D2", "=INDEX(B:B, MATCH(\"walnut\", A:A, 0))");
+    assert_eq!(number(&cell(&state, "D2")), 20.0);
+
+    set(&state, "B2", "5 USD");
+    assert_eq!(number(&cell(&state, "D1")), 5.0);
+}
@@ -26,6 +26,7 @@ fn test_tool_definitions() {
     assert!(tool_names.contains(&"convert_value".to_string()));
     assert!(tool_names.contains(&"list_tables".to_string()));
     assert!(tool_names.contains(&"get_workbook_metadata".to_string()));
+    assert!(tool_names.contains(&"goal_seek".to_string()));
 
     // Verify each tool has required fields
     for tool in &tools {
@@ -191,6 +192,29 @@ fn test_convert_value_tool() {
     }
 }
 
+#[test]
+fn test_convert_value_compound_units() {
+    let workbook = Workbook::new("Test");
+    let workbook = Arc::new(Mutex::new(workbook));
+    let unit_library = Arc::new(UnitLibrary::new());
+    let handler = ToolHandler::new(workbook, unit_library);
+
+    // Compound units convert the same way as the CONVERT function in a formula
+    let mut args = HashMap::new();
+    args.insert("value".to_string(), json!(30000.0));
+    args.insert("from_unit".to_string(), json!("$/quarter"));
+    args.insert("to_unit".to_string(), json!("$/year"));
+
+    let result = handler.handle_tool_call("convert_value", Some(args));
+
+    assert_eq!(result.is_error, Some(false));
+
+    if let unicel_lib::mcp::ToolContent::Text { text } = &result.content[0] {
+        let response: serde_json::Value = serde_json::from_str(text).unwrap();
+        assert_eq!(response["converted"]["value"], 120000.0);
+    }
+}
+
 #[test]
 fn test_get_conversion_rate() {
     let workbook = Workbook::new("Test");
@@ -372,6 +396,44 @@ fn test_get_workbook_metadata() {
     }
 }
 
+#[test]
+fn test_goal_seek_tool() {
+    let mut workbook = Workbook::new("Test");
+    let usd = Unit::simple("USD", BaseDimension::Currency);
+    workbook
+        .set_cell(0, CellAddr::new("A", 1), Cell::new(100.0, usd))
+        .unwrap();
+    workbook
+        .set_cell(0, CellAddr::new("A", 2), Cell::with_formula("=A1 * 12"))
+        .unwrap();
+
+    let workbook = Arc::new(Mutex::new(workbook));
+    let unit_library = Arc::new(UnitLibrary::new());
+    let handler = ToolHandler::new(Arc::clone(&workbook), unit_library);
+
+    let mut args = HashMap::new();
+    args.insert("target_cell".to_string(), json!("A2"));
+    args.insert("target_value".to_string(), json!(1800));
+    args.insert("unit".to_string(), json!("USD"));
+    args.insert("changing_cell".to_string(), json!("A1"));
+
+    let result = handler.handle_tool_call("goal_seek", Some(args));
+
+    assert_eq!(result.is_error, Some(false));
+
+    if let unicel_lib::mcp::ToolContent::Text { text } = &result.content[0] {
+        let response: serde_json::Value = serde_json::from_str(text).unwrap();
+
+        assert!((response["value"].as_f64().unwrap() - 150.0).abs() < 1e-6);
+        assert_eq!(response["unit"], "USD");
+        assert_eq!(response["target_cell"], "A2");
+    }
+
+    let workbook = workbook.lock().unwrap();
+    let a1 = workbook.active_sheet().get(&CellAddr::new("A", 1)).unwrap();
+    assert!((a1.as_number().unwrap() - 150.0).abs() < 1e-6);
+}
+
 #[test]
 fn test_get_sheet_structure() {
     let mut workbook = Workbook::new("Test");
@@ -0,0 +1,91 @@
+// Test significant-figure tracking: the precision of typed numbers and of formula results
+
+use unicel_lib::commands::workbook::{
+    get_sheet_cells_impl, get_workbook_info_impl, parse_cell_input, set_significant_figures_impl,
+};
+
+mod common;
+use common::{new_state, set};
+
+fn figures(input: &str) -> Option<u32> {
+    parse_cell_input(input).unwrap().significant_figures()
+}
+
+#[test]
+fn test_input_precision() {
+    assert_eq!(figures("12.0 m"), Some(3));
+    assert_eq!(figures("12 m"), Some(2));
+    assert_eq!(figures("1200 kg"), Some(2));
+    assert_eq!(figures("0.050"), Some(2));
+    assert_eq!(figures("$15.00"), Some(4));
+    assert_eq!(figures("12.5%"), Some(3));
+    assert_eq!(figures("100±2 m"), Some(1));
+    assert_eq!(figures("=A1*2"), None);
+    assert_eq!(figures("twelve"), None);
+}
+
+#[test]
+fn test_precision_through_formulas() {
+    let state = new_state("Lab");
+    set_significant_figures_impl(&state, true).unwrap();
+
+    set(&state, "A1", "12.0 m");
+    set(&state, "A2", "3.0 m");
+    set(&state, "A3", "12.11 m");
+    set(&state, "A4", "0.3 m");
+    set(&state, "A5", "12 m");
+
+    // Products and quotients have the figures of the least precise operand
+    assert_eq!(set(&state, "B1", "=A1 * A2").significant_figures, Some(2));
+    assert_eq!(set(&state, "B2", "=A1 / A5").significant_figures, Some(2));
+    // Numbers written in the formula are exact
+    assert_eq!(set(&state, "B3", "=A1 * 2").significant_figures, Some(3));
+    assert_eq!(set(&state, "B4", "=2 m * 3").significant_figures, None);
+
+    // Sums are known to the decimal place of the least precise operand: 12.41 is 12.4
+    assert_eq!(set(&state, "C1", "=A3 + A4").significant_figures, Some(3));
+    assert_eq!(
+        set(&state, "C2", "=SUM(A3:A4)").significant_figures,
+        Some(3)
+    );
+    // 12.0 m + 3.0 cm is in cm, known to the tens of centimetres: 1203 cm is 1.20e3 cm
+    set(&state, "A6", "3.0 cm");
+    assert_eq!(set(&state, "C3", "=A1 + A6").significant_figures, Some(3));
+
+    // Results carry their precision into the formulas that use them
+    assert_eq!(set(&state, "D1", "=C1 * A1").significant_figures, Some(3));
+    assert_eq!(set(&state, "D2", "=SQRT(B1)").significant_figures, Some(2));
+}
+
+#[test]
+fn test_significant_figures_mode() {
+    let state = new_state("Lab");
+    set(&state, "A1", "12.0 m");
+
+    // Off by default: numbers are shown as they are
+    assert!(!get_workbook_info_impl(&state).unwrap().significant_figures);
+    assert_eq!(set(&state, "B1", "=A1 * 3.1").significant_figures, None);
+
+    // The precision was tracked all along, so it shows once the mode is on
+    set_significant_figures_impl(&state, true).unwrap();
+    assert!(get_workbook_info_impl(&state).unwrap().significant_figures);
+    let cells = get_sheet_cells_impl(&state).unwrap();
+    let (_, data) = cells.iter().find(|(addr, _)| addr == "B1").unwrap();
+    assert_eq!(data.significant_figures, Some(3));
+}
+
+#[test]
+fn test_changing_the_mode_is_an_unsaved_change() {
+    let state = new_state("Lab");
+    state
+        .workbook
+        .lock()
+        .unwrap()
+        .as_mut()
+        .unwrap()
+        .mark_clean();
+    assert!(!get_workbook_info_impl(&state).unwrap().is_dirty);
+
+    set_significant_figures_impl(&state, true).unwrap();
+    assert!(get_workbook_info_impl(&state).unwrap().is_dirty);
+}
@@ -0,0 +1,85 @@
+// Test percentiles, trend lines and forecasts for capacity planning
+
+use unicel_lib::commands::workbook::{AppState, CellValueData};
+
+mod common;
+use common::{new_state, quantity, set};
+
+fn assert_quantity(state: &AppState, cell_ref: &str, expected: f64, unit: &str) {
+    let (value, actual_unit) = quantity(state, cell_ref);
+    assert!(
+        (value - expected).abs() < 1e-6,
+        "{}: {} {}",
+        cell_ref,
+        value,
+        actual_unit
+    );
+    assert_eq!(actual_unit, unit, "{}", cell_ref);
+}
+
+#[test]
+fn test_latency_percentiles() {
+    let state = new_state("Latency");
+
+    for (row, latency) in ["45 ms", "0.05 s", "120 ms", "60 ms", "1.2 s", "80 ms"]
+        .into_iter()
+        .enumerate()
+    {
+        set(&state, &format!("A{}", row + 1), latency);
+    }
+
+    // Sorted in ms: 45, 50, 60, 80, 120, 1200
+    set(&state, "B1", "=PERCENTILE(A1:A6, 0.5)");
+    assert_quantity(&state, "B1", 70.0, "ms");
+    set(&state, "B2", "=PERCENTILE(A1:A6, 99%)");
+    assert_quantity(&state, "B2", 1146.0, "ms");
+    set(&state, "B3", "=QUARTILE(A1:A6, 3)");
+    assert_quantity(&state, "B3", 110.0, "ms");
+}
+
+#[test]
+fn test_storage_growth_trend() {
+    let state = new_state("Storage");
+
+    // Storage used on four dates, growing by 3 GB a day with some noise
+    for (row, (date, used)) in [
+        ("2024-03-01", "100 GB"),
+        ("2024-03-02", "104 GB"),
+        ("2024-03-04", "109 GB"),
+        ("2024-03-08", "121 GB"),
+    ]
+    .into_iter()
+    .enumerate()
+    {
+        set(&state, &format!("A{}", row + 1), date);
+        set(&state, &format!("B{}", row + 1), used);
+    }
+
+    set(&state, "C1", "=SLOPE(B1:B4, A1:A4)");
+    let (slope, unit) = quantity(&state, "C1");
+    assert_eq!(unit, "GB/day");
+    assert!((slope - 3.0).abs() < 0.2, "{}", slope);
+
+    match set(&state, "C2", "=CORREL(B1:B4, A1:A4)").value {
+        CellValueData::Number { value } => assert!(value > 0.99, "{}", value),
+        other => panic!("Expected a number, got {:?}", other),
+    }
+
+    // LINEST spills the slope and intercept side by side
+    set(&state, "D1", "=LINEST(B1:B4, A1:A4)");
+    let (linest_slope, unit) = quantity(&state, "D1");
+    assert!((linest_slope - slope).abs() < 1e-9);
+    assert_eq!(unit, "GB/day");
+    assert_eq!(quantity(&state, "E1").1, "GB");
+
+    // A forecast for a date is in GB and agrees with the trend line
+    set(&state, "C3", "=FORECAST(DATE(2024, 3, 31), B1:B4, A1:A4)");
+    set(
+        &state,
+        "C4",
+        "=FORECAST(DATE(2024, 3, 8), B1:B4, A1:A4) + 23 day * C1",
+    );
+    let (forecast, unit) = quantity(&state, "C3");
+    assert_eq!(unit, "GB");
+    assert!((forecast - quantity(&state, "C4").0).abs() < 1e-6);
+}
@@ -0,0 +1,38 @@
+// Test SUMPRODUCT and array arithmetic on price x quantity columns
+
+use unicel_lib::commands::workbook::CellValueData;
+
+mod common;
+use common::{new_state, quantity, set};
+
+#[test]
+fn test_storage_cost_per_month() {
+    let state = new_state("Storage");
+
+    set(&state, "A1", "0.10 $/GB/month");
+    set(&state, "A2", "0.05 $/GB/month");
+    set(&state, "B1", "200 GB");
+    set(&state, "B2", "2 TB");
+
+    set(&state, "C1", "=SUMPRODUCT(A1:A2, B1:B2)");
+    let (value, unit) = quantity(&state, "C1");
+    assert!((value - (20.0 + 0.05 * 2048.0)).abs() < 1e-9);
+    assert_eq!(unit, "$/month");
+
+    // The same total as a helper column of products
+    set(&state, "D1", "=A1 * B1");
+    set(&state, "D2", "=A2 * B2");
+    set(&state, "D3", "=SUM(D1:D2)");
+    set(&state, "C2", "=SUM(A1:A2 * B1:B2)");
+    assert_eq!(quantity(&state, "C2"), quantity(&state, "D3"));
+
+    // A row in another unit is reported
+    set(&state, "B2", "2 hr");
+    match set(&state, "C3", "=SUMPRODUCT(A1:A2, B1:B2)").value {
+        CellValueData::Error { code, message, .. } => {
+            assert_eq!(code, "#UNIT!");
+            assert!(message.contains("row 2"), "{}", message);
+        }
+        other => panic!("Expected a unit error, got {:?}", other),
+    }
+}
@@ -0,0 +1,73 @@
+// Test the & operator and text functions on sheet values, including unit-aware TEXT
+
+use unicel_lib::commands::workbook::{CellData, CellValueData};
+use unicel_lib::core::table::CellAddr;
+
+mod common;
+use common::{new_state, set};
+
+fn text(data: &CellData) -> String {
+    match &data.value {
+        CellValueData::Text { text } => text.clone(),
+        other => panic!("Expected text, got {:?}", other),
+    }
+}
+
+#[test]
+fn test_report_labels() {
+    let state = new_state("Report");
+
+    set(&state, "A1", "120.5 m");
+    set(&state, "A2", "255.7756 m");
+    set(&state, "A3", "=SUM(A1:A2)");
+
+    let label = text(&set(
+        &state,
+        "B1",
+        "=\"Total: \" & TEXT(A3, \"#,##0.0 [ft]\")",
+    ));
+    assert_eq!(label, "Total: 1,234.5 ft");
+
+    // Labels follow the value they describe
+    set(&state, "A1", "0.2 km");
+    let workbook = state.workbook.lock().unwrap();
+    let sheet = workbook.as_ref().unwrap().active_sheet();
+    let cell = sheet.get(&CellAddr::new("B", 1)).unwrap();
+    assert_eq!(cell.as_text(), Some("Total: 1,495.3 ft"));
+}
+
+#[test]
+fn test_text_from_cells() {
+    let state = new_state("Inventory");
+
+    set(&state, "A1", "  t3.large  ");
+    set(&state, "A2", "m5.xlarge");
+    set(&state, "A3", "c6g.medium");
+
+    assert_eq!(text(&set(&state, "B1", "=UPPER(TRIM(A1))")), "T3.LARGE");
+    assert_eq!(
+        text(&set(&state, "B2", "=LEFT(A2, FIND(\".\", A2) - 1)")),
+        "m5"
+    );
+    assert_eq!(
+        text(&set(&state, "B3", "=TEXTJOIN(\", \", TRUE, A2:A4)")),
+        "m5.xlarge, c6g.medium"
+    );
+    assert_eq!(
+        text(&set(&state, "B4", "=CONCAT(A2:A3)")),
+        "m5.xlargec6g.medium"
+    );
+    match set(&state, "B5", "=LEN(A3)").value {
+        CellValueData::Number { value } => assert_eq!(value, 10.0),
+        other => panic!("Expected a number, got {:?}", other),
+    }
+
+    // VALUE reads quantities written as text, which then add up in the left operand's unit
+    set(&state, "C1", "12.5 kg");
+    set(&state, "C2", "1500 g");
+    set(&state, "C3", "=\"Pallet: \" & C2");
+    match set(&state, "C4", "=C1 + VALUE(MID(C3, 9, 10))").value {
+        CellValueData::Number { value } => assert_eq!(value, 14.0),
+        other => panic!("Expected a number, got {:?}", other),
+    }
+}
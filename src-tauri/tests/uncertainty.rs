@@ -0,0 +1,130 @@
+// Test quantities with an uncertainty (100 m ± 2 m) and how it propagates through formulas
+
+use unicel_lib::commands::workbook::{
+    get_sheet_cells_impl, parse_cell_input, set_cell_impl, set_display_mode_impl, CellData,
+    CellValueData,
+};
+
+mod common;
+use common::{new_state, set};
+
+/// Check a cell's number, unit and uncertainty
+fn assert_uncertain(data: &CellData, value: f64, uncertainty: f64, unit: &str) {
+    match data.value {
+        CellValueData::Number { value: actual } => {
+            assert!((actual - value).abs() < 1e-6, "{} {}", actual, unit)
+        }
+        ref other => panic!("Expected a number, got {:?}", other),
+    }
+    let actual = data.uncertainty.expect("an uncertainty");
+    assert!((actual - uncertainty).abs() < 1e-4, "± {} {}", actual, unit);
+    assert_eq!(data.storage_unit, unit);
+}
+
+#[test]
+fn test_uncertain_input() {
+    for input in ["100 m ± 2 m", "100±2 m", "100 m ± 200 cm", "100 m ± 2%"] {
+        let cell = parse_cell_input(input).unwrap();
+        assert_eq!(cell.as_number(), Some(100.0), "{}", input);
+        assert_eq!(cell.storage_unit().canonical(), "m", "{}", input);
+        assert!(
+            (cell.uncertainty().unwrap() - 2.0).abs() < 1e-9,
+            "{}",
+            input
+        );
+    }
+
+    // Text that merely contains ± stays text
+    let cell = parse_cell_input("Tolerance ± 2 mm").unwrap();
+    assert_eq!(cell.as_text(), Some("Tolerance ± 2 mm"));
+    assert_eq!(parse_cell_input("100 m").unwrap().uncertainty(), None);
+}
+
+#[test]
+fn test_invalid_uncertainty_rejected() {
+    // An uncertainty in a unit the value cannot convert to
+    for input in ["100 m ± 2 kg", "100 USD ± 2 s"] {
+        let error = parse_cell_input(input).unwrap_err();
+        assert!(
+            error.contains("cannot be converted"),
+            "{}: {}",
+            input,
+            error
+        );
+    }
+
+    // A negative uncertainty
+    for input in ["100 m ± -2 m", "100±-2"] {
+        let error = parse_cell_input(input).unwrap_err();
+        assert!(error.contains("negative"), "{}: {}", input, error);
+    }
+
+    // Neither is stored in the cell
+    let state = new_state("Survey");
+    assert!(set_cell_impl(&state, "A1".to_string(), "100 m ± 2 kg".to_string()).is_err());
+    assert!(get_sheet_cells_impl(&state).unwrap().is_empty());
+}
+
+#[test]
+fn test_uncertainty_propagation() {
+    let state = new_state("Survey");
+
+    set(&state, "A1", "100 m ± 2 m");
+    set(&state, "A2", "50±1.5 m");
+    set(&state, "A3", "10 s ± 0.5 s");
+
+    // Sums and differences add absolute uncertainties in quadrature
+    assert_uncertain(&set(&state, "B1", "=A1 + A2"), 150.0, 2.5, "m");
+    assert_uncertain(&set(&state, "B2", "=A1 - A2"), 50.0, 2.5, "m");
+
+    // Products and quotients add relative uncertainties in quadrature
+    let area = set(&state, "B3", "=A1 * A2");
+    assert_uncertain(&area, 5000.0, 32500.0_f64.sqrt(), "m²");
+    let speed = set(&state, "B4", "=A1 / A3");
+    assert_uncertain(&speed, 10.0, 10.0 * 0.0029_f64.sqrt(), "m/s");
+
+    // Powers multiply the relative uncertainty by the exponent
+    assert_uncertain(&set(&state, "B5", "=POWER(A1, 2)"), 10000.0, 400.0, "m²");
+    assert_uncertain(
+        &set(&state, "B6", "=SQRT(A1 * A1)"),
+        100.0,
+        2.0_f64.sqrt(),
+        "m",
+    );
+
+    // The uncertainty is converted with the value (the library has 3.28084 ft in a metre)
+    let ft = 3.28084;
+    assert_uncertain(
+        &set(&state, "C1", "=3 ft + A1"),
+        100.0 * ft + 3.0,
+        2.0 * ft,
+        "ft",
+    );
+    assert_uncertain(
+        &set(&state, "C2", "=CONVERT(A1, \"ft\")"),
+        100.0 * ft,
+        2.0 * ft,
+        "ft",
+    );
+
+    // Values without an uncertainty keep none
+    assert_eq!(set(&state, "C3", "=2 m * 3").uncertainty, None);
+}
+
+#[test]
+fn test_uncertainty_in_display_unit() {
+    let state = new_state("Survey");
+    set(&state, "A1", "100 m ± 2 m");
+
+    set_display_mode_impl(&state, "Imperial".to_string()).unwrap();
+    let cells = get_sheet_cells_impl(&state).unwrap();
+    let (_, data) = cells.iter().find(|(addr, _)| addr == "A1").unwrap();
+
+    assert_eq!(data.display_unit.as_deref(), Some("ft"));
+    let uncertainty = data.uncertainty.unwrap();
+    assert!(
+        (uncertainty - 2.0 * 3.28084).abs() < 1e-6,
+        "{}",
+        uncertainty
+    );
+}
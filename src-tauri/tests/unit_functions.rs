@@ -0,0 +1,65 @@
+// Test functions that inspect and change units: UNIT, VALUE_IN, DIMENSION, STRIPUNIT, WITHUNIT, ISCOMPATIBLE
+
+use std::env;
+use std::fs;
+use unicel_lib::commands::workbook::{AppState, CellValueData};
+use unicel_lib::core::table::CellAddr;
+use unicel_lib::formats::excel::export_to_excel;
+
+mod common;
+use common::{new_state, set};
+
+/// The value shown in a cell
+fn shown(state: &AppState, cell_ref: &str) -> String {
+    let workbook = state.workbook.lock().unwrap();
+    let sheet = workbook.as_ref().unwrap().active_sheet();
+    sheet
+        .get(&CellAddr::from_string(cell_ref).unwrap())
+        .unwrap()
+        .formatted()
+}
+
+#[test]
+fn test_unit_functions() {
+    let state = new_state("Units");
+
+    set(&state, "A1", "12 in");
+    set(&state, "A2", "2 ft");
+    set(&state, "A3", "250 USD/month");
+
+    set(&state, "B1", "=UNIT(A3)");
+    assert_eq!(shown(&state, "B1"), "USD/month");
+    set(&state, "B2", "=DIMENSION(A3)");
+    assert_eq!(shown(&state, "B2"), "Currency/Time");
+
+    // A bare number for a calculation that can't take units, in a unit of our choosing
+    match set(&state, "B3", "=VALUE_IN(A1 + A2, \"ft\")").value {
+        CellValueData::Number { value } => assert!((value - 3.0).abs() < 1e-9),
+        other => panic!("Expected a number, got {:?}", other),
+    }
+
+    // The unit is given back afterwards, and the result converts like any other value
+    set(&state, "B4", "=WITHUNIT(SQRT(B3), \"ft\")");
+    match set(&state, "B5", "=CONVERT(B4, \"in\")").value {
+        CellValueData::Number { value } => assert!((value - 12.0 * 3f64.sqrt()).abs() < 1e-6),
+        other => panic!("Expected a number, got {:?}", other),
+    }
+
+    set(
+        &state,
+        "B6",
+        "=IF(ISCOMPATIBLE(A1, A3), \"same\", \"different\")",
+    );
+    assert_eq!(shown(&state, "B6"), "different");
+    set(&state, "B7", "=STRIPUNIT(A3)");
+    assert_eq!(shown(&state, "B7"), "250");
+
+    // Excel has no units, so these are exported as their values
+    let temp_file = env::temp_dir().join("test_unit_functions.xlsx");
+    {
+        let workbook = state.workbook.lock().unwrap();
+        export_to_excel(workbook.as_ref().unwrap(), &temp_file).expect("Export failed");
+    }
+    assert!(fs::metadata(&temp_file).is_ok());
+    fs::remove_file(&temp_file).ok();
+}
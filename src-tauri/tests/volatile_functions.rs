@@ -0,0 +1,125 @@
+// Test volatile functions (NOW, TODAY, RAND, RANDBETWEEN) and seeded random numbers
+
+use unicel_lib::commands::workbook::{
+    get_workbook_info_impl, recalculate_impl, set_random_seed_impl, CellValueData,
+};
+use unicel_lib::core::table::CellAddr;
+use unicel_lib::core::workbook::Workbook;
+use unicel_lib::formats::json::WorkbookFile;
+
+mod common;
+use common::{cell, new_state, number, set};
+
+#[test]
+fn test_random_functions() {
+    let state = new_state("Simulation");
+    set(&state, "A1", "=RAND()");
+    set(&state, "A2", "=RANDBETWEEN(1 m, 6 m)");
+    set(&state, "A3", "=RANDBETWEEN(1 m, 2 km)");
+
+    let value = number(&cell(&state, "A1"));
+    assert!((0.0..1.0).contains(&value));
+    let roll = number(&cell(&state, "A2"));
+    assert!((1.0..=6.0).contains(&roll) && roll.fract() == 0.0);
+    assert!((1.0..=2000.0).contains(&number(&cell(&state, "A3"))));
+
+    // The bounds must be in order and in compatible units
+    assert!(matches!(
+        set(&state, "B1", "=RANDBETWEEN(6, 1)").value,
+        CellValueData::Error { .. }
+    ));
+    assert!(matches!(
+        set(&state, "B2", "=RANDBETWEEN(1 m, 6 kg)").value,
+        CellValueData::Error { .. }
+    ));
+}
+
+#[test]
+fn test_volatile_cells_recalculate() {
+    let state = new_state("Simulation");
+    set(&state, "A1", "=RAND()");
+    set(&state, "B1", "=A1 * 100 USD");
+
+    // Editing any cell recalculates the random cell and what depends on it
+    let before = number(&cell(&state, "A1"));
+    set(&state, "C1", "5");
+    let after = number(&cell(&state, "A1"));
+    assert_ne!(before, after);
+    assert!((number(&cell(&state, "B1")) - after * 100.0).abs() < 1e-9);
+
+    // So does an explicit recalculation
+    recalculate_impl(&state).unwrap();
+    let recalculated = number(&cell(&state, "A1"));
+    assert_ne!(after, recalculated);
+    assert!((number(&cell(&state, "B1")) - recalculated * 100.0).abs() < 1e-9);
+
+    // A cell that no longer calls a volatile function stays put
+    set(&state, "A1", "0.5");
+    set(&state, "C1", "6");
+    assert_eq!(number(&cell(&state, "B1")), 50.0);
+}
+
+#[test]
+fn test_seeded_random_numbers() {
+    let state = new_state("Simulation");
+    set_random_seed_impl(&state, Some(42)).unwrap();
+    assert_eq!(
+        get_workbook_info_impl(&state).unwrap().random_seed,
+        Some(42)
+    );
+    set(&state, "A1", "=RAND()");
+    set(&state, "A2", "=RAND()");
+    set(&state, "A3", "=RAND() + RAND()");
+
+    // Each cell has its own numbers, which recalculating does not change
+    let a1 = number(&cell(&state, "A1"));
+    assert_ne!(a1, number(&cell(&state, "A2")));
+    recalculate_impl(&state).unwrap();
+    assert_eq!(number(&cell(&state, "A1")), a1);
+
+    // Another workbook with the same seed shows the same numbers
+    let other = new_state("Simulation");
+    set_random_seed_impl(&other, Some(42)).unwrap();
+    set(&other, "A1", "=RAND()");
+    assert_eq!(number(&cell(&other, "A1")), a1);
+
+    // A new seed is a new draw, and removing the seed draws on every recalculation
+    set_random_seed_impl(&state, Some(7)).unwrap();
+    assert_ne!(number(&cell(&state, "A1")), a1);
+    set_random_seed_impl(&state, None).unwrap();
+    let unseeded = number(&cell(&state, "A1"));
+    recalculate_impl(&state).unwrap();
+    assert_ne!(number(&cell(&state, "A1")), unseeded);
+}
+
+#[test]
+fn test_seed_persists() {
+    let mut workbook = Workbook::new("Simulation");
+    workbook.set_random_seed(Some(2024)).unwrap();
+    workbook
+        .set_cell(
+            0,
+            CellAddr::new("A", 1),
+            unicel_lib::core::cell::Cell::with_formula("=RANDBETWEEN(1, 1000000)"),
+        )
+        .unwrap();
+    let value = workbook
+        .active_sheet()
+        .get(&CellAddr::new("A", 1))
+        .unwrap()
+        .as_number();
+
+    let json = WorkbookFile::from_workbook(&workbook).to_json().unwrap();
+    let mut restored = WorkbookFile::from_json(&json)
+        .unwrap()
+        .to_workbook()
+        .unwrap();
+    restored.recalculate_all().unwrap();
+    assert_eq!(restored.settings().random_seed, Some(2024));
+    let restored_value = restored
+        .active_sheet()
+        .get(&CellAddr::new("A", 1))
+        .unwrap()
+        .as_number();
+    assert_eq!(restored_value, value);
+}
@@ -8,6 +8,8 @@ export interface CellData {
   value: CellValueData;
   storage_unit: string;
   display_unit?: string;
+  uncertainty?: number;
+  significant_figures?: number;
   formula?: string;
   warning?: string;
 }
@@ -16,13 +18,31 @@ export type CellValueData =
   | { type: 'Empty' }
   | { type: 'Number'; value: number }
   | { type: 'Text'; text: string }
-  | { type: 'Error'; message: string };
+  | { type: 'DateTime'; value: string; text: string }
+  | { type: 'Boolean'; value: boolean }
+  | { type: 'Error'; kind: string; code: string; message: string };
 
 export interface WorkbookInfo {
   name: string;
   sheet_names: string[];
   active_sheet_index: number;
   is_dirty: boolean;
+  significant_figures: boolean;
+  random_seed: number | null;
+  iterative_calculation: IterativeCalculation | null;
+}
+
+export interface GoalSeekResult {
+  value: number;
+  unit: string;
+  target_value: number;
+  target_unit: string;
+  iterations: number;
+}
+
+export interface IterativeCalculation {
+  max_iterations: number;
+  tolerance: number;
 }
 
 export interface UnitPreferences {
@@ -47,6 +67,11 @@ export interface NamedRangeInfo {
   cell_address: string;
 }
 
+export interface FunctionInfo {
+  name: string;
+  definition: string;
+}
+
 // Tauri command wrappers
 export const tauriApi = {
   // Workbook operations
@@ -86,6 +111,30 @@ export const tauriApi = {
     return invoke('set_display_mode', { mode });
   },
 
+  async setSignificantFigures(enabled: boolean): Promise<void> {
+    return invoke('set_significant_figures', { enabled });
+  },
+
+  async setRandomSeed(seed: number | null): Promise<void> {
+    return invoke('set_random_seed', { seed });
+  },
+
+  async setIterativeCalculation(iteration: IterativeCalculation | null): Promise<void> {
+    return invoke('set_iterative_calculation', { iteration });
+  },
+
+  async getCircularReferences(): Promise<string[][]> {
+    return invoke('get_circular_references');
+  },
+
+  async goalSeek(target: string, goal: string, changing: string): Promise<GoalSeekResult> {
+    return invoke('goal_seek', { target, goal, changing });
+  },
+
+  async recalculate(): Promise<void> {
+    return invoke('recalculate');
+  },
+
   async getUnitPreferences(): Promise<UnitPreferences> {
     return invoke('get_unit_preferences');
   },
@@ -178,6 +227,19 @@ export const tauriApi = {
     return invoke('get_named_range_for_cell', { sheetIndex, cellAddress });
   },
 
+  // Workbook functions (LAMBDA)
+  async listFunctions(): Promise<FunctionInfo[]> {
+    return invoke('list_functions');
+  },
+
+  async defineFunction(name: string, definition: string): Promise<void> {
+    return invoke('define_function', { name, definition });
+  },
+
+  async deleteFunction(name: string): Promise<void> {
+    return invoke('delete_function', { name });
+  },
+
   // Column and row sizing
   async setColumnWidth(col: string, width: number): Promise<void> {
     return invoke('set_column_width', { col, width });
@@ -292,8 +354,18 @@ export function convertCellData(data: CellData) {
     case 'Text':
       value = { type: 'text' as const, text: data.value.text };
       break;
+    case 'DateTime':
+      value = { type: 'datetime' as const, text: data.value.text };
+      break;
+    case 'Boolean':
+      value = { type: 'boolean' as const, text: data.value.value ? 'TRUE' : 'FALSE' };
+      break;
     case 'Error':
-      value = { type: 'error' as const, error: data.value.message };
+      value = {
+        type: 'error' as const,
+        error: data.value.message,
+        errorCode: data.value.code,
+      };
       break;
   }
 
@@ -301,6 +373,8 @@ export function convertCellData(data: CellData) {
     value,
     storageUnit: data.storage_unit,
     displayUnit: data.display_unit,
+    uncertainty: data.uncertainty ?? undefined,
+    significantFigures: data.significant_figures ?? undefined,
     formula: data.formula,
     warning: data.warning,
   };
@@ -261,40 +261,67 @@ export default function Grid({
       return '';
     }
     if (cell.value.type === 'error') {
-      return `#ERROR: ${cell.value.error}`;
+      return cell.value.errorCode ?? `#ERROR: ${cell.value.error}`;
     }
-    if (cell.value.type === 'text' && cell.value.text !== undefined) {
+    if (
+      (cell.value.type === 'text' ||
+        cell.value.type === 'datetime' ||
+        cell.value.type === 'boolean') &&
+      cell.value.text !== undefined
+    ) {
       return cell.value.text;
     }
     if (cell.value.type === 'number' && cell.value.value !== undefined) {
       const unit = cell.displayUnit || cell.storageUnit;
 
-      // Special handling for percentages: convert 0.15 -> "15%"
-      if (unit === '%') {
-        return `${(cell.value.value * 100).toFixed(2)}%`;
-      }
-
       // Check if this is a currency unit
       const currencyUnits = ['USD', 'EUR', 'GBP', '$', '€', '£'];
       const isCurrency = currencyUnits.includes(unit);
 
-      // Format number based on unit type
-      let formattedNumber: string;
-      if (isCurrency) {
-        // Format currency with thousands separators and 2 decimal places
-        formattedNumber = cell.value.value.toLocaleString('en-US', {
-          minimumFractionDigits: 2,
-          maximumFractionDigits: 2,
+      // In significant-figures mode, numbers are rounded to the figures they are known to,
+      // keeping significant zeros: 12.0 m stays "12.0 m"
+      const figures = cell.significantFigures;
+      const formatSignificant = (value: number): string =>
+        value.toLocaleString('en-US', {
+          minimumSignificantDigits: figures,
+          maximumSignificantDigits: figures,
+          useGrouping: isCurrency,
         });
-      } else {
-        // For non-currency, use default formatting
-        formattedNumber = cell.value.value.toString();
-      }
 
-      if (unit) {
-        return `${formattedNumber} ${unit}`;
+      const formatQuantity = (value: number): string => {
+        // Special handling for percentages: convert 0.15 -> "15%", 0.06 %/year -> "6%/year"
+        if (unit.startsWith('%')) {
+          const percent =
+            figures !== undefined ? formatSignificant(value * 100) : (value * 100).toFixed(2);
+          return `${percent}${unit}`;
+        }
+
+        // Format number based on unit type
+        let formattedNumber: string;
+        if (figures !== undefined) {
+          formattedNumber = formatSignificant(value);
+        } else if (isCurrency) {
+          // Format currency with thousands separators and 2 decimal places
+          formattedNumber = value.toLocaleString('en-US', {
+            minimumFractionDigits: 2,
+            maximumFractionDigits: 2,
+          });
+        } else {
+          // For non-currency, use default formatting
+          formattedNumber = value.toString();
+        }
+
+        if (unit) {
+          return `${formattedNumber} ${unit}`;
+        }
+        return formattedNumber;
+      };
+
+      // Quantities with an uncertainty show it in the same unit: "100 m ± 2 m"
+      if (cell.uncertainty !== undefined) {
+        return `${formatQuantity(cell.value.value)} ± ${formatQuantity(cell.uncertainty)}`;
       }
-      return formattedNumber;
+      return formatQuantity(cell.value.value);
     }
     return '';
   };
@@ -700,7 +727,7 @@ export default function Grid({
                     `}
                     onClick={() => !isEditing && handleCellClick(col, row)}
                     onDoubleClick={() => !isEditing && onCellDoubleClick?.({ col, row })}
-                    title={cell?.warning || cell?.formula || undefined}
+                    title={cell?.warning || cell?.value.error || cell?.formula || undefined}
                   >
                     {isEditing ? (
                       <input
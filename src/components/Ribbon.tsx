@@ -4,6 +4,8 @@ import { tauriApi } from '../api/tauri';
 interface RibbonProps {
   displayMode: 'AsEntered' | 'Metric' | 'Imperial';
   onDisplayModeChange: (mode: 'AsEntered' | 'Metric' | 'Imperial') => void;
+  significantFigures?: boolean;
+  onSignificantFiguresChange?: (enabled: boolean) => void;
   onNew: () => void;
   onOpen: () => void;
   onOpenRecent?: (path: string) => void;
@@ -20,6 +22,8 @@ interface RibbonProps {
 export default function Ribbon({
   displayMode,
   onDisplayModeChange,
+  significantFigures = false,
+  onSignificantFiguresChange,
   onNew,
   onOpen,
   onOpenRecent,
@@ -210,6 +214,17 @@ export default function Ribbon({
               </button>
             ))}
           </div>
+          {onSignificantFiguresChange && (
+            <button
+              className={`px-3 py-1 text-sm border border-gray-300 rounded ${
+                significantFigures ? 'bg-blue-500 text-white' : 'bg-white hover:bg-gray-100 text-gray-700'
+              }`}
+              onClick={() => onSignificantFiguresChange(!significantFigures)}
+              title="Show numbers to their significant figures"
+            >
+              Sig. figs
+            </button>
+          )}
         </div>
 
         {/* Divider */}
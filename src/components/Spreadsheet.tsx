@@ -17,6 +17,21 @@ interface ToastMessage {
   type?: 'info' | 'success' | 'warning' | 'error';
 }
 
+// A number cell as it would be typed, e.g. "100 m", "15%" or "100 m ± 2 m"
+function numberInput(value: number, unit: string, uncertainty?: number): string {
+  const quantity = (n: number) => {
+    // Special handling for percentages: convert 0.15 -> "15%", 0.06 %/year -> "6%/year"
+    if (unit.startsWith('%')) {
+      return `${n * 100}${unit}`;
+    }
+    return unit ? `${n} ${unit}` : `${n}`;
+  };
+  if (uncertainty !== undefined) {
+    return `${quantity(value)} ± ${quantity(uncertainty)}`;
+  }
+  return quantity(value);
+}
+
 // Mock data for testing
 function createMockCells(): Map<string, Cell> {
   const cells = new Map<string, Cell>();
@@ -66,6 +81,7 @@ export default function Spreadsheet() {
   const [editingCell, setEditingCell] = useState<CellAddress | null>(null);
   const [formulaBarValue, setFormulaBarValue] = useState('');
   const [displayMode, setDisplayMode] = useState<'AsEntered' | 'Metric' | 'Imperial'>('AsEntered');
+  const [significantFigures, setSignificantFigures] = useState(false);
   const [isDirty, setIsDirty] = useState(false);
   const [toasts, setToasts] = useState<ToastMessage[]>([]);
   const [isLoading, setIsLoading] = useState(false);
@@ -116,6 +132,7 @@ export default function Spreadsheet() {
       setCells(newCells);
       setSheetNames(workbookInfo.sheet_names);
       setActiveSheetIndex(workbookInfo.active_sheet_index);
+      setSignificantFigures(workbookInfo.significant_figures);
     } catch (error) {
       addToast(`Failed to load cells: ${error}`, 'error');
     }
@@ -132,16 +149,14 @@ export default function Spreadsheet() {
     const cell = cells.get(cellAddr);
     if (cell?.formula) {
       setFormulaBarValue(cell.formula);
-    } else if (cell?.value.type === 'text') {
+    } else if (
+      cell?.value.type === 'text' ||
+      cell?.value.type === 'datetime' ||
+      cell?.value.type === 'boolean'
+    ) {
       setFormulaBarValue(cell.value.text || '');
     } else if (cell?.value.type === 'number' && cell.value.value !== undefined) {
-      const unit = cell.storageUnit;
-      // Special handling for percentages: convert 0.15 -> "15%"
-      if (unit === '%') {
-        setFormulaBarValue(`${cell.value.value * 100}%`);
-      } else {
-        setFormulaBarValue(unit ? `${cell.value.value} ${unit}` : `${cell.value.value}`);
-      }
+      setFormulaBarValue(numberInput(cell.value.value, cell.storageUnit, cell.uncertainty));
     } else {
       setFormulaBarValue('');
     }
@@ -208,15 +223,14 @@ export default function Spreadsheet() {
 
     if (cell?.formula) {
       initialValue = cell.formula;
-    } else if (cell?.value.type === 'text') {
+    } else if (
+      cell?.value.type === 'text' ||
+      cell?.value.type === 'datetime' ||
+      cell?.value.type === 'boolean'
+    ) {
       initialValue = cell.value.text || '';
     } else if (cell?.value.type === 'number' && cell.value.value !== undefined) {
-      const unit = cell.storageUnit;
-      if (unit === '%') {
-        initialValue = `${cell.value.value * 100}%`;
-      } else {
-        initialValue = unit ? `${cell.value.value} ${unit}` : `${cell.value.value}`;
-      }
+      initialValue = numberInput(cell.value.value, cell.storageUnit, cell.uncertainty);
     }
 
     setFormulaBarValue(initialValue);
@@ -249,6 +263,25 @@ export default function Spreadsheet() {
     }
   };
 
+  const handleSignificantFiguresChange = async (enabled: boolean) => {
+    try {
+      await tauriApi.setSignificantFigures(enabled);
+      await loadCellsFromBackend(); // Reload cells rounded to their significant figures
+      setIsDirty(true);
+    } catch (error) {
+      addToast(`Failed to change significant figures: ${error}`, 'error');
+    }
+  };
+
+  const handleRecalculate = async () => {
+    try {
+      await tauriApi.recalculate();
+      await loadCellsFromBackend(); // Show new values of NOW(), RAND() and their dependents
+    } catch (error) {
+      addToast(`Failed to recalculate: ${error}`, 'error');
+    }
+  };
+
   const handleNew = async () => {
     if (isDirty && !confirm('You have unsaved changes. Create new workbook?')) {
       return;
@@ -707,6 +740,10 @@ export default function Spreadsheet() {
       } else if (isMod && e.key === 'n') {
         e.preventDefault();
         handleNew();
+      } else if (e.key === 'F9') {
+        // F9 recalculates every formula, as in other spreadsheets
+        e.preventDefault();
+        handleRecalculate();
       } else if ((e.key === 'Delete' || e.key === 'Backspace') && selectedCell && !editingCell) {
         // Clear selected cell when Delete or Backspace is pressed (not in edit mode)
         e.preventDefault();
@@ -758,6 +795,8 @@ export default function Spreadsheet() {
         <Ribbon
           displayMode={displayMode}
           onDisplayModeChange={handleDisplayModeChange}
+          significantFigures={significantFigures}
+          onSignificantFiguresChange={handleSignificantFiguresChange}
           onNew={handleNew}
           onOpen={handleOpen}
           onOpenRecent={handleOpenRecent}
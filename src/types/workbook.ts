@@ -1,16 +1,19 @@
 // Type definitions for Unicel workbook data structures
 
 export interface CellValue {
-  type: 'empty' | 'number' | 'text' | 'error';
+  type: 'empty' | 'number' | 'text' | 'datetime' | 'boolean' | 'error';
   value?: number;
-  text?: string;
+  text?: string; // Also the displayed date or TRUE/FALSE for datetime and boolean values
   error?: string;
+  errorCode?: string; // e.g. #DIV/0!, #REF!, #UNIT!
 }
 
 export interface Cell {
   value: CellValue;
   storageUnit: string;
   displayUnit?: string;
+  uncertainty?: number; // Standard uncertainty, in the displayed unit
+  significantFigures?: number; // Set in significant-figures mode for measured numbers
   formula?: string;
   warning?: string;
 }